@@ -10,6 +10,7 @@ fn main() {
         // 检查 models 资源是否存在
         check_models_resources(&manifest_path);
     }
+    #[cfg(feature = "gui")]
     tauri_build::build()
 }
 
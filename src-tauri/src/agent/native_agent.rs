@@ -19,7 +19,9 @@
 
 use crate::agent::protocols::{create_protocol, Protocol};
 use crate::agent::tool_loop::{ToolCallResult, ToolLoopEngine, ToolLoopState};
-use crate::agent::tools::{create_default_registry, create_terminal_registry, ToolRegistry};
+#[cfg(feature = "gui")]
+use crate::agent::tools::create_terminal_registry;
+use crate::agent::tools::{create_default_registry, ToolRegistry};
 use crate::agent::types::*;
 use crate::models::openai::{
     ChatCompletionRequest, ChatCompletionResponse, ChatMessage, ContentPart as OpenAIContentPart,
@@ -819,18 +821,20 @@ impl NativeAgentState {
     /// 获取工具注册表（支持 Terminal 模式）
     ///
     /// # Arguments
-    /// * `terminal_mode` - 是否使用 Terminal 模式（使用 TerminalTool 替代 BashTool）
+    /// * `terminal_mode` - 是否使用 Terminal 模式（使用 TerminalTool 替代 BashTool，
+    ///   TerminalTool 通过前端执行命令，未启用 `gui` 特性时忽略）
     pub fn get_tool_registry_with_mode(
         &self,
         terminal_mode: bool,
     ) -> Result<Arc<ToolRegistry>, String> {
         let base_dir = dirs::home_dir().ok_or_else(|| "无法获取用户 home 目录".to_string())?;
-        let registry = if terminal_mode {
-            create_terminal_registry(base_dir)
-        } else {
-            create_default_registry(base_dir)
-        };
-        Ok(Arc::new(registry))
+        #[cfg(feature = "gui")]
+        if terminal_mode {
+            return Ok(Arc::new(create_terminal_registry(base_dir)));
+        }
+        #[cfg(not(feature = "gui"))]
+        let _ = terminal_mode;
+        Ok(Arc::new(create_default_registry(base_dir)))
     }
 
     /// 创建临时 Agent 用于异步操作
@@ -7,7 +7,7 @@
 //! - `registry`: 工具注册表和 Tool trait
 //! - `security`: 安全管理器（路径验证、符号链接检查等）
 //! - `bash`: Bash 命令执行工具
-//! - `terminal`: 终端命令执行工具（通过前端审批）
+//! - `terminal`: 终端命令执行工具（通过前端审批，仅 `gui` 特性）
 //! - `read_file`: 文件读取工具
 //! - `write_file`: 文件写入工具
 //! - `edit_file`: 文件编辑工具
@@ -19,7 +19,9 @@ pub mod prompt;
 pub mod read_file;
 pub mod registry;
 pub mod security;
+#[cfg(feature = "gui")]
 pub mod term_scrollback;
+#[cfg(feature = "gui")]
 pub mod terminal;
 pub mod types;
 pub mod write_file;
@@ -30,10 +32,12 @@ pub use prompt::{generate_tools_prompt, PromptFormat, ToolPromptGenerator};
 pub use read_file::{ReadFileResult, ReadFileTool};
 pub use registry::{Tool, ToolRegistry};
 pub use security::{SecurityError, SecurityManager};
+#[cfg(feature = "gui")]
 pub use term_scrollback::{
     get_term_scrollback_tool, handle_term_scrollback_response, set_term_scrollback_tool_app_handle,
     GetScrollbackRequest, GetScrollbackResponse, TermScrollbackTool,
 };
+#[cfg(feature = "gui")]
 pub use terminal::{
     get_terminal_tool, handle_terminal_command_response, set_terminal_tool_app_handle,
     TerminalCommandRequest, TerminalCommandResponse, TerminalTool,
@@ -92,6 +96,7 @@ pub fn create_default_registry(base_dir: impl AsRef<Path>) -> ToolRegistry {
 ///
 /// # Returns
 /// 包含 terminal, term_get_scrollback, read_file, write_file, edit_file 工具的注册表
+#[cfg(feature = "gui")]
 pub fn create_terminal_registry(base_dir: impl AsRef<Path>) -> ToolRegistry {
     let security = Arc::new(SecurityManager::new(base_dir.as_ref()));
     let registry = ToolRegistry::new();
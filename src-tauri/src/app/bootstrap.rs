@@ -3,44 +3,74 @@
 //! 包含配置验证、状态初始化等启动逻辑。
 
 use std::sync::Arc;
+#[cfg(feature = "gui")]
 use tokio::sync::RwLock;
 
+#[cfg(feature = "gui")]
 use crate::agent::NativeAgentState;
+#[cfg(feature = "gui")]
 use crate::commands::api_key_provider_cmd::ApiKeyProviderServiceState;
+#[cfg(feature = "gui")]
 use crate::commands::connect_cmd::ConnectStateWrapper;
+#[cfg(feature = "gui")]
 use crate::commands::flow_monitor_cmd::{
     BatchOperationsState, BookmarkManagerState, EnhancedStatsServiceState, FlowInterceptorState,
     FlowMonitorState, FlowQueryServiceState, FlowReplayerState, QuickFilterManagerState,
     SessionManagerState,
 };
+#[cfg(feature = "gui")]
 use crate::commands::machine_id_cmd::MachineIdState;
+#[cfg(feature = "gui")]
 use crate::commands::model_registry_cmd::ModelRegistryState;
+#[cfg(feature = "gui")]
 use crate::commands::orchestrator_cmd::OrchestratorState;
+#[cfg(feature = "gui")]
 use crate::commands::plugin_cmd::PluginManagerState;
+#[cfg(feature = "gui")]
 use crate::commands::plugin_install_cmd::PluginInstallerState;
+#[cfg(feature = "gui")]
 use crate::commands::provider_pool_cmd::{CredentialSyncServiceState, ProviderPoolServiceState};
+#[cfg(feature = "gui")]
 use crate::commands::resilience_cmd::ResilienceConfigState;
+#[cfg(feature = "gui")]
 use crate::commands::session_files_cmd::SessionFilesState;
+#[cfg(feature = "gui")]
 use crate::commands::skill_cmd::SkillServiceState;
+#[cfg(feature = "gui")]
 use crate::commands::terminal_cmd::TerminalManagerState;
+#[cfg(feature = "gui")]
 use crate::commands::webview_cmd::{WebviewManagerState, WebviewManagerWrapper};
-use crate::config::{self, Config, ConfigManager, GlobalConfigManager, GlobalConfigManagerState};
-use crate::database::{self, DbConnection};
+use crate::config::{self, Config};
+#[cfg(feature = "gui")]
+use crate::config::{ConfigManager, GlobalConfigManager, GlobalConfigManagerState};
+use crate::database;
+#[cfg(feature = "gui")]
+use crate::database::DbConnection;
+#[cfg(feature = "gui")]
 use crate::flow_monitor::{
-    BatchOperations, BookmarkManager, EnhancedStatsService, FlowFileStore, FlowInterceptor,
-    FlowMonitor, FlowMonitorConfig, FlowQueryService, FlowReplayer, InterceptConfig,
-    QuickFilterManager, RotationConfig, SessionManager,
+    BatchOperations, BookmarkManager, EnhancedStatsService, FlowInterceptor, FlowQueryService,
+    FlowReplayer, InterceptConfig, QuickFilterManager, SessionManager,
 };
+use crate::flow_monitor::{FlowFileStore, FlowMonitor, FlowMonitorConfig, RotationConfig};
+#[cfg(feature = "gui")]
 use crate::logger;
 use crate::plugin;
+use crate::resilience::{CircuitBreakerRegistry, CircuitTransition};
+#[cfg(feature = "gui")]
 use crate::server;
+#[cfg(feature = "gui")]
 use crate::services::api_key_provider_service::ApiKeyProviderService;
+#[cfg(feature = "gui")]
 use crate::services::provider_pool_service::ProviderPoolService;
+#[cfg(feature = "gui")]
 use crate::services::skill_service::SkillService;
+#[cfg(feature = "gui")]
 use crate::services::token_cache_service::TokenCacheService;
+#[cfg(feature = "gui")]
 use crate::services::update_check_service::UpdateCheckServiceState;
 use crate::telemetry;
 
+#[cfg(feature = "gui")]
 use super::types::{AppState, LogState, TokenCacheServiceState};
 use super::utils::{generate_api_key, is_non_local_bind, is_valid_bind_host};
 
@@ -82,17 +112,28 @@ impl std::fmt::Display for ConfigError {
 pub fn load_and_validate_config() -> Result<Config, ConfigError> {
     let mut config = config::load_config().map_err(|e| ConfigError::LoadFailed(e.to_string()))?;
 
+    if validate_config(&mut config)? {
+        config::save_config(&config).map_err(|e| ConfigError::SaveFailed(e.to_string()))?;
+        tracing::info!("检测到默认 API key，已自动生成并保存新密钥");
+    }
+
+    Ok(config)
+}
+
+/// 验证配置
+///
+/// 如果使用默认 API key，会自动生成新密钥。返回配置是否被修改（需要调用方保存）。
+pub fn validate_config(config: &mut Config) -> Result<bool, ConfigError> {
     // 验证主机地址
     if !is_valid_bind_host(&config.server.host) {
         return Err(ConfigError::InvalidHost);
     }
 
     // 如果使用默认 API key，自动生成新密钥
+    let mut changed = false;
     if config.server.api_key == config::DEFAULT_API_KEY {
-        let new_key = generate_api_key();
-        config.server.api_key = new_key;
-        config::save_config(&config).map_err(|e| ConfigError::SaveFailed(e.to_string()))?;
-        tracing::info!("检测到默认 API key，已自动生成并保存新密钥");
+        config.server.api_key = generate_api_key();
+        changed = true;
     }
 
     // 检查 TLS 配置
@@ -105,10 +146,11 @@ pub fn load_and_validate_config() -> Result<Config, ConfigError> {
         return Err(ConfigError::RemoteManagementNotSupported);
     }
 
-    Ok(config)
+    Ok(changed)
 }
 
 /// 应用状态集合
+#[cfg(feature = "gui")]
 pub struct AppStates {
     pub state: AppState,
     pub logs: LogState,
@@ -152,6 +194,7 @@ pub struct AppStates {
 }
 
 /// 初始化所有应用状态
+#[cfg(feature = "gui")]
 pub fn init_states(config: &Config) -> Result<AppStates, String> {
     // 核心状态
     let state: AppState = Arc::new(RwLock::new(server::ServerState::new(config.clone())));
@@ -188,13 +231,20 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
     let plugin_manager_state = PluginManagerState(Arc::new(RwLock::new(plugin_manager)));
 
     // 插件安装器
-    let plugin_installer_state = init_plugin_installer()?;
+    let plugin_installer_state =
+        PluginInstallerState(Arc::new(RwLock::new(init_plugin_installer()?)));
 
     // 插件 RPC 管理器
     let plugin_rpc_manager_state = crate::commands::plugin_rpc_cmd::PluginRpcManagerState::new();
 
     // 遥测系统
-    let (telemetry_state, shared_stats, shared_tokens, shared_logger) = init_telemetry(config)?;
+    let (shared_stats, shared_tokens, shared_logger) = init_telemetry(config)?;
+    let telemetry_state = crate::commands::telemetry_cmd::TelemetryState::with_shared(
+        shared_stats.clone(),
+        shared_tokens.clone(),
+        Some(shared_logger.clone()),
+    )
+    .map_err(|e| format!("TelemetryState 初始化失败: {}", e))?;
 
     // Flow Monitor 系统（根据插件安装状态启用/禁用）
     let (
@@ -293,7 +343,7 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
 }
 
 /// 初始化插件安装器
-fn init_plugin_installer() -> Result<PluginInstallerState, String> {
+pub(super) fn init_plugin_installer() -> Result<plugin::installer::PluginInstaller, String> {
     let db_path = database::get_db_path().map_err(|e| format!("获取数据库路径失败: {}", e))?;
     let plugins_dir = dirs::data_dir()
         .unwrap_or_else(|| std::path::PathBuf::from("."))
@@ -311,7 +361,7 @@ fn init_plugin_installer() -> Result<PluginInstallerState, String> {
     ) {
         Ok(installer) => {
             tracing::info!("[启动] 插件安装器初始化成功");
-            Ok(PluginInstallerState(Arc::new(RwLock::new(installer))))
+            Ok(installer)
         }
         Err(e) => {
             tracing::error!("[启动] 插件安装器初始化失败: {}", e);
@@ -320,23 +370,22 @@ fn init_plugin_installer() -> Result<PluginInstallerState, String> {
             let fallback_temp_dir = std::env::temp_dir().join("proxycast_plugin_install_fallback");
             let _ = std::fs::create_dir_all(&fallback_plugins_dir);
             let _ = std::fs::create_dir_all(&fallback_temp_dir);
-            let installer = plugin::installer::PluginInstaller::from_paths(
+            plugin::installer::PluginInstaller::from_paths(
                 fallback_plugins_dir,
                 fallback_temp_dir,
                 &db_path,
             )
-            .map_err(|e| format!("后备插件安装器初始化失败: {}", e))?;
-            Ok(PluginInstallerState(Arc::new(RwLock::new(installer))))
+            .map_err(|e| format!("后备插件安装器初始化失败: {}", e))
         }
     }
 }
 
-/// 初始化遥测系统
-fn init_telemetry(
+/// 初始化遥测系统，返回统计聚合器、Token 追踪器和请求日志记录器
+#[allow(clippy::type_complexity)]
+pub(super) fn init_telemetry(
     config: &Config,
 ) -> Result<
     (
-        crate::commands::telemetry_cmd::TelemetryState,
         Arc<parking_lot::RwLock<telemetry::StatsAggregator>>,
         Arc<parking_lot::RwLock<telemetry::TokenTracker>>,
         Arc<telemetry::RequestLogger>,
@@ -360,19 +409,13 @@ fn init_telemetry(
             .map_err(|e| format!("RequestLogger 初始化失败: {}", e))?,
     );
 
-    let telemetry_state = crate::commands::telemetry_cmd::TelemetryState::with_shared(
-        shared_stats.clone(),
-        shared_tokens.clone(),
-        Some(shared_logger.clone()),
-    )
-    .map_err(|e| format!("TelemetryState 初始化失败: {}", e))?;
-
-    Ok((telemetry_state, shared_stats, shared_tokens, shared_logger))
+    Ok((shared_stats, shared_tokens, shared_logger))
 }
 
 /// 初始化 Flow Monitor 系统
 ///
 /// 如果 flow-monitor 插件已安装，则启用监控功能；否则禁用。
+#[cfg(feature = "gui")]
 #[allow(clippy::type_complexity)]
 fn init_flow_monitor(
     provider_pool_service_state: &ProviderPoolServiceState,
@@ -394,37 +437,11 @@ fn init_flow_monitor(
     ),
     String,
 > {
-    // 检查 flow-monitor 插件是否已安装
-    let is_plugin_installed = {
-        let installer = plugin_installer_state.0.blocking_read();
-        installer.is_installed("flow-monitor").unwrap_or(false)
-    };
-
-    // 根据插件安装状态设置 enabled
-    let mut flow_monitor_config = FlowMonitorConfig::default();
-    flow_monitor_config.enabled = is_plugin_installed;
-
-    if is_plugin_installed {
-        tracing::info!("[启动] flow-monitor 插件已安装，启用 Flow 监控");
-    } else {
-        tracing::info!("[启动] flow-monitor 插件未安装，禁用 Flow 监控");
-    }
+    let flow_monitor_config = flow_monitor_config(&plugin_installer_state.0.blocking_read());
 
     // 初始化文件存储
-    let data_dir = dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("proxycast")
-        .join("flows");
-    let _ = std::fs::create_dir_all(&data_dir);
-
     let rotation_config = RotationConfig::default();
-    let flow_file_store = match FlowFileStore::new(data_dir, rotation_config.clone()) {
-        Ok(store) => Some(Arc::new(store)),
-        Err(e) => {
-            tracing::warn!("无法初始化 Flow 文件存储: {}", e);
-            None
-        }
-    };
+    let flow_file_store = init_flow_file_store(&rotation_config);
 
     let flow_monitor = Arc::new(FlowMonitor::new(
         flow_monitor_config,
@@ -449,6 +466,9 @@ fn init_flow_monitor(
             .map_err(|e| format!("SessionManager 初始化失败: {}", e))?,
     );
     let session_manager_state = SessionManagerState(session_manager.clone());
+    flow_monitor
+        .playback()
+        .set_session_manager(session_manager.clone());
 
     let quick_filter_manager = Arc::new(
         QuickFilterManager::new(db_path.clone())
@@ -498,3 +518,67 @@ fn init_flow_monitor(
         flow_interceptor,
     ))
 }
+
+/// 根据 flow-monitor 插件安装状态生成 Flow 监控配置
+pub(super) fn flow_monitor_config(
+    installer: &plugin::installer::PluginInstaller,
+) -> FlowMonitorConfig {
+    // 检查 flow-monitor 插件是否已安装
+    let is_plugin_installed = installer.is_installed("flow-monitor").unwrap_or(false);
+
+    // 根据插件安装状态设置 enabled
+    let flow_monitor_config = FlowMonitorConfig {
+        enabled: is_plugin_installed,
+        ..Default::default()
+    };
+
+    if is_plugin_installed {
+        tracing::info!("[启动] flow-monitor 插件已安装，启用 Flow 监控");
+    } else {
+        tracing::info!("[启动] flow-monitor 插件未安装，禁用 Flow 监控");
+    }
+
+    flow_monitor_config
+}
+
+/// 初始化 Flow 文件存储（用于持久化）
+pub(super) fn init_flow_file_store(rotation_config: &RotationConfig) -> Option<Arc<FlowFileStore>> {
+    let data_dir = dirs::data_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("proxycast")
+        .join("flows");
+    let _ = std::fs::create_dir_all(&data_dir);
+
+    match FlowFileStore::new(data_dir, rotation_config.clone()) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            tracing::warn!("无法初始化 Flow 文件存储: {}", e);
+            None
+        }
+    }
+}
+
+/// 将熔断器状态变化转发到 Flow 事件总线
+///
+/// 每次状态变化后以当前打开的熔断器数量调用 `on_transition`，供托盘等展示使用。
+pub(super) async fn forward_circuit_transitions<F>(
+    registry: Arc<CircuitBreakerRegistry>,
+    flow_monitor: Arc<FlowMonitor>,
+    mut on_transition: F,
+) where
+    F: FnMut(&CircuitTransition, usize),
+{
+    let mut receiver = registry.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(transition) => {
+                on_transition(&transition, registry.open_count());
+                flow_monitor.publish_circuit_transition(transition);
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!("[CIRCUIT] 事件转发落后，丢弃 {} 条状态变化", skipped);
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
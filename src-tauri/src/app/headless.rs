@@ -0,0 +1,311 @@
+//! 无界面（Headless）服务器模式
+//!
+//! 供 `proxycast-server` 二进制使用：不创建窗口、托盘或 Webview，
+//! 只加载配置、打开数据库并启动 API 服务器，适合在容器或 systemd 中运行。
+//!
+//! 与桌面应用共享同一份 `config.yaml`、SQLite 数据库和 Flow 存储目录。
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::config::{Config, ConfigManager};
+use crate::database::{self, DbConnection};
+use crate::flow_monitor::{
+    FlowInterceptor, FlowMonitor, InterceptConfig, RotationConfig, SessionManager,
+};
+use crate::logger;
+use crate::server;
+use crate::services::provider_pool_service::ProviderPoolService;
+use crate::services::token_cache_service::TokenCacheService;
+
+use super::bootstrap;
+use super::types::LogState;
+
+/// 配置文件路径环境变量
+const CONFIG_ENV: &str = "PROXYCAST_CONFIG";
+
+/// 日志级别环境变量
+const LOG_LEVEL_ENV: &str = "PROXYCAST_LOG_LEVEL";
+
+const USAGE: &str = "\
+proxycast-server - ProxyCast 无界面 API 服务器
+
+用法:
+    proxycast-server [--config <PATH>]
+
+选项:
+    -c, --config <PATH>  配置文件路径（默认读取 PROXYCAST_CONFIG 或 ~/.config/proxycast/config.yaml）
+    -h, --help           显示帮助
+    -V, --version        显示版本
+
+环境变量:
+    PROXYCAST_CONFIG     配置文件路径
+    PROXYCAST_LOG_LEVEL  日志级别（trace/debug/info/warn/error，默认 info）
+";
+
+/// 命令行参数
+#[derive(Debug, Default, PartialEq)]
+struct HeadlessArgs {
+    config_path: Option<PathBuf>,
+    help: bool,
+    version: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<HeadlessArgs, String> {
+    let mut parsed = HeadlessArgs::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format!("{} 需要一个路径参数", arg))?;
+                parsed.config_path = Some(PathBuf::from(path));
+            }
+            "-h" | "--help" => parsed.help = true,
+            "-V" | "--version" => parsed.version = true,
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    parsed.config_path = Some(PathBuf::from(path));
+                } else {
+                    return Err(format!("未知参数: {}", arg));
+                }
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// 运行无界面服务器
+///
+/// 启动流程：
+/// 1. 通过 `ConfigManager` 加载并验证配置
+/// 2. 打开 SQLite 数据库，初始化凭证池、Token 缓存、遥测和 Flow Monitor
+/// 3. 启动 API 服务器，收到 Ctrl+C / SIGTERM 后优雅退出
+pub fn run_headless() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        print!("{}", USAGE);
+        return;
+    }
+    if args.version {
+        println!("proxycast-server {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    init_tracing();
+
+    let config_path = args
+        .config_path
+        .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+        .unwrap_or_else(ConfigManager::default_config_path);
+
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("[HEADLESS] {}", e);
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = run(config, config_path) {
+        tracing::error!("[HEADLESS] {}", e);
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// 初始化控制台日志输出
+fn init_tracing() {
+    let level = std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|v| v.parse::<tracing::Level>().ok())
+        .unwrap_or(tracing::Level::INFO);
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .try_init();
+}
+
+/// 加载并验证配置，必要时写回（首次启动生成 API Key）
+fn load_config(config_path: &std::path::Path) -> Result<Config, String> {
+    let manager = ConfigManager::load(config_path).map_err(|e| e.to_string())?;
+    let mut config = manager.config().clone();
+
+    let changed = bootstrap::validate_config(&mut config).map_err(|e| e.to_string())?;
+    if changed {
+        ConfigManager::with_config(config.clone(), config_path.to_path_buf())
+            .save()
+            .map_err(|e| format!("配置保存失败: {}", e))?;
+        tracing::info!("[HEADLESS] 已生成新的 API Key 并保存到 {:?}", config_path);
+    }
+
+    tracing::info!("[HEADLESS] 已加载配置: {:?}", config_path);
+    Ok(config)
+}
+
+fn run(config: Config, config_path: PathBuf) -> Result<(), String> {
+    // 服务器在后台任务中绑定端口，绑定失败不会返回错误，因此提前检查端口是否可用
+    let (host, port) = (config.server.host.as_str(), config.server.port);
+    drop(
+        std::net::TcpListener::bind((host, port))
+            .map_err(|e| format!("无法监听 {}:{} - {}", host, port, e))?,
+    );
+
+    let logs: LogState = Arc::new(RwLock::new(logger::LogStore::with_config(&config.logging)));
+    let db = database::init_database().map_err(|e| format!("数据库初始化失败: {}", e))?;
+    tracing::info!(
+        "[HEADLESS] 数据库已打开: {:?}",
+        database::get_db_path().unwrap_or_default()
+    );
+
+    let pool_service = Arc::new(ProviderPoolService::new());
+    let token_cache = Arc::new(TokenCacheService::new());
+
+    let (shared_stats, shared_tokens, shared_logger) = bootstrap::init_telemetry(&config)?;
+
+    // Flow Monitor（与桌面应用一致：由 flow-monitor 插件安装状态决定是否启用）
+    let plugin_installer = bootstrap::init_plugin_installer()?;
+    let flow_monitor = Arc::new(FlowMonitor::new(
+        bootstrap::flow_monitor_config(&plugin_installer),
+        bootstrap::init_flow_file_store(&RotationConfig::default()),
+    ));
+    // 会话录制/回放需要会话管理器（与桌面应用共用数据库）
+    match database::get_db_path()
+        .and_then(|path| SessionManager::new(path).map_err(|e| e.to_string()))
+    {
+        Ok(manager) => flow_monitor
+            .playback()
+            .set_session_manager(Arc::new(manager)),
+        Err(e) => tracing::warn!("[HEADLESS] 会话管理器初始化失败，录制/回放不可用: {}", e),
+    }
+    let flow_interceptor = Arc::new(FlowInterceptor::new(InterceptConfig::default()));
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("无法创建运行时: {}", e))?;
+    runtime.block_on(async move {
+        log_credential_pool(&logs, &pool_service, &db).await;
+
+        tokio::spawn(bootstrap::forward_circuit_transitions(
+            pool_service.circuit_breaker().clone(),
+            flow_monitor.clone(),
+            |_, _| {},
+        ));
+
+        let mut server = server::ServerState::new(config).with_config_path(config_path);
+        server
+            .start_with_telemetry_and_flow_monitor(
+                logs.clone(),
+                pool_service,
+                token_cache,
+                Some(db),
+                Some(shared_stats),
+                Some(shared_tokens),
+                Some(shared_logger),
+                Some(flow_monitor),
+                Some(flow_interceptor),
+            )
+            .await
+            .map_err(|e| format!("服务器启动失败: {}", e))?;
+
+        let status = server.status();
+        tracing::info!("[HEADLESS] 服务器已启动: {}:{}", status.host, status.port);
+        logs.write().await.add(
+            "info",
+            &format!("[启动] 服务器已启动: {}:{}", status.host, status.port),
+        );
+
+        wait_for_shutdown().await;
+        tracing::info!("[HEADLESS] 收到退出信号，正在停止服务器...");
+        server.stop().await;
+        Ok(())
+    })
+}
+
+/// 记录凭证池加载情况
+async fn log_credential_pool(
+    logs: &LogState,
+    pool_service: &ProviderPoolService,
+    db: &DbConnection,
+) {
+    match pool_service.get_overview(db) {
+        Ok(overview) => {
+            let loaded: Vec<String> = overview
+                .iter()
+                .filter(|o| o.stats.total_count > 0)
+                .map(|o| format!("{} ({} 个)", o.provider_type, o.stats.total_count))
+                .collect();
+            let message = if loaded.is_empty() {
+                "[启动] 未找到任何可用凭证".to_string()
+            } else {
+                format!("[启动] 凭证已加载: {}", loaded.join(", "))
+            };
+            tracing::info!("{}", message);
+            logs.write().await.add("info", &message);
+        }
+        Err(e) => {
+            tracing::warn!("[启动] 获取凭证池信息失败: {}", e);
+        }
+    }
+}
+
+/// 等待 Ctrl+C 或 SIGTERM
+async fn wait_for_shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("[HEADLESS] 无法监听 SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(args(&[])).unwrap(), HeadlessArgs::default());
+        assert_eq!(
+            parse_args(args(&["--config", "/etc/proxycast/config.yaml"]))
+                .unwrap()
+                .config_path,
+            Some(PathBuf::from("/etc/proxycast/config.yaml"))
+        );
+        assert_eq!(
+            parse_args(args(&["--config=/tmp/c.yaml"]))
+                .unwrap()
+                .config_path,
+            Some(PathBuf::from("/tmp/c.yaml"))
+        );
+        assert!(parse_args(args(&["-h"])).unwrap().help);
+        assert!(parse_args(args(&["-c"])).is_err());
+        assert!(parse_args(args(&["--port", "8999"])).is_err());
+    }
+}
@@ -10,17 +10,31 @@
 //! - `utils` - 辅助函数
 //! - `bootstrap` - 应用启动引导（配置验证、状态初始化）
 //! - `runner` - 应用运行器（Tauri Builder 配置和命令注册）
+//! - `headless` - 无界面服务器模式（`proxycast-server` 二进制）
+//! - `network` - 本地网络信息
+//!
+//! `commands`、`setup`、`runner` 和状态初始化只在 `gui` 特性下编译。
 
 pub mod bootstrap;
+#[cfg(feature = "gui")]
 pub mod commands;
+pub mod headless;
+pub mod network;
+#[cfg(feature = "gui")]
 pub mod runner;
+#[cfg(feature = "gui")]
 mod setup;
+#[cfg(feature = "gui")]
 mod state;
 mod types;
 mod utils;
 
+pub use headless::run_headless;
+#[cfg(feature = "gui")]
 pub use runner::run;
+#[cfg(feature = "gui")]
 pub use setup::setup_app;
+#[cfg(feature = "gui")]
 pub use state::*;
 pub use types::*;
 pub use utils::*;
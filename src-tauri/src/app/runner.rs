@@ -430,6 +430,29 @@ pub fn run() {
                 });
             }
 
+            // 熔断器状态变化：转发到 Flow 事件总线并更新托盘
+            {
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(bootstrap::forward_circuit_transitions(
+                    pool_service_clone.circuit_breaker().clone(),
+                    flow_monitor_clone.clone(),
+                    move |_, open_count| {
+                        let app_handle = app_handle.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Some(tray_state) =
+                                app_handle.try_state::<TrayManagerState<tauri::Wry>>()
+                            {
+                                if let Some(tray_manager) = tray_state.0.read().await.as_ref() {
+                                    if let Err(e) = tray_manager.set_open_circuits(open_count).await {
+                                        tracing::warn!("[CIRCUIT] 更新托盘熔断状态失败: {}", e);
+                                    }
+                                }
+                            }
+                        });
+                    },
+                ));
+            }
+
             // 自动启动服务器
             let state = state_clone.clone();
             let logs = logs_clone.clone();
@@ -827,6 +850,7 @@ pub fn run() {
             // Route commands
             commands::route_cmd::get_available_routes,
             commands::route_cmd::get_route_curl_examples,
+            commands::route_cmd::explain_routing_rules,
             // Resilience config commands
             commands::resilience_cmd::get_retry_config,
             commands::resilience_cmd::update_retry_config,
@@ -892,7 +916,10 @@ pub fn run() {
             commands::flow_monitor_cmd::get_flow_detail,
             commands::flow_monitor_cmd::search_flows,
             commands::flow_monitor_cmd::get_flow_stats,
+            commands::flow_monitor_cmd::list_conversations,
+            commands::flow_monitor_cmd::get_conversation,
             commands::flow_monitor_cmd::export_flows,
+            commands::flow_monitor_cmd::import_flows,
             commands::flow_monitor_cmd::update_flow_annotations,
             commands::flow_monitor_cmd::toggle_flow_starred,
             commands::flow_monitor_cmd::add_flow_comment,
@@ -954,6 +981,10 @@ pub fn run() {
             commands::flow_monitor_cmd::get_auto_session_config,
             commands::flow_monitor_cmd::set_auto_session_config,
             commands::flow_monitor_cmd::register_active_session,
+            // Session record/playback commands
+            commands::flow_monitor_cmd::set_session_mode,
+            commands::flow_monitor_cmd::stop_session_mode,
+            commands::flow_monitor_cmd::get_playback_status,
             // Quick Filter commands
             commands::flow_monitor_cmd::save_quick_filter,
             commands::flow_monitor_cmd::get_quick_filter,
@@ -1046,6 +1077,8 @@ pub fn run() {
             commands::machine_id_cmd::copy_machine_id_to_clipboard,
             commands::machine_id_cmd::paste_machine_id_from_clipboard,
             commands::machine_id_cmd::get_system_info,
+            // Secrets commands
+            commands::secrets_cmd::rekey_secrets,
             // Kiro Local commands
             commands::kiro_local::switch_kiro_to_local,
             commands::kiro_local::get_kiro_fingerprint_info,
@@ -263,6 +263,9 @@ pub fn init_flow_monitor_states(
     let session_manager =
         Arc::new(SessionManager::new(db_path.clone()).expect("Failed to create SessionManager"));
     let session_manager_state = SessionManagerState(session_manager.clone());
+    flow_monitor
+        .playback()
+        .set_session_manager(session_manager.clone());
 
     // 初始化快速过滤器管理器
     let quick_filter_manager = Arc::new(
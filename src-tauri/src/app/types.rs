@@ -4,12 +4,14 @@
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+#[cfg(feature = "gui")]
 use tauri::Runtime;
 use tokio::sync::RwLock;
 
 use crate::logger;
 use crate::server;
 use crate::services::token_cache_service::TokenCacheService;
+#[cfg(feature = "gui")]
 use crate::tray::TrayManager;
 
 /// Provider 类型枚举
@@ -97,6 +99,7 @@ pub type LogState = Arc<RwLock<logger::LogStore>>;
 pub struct TokenCacheServiceState(pub Arc<TokenCacheService>);
 
 /// TrayManager 状态封装
+#[cfg(feature = "gui")]
 pub struct TrayManagerState<R: Runtime>(pub Arc<tokio::sync::RwLock<Option<TrayManager<R>>>>);
 
 #[cfg(test)]
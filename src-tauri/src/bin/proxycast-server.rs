@@ -0,0 +1,5 @@
+//! 无界面 API 服务器入口（不启动窗口、托盘和 Webview）
+
+fn main() {
+    proxycast_lib::run_headless()
+}
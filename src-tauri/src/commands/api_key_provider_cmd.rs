@@ -147,7 +147,7 @@ fn provider_to_display(provider: &ApiKeyProvider, api_key_count: usize) -> Provi
 /// 将 ApiKeyEntry 转换为 ApiKeyDisplay（需要解密后掩码）
 fn api_key_to_display(key: &ApiKeyEntry, service: &ApiKeyProviderService) -> ApiKeyDisplay {
     // 解密后掩码显示
-    let masked = match service.decrypt_api_key(&key.api_key_encrypted) {
+    let masked = match service.decrypt_api_key(&key.id, &key.api_key_encrypted) {
         Ok(decrypted) => mask_api_key(&decrypted),
         Err(_) => "****".to_string(),
     };
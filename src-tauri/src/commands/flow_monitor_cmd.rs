@@ -8,12 +8,16 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::State;
 
+use crate::database::DbConnection;
 use crate::flow_monitor::{
-    get_filter_help, BatchOperation, BatchOperations, BatchResult, DiffConfig, ExportFormat,
-    ExportOptions, FilterExpr, FilterParser, FlowAnnotations, FlowDiff, FlowDiffResult,
-    FlowExporter, FlowFilter, FlowMonitor, FlowQueryResult, FlowQueryService, FlowSearchResult,
-    FlowSortBy, FlowStats, LLMFlow, FILTER_HELP,
+    get_filter_help, BatchOperation, BatchOperations, BatchResult, Conversation,
+    ConversationSummary, DiffConfig, ExportFormat, ExportOptions, FilterExpr, FilterParser,
+    FlowAnnotations, FlowDiff, FlowDiffResult, FlowExporter, FlowFilter, FlowImporter, FlowMonitor,
+    FlowQueryResult, FlowQueryService, FlowSearchResult, FlowSortBy, FlowStats, ImportFormat,
+    ImportOptions, ImportSummary, LLMFlow, FILTER_HELP,
 };
+use crate::services::budget_service::PricingTable;
+use crate::AppState;
 
 // ============================================================================
 // 状态封装
@@ -109,6 +113,19 @@ pub struct ExportFlowsRequest {
     pub flow_ids: Option<Vec<String>>,
 }
 
+/// 导入 Flow 请求参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFlowsRequest {
+    /// 导入的数据（HAR / JSONL / 请求响应日志）
+    pub data: String,
+    /// 导入格式，默认自动识别
+    #[serde(default)]
+    pub format: ImportFormat,
+    /// 来源名称（如文件名或分享者）
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
 /// 导出结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportFlowsResponse {
@@ -276,6 +293,67 @@ pub async fn get_flow_stats(
     Ok(query_service.0.get_stats(&filter).await)
 }
 
+/// 加载计算对话费用用的模型定价
+async fn load_pricing(app_state: &AppState, db: &DbConnection) -> PricingTable {
+    let budget = app_state.read().await.config.budget.clone();
+    PricingTable::load(&budget, db)
+}
+
+/// 列出最近活跃的对话
+///
+/// # Arguments
+/// * `filter` - 过滤条件（可选，用于筛选参与统计的最近 Flow）
+/// * `limit` - 最大返回数量（默认 50）
+/// * `query_service` - 查询服务状态
+/// * `app_state` / `db` - 用于加载模型定价
+///
+/// # Returns
+/// * `Ok(Vec<ConversationSummary>)` - 按最近活动时间排序的对话摘要及合计
+/// * `Err(String)` - 失败时返回错误消息
+#[tauri::command]
+pub async fn list_conversations(
+    filter: Option<FlowFilter>,
+    limit: Option<usize>,
+    query_service: State<'_, FlowQueryServiceState>,
+    app_state: State<'_, AppState>,
+    db: State<'_, DbConnection>,
+) -> Result<Vec<ConversationSummary>, String> {
+    let pricing = load_pricing(&app_state, &db).await;
+    query_service
+        .0
+        .list_conversations(&filter.unwrap_or_default(), limit.unwrap_or(50), |model| {
+            pricing.get(model)
+        })
+        .await
+        .map_err(|e| format!("获取对话列表失败: {}", e))
+}
+
+/// 获取对话树
+///
+/// # Arguments
+/// * `conversation_id` - 对话 ID（根 Flow 的 ID）
+/// * `query_service` - 查询服务状态
+/// * `app_state` / `db` - 用于加载模型定价
+///
+/// # Returns
+/// * `Ok(Some(Conversation))` - 对话树节点及合计（Token、费用、耗时、工具调用）
+/// * `Ok(None)` - 对话不存在
+/// * `Err(String)` - 失败时返回错误消息
+#[tauri::command]
+pub async fn get_conversation(
+    conversation_id: String,
+    query_service: State<'_, FlowQueryServiceState>,
+    app_state: State<'_, AppState>,
+    db: State<'_, DbConnection>,
+) -> Result<Option<Conversation>, String> {
+    let pricing = load_pricing(&app_state, &db).await;
+    query_service
+        .0
+        .get_conversation(&conversation_id, |model| pricing.get(model))
+        .await
+        .map_err(|e| format!("获取对话失败: {}", e))
+}
+
 /// 导出 Flow
 ///
 /// **Validates: Requirements 10.5**
@@ -349,6 +427,45 @@ pub async fn export_flows(
     })
 }
 
+/// 导入 Flow
+///
+/// 解析 HAR、JSONL 或 OpenAI/Anthropic 请求响应日志，写入文件存储并打上来源标签。
+/// 已存在的 Flow ID 会被跳过。
+///
+/// # Arguments
+/// * `request` - 导入请求参数
+/// * `monitor` - Flow 监控服务状态
+///
+/// # Returns
+/// * `Ok(ImportSummary)` - 成功时返回导入统计
+/// * `Err(String)` - 失败时返回错误消息
+#[tauri::command]
+pub async fn import_flows(
+    request: ImportFlowsRequest,
+    monitor: State<'_, FlowMonitorState>,
+) -> Result<ImportSummary, String> {
+    let store = monitor
+        .0
+        .file_store()
+        .ok_or_else(|| "导入 Flow 失败: 未启用文件存储".to_string())?;
+    let importer = FlowImporter::new(ImportOptions {
+        format: request.format,
+        source: request.source,
+    });
+
+    let summary = importer
+        .import_into(&request.data, &store)
+        .map_err(|e| format!("导入 Flow 失败: {}", e))?;
+    tracing::info!(
+        "[FLOW_MONITOR] 导入 Flow: 新增 {}, 重复 {}, 跳过 {}, 失败 {}",
+        summary.imported,
+        summary.duplicates,
+        summary.skipped,
+        summary.errors.len()
+    );
+    Ok(summary)
+}
+
 /// 更新 Flow 标注
 ///
 /// **Validates: Requirements 10.6**
@@ -799,6 +916,7 @@ pub async fn create_test_flows(
                 ip: Some("127.0.0.1".to_string()),
                 user_agent: Some("test-agent".to_string()),
                 request_id: Some(format!("test-req-{}", i)),
+                ..Default::default()
             },
             routing_info: RoutingInfo {
                 target_url: Some("https://api.openai.com".to_string()),
@@ -807,6 +925,11 @@ pub async fn create_test_flows(
             },
             injected_params: None,
             context_usage_percentage: Some(50.0),
+            cached: false,
+            hedge_attempts: Vec::new(),
+            recording_session: None,
+            playback_of: None,
+            conversation: None,
         };
 
         // 启动 Flow
@@ -2027,6 +2150,84 @@ pub async fn register_active_session(
     Ok(())
 }
 
+// ============================================================================
+// 会话录制/回放命令
+// ============================================================================
+
+use crate::flow_monitor::{PlaybackOptions, PlaybackStatus, SessionMode};
+
+/// 切换会话模式请求参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetSessionModeRequest {
+    /// 会话 ID
+    pub session_id: String,
+    /// 录制或回放
+    pub mode: SessionMode,
+    /// 回放选项（仅回放模式使用）
+    #[serde(default)]
+    pub options: PlaybackOptions,
+}
+
+/// 将会话切换为录制或回放模式
+///
+/// 录制模式下请求照常发往上游，完成的 Flow 加入会话；
+/// 回放模式下从会话中录制的 Flow 返回响应，不调用上游。
+///
+/// # Arguments
+/// * `request` - 切换模式请求参数
+/// * `monitor` - Flow 监控服务状态
+///
+/// # Returns
+/// * `Ok(PlaybackStatus)` - 成功时返回会话的录制/回放状态
+/// * `Err(String)` - 失败时返回错误消息
+#[tauri::command]
+pub async fn set_session_mode(
+    request: SetSessionModeRequest,
+    monitor: State<'_, FlowMonitorState>,
+) -> Result<PlaybackStatus, String> {
+    let result = match request.mode {
+        SessionMode::Record => monitor.0.playback().record(&request.session_id),
+        SessionMode::Playback => {
+            monitor
+                .0
+                .start_playback(&request.session_id, request.options)
+                .await
+        }
+    };
+    result.map_err(|e| format!("切换会话模式失败: {}", e))
+}
+
+/// 停止会话的录制/回放
+///
+/// # Arguments
+/// * `session_id` - 会话 ID
+/// * `monitor` - Flow 监控服务状态
+///
+/// # Returns
+/// * `Ok(Some(PlaybackStatus))` - 停止前的状态
+/// * `Ok(None)` - 会话未处于录制或回放模式
+#[tauri::command]
+pub async fn stop_session_mode(
+    session_id: String,
+    monitor: State<'_, FlowMonitorState>,
+) -> Result<Option<PlaybackStatus>, String> {
+    Ok(monitor.0.playback().stop(&session_id))
+}
+
+/// 获取所有处于录制或回放模式的会话状态
+///
+/// # Arguments
+/// * `monitor` - Flow 监控服务状态
+///
+/// # Returns
+/// * `Ok(Vec<PlaybackStatus>)` - 会话状态列表
+#[tauri::command]
+pub async fn get_playback_status(
+    monitor: State<'_, FlowMonitorState>,
+) -> Result<Vec<PlaybackStatus>, String> {
+    Ok(monitor.0.playback().status())
+}
+
 // ============================================================================
 // 快速过滤器命令
 // ============================================================================
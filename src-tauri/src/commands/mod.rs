@@ -27,6 +27,7 @@ pub mod provider_pool_cmd;
 pub mod resilience_cmd;
 pub mod route_cmd;
 pub mod screenshot_cmd;
+pub mod secrets_cmd;
 pub mod session_files_cmd;
 pub mod skill_cmd;
 pub mod switch_cmd;
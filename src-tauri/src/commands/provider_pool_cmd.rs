@@ -480,6 +480,9 @@ pub fn update_provider_pool_credential(
         if let Some(not_supported_models) = request.not_supported_models {
             current_credential.not_supported_models = not_supported_models;
         }
+        if let Some(weight) = request.weight {
+            current_credential.weight = weight;
+        }
 
         current_credential.updated_at = Utc::now();
 
@@ -498,6 +501,7 @@ pub fn update_provider_pool_credential(
             request.check_model_name,
             request.not_supported_models,
             request.new_proxy_url,
+            request.weight,
         )?
     };
 
@@ -551,9 +555,17 @@ pub fn toggle_provider_pool_credential(
     uuid: String,
     is_disabled: bool,
 ) -> Result<ProviderCredential, String> {
-    pool_service
-        .0
-        .update_credential(&db, &uuid, None, Some(is_disabled), None, None, None, None)
+    pool_service.0.update_credential(
+        &db,
+        &uuid,
+        None,
+        Some(is_disabled),
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
 }
 
 /// 重置凭证计数器
@@ -5,6 +5,8 @@ use crate::commands::provider_pool_cmd::ProviderPoolServiceState;
 use crate::config;
 use crate::database::DbConnection;
 use crate::models::route_model::{RouteInfo, RouteListResponse};
+use crate::router::{ModelMapper, RouteExplainRequest, RouteExplanation, Router};
+use crate::telemetry::AnthropicTokenCounter;
 
 /// 获取可访问的服务器地址
 ///
@@ -92,3 +94,40 @@ pub async fn get_route_curl_examples(
         }
     }
 }
+
+/// 解释路由规则的匹配过程（dry-run）
+///
+/// 使用配置文件中的路由规则评估给定请求，返回每条规则的匹配结果以及最终的 Provider 和模型，
+/// 不会发送任何上游请求。
+#[tauri::command]
+pub async fn explain_routing_rules(
+    request: RouteExplainRequest,
+) -> Result<RouteExplanation, String> {
+    let config = config::load_config().map_err(|e| e.to_string())?;
+
+    let mut router = Router::new_empty();
+    if let Ok(provider) = config
+        .routing
+        .default_provider
+        .parse::<crate::ProviderType>()
+    {
+        router.set_default_provider(provider);
+    }
+    let errors = router.set_rules(&config.routing.rules);
+    if !errors.is_empty() {
+        return Err(format!("路由规则配置无效: {}", errors.join("; ")));
+    }
+
+    let mapper = ModelMapper::from_aliases(config.routing.model_aliases.clone());
+    let token_counter = AnthropicTokenCounter::new()
+        .map_err(|e| e.to_string())?
+        .with_calibration_overrides(config.token_counting.calibration.clone());
+    let features = request.features(&mapper.resolve(&request.model), &token_counter)?;
+    let fallback = config
+        .endpoint_providers
+        .get_provider(features.client_type.config_key())
+        .unwrap_or(&config.routing.default_provider)
+        .clone();
+
+    Ok(router.explain(&features).with_fallback_provider(&fallback))
+}
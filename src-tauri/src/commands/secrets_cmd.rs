@@ -0,0 +1,27 @@
+//! 凭证加密 Tauri 命令
+//!
+//! 提供存储凭证的加密密钥轮换（rekey）命令。
+
+use crate::database::secrets::{self, KeySource, RekeySummary};
+use crate::database::DbConnection;
+use tauri::State;
+
+/// 轮换凭证加密密钥
+///
+/// 使用当前密钥解密所有已加密的凭证，再用新密钥重新加密。
+///
+/// # Arguments
+/// * `passphrase` - 新的主密码；为空时改用本地密钥文件（生成新的随机密钥）
+#[tauri::command]
+pub async fn rekey_secrets(
+    passphrase: Option<String>,
+    db: State<'_, DbConnection>,
+) -> Result<RekeySummary, String> {
+    let new_source = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => KeySource::Passphrase(passphrase),
+        None => KeySource::KeyFile(secrets::default_key_file_path()?),
+    };
+
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    secrets::rekey(&conn, new_source)
+}
@@ -336,6 +336,7 @@ impl ExportService {
             vertex_api_keys: pool.vertex_api_keys.clone(),
             codex: pool.codex.clone(),
             iflow: pool.iflow.clone(),
+            strategies: pool.strategies.clone(),
         }
     }
 
@@ -376,6 +376,12 @@ impl ImportService {
             vertex_api_keys: imported.vertex_api_keys.clone(),
             codex: Self::merge_credential_entries(&current.codex, &imported.codex),
             iflow: imported.iflow.clone(),
+            strategies: current
+                .strategies
+                .iter()
+                .chain(&imported.strategies)
+                .map(|(provider, strategy)| (provider.clone(), *strategy))
+                .collect(),
         }
     }
 
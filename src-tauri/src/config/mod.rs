@@ -8,6 +8,7 @@
 mod export;
 mod hot_reload;
 mod import;
+#[cfg(feature = "gui")]
 pub mod observer;
 mod path_utils;
 mod types;
@@ -21,17 +22,22 @@ pub use hot_reload::{
 pub use import::{ImportOptions, ImportService, ValidationResult};
 pub use path_utils::{collapse_tilde, contains_tilde, expand_tilde};
 pub use types::{
-    generate_secure_api_key, AmpConfig, AmpModelMapping, ApiKeyEntry, Config, CredentialEntry,
-    CredentialPoolConfig, CustomProviderConfig, EndpointProvidersConfig, ExperimentalFeatures,
-    GeminiApiKeyEntry, IFlowCredentialEntry, InjectionRuleConfig, InjectionSettings, LoggingConfig,
-    ModelInfo, ModelsConfig, NativeAgentConfig, ProviderConfig, ProviderModelsConfig,
-    ProvidersConfig, QuotaExceededConfig, RemoteManagementConfig, RetrySettings, RoutingConfig,
-    ScreenshotChatConfig, ServerConfig, TlsConfig, VertexApiKeyEntry, VertexModelAlias,
-    DEFAULT_API_KEY,
+    generate_secure_api_key, AlertConfig, AlertMetric, AlertOperator, AlertRule, AlertWebhook,
+    AlertWebhookFormat, AmpConfig, AmpModelMapping, ApiKeyEntry, BudgetAction, BudgetConfig,
+    BudgetRule, BudgetScope, BudgetWindow, CanaryConfig, CircuitBreakerConfig, ConcurrencyConfig,
+    Config, CredentialEntry, CredentialPoolConfig, CustomProviderConfig, EndpointProvidersConfig,
+    EquivalentModel, ExperimentalFeatures, GeminiApiKeyEntry, HedgingConfig, IFlowCredentialEntry,
+    InjectionRuleConfig, InjectionSettings, LoggingConfig, ModelEquivalenceGroup, ModelInfo,
+    ModelsConfig, NativeAgentConfig, OtelConfig, ProviderConfig, ProviderModelsConfig,
+    ProvidersConfig, QuotaExceededConfig, RemoteManagementConfig, ResponseCacheConfig,
+    RetrySettings, RoutingConfig, RoutingRuleConditions, RoutingRuleConfig, ScreenshotChatConfig,
+    ServerConfig, TlsConfig, TokenCountingConfig, TokenRefreshConfig, VertexApiKeyEntry,
+    VertexModelAlias, DEFAULT_API_KEY,
 };
 pub use yaml::{load_config, save_config, ConfigError, ConfigManager, YamlService};
 
-// 重新导出观察者模块的核心类型
+// 重新导出观察者模块的核心类型（观察者向前端发送 Tauri 事件，仅 `gui` 特性）
+#[cfg(feature = "gui")]
 pub use observer::{
     ConfigChangeEvent, ConfigChangeSource, ConfigObserver, ConfigSubject, GlobalConfigManager,
     GlobalConfigManagerState,
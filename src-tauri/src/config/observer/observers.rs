@@ -62,6 +62,14 @@ impl ConfigObserver for RouterObserver {
             );
         }
 
+        // 更新路由规则
+        {
+            let mut router = self.router.write().await;
+            for error in router.set_rules(&config.routing.rules) {
+                tracing::warn!("[RouterObserver] 跳过无效的路由规则: {}", error);
+            }
+        }
+
         // 更新模型别名
         {
             let mut mapper = self.mapper.write().await;
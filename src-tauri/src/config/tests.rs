@@ -116,6 +116,8 @@ fn arb_routing_config() -> impl Strategy<Value = RoutingConfig> {
         .prop_map(|(default_provider, model_aliases)| RoutingConfig {
             default_provider,
             model_aliases,
+            rules: Vec::new(),
+            equivalence_groups: Vec::new(),
         })
 }
 
@@ -133,6 +135,7 @@ fn arb_retry_settings() -> impl Strategy<Value = RetrySettings> {
                 base_delay_ms,
                 max_delay_ms,
                 auto_switch_provider,
+                ..RetrySettings::default()
             },
         )
 }
@@ -189,6 +192,16 @@ fn arb_config() -> impl Strategy<Value = Config> {
             agent: crate::config::NativeAgentConfig::default(),
             language: "zh".to_string(),
             experimental: crate::config::ExperimentalFeatures::default(),
+            token_counting: crate::config::TokenCountingConfig::default(),
+            otel: crate::config::OtelConfig::default(),
+            response_cache: crate::config::ResponseCacheConfig::default(),
+            circuit_breaker: crate::config::CircuitBreakerConfig::default(),
+            hedging: crate::config::HedgingConfig::default(),
+            concurrency: crate::config::ConcurrencyConfig::default(),
+            budget: crate::config::BudgetConfig::default(),
+            token_refresh: crate::config::TokenRefreshConfig::default(),
+            canary: crate::config::CanaryConfig::default(),
+            alerts: crate::config::AlertConfig::default(),
         })
 }
 
@@ -371,6 +384,7 @@ fn arb_valid_retry_settings() -> impl Strategy<Value = RetrySettings> {
                 base_delay_ms,
                 max_delay_ms,
                 auto_switch_provider,
+                ..RetrySettings::default()
             },
         )
 }
@@ -427,6 +441,16 @@ fn arb_valid_config() -> impl Strategy<Value = Config> {
             agent: crate::config::NativeAgentConfig::default(),
             language: "zh".to_string(),
             experimental: crate::config::ExperimentalFeatures::default(),
+            token_counting: crate::config::TokenCountingConfig::default(),
+            otel: crate::config::OtelConfig::default(),
+            response_cache: crate::config::ResponseCacheConfig::default(),
+            circuit_breaker: crate::config::CircuitBreakerConfig::default(),
+            hedging: crate::config::HedgingConfig::default(),
+            concurrency: crate::config::ConcurrencyConfig::default(),
+            budget: crate::config::BudgetConfig::default(),
+            token_refresh: crate::config::TokenRefreshConfig::default(),
+            canary: crate::config::CanaryConfig::default(),
+            alerts: crate::config::AlertConfig::default(),
         })
 }
 
@@ -476,6 +500,16 @@ fn arb_invalid_config() -> impl Strategy<Value = Config> {
                     agent: crate::config::NativeAgentConfig::default(),
                     language: "zh".to_string(),
                     experimental: crate::config::ExperimentalFeatures::default(),
+                    token_counting: crate::config::TokenCountingConfig::default(),
+                    otel: crate::config::OtelConfig::default(),
+                    response_cache: crate::config::ResponseCacheConfig::default(),
+                    circuit_breaker: crate::config::CircuitBreakerConfig::default(),
+                    hedging: crate::config::HedgingConfig::default(),
+                    concurrency: crate::config::ConcurrencyConfig::default(),
+                    budget: crate::config::BudgetConfig::default(),
+                    token_refresh: crate::config::TokenRefreshConfig::default(),
+                    canary: crate::config::CanaryConfig::default(),
+                    alerts: crate::config::AlertConfig::default(),
                 };
                 // 根据类型使配置无效
                 match invalid_type {
@@ -1229,6 +1263,7 @@ fn arb_credential_pool_config() -> impl Strategy<Value = CredentialPoolConfig> {
                 vertex_api_keys: vec![],
                 codex: vec![],
                 iflow: vec![],
+                strategies: std::collections::HashMap::new(),
             },
         )
 }
@@ -2281,6 +2316,7 @@ fn arb_extended_credential_pool_config() -> impl Strategy<Value = CredentialPool
                 vertex_api_keys,
                 codex,
                 iflow,
+                strategies: std::collections::HashMap::new(),
             },
         )
 }
@@ -41,6 +41,11 @@ pub struct CredentialPoolConfig {
     /// iFlow 凭证列表
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub iflow: Vec<IFlowCredentialEntry>,
+    /// 按 Provider 类型配置的负载均衡策略（键为 Provider 类型，如 "kiro"）
+    ///
+    /// 未配置的 Provider 使用默认的综合评分选择
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub strategies: HashMap<String, crate::credential::BalanceStrategy>,
 }
 
 /// Gemini API Key 凭证条目
@@ -323,6 +328,36 @@ pub struct Config {
     /// 实验室功能配置
     #[serde(default)]
     pub experimental: ExperimentalFeatures,
+    /// Token 计数配置（/v1/messages/count_tokens）
+    #[serde(default)]
+    pub token_counting: TokenCountingConfig,
+    /// OpenTelemetry 链路追踪配置
+    #[serde(default)]
+    pub otel: OtelConfig,
+    /// 响应缓存配置
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+    /// 熔断器配置
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// 对冲请求配置
+    #[serde(default)]
+    pub hedging: HedgingConfig,
+    /// 并发限制与请求排队配置
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    /// 费用预算配置
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// OAuth Token 提前刷新配置
+    #[serde(default)]
+    pub token_refresh: TokenRefreshConfig,
+    /// 凭证健康定时探测配置
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    /// 流量告警规则配置
+    #[serde(default)]
+    pub alerts: AlertConfig,
 }
 
 // ============ Native Agent 配置类型 ============
@@ -562,6 +597,660 @@ pub struct RemoteManagementConfig {
     pub disable_control_panel: bool,
 }
 
+/// Token 计数配置
+///
+/// 控制 `/v1/messages/count_tokens` 端点的计数方式
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TokenCountingConfig {
+    /// 路由到的 Provider 支持原生计数端点时，优先调用上游（如 Anthropic API Key）
+    #[serde(default)]
+    pub use_native_endpoint: bool,
+    /// 按模型家族覆盖校准系数（claude/gpt/gemini/qwen/deepseek/other）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub calibration: HashMap<String, f64>,
+}
+
+/// OpenTelemetry 链路追踪配置
+///
+/// 启用后每个代理请求都会生成一棵 span 树，并通过 OTLP/HTTP (JSON) 导出到采集器
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OtelConfig {
+    /// 是否启用追踪导出
+    #[serde(default)]
+    pub enabled: bool,
+    /// OTLP/HTTP traces 端点
+    #[serde(default = "default_otel_endpoint")]
+    pub endpoint: String,
+    /// 上报的 service.name
+    #[serde(default = "default_otel_service_name")]
+    pub service_name: String,
+    /// 导出请求附带的额外 Header（如采集器鉴权）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// 采样比例（0.0 - 1.0），上游 traceparent 的采样标记优先
+    #[serde(default = "default_otel_sample_ratio")]
+    pub sample_ratio: f64,
+    /// 单次导出的最大 span 数
+    #[serde(default = "default_otel_max_batch_size")]
+    pub max_batch_size: usize,
+    /// 批量导出间隔（毫秒）
+    #[serde(default = "default_otel_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+}
+
+fn default_otel_endpoint() -> String {
+    "http://127.0.0.1:4318/v1/traces".to_string()
+}
+
+fn default_otel_service_name() -> String {
+    "proxycast".to_string()
+}
+
+fn default_otel_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_otel_max_batch_size() -> usize {
+    512
+}
+
+fn default_otel_flush_interval_ms() -> u64 {
+    5000
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_otel_endpoint(),
+            service_name: default_otel_service_name(),
+            headers: HashMap::new(),
+            sample_ratio: default_otel_sample_ratio(),
+            max_batch_size: default_otel_max_batch_size(),
+            flush_interval_ms: default_otel_flush_interval_ms(),
+        }
+    }
+}
+
+/// 响应缓存配置
+///
+/// 对完全相同的请求（规范化后的模型、消息、工具和采样参数）直接返回缓存的响应，
+/// JSON 和 SSE 响应都会缓存，条目存储在 SQLite 中
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResponseCacheConfig {
+    /// 是否启用响应缓存
+    #[serde(default)]
+    pub enabled: bool,
+    /// 缓存条目存活时间（秒）
+    #[serde(default = "default_response_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// 最大条目数
+    #[serde(default = "default_response_cache_max_entries")]
+    pub max_entries: usize,
+    /// 缓存总大小上限（MB）
+    #[serde(default = "default_response_cache_max_size_mb")]
+    pub max_size_mb: u64,
+    /// 单个响应大小上限（KB），超过的响应不缓存
+    #[serde(default = "default_response_cache_max_entry_kb")]
+    pub max_entry_kb: u64,
+}
+
+fn default_response_cache_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_response_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_response_cache_max_size_mb() -> u64 {
+    256
+}
+
+fn default_response_cache_max_entry_kb() -> u64 {
+    4096
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_response_cache_ttl_secs(),
+            max_entries: default_response_cache_max_entries(),
+            max_size_mb: default_response_cache_max_size_mb(),
+            max_entry_kb: default_response_cache_max_entry_kb(),
+        }
+    }
+}
+
+/// 熔断器配置
+///
+/// 按凭证和上游主机分别跟踪请求结果：连续失败或滚动窗口内错误率过高时打开熔断，
+/// 冷却结束后进入半开状态，只放行少量探测请求
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// 是否启用熔断器
+    #[serde(default = "default_circuit_breaker_enabled")]
+    pub enabled: bool,
+    /// 连续失败多少次后打开熔断
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    /// 滚动窗口内错误率阈值（0.0 - 1.0）
+    #[serde(default = "default_circuit_error_rate_threshold")]
+    pub error_rate_threshold: f64,
+    /// 按错误率判断前窗口内至少需要的请求数
+    #[serde(default = "default_circuit_min_requests")]
+    pub min_requests: u32,
+    /// 滚动窗口长度（秒）
+    #[serde(default = "default_circuit_window_secs")]
+    pub window_secs: u64,
+    /// 熔断打开后的冷却时间（秒），结束后进入半开状态
+    #[serde(default = "default_circuit_open_duration_secs")]
+    pub open_duration_secs: u64,
+    /// 半开状态下允许的探测请求数，全部成功后关闭熔断
+    #[serde(default = "default_circuit_half_open_max_probes")]
+    pub half_open_max_probes: u32,
+}
+
+fn default_circuit_breaker_enabled() -> bool {
+    true
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_error_rate_threshold() -> f64 {
+    0.5
+}
+
+fn default_circuit_min_requests() -> u32 {
+    10
+}
+
+fn default_circuit_window_secs() -> u64 {
+    60
+}
+
+fn default_circuit_open_duration_secs() -> u64 {
+    30
+}
+
+fn default_circuit_half_open_max_probes() -> u32 {
+    1
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_circuit_breaker_enabled(),
+            failure_threshold: default_circuit_failure_threshold(),
+            error_rate_threshold: default_circuit_error_rate_threshold(),
+            min_requests: default_circuit_min_requests(),
+            window_secs: default_circuit_window_secs(),
+            open_duration_secs: default_circuit_open_duration_secs(),
+            half_open_max_probes: default_circuit_half_open_max_probes(),
+        }
+    }
+}
+
+/// 对冲请求配置
+///
+/// 首字节迟迟未到达时（超过该模型近期 TTFB 的指定分位数），
+/// 使用其他凭证或 Provider 发出第二个请求，先产生 token 的一方胜出
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HedgingConfig {
+    /// 是否启用对冲请求（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 触发对冲的 TTFB 分位数（0.0 - 1.0）
+    #[serde(default = "default_hedging_ttfb_percentile")]
+    pub ttfb_percentile: f64,
+    /// 计算分位数前每个模型至少需要的 TTFB 样本数，不足时不触发对冲
+    #[serde(default = "default_hedging_min_samples")]
+    pub min_samples: usize,
+    /// 对冲等待时间下限（毫秒）
+    #[serde(default = "default_hedging_min_delay_ms")]
+    pub min_delay_ms: u64,
+    /// 对冲等待时间上限（毫秒）
+    #[serde(default = "default_hedging_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// 启用对冲的 Provider（为空时对所有 Provider 生效）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+}
+
+fn default_hedging_ttfb_percentile() -> f64 {
+    0.95
+}
+
+fn default_hedging_min_samples() -> usize {
+    20
+}
+
+fn default_hedging_min_delay_ms() -> u64 {
+    500
+}
+
+fn default_hedging_max_delay_ms() -> u64 {
+    10_000
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttfb_percentile: default_hedging_ttfb_percentile(),
+            min_samples: default_hedging_min_samples(),
+            min_delay_ms: default_hedging_min_delay_ms(),
+            max_delay_ms: default_hedging_max_delay_ms(),
+            providers: Vec::new(),
+        }
+    }
+}
+
+/// 并发限制配置
+///
+/// 限制每个凭证和每个 Provider 的同时在途请求数，超出的请求进入公平的 FIFO 队列等待，
+/// 交互式请求优先于批量请求出队。队列已满或等待超时时返回 429
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConcurrencyConfig {
+    /// 是否启用并发限制（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 每个凭证的默认最大并发数（0 表示不限制）
+    #[serde(default)]
+    pub max_per_credential: u32,
+    /// 按凭证 UUID 覆盖的最大并发数
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub credential_limits: HashMap<String, u32>,
+    /// 按 Provider 类型配置的最大并发数（键为 Provider 类型，如 "kiro"）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub provider_limits: HashMap<String, u32>,
+    /// 每个凭证 / Provider 的最大排队请求数
+    #[serde(default = "default_concurrency_max_queue_size")]
+    pub max_queue_size: usize,
+    /// 最大排队等待时间（毫秒）
+    #[serde(default = "default_concurrency_max_queue_time_ms")]
+    pub max_queue_time_ms: u64,
+    /// 默认按批量优先级处理的客户端 key（ID 或名称）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub batch_client_keys: Vec<String>,
+}
+
+fn default_concurrency_max_queue_size() -> usize {
+    64
+}
+
+fn default_concurrency_max_queue_time_ms() -> u64 {
+    30_000
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_per_credential: 0,
+            credential_limits: HashMap::new(),
+            provider_limits: HashMap::new(),
+            max_queue_size: default_concurrency_max_queue_size(),
+            max_queue_time_ms: default_concurrency_max_queue_time_ms(),
+            batch_client_keys: Vec::new(),
+        }
+    }
+}
+
+/// 费用预算配置
+///
+/// 按模型注册表中的定价把 Token 用量换算为费用，按 Provider、凭证或客户端 key
+/// 统计每日 / 每月花费，超出预算时阻止请求、发送通知或降级到更便宜的模型
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BudgetConfig {
+    /// 是否启用预算检查（花费统计始终进行）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 预算金额使用的货币，与模型定价货币不同的费用不计入预算
+    #[serde(default = "default_budget_currency")]
+    pub currency: String,
+    /// 预算规则
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<BudgetRule>,
+    /// 覆盖模型注册表的定价（键为模型 ID）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, crate::models::model_registry::ModelPricing>,
+}
+
+fn default_budget_currency() -> String {
+    "USD".to_string()
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            currency: default_budget_currency(),
+            rules: Vec::new(),
+            pricing: HashMap::new(),
+        }
+    }
+}
+
+/// 预算规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BudgetRule {
+    /// 统计范围
+    pub scope: BudgetScope,
+    /// 目标：Provider 类型、凭证 UUID 或客户端 key（ID 或名称），`*` 表示分别应用到每个目标
+    pub target: String,
+    /// 统计周期
+    pub window: BudgetWindow,
+    /// 预算金额
+    pub limit: f64,
+    /// 超出预算时的处理方式
+    #[serde(default)]
+    pub action: BudgetAction,
+    /// 降级使用的模型（`action` 为 `downgrade` 时必填）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downgrade_model: Option<String>,
+    /// 花费达到预算的该比例时发送预警通知（0 表示不预警）
+    #[serde(default = "default_budget_warn_ratio")]
+    pub warn_ratio: f64,
+}
+
+fn default_budget_warn_ratio() -> f64 {
+    0.8
+}
+
+/// 预算统计范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    Provider,
+    Credential,
+    ClientKey,
+}
+
+/// 预算统计周期（UTC 自然日 / 自然月）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetWindow {
+    Daily,
+    Monthly,
+}
+
+/// 超出预算时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// 拒绝请求（返回 429）
+    #[default]
+    Block,
+    /// 只发送通知，不影响请求
+    Warn,
+    /// 改用 `downgrade_model` 指定的更便宜模型
+    Downgrade,
+}
+
+/// OAuth Token 提前刷新配置
+///
+/// 后台定期扫描凭证池中 OAuth 凭证的 Token 过期时间，在过期前带随机抖动地提前刷新，
+/// 避免空闲后的第一个请求承担刷新延迟
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TokenRefreshConfig {
+    /// 是否启用提前刷新
+    #[serde(default = "default_token_refresh_enabled")]
+    pub enabled: bool,
+    /// 扫描间隔（秒）
+    #[serde(default = "default_token_refresh_scan_interval_secs")]
+    pub scan_interval_secs: u64,
+    /// 在过期前多少秒刷新
+    #[serde(default = "default_token_refresh_before_secs")]
+    pub refresh_before_secs: u64,
+    /// 随机抖动上限（秒），各凭证在此范围内错开刷新
+    #[serde(default = "default_token_refresh_jitter_secs")]
+    pub jitter_secs: u64,
+    /// 同时进行的最大刷新数
+    #[serde(default = "default_token_refresh_max_concurrent")]
+    pub max_concurrent: usize,
+    /// 刷新失败后的初始退避时间（秒），每次失败翻倍
+    #[serde(default = "default_token_refresh_backoff_base_secs")]
+    pub backoff_base_secs: u64,
+    /// 最大退避时间（秒）
+    #[serde(default = "default_token_refresh_backoff_max_secs")]
+    pub backoff_max_secs: u64,
+    /// refresh token 连续被拒绝多少次后标记凭证不健康
+    #[serde(default = "default_token_refresh_dead_after_failures")]
+    pub dead_after_failures: u32,
+}
+
+fn default_token_refresh_enabled() -> bool {
+    true
+}
+
+fn default_token_refresh_scan_interval_secs() -> u64 {
+    60
+}
+
+fn default_token_refresh_before_secs() -> u64 {
+    600
+}
+
+fn default_token_refresh_jitter_secs() -> u64 {
+    120
+}
+
+fn default_token_refresh_max_concurrent() -> usize {
+    4
+}
+
+fn default_token_refresh_backoff_base_secs() -> u64 {
+    30
+}
+
+fn default_token_refresh_backoff_max_secs() -> u64 {
+    1800
+}
+
+fn default_token_refresh_dead_after_failures() -> u32 {
+    2
+}
+
+impl Default for TokenRefreshConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_token_refresh_enabled(),
+            scan_interval_secs: default_token_refresh_scan_interval_secs(),
+            refresh_before_secs: default_token_refresh_before_secs(),
+            jitter_secs: default_token_refresh_jitter_secs(),
+            max_concurrent: default_token_refresh_max_concurrent(),
+            backoff_base_secs: default_token_refresh_backoff_base_secs(),
+            backoff_max_secs: default_token_refresh_backoff_max_secs(),
+            dead_after_failures: default_token_refresh_dead_after_failures(),
+        }
+    }
+}
+
+/// 凭证健康定时探测配置
+///
+/// 按间隔（带随机抖动）向凭证池中每个启用健康检查的凭证发送一个极小的请求，
+/// 记录延迟和结果到健康检查历史，并据此自动移出或恢复凭证
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CanaryConfig {
+    /// 是否启用定时探测（探测请求会消耗少量额度，默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 每个凭证的探测间隔（秒）
+    #[serde(default = "default_canary_interval_secs")]
+    pub interval_secs: u64,
+    /// 随机抖动上限（秒），各凭证在此范围内错开探测
+    #[serde(default = "default_canary_jitter_secs")]
+    pub jitter_secs: u64,
+    /// 同时进行的最大探测数
+    #[serde(default = "default_canary_max_concurrent")]
+    pub max_concurrent: usize,
+    /// 按 Provider 类型指定探测模型（键为小写 Provider 类型，如 `claude`）
+    ///
+    /// 未指定时使用凭证的检查模型或 Provider 默认检查模型
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+}
+
+fn default_canary_interval_secs() -> u64 {
+    300
+}
+
+fn default_canary_jitter_secs() -> u64 {
+    60
+}
+
+fn default_canary_max_concurrent() -> usize {
+    2
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_canary_interval_secs(),
+            jitter_secs: default_canary_jitter_secs(),
+            max_concurrent: default_canary_max_concurrent(),
+            models: HashMap::new(),
+        }
+    }
+}
+
+/// 流量告警配置
+///
+/// 告警规则用过滤表达式选出 Flow，在滑动窗口内计算错误率、延迟分位数等指标，
+/// 满足条件时向 Webhook 发送告警（同一告警只发送一次），指标恢复后发送解除通知
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertConfig {
+    /// 是否启用告警
+    #[serde(default)]
+    pub enabled: bool,
+    /// 规则评估间隔（秒）
+    #[serde(default = "default_alert_evaluate_interval_secs")]
+    pub evaluate_interval_secs: u64,
+    /// 告警规则
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<AlertRule>,
+    /// 告警接收 Webhook
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<AlertWebhook>,
+}
+
+fn default_alert_evaluate_interval_secs() -> u64 {
+    15
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            evaluate_interval_secs: default_alert_evaluate_interval_secs(),
+            rules: Vec::new(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+/// 告警规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertRule {
+    /// 规则名称（唯一）
+    pub name: String,
+    /// Flow 过滤表达式（如 `~p kiro`），省略时统计所有 Flow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_expr: Option<String>,
+    /// 统计指标
+    pub metric: AlertMetric,
+    /// 比较方式
+    #[serde(default)]
+    pub operator: AlertOperator,
+    /// 阈值（错误率为百分比，延迟为毫秒）
+    pub threshold: f64,
+    /// 统计窗口（秒）
+    #[serde(default = "default_alert_window_secs")]
+    pub window_secs: u64,
+    /// 窗口内请求数少于该值时不评估错误率和延迟类指标
+    #[serde(default = "default_alert_min_samples")]
+    pub min_samples: usize,
+    /// 接收告警的 Webhook 名称，为空时发送到全部 Webhook
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<String>,
+}
+
+fn default_alert_window_secs() -> u64 {
+    300
+}
+
+fn default_alert_min_samples() -> usize {
+    5
+}
+
+/// 告警统计指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// 错误率（百分比，失败或状态码 >= 400 的请求占比）
+    ErrorRate,
+    /// 错误请求数
+    ErrorCount,
+    /// 请求数
+    RequestCount,
+    /// 平均延迟（毫秒）
+    AvgLatencyMs,
+    /// P50 延迟（毫秒）
+    P50LatencyMs,
+    /// P95 延迟（毫秒）
+    P95LatencyMs,
+    /// P99 延迟（毫秒）
+    P99LatencyMs,
+    /// Token 总用量
+    TotalTokens,
+}
+
+/// 告警比较方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertOperator {
+    /// 大于
+    #[default]
+    Gt,
+    /// 大于等于
+    Gte,
+    /// 小于
+    Lt,
+    /// 小于等于
+    Lte,
+}
+
+/// 告警 Webhook
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertWebhook {
+    /// Webhook 名称，供规则引用
+    pub name: String,
+    /// 接收地址
+    pub url: String,
+    /// 消息格式
+    #[serde(default)]
+    pub format: AlertWebhookFormat,
+    /// 附加请求头（如认证 Token）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+/// 告警 Webhook 消息格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertWebhookFormat {
+    /// 通用 JSON（告警字段加 `text` 摘要）
+    #[default]
+    Json,
+    /// Slack Incoming Webhook 兼容格式
+    Slack,
+}
+
 /// 配额超限配置
 ///
 /// 用于配置配额超限时的自动切换策略
@@ -763,6 +1452,12 @@ pub struct RoutingConfig {
     /// 模型别名映射
     #[serde(default)]
     pub model_aliases: HashMap<String, String>,
+    /// 路由规则（按顺序匹配，首个命中的规则生效）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RoutingRuleConfig>,
+    /// 跨 Provider 模型等价组（请求的 Provider 无可用凭证时按组内顺序降级）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub equivalence_groups: Vec<ModelEquivalenceGroup>,
 }
 
 fn default_provider() -> String {
@@ -774,10 +1469,86 @@ impl Default for RoutingConfig {
         Self {
             default_provider: default_provider(),
             model_aliases: HashMap::new(),
+            rules: Vec::new(),
+            equivalence_groups: Vec::new(),
         }
     }
 }
 
+/// 路由规则配置
+///
+/// `when` 中的所有条件都满足时命中，未设置的条件不参与匹配
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoutingRuleConfig {
+    /// 规则名称（用于日志和 dry-run 说明）
+    pub name: String,
+    /// 是否启用
+    #[serde(default = "default_routing_rule_enabled")]
+    pub enabled: bool,
+    /// 匹配条件
+    #[serde(default)]
+    pub when: RoutingRuleConditions,
+    /// 目标 Provider（Provider 类型或自定义 Provider ID）
+    pub provider: String,
+    /// 改写后的模型名（不设置则保持原模型）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+fn default_routing_rule_enabled() -> bool {
+    true
+}
+
+/// 路由规则匹配条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoutingRuleConditions {
+    /// 模型名通配符（任一匹配即可，支持 `*` 和 `?`）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// 客户端类型（cursor / claude_code / codex / windsurf / kiro / other）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub client_types: Vec<String>,
+    /// 是否携带工具定义
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_tools: Option<bool>,
+    /// 是否包含图片
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_images: Option<bool>,
+    /// 是否启用 thinking / reasoning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<bool>,
+    /// 估算输入 Token 下限（包含）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_input_tokens: Option<u32>,
+    /// 估算输入 Token 上限（包含）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<u32>,
+    /// 请求头匹配（头名不区分大小写，值支持通配符）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+/// 模型等价组
+///
+/// 组内成员是由不同 Provider 提供的同一模型，按顺序作为降级候选
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelEquivalenceGroup {
+    /// 组名称（用于日志）
+    pub name: String,
+    /// 组成员（按优先级排序）
+    #[serde(default)]
+    pub members: Vec<EquivalentModel>,
+}
+
+/// 模型等价组成员
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EquivalentModel {
+    /// Provider 类型（如 kiro、claude、anthropic、antigravity）
+    pub provider: String,
+    /// 该 Provider 上的模型名
+    pub model: String,
+}
+
 /// 重试配置
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RetrySettings {
@@ -793,6 +1564,12 @@ pub struct RetrySettings {
     /// 是否自动切换 Provider
     #[serde(default = "default_auto_switch")]
     pub auto_switch_provider: bool,
+    /// 流式响应中途断开时是否切换凭证续传
+    #[serde(default = "default_stream_failover")]
+    pub stream_failover: bool,
+    /// 单个流式响应最多续传次数
+    #[serde(default = "default_max_stream_continuations")]
+    pub max_stream_continuations: u32,
 }
 
 fn default_max_retries() -> u32 {
@@ -811,6 +1588,14 @@ fn default_auto_switch() -> bool {
     true
 }
 
+fn default_stream_failover() -> bool {
+    true
+}
+
+fn default_max_stream_continuations() -> u32 {
+    2
+}
+
 impl Default for RetrySettings {
     fn default() -> Self {
         Self {
@@ -818,6 +1603,8 @@ impl Default for RetrySettings {
             base_delay_ms: default_base_delay_ms(),
             max_delay_ms: default_max_delay_ms(),
             auto_switch_provider: default_auto_switch(),
+            stream_failover: default_stream_failover(),
+            max_stream_continuations: default_max_stream_continuations(),
         }
     }
 }
@@ -1266,6 +2053,16 @@ impl Default for Config {
             models: ModelsConfig::default(),
             agent: NativeAgentConfig::default(),
             experimental: ExperimentalFeatures::default(),
+            token_counting: TokenCountingConfig::default(),
+            otel: OtelConfig::default(),
+            response_cache: ResponseCacheConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            hedging: HedgingConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            budget: BudgetConfig::default(),
+            token_refresh: TokenRefreshConfig::default(),
+            canary: CanaryConfig::default(),
+            alerts: AlertConfig::default(),
         }
     }
 }
@@ -1378,6 +2175,7 @@ mod unit_tests {
             vertex_api_keys: vec![],
             codex: vec![],
             iflow: vec![],
+            strategies: HashMap::new(),
         };
 
         let yaml = serde_yaml::to_string(&pool).unwrap();
@@ -1,7 +1,9 @@
 pub mod anthropic_to_openai;
 pub mod cw_to_openai;
+pub mod openai_responses;
 pub mod openai_to_antigravity;
 pub mod openai_to_cw;
+pub mod openai_to_gemini_embeddings;
 pub mod protocol_selector;
 
 #[allow(unused_imports)]
@@ -9,8 +11,12 @@ pub use anthropic_to_openai::*;
 #[allow(unused_imports)]
 pub use cw_to_openai::*;
 #[allow(unused_imports)]
+pub use openai_responses::*;
+#[allow(unused_imports)]
 pub use openai_to_antigravity::*;
 #[allow(unused_imports)]
 pub use openai_to_cw::*;
 #[allow(unused_imports)]
+pub use openai_to_gemini_embeddings::*;
+#[allow(unused_imports)]
 pub use protocol_selector::*;
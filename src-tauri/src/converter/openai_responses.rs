@@ -0,0 +1,1065 @@
+//! OpenAI Responses API 与 Chat Completions 格式互转
+//!
+//! `/v1/responses` 请求先转换为 `ChatCompletionRequest`，复用 chat completions 的
+//! 全部路由与凭证池逻辑（Kiro、Antigravity、Claude、Gemini 等后端），
+//! 再将 chat completions 响应（JSON 或 SSE chunk）转换回 Responses 格式。
+//!
+//! 流式响应输出的语义事件：
+//! - `response.created` / `response.in_progress` / `response.completed`
+//! - `response.output_item.added` / `response.output_item.done`
+//! - `response.output_text.delta` / `response.output_text.done`
+//! - `response.reasoning_summary_text.delta` / `response.reasoning_summary_text.done`
+//! - `response.function_call_arguments.delta` / `response.function_call_arguments.done`
+
+use crate::models::openai::*;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// 将 Responses API 请求转换为 OpenAI ChatCompletionRequest
+pub fn convert_responses_to_openai(request: &Value) -> Result<ChatCompletionRequest, String> {
+    let model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .ok_or_else(|| "Missing required field: model".to_string())?
+        .to_string();
+
+    let mut messages: Vec<ChatMessage> = Vec::new();
+
+    // instructions 作为 system 消息
+    if let Some(instructions) = request.get("instructions").and_then(|i| i.as_str()) {
+        if !instructions.is_empty() {
+            messages.push(text_message("system", instructions.to_string()));
+        }
+    }
+
+    match request.get("input") {
+        Some(Value::String(s)) => messages.push(text_message("user", s.clone())),
+        Some(Value::Array(items)) => {
+            for item in items {
+                convert_input_item(item, &mut messages);
+            }
+        }
+        Some(Value::Null) | None => {}
+        Some(_) => return Err("Invalid field: input must be a string or an array".to_string()),
+    }
+
+    let tools: Vec<Tool> = request
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .map(|tools| tools.iter().filter_map(convert_tool).collect())
+        .unwrap_or_default();
+
+    let tool_choice = request.get("tool_choice").map(|choice| match choice {
+        Value::Object(obj) if obj.get("type").and_then(|t| t.as_str()) == Some("function") => {
+            json!({
+                "type": "function",
+                "function": { "name": obj.get("name").cloned().unwrap_or(Value::Null) }
+            })
+        }
+        other => other.clone(),
+    });
+
+    Ok(ChatCompletionRequest {
+        model,
+        messages,
+        temperature: request
+            .get("temperature")
+            .and_then(|t| t.as_f64())
+            .map(|t| t as f32),
+        max_tokens: request
+            .get("max_output_tokens")
+            .and_then(|t| t.as_u64())
+            .map(|t| t as u32),
+        top_p: request
+            .get("top_p")
+            .and_then(|t| t.as_f64())
+            .map(|t| t as f32),
+        stream: request
+            .get("stream")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false),
+        tools: if tools.is_empty() { None } else { Some(tools) },
+        tool_choice,
+        reasoning_effort: request
+            .get("reasoning")
+            .and_then(|r| r.get("effort"))
+            .and_then(|e| e.as_str())
+            .map(|e| e.to_string()),
+    })
+}
+
+fn text_message(role: &str, text: String) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: Some(MessageContent::Text(text)),
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+/// 转换单个 input item，连续的 function_call 合并到同一条 assistant 消息
+fn convert_input_item(item: &Value, messages: &mut Vec<ChatMessage>) {
+    let item_type = item
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("message");
+
+    match item_type {
+        "message" => {
+            let role = match item.get("role").and_then(|r| r.as_str()).unwrap_or("user") {
+                "developer" => "system",
+                other => other,
+            };
+            let content = match item.get("content") {
+                Some(Value::String(s)) => Some(MessageContent::Text(s.clone())),
+                Some(Value::Array(parts)) => {
+                    let parts: Vec<ContentPart> =
+                        parts.iter().filter_map(convert_content_part).collect();
+                    match parts.as_slice() {
+                        [] => None,
+                        [ContentPart::Text { text }] => Some(MessageContent::Text(text.clone())),
+                        _ => Some(MessageContent::Parts(parts)),
+                    }
+                }
+                _ => None,
+            };
+            messages.push(ChatMessage {
+                role: role.to_string(),
+                content,
+                tool_calls: None,
+                tool_call_id: None,
+            });
+        }
+        "function_call" => {
+            let call = ToolCall {
+                id: item
+                    .get("call_id")
+                    .or_else(|| item.get("id"))
+                    .and_then(|i| i.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| {
+                        format!("call_{}", &Uuid::new_v4().simple().to_string()[..24])
+                    }),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: item
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    arguments: item
+                        .get("arguments")
+                        .and_then(|a| a.as_str())
+                        .unwrap_or("{}")
+                        .to_string(),
+                },
+            };
+
+            match messages.last_mut() {
+                Some(last) if last.role == "assistant" && last.tool_calls.is_some() => {
+                    if let Some(calls) = last.tool_calls.as_mut() {
+                        calls.push(call);
+                    }
+                }
+                _ => messages.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: None,
+                    tool_calls: Some(vec![call]),
+                    tool_call_id: None,
+                }),
+            }
+        }
+        "function_call_output" => {
+            let output = match item.get("output") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            messages.push(ChatMessage {
+                role: "tool".to_string(),
+                content: Some(MessageContent::Text(output)),
+                tool_calls: None,
+                tool_call_id: item
+                    .get("call_id")
+                    .and_then(|i| i.as_str())
+                    .map(|s| s.to_string()),
+            });
+        }
+        // reasoning 等 item 由上游重新生成，不回传
+        _ => {}
+    }
+}
+
+fn convert_content_part(part: &Value) -> Option<ContentPart> {
+    match part.get("type").and_then(|t| t.as_str())? {
+        "input_text" | "output_text" | "text" => Some(ContentPart::Text {
+            text: part.get("text")?.as_str()?.to_string(),
+        }),
+        "input_image" => {
+            let url = part
+                .get("image_url")
+                .and_then(|u| u.as_str().or_else(|| u.get("url").and_then(|u| u.as_str())))?;
+            Some(ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: url.to_string(),
+                    detail: part
+                        .get("detail")
+                        .and_then(|d| d.as_str())
+                        .map(|d| d.to_string()),
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+fn convert_tool(tool: &Value) -> Option<Tool> {
+    match tool.get("type").and_then(|t| t.as_str())? {
+        "function" => Some(Tool::Function {
+            function: FunctionDef {
+                name: tool.get("name")?.as_str()?.to_string(),
+                description: tool
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .map(|d| d.to_string()),
+                parameters: tool.get("parameters").cloned(),
+            },
+        }),
+        "web_search" | "web_search_preview" => Some(Tool::WebSearch),
+        _ => None,
+    }
+}
+
+/// 生成 Responses API 风格的 ID
+fn new_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, Uuid::new_v4().simple())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// 将 chat completions 的 usage 转换为 Responses usage
+fn convert_usage(usage: &Value) -> Value {
+    let input = usage["prompt_tokens"].as_u64().unwrap_or(0);
+    let output = usage["completion_tokens"].as_u64().unwrap_or(0);
+    json!({
+        "input_tokens": input,
+        "input_tokens_details": {
+            "cached_tokens": usage["prompt_tokens_details"]["cached_tokens"].as_u64().unwrap_or(0)
+        },
+        "output_tokens": output,
+        "output_tokens_details": {
+            "reasoning_tokens": usage["completion_tokens_details"]["reasoning_tokens"].as_u64().unwrap_or(0)
+        },
+        "total_tokens": usage["total_tokens"].as_u64().unwrap_or(input + output)
+    })
+}
+
+/// 根据 finish_reason 计算 Responses 状态
+fn status_for_finish_reason(finish_reason: Option<&str>) -> (&'static str, Value) {
+    match finish_reason {
+        Some("length") => ("incomplete", json!({ "reason": "max_output_tokens" })),
+        Some("content_filter") => ("incomplete", json!({ "reason": "content_filter" })),
+        _ => ("completed", Value::Null),
+    }
+}
+
+fn build_response_object(
+    id: &str,
+    model: &str,
+    created_at: u64,
+    status: &str,
+    incomplete_details: Value,
+    output: Vec<Value>,
+    usage: Value,
+) -> Value {
+    json!({
+        "id": id,
+        "object": "response",
+        "created_at": created_at,
+        "status": status,
+        "model": model,
+        "output": output,
+        "incomplete_details": incomplete_details,
+        "parallel_tool_calls": true,
+        "usage": usage,
+    })
+}
+
+fn message_item(id: &str, text: &str, status: &str) -> Value {
+    json!({
+        "id": id,
+        "type": "message",
+        "status": status,
+        "role": "assistant",
+        "content": if status == "in_progress" {
+            json!([])
+        } else {
+            json!([{ "type": "output_text", "text": text, "annotations": [] }])
+        }
+    })
+}
+
+fn reasoning_item(id: &str, text: &str, done: bool) -> Value {
+    json!({
+        "id": id,
+        "type": "reasoning",
+        "summary": if done {
+            json!([{ "type": "summary_text", "text": text }])
+        } else {
+            json!([])
+        }
+    })
+}
+
+fn function_call_item(id: &str, call_id: &str, name: &str, arguments: &str, status: &str) -> Value {
+    json!({
+        "id": id,
+        "type": "function_call",
+        "status": status,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments
+    })
+}
+
+/// 将 chat completions 非流式响应转换为 Responses 响应对象
+pub fn convert_openai_to_responses(chat_response: &Value, model: &str) -> Value {
+    let choice = &chat_response["choices"][0];
+    let message = &choice["message"];
+    let mut output = Vec::new();
+
+    if let Some(reasoning) = message["reasoning_content"].as_str() {
+        if !reasoning.is_empty() {
+            output.push(reasoning_item(&new_id("rs"), reasoning, true));
+        }
+    }
+    if let Some(text) = message["content"].as_str() {
+        if !text.is_empty() {
+            output.push(message_item(&new_id("msg"), text, "completed"));
+        }
+    }
+    if let Some(tool_calls) = message["tool_calls"].as_array() {
+        for call in tool_calls {
+            output.push(function_call_item(
+                &new_id("fc"),
+                call["id"].as_str().unwrap_or_default(),
+                call["function"]["name"].as_str().unwrap_or_default(),
+                call["function"]["arguments"].as_str().unwrap_or("{}"),
+                "completed",
+            ));
+        }
+    }
+
+    let (status, incomplete_details) = status_for_finish_reason(choice["finish_reason"].as_str());
+    let model = chat_response["model"].as_str().unwrap_or(model);
+
+    build_response_object(
+        &new_id("resp"),
+        model,
+        chat_response["created"].as_u64().unwrap_or_else(now_secs),
+        status,
+        incomplete_details,
+        output,
+        convert_usage(&chat_response["usage"]),
+    )
+}
+
+/// 将 chat completions 非流式响应改写为单个 chunk，便于流式转换器统一处理
+pub fn chat_completion_to_chunk(chat_response: &Value) -> Value {
+    let choice = &chat_response["choices"][0];
+    let message = &choice["message"];
+    let tool_calls: Vec<Value> = message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(i, call)| {
+                    let mut call = call.clone();
+                    call["index"] = json!(i);
+                    call
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut delta = json!({ "role": "assistant" });
+    if let Some(text) = message["content"].as_str() {
+        delta["content"] = json!(text);
+    }
+    if let Some(reasoning) = message["reasoning_content"].as_str() {
+        delta["reasoning_content"] = json!(reasoning);
+    }
+    if !tool_calls.is_empty() {
+        delta["tool_calls"] = json!(tool_calls);
+    }
+
+    json!({
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": choice["finish_reason"] }],
+        "usage": chat_response["usage"]
+    })
+}
+
+/// 正在输出的文本/推理 item
+struct OpenItem {
+    id: String,
+    output_index: usize,
+    text: String,
+}
+
+/// 正在输出的 function_call item
+struct OpenToolCall {
+    id: String,
+    output_index: usize,
+    call_id: String,
+    name: String,
+    arguments: String,
+}
+
+/// chat completions SSE chunk → Responses 语义事件的流式转换器
+pub struct ResponsesStreamConverter {
+    response_id: String,
+    model: String,
+    created_at: u64,
+    sequence_number: u64,
+    next_output_index: usize,
+    message: Option<OpenItem>,
+    reasoning: Option<OpenItem>,
+    tool_calls: BTreeMap<u64, OpenToolCall>,
+    /// 已完成的 output item（按 output_index 排序后写入 response.completed）
+    completed_items: Vec<(usize, Value)>,
+    usage: Value,
+    finish_reason: Option<String>,
+    started: bool,
+    finished: bool,
+}
+
+impl ResponsesStreamConverter {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            response_id: new_id("resp"),
+            model: model.into(),
+            created_at: now_secs(),
+            sequence_number: 0,
+            next_output_index: 0,
+            message: None,
+            reasoning: None,
+            tool_calls: BTreeMap::new(),
+            completed_items: Vec::new(),
+            usage: Value::Null,
+            finish_reason: None,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// 响应 ID
+    pub fn response_id(&self) -> &str {
+        &self.response_id
+    }
+
+    fn event(&mut self, event_type: &str, mut data: Value) -> String {
+        data["type"] = json!(event_type);
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        format!("event: {}\ndata: {}\n\n", event_type, data)
+    }
+
+    fn snapshot(&self, status: &str, incomplete_details: Value, usage: Value) -> Value {
+        let mut items = self.completed_items.clone();
+        items.sort_by_key(|(index, _)| *index);
+        build_response_object(
+            &self.response_id,
+            &self.model,
+            self.created_at,
+            status,
+            incomplete_details,
+            items.into_iter().map(|(_, item)| item).collect(),
+            usage,
+        )
+    }
+
+    /// 生成流开始事件（response.created / response.in_progress）
+    pub fn start(&mut self) -> Vec<String> {
+        if self.started {
+            return Vec::new();
+        }
+        self.started = true;
+        let response = self.snapshot("in_progress", Value::Null, Value::Null);
+        vec![
+            self.event("response.created", json!({ "response": response.clone() })),
+            self.event("response.in_progress", json!({ "response": response })),
+        ]
+    }
+
+    /// 处理单个 chat completions chunk
+    pub fn process_chunk(&mut self, chunk: &Value) -> Vec<String> {
+        let mut events = self.start();
+
+        if let Some(model) = chunk["model"].as_str() {
+            if !model.is_empty() {
+                self.model = model.to_string();
+            }
+        }
+        if chunk["usage"].is_object() {
+            self.usage = chunk["usage"].clone();
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+
+        if let Some(reasoning) = delta["reasoning_content"].as_str() {
+            if !reasoning.is_empty() {
+                events.extend(self.close_message());
+                events.extend(self.reasoning_delta(reasoning));
+            }
+        }
+
+        if let Some(text) = delta["content"].as_str() {
+            if !text.is_empty() {
+                events.extend(self.close_reasoning());
+                events.extend(self.text_delta(text));
+            }
+        }
+
+        if let Some(calls) = delta["tool_calls"].as_array() {
+            events.extend(self.close_reasoning());
+            events.extend(self.close_message());
+            for (position, call) in calls.iter().enumerate() {
+                let index = call["index"].as_u64().unwrap_or(position as u64);
+                events.extend(self.tool_call_delta(index, call));
+            }
+        }
+
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.finish_reason = Some(reason.to_string());
+        }
+
+        events
+    }
+
+    fn reasoning_delta(&mut self, delta: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        if self.reasoning.is_none() {
+            let item = OpenItem {
+                id: new_id("rs"),
+                output_index: self.next_output_index,
+                text: String::new(),
+            };
+            self.next_output_index += 1;
+            events.push(self.event(
+                "response.output_item.added",
+                json!({ "output_index": item.output_index, "item": reasoning_item(&item.id, "", false) }),
+            ));
+            events.push(self.event(
+                "response.reasoning_summary_part.added",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "summary_index": 0,
+                    "part": { "type": "summary_text", "text": "" }
+                }),
+            ));
+            self.reasoning = Some(item);
+        }
+
+        let (item_id, output_index) = match self.reasoning.as_mut() {
+            Some(item) => {
+                item.text.push_str(delta);
+                (item.id.clone(), item.output_index)
+            }
+            None => return events,
+        };
+        events.push(self.event(
+            "response.reasoning_summary_text.delta",
+            json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "summary_index": 0,
+                "delta": delta
+            }),
+        ));
+        events
+    }
+
+    fn close_reasoning(&mut self) -> Vec<String> {
+        let Some(item) = self.reasoning.take() else {
+            return Vec::new();
+        };
+        let done = reasoning_item(&item.id, &item.text, true);
+        let events = vec![
+            self.event(
+                "response.reasoning_summary_text.done",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "summary_index": 0,
+                    "text": item.text
+                }),
+            ),
+            self.event(
+                "response.reasoning_summary_part.done",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "summary_index": 0,
+                    "part": { "type": "summary_text", "text": item.text }
+                }),
+            ),
+            self.event(
+                "response.output_item.done",
+                json!({ "output_index": item.output_index, "item": done.clone() }),
+            ),
+        ];
+        self.completed_items.push((item.output_index, done));
+        events
+    }
+
+    fn text_delta(&mut self, delta: &str) -> Vec<String> {
+        let mut events = Vec::new();
+        if self.message.is_none() {
+            let item = OpenItem {
+                id: new_id("msg"),
+                output_index: self.next_output_index,
+                text: String::new(),
+            };
+            self.next_output_index += 1;
+            events.push(self.event(
+                "response.output_item.added",
+                json!({ "output_index": item.output_index, "item": message_item(&item.id, "", "in_progress") }),
+            ));
+            events.push(self.event(
+                "response.content_part.added",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "content_index": 0,
+                    "part": { "type": "output_text", "text": "", "annotations": [] }
+                }),
+            ));
+            self.message = Some(item);
+        }
+
+        let (item_id, output_index) = match self.message.as_mut() {
+            Some(item) => {
+                item.text.push_str(delta);
+                (item.id.clone(), item.output_index)
+            }
+            None => return events,
+        };
+        events.push(self.event(
+            "response.output_text.delta",
+            json!({
+                "item_id": item_id,
+                "output_index": output_index,
+                "content_index": 0,
+                "delta": delta
+            }),
+        ));
+        events
+    }
+
+    fn close_message(&mut self) -> Vec<String> {
+        let Some(item) = self.message.take() else {
+            return Vec::new();
+        };
+        let done = message_item(&item.id, &item.text, "completed");
+        let events = vec![
+            self.event(
+                "response.output_text.done",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "content_index": 0,
+                    "text": item.text
+                }),
+            ),
+            self.event(
+                "response.content_part.done",
+                json!({
+                    "item_id": item.id,
+                    "output_index": item.output_index,
+                    "content_index": 0,
+                    "part": { "type": "output_text", "text": item.text, "annotations": [] }
+                }),
+            ),
+            self.event(
+                "response.output_item.done",
+                json!({ "output_index": item.output_index, "item": done.clone() }),
+            ),
+        ];
+        self.completed_items.push((item.output_index, done));
+        events
+    }
+
+    fn tool_call_delta(&mut self, index: u64, call: &Value) -> Vec<String> {
+        let mut events = Vec::new();
+        if !self.tool_calls.contains_key(&index) {
+            let open = OpenToolCall {
+                id: new_id("fc"),
+                output_index: self.next_output_index,
+                call_id: call["id"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| new_id("call")),
+                name: call["function"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                arguments: String::new(),
+            };
+            self.next_output_index += 1;
+            let item = function_call_item(&open.id, &open.call_id, &open.name, "", "in_progress");
+            events.push(self.event(
+                "response.output_item.added",
+                json!({ "output_index": open.output_index, "item": item }),
+            ));
+            self.tool_calls.insert(index, open);
+        }
+
+        let arguments = call["function"]["arguments"].as_str().unwrap_or_default();
+        let (item_id, output_index) = match self.tool_calls.get_mut(&index) {
+            Some(open) => {
+                if open.name.is_empty() {
+                    if let Some(name) = call["function"]["name"].as_str() {
+                        open.name = name.to_string();
+                    }
+                }
+                open.arguments.push_str(arguments);
+                (open.id.clone(), open.output_index)
+            }
+            None => return events,
+        };
+        if !arguments.is_empty() {
+            events.push(self.event(
+                "response.function_call_arguments.delta",
+                json!({ "item_id": item_id, "output_index": output_index, "delta": arguments }),
+            ));
+        }
+        events
+    }
+
+    fn close_tool_calls(&mut self) -> Vec<String> {
+        let mut events = Vec::new();
+        let calls = std::mem::take(&mut self.tool_calls);
+        for (_, open) in calls {
+            let arguments = if open.arguments.is_empty() {
+                "{}".to_string()
+            } else {
+                open.arguments.clone()
+            };
+            let done =
+                function_call_item(&open.id, &open.call_id, &open.name, &arguments, "completed");
+            events.push(self.event(
+                "response.function_call_arguments.done",
+                json!({ "item_id": open.id, "output_index": open.output_index, "arguments": arguments }),
+            ));
+            events.push(self.event(
+                "response.output_item.done",
+                json!({ "output_index": open.output_index, "item": done.clone() }),
+            ));
+            self.completed_items.push((open.output_index, done));
+        }
+        events
+    }
+
+    /// 结束流：关闭所有未完成的 item 并发送 response.completed
+    pub fn finish(&mut self) -> Vec<String> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+
+        let mut events = self.start();
+        events.extend(self.close_reasoning());
+        events.extend(self.close_message());
+        events.extend(self.close_tool_calls());
+
+        let (status, incomplete_details) = status_for_finish_reason(self.finish_reason.as_deref());
+        let usage = if self.usage.is_null() {
+            Value::Null
+        } else {
+            convert_usage(&self.usage)
+        };
+        let response = self.snapshot(status, incomplete_details, usage);
+        let event_type = if status == "completed" {
+            "response.completed"
+        } else {
+            "response.incomplete"
+        };
+        events.push(self.event(event_type, json!({ "response": response })));
+        events
+    }
+
+    /// 流中出错时发送 response.failed
+    pub fn fail(&mut self, message: &str) -> Vec<String> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+        let mut events = self.start();
+        let mut response = self.snapshot("failed", Value::Null, Value::Null);
+        response["error"] = json!({ "code": "server_error", "message": message });
+        events.push(self.event("response.failed", json!({ "response": response })));
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_events(events: &[String]) -> Vec<Value> {
+        events
+            .iter()
+            .map(|e| {
+                let data = e.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+                serde_json::from_str(data).unwrap()
+            })
+            .collect()
+    }
+
+    fn event_types(events: &[String]) -> Vec<String> {
+        parse_events(events)
+            .iter()
+            .map(|e| e["type"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_convert_request_string_input() {
+        let request = json!({
+            "model": "gpt-5",
+            "instructions": "Be concise.",
+            "input": "Hello",
+            "max_output_tokens": 256,
+            "reasoning": {"effort": "high"},
+            "stream": true
+        });
+        let chat = convert_responses_to_openai(&request).unwrap();
+
+        assert_eq!(chat.model, "gpt-5");
+        assert_eq!(chat.messages.len(), 2);
+        assert_eq!(chat.messages[0].role, "system");
+        assert_eq!(chat.messages[1].get_content_text(), "Hello");
+        assert_eq!(chat.max_tokens, Some(256));
+        assert_eq!(chat.reasoning_effort.as_deref(), Some("high"));
+        assert!(chat.stream);
+    }
+
+    #[test]
+    fn test_convert_request_items_and_tools() {
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "input": [
+                {"type": "message", "role": "developer", "content": [{"type": "input_text", "text": "rules"}]},
+                {"role": "user", "content": [
+                    {"type": "input_text", "text": "look"},
+                    {"type": "input_image", "image_url": "data:image/png;base64,AAAA"}
+                ]},
+                {"type": "reasoning", "summary": []},
+                {"type": "function_call", "call_id": "call_1", "name": "ls", "arguments": "{}"},
+                {"type": "function_call", "call_id": "call_2", "name": "cat", "arguments": "{\"p\":1}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "a.txt"}
+            ],
+            "tools": [
+                {"type": "function", "name": "ls", "description": "list", "parameters": {"type": "object"}},
+                {"type": "web_search_preview"},
+                {"type": "file_search"}
+            ],
+            "tool_choice": {"type": "function", "name": "ls"}
+        });
+        let chat = convert_responses_to_openai(&request).unwrap();
+
+        assert_eq!(chat.messages.len(), 4);
+        assert_eq!(chat.messages[0].role, "system");
+        assert!(matches!(
+            chat.messages[1].content,
+            Some(MessageContent::Parts(ref parts)) if parts.len() == 2
+        ));
+        let calls = chat.messages[2].tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].function.name, "cat");
+        assert_eq!(chat.messages[3].role, "tool");
+        assert_eq!(chat.messages[3].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(chat.tools.as_ref().unwrap().len(), 2);
+        assert_eq!(chat.tool_choice.unwrap()["function"]["name"], "ls");
+    }
+
+    #[test]
+    fn test_convert_request_requires_model() {
+        assert!(convert_responses_to_openai(&json!({"input": "hi"})).is_err());
+        assert!(convert_responses_to_openai(&json!({"model": "m", "input": 1})).is_err());
+    }
+
+    #[test]
+    fn test_convert_non_stream_response() {
+        let chat = json!({
+            "id": "chatcmpl-1",
+            "created": 1700000000,
+            "model": "gpt-5",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "Hi there",
+                    "reasoning_content": "thinking",
+                    "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "ls", "arguments": "{}"}}]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        });
+        let resp = convert_openai_to_responses(&chat, "fallback");
+
+        assert_eq!(resp["object"], "response");
+        assert_eq!(resp["status"], "completed");
+        assert_eq!(resp["model"], "gpt-5");
+        let output = resp["output"].as_array().unwrap();
+        assert_eq!(output[0]["type"], "reasoning");
+        assert_eq!(output[1]["content"][0]["text"], "Hi there");
+        assert_eq!(output[2]["call_id"], "call_1");
+        assert_eq!(resp["usage"]["input_tokens"], 10);
+        assert_eq!(resp["usage"]["total_tokens"], 15);
+    }
+
+    #[test]
+    fn test_non_stream_length_is_incomplete() {
+        let chat = json!({
+            "choices": [{"message": {"content": "cut"}, "finish_reason": "length"}],
+            "usage": {}
+        });
+        let resp = convert_openai_to_responses(&chat, "m");
+        assert_eq!(resp["status"], "incomplete");
+        assert_eq!(resp["incomplete_details"]["reason"], "max_output_tokens");
+    }
+
+    #[test]
+    fn test_stream_text_events() {
+        let mut converter = ResponsesStreamConverter::new("gpt-5");
+        let mut events = Vec::new();
+        events
+            .extend(converter.process_chunk(&json!({"choices": [{"delta": {"content": "Hel"}}]})));
+        events.extend(converter.process_chunk(&json!({"choices": [{"delta": {"content": "lo"}}]})));
+        events.extend(converter.process_chunk(&json!({
+            "choices": [{"delta": {}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5}
+        })));
+        events.extend(converter.finish());
+
+        assert_eq!(
+            event_types(&events),
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+
+        let parsed = parse_events(&events);
+        let sequence: Vec<u64> = parsed
+            .iter()
+            .map(|e| e["sequence_number"].as_u64().unwrap())
+            .collect();
+        assert_eq!(sequence, (0..parsed.len() as u64).collect::<Vec<_>>());
+
+        let completed = parsed.last().unwrap();
+        assert_eq!(
+            completed["response"]["output"][0]["content"][0]["text"],
+            "Hello"
+        );
+        assert_eq!(completed["response"]["usage"]["output_tokens"], 2);
+        assert!(events[0].starts_with("event: response.created\n"));
+    }
+
+    #[test]
+    fn test_stream_reasoning_then_tool_call() {
+        let mut converter = ResponsesStreamConverter::new("m");
+        let mut events = Vec::new();
+        events.extend(
+            converter
+                .process_chunk(&json!({"choices": [{"delta": {"reasoning_content": "plan"}}]})),
+        );
+        events.extend(converter.process_chunk(&json!({"choices": [{"delta": {"tool_calls": [
+            {"index": 0, "id": "call_9", "type": "function", "function": {"name": "ls", "arguments": ""}}
+        ]}}]})));
+        events.extend(
+            converter.process_chunk(&json!({"choices": [{"delta": {"tool_calls": [
+                {"index": 0, "function": {"arguments": "{\"a\":"}}
+            ]}}]})),
+        );
+        events.extend(
+            converter.process_chunk(&json!({"choices": [{"delta": {"tool_calls": [
+            {"index": 0, "function": {"arguments": "1}"}}
+        ]}, "finish_reason": "tool_calls"}]})),
+        );
+        events.extend(converter.finish());
+
+        let types = event_types(&events);
+        assert!(types.contains(&"response.reasoning_summary_text.delta".to_string()));
+        assert!(types.contains(&"response.function_call_arguments.done".to_string()));
+
+        let parsed = parse_events(&events);
+        let output = parsed.last().unwrap()["response"]["output"]
+            .as_array()
+            .unwrap()
+            .clone();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[0]["type"], "reasoning");
+        assert_eq!(output[0]["summary"][0]["text"], "plan");
+        assert_eq!(output[1]["type"], "function_call");
+        assert_eq!(output[1]["call_id"], "call_9");
+        assert_eq!(output[1]["arguments"], "{\"a\":1}");
+    }
+
+    #[test]
+    fn test_chat_completion_to_chunk_roundtrip() {
+        let chat = json!({
+            "choices": [{
+                "message": {"content": "ok", "tool_calls": [
+                    {"id": "c1", "type": "function", "function": {"name": "f", "arguments": "{}"}}
+                ]},
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+        });
+        let mut converter = ResponsesStreamConverter::new("m");
+        let mut events = converter.process_chunk(&chat_completion_to_chunk(&chat));
+        events.extend(converter.finish());
+
+        let parsed = parse_events(&events);
+        let output = parsed.last().unwrap()["response"]["output"]
+            .as_array()
+            .unwrap()
+            .clone();
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[0]["type"], "message");
+        assert_eq!(output[1]["name"], "f");
+    }
+
+    #[test]
+    fn test_stream_finish_is_idempotent_and_fail() {
+        let mut converter = ResponsesStreamConverter::new("m");
+        let events = converter.fail("boom");
+        let parsed = parse_events(&events);
+        assert_eq!(parsed.last().unwrap()["type"], "response.failed");
+        assert_eq!(
+            parsed.last().unwrap()["response"]["error"]["message"],
+            "boom"
+        );
+        assert!(converter.finish().is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+//! OpenAI Embeddings 与 Gemini embedContent / batchEmbedContents 格式互转
+//!
+//! - 单个输入使用 `embedContent`
+//! - 多个输入使用 `batchEmbedContents`
+//! - `encoding_format = "base64"` 时将 f32 向量按 little-endian 编码为 base64
+
+use crate::models::openai::{
+    EmbeddingData, EmbeddingInput, EmbeddingRequest, EmbeddingResponse, EmbeddingUsage,
+    EmbeddingVector,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
+
+/// Gemini embeddings 调用方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeminiEmbedAction {
+    /// 单个输入：`models/{model}:embedContent`
+    EmbedContent,
+    /// 多个输入：`models/{model}:batchEmbedContents`
+    BatchEmbedContents,
+}
+
+impl GeminiEmbedAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GeminiEmbedAction::EmbedContent => "embedContent",
+            GeminiEmbedAction::BatchEmbedContents => "batchEmbedContents",
+        }
+    }
+}
+
+/// 去掉 `models/` 前缀，得到 Gemini 模型名
+fn gemini_model_name(model: &str) -> &str {
+    model.strip_prefix("models/").unwrap_or(model)
+}
+
+fn gemini_embed_entry(model: &str, text: &str, dimensions: Option<u32>) -> Value {
+    let mut entry = json!({
+        "model": format!("models/{}", gemini_model_name(model)),
+        "content": { "parts": [{ "text": text }] }
+    });
+    if let Some(dim) = dimensions {
+        entry["outputDimensionality"] = json!(dim);
+    }
+    entry
+}
+
+/// 将 OpenAI embeddings 请求转换为 Gemini 请求体
+pub fn convert_embedding_request_to_gemini(
+    request: &EmbeddingRequest,
+) -> (GeminiEmbedAction, Value) {
+    match &request.input {
+        EmbeddingInput::Single(text) => (
+            GeminiEmbedAction::EmbedContent,
+            gemini_embed_entry(&request.model, text, request.dimensions),
+        ),
+        EmbeddingInput::Multiple(texts) => {
+            let requests: Vec<Value> = texts
+                .iter()
+                .map(|text| gemini_embed_entry(&request.model, text, request.dimensions))
+                .collect();
+            (
+                GeminiEmbedAction::BatchEmbedContents,
+                json!({ "requests": requests }),
+            )
+        }
+    }
+}
+
+/// 将 f32 向量编码为 base64（little-endian），与 OpenAI `encoding_format=base64` 一致
+pub fn encode_embedding_base64(values: &[f32]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    BASE64.encode(bytes)
+}
+
+fn parse_values(embedding: &Value) -> Option<Vec<f32>> {
+    embedding
+        .get("values")?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_f64().map(|f| f as f32))
+        .collect()
+}
+
+/// 将 Gemini embeddings 响应转换为 OpenAI 格式
+///
+/// Gemini 不返回 usage，`prompt_tokens` 由调用方估算后传入。
+pub fn convert_gemini_embeddings_to_openai(
+    response: &Value,
+    model: &str,
+    encoding_format: Option<&str>,
+    prompt_tokens: u32,
+) -> Result<EmbeddingResponse, String> {
+    let embeddings: Vec<Vec<f32>> = if let Some(single) = response.get("embedding") {
+        vec![parse_values(single).ok_or("Invalid Gemini embedding response")?]
+    } else if let Some(list) = response.get("embeddings").and_then(|e| e.as_array()) {
+        list.iter()
+            .map(|e| parse_values(e).ok_or("Invalid Gemini embedding response"))
+            .collect::<Result<_, _>>()?
+    } else {
+        return Err("Gemini response contains no embeddings".to_string());
+    };
+
+    let use_base64 = encoding_format == Some("base64");
+    let data = embeddings
+        .into_iter()
+        .enumerate()
+        .map(|(index, values)| EmbeddingData {
+            object: "embedding".to_string(),
+            embedding: if use_base64 {
+                EmbeddingVector::Base64(encode_embedding_base64(&values))
+            } else {
+                EmbeddingVector::Float(values)
+            },
+            index: index as u32,
+        })
+        .collect();
+
+    Ok(EmbeddingResponse {
+        object: "list".to_string(),
+        data,
+        model: model.to_string(),
+        usage: EmbeddingUsage {
+            prompt_tokens,
+            total_tokens: prompt_tokens,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(input: EmbeddingInput, dimensions: Option<u32>) -> EmbeddingRequest {
+        EmbeddingRequest {
+            model: "gemini-embedding-001".to_string(),
+            input,
+            encoding_format: None,
+            dimensions,
+            user: None,
+        }
+    }
+
+    #[test]
+    fn test_single_input_uses_embed_content() {
+        let (action, body) = convert_embedding_request_to_gemini(&request(
+            EmbeddingInput::Single("hello".to_string()),
+            Some(768),
+        ));
+        assert_eq!(action, GeminiEmbedAction::EmbedContent);
+        assert_eq!(body["model"], "models/gemini-embedding-001");
+        assert_eq!(body["content"]["parts"][0]["text"], "hello");
+        assert_eq!(body["outputDimensionality"], 768);
+    }
+
+    #[test]
+    fn test_multiple_inputs_use_batch() {
+        let (action, body) = convert_embedding_request_to_gemini(&request(
+            EmbeddingInput::Multiple(vec!["a".to_string(), "b".to_string()]),
+            None,
+        ));
+        assert_eq!(action, GeminiEmbedAction::BatchEmbedContents);
+        assert_eq!(action.as_str(), "batchEmbedContents");
+        let requests = body["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "b");
+        assert!(requests[0].get("outputDimensionality").is_none());
+    }
+
+    #[test]
+    fn test_convert_single_response() {
+        let response = json!({"embedding": {"values": [0.5, -1.0]}});
+        let converted =
+            convert_gemini_embeddings_to_openai(&response, "gemini-embedding-001", None, 3)
+                .unwrap();
+        assert_eq!(converted.object, "list");
+        assert_eq!(converted.data.len(), 1);
+        assert!(matches!(
+            converted.data[0].embedding,
+            EmbeddingVector::Float(ref v) if v == &vec![0.5, -1.0]
+        ));
+        assert_eq!(converted.usage.prompt_tokens, 3);
+        assert_eq!(converted.usage.total_tokens, 3);
+    }
+
+    #[test]
+    fn test_convert_batch_response_base64() {
+        let response = json!({"embeddings": [{"values": [1.0]}, {"values": [2.0]}]});
+        let converted =
+            convert_gemini_embeddings_to_openai(&response, "m", Some("base64"), 0).unwrap();
+        assert_eq!(converted.data[1].index, 1);
+        match &converted.data[0].embedding {
+            EmbeddingVector::Base64(s) => {
+                assert_eq!(BASE64.decode(s).unwrap(), 1.0f32.to_le_bytes().to_vec())
+            }
+            other => panic!("expected base64, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_invalid_response() {
+        assert!(convert_gemini_embeddings_to_openai(&json!({}), "m", None, 0).is_err());
+        assert!(convert_gemini_embeddings_to_openai(
+            &json!({"embedding": {"values": ["x"]}}),
+            "m",
+            None,
+            0
+        )
+        .is_err());
+    }
+}
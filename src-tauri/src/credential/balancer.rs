@@ -9,8 +9,10 @@ use crate::proxy::ProxyClientFactory;
 use crate::ProviderType;
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
+use parking_lot::Mutex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -25,6 +27,116 @@ pub enum BalanceStrategy {
     LeastUsed,
     /// 随机策略
     Random,
+    /// 加权策略（平滑加权轮询，按凭证权重分配请求）
+    Weighted,
+    /// 延迟优先策略（选择 EWMA 延迟最低的健康凭证）
+    EwmaLatency,
+    /// 耗尽策略（持续使用同一凭证，直到其冷却或配额耗尽再切换下一个）
+    Drain,
+}
+
+/// 平滑加权轮询状态
+///
+/// 以凭证 ID 为键记录当前权重，候选集合可以在每次选择时变化。
+/// 权重为 0 的候选不会被选中；所有候选权重都为 0 时按等权处理
+#[derive(Debug, Default)]
+pub struct WeightedRoundRobin {
+    current: Mutex<HashMap<String, i64>>,
+}
+
+impl WeightedRoundRobin {
+    /// 创建新的加权轮询状态
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从候选 `(凭证 ID, 权重)` 中选择一个，返回其下标
+    pub fn pick(&self, candidates: &[(&str, u32)]) -> Option<usize> {
+        let all_zero = candidates.iter().all(|(_, weight)| *weight == 0);
+        let weight_of = |weight: u32| if all_zero { 1 } else { weight as i64 };
+        let total: i64 = candidates.iter().map(|(_, w)| weight_of(*w)).sum();
+
+        let mut current = self.current.lock();
+        let mut best: Option<(usize, i64)> = None;
+        for (index, (id, weight)) in candidates.iter().enumerate() {
+            let weight = weight_of(*weight);
+            if weight == 0 {
+                continue;
+            }
+            let current_weight = current.entry(id.to_string()).or_insert(0);
+            *current_weight += weight;
+            if best.is_none_or(|(_, best_weight)| *current_weight > best_weight) {
+                best = Some((index, *current_weight));
+            }
+        }
+
+        let (index, _) = best?;
+        if let Some(current_weight) = current.get_mut(candidates[index].0) {
+            *current_weight -= total;
+        }
+        Some(index)
+    }
+}
+
+/// EWMA 延迟的平滑系数
+pub const EWMA_LATENCY_ALPHA: f64 = 0.3;
+
+/// 失败请求计入 EWMA 的最小延迟（毫秒）
+///
+/// 失败的凭证按至少该延迟计入，使延迟优先策略避开持续失败的凭证
+pub const LATENCY_FAILURE_PENALTY_MS: u64 = 10_000;
+
+/// 按凭证 ID 记录的 EWMA 首字节延迟
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    values: DashMap<String, f64>,
+}
+
+impl LatencyTracker {
+    /// 创建新的延迟追踪器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次成功请求的首字节延迟
+    pub fn record(&self, credential_id: &str, latency_ms: u64) {
+        let latency_ms = latency_ms as f64;
+        self.values
+            .entry(credential_id.to_string())
+            .and_modify(|ewma| {
+                *ewma = EWMA_LATENCY_ALPHA * latency_ms + (1.0 - EWMA_LATENCY_ALPHA) * *ewma
+            })
+            .or_insert(latency_ms);
+    }
+
+    /// 记录一次失败请求，按 `LATENCY_FAILURE_PENALTY_MS` 与实际耗时中的较大者计入
+    pub fn record_failure(&self, credential_id: &str, elapsed_ms: u64) {
+        self.record(credential_id, elapsed_ms.max(LATENCY_FAILURE_PENALTY_MS));
+    }
+
+    /// 获取凭证的 EWMA 延迟（没有样本时返回 None）
+    pub fn get(&self, credential_id: &str) -> Option<f64> {
+        self.values.get(credential_id).map(|v| *v)
+    }
+}
+
+/// 选择延迟最低的候选，返回其下标
+///
+/// 尚无延迟样本的候选优先，以便为其采集样本
+pub fn select_lowest_latency<T>(
+    candidates: &[T],
+    latency: impl Fn(&T) -> Option<f64>,
+) -> Option<usize> {
+    let latencies: Vec<Option<f64>> = candidates.iter().map(latency).collect();
+    if let Some(index) = latencies.iter().position(Option::is_none) {
+        return Some(index);
+    }
+    latencies
+        .iter()
+        .enumerate()
+        .filter_map(|(index, latency)| latency.map(|l| (index, l)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
 }
 
 /// 冷却信息
@@ -157,6 +269,10 @@ impl LoadBalancer {
             BalanceStrategy::RoundRobin => self.select_round_robin(&pool, provider),
             BalanceStrategy::LeastUsed => self.select_least_used(&pool),
             BalanceStrategy::Random => self.select_random(&pool),
+            // 加权、延迟优先和耗尽策略只由凭证池服务（ProviderPoolService）实现
+            BalanceStrategy::Weighted | BalanceStrategy::EwmaLatency | BalanceStrategy::Drain => {
+                self.select_round_robin(&pool, provider)
+            }
         }
     }
 
@@ -518,6 +634,37 @@ mod balancer_tests {
         assert_eq!(ids.len(), 3);
     }
 
+    #[test]
+    fn test_weighted_round_robin_zero_weight() {
+        let wrr = WeightedRoundRobin::new();
+        for _ in 0..4 {
+            assert_eq!(wrr.pick(&[("a", 0), ("b", 2)]), Some(1));
+        }
+        // 全部为 0 时按等权轮询
+        let picks: Vec<_> = (0..4).map(|_| wrr.pick(&[("c", 0), ("d", 0)])).collect();
+        assert_eq!(picks, vec![Some(0), Some(1), Some(0), Some(1)]);
+        assert_eq!(wrr.pick(&[]), None);
+    }
+
+    #[test]
+    fn test_latency_tracker_penalizes_failures() {
+        let tracker = LatencyTracker::new();
+        tracker.record("fast", 200);
+        tracker.record("slow", 900);
+        assert_eq!(
+            select_lowest_latency(&["fast", "slow"], |id| tracker.get(id)),
+            Some(0)
+        );
+
+        // 快速失败也按惩罚延迟计入，持续失败的凭证不再被优先选择
+        tracker.record_failure("fast", 50);
+        assert!((tracker.get("fast").unwrap() - 3140.0).abs() < 0.001);
+        assert_eq!(
+            select_lowest_latency(&["fast", "slow"], |id| tracker.get(id)),
+            Some(1)
+        );
+    }
+
     #[test]
     fn test_load_balancer_select_empty_pool() {
         let lb = LoadBalancer::round_robin();
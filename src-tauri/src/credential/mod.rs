@@ -30,7 +30,10 @@ mod sync;
 mod types;
 mod unified;
 
-pub use balancer::{BalanceStrategy, CooldownInfo, CredentialSelection, LoadBalancer};
+pub use balancer::{
+    select_lowest_latency, BalanceStrategy, CooldownInfo, CredentialSelection, LatencyTracker,
+    LoadBalancer, WeightedRoundRobin,
+};
 pub use health::{HealthCheckConfig, HealthCheckResult, HealthChecker, HealthStatus};
 pub use oauth_plugin_loader::{
     BinaryManifest, ExternalOAuthPlugin, OAuthPluginLoader, OAuthPluginManifest, ProviderManifest,
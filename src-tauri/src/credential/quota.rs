@@ -1,13 +1,17 @@
 //! 配额管理器实现
 //!
-//! 提供配额超限检测、自动切换和冷却恢复功能
+//! 提供配额超限检测、自动切换和冷却恢复功能，冷却记录可持久化到 SQLite
 
 use crate::config::QuotaExceededConfig;
+use crate::database::dao::cooldown_state::CooldownStore;
 use crate::resilience::{QUOTA_EXCEEDED_KEYWORDS, QUOTA_EXCEEDED_STATUS_CODES};
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+
+/// 配额超限记录的持久化作用域
+const QUOTA_SCOPE: &str = "quota";
 
 /// 配额超限记录
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +39,8 @@ pub struct QuotaManager {
     config: QuotaExceededConfig,
     /// 超限凭证记录（credential_id -> record）
     exceeded_credentials: DashMap<String, QuotaExceededRecord>,
+    /// 持久化存储（可选）
+    store: OnceLock<CooldownStore>,
 }
 
 impl QuotaManager {
@@ -43,6 +49,30 @@ impl QuotaManager {
         Self {
             config,
             exceeded_credentials: DashMap::new(),
+            store: OnceLock::new(),
+        }
+    }
+
+    /// 挂载持久化存储，并恢复未过期的配额超限记录
+    ///
+    /// 返回恢复的记录数
+    pub fn attach_store(&self, store: CooldownStore) -> usize {
+        let records: Vec<(String, QuotaExceededRecord)> = store.load(QUOTA_SCOPE);
+        let restored = records.len();
+        for (credential_id, record) in records {
+            self.exceeded_credentials.insert(credential_id, record);
+        }
+        let _ = self.store.set(store);
+
+        if restored > 0 {
+            tracing::info!("[QUOTA] 已恢复 {} 条未过期的配额超限记录", restored);
+        }
+        restored
+    }
+
+    fn forget(&self, credential_id: &str) {
+        if let Some(store) = self.store.get() {
+            store.remove(QUOTA_SCOPE, credential_id);
         }
     }
 
@@ -75,8 +105,21 @@ impl QuotaManager {
     /// # 返回
     /// 配额超限记录
     pub fn mark_quota_exceeded(&self, credential_id: &str, reason: &str) -> QuotaExceededRecord {
+        self.mark_quota_exceeded_with_delay(credential_id, reason, None)
+    }
+
+    /// 标记凭证为配额超限，使用上游给出的重置时间作为冷却时长
+    ///
+    /// `reset_after` 通常来自 `extract_retry_delay` 解析的 `retry-after` 或 `quotaResetTime`，
+    /// 为 None 时使用配置的冷却时长
+    pub fn mark_quota_exceeded_with_delay(
+        &self,
+        credential_id: &str,
+        reason: &str,
+        reset_after: Option<Duration>,
+    ) -> QuotaExceededRecord {
         let now = Utc::now();
-        let cooldown_until = now + self.cooldown_duration();
+        let cooldown_until = now + reset_after.unwrap_or_else(|| self.cooldown_duration());
 
         let record = QuotaExceededRecord {
             credential_id: credential_id.to_string(),
@@ -87,6 +130,9 @@ impl QuotaManager {
 
         self.exceeded_credentials
             .insert(credential_id.to_string(), record.clone());
+        if let Some(store) = self.store.get() {
+            store.save(QUOTA_SCOPE, credential_id, &record, cooldown_until);
+        }
 
         tracing::info!(
             credential_id = %credential_id,
@@ -114,6 +160,7 @@ impl QuotaManager {
                     // 冷却期已过，移除记录
                     drop(record); // 释放读锁
                     self.exceeded_credentials.remove(credential_id);
+                    self.forget(credential_id);
                     true
                 } else {
                     false
@@ -170,6 +217,9 @@ impl QuotaManager {
         if cleaned > 0 {
             tracing::info!(count = cleaned, "已清理过期的配额超限记录");
         }
+        if let Some(store) = self.store.get() {
+            store.purge_expired();
+        }
 
         cleaned
     }
@@ -183,7 +233,11 @@ impl QuotaManager {
     /// - `true`: 成功移除冷却状态
     /// - `false`: 凭证未处于冷却期
     pub fn restore_credential(&self, credential_id: &str) -> bool {
-        self.exceeded_credentials.remove(credential_id).is_some()
+        let removed = self.exceeded_credentials.remove(credential_id).is_some();
+        if removed {
+            self.forget(credential_id);
+        }
+        removed
     }
 
     /// 获取所有处于冷却期的凭证 ID
@@ -1112,6 +1166,32 @@ mod unit_tests {
         assert!(remaining.unwrap() <= 300);
     }
 
+    #[test]
+    fn test_quota_records_survive_restart() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let db = Arc::new(std::sync::Mutex::new(conn));
+        let config = QuotaExceededConfig {
+            switch_project: true,
+            switch_preview_model: true,
+            cooldown_seconds: 300,
+        };
+
+        let manager = QuotaManager::new(config.clone());
+        manager.attach_store(CooldownStore::new(db.clone()));
+        // 上游给出的重置时间优先于配置的冷却时长
+        manager.mark_quota_exceeded_with_delay("cred-1", "quota reset", Some(Duration::hours(3)));
+        manager.mark_quota_exceeded("cred-2", "quota");
+        manager.restore_credential("cred-2");
+
+        // 模拟重启
+        let restarted = QuotaManager::new(config);
+        assert_eq!(restarted.attach_store(CooldownStore::new(db)), 1);
+        assert!(!restarted.is_available("cred-1"));
+        assert!(restarted.remaining_cooldown_seconds("cred-1").unwrap() > 10_000);
+        assert!(restarted.is_available("cred-2"));
+    }
+
     #[test]
     fn test_all_credentials_exhausted_into_response() {
         use axum::http::{header, StatusCode};
@@ -7,12 +7,18 @@
 //! - **限流检测**: 检测 API 返回的限流错误（429、rate limit）
 //! - **冷却期管理**: 自动计算和管理凭证冷却时间
 //! - **风险评估**: 根据历史数据评估凭证风险等级
+//! - **状态持久化**: 冷却状态可写入 SQLite，重启后恢复
 
+use crate::database::dao::cooldown_state::CooldownStore;
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// 风控冷却状态的持久化作用域
+const RISK_SCOPE: &str = "risk";
 
 /// 风险等级
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -154,6 +160,33 @@ impl CredentialRiskState {
             last_rate_limit: None,
         }
     }
+
+    fn to_persisted(&self) -> PersistedRiskState {
+        PersistedRiskState {
+            events: self.events.iter().cloned().collect(),
+            consecutive_rate_limits: self.consecutive_rate_limits.load(Ordering::SeqCst),
+            cooldown_until: self.cooldown_until,
+            last_rate_limit: self.last_rate_limit,
+        }
+    }
+
+    fn from_persisted(persisted: PersistedRiskState) -> Self {
+        Self {
+            events: persisted.events.into(),
+            consecutive_rate_limits: AtomicU64::new(persisted.consecutive_rate_limits),
+            cooldown_until: persisted.cooldown_until,
+            last_rate_limit: persisted.last_rate_limit,
+        }
+    }
+}
+
+/// 持久化的凭证风控状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedRiskState {
+    events: Vec<RateLimitEvent>,
+    consecutive_rate_limits: u64,
+    cooldown_until: Option<DateTime<Utc>>,
+    last_rate_limit: Option<DateTime<Utc>>,
 }
 
 /// 风控控制器
@@ -164,6 +197,8 @@ pub struct RiskController {
     config: CooldownConfig,
     /// 各凭证的风控状态
     states: DashMap<String, CredentialRiskState>,
+    /// 持久化存储（可选）
+    store: OnceLock<CooldownStore>,
 }
 
 impl RiskController {
@@ -172,9 +207,30 @@ impl RiskController {
         Self {
             config,
             states: DashMap::new(),
+            store: OnceLock::new(),
         }
     }
 
+    /// 挂载持久化存储，并恢复仍处于冷却期的凭证状态
+    ///
+    /// 返回恢复的凭证数
+    pub fn attach_store(&self, store: CooldownStore) -> usize {
+        let records: Vec<(String, PersistedRiskState)> = store.load(RISK_SCOPE);
+        let restored = records.len();
+        for (credential_id, persisted) in records {
+            self.states.insert(
+                credential_id,
+                CredentialRiskState::from_persisted(persisted),
+            );
+        }
+        let _ = self.store.set(store);
+
+        if restored > 0 {
+            tracing::info!("[RISK] 已恢复 {} 个凭证的冷却状态", restored);
+        }
+        restored
+    }
+
     /// 使用默认配置创建
     pub fn with_defaults() -> Self {
         Self::new(CooldownConfig::default())
@@ -212,7 +268,17 @@ impl RiskController {
         let cooldown_secs = self.calculate_cooldown(&state, retry_after);
 
         // 设置冷却结束时间
-        state.cooldown_until = Some(Utc::now() + Duration::seconds(cooldown_secs as i64));
+        let cooldown_until = Utc::now() + Duration::seconds(cooldown_secs as i64);
+        state.cooldown_until = Some(cooldown_until);
+
+        if let Some(store) = self.store.get() {
+            store.save(
+                RISK_SCOPE,
+                &credential_id,
+                &state.to_persisted(),
+                cooldown_until,
+            );
+        }
 
         cooldown_secs
     }
@@ -275,6 +341,9 @@ impl RiskController {
             state.cooldown_until = None;
             state.consecutive_rate_limits.store(0, Ordering::SeqCst);
         }
+        if let Some(store) = self.store.get() {
+            store.remove(RISK_SCOPE, credential_id);
+        }
     }
 
     /// 获取所有处于冷却中的凭证 ID
@@ -548,6 +617,27 @@ mod tests {
         assert_eq!(cooldown, 120); // 应该使用 retry_after 的值
     }
 
+    #[test]
+    fn test_cooldown_survives_restart() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let db = std::sync::Arc::new(std::sync::Mutex::new(conn));
+
+        let controller = RiskController::with_defaults();
+        controller.attach_store(CooldownStore::new(db.clone()));
+        controller
+            .record_rate_limit(RateLimitEvent::new("cred-1".to_string()).with_retry_after(600));
+        controller.record_rate_limit(RateLimitEvent::new("cred-2".to_string()));
+        controller.clear_cooldown("cred-2");
+
+        // 模拟重启
+        let restarted = RiskController::with_defaults();
+        assert_eq!(restarted.attach_store(CooldownStore::new(db)), 1);
+        assert!(restarted.is_in_cooldown("cred-1"));
+        assert!(restarted.get_remaining_cooldown_secs("cred-1").unwrap() > 500);
+        assert!(!restarted.is_in_cooldown("cred-2"));
+    }
+
     #[test]
     fn test_exponential_backoff() {
         let controller = RiskController::with_defaults();
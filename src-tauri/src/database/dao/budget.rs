@@ -0,0 +1,84 @@
+//! 预算数据访问对象
+//!
+//! 按 `(scope, target, period)` 累计费用，`period` 为 UTC 日期（`YYYY-MM-DD`）或月份（`YYYY-MM`）。
+//! 同时提供从模型注册表读取定价的查询，供费用计算使用。
+
+use chrono::Utc;
+use rusqlite::{params, Connection};
+
+use crate::models::model_registry::ModelPricing;
+
+/// 一条花费累计记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetSpendRow {
+    pub scope: String,
+    pub target: String,
+    pub period: String,
+    pub amount: f64,
+}
+
+pub struct BudgetDao;
+
+impl BudgetDao {
+    /// 累加花费
+    pub fn add_spend(
+        conn: &Connection,
+        scope: &str,
+        target: &str,
+        period: &str,
+        amount: f64,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO budget_spend (scope, target, period, amount, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(scope, target, period)
+             DO UPDATE SET amount = amount + excluded.amount, updated_at = excluded.updated_at",
+            params![scope, target, period, amount, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// 获取指定周期的所有花费
+    pub fn list_by_periods(
+        conn: &Connection,
+        periods: &[&str],
+    ) -> Result<Vec<BudgetSpendRow>, rusqlite::Error> {
+        let mut stmt = conn
+            .prepare("SELECT scope, target, period, amount FROM budget_spend WHERE period = ?1")?;
+        let mut rows = Vec::new();
+        for period in periods {
+            let mapped = stmt.query_map([period], |row| {
+                Ok(BudgetSpendRow {
+                    scope: row.get(0)?,
+                    target: row.get(1)?,
+                    period: row.get(2)?,
+                    amount: row.get(3)?,
+                })
+            })?;
+            for row in mapped {
+                rows.push(row?);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// 读取模型注册表中有定价的模型（模型 ID -> 定价）
+    pub fn list_model_pricing(
+        conn: &Connection,
+    ) -> Result<Vec<(String, ModelPricing)>, rusqlite::Error> {
+        let mut stmt =
+            conn.prepare("SELECT id, pricing FROM model_registry WHERE pricing IS NOT NULL")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut pricing = Vec::new();
+        for row in rows {
+            let (id, json) = row?;
+            if let Ok(p) = serde_json::from_str::<ModelPricing>(&json) {
+                pricing.push((id, p));
+            }
+        }
+        Ok(pricing)
+    }
+}
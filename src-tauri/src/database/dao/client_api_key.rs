@@ -0,0 +1,308 @@
+//! 客户端 API Key 数据访问对象
+//!
+//! 为每个使用者（个人、CI 任务）签发独立的客户端 key，
+//! 每个 key 可配置过期时间、模型白名单、RPM/TPM 限制和月度 Token 预算。
+//!
+//! 数据库只保存 key 的 SHA-256 哈希和前缀，明文 key 只在创建时返回一次。
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// 数据模型
+// ============================================================================
+
+/// 客户端 API Key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientApiKey {
+    pub id: String,
+    /// 显示名称（如 "alice"、"ci-nightly"）
+    pub label: String,
+    /// key 的 SHA-256 哈希（十六进制）
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    /// key 前缀，用于在列表中识别 key
+    pub key_prefix: String,
+    pub enabled: bool,
+    /// 过期时间（None 表示永不过期）
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 允许访问的模型（支持 `*` 通配符，空列表表示不限制）
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// 每分钟请求数限制
+    pub rpm_limit: Option<u32>,
+    /// 每分钟 Token 数限制
+    pub tpm_limit: Option<u64>,
+    /// 每月 Token 预算
+    pub monthly_token_budget: Option<u64>,
+    /// 当前统计月份（YYYY-MM）
+    pub usage_month: Option<String>,
+    /// 当月已用 Token 数
+    pub month_tokens: u64,
+    /// 累计请求数
+    pub request_count: u64,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// DAO 实现
+// ============================================================================
+
+pub struct ClientApiKeyDao;
+
+const SELECT_COLUMNS: &str = "SELECT id, label, key_hash, key_prefix, enabled, expires_at,
+        allowed_models, rpm_limit, tpm_limit, monthly_token_budget, usage_month,
+        month_tokens, request_count, last_used_at, created_at, updated_at
+     FROM client_api_keys";
+
+impl ClientApiKeyDao {
+    /// 获取所有客户端 key
+    pub fn get_all(conn: &Connection) -> Result<Vec<ClientApiKey>, rusqlite::Error> {
+        let mut stmt = conn.prepare(&format!("{} ORDER BY created_at ASC", SELECT_COLUMNS))?;
+        let rows = stmt.query_map([], Self::row_to_key)?;
+        let mut keys = Vec::new();
+        for key in rows.flatten() {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    /// 根据 ID 获取客户端 key
+    pub fn get_by_id(conn: &Connection, id: &str) -> Result<Option<ClientApiKey>, rusqlite::Error> {
+        let mut stmt = conn.prepare(&format!("{} WHERE id = ?1", SELECT_COLUMNS))?;
+        let mut rows = stmt.query([id])?;
+        if let Some(row) = rows.next()? {
+            Ok(Some(Self::row_to_key(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 插入新的客户端 key
+    pub fn insert(conn: &Connection, key: &ClientApiKey) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO client_api_keys
+             (id, label, key_hash, key_prefix, enabled, expires_at, allowed_models,
+              rpm_limit, tpm_limit, monthly_token_budget, usage_month, month_tokens,
+              request_count, last_used_at, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                key.id,
+                key.label,
+                key.key_hash,
+                key.key_prefix,
+                key.enabled,
+                key.expires_at.map(|t| t.to_rfc3339()),
+                Self::models_to_json(&key.allowed_models),
+                key.rpm_limit,
+                key.tpm_limit.map(|v| v as i64),
+                key.monthly_token_budget.map(|v| v as i64),
+                key.usage_month,
+                key.month_tokens as i64,
+                key.request_count as i64,
+                key.last_used_at.map(|t| t.to_rfc3339()),
+                key.created_at.to_rfc3339(),
+                key.updated_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 更新客户端 key 的配置（不修改哈希和用量）
+    pub fn update(conn: &Connection, key: &ClientApiKey) -> Result<bool, rusqlite::Error> {
+        let affected = conn.execute(
+            "UPDATE client_api_keys SET
+             label = ?2, enabled = ?3, expires_at = ?4, allowed_models = ?5,
+             rpm_limit = ?6, tpm_limit = ?7, monthly_token_budget = ?8, updated_at = ?9
+             WHERE id = ?1",
+            params![
+                key.id,
+                key.label,
+                key.enabled,
+                key.expires_at.map(|t| t.to_rfc3339()),
+                Self::models_to_json(&key.allowed_models),
+                key.rpm_limit,
+                key.tpm_limit.map(|v| v as i64),
+                key.monthly_token_budget.map(|v| v as i64),
+                key.updated_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// 删除客户端 key
+    pub fn delete(conn: &Connection, id: &str) -> Result<bool, rusqlite::Error> {
+        let affected = conn.execute("DELETE FROM client_api_keys WHERE id = ?1", [id])?;
+        Ok(affected > 0)
+    }
+
+    /// 记录一次请求
+    pub fn record_request(
+        conn: &Connection,
+        id: &str,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE client_api_keys SET request_count = request_count + 1, last_used_at = ?2
+             WHERE id = ?1",
+            params![id, used_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// 累加月度 Token 用量，跨月时自动重置
+    pub fn add_month_tokens(
+        conn: &Connection,
+        id: &str,
+        month: &str,
+        tokens: u64,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE client_api_keys SET
+             month_tokens = CASE WHEN usage_month = ?2 THEN month_tokens + ?3 ELSE ?3 END,
+             usage_month = ?2
+             WHERE id = ?1",
+            params![id, month, tokens as i64],
+        )?;
+        Ok(())
+    }
+
+    fn models_to_json(models: &[String]) -> Option<String> {
+        if models.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(models).unwrap_or_default())
+        }
+    }
+
+    fn parse_time(value: Option<String>) -> Option<DateTime<Utc>> {
+        value.and_then(|s| {
+            DateTime::parse_from_rfc3339(&s)
+                .ok()
+                .map(|dt| dt.with_timezone(&Utc))
+        })
+    }
+
+    /// 从数据库行转换为 ClientApiKey
+    fn row_to_key(row: &rusqlite::Row) -> Result<ClientApiKey, rusqlite::Error> {
+        let allowed_models_json: Option<String> = row.get(6)?;
+        let tpm_limit: Option<i64> = row.get(8)?;
+        let monthly_token_budget: Option<i64> = row.get(9)?;
+        let month_tokens: i64 = row.get(11)?;
+        let request_count: i64 = row.get(12)?;
+        let created_at: Option<String> = row.get(14)?;
+        let updated_at: Option<String> = row.get(15)?;
+
+        Ok(ClientApiKey {
+            id: row.get(0)?,
+            label: row.get(1)?,
+            key_hash: row.get(2)?,
+            key_prefix: row.get(3)?,
+            enabled: row.get(4)?,
+            expires_at: Self::parse_time(row.get(5)?),
+            allowed_models: allowed_models_json
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            rpm_limit: row.get(7)?,
+            tpm_limit: tpm_limit.map(|v| v.max(0) as u64),
+            monthly_token_budget: monthly_token_budget.map(|v| v.max(0) as u64),
+            usage_month: row.get(10)?,
+            month_tokens: month_tokens.max(0) as u64,
+            request_count: request_count.max(0) as u64,
+            last_used_at: Self::parse_time(row.get(13)?),
+            created_at: Self::parse_time(created_at).unwrap_or_else(Utc::now),
+            updated_at: Self::parse_time(updated_at).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn sample_key(id: &str) -> ClientApiKey {
+        let now = Utc::now();
+        ClientApiKey {
+            id: id.to_string(),
+            label: "ci".to_string(),
+            key_hash: format!("hash-{}", id),
+            key_prefix: "pc-abcd".to_string(),
+            enabled: true,
+            expires_at: None,
+            allowed_models: vec!["claude-*".to_string()],
+            rpm_limit: Some(60),
+            tpm_limit: Some(100_000),
+            monthly_token_budget: Some(1_000_000),
+            usage_month: None,
+            month_tokens: 0,
+            request_count: 0,
+            last_used_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_client_key_crud() {
+        let conn = setup_test_db();
+        ClientApiKeyDao::insert(&conn, &sample_key("k1")).unwrap();
+
+        let loaded = ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().unwrap();
+        assert_eq!(loaded.label, "ci");
+        assert_eq!(loaded.allowed_models, vec!["claude-*"]);
+        assert_eq!(loaded.tpm_limit, Some(100_000));
+
+        let mut updated = loaded.clone();
+        updated.label = "ci-nightly".to_string();
+        updated.allowed_models.clear();
+        updated.rpm_limit = None;
+        assert!(ClientApiKeyDao::update(&conn, &updated).unwrap());
+
+        let loaded = ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().unwrap();
+        assert_eq!(loaded.label, "ci-nightly");
+        assert!(loaded.allowed_models.is_empty());
+        assert_eq!(loaded.rpm_limit, None);
+        assert_eq!(loaded.key_hash, "hash-k1");
+
+        assert_eq!(ClientApiKeyDao::get_all(&conn).unwrap().len(), 1);
+        assert!(ClientApiKeyDao::delete(&conn, "k1").unwrap());
+        assert!(ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_month_tokens_reset_on_new_month() {
+        let conn = setup_test_db();
+        ClientApiKeyDao::insert(&conn, &sample_key("k1")).unwrap();
+
+        ClientApiKeyDao::add_month_tokens(&conn, "k1", "2026-01", 100).unwrap();
+        ClientApiKeyDao::add_month_tokens(&conn, "k1", "2026-01", 50).unwrap();
+        let key = ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().unwrap();
+        assert_eq!(key.month_tokens, 150);
+        assert_eq!(key.usage_month.as_deref(), Some("2026-01"));
+
+        ClientApiKeyDao::add_month_tokens(&conn, "k1", "2026-02", 10).unwrap();
+        let key = ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().unwrap();
+        assert_eq!(key.month_tokens, 10);
+        assert_eq!(key.usage_month.as_deref(), Some("2026-02"));
+    }
+
+    #[test]
+    fn test_record_request() {
+        let conn = setup_test_db();
+        ClientApiKeyDao::insert(&conn, &sample_key("k1")).unwrap();
+        ClientApiKeyDao::record_request(&conn, "k1", Utc::now()).unwrap();
+        ClientApiKeyDao::record_request(&conn, "k1", Utc::now()).unwrap();
+        let key = ClientApiKeyDao::get_by_id(&conn, "k1").unwrap().unwrap();
+        assert_eq!(key.request_count, 2);
+        assert!(key.last_used_at.is_some());
+    }
+}
@@ -0,0 +1,154 @@
+//! 冷却状态数据访问对象
+//!
+//! 持久化限流追踪器、配额管理器和风控控制器的冷却记录，重启后恢复，
+//! 避免重启后立即重新请求已经限流或配额耗尽的账号。
+//!
+//! 记录按 `(scope, key)` 唯一，`payload` 为各模块自己的 JSON 记录。
+//! 时间统一以秒精度的 RFC 3339 UTC 字符串保存，便于在 SQL 中按字典序比较过期时间。
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::database::DbConnection;
+
+pub struct CooldownStateDao;
+
+impl CooldownStateDao {
+    /// 插入或覆盖冷却记录
+    pub fn upsert(
+        conn: &Connection,
+        scope: &str,
+        key: &str,
+        payload: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT OR REPLACE INTO cooldown_state (scope, key, payload, expires_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                scope,
+                key,
+                payload,
+                format_time(expires_at),
+                format_time(Utc::now())
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 删除冷却记录
+    pub fn delete(conn: &Connection, scope: &str, key: &str) -> Result<usize, rusqlite::Error> {
+        conn.execute(
+            "DELETE FROM cooldown_state WHERE scope = ?1 AND key = ?2",
+            params![scope, key],
+        )
+    }
+
+    /// 获取指定作用域下未过期的记录 `(key, payload)`
+    pub fn list_active(
+        conn: &Connection,
+        scope: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(String, String)>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT key, payload FROM cooldown_state
+             WHERE scope = ?1 AND expires_at > ?2 ORDER BY key",
+        )?;
+        let rows = stmt.query_map(params![scope, format_time(now)], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// 删除过期记录，返回删除数量
+    pub fn delete_expired(conn: &Connection, now: DateTime<Utc>) -> Result<usize, rusqlite::Error> {
+        conn.execute(
+            "DELETE FROM cooldown_state WHERE expires_at <= ?1",
+            [format_time(now)],
+        )
+    }
+}
+
+/// 冷却状态存储句柄
+///
+/// 供内存中的冷却追踪器写穿持久化。写入失败只记录日志，不影响内存中的冷却判断
+#[derive(Debug, Clone)]
+pub struct CooldownStore {
+    db: DbConnection,
+}
+
+impl CooldownStore {
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+
+    /// 保存冷却记录
+    pub fn save<T: Serialize>(
+        &self,
+        scope: &str,
+        key: &str,
+        record: &T,
+        expires_at: DateTime<Utc>,
+    ) {
+        let result = serde_json::to_string(record)
+            .map_err(|e| e.to_string())
+            .and_then(|payload| {
+                let conn = self.db.lock().map_err(|e| e.to_string())?;
+                CooldownStateDao::upsert(&conn, scope, key, &payload, expires_at)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::warn!("[COOLDOWN] 保存冷却记录 {}/{} 失败: {}", scope, key, e);
+        }
+    }
+
+    /// 删除冷却记录
+    pub fn remove(&self, scope: &str, key: &str) {
+        let result = self.db.lock().map_err(|e| e.to_string()).and_then(|conn| {
+            CooldownStateDao::delete(&conn, scope, key).map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            tracing::warn!("[COOLDOWN] 删除冷却记录 {}/{} 失败: {}", scope, key, e);
+        }
+    }
+
+    /// 加载指定作用域下未过期的记录，无法解析的记录被跳过
+    pub fn load<T: DeserializeOwned>(&self, scope: &str) -> Vec<(String, T)> {
+        let rows = match self.db.lock() {
+            Ok(conn) => CooldownStateDao::list_active(&conn, scope, Utc::now()),
+            Err(e) => {
+                tracing::warn!("[COOLDOWN] 加载冷却记录 {} 失败: {}", scope, e);
+                return Vec::new();
+            }
+        };
+        match rows {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|(key, payload)| {
+                    serde_json::from_str(&payload)
+                        .ok()
+                        .map(|record| (key, record))
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("[COOLDOWN] 加载冷却记录 {} 失败: {}", scope, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// 删除所有过期记录
+    pub fn purge_expired(&self) -> usize {
+        self.db
+            .lock()
+            .ok()
+            .and_then(|conn| CooldownStateDao::delete_expired(&conn, Utc::now()).ok())
+            .unwrap_or(0)
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
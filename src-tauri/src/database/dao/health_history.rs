@@ -0,0 +1,145 @@
+//! 凭证健康检查历史数据访问对象
+//!
+//! 记录每次健康检查（手动触发或定时探测）的结果和延迟，每个凭证只保留最近的若干条。
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// 一条健康检查记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthHistoryRecord {
+    pub credential_uuid: String,
+    pub provider_type: String,
+    /// 检查来源：`manual`（手动/批量检查）或 `canary`（定时探测）
+    pub source: String,
+    pub success: bool,
+    pub latency_ms: u64,
+    pub model: Option<String>,
+    pub message: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+pub struct HealthHistoryDao;
+
+impl HealthHistoryDao {
+    /// 写入一条记录，并只保留该凭证最近 `keep` 条
+    pub fn insert(
+        conn: &Connection,
+        record: &HealthHistoryRecord,
+        keep: usize,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO credential_health_history
+             (credential_uuid, provider_type, source, success, latency_ms, model, message, checked_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.credential_uuid,
+                record.provider_type,
+                record.source,
+                record.success,
+                record.latency_ms as i64,
+                record.model,
+                record.message,
+                record.checked_at.to_rfc3339(),
+            ],
+        )?;
+        conn.execute(
+            "DELETE FROM credential_health_history
+             WHERE credential_uuid = ?1 AND id NOT IN (
+                 SELECT id FROM credential_health_history
+                 WHERE credential_uuid = ?1 ORDER BY id DESC LIMIT ?2
+             )",
+            params![record.credential_uuid, keep.max(1) as i64],
+        )?;
+        Ok(())
+    }
+
+    /// 获取凭证最近的检查记录（按时间倒序）
+    pub fn list_by_credential(
+        conn: &Connection,
+        uuid: &str,
+        limit: usize,
+    ) -> Result<Vec<HealthHistoryRecord>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT credential_uuid, provider_type, source, success, latency_ms, model, message, checked_at
+             FROM credential_health_history
+             WHERE credential_uuid = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![uuid, limit as i64], |row| {
+            let checked_at: String = row.get(7)?;
+            Ok(HealthHistoryRecord {
+                credential_uuid: row.get(0)?,
+                provider_type: row.get(1)?,
+                source: row.get(2)?,
+                success: row.get(3)?,
+                latency_ms: row.get::<_, i64>(4)?.max(0) as u64,
+                model: row.get(5)?,
+                message: row.get(6)?,
+                checked_at: DateTime::parse_from_rfc3339(&checked_at)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// 删除凭证的全部记录
+    pub fn delete_by_credential(conn: &Connection, uuid: &str) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "DELETE FROM credential_health_history WHERE credential_uuid = ?1",
+            [uuid],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn record(uuid: &str, success: bool, latency_ms: u64) -> HealthHistoryRecord {
+        HealthHistoryRecord {
+            credential_uuid: uuid.to_string(),
+            provider_type: "claude".to_string(),
+            source: "canary".to_string(),
+            success,
+            latency_ms,
+            model: Some("claude-haiku-4-5".to_string()),
+            message: None,
+            checked_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_latest_per_credential() {
+        let conn = setup_test_db();
+        for i in 0..5 {
+            HealthHistoryDao::insert(&conn, &record("a", i % 2 == 0, i * 100), 3).unwrap();
+        }
+        HealthHistoryDao::insert(&conn, &record("b", true, 42), 3).unwrap();
+
+        let history = HealthHistoryDao::list_by_credential(&conn, "a", 10).unwrap();
+        let latencies: Vec<_> = history.iter().map(|r| r.latency_ms).collect();
+        assert_eq!(latencies, vec![400, 300, 200]);
+        assert!(history[0].success);
+        assert!(!history[1].success);
+
+        assert_eq!(
+            HealthHistoryDao::list_by_credential(&conn, "b", 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        HealthHistoryDao::delete_by_credential(&conn, "a").unwrap();
+        assert!(HealthHistoryDao::list_by_credential(&conn, "a", 10)
+            .unwrap()
+            .is_empty());
+    }
+}
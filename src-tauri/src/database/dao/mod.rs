@@ -1,5 +1,9 @@
 pub mod agent;
 pub mod api_key_provider;
+pub mod budget;
+pub mod client_api_key;
+pub mod cooldown_state;
+pub mod health_history;
 pub mod installed_plugins;
 pub mod mcp;
 pub mod orchestrator;
@@ -7,4 +11,5 @@ pub mod plugin_credential;
 pub mod prompts;
 pub mod provider_pool;
 pub mod providers;
+pub mod response_cache;
 pub mod skills;
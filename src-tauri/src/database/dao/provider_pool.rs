@@ -2,9 +2,10 @@
 //!
 //! 提供凭证池的 CRUD 操作。
 
+use crate::database::secrets;
 use crate::models::provider_pool_model::{
-    CachedTokenInfo, CredentialData, CredentialSource, PoolProviderType, ProviderCredential,
-    ProviderPools,
+    default_weight, CachedTokenInfo, CredentialData, CredentialSource, PoolProviderType,
+    ProviderCredential, ProviderPools,
 };
 use chrono::{DateTime, TimeZone, Utc};
 use rusqlite::{params, Connection};
@@ -18,7 +19,7 @@ impl ProviderPoolDao {
             "SELECT uuid, provider_type, credential_data, name, is_healthy, is_disabled,
                     check_health, check_model_name, not_supported_models, supported_models, usage_count, error_count,
                     last_used, last_error_time, last_error_message, last_health_check_time,
-                    last_health_check_model, created_at, updated_at, source, proxy_url
+                    last_health_check_model, created_at, updated_at, source, proxy_url, weight
              FROM provider_pool_credentials
              ORDER BY provider_type, created_at ASC",
         )?;
@@ -41,7 +42,7 @@ impl ProviderPoolDao {
             "SELECT uuid, provider_type, credential_data, name, is_healthy, is_disabled,
                     check_health, check_model_name, not_supported_models, supported_models, usage_count, error_count,
                     last_used, last_error_time, last_error_message, last_health_check_time,
-                    last_health_check_model, created_at, updated_at, source, proxy_url
+                    last_health_check_model, created_at, updated_at, source, proxy_url, weight
              FROM provider_pool_credentials
              WHERE provider_type = ?1
              ORDER BY created_at ASC",
@@ -67,7 +68,7 @@ impl ProviderPoolDao {
             "SELECT uuid, provider_type, credential_data, name, is_healthy, is_disabled,
                     check_health, check_model_name, not_supported_models, supported_models, usage_count, error_count,
                     last_used, last_error_time, last_error_message, last_health_check_time,
-                    last_health_check_model, created_at, updated_at, source, proxy_url
+                    last_health_check_model, created_at, updated_at, source, proxy_url, weight
              FROM provider_pool_credentials
              WHERE uuid = ?1",
         )?;
@@ -89,7 +90,7 @@ impl ProviderPoolDao {
             "SELECT uuid, provider_type, credential_data, name, is_healthy, is_disabled,
                     check_health, check_model_name, not_supported_models, supported_models, usage_count, error_count,
                     last_used, last_error_time, last_error_message, last_health_check_time,
-                    last_health_check_model, created_at, updated_at, source, proxy_url
+                    last_health_check_model, created_at, updated_at, source, proxy_url, weight
              FROM provider_pool_credentials
              WHERE name = ?1",
         )?;
@@ -116,8 +117,10 @@ impl ProviderPoolDao {
 
     /// 插入新凭证
     pub fn insert(conn: &Connection, cred: &ProviderCredential) -> Result<(), rusqlite::Error> {
-        let credential_json =
-            serde_json::to_string(&cred.credential).unwrap_or_else(|_| "{}".to_string());
+        let credential_json = secrets::encrypt_column(
+            &serde_json::to_string(&cred.credential).unwrap_or_else(|_| "{}".to_string()),
+            &cred.uuid,
+        )?;
         let not_supported_models_json =
             serde_json::to_string(&cred.not_supported_models).unwrap_or_else(|_| "[]".to_string());
         let supported_models_json =
@@ -133,8 +136,8 @@ impl ProviderPoolDao {
              (uuid, provider_type, credential_data, name, is_healthy, is_disabled,
               check_health, check_model_name, not_supported_models, supported_models, usage_count, error_count,
               last_used, last_error_time, last_error_message, last_health_check_time,
-              last_health_check_model, created_at, updated_at, source, proxy_url)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+              last_health_check_model, created_at, updated_at, source, proxy_url, weight)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
             params![
                 cred.uuid,
                 cred.provider_type.to_string(),
@@ -157,6 +160,7 @@ impl ProviderPoolDao {
                 cred.updated_at.timestamp(),
                 source_str,
                 cred.proxy_url,
+                cred.weight,
             ],
         )?;
         Ok(())
@@ -164,8 +168,10 @@ impl ProviderPoolDao {
 
     /// 更新凭证
     pub fn update(conn: &Connection, cred: &ProviderCredential) -> Result<(), rusqlite::Error> {
-        let credential_json =
-            serde_json::to_string(&cred.credential).unwrap_or_else(|_| "{}".to_string());
+        let credential_json = secrets::encrypt_column(
+            &serde_json::to_string(&cred.credential).unwrap_or_else(|_| "{}".to_string()),
+            &cred.uuid,
+        )?;
         let not_supported_models_json =
             serde_json::to_string(&cred.not_supported_models).unwrap_or_else(|_| "[]".to_string());
         let supported_models_json =
@@ -177,7 +183,8 @@ impl ProviderPoolDao {
              is_disabled = ?6, check_health = ?7, check_model_name = ?8,
              not_supported_models = ?9, supported_models = ?10, usage_count = ?11, error_count = ?12,
              last_used = ?13, last_error_time = ?14, last_error_message = ?15,
-             last_health_check_time = ?16, last_health_check_model = ?17, updated_at = ?18, proxy_url = ?19
+             last_health_check_time = ?16, last_health_check_model = ?17, updated_at = ?18, proxy_url = ?19,
+             weight = ?20
              WHERE uuid = ?1",
             params![
                 cred.uuid,
@@ -199,6 +206,7 @@ impl ProviderPoolDao {
                 cred.last_health_check_model,
                 cred.updated_at.timestamp(),
                 cred.proxy_url,
+                cred.weight,
             ],
         )?;
         Ok(())
@@ -315,10 +323,16 @@ impl ProviderPoolDao {
         let updated_at_ts: i64 = row.get(18)?;
         let source_str: Option<String> = row.get(19).ok();
         let proxy_url: Option<String> = row.get(20).ok();
+        let weight: u32 = row
+            .get::<_, Option<i64>>(21)
+            .ok()
+            .flatten()
+            .map_or(default_weight(), |w| w.max(0) as u32);
 
         let provider_type: PoolProviderType =
             provider_type_str.parse().unwrap_or(PoolProviderType::Kiro);
 
+        let credential_json = secrets::decrypt_column(&credential_json, &uuid, 2)?;
         let credential: CredentialData = serde_json::from_str(&credential_json).map_err(|e| {
             rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
         })?;
@@ -367,6 +381,7 @@ impl ProviderPoolDao {
             cached_token: None, // 从 get_token_cache 单独获取
             source,
             proxy_url,
+            weight,
         })
     }
 
@@ -388,6 +403,12 @@ impl ProviderPoolDao {
         if let Some(row) = rows.next()? {
             let access_token: Option<String> = row.get(0)?;
             let refresh_token: Option<String> = row.get(1)?;
+            let access_token = access_token
+                .map(|t| secrets::decrypt_column(&t, uuid, 0))
+                .transpose()?;
+            let refresh_token = refresh_token
+                .map(|t| secrets::decrypt_column(&t, uuid, 1))
+                .transpose()?;
             let expiry_time_str: Option<String> = row.get(2)?;
             let last_refresh_str: Option<String> = row.get(3)?;
             let refresh_error_count: i32 = row.get::<_, Option<i32>>(4)?.unwrap_or(0);
@@ -425,6 +446,17 @@ impl ProviderPoolDao {
         uuid: &str,
         token_info: &CachedTokenInfo,
     ) -> Result<(), rusqlite::Error> {
+        let access_token = token_info
+            .access_token
+            .as_deref()
+            .map(|t| secrets::encrypt_column(t, uuid))
+            .transpose()?;
+        let refresh_token = token_info
+            .refresh_token
+            .as_deref()
+            .map(|t| secrets::encrypt_column(t, uuid))
+            .transpose()?;
+
         conn.execute(
             "UPDATE provider_pool_credentials SET
              cached_access_token = ?2,
@@ -437,8 +469,8 @@ impl ProviderPoolDao {
              WHERE uuid = ?1",
             params![
                 uuid,
-                token_info.access_token,
-                token_info.refresh_token,
+                access_token,
+                refresh_token,
                 token_info.expiry_time.map(|t| t.to_rfc3339()),
                 token_info.last_refresh.map(|t| t.to_rfc3339()),
                 token_info.refresh_error_count as i32,
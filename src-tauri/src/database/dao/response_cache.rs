@@ -0,0 +1,185 @@
+//! 响应缓存数据访问对象
+//!
+//! 存储按规范化请求哈希索引的上游响应。时间统一以秒精度的 RFC 3339 UTC 字符串保存，
+//! 便于直接在 SQL 中按字典序比较过期时间。
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+/// 响应缓存条目
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseCacheEntry {
+    /// 规范化请求的 SHA-256（十六进制）
+    pub cache_key: String,
+    /// 请求端点（如 `/v1/chat/completions`）
+    pub endpoint: String,
+    pub model: String,
+    /// 是否为 SSE 响应
+    pub is_stream: bool,
+    pub status_code: u16,
+    pub content_type: String,
+    /// 原始响应体（SSE 响应为完整的事件流字节）
+    pub body: Vec<u8>,
+    pub hit_count: u64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 缓存统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ResponseCacheUsage {
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub total_hits: u64,
+}
+
+pub struct ResponseCacheDao;
+
+impl ResponseCacheDao {
+    /// 获取未过期的缓存条目
+    pub fn get(
+        conn: &Connection,
+        cache_key: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ResponseCacheEntry>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT cache_key, endpoint, model, is_stream, status_code, content_type, body,
+                    hit_count, created_at, expires_at
+             FROM response_cache WHERE cache_key = ?1 AND expires_at > ?2",
+            params![cache_key, format_time(now)],
+            Self::row_to_entry,
+        )
+        .optional()
+    }
+
+    /// 插入或覆盖缓存条目
+    pub fn upsert(conn: &Connection, entry: &ResponseCacheEntry) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT OR REPLACE INTO response_cache
+             (cache_key, endpoint, model, is_stream, status_code, content_type, body,
+              size_bytes, hit_count, created_at, expires_at, last_hit_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, NULL)",
+            params![
+                entry.cache_key,
+                entry.endpoint,
+                entry.model,
+                entry.is_stream,
+                entry.status_code,
+                entry.content_type,
+                entry.body,
+                entry.body.len() as i64,
+                entry.hit_count as i64,
+                format_time(entry.created_at),
+                format_time(entry.expires_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 记录一次命中
+    pub fn record_hit(
+        conn: &Connection,
+        cache_key: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE response_cache SET hit_count = hit_count + 1, last_hit_at = ?2
+             WHERE cache_key = ?1",
+            params![cache_key, format_time(now)],
+        )?;
+        Ok(())
+    }
+
+    /// 删除过期条目，返回删除数量
+    pub fn delete_expired(conn: &Connection, now: DateTime<Utc>) -> Result<usize, rusqlite::Error> {
+        conn.execute(
+            "DELETE FROM response_cache WHERE expires_at <= ?1",
+            [format_time(now)],
+        )
+    }
+
+    /// 按最近使用时间淘汰条目，直到条目数和总大小都不超过上限
+    pub fn evict_to_limits(
+        conn: &Connection,
+        max_entries: usize,
+        max_bytes: u64,
+    ) -> Result<usize, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT cache_key, size_bytes FROM response_cache
+             ORDER BY COALESCE(last_hit_at, created_at) DESC, created_at DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut kept_entries = 0usize;
+        let mut kept_bytes = 0u64;
+        let mut evict = Vec::new();
+        for row in rows {
+            let (key, size) = row?;
+            let size = size.max(0) as u64;
+            if kept_entries < max_entries && kept_bytes + size <= max_bytes {
+                kept_entries += 1;
+                kept_bytes += size;
+            } else {
+                evict.push(key);
+            }
+        }
+        drop(stmt);
+
+        for key in &evict {
+            conn.execute("DELETE FROM response_cache WHERE cache_key = ?1", [key])?;
+        }
+        Ok(evict.len())
+    }
+
+    /// 清空缓存，返回删除数量
+    pub fn clear(conn: &Connection) -> Result<usize, rusqlite::Error> {
+        conn.execute("DELETE FROM response_cache", [])
+    }
+
+    /// 获取缓存统计
+    pub fn usage(conn: &Connection) -> Result<ResponseCacheUsage, rusqlite::Error> {
+        conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size_bytes), 0), COALESCE(SUM(hit_count), 0)
+             FROM response_cache",
+            [],
+            |row| {
+                Ok(ResponseCacheUsage {
+                    entries: row.get::<_, i64>(0)? as u64,
+                    size_bytes: row.get::<_, i64>(1)? as u64,
+                    total_hits: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )
+    }
+
+    fn row_to_entry(row: &rusqlite::Row) -> Result<ResponseCacheEntry, rusqlite::Error> {
+        let hit_count: i64 = row.get(7)?;
+        let created_at: String = row.get(8)?;
+        let expires_at: String = row.get(9)?;
+        Ok(ResponseCacheEntry {
+            cache_key: row.get(0)?,
+            endpoint: row.get(1)?,
+            model: row.get(2)?,
+            is_stream: row.get(3)?,
+            status_code: row.get(4)?,
+            content_type: row.get(5)?,
+            body: row.get(6)?,
+            hit_count: hit_count.max(0) as u64,
+            created_at: parse_time(&created_at),
+            expires_at: parse_time(&expires_at),
+        })
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn parse_time(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
@@ -364,3 +364,206 @@ pub fn cleanup_legacy_api_key_credentials(conn: &Connection) -> Result<usize, St
 
     Ok(deleted)
 }
+
+/// 需要加密存储的敏感字段：(表名, 主键列, 字段列)
+const SECRET_COLUMNS: &[(&str, &str, &str)] = &[
+    ("provider_pool_credentials", "uuid", "credential_data"),
+    ("provider_pool_credentials", "uuid", "cached_access_token"),
+    ("provider_pool_credentials", "uuid", "cached_refresh_token"),
+    ("api_keys", "id", "api_key_encrypted"),
+];
+
+/// 遍历所有敏感字段并按需改写
+///
+/// `rewrite(column, id, value)` 返回 `Some(new_value)` 时更新该字段，返回改写的字段数。
+/// `id` 为所在行的主键，加密时用作 AAD。
+pub fn rewrite_secret_columns(
+    conn: &Connection,
+    mut rewrite: impl FnMut(&str, &str, &str) -> Result<Option<String>, String>,
+) -> Result<usize, String> {
+    let mut count = 0;
+
+    for (table, key_column, column) in SECRET_COLUMNS {
+        let rows: Vec<(String, String)> = {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {key_column}, {column} FROM {table} WHERE {column} IS NOT NULL"
+                ))
+                .map_err(|e| format!("查询 {}.{} 失败: {}", table, column, e))?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| format!("查询 {}.{} 失败: {}", table, column, e))?;
+            rows.collect::<Result<_, _>>()
+                .map_err(|e| format!("读取 {}.{} 失败: {}", table, column, e))?
+        };
+
+        for (id, value) in rows {
+            let new_value = rewrite(column, &id, &value)
+                .map_err(|e| format!("处理 {}.{} (id={}) 失败: {}", table, column, id, e))?;
+            if let Some(new_value) = new_value {
+                conn.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE {key_column} = ?2"),
+                    params![new_value, id],
+                )
+                .map_err(|e| format!("更新 {}.{} 失败: {}", table, column, e))?;
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// 加密数据库中已有的明文凭证（一次性迁移）
+///
+/// - `provider_pool_credentials` 的凭证 JSON 和缓存 token 从明文加密
+/// - `api_keys` 中旧版 XOR 混淆的 API Key 先还原再加密
+pub fn encrypt_existing_secrets(
+    conn: &Connection,
+    cipher: &crate::database::secrets::SecretCipher,
+) -> Result<usize, String> {
+    use crate::database::secrets::{decrypt_legacy_xor, is_encrypted};
+
+    // 检查是否已经迁移过
+    let migrated: bool = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'encrypted_secrets_v1'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .map(|v| v == "true")
+        .unwrap_or(false);
+
+    if migrated {
+        tracing::debug!("[迁移] 凭证已加密，跳过");
+        return Ok(0);
+    }
+
+    tracing::info!("[迁移] 开始加密数据库中的凭证");
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("开始事务失败: {}", e))?;
+
+    let count = rewrite_secret_columns(&tx, |column, id, value| {
+        if is_encrypted(value) {
+            return Ok(None);
+        }
+        let plaintext = if column == "api_key_encrypted" {
+            decrypt_legacy_xor(value).unwrap_or_else(|_| value.to_string())
+        } else {
+            value.to_string()
+        };
+        Ok(Some(cipher.encrypt(&plaintext, id)))
+    })?;
+
+    // 标记迁移完成
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('encrypted_secrets_v1', 'true')",
+        [],
+    )
+    .map_err(|e| format!("标记迁移完成失败: {}", e))?;
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+
+    tracing::info!("[迁移] 凭证加密完成，共加密 {} 个字段", count);
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::secrets::{is_encrypted, rekey_with, KeySource, SecretCipher};
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn raw_value(conn: &Connection, sql: &str) -> String {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_existing_secrets_and_rekey() {
+        let conn = setup_test_db();
+
+        // 旧版本写入的明文凭证 + 明文 token 缓存
+        conn.execute(
+            "INSERT INTO provider_pool_credentials
+             (uuid, provider_type, credential_data, cached_access_token, cached_refresh_token,
+              created_at, updated_at)
+             VALUES ('c1', 'openai', '{\"api_key\":\"sk-plaintext\"}',
+                     'access-plain', 'refresh-plain', 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        // 旧版 XOR 混淆的 API Key（"sk-" 前缀无法 Base64 解码，按明文处理）
+        conn.execute(
+            "INSERT INTO api_key_providers (id, name, type, api_host, group_name, created_at, updated_at)
+             VALUES ('openai', 'OpenAI', 'openai', 'https://api.openai.com', 'mainstream',
+                     '2026-01-01T00:00:00Z', '2026-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO api_keys (id, provider_id, api_key_encrypted, created_at)
+             VALUES ('k1', 'openai', 'sk-legacy-plain', '2026-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+
+        let cipher = SecretCipher::from_key(&[3u8; 32]);
+        assert_eq!(encrypt_existing_secrets(&conn, &cipher).unwrap(), 4);
+        // 再次执行时跳过
+        assert_eq!(encrypt_existing_secrets(&conn, &cipher).unwrap(), 0);
+
+        let credential_data = raw_value(
+            &conn,
+            "SELECT credential_data FROM provider_pool_credentials",
+        );
+        assert!(is_encrypted(&credential_data));
+        assert!(!credential_data.contains("sk-plaintext"));
+        assert!(cipher
+            .decrypt(&credential_data, "c1")
+            .unwrap()
+            .contains("sk-plaintext"));
+
+        let refresh = raw_value(
+            &conn,
+            "SELECT cached_refresh_token FROM provider_pool_credentials",
+        );
+        assert_eq!(cipher.decrypt(&refresh, "c1").unwrap(), "refresh-plain");
+
+        let api_key = raw_value(&conn, "SELECT api_key_encrypted FROM api_keys");
+        assert_eq!(cipher.decrypt(&api_key, "k1").unwrap(), "sk-legacy-plain");
+        // 密文与所在行绑定
+        assert!(cipher.decrypt(&api_key, "c1").is_err());
+
+        // 换钥为主密码
+        let (new_cipher, summary) = rekey_with(
+            &conn,
+            &cipher,
+            KeySource::Passphrase("correct horse".to_string()),
+        )
+        .unwrap();
+        assert_eq!(summary.key_source, "passphrase");
+        assert_eq!(summary.reencrypted_fields, 4);
+
+        let api_key = raw_value(&conn, "SELECT api_key_encrypted FROM api_keys");
+        assert!(cipher.decrypt(&api_key, "k1").is_err());
+        assert_eq!(
+            new_cipher.decrypt(&api_key, "k1").unwrap(),
+            "sk-legacy-plain"
+        );
+
+        let kdf = raw_value(
+            &conn,
+            "SELECT value FROM settings WHERE key = 'secrets_kdf'",
+        );
+        assert_eq!(kdf, "passphrase");
+    }
+}
@@ -1,6 +1,7 @@
 pub mod dao;
 pub mod migration;
 pub mod schema;
+pub mod secrets;
 pub mod system_providers;
 
 use rusqlite::Connection;
@@ -55,5 +56,20 @@ pub fn init_database() -> Result<DbConnection, String> {
         }
     }
 
+    // 初始化凭证加密（密钥来自主密码或本地密钥文件）
+    let cipher = secrets::init(&conn)?;
+
+    // 加密已有的明文凭证
+    match migration::encrypt_existing_secrets(&conn, &cipher) {
+        Ok(count) => {
+            if count > 0 {
+                tracing::info!("[数据库] 已加密 {} 个凭证字段", count);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("[数据库] 凭证加密迁移失败（非致命）: {}", e);
+        }
+    }
+
     Ok(Arc::new(Mutex::new(conn)))
 }
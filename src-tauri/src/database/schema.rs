@@ -60,6 +60,108 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
         [],
     )?;
 
+    // 客户端 API Key 表（多租户访问控制）
+    // 仅保存 key 的 SHA-256 哈希，明文只在创建时返回一次
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS client_api_keys (
+            id TEXT PRIMARY KEY,
+            label TEXT NOT NULL,
+            key_hash TEXT NOT NULL UNIQUE,
+            key_prefix TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            expires_at TEXT,
+            allowed_models TEXT,
+            rpm_limit INTEGER,
+            tpm_limit INTEGER,
+            monthly_token_budget INTEGER,
+            usage_month TEXT,
+            month_tokens INTEGER NOT NULL DEFAULT 0,
+            request_count INTEGER NOT NULL DEFAULT 0,
+            last_used_at TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // 响应缓存表
+    // 按规范化请求的 SHA-256 缓存上游响应（JSON 或原始 SSE 字节）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS response_cache (
+            cache_key TEXT PRIMARY KEY,
+            endpoint TEXT NOT NULL,
+            model TEXT NOT NULL,
+            is_stream INTEGER NOT NULL DEFAULT 0,
+            status_code INTEGER NOT NULL,
+            content_type TEXT NOT NULL,
+            body BLOB NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            hit_count INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            last_hit_at TEXT
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_response_cache_expires ON response_cache(expires_at)",
+        [],
+    )?;
+
+    // 冷却状态表（限流、配额耗尽和风控冷却），重启后恢复
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cooldown_state (
+            scope TEXT NOT NULL,
+            key TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (scope, key)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_cooldown_state_expires ON cooldown_state(expires_at)",
+        [],
+    )?;
+
+    // 预算花费表（按范围、目标和统计周期累计费用）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS budget_spend (
+            scope TEXT NOT NULL,
+            target TEXT NOT NULL,
+            period TEXT NOT NULL,
+            amount REAL NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (scope, target, period)
+        )",
+        [],
+    )?;
+
+    // 凭证健康检查历史（手动检查与定时探测）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS credential_health_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            credential_uuid TEXT NOT NULL,
+            provider_type TEXT NOT NULL,
+            source TEXT NOT NULL,
+            success INTEGER NOT NULL,
+            latency_ms INTEGER NOT NULL,
+            model TEXT,
+            message TEXT,
+            checked_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_credential_health_history_uuid
+         ON credential_health_history(credential_uuid, id)",
+        [],
+    )?;
+
     // Provider UI 状态表
     // _Requirements: 8.4_
     conn.execute(
@@ -237,6 +339,12 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
         [],
     );
 
+    // Migration: 添加凭证权重字段（加权负载均衡）
+    let _ = conn.execute(
+        "ALTER TABLE provider_pool_credentials ADD COLUMN weight INTEGER DEFAULT 1",
+        [],
+    );
+
     // 已安装插件表
     // _需求: 1.2, 1.3_
     conn.execute(
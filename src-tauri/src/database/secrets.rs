@@ -0,0 +1,685 @@
+//! 敏感字段的静态加密
+//!
+//! API Key、OAuth 凭证和缓存的 access/refresh token 在写入 SQLite 前使用
+//! XChaCha20-Poly1305 加密，密文格式为 `enc:v1:` + base64(nonce || ciphertext)。
+//! 加密时以所属凭证 / API Key 的 ID 作为附加认证数据（AAD），
+//! 密文被复制到其他行时无法解密。
+//!
+//! 加密密钥来源（二选一）：
+//! - 主密码：环境变量 `PROXYCAST_MASTER_PASSPHRASE`，通过 Argon2id 派生（salt 存在 settings 表）
+//! - 本地密钥文件：默认 `~/.proxycast/secret.key`，可通过 `PROXYCAST_KEY_FILE` 指定
+//!
+//! 单独拷走 `proxycast.db` 不会泄露任何凭证。
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 密文前缀
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// 主密码环境变量
+pub const MASTER_PASSPHRASE_ENV: &str = "PROXYCAST_MASTER_PASSPHRASE";
+
+/// 密钥文件路径环境变量
+pub const KEY_FILE_ENV: &str = "PROXYCAST_KEY_FILE";
+
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// 用于校验密钥是否正确的固定明文
+const KEY_CHECK_PLAINTEXT: &str = "proxycast-secrets-key-check";
+/// 密钥校验值的 AAD
+const KEY_CHECK_AAD: &str = "secrets_key_check";
+
+const SETTING_KDF: &str = "secrets_kdf";
+const SETTING_SALT: &str = "secrets_kdf_salt";
+const SETTING_KEY_CHECK: &str = "secrets_key_check";
+
+/// 当前进程使用的加密器
+static ACTIVE_CIPHER: Lazy<RwLock<Option<Arc<SecretCipher>>>> = Lazy::new(|| RwLock::new(None));
+
+// ============================================================================
+// 密钥来源
+// ============================================================================
+
+/// 加密密钥来源
+#[derive(Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// 主密码（Argon2id 派生）
+    Passphrase(String),
+    /// 本地密钥文件
+    KeyFile(PathBuf),
+}
+
+impl KeySource {
+    /// 根据环境变量确定密钥来源
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(MASTER_PASSPHRASE_ENV) {
+            Ok(passphrase) if !passphrase.is_empty() => Ok(KeySource::Passphrase(passphrase)),
+            _ => Ok(KeySource::KeyFile(default_key_file_path()?)),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            KeySource::Passphrase(_) => "passphrase",
+            KeySource::KeyFile(_) => "key_file",
+        }
+    }
+}
+
+impl std::fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Passphrase(_) => write!(f, "Passphrase(***)"),
+            KeySource::KeyFile(path) => write!(f, "KeyFile({:?})", path),
+        }
+    }
+}
+
+/// 默认密钥文件路径
+pub fn default_key_file_path() -> Result<PathBuf, String> {
+    if let Ok(path) = std::env::var(KEY_FILE_ENV) {
+        if !path.is_empty() {
+            return Ok(PathBuf::from(path));
+        }
+    }
+    let home = dirs::home_dir().ok_or_else(|| "无法获取主目录".to_string())?;
+    Ok(home.join(".proxycast").join("secret.key"))
+}
+
+// ============================================================================
+// 加密器
+// ============================================================================
+
+/// 敏感字段加密器
+pub struct SecretCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for SecretCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretCipher(***)")
+    }
+}
+
+impl SecretCipher {
+    /// 使用原始密钥创建
+    pub fn from_key(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// 使用主密码创建（Argon2id）
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, String> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("主密码派生密钥失败: {}", e))?;
+        Ok(Self::from_key(&key))
+    }
+
+    /// 使用密钥文件创建（文件不存在时生成）
+    pub fn from_key_file(path: &Path) -> Result<Self, String> {
+        let key = if path.exists() {
+            read_key_file(path)?
+        } else {
+            let key = generate_key();
+            write_key_file(path, &key)?;
+            tracing::info!("[SECRETS] 已生成本地密钥文件: {:?}", path);
+            key
+        };
+        Ok(Self::from_key(&key))
+    }
+
+    /// 加密字符串，`aad` 为所属记录的 ID
+    pub fn encrypt(&self, plaintext: &str, aad: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: aad.as_bytes(),
+        };
+        // 只有明文超过 XChaCha20-Poly1305 的长度上限（约 256 GiB）时才会失败
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .expect("plaintext exceeds XChaCha20-Poly1305 length limit");
+
+        let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(payload))
+    }
+
+    /// 解密字符串（未加密的值原样返回），`aad` 须与加密时一致
+    pub fn decrypt(&self, value: &str, aad: &str) -> Result<String, String> {
+        let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(value.to_string());
+        };
+
+        let payload = BASE64
+            .decode(encoded)
+            .map_err(|e| format!("密文 Base64 解码失败: {}", e))?;
+        if payload.len() < NONCE_LEN {
+            return Err("密文长度无效".to_string());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: aad.as_bytes(),
+        };
+        let plaintext = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| "解密失败：密钥不匹配或数据已损坏".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| format!("UTF-8 解码失败: {}", e))
+    }
+}
+
+/// 值是否为本模块生成的密文
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+fn generate_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+fn read_key_file(path: &Path) -> Result<[u8; KEY_LEN], String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取密钥文件 {:?} 失败: {}", path, e))?;
+    let bytes = hex::decode(content.trim()).map_err(|e| format!("密钥文件格式无效: {}", e))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("密钥文件长度无效，应为 {} 字节", KEY_LEN))
+}
+
+/// 写入密钥文件
+///
+/// 先在同目录下以 0600 权限新建临时文件（Unix），写入并落盘后再重命名为目标文件，
+/// 密钥内容不会以默认权限出现在磁盘上，也不会留下写了一半的文件。
+fn write_key_file(path: &Path, key: &[u8; KEY_LEN]) -> Result<(), String> {
+    use std::io::Write;
+
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(parent).map_err(|e| format!("创建目录 {:?} 失败: {}", parent, e))?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("密钥文件路径无效: {:?}", path))?
+        .to_string_lossy();
+    let tmp_path = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(hex::encode(key).as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp_path, path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(format!("写入密钥文件 {:?} 失败: {}", path, e));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// 全局加密器
+// ============================================================================
+
+/// 设置当前进程使用的加密器
+pub fn install(cipher: Arc<SecretCipher>) {
+    *ACTIVE_CIPHER.write() = Some(cipher);
+}
+
+/// 获取当前进程使用的加密器
+pub fn active() -> Option<Arc<SecretCipher>> {
+    ACTIVE_CIPHER.read().clone()
+}
+
+/// 加密待写入数据库的字段，`id` 为所属凭证 / API Key 的 ID
+///
+/// 未初始化加密器时返回错误，不会以明文写入数据库。
+pub fn encrypt_field(value: &str, id: &str) -> Result<String, String> {
+    match active() {
+        Some(cipher) => Ok(cipher.encrypt(value, id)),
+        None => {
+            tracing::error!("[SECRETS] 加密器未初始化，拒绝以明文保存凭证: id={}", id);
+            Err("加密器未初始化，拒绝以明文保存凭证".to_string())
+        }
+    }
+}
+
+/// 解密从数据库读取的字段（兼容尚未迁移的明文）
+pub fn decrypt_field(value: &str, id: &str) -> Result<String, String> {
+    if !is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    active()
+        .ok_or_else(|| "加密器未初始化，无法解密凭证".to_string())?
+        .decrypt(value, id)
+}
+
+/// DAO 写入辅助：加密失败转换为 rusqlite 错误
+pub fn encrypt_column(value: &str, id: &str) -> Result<String, rusqlite::Error> {
+    encrypt_field(value, id).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))
+}
+
+/// DAO 读取辅助：解密失败转换为 rusqlite 错误
+pub fn decrypt_column(value: &str, id: &str, index: usize) -> Result<String, rusqlite::Error> {
+    decrypt_field(value, id).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+/// 安装固定密钥的测试加密器
+///
+/// 加密器是进程级全局状态，测试并行运行时只能共用同一把密钥
+#[cfg(test)]
+pub fn install_test_cipher() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| install(Arc::new(SecretCipher::from_key(&[42u8; KEY_LEN]))));
+}
+
+// ============================================================================
+// 初始化与换钥
+// ============================================================================
+
+fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+        row.get(0)
+    })
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn new_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// 根据密钥来源构建加密器，返回加密器和需要保存的 salt
+fn build_cipher(
+    source: &KeySource,
+    salt: Option<Vec<u8>>,
+) -> Result<(SecretCipher, Option<Vec<u8>>), String> {
+    match source {
+        KeySource::Passphrase(passphrase) => {
+            let salt = salt.unwrap_or_else(new_salt);
+            let cipher = SecretCipher::from_passphrase(passphrase, &salt)?;
+            Ok((cipher, Some(salt)))
+        }
+        KeySource::KeyFile(path) => Ok((SecretCipher::from_key_file(path)?, None)),
+    }
+}
+
+/// 用已保存的校验值确认密钥正确
+fn verify_key(conn: &Connection, cipher: &SecretCipher) -> Result<bool, String> {
+    match get_setting(conn, SETTING_KEY_CHECK)? {
+        Some(check) => Ok(cipher
+            .decrypt(&check, KEY_CHECK_AAD)
+            .map(|v| v == KEY_CHECK_PLAINTEXT)
+            .unwrap_or(false)),
+        None => Ok(true),
+    }
+}
+
+fn save_key_metadata(
+    conn: &Connection,
+    source: &KeySource,
+    cipher: &SecretCipher,
+    salt: Option<&[u8]>,
+) -> Result<(), String> {
+    set_setting(conn, SETTING_KDF, source.kind())?;
+    match salt {
+        Some(salt) => set_setting(conn, SETTING_SALT, &hex::encode(salt))?,
+        None => {
+            conn.execute("DELETE FROM settings WHERE key = ?1", [SETTING_SALT])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    set_setting(
+        conn,
+        SETTING_KEY_CHECK,
+        &cipher.encrypt(KEY_CHECK_PLAINTEXT, KEY_CHECK_AAD),
+    )
+}
+
+/// 为已有数据库初始化加密器并设置为当前进程的加密器
+///
+/// - 首次运行时按环境变量选择密钥来源并保存元数据
+/// - 数据库已使用主密码加密但未提供主密码时返回错误
+/// - 数据库使用密钥文件加密而环境变量提供了主密码时，自动换钥为主密码
+pub fn init(conn: &Connection) -> Result<Arc<SecretCipher>, String> {
+    let requested = KeySource::from_env()?;
+    let stored_kind = get_setting(conn, SETTING_KDF)?;
+
+    let cipher = match stored_kind.as_deref() {
+        None => {
+            let (cipher, salt) = build_cipher(&requested, None)?;
+            save_key_metadata(conn, &requested, &cipher, salt.as_deref())?;
+            cipher
+        }
+        Some("passphrase") if matches!(requested, KeySource::KeyFile(_)) => {
+            return Err(format!(
+                "数据库中的凭证已使用主密码加密，请设置环境变量 {} 后重新启动",
+                MASTER_PASSPHRASE_ENV
+            ));
+        }
+        Some("key_file") if matches!(requested, KeySource::Passphrase(_)) => {
+            let old_cipher = load_key_file_cipher(conn)?;
+            let (cipher, summary) = rekey_with(conn, &old_cipher, requested)?;
+            tracing::info!(
+                "[SECRETS] 已从密钥文件切换为主密码加密，重新加密 {} 个字段",
+                summary.reencrypted_fields
+            );
+            cipher
+        }
+        Some("key_file") => load_key_file_cipher(conn)?,
+        Some(_) => {
+            let salt = get_setting(conn, SETTING_SALT)?
+                .map(|s| hex::decode(s).map_err(|e| format!("salt 格式无效: {}", e)))
+                .transpose()?;
+            let (cipher, _) = build_cipher(&requested, salt)?;
+            if !verify_key(conn, &cipher)? {
+                return Err("主密码错误，无法解密数据库中的凭证".to_string());
+            }
+            cipher
+        }
+    };
+
+    let cipher = Arc::new(cipher);
+    install(cipher.clone());
+    Ok(cipher)
+}
+
+/// 加载已存在的本地密钥文件并校验
+fn load_key_file_cipher(conn: &Connection) -> Result<SecretCipher, String> {
+    let path = default_key_file_path()?;
+    if !path.exists() {
+        return Err(format!(
+            "数据库中的凭证已使用本地密钥文件加密，但密钥文件 {:?} 不存在",
+            path
+        ));
+    }
+    let cipher = SecretCipher::from_key_file(&path)?;
+    if !verify_key(conn, &cipher)? {
+        return Err(format!("密钥文件 {:?} 与数据库不匹配，无法解密凭证", path));
+    }
+    Ok(cipher)
+}
+
+/// 换钥结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RekeySummary {
+    /// 新的密钥来源（passphrase / key_file）
+    pub key_source: String,
+    /// 重新加密的字段数
+    pub reencrypted_fields: usize,
+}
+
+/// 使用新的密钥来源重新加密所有敏感字段，并切换当前进程的加密器
+pub fn rekey(conn: &Connection, new_source: KeySource) -> Result<RekeySummary, String> {
+    let old_cipher = active().ok_or_else(|| "加密器未初始化".to_string())?;
+    let (new_cipher, summary) = rekey_with(conn, &old_cipher, new_source)?;
+    install(Arc::new(new_cipher));
+    Ok(summary)
+}
+
+/// 使用新的密钥来源重新加密所有敏感字段
+///
+/// 所有字段在一个事务中解密并重新加密；切换到密钥文件时，新密钥先写入临时文件，
+/// 事务提交后再替换正式文件，避免中途失败导致数据无法解密。
+pub fn rekey_with(
+    conn: &Connection,
+    old_cipher: &SecretCipher,
+    new_source: KeySource,
+) -> Result<(SecretCipher, RekeySummary), String> {
+    let (new_cipher, salt, pending_key_file) = match &new_source {
+        KeySource::Passphrase(passphrase) => {
+            if passphrase.is_empty() {
+                return Err("主密码不能为空".to_string());
+            }
+            let salt = new_salt();
+            let cipher = SecretCipher::from_passphrase(passphrase, &salt)?;
+            (cipher, Some(salt), None)
+        }
+        KeySource::KeyFile(path) => {
+            let key = generate_key();
+            let tmp_path = path.with_extension("key.tmp");
+            write_key_file(&tmp_path, &key)?;
+            (
+                SecretCipher::from_key(&key),
+                None,
+                Some((tmp_path, path.clone())),
+            )
+        }
+    };
+
+    let result = (|| {
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        // 只处理已加密的值，尚未迁移的值保持原样
+        let count = super::migration::rewrite_secret_columns(&tx, |_, id, value| {
+            if !is_encrypted(value) {
+                return Ok(None);
+            }
+            let plaintext = old_cipher.decrypt(value, id)?;
+            Ok(Some(new_cipher.encrypt(&plaintext, id)))
+        })?;
+        save_key_metadata(&tx, &new_source, &new_cipher, salt.as_deref())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok::<usize, String>(count)
+    })();
+
+    let count = match result {
+        Ok(count) => count,
+        Err(e) => {
+            if let Some((tmp_path, _)) = &pending_key_file {
+                let _ = std::fs::remove_file(tmp_path);
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some((tmp_path, path)) = pending_key_file {
+        std::fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("替换密钥文件失败（新密钥保存在 {:?}）: {}", tmp_path, e))?;
+    }
+
+    tracing::info!(
+        "[SECRETS] 换钥完成: source={} fields={}",
+        new_source.kind(),
+        count
+    );
+
+    Ok((
+        new_cipher,
+        RekeySummary {
+            key_source: new_source.kind().to_string(),
+            reencrypted_fields: count,
+        },
+    ))
+}
+
+// ============================================================================
+// 旧版 XOR 混淆（仅用于迁移）
+// ============================================================================
+
+/// 获取机器 ID（与旧版 API Key 混淆使用的密钥来源保持一致）
+fn legacy_machine_id() -> String {
+    if let Ok(id) = std::fs::read_to_string("/etc/machine-id") {
+        return id.trim().to_string();
+    }
+    if let Ok(id) = std::fs::read_to_string("/var/lib/dbus/machine-id") {
+        return id.trim().to_string();
+    }
+    // macOS: 使用 IOPlatformUUID
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(output) = std::process::Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+        {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                if line.contains("IOPlatformUUID") {
+                    if let Some(uuid) = line.split('"').nth(3) {
+                        return uuid.to_string();
+                    }
+                }
+            }
+        }
+    }
+    "proxycast-default-machine-id".to_string()
+}
+
+/// 解密旧版本的 XOR + Base64 混淆值
+pub fn decrypt_legacy_xor(value: &str) -> Result<String, String> {
+    static LEGACY_KEY: Lazy<Vec<u8>> = Lazy::new(|| {
+        let mut hasher = Sha256::new();
+        hasher.update(legacy_machine_id().as_bytes());
+        hasher.update(b"proxycast-api-key-encryption-salt");
+        hasher.finalize().to_vec()
+    });
+
+    let encrypted = BASE64
+        .decode(value)
+        .map_err(|e| format!("Base64 解码失败: {}", e))?;
+    let decrypted: Vec<u8> = encrypted
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ LEGACY_KEY[i % LEGACY_KEY.len()])
+        .collect();
+    String::from_utf8(decrypted).map_err(|e| format!("UTF-8 解码失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let cipher = SecretCipher::from_key(&[7u8; KEY_LEN]);
+        let encrypted = cipher.encrypt("sk-secret", "cred-1");
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("sk-secret"));
+        assert_eq!(cipher.decrypt(&encrypted, "cred-1").unwrap(), "sk-secret");
+
+        // 随机 nonce：同一明文两次加密结果不同
+        assert_ne!(cipher.encrypt("sk-secret", "cred-1"), encrypted);
+        // 明文原样返回
+        assert_eq!(cipher.decrypt("plain", "cred-1").unwrap(), "plain");
+    }
+
+    #[test]
+    fn test_ciphertext_bound_to_record_id() {
+        let cipher = SecretCipher::from_key(&[3u8; KEY_LEN]);
+        let encrypted = cipher.encrypt("sk-secret", "cred-1");
+        // 密文复制到其他凭证行后无法解密
+        assert!(cipher.decrypt(&encrypted, "cred-2").is_err());
+        assert!(cipher.decrypt(&encrypted, "").is_err());
+    }
+
+    #[test]
+    fn test_wrong_key_and_tampering_fail() {
+        let cipher = SecretCipher::from_key(&[1u8; KEY_LEN]);
+        let other = SecretCipher::from_key(&[2u8; KEY_LEN]);
+        let encrypted = cipher.encrypt("token", "id");
+        assert!(other.decrypt(&encrypted, "id").is_err());
+
+        let mut payload = BASE64
+            .decode(encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap())
+            .unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 0x01;
+        let tampered = format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(payload));
+        assert!(cipher.decrypt(&tampered, "id").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_derivation() {
+        let salt = [9u8; SALT_LEN];
+        let a = SecretCipher::from_passphrase("correct horse", &salt).unwrap();
+        let b = SecretCipher::from_passphrase("correct horse", &salt).unwrap();
+        let c = SecretCipher::from_passphrase("wrong", &salt).unwrap();
+        let encrypted = a.encrypt("refresh-token", "id");
+        assert_eq!(b.decrypt(&encrypted, "id").unwrap(), "refresh-token");
+        assert!(c.decrypt(&encrypted, "id").is_err());
+    }
+
+    #[test]
+    fn test_key_file_created_and_reused() {
+        let dir = std::env::temp_dir().join(format!("proxycast-secrets-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("secret.key");
+        let first = SecretCipher::from_key_file(&path).unwrap();
+        assert!(path.exists());
+        let second = SecretCipher::from_key_file(&path).unwrap();
+        let encrypted = first.encrypt("value", "id");
+        assert_eq!(second.decrypt(&encrypted, "id").unwrap(), "value");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // 只留下密钥文件，没有残留的临时文件
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_legacy_xor_roundtrip() {
+        let key = {
+            let mut hasher = Sha256::new();
+            hasher.update(legacy_machine_id().as_bytes());
+            hasher.update(b"proxycast-api-key-encryption-salt");
+            hasher.finalize().to_vec()
+        };
+        let obfuscated: Vec<u8> = "sk-legacy"
+            .bytes()
+            .enumerate()
+            .map(|(i, b)| b ^ key[i % key.len()])
+            .collect();
+        assert_eq!(
+            decrypt_legacy_xor(&BASE64.encode(obfuscated)).unwrap(),
+            "sk-legacy"
+        );
+    }
+}
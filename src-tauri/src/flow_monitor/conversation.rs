@@ -0,0 +1,918 @@
+//! 对话串联
+//!
+//! Agent 客户端每一轮都会重发完整的消息历史，因此同一个任务会产生大量独立的 Flow。
+//! 本模块按消息前缀关系把这些 Flow 串成对话树：
+//!
+//! - 延续：请求消息以某个 Flow 的「请求消息 + 响应」开头，该 Flow 即为父节点
+//! - 分支：编辑了历史消息后重发，新请求只能匹配到更早的节点，形成同一父节点下的多个子节点
+//! - 重试：短时间内请求消息完全相同，与原始 Flow 共享父节点，并记录 `retry_of`
+//!
+//! 消息指纹只取角色、文本（忽略空白）、工具调用 / 工具结果 ID 和图片数量，
+//! 忽略 `cache_control`、思维链等客户端重发时可能变化的字段。
+//! 串联状态只保存在内存中，重启后新的请求会开启新的对话。
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+use super::models::{FlowState, LLMFlow, LLMRequest, LLMResponse, MessageRole};
+use crate::models::model_registry::ModelPricing;
+
+/// 串联状态最多保留的指纹数量
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// 请求完全相同时视为重试的时间窗口（秒）
+const RETRY_WINDOW_SECS: i64 = 600;
+
+/// 对话标题的最大长度（字符）
+const TITLE_MAX_CHARS: usize = 100;
+
+type Digest = [u8; 32];
+
+// ============================================================================
+// Flow 上的对话信息
+// ============================================================================
+
+/// Flow 与上一个 Flow 的关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversationRelation {
+    /// 对话的第一个请求
+    Root,
+    /// 在父节点的响应之后继续对话
+    Continuation,
+    /// 重试（请求与 `retry_of` 完全相同）
+    Retry,
+}
+
+/// Flow 所属对话及其在对话树中的位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationLink {
+    /// 对话 ID（根 Flow 的 ID）
+    pub conversation_id: String,
+    /// 父 Flow ID（根节点为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_flow_id: Option<String>,
+    /// 与父节点的关系
+    pub relation: ConversationRelation,
+    /// 轮次（根节点为 1）
+    pub turn: u32,
+    /// 重试的原始 Flow ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_of: Option<String>,
+}
+
+// ============================================================================
+// 串联追踪器
+// ============================================================================
+
+/// 已登记的 Flow
+#[derive(Debug, Clone)]
+struct TrackedFlow {
+    flow_id: String,
+    conversation_id: String,
+    parent_flow_id: Option<String>,
+    turn: u32,
+    seen_at: DateTime<Utc>,
+}
+
+impl TrackedFlow {
+    fn link(&self, relation: ConversationRelation, retry_of: Option<String>) -> ConversationLink {
+        ConversationLink {
+            conversation_id: self.conversation_id.clone(),
+            parent_flow_id: self.parent_flow_id.clone(),
+            relation,
+            turn: self.turn,
+            retry_of,
+        }
+    }
+}
+
+/// 请求登记凭据，Flow 完成后用于登记响应
+#[derive(Debug, Clone)]
+pub struct ConversationKey {
+    digest: Digest,
+    flow: TrackedFlow,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Request,
+    Reply,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    /// 请求消息指纹 -> Flow
+    requests: HashMap<Digest, TrackedFlow>,
+    /// 请求消息 + 响应指纹 -> Flow
+    replies: HashMap<Digest, TrackedFlow>,
+    /// 登记顺序（用于淘汰最早的指纹）
+    order: VecDeque<(Slot, Digest)>,
+}
+
+impl TrackerState {
+    fn insert(&mut self, slot: Slot, digest: Digest, flow: TrackedFlow, capacity: usize) {
+        let map = match slot {
+            Slot::Request => &mut self.requests,
+            Slot::Reply => &mut self.replies,
+        };
+        if map.insert(digest, flow).is_none() {
+            self.order.push_back((slot, digest));
+        }
+        while self.order.len() > capacity {
+            match self.order.pop_front() {
+                Some((Slot::Request, d)) => self.requests.remove(&d),
+                Some((Slot::Reply, d)) => self.replies.remove(&d),
+                None => break,
+            };
+        }
+    }
+}
+
+/// 对话串联追踪器
+///
+/// 请求开始时调用 [`link_request`](Self::link_request) 确定所属对话，
+/// 请求完成后调用 [`record_reply`](Self::record_reply) 登记响应，供下一轮请求匹配。
+pub struct ConversationTracker {
+    state: Mutex<TrackerState>,
+    capacity: usize,
+}
+
+impl Default for ConversationTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationTracker {
+    /// 创建追踪器
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// 创建指定容量的追踪器
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(TrackerState::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// 为新请求确定所属对话
+    ///
+    /// 请求中没有消息（如 Embeddings）时返回 None
+    pub fn link_request(
+        &self,
+        flow_id: &str,
+        request: &LLMRequest,
+        client_key_id: Option<&str>,
+    ) -> Option<(ConversationLink, ConversationKey)> {
+        self.link_request_at(flow_id, request, client_key_id, Utc::now())
+    }
+
+    fn link_request_at(
+        &self,
+        flow_id: &str,
+        request: &LLMRequest,
+        client_key_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Option<(ConversationLink, ConversationKey)> {
+        let messages = request_message_digests(request);
+        if messages.is_empty() {
+            return None;
+        }
+
+        // 逐条累积的前缀指纹：prefixes[k - 1] 对应前 k 条消息
+        let mut prefixes = Vec::with_capacity(messages.len());
+        let mut digest = seed_digest(client_key_id, request.system_prompt.as_deref());
+        for message in &messages {
+            digest = chain(&digest, message);
+            prefixes.push(digest);
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(original) = state.requests.get_mut(&digest) {
+            if now - original.seen_at <= Duration::seconds(RETRY_WINDOW_SECS) {
+                original.seen_at = now;
+                let link =
+                    original.link(ConversationRelation::Retry, Some(original.flow_id.clone()));
+                let flow = TrackedFlow {
+                    flow_id: flow_id.to_string(),
+                    seen_at: now,
+                    ..original.clone()
+                };
+                return Some((link, ConversationKey { digest, flow }));
+            }
+        }
+
+        // 最长前缀优先；同一长度下优先匹配「请求 + 响应」
+        let n = prefixes.len();
+        let parent = (1..=n).rev().find_map(|k| {
+            let prefix = &prefixes[k - 1];
+            state
+                .replies
+                .get(prefix)
+                .or_else(|| (k < n).then(|| state.requests.get(prefix)).flatten())
+                .cloned()
+        });
+
+        let (flow, relation) = match parent {
+            Some(parent) => (
+                TrackedFlow {
+                    flow_id: flow_id.to_string(),
+                    conversation_id: parent.conversation_id,
+                    parent_flow_id: Some(parent.flow_id),
+                    turn: parent.turn + 1,
+                    seen_at: now,
+                },
+                ConversationRelation::Continuation,
+            ),
+            None => (
+                TrackedFlow {
+                    flow_id: flow_id.to_string(),
+                    conversation_id: flow_id.to_string(),
+                    parent_flow_id: None,
+                    turn: 1,
+                    seen_at: now,
+                },
+                ConversationRelation::Root,
+            ),
+        };
+
+        state.insert(Slot::Request, digest, flow.clone(), self.capacity);
+        let link = flow.link(relation, None);
+        Some((link, ConversationKey { digest, flow }))
+    }
+
+    /// 登记 Flow 的响应，之后以「请求 + 响应」开头的请求会延续该 Flow
+    pub fn record_reply(&self, key: &ConversationKey, response: &LLMResponse) {
+        let Some(reply) = response_digest(response) else {
+            return;
+        };
+        let digest = chain(&key.digest, &reply);
+        self.state
+            .lock()
+            .unwrap()
+            .insert(Slot::Reply, digest, key.flow.clone(), self.capacity);
+    }
+}
+
+// ============================================================================
+// 消息指纹
+// ============================================================================
+
+/// 消息中参与指纹计算的部分
+#[derive(Default)]
+struct MessageParts {
+    text: String,
+    tool_ids: Vec<String>,
+    images: usize,
+}
+
+impl MessageParts {
+    fn push_text(&mut self, text: &str) {
+        // 去掉全部空白，避免文本块拆分、拼接方式不同导致指纹不一致
+        self.text
+            .extend(text.split_whitespace().flat_map(str::chars));
+    }
+
+    fn push_id(&mut self, id: Option<&str>) {
+        if let Some(id) = id.filter(|id| !id.is_empty()) {
+            self.tool_ids.push(id.to_string());
+        }
+    }
+
+    fn push_content(&mut self, content: &Value) {
+        match content {
+            Value::String(text) => self.push_text(text),
+            Value::Array(blocks) => blocks.iter().for_each(|b| self.push_block(b)),
+            _ => {}
+        }
+    }
+
+    fn push_block(&mut self, block: &Value) {
+        let str_field = |name: &str| block.get(name).and_then(|v| v.as_str());
+        match str_field("type") {
+            Some("text") => self.push_text(str_field("text").unwrap_or_default()),
+            Some("tool_use") | Some("server_tool_use") => self.push_id(str_field("id")),
+            Some("tool_result") => self.push_id(str_field("tool_use_id")),
+            Some("image") | Some("image_url") | Some("document") | Some("input_image") => {
+                self.images += 1
+            }
+            Some(_) => {}
+            // Gemini parts 没有 type 字段
+            None => {
+                if let Some(text) = str_field("text") {
+                    self.push_text(text);
+                } else if let Some(call) = block.get("functionCall") {
+                    self.push_id(call.get("name").and_then(|v| v.as_str()));
+                } else if let Some(result) = block.get("functionResponse") {
+                    self.push_id(result.get("name").and_then(|v| v.as_str()));
+                } else if block.get("inlineData").is_some() || block.get("fileData").is_some() {
+                    self.images += 1;
+                }
+            }
+        }
+    }
+
+    fn digest(&self, role: &str) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(role.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.text.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.tool_ids.join("\x1f").as_bytes());
+        hasher.update([0]);
+        hasher.update(self.images.to_le_bytes());
+        hasher.finalize().into()
+    }
+}
+
+fn normalize_role(role: &str) -> &str {
+    match role {
+        "model" => "assistant",
+        "function" => "tool",
+        other => other,
+    }
+}
+
+/// 计算请求中每条消息的指纹
+///
+/// 优先使用原始请求体（保留工具调用 / 工具结果块），否则使用解析后的消息
+fn request_message_digests(request: &LLMRequest) -> Vec<Digest> {
+    if let Some(messages) = request.body.get("messages").and_then(|v| v.as_array()) {
+        return messages
+            .iter()
+            .map(|message| {
+                let mut parts = MessageParts::default();
+                if let Some(content) = message.get("content") {
+                    parts.push_content(content);
+                }
+                if let Some(calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+                    for call in calls {
+                        parts.push_id(call.get("id").and_then(|v| v.as_str()));
+                    }
+                }
+                parts.push_id(message.get("tool_call_id").and_then(|v| v.as_str()));
+                let role = message.get("role").and_then(|v| v.as_str());
+                parts.digest(normalize_role(role.unwrap_or("user")))
+            })
+            .collect();
+    }
+
+    if let Some(contents) = request.body.get("contents").and_then(|v| v.as_array()) {
+        return contents
+            .iter()
+            .map(|content| {
+                let mut parts = MessageParts::default();
+                if let Some(blocks) = content.get("parts") {
+                    parts.push_content(blocks);
+                }
+                let role = content.get("role").and_then(|v| v.as_str());
+                parts.digest(normalize_role(role.unwrap_or("user")))
+            })
+            .collect();
+    }
+
+    request
+        .messages
+        .iter()
+        .map(|message| {
+            let mut parts = MessageParts::default();
+            parts.push_text(&message.content.get_all_text());
+            for call in message.tool_calls.iter().flatten() {
+                parts.push_id(Some(&call.id));
+            }
+            if let Some(result) = &message.tool_result {
+                parts.push_id(Some(&result.tool_call_id));
+            }
+            let role = match message.role {
+                MessageRole::System => "system",
+                MessageRole::User => "user",
+                MessageRole::Assistant => "assistant",
+                MessageRole::Tool | MessageRole::Function => "tool",
+            };
+            parts.digest(role)
+        })
+        .collect()
+}
+
+/// 计算响应（作为下一轮请求中的 assistant 消息）的指纹，响应为空时返回 None
+fn response_digest(response: &LLMResponse) -> Option<Digest> {
+    let mut parts = MessageParts::default();
+    parts.push_text(&response.content);
+    for call in &response.tool_calls {
+        parts.push_id(Some(&call.id));
+    }
+    if parts.text.is_empty() && parts.tool_ids.is_empty() {
+        return None;
+    }
+    Some(parts.digest("assistant"))
+}
+
+/// 前缀指纹的起点：不同客户端 key、不同系统提示词的请求不会串到一起
+fn seed_digest(client_key_id: Option<&str>, system_prompt: Option<&str>) -> Digest {
+    let mut parts = MessageParts::default();
+    parts.push_text(system_prompt.unwrap_or_default());
+    let mut hasher = Sha256::new();
+    hasher.update(b"conversation\0");
+    hasher.update(client_key_id.unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(parts.text.as_bytes());
+    hasher.finalize().into()
+}
+
+fn chain(prefix: &Digest, message: &Digest) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(prefix);
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+// ============================================================================
+// 对话汇总
+// ============================================================================
+
+/// 对话树中的一个节点（一个 Flow）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationNode {
+    pub flow_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_flow_id: Option<String>,
+    pub relation: ConversationRelation,
+    pub turn: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_of: Option<String>,
+    /// 子节点 Flow ID（按创建时间排序）
+    pub children: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub model: String,
+    pub state: FlowState,
+    pub duration_ms: u64,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub tool_calls: usize,
+    /// 费用（模型无定价时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// 对话合计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversationTotals {
+    /// Flow 数量
+    pub flows: usize,
+    /// 最大轮次
+    pub turns: u32,
+    /// 分支数（编辑历史消息产生的额外子节点）
+    pub branches: usize,
+    /// 重试次数
+    pub retries: usize,
+    /// 失败的 Flow 数量
+    pub errors: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub total_tokens: u64,
+    /// 按货币汇总的费用
+    pub cost: BTreeMap<String, f64>,
+    /// 模型无定价、未计入费用的 Flow 数量
+    pub unpriced_flows: usize,
+    /// 各 Flow 耗时之和（毫秒）
+    pub duration_ms: u64,
+    /// 从第一个请求开始到最后一个响应结束的时长（毫秒）
+    pub wall_time_ms: u64,
+    /// 工具调用次数
+    pub tool_calls: usize,
+}
+
+/// 对话摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    /// 对话 ID（根 Flow 的 ID）
+    pub id: String,
+    /// 标题（根请求最后一条用户消息的开头）
+    pub title: String,
+    pub started_at: DateTime<Utc>,
+    pub last_activity_at: DateTime<Utc>,
+    /// 使用过的模型
+    pub models: Vec<String>,
+    pub totals: ConversationTotals,
+}
+
+/// 完整对话树
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    #[serde(flatten)]
+    pub summary: ConversationSummary,
+    /// 按创建时间排序的节点
+    pub nodes: Vec<ConversationNode>,
+}
+
+impl Conversation {
+    /// 由同一对话的 Flow 构建对话树，没有 Flow 时返回 None
+    pub fn build<F>(id: &str, mut flows: Vec<LLMFlow>, pricing: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<ModelPricing>,
+    {
+        if flows.is_empty() {
+            return None;
+        }
+        flows.sort_by_key(|f| f.timestamps.created);
+
+        let mut totals = ConversationTotals::default();
+        let mut models = Vec::new();
+        let mut nodes: Vec<ConversationNode> = Vec::with_capacity(flows.len());
+        let mut last_activity_at = flows[0].timestamps.created;
+
+        for flow in &flows {
+            let link = flow.metadata.conversation.clone();
+            let (relation, parent_flow_id, turn, retry_of) = match link {
+                Some(link) => (link.relation, link.parent_flow_id, link.turn, link.retry_of),
+                None => (ConversationRelation::Root, None, 1, None),
+            };
+            let usage = flow
+                .response
+                .as_ref()
+                .map(|r| r.usage.clone())
+                .unwrap_or_default();
+            let tool_calls = flow.response.as_ref().map_or(0, |r| r.tool_calls.len());
+            let cache_read = usage.cache_read_tokens.unwrap_or(0);
+            let cache_write = usage.cache_write_tokens.unwrap_or(0);
+
+            let priced = pricing(&flow.request.model).filter(|p| p.is_priced());
+            let cost = priced.as_ref().map(|p| {
+                p.cost(
+                    usage.input_tokens,
+                    usage.output_tokens,
+                    cache_read,
+                    cache_write,
+                )
+            });
+            match (&cost, &priced) {
+                (Some(cost), Some(p)) => {
+                    *totals.cost.entry(p.currency.clone()).or_default() += cost;
+                }
+                _ => totals.unpriced_flows += 1,
+            }
+
+            totals.flows += 1;
+            totals.turns = totals.turns.max(turn);
+            totals.retries += usize::from(relation == ConversationRelation::Retry);
+            totals.errors += usize::from(flow.state == FlowState::Failed || flow.error.is_some());
+            totals.input_tokens += u64::from(usage.input_tokens);
+            totals.output_tokens += u64::from(usage.output_tokens);
+            totals.cache_read_tokens += u64::from(cache_read);
+            totals.cache_write_tokens += u64::from(cache_write);
+            totals.total_tokens += u64::from(usage.total_tokens);
+            totals.duration_ms += flow.timestamps.duration_ms;
+            totals.tool_calls += tool_calls;
+
+            let ended_at = flow
+                .timestamps
+                .response_end
+                .unwrap_or(flow.timestamps.created);
+            last_activity_at = last_activity_at.max(ended_at);
+            if !models.contains(&flow.request.model) {
+                models.push(flow.request.model.clone());
+            }
+
+            nodes.push(ConversationNode {
+                flow_id: flow.id.clone(),
+                parent_flow_id,
+                relation,
+                turn,
+                retry_of,
+                children: Vec::new(),
+                created_at: flow.timestamps.created,
+                model: flow.request.model.clone(),
+                state: flow.state.clone(),
+                duration_ms: flow.timestamps.duration_ms,
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                tool_calls,
+                cost,
+                currency: priced.map(|p| p.currency),
+            });
+        }
+
+        // 建立父子关系；同一父节点下多个非重试子节点即为分支
+        let index: HashMap<String, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.flow_id.clone(), i))
+            .collect();
+        let mut continuations: HashMap<usize, usize> = HashMap::new();
+        for i in 0..nodes.len() {
+            let Some(parent) = nodes[i].parent_flow_id.as_ref().and_then(|p| index.get(p)) else {
+                continue;
+            };
+            let parent = *parent;
+            if nodes[i].relation == ConversationRelation::Continuation {
+                *continuations.entry(parent).or_default() += 1;
+            }
+            let child = nodes[i].flow_id.clone();
+            nodes[parent].children.push(child);
+        }
+        totals.branches = continuations.values().map(|c| c - 1).sum();
+
+        let started_at = flows[0].timestamps.created;
+        totals.wall_time_ms = (last_activity_at - started_at).num_milliseconds().max(0) as u64;
+
+        let root = flows.iter().find(|f| f.id == id).unwrap_or(&flows[0]);
+        let title = root
+            .request
+            .messages
+            .iter()
+            .rev()
+            .filter(|m| m.role == MessageRole::User)
+            .map(|m| m.content.get_all_text())
+            .find(|text| !text.trim().is_empty())
+            .map(|text| text.trim().chars().take(TITLE_MAX_CHARS).collect())
+            .unwrap_or_default();
+
+        Some(Self {
+            summary: ConversationSummary {
+                id: id.to_string(),
+                title,
+                started_at,
+                last_activity_at,
+                models,
+                totals,
+            },
+            nodes,
+        })
+    }
+}
+
+/// 按最近活动时间提取 Flow 所属的对话 ID（去重）
+pub(crate) fn recent_conversation_ids(flows: &[LLMFlow], limit: usize) -> Vec<String> {
+    let mut latest: HashMap<&str, DateTime<Utc>> = HashMap::new();
+    for flow in flows {
+        if let Some(link) = &flow.metadata.conversation {
+            let at = latest
+                .entry(link.conversation_id.as_str())
+                .or_insert(flow.timestamps.created);
+            *at = (*at).max(flow.timestamps.created);
+        }
+    }
+    let mut ids: Vec<(&str, DateTime<Utc>)> = latest.into_iter().collect();
+    ids.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    ids.into_iter()
+        .take(limit)
+        .map(|(id, _)| id.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::models::{
+        FlowMetadata, FlowType, FunctionCall, Message, MessageContent, TokenUsage, ToolCall,
+    };
+    use serde_json::json;
+
+    fn request(messages: Value) -> LLMRequest {
+        LLMRequest {
+            path: "/v1/messages".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            body: json!({ "model": "claude-sonnet-4-5", "messages": messages }),
+            system_prompt: Some("You are a coding agent.".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn response(content: &str, tool_ids: &[&str]) -> LLMResponse {
+        LLMResponse {
+            content: content.to_string(),
+            tool_calls: tool_ids
+                .iter()
+                .map(|id| ToolCall {
+                    id: id.to_string(),
+                    tool_type: "function".to_string(),
+                    function: FunctionCall {
+                        name: "Read".to_string(),
+                        arguments: "{}".to_string(),
+                    },
+                })
+                .collect(),
+            usage: TokenUsage {
+                input_tokens: 1000,
+                output_tokens: 200,
+                cache_read_tokens: Some(4000),
+                total_tokens: 1200,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn link(
+        tracker: &ConversationTracker,
+        flow_id: &str,
+        messages: Value,
+    ) -> (ConversationLink, ConversationKey) {
+        tracker
+            .link_request(flow_id, &request(messages), None)
+            .expect("request has messages")
+    }
+
+    #[test]
+    fn test_links_tool_use_turns_into_one_conversation() {
+        let tracker = ConversationTracker::new();
+        let (root, key) = link(
+            &tracker,
+            "f1",
+            json!([{ "role": "user", "content": "Fix the failing test" }]),
+        );
+        assert_eq!(root.relation, ConversationRelation::Root);
+        assert_eq!(root.conversation_id, "f1");
+        tracker.record_reply(&key, &response("Let me look at it.", &["toolu_1"]));
+
+        // 客户端重发时附带思维链、拆分文本块并移动 cache_control，指纹不受影响
+        let (next, _) = link(
+            &tracker,
+            "f2",
+            json!([
+                {
+                    "role": "user",
+                    "content": [{
+                        "type": "text",
+                        "text": "Fix the failing test",
+                        "cache_control": { "type": "ephemeral" }
+                    }]
+                },
+                {
+                    "role": "assistant",
+                    "content": [
+                        { "type": "thinking", "thinking": "...", "signature": "sig" },
+                        { "type": "text", "text": "Let me look" },
+                        { "type": "text", "text": " at it." },
+                        { "type": "tool_use", "id": "toolu_1", "name": "Read", "input": {} }
+                    ]
+                },
+                {
+                    "role": "user",
+                    "content": [{ "type": "tool_result", "tool_use_id": "toolu_1", "content": "..." }]
+                }
+            ]),
+        );
+        assert_eq!(next.relation, ConversationRelation::Continuation);
+        assert_eq!(next.conversation_id, "f1");
+        assert_eq!(next.parent_flow_id.as_deref(), Some("f1"));
+        assert_eq!(next.turn, 2);
+    }
+
+    #[test]
+    fn test_retry_and_edit_branch() {
+        let tracker = ConversationTracker::new();
+        let first = json!([{ "role": "user", "content": "hi" }]);
+        let (_, key) = link(&tracker, "a", first.clone());
+        tracker.record_reply(&key, &response("Hello!", &[]));
+
+        let second = json!([
+            { "role": "user", "content": "hi" },
+            { "role": "assistant", "content": "Hello!" },
+            { "role": "user", "content": "write a poem" }
+        ]);
+        let (b, _) = link(&tracker, "b", second.clone());
+        assert_eq!(b.parent_flow_id.as_deref(), Some("a"));
+
+        let (retry, _) = link(&tracker, "b2", second);
+        assert_eq!(retry.relation, ConversationRelation::Retry);
+        assert_eq!(retry.retry_of.as_deref(), Some("b"));
+        assert_eq!(retry.parent_flow_id.as_deref(), Some("a"));
+        assert_eq!(retry.turn, 2);
+
+        // 编辑最后一条消息后重发：仍然延续 a，形成分支
+        let (edited, _) = link(
+            &tracker,
+            "c",
+            json!([
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": "Hello!" },
+                { "role": "user", "content": "write a haiku" }
+            ]),
+        );
+        assert_eq!(edited.relation, ConversationRelation::Continuation);
+        assert_eq!(edited.parent_flow_id.as_deref(), Some("a"));
+        assert_eq!(edited.conversation_id, "a");
+    }
+
+    #[test]
+    fn test_identical_request_outside_retry_window_starts_new_conversation() {
+        let tracker = ConversationTracker::new();
+        let messages = json!([{ "role": "user", "content": "quota" }]);
+        let start = Utc::now();
+        tracker
+            .link_request_at("q1", &request(messages.clone()), None, start)
+            .unwrap();
+
+        let (link, _) = tracker
+            .link_request_at(
+                "q2",
+                &request(messages.clone()),
+                None,
+                start + Duration::seconds(RETRY_WINDOW_SECS + 1),
+            )
+            .unwrap();
+        assert_eq!(link.relation, ConversationRelation::Root);
+        assert_eq!(link.conversation_id, "q2");
+
+        // 不同客户端 key 的相同请求不会串到一起
+        let (other, _) = tracker
+            .link_request("q3", &request(messages), Some("key-2"))
+            .unwrap();
+        assert_eq!(other.relation, ConversationRelation::Root);
+    }
+
+    #[test]
+    fn test_build_conversation_totals() {
+        let tracker = ConversationTracker::new();
+        let mut flows = Vec::new();
+        let turns = [
+            ("a", json!([{ "role": "user", "content": "hi" }])),
+            (
+                "b",
+                json!([
+                    { "role": "user", "content": "hi" },
+                    { "role": "assistant", "content": "Reading." },
+                    { "role": "user", "content": "go on" }
+                ]),
+            ),
+            (
+                "b2",
+                json!([
+                    { "role": "user", "content": "hi" },
+                    { "role": "assistant", "content": "Reading." },
+                    { "role": "user", "content": "go on" }
+                ]),
+            ),
+            (
+                "c",
+                json!([
+                    { "role": "user", "content": "hi" },
+                    { "role": "assistant", "content": "Reading." },
+                    { "role": "user", "content": "stop" }
+                ]),
+            ),
+        ];
+        for (i, (id, messages)) in turns.into_iter().enumerate() {
+            let mut request = request(messages);
+            request.messages = vec![Message {
+                content: MessageContent::Text("hi".to_string()),
+                ..Default::default()
+            }];
+            let (link, key) = tracker.link_request(id, &request, None).unwrap();
+            let reply = response("Reading.", &["toolu_x"]);
+            tracker.record_reply(&key, &reply);
+
+            let mut flow = LLMFlow::new(
+                id.to_string(),
+                FlowType::AnthropicMessages,
+                request,
+                FlowMetadata {
+                    conversation: Some(link),
+                    ..Default::default()
+                },
+            );
+            flow.timestamps.created += Duration::seconds(i as i64);
+            flow.timestamps.duration_ms = 1500;
+            flow.response = Some(reply);
+            flow.state = FlowState::Completed;
+            flows.push(flow);
+        }
+
+        let pricing = |model: &str| {
+            (model == "claude-sonnet-4-5").then(|| ModelPricing {
+                input_per_million: Some(3.0),
+                output_per_million: Some(15.0),
+                cache_read_per_million: Some(0.3),
+                ..Default::default()
+            })
+        };
+        let conversation = Conversation::build("a", flows, pricing).unwrap();
+        let totals = &conversation.summary.totals;
+        assert_eq!(conversation.summary.title, "hi");
+        assert_eq!(totals.flows, 4);
+        assert_eq!(totals.turns, 2);
+        assert_eq!(totals.retries, 1);
+        assert_eq!(totals.branches, 1);
+        assert_eq!(totals.input_tokens, 4000);
+        assert_eq!(totals.cache_read_tokens, 16000);
+        assert_eq!(totals.tool_calls, 4);
+        assert_eq!(totals.duration_ms, 6000);
+        assert_eq!(totals.unpriced_flows, 0);
+        // 每个 Flow：1000 * 3 + 200 * 15 + 4000 * 0.3 = 7200 / 1e6
+        assert!((totals.cost["USD"] - 4.0 * 0.0072).abs() < 1e-9);
+        assert_eq!(conversation.nodes[0].children, vec!["b", "b2", "c"]);
+    }
+}
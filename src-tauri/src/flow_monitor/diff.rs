@@ -1277,6 +1277,11 @@ mod property_tests {
             routing_info: Default::default(),
             injected_params: None,
             context_usage_percentage: None,
+            cached: false,
+            hedge_attempts: Vec::new(),
+            recording_session: None,
+            playback_of: None,
+            conversation: None,
         })
     }
 
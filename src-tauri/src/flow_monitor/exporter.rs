@@ -1626,6 +1626,11 @@ mod property_tests {
             routing_info: RoutingInfo::default(),
             injected_params: None,
             context_usage_percentage: None,
+            cached: false,
+            hedge_attempts: Vec::new(),
+            recording_session: None,
+            playback_of: None,
+            conversation: None,
         })
     }
 
@@ -1978,6 +1983,11 @@ mod redaction_property_tests {
                         routing_info: RoutingInfo::default(),
                         injected_params: None,
                         context_usage_percentage: None,
+                        cached: false,
+                        hedge_attempts: Vec::new(),
+                        recording_session: None,
+                        playback_of: None,
+                        conversation: None,
                     };
 
                     let mut flow = LLMFlow::new(id, flow_type, request, metadata);
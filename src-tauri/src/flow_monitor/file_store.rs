@@ -104,6 +104,7 @@ pub struct FlowIndexRecord {
     pub file_offset: i64,
     pub content_preview: Option<String>,
     pub request_preview: Option<String>,
+    pub conversation_id: Option<String>,
 }
 
 /// FTS 搜索结果
@@ -166,6 +167,11 @@ impl FlowIndexRecord {
             file_offset,
             content_preview,
             request_preview,
+            conversation_id: flow
+                .metadata
+                .conversation
+                .as_ref()
+                .map(|c| c.conversation_id.clone()),
         }
     }
 }
@@ -296,7 +302,8 @@ impl FlowFileStore {
                 file_path TEXT NOT NULL,
                 file_offset INTEGER NOT NULL,
                 content_preview TEXT,
-                request_preview TEXT
+                request_preview TEXT,
+                conversation_id TEXT
             );
 
             CREATE INDEX IF NOT EXISTS idx_created_at ON flow_index(created_at);
@@ -336,6 +343,13 @@ impl FlowFileStore {
             "#,
         )?;
 
+        // Migration: 添加 conversation_id 列（如果不存在）
+        let _ = conn.execute("ALTER TABLE flow_index ADD COLUMN conversation_id TEXT", []);
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_conversation_id ON flow_index(conversation_id)",
+            [],
+        )?;
+
         Ok(())
     }
 
@@ -439,12 +453,14 @@ impl FlowFileStore {
                 id, created_at, provider, model, status,
                 duration_ms, input_tokens, output_tokens,
                 has_error, has_tool_calls, has_thinking,
-                file_path, file_offset, content_preview, request_preview
+                file_path, file_offset, content_preview, request_preview,
+                conversation_id
             ) VALUES (
                 ?1, ?2, ?3, ?4, ?5,
                 ?6, ?7, ?8,
                 ?9, ?10, ?11,
-                ?12, ?13, ?14, ?15
+                ?12, ?13, ?14, ?15,
+                ?16
             )
             "#,
             params![
@@ -463,6 +479,7 @@ impl FlowFileStore {
                 record.file_offset,
                 record.content_preview,
                 record.request_preview,
+                record.conversation_id,
             ],
         )?;
 
@@ -557,6 +574,20 @@ impl FlowFileStore {
         }
     }
 
+    /// 索引中是否已有该 Flow
+    pub fn contains(&self, id: &str) -> Result<bool> {
+        let conn = self.index_db.lock().unwrap();
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM flow_index WHERE id = ?1",
+                params![id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        Ok(exists)
+    }
+
     /// 从文件读取 Flow
     fn read_flow_from_file(&self, file_path: &str, file_offset: i64) -> Result<Option<LLMFlow>> {
         let path = Path::new(file_path);
@@ -666,6 +697,12 @@ impl FlowFileStore {
             params_vec.push(Box::new(has_thinking as i32));
         }
 
+        // 对话过滤
+        if let Some(ref conversation_id) = filter.conversation_id {
+            conditions.push("conversation_id = ?".to_string());
+            params_vec.push(Box::new(conversation_id.clone()));
+        }
+
         // 构建 SQL
         let where_clause = if conditions.is_empty() {
             String::new()
@@ -0,0 +1,1394 @@
+//! LLM Flow 导入服务
+//!
+//! 将外部捕获的流量解析为 `LLMFlow`，支持：
+//! - HAR：本应用导出的 HAR（包括 `_llm` 扩展字段），以及浏览器等工具导出的 HAR
+//! - JSONL / JSON：本应用导出的 Flow（每行或数组中一个 `LLMFlow`）
+//! - 请求/响应日志：OpenAI、Anthropic 原始请求/响应对，以及 LiteLLM 日志
+//!
+//! 导入的 Flow 会打上来源标签（`imported`、`import:<格式>`、`source:<名称>`），
+//! 写入 `FlowFileStore` 后即可与本地流量一起查询。
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use super::exporter::{HarEntry, HarLlmExtension};
+use super::file_store::{FileStoreError, FlowFileStore};
+use super::models::{
+    ContentPart, FlowError, FlowErrorType, FlowMetadata, FlowState, FlowTimestamps, FlowType,
+    FunctionCall, FunctionDefinition, ImageUrl, LLMFlow, LLMRequest, LLMResponse, Message,
+    MessageContent, MessageRole, RequestParameters, RoutingInfo, StopReason, ThinkingContent,
+    TokenUsage, ToolCall, ToolDefinition, ToolResult,
+};
+use crate::ProviderType;
+
+/// 所有导入的 Flow 都带有的标签
+pub const IMPORTED_TAG: &str = "imported";
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+/// 导入错误
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("JSON 解析错误: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("无法识别的导入格式")]
+    UnrecognizedFormat,
+
+    #[error("没有可导入的 Flow")]
+    Empty,
+}
+
+// ============================================================================
+// 导入格式与选项
+// ============================================================================
+
+/// 导入格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// 根据内容自动识别
+    #[default]
+    Auto,
+    /// HAR (HTTP Archive) 格式
+    HAR,
+    /// 本应用导出的 JSONL / JSON 格式
+    JSONL,
+    /// OpenAI / Anthropic / LiteLLM 请求响应日志
+    Log,
+}
+
+impl ImportFormat {
+    /// 用于来源标签的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportFormat::Auto => "auto",
+            ImportFormat::HAR => "har",
+            ImportFormat::JSONL => "jsonl",
+            ImportFormat::Log => "log",
+        }
+    }
+}
+
+/// 导入选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// 导入格式
+    #[serde(default)]
+    pub format: ImportFormat,
+    /// 来源名称（如文件名或分享者），写入 `source:<名称>` 标签
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// 解析结果
+#[derive(Debug, Clone)]
+pub struct ImportResult {
+    /// 实际使用的格式
+    pub format: ImportFormat,
+    /// 解析出的 Flow
+    pub flows: Vec<LLMFlow>,
+    /// 跳过的条目数（非 LLM 流量）
+    pub skipped: usize,
+    /// 解析失败的条目
+    pub errors: Vec<String>,
+}
+
+/// 写入存储的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    /// 实际使用的格式
+    pub format: Option<ImportFormat>,
+    /// 新写入的 Flow 数
+    pub imported: usize,
+    /// 已存在而跳过的 Flow 数
+    pub duplicates: usize,
+    /// 跳过的条目数（非 LLM 流量）
+    pub skipped: usize,
+    /// 解析失败的条目
+    pub errors: Vec<String>,
+    /// 新写入的 Flow ID
+    pub flow_ids: Vec<String>,
+}
+
+// ============================================================================
+// Flow 导入器
+// ============================================================================
+
+/// Flow 导入器
+pub struct FlowImporter {
+    options: ImportOptions,
+}
+
+impl FlowImporter {
+    /// 创建新的导入器
+    pub fn new(options: ImportOptions) -> Self {
+        Self { options }
+    }
+
+    /// 使用默认选项创建导入器
+    pub fn with_defaults() -> Self {
+        Self::new(ImportOptions::default())
+    }
+
+    /// 根据内容识别格式
+    pub fn detect_format(data: &str) -> ImportFormat {
+        let trimmed = data.trim_start();
+        if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+            if value.get("log").and_then(|l| l.get("entries")).is_some() {
+                return ImportFormat::HAR;
+            }
+            let first = match &value {
+                Value::Array(items) => items.first(),
+                other => Some(other),
+            };
+            return match first {
+                Some(v) if is_exported_flow(v) => ImportFormat::JSONL,
+                Some(_) => ImportFormat::Log,
+                None => ImportFormat::Auto,
+            };
+        }
+
+        let first_line = trimmed.lines().find(|l| !l.trim().is_empty());
+        match first_line.and_then(|l| serde_json::from_str::<Value>(l).ok()) {
+            Some(v) if is_exported_flow(&v) => ImportFormat::JSONL,
+            Some(_) => ImportFormat::Log,
+            None => ImportFormat::Auto,
+        }
+    }
+
+    /// 解析数据为 Flow
+    pub fn import(&self, data: &str) -> Result<ImportResult, ImportError> {
+        let format = match self.options.format {
+            ImportFormat::Auto => Self::detect_format(data),
+            format => format,
+        };
+
+        let mut result = match format {
+            ImportFormat::HAR => self.import_har(data)?,
+            ImportFormat::JSONL => self.import_jsonl(data)?,
+            ImportFormat::Log => self.import_log(data)?,
+            ImportFormat::Auto => return Err(ImportError::UnrecognizedFormat),
+        };
+
+        for flow in &mut result.flows {
+            self.tag_origin(flow, format);
+        }
+        if result.flows.is_empty() && result.errors.is_empty() {
+            return Err(ImportError::Empty);
+        }
+        Ok(result)
+    }
+
+    /// 解析并写入文件存储，已存在的 Flow ID 会被跳过
+    pub fn import_into(
+        &self,
+        data: &str,
+        store: &FlowFileStore,
+    ) -> Result<ImportSummary, ImportStoreError> {
+        let result = self.import(data)?;
+        let mut summary = ImportSummary {
+            format: Some(result.format),
+            skipped: result.skipped,
+            errors: result.errors,
+            ..Default::default()
+        };
+
+        for flow in result.flows {
+            if store.contains(&flow.id)? {
+                summary.duplicates += 1;
+                continue;
+            }
+            store.write(&flow)?;
+            summary.imported += 1;
+            summary.flow_ids.push(flow.id);
+        }
+        Ok(summary)
+    }
+
+    /// 解析 HAR
+    pub fn import_har(&self, data: &str) -> Result<ImportResult, ImportError> {
+        let value: Value = serde_json::from_str(data)?;
+        let entries = value
+            .get("log")
+            .and_then(|l| l.get("entries"))
+            .and_then(|e| e.as_array())
+            .ok_or(ImportError::UnrecognizedFormat)?;
+
+        let mut result = ImportResult::empty(ImportFormat::HAR);
+        for (index, entry) in entries.iter().enumerate() {
+            let parsed = serde_json::from_value::<HarEntry>(entry.clone())
+                .map_err(|e| e.to_string())
+                .and_then(|entry| har_entry_to_flow(&entry));
+            result.push(parsed, || format!("第 {} 个 HAR 条目", index + 1));
+        }
+        Ok(result)
+    }
+
+    /// 解析本应用导出的 JSONL / JSON
+    pub fn import_jsonl(&self, data: &str) -> Result<ImportResult, ImportError> {
+        let mut result = ImportResult::empty(ImportFormat::JSONL);
+        for (label, record) in split_records(data)? {
+            let parsed = record.and_then(|value| {
+                serde_json::from_value::<LLMFlow>(value)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            });
+            result.push(parsed, || label.clone());
+        }
+        Ok(result)
+    }
+
+    /// 解析 OpenAI / Anthropic / LiteLLM 请求响应日志
+    pub fn import_log(&self, data: &str) -> Result<ImportResult, ImportError> {
+        let mut result = ImportResult::empty(ImportFormat::Log);
+        for (label, record) in split_records(data)? {
+            let parsed = record.and_then(|value| log_record_to_flow(&value));
+            result.push(parsed, || label.clone());
+        }
+        Ok(result)
+    }
+
+    /// 添加来源标签
+    fn tag_origin(&self, flow: &mut LLMFlow, format: ImportFormat) {
+        let mut tags = vec![
+            IMPORTED_TAG.to_string(),
+            format!("import:{}", format.as_str()),
+        ];
+        if let Some(source) = self.options.source.as_deref().map(str::trim) {
+            if !source.is_empty() {
+                tags.push(format!("source:{}", source));
+            }
+        }
+        for tag in tags {
+            if !flow.annotations.tags.contains(&tag) {
+                flow.annotations.tags.push(tag);
+            }
+        }
+    }
+}
+
+/// 导入并写入存储时的错误
+#[derive(Debug, Error)]
+pub enum ImportStoreError {
+    #[error(transparent)]
+    Import(#[from] ImportError),
+
+    #[error(transparent)]
+    Store(#[from] FileStoreError),
+}
+
+impl ImportResult {
+    fn empty(format: ImportFormat) -> Self {
+        Self {
+            format,
+            flows: Vec::new(),
+            skipped: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, parsed: Result<Option<LLMFlow>, String>, label: impl FnOnce() -> String) {
+        match parsed {
+            Ok(Some(flow)) => self.flows.push(flow),
+            Ok(None) => self.skipped += 1,
+            Err(e) => self.errors.push(format!("{}: {}", label(), e)),
+        }
+    }
+}
+
+// ============================================================================
+// 记录拆分
+// ============================================================================
+
+/// 一条待解析的记录：(位置描述, JSON 值或解析错误)
+type Record = (String, Result<Value, String>);
+
+/// 将 JSON 数组、单个对象或 JSONL 拆分为记录
+fn split_records(data: &str) -> Result<Vec<Record>, ImportError> {
+    let trimmed = data.trim();
+    if trimmed.is_empty() {
+        return Err(ImportError::Empty);
+    }
+
+    if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+        return Ok(match value {
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (format!("第 {} 条记录", i + 1), Ok(v)))
+                .collect(),
+            other => vec![("第 1 条记录".to_string(), Ok(other))],
+        });
+    }
+
+    Ok(trimmed
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            (
+                format!("第 {} 行", i + 1),
+                serde_json::from_str::<Value>(line).map_err(|e| e.to_string()),
+            )
+        })
+        .collect())
+}
+
+/// 是否为本应用导出的 Flow
+fn is_exported_flow(value: &Value) -> bool {
+    ["id", "flow_type", "request", "metadata", "timestamps"]
+        .iter()
+        .all(|key| value.get(key).is_some())
+}
+
+// ============================================================================
+// HAR 条目转换
+// ============================================================================
+
+/// 将 HAR 条目转换为 Flow，非 LLM 请求返回 `None`
+fn har_entry_to_flow(entry: &HarEntry) -> Result<Option<LLMFlow>, String> {
+    let ext = entry.llm_extension.as_ref();
+    let body = entry
+        .request
+        .post_data
+        .as_ref()
+        .and_then(|p| serde_json::from_str::<Value>(&p.text).ok())
+        .unwrap_or(Value::Null);
+    if ext.is_none() && !looks_like_llm_request(&body) {
+        return Ok(None);
+    }
+
+    let (base_url, path) = split_url(&entry.request.url);
+    let started = parse_time(&Value::String(entry.started_date_time.clone()))
+        .ok_or_else(|| format!("无效的开始时间: {}", entry.started_date_time))?;
+    let headers = entry
+        .request
+        .headers
+        .iter()
+        .map(|h| (h.name.clone(), h.value.clone()))
+        .collect();
+
+    let mut request = build_request(body, &path, headers, started);
+    request.method = entry.request.method.clone();
+    if let Some(ext) = ext {
+        if request.model.is_empty() {
+            request.model = ext.model.clone();
+        }
+        request.parameters.stream |= ext.streaming;
+    }
+
+    let duration_ms = entry.time.max(0.0) as u64;
+    let ttfb_ms = ext
+        .and_then(|e| e.ttfb_ms)
+        .or_else(|| (entry.timings.wait >= 0.0).then_some(entry.timings.wait as u64));
+    let ended = started + chrono::Duration::milliseconds(duration_ms as i64);
+
+    let response = (entry.response.status != 0).then(|| {
+        let text = entry.response.content.text.clone().unwrap_or_default();
+        let body = serde_json::from_str::<Value>(&text).unwrap_or(Value::String(text));
+        let headers = entry
+            .response
+            .headers
+            .iter()
+            .map(|h| (h.name.clone(), h.value.clone()))
+            .collect();
+        let mut response = build_response(entry.response.status, body, headers, started, ended);
+        response.status_text = entry.response.status_text.clone();
+        if let Some(ext) = ext {
+            apply_har_extension(&mut response, ext);
+        }
+        response
+    });
+
+    let flow_type = ext
+        .and_then(|e| parse_flow_type(&e.flow_type))
+        .unwrap_or_else(|| infer_flow_type(&path, &request.body));
+    let provider = ext
+        .and_then(|e| parse_provider(&e.provider))
+        .unwrap_or_else(|| infer_provider(&flow_type));
+    let id = ext
+        .map(|e| e.flow_id.clone())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut flow = assemble_flow(
+        id,
+        flow_type,
+        provider,
+        request,
+        response,
+        duration_ms,
+        ttfb_ms,
+    );
+    flow.metadata.routing_info.target_url = base_url;
+    if let Some(state) = ext.and_then(|e| parse_flow_state(&e.state)) {
+        flow.state = state;
+    }
+    if let Some(annotations) = ext.and_then(|e| e.annotations.clone()) {
+        flow.annotations = annotations;
+    } else if let Some(comment) = entry.comment.clone() {
+        flow.annotations.comment = Some(comment);
+    }
+    Ok(Some(flow))
+}
+
+/// 用 `_llm` 扩展中记录的值覆盖从响应体解析出的值
+fn apply_har_extension(response: &mut LLMResponse, ext: &HarLlmExtension) {
+    if let Some(tokens) = &ext.tokens {
+        response.usage = TokenUsage {
+            input_tokens: tokens.input,
+            output_tokens: tokens.output,
+            cache_read_tokens: tokens.cache_read,
+            cache_write_tokens: tokens.cache_write,
+            thinking_tokens: tokens.thinking,
+            total_tokens: tokens.total,
+        };
+    }
+    if let Some(reason) = ext.stop_reason.as_deref().and_then(parse_stop_reason_debug) {
+        response.stop_reason = Some(reason);
+    }
+}
+
+/// 拆分 URL 为 (`scheme://host`, 路径)
+fn split_url(url: &str) -> (Option<String>, String) {
+    match url::Url::parse(url) {
+        Ok(parsed) => {
+            let base = parsed.host_str().map(|host| match parsed.port() {
+                Some(port) => format!("{}://{}:{}", parsed.scheme(), host, port),
+                None => format!("{}://{}", parsed.scheme(), host),
+            });
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            (base, path)
+        }
+        Err(_) => (None, url.to_string()),
+    }
+}
+
+// ============================================================================
+// 日志记录转换
+// ============================================================================
+
+/// 将一条请求/响应日志转换为 Flow
+///
+/// 支持的形式：
+/// - `{"request": {...}, "response": {...}}`（也接受 `request_body` / `response_body`）
+/// - OpenAI Batch 风格：`{"response": {"status_code": 200, "body": {...}}}`
+/// - LiteLLM 日志：`{"model", "messages", "response", "startTime", "endTime", ...}`
+fn log_record_to_flow(record: &Value) -> Result<Option<LLMFlow>, String> {
+    let request_body = first_of(record, &["request", "request_body"])
+        .cloned()
+        .or_else(|| litellm_request(record))
+        .ok_or("缺少 request 字段")?;
+    let request_body = parse_embedded_json(request_body);
+    if !looks_like_llm_request(&request_body) {
+        return Ok(None);
+    }
+
+    let mut response_body = first_of(record, &["response", "response_body", "response_obj"])
+        .cloned()
+        .map(parse_embedded_json);
+    let mut status = first_of(record, &["status_code", "status"])
+        .and_then(|v| v.as_u64())
+        .map(|s| s as u16);
+    if let Some(Value::Object(obj)) = &response_body {
+        if let (Some(code), Some(body)) = (obj.get("status_code"), obj.get("body")) {
+            status = code.as_u64().map(|s| s as u16).or(status);
+            response_body = Some(parse_embedded_json(body.clone()));
+        }
+    }
+
+    let started = first_of(
+        record,
+        &["timestamp", "start_time", "startTime", "created_at"],
+    )
+    .and_then(parse_time)
+    .unwrap_or_else(Utc::now);
+    let ended = first_of(record, &["end_time", "endTime", "completed_at"])
+        .and_then(parse_time)
+        .filter(|t| *t >= started);
+    let duration_ms = ended
+        .map(|t| (t - started).num_milliseconds().max(0) as u64)
+        .or_else(|| first_of(record, &["duration_ms", "latency_ms"]).and_then(|v| v.as_u64()))
+        .unwrap_or(0);
+    let ended = ended.unwrap_or(started + chrono::Duration::milliseconds(duration_ms as i64));
+
+    let path = first_of(record, &["path", "url", "endpoint", "api_base"])
+        .and_then(|v| v.as_str())
+        .map(split_url)
+        .unwrap_or((None, String::new()));
+    let flow_type = infer_flow_type(&path.1, &request_body);
+    let request_path = if path.1.is_empty() {
+        default_path(&flow_type).to_string()
+    } else {
+        path.1
+    };
+    let request = build_request(request_body, &request_path, HashMap::new(), started);
+
+    let response = response_body.filter(|b| !b.is_null()).map(|body| {
+        let status = status.unwrap_or(if body.get("error").is_some() {
+            500
+        } else {
+            200
+        });
+        build_response(status, body, HashMap::new(), started, ended)
+    });
+
+    let provider = first_of(record, &["provider", "custom_llm_provider"])
+        .and_then(|v| v.as_str())
+        .and_then(parse_provider)
+        .unwrap_or_else(|| infer_provider(&flow_type));
+    let id = first_of(
+        record,
+        &["id", "request_id", "custom_id", "litellm_call_id"],
+    )
+    .and_then(|v| v.as_str())
+    .map(|s| s.to_string())
+    .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut flow = assemble_flow(
+        id,
+        flow_type,
+        provider,
+        request,
+        response,
+        duration_ms,
+        None,
+    );
+    flow.metadata.routing_info.target_url = path.0;
+    Ok(Some(flow))
+}
+
+/// 从 LiteLLM 日志组装请求体
+fn litellm_request(record: &Value) -> Option<Value> {
+    let messages = record.get("messages")?;
+    let mut body = serde_json::Map::new();
+    if let Some(Value::Object(params)) = record.get("model_parameters") {
+        body.extend(params.clone());
+    }
+    body.insert(
+        "model".to_string(),
+        record.get("model").cloned().unwrap_or(Value::Null),
+    );
+    body.insert("messages".to_string(), messages.clone());
+    Some(Value::Object(body))
+}
+
+/// 取第一个存在的字段
+fn first_of<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter()
+        .find_map(|key| value.get(key).filter(|v| !v.is_null()))
+}
+
+/// 日志中请求/响应体可能被序列化为字符串
+fn parse_embedded_json(value: Value) -> Value {
+    match value {
+        Value::String(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        other => other,
+    }
+}
+
+/// 解析 RFC3339 时间或 Unix 时间戳（秒或毫秒）
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+                    .ok()
+                    .map(|t| t.and_utc())
+            }),
+        Value::Number(n) => {
+            let secs = n.as_f64()?;
+            let millis = if secs > 1e12 { secs } else { secs * 1000.0 };
+            Utc.timestamp_millis_opt(millis as i64).single()
+        }
+        _ => None,
+    }
+}
+
+// ============================================================================
+// 请求与响应解析
+// ============================================================================
+
+/// 请求体是否像 LLM 请求
+fn looks_like_llm_request(body: &Value) -> bool {
+    body.get("model").is_some() || body.get("messages").is_some() || body.get("contents").is_some()
+}
+
+/// 从请求体构建 LLMRequest
+fn build_request(
+    body: Value,
+    path: &str,
+    headers: HashMap<String, String>,
+    timestamp: DateTime<Utc>,
+) -> LLMRequest {
+    let messages: Vec<Message> = body
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .map(|items| items.iter().map(parse_message).collect())
+        .unwrap_or_default();
+    let system_prompt = body
+        .get("system")
+        .map(extract_text)
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            messages
+                .iter()
+                .find(|m| m.role == MessageRole::System)
+                .map(|m| m.content.get_all_text())
+        });
+    let tools = body
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .map(|items| items.iter().filter_map(parse_tool_definition).collect());
+
+    let parameters = RequestParameters {
+        temperature: body
+            .get("temperature")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32),
+        top_p: body.get("top_p").and_then(|v| v.as_f64()).map(|v| v as f32),
+        max_tokens: first_of(&body, &["max_tokens", "max_completion_tokens"])
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32),
+        stop: first_of(&body, &["stop", "stop_sequences"]).map(|v| match v {
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|s| s.as_str().map(|s| s.to_string()))
+                .collect(),
+            other => vec![extract_text(other)],
+        }),
+        stream: body
+            .get("stream")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        extra: HashMap::new(),
+    };
+
+    LLMRequest {
+        method: "POST".to_string(),
+        path: path.to_string(),
+        headers: strip_sensitive_headers(headers),
+        model: body
+            .get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        size_bytes: body.to_string().len(),
+        body,
+        messages,
+        system_prompt,
+        tools,
+        original_model: None,
+        parameters,
+        timestamp,
+    }
+}
+
+/// 去掉认证相关请求头（导入的数据可能来自他人）
+fn strip_sensitive_headers(headers: HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .into_iter()
+        .filter(|(name, _)| {
+            let name = name.to_lowercase();
+            !name.contains("authorization") && !name.contains("api-key") && name != "cookie"
+        })
+        .collect()
+}
+
+/// 解析单条消息（OpenAI 或 Anthropic 格式）
+fn parse_message(value: &Value) -> Message {
+    let role = match value.get("role").and_then(|r| r.as_str()).unwrap_or("user") {
+        "system" | "developer" => MessageRole::System,
+        "assistant" | "model" => MessageRole::Assistant,
+        "tool" => MessageRole::Tool,
+        "function" => MessageRole::Function,
+        _ => MessageRole::User,
+    };
+
+    let mut tool_calls: Vec<ToolCall> = value
+        .get("tool_calls")
+        .and_then(|t| t.as_array())
+        .map(|items| items.iter().filter_map(parse_openai_tool_call).collect())
+        .unwrap_or_default();
+    let mut tool_result = value
+        .get("tool_call_id")
+        .and_then(|id| id.as_str())
+        .map(|id| ToolResult {
+            tool_call_id: id.to_string(),
+            content: value.get("content").map(extract_text).unwrap_or_default(),
+            is_error: false,
+        });
+
+    let content = match value.get("content") {
+        Some(Value::String(text)) => MessageContent::Text(text.clone()),
+        Some(Value::Array(blocks)) => {
+            let mut parts = Vec::new();
+            for block in blocks {
+                match block.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                    "text" | "input_text" | "output_text" => {
+                        if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                            parts.push(ContentPart::Text {
+                                text: text.to_string(),
+                            });
+                        }
+                    }
+                    "image_url" => {
+                        let image_url = block.get("image_url");
+                        let url = image_url
+                            .and_then(|u| u.get("url").or(Some(u)))
+                            .and_then(|u| u.as_str())
+                            .unwrap_or_default();
+                        parts.push(ContentPart::ImageUrl {
+                            image_url: ImageUrl {
+                                url: url.to_string(),
+                                detail: image_url
+                                    .and_then(|u| u.get("detail"))
+                                    .and_then(|d| d.as_str())
+                                    .map(|d| d.to_string()),
+                            },
+                        });
+                    }
+                    "image" => {
+                        let source = block.get("source");
+                        let field = |key: &str| {
+                            source
+                                .and_then(|s| s.get(key))
+                                .and_then(|v| v.as_str())
+                                .map(|s| s.to_string())
+                        };
+                        parts.push(ContentPart::Image {
+                            media_type: field("media_type"),
+                            data: field("data"),
+                            url: field("url"),
+                        });
+                    }
+                    "tool_use" => tool_calls.extend(parse_anthropic_tool_use(block)),
+                    "tool_result" => {
+                        tool_result = Some(ToolResult {
+                            tool_call_id: block
+                                .get("tool_use_id")
+                                .and_then(|id| id.as_str())
+                                .unwrap_or_default()
+                                .to_string(),
+                            content: block.get("content").map(extract_text).unwrap_or_default(),
+                            is_error: block
+                                .get("is_error")
+                                .and_then(|e| e.as_bool())
+                                .unwrap_or(false),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            MessageContent::MultiModal(parts)
+        }
+        _ => MessageContent::Text(String::new()),
+    };
+
+    Message {
+        role,
+        content,
+        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+        tool_result,
+        name: value
+            .get("name")
+            .and_then(|n| n.as_str())
+            .map(|n| n.to_string()),
+    }
+}
+
+/// 解析工具定义（OpenAI `{type, function}` 或 Anthropic `{name, input_schema}`）
+fn parse_tool_definition(value: &Value) -> Option<ToolDefinition> {
+    let function = value.get("function").unwrap_or(value);
+    Some(ToolDefinition {
+        tool_type: value
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("function")
+            .to_string(),
+        function: FunctionDefinition {
+            name: function.get("name")?.as_str()?.to_string(),
+            description: function
+                .get("description")
+                .and_then(|d| d.as_str())
+                .map(|d| d.to_string()),
+            parameters: function
+                .get("parameters")
+                .or_else(|| function.get("input_schema"))
+                .cloned(),
+        },
+    })
+}
+
+/// 解析 OpenAI 工具调用
+fn parse_openai_tool_call(value: &Value) -> Option<ToolCall> {
+    let function = value.get("function")?;
+    Some(ToolCall {
+        id: value
+            .get("id")
+            .and_then(|id| id.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCall {
+            name: function.get("name")?.as_str()?.to_string(),
+            arguments: match function.get("arguments") {
+                Some(Value::String(args)) => args.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            },
+        },
+    })
+}
+
+/// 解析 Anthropic `tool_use` 内容块
+fn parse_anthropic_tool_use(block: &Value) -> Option<ToolCall> {
+    Some(ToolCall {
+        id: block
+            .get("id")
+            .and_then(|id| id.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCall {
+            name: block.get("name")?.as_str()?.to_string(),
+            arguments: block
+                .get("input")
+                .map(|i| i.to_string())
+                .unwrap_or_default(),
+        },
+    })
+}
+
+/// 提取文本（字符串或内容块数组）
+fn extract_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|p| match p {
+                Value::String(s) => Some(s.as_str()),
+                other => other.get("text").and_then(|t| t.as_str()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 从响应体构建 LLMResponse
+fn build_response(
+    status_code: u16,
+    body: Value,
+    headers: HashMap<String, String>,
+    started: DateTime<Utc>,
+    ended: DateTime<Utc>,
+) -> LLMResponse {
+    let mut response = LLMResponse {
+        status_code,
+        status_text: if status_code < 400 { "OK" } else { "Error" }.to_string(),
+        headers,
+        size_bytes: match &body {
+            Value::String(s) => s.len(),
+            other => other.to_string().len(),
+        },
+        timestamp_start: started,
+        timestamp_end: ended,
+        ..Default::default()
+    };
+
+    if let Some(choice) = body
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .and_then(|c| c.first())
+    {
+        // OpenAI Chat Completions
+        let message = choice.get("message").unwrap_or(&Value::Null);
+        response.content = message.get("content").map(extract_text).unwrap_or_default();
+        response.thinking = message
+            .get("reasoning_content")
+            .and_then(|r| r.as_str())
+            .filter(|r| !r.is_empty())
+            .map(|text| ThinkingContent {
+                text: text.to_string(),
+                tokens: None,
+                signature: None,
+            });
+        response.tool_calls = message
+            .get("tool_calls")
+            .and_then(|t| t.as_array())
+            .map(|items| items.iter().filter_map(parse_openai_tool_call).collect())
+            .unwrap_or_default();
+        response.stop_reason = choice
+            .get("finish_reason")
+            .and_then(|r| r.as_str())
+            .map(parse_stop_reason);
+    } else if let Some(blocks) = body.get("content").and_then(|c| c.as_array()) {
+        // Anthropic Messages
+        let mut text = Vec::new();
+        let mut thinking = Vec::new();
+        let mut signature = None;
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+                "text" => text.extend(block.get("text").and_then(|t| t.as_str())),
+                "thinking" => {
+                    thinking.extend(block.get("thinking").and_then(|t| t.as_str()));
+                    signature = block
+                        .get("signature")
+                        .and_then(|s| s.as_str())
+                        .map(|s| s.to_string());
+                }
+                "tool_use" => response.tool_calls.extend(parse_anthropic_tool_use(block)),
+                _ => {}
+            }
+        }
+        response.content = text.join("");
+        if !thinking.is_empty() {
+            response.thinking = Some(ThinkingContent {
+                text: thinking.join("\n"),
+                tokens: None,
+                signature,
+            });
+        }
+        response.stop_reason = body
+            .get("stop_reason")
+            .and_then(|r| r.as_str())
+            .map(parse_stop_reason);
+    }
+
+    if let Some(usage) = body.get("usage") {
+        let field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| usage.pointer(k).and_then(|v| v.as_u64()))
+                .map(|v| v as u32)
+        };
+        response.usage = TokenUsage {
+            input_tokens: field(&["/prompt_tokens", "/input_tokens"]).unwrap_or(0),
+            output_tokens: field(&["/completion_tokens", "/output_tokens"]).unwrap_or(0),
+            cache_read_tokens: field(&[
+                "/cache_read_input_tokens",
+                "/prompt_tokens_details/cached_tokens",
+            ]),
+            cache_write_tokens: field(&["/cache_creation_input_tokens"]),
+            thinking_tokens: field(&["/completion_tokens_details/reasoning_tokens"]),
+            total_tokens: field(&["/total_tokens"]).unwrap_or(0),
+        };
+        if response.usage.total_tokens == 0 {
+            response.usage.calculate_total();
+        }
+    }
+
+    response.body = body;
+    response
+}
+
+/// 解析 API 返回的停止原因
+fn parse_stop_reason(reason: &str) -> StopReason {
+    match reason {
+        "stop" | "stop_sequence" => StopReason::Stop,
+        "length" | "max_tokens" => StopReason::Length,
+        "tool_calls" | "tool_use" => StopReason::ToolCalls,
+        "content_filter" => StopReason::ContentFilter,
+        "function_call" => StopReason::FunctionCall,
+        "end_turn" => StopReason::EndTurn,
+        other => StopReason::Other(other.to_string()),
+    }
+}
+
+/// 组装 Flow，并根据响应状态补全状态和错误
+fn assemble_flow(
+    id: String,
+    flow_type: FlowType,
+    provider: ProviderType,
+    request: LLMRequest,
+    response: Option<LLMResponse>,
+    duration_ms: u64,
+    ttfb_ms: Option<u64>,
+) -> LLMFlow {
+    let started = request.timestamp;
+    let ended = started + chrono::Duration::milliseconds(duration_ms as i64);
+    let error = response.as_ref().filter(|r| r.status_code >= 400).map(|r| {
+        let message = r
+            .body
+            .pointer("/error/message")
+            .map(extract_text)
+            .unwrap_or_else(|| r.status_text.clone());
+        let error_type = FlowErrorType::from_status_code(r.status_code);
+        FlowError::new(error_type.clone(), message)
+            .with_status_code(r.status_code)
+            .with_raw_response(r.body.to_string())
+            .with_retryable(error_type.is_retryable())
+    });
+    let state = match (&response, &error) {
+        (_, Some(_)) => FlowState::Failed,
+        (Some(_), None) => FlowState::Completed,
+        (None, None) => FlowState::Failed,
+    };
+
+    LLMFlow {
+        id,
+        flow_type,
+        timestamps: FlowTimestamps {
+            created: started,
+            request_start: started,
+            request_end: Some(started),
+            response_start: ttfb_ms
+                .map(|ttfb| started + chrono::Duration::milliseconds(ttfb as i64)),
+            response_end: response.as_ref().map(|_| ended),
+            duration_ms,
+            ttfb_ms,
+        },
+        request,
+        response,
+        error,
+        metadata: FlowMetadata {
+            provider,
+            routing_info: RoutingInfo::default(),
+            ..Default::default()
+        },
+        state,
+        annotations: Default::default(),
+    }
+}
+
+// ============================================================================
+// 类型推断
+// ============================================================================
+
+/// 根据路径和请求体推断 Flow 类型
+fn infer_flow_type(path: &str, body: &Value) -> FlowType {
+    if path.contains("/messages") || body.get("system").is_some() && body.get("messages").is_some()
+    {
+        FlowType::AnthropicMessages
+    } else if path.contains("generateContent") || body.get("contents").is_some() {
+        FlowType::GeminiGenerateContent
+    } else if path.contains("/embeddings") || body.get("input").is_some() {
+        FlowType::Embeddings
+    } else {
+        FlowType::ChatCompletions
+    }
+}
+
+/// Flow 类型对应的默认请求路径
+fn default_path(flow_type: &FlowType) -> &'static str {
+    match flow_type {
+        FlowType::AnthropicMessages => "/v1/messages",
+        FlowType::Embeddings => "/v1/embeddings",
+        _ => "/v1/chat/completions",
+    }
+}
+
+/// 根据 Flow 类型推断提供商
+fn infer_provider(flow_type: &FlowType) -> ProviderType {
+    match flow_type {
+        FlowType::AnthropicMessages => ProviderType::Claude,
+        FlowType::GeminiGenerateContent => ProviderType::Gemini,
+        _ => ProviderType::OpenAI,
+    }
+}
+
+/// 所有提供商类型，用于匹配 HAR 中的 Debug 格式名称
+const ALL_PROVIDERS: &[ProviderType] = &[
+    ProviderType::Kiro,
+    ProviderType::Gemini,
+    ProviderType::Qwen,
+    ProviderType::OpenAI,
+    ProviderType::Claude,
+    ProviderType::Antigravity,
+    ProviderType::Vertex,
+    ProviderType::GeminiApiKey,
+    ProviderType::Codex,
+    ProviderType::ClaudeOAuth,
+    ProviderType::IFlow,
+    ProviderType::Anthropic,
+    ProviderType::AzureOpenai,
+    ProviderType::AwsBedrock,
+    ProviderType::Ollama,
+];
+
+/// 解析提供商名称（`openai`、`OpenAI`、`GeminiApiKey` 等）
+fn parse_provider(name: &str) -> Option<ProviderType> {
+    name.parse().ok().or_else(|| {
+        ALL_PROVIDERS
+            .iter()
+            .find(|p| format!("{:?}", p).eq_ignore_ascii_case(name))
+            .copied()
+    })
+}
+
+/// 解析 Debug 格式的 Flow 类型（如 `ChatCompletions`、`Other("x")`）
+fn parse_flow_type(name: &str) -> Option<FlowType> {
+    match name {
+        "ChatCompletions" => Some(FlowType::ChatCompletions),
+        "AnthropicMessages" => Some(FlowType::AnthropicMessages),
+        "GeminiGenerateContent" => Some(FlowType::GeminiGenerateContent),
+        "Embeddings" => Some(FlowType::Embeddings),
+        other => parse_debug_other(other).map(FlowType::Other),
+    }
+}
+
+/// 解析 Debug 格式的 Flow 状态
+fn parse_flow_state(name: &str) -> Option<FlowState> {
+    match name {
+        "Pending" => Some(FlowState::Pending),
+        "Streaming" => Some(FlowState::Streaming),
+        "Completed" => Some(FlowState::Completed),
+        "Failed" => Some(FlowState::Failed),
+        "Cancelled" => Some(FlowState::Cancelled),
+        _ => None,
+    }
+}
+
+/// 解析 Debug 格式的停止原因
+fn parse_stop_reason_debug(name: &str) -> Option<StopReason> {
+    match name {
+        "Stop" => Some(StopReason::Stop),
+        "Length" => Some(StopReason::Length),
+        "ToolCalls" => Some(StopReason::ToolCalls),
+        "ContentFilter" => Some(StopReason::ContentFilter),
+        "FunctionCall" => Some(StopReason::FunctionCall),
+        "EndTurn" => Some(StopReason::EndTurn),
+        other => parse_debug_other(other).map(StopReason::Other),
+    }
+}
+
+/// 解析 `Other("x")` 形式
+fn parse_debug_other(name: &str) -> Option<String> {
+    let inner = name.strip_prefix("Other(")?.strip_suffix(')')?;
+    serde_json::from_str::<String>(inner).ok()
+}
+
+// ============================================================================
+// 测试模块
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::exporter::FlowExporter;
+    use crate::flow_monitor::file_store::RotationConfig;
+    use serde_json::json;
+
+    fn exported_flow() -> LLMFlow {
+        let body = json!({
+            "model": "claude-sonnet-4-5",
+            "system": "You are terse.",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+        let started = Utc::now();
+        let mut flow = LLMFlow::new(
+            "flow-001".to_string(),
+            FlowType::AnthropicMessages,
+            build_request(body, "/v1/messages", HashMap::new(), started),
+            FlowMetadata {
+                provider: ProviderType::ClaudeOAuth,
+                ..Default::default()
+            },
+        );
+        flow.response = Some(build_response(
+            200,
+            json!({
+                "type": "message",
+                "content": [{"type": "text", "text": "Hello"}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 12, "output_tokens": 3}
+            }),
+            HashMap::new(),
+            started,
+            started,
+        ));
+        flow.state = FlowState::Completed;
+        flow.timestamps.duration_ms = 840;
+        flow.timestamps.ttfb_ms = Some(120);
+        flow.metadata.routing_info.target_url = Some("https://api.anthropic.com".to_string());
+        flow.annotations.starred = true;
+        flow.annotations.tags = vec!["regression".to_string()];
+        flow
+    }
+
+    #[test]
+    fn test_har_round_trip() {
+        let har = FlowExporter::with_defaults().export_har(&[exported_flow()]);
+        let data = serde_json::to_string(&har).unwrap();
+        assert_eq!(FlowImporter::detect_format(&data), ImportFormat::HAR);
+
+        let importer = FlowImporter::new(ImportOptions {
+            source: Some("alice".to_string()),
+            ..Default::default()
+        });
+        let result = importer.import(&data).unwrap();
+        assert_eq!(result.format, ImportFormat::HAR);
+        let flow = &result.flows[0];
+        assert_eq!(flow.id, "flow-001");
+        assert_eq!(flow.metadata.provider, ProviderType::ClaudeOAuth);
+        assert_eq!(flow.flow_type, FlowType::AnthropicMessages);
+        assert_eq!(flow.request.path, "/v1/messages");
+        assert_eq!(
+            flow.request.system_prompt.as_deref(),
+            Some("You are terse.")
+        );
+        assert_eq!(
+            flow.metadata.routing_info.target_url.as_deref(),
+            Some("https://api.anthropic.com")
+        );
+        let response = flow.response.as_ref().unwrap();
+        assert_eq!(response.content, "Hello");
+        assert_eq!(response.usage.total_tokens, 15);
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(flow.timestamps.duration_ms, 840);
+        assert_eq!(flow.timestamps.ttfb_ms, Some(120));
+        assert!(flow.annotations.starred);
+        assert_eq!(
+            flow.annotations.tags,
+            vec!["regression", "imported", "import:har", "source:alice"]
+        );
+    }
+
+    #[test]
+    fn test_har_skips_non_llm_entries() {
+        let mut har =
+            serde_json::to_value(FlowExporter::with_defaults().export_har(&[exported_flow()]))
+                .unwrap();
+        let mut entry = har["log"]["entries"][0].clone();
+        entry.as_object_mut().unwrap().remove("_llm");
+        entry["request"]["url"] = json!("https://example.com/static/app.js");
+        entry["request"]["postData"]["text"] = json!("");
+        har["log"]["entries"].as_array_mut().unwrap().push(entry);
+
+        let result = FlowImporter::with_defaults()
+            .import(&har.to_string())
+            .unwrap();
+        assert_eq!(result.flows.len(), 1);
+        assert_eq!(result.skipped, 1);
+    }
+
+    #[test]
+    fn test_jsonl_import_reports_bad_lines() {
+        let jsonl = FlowExporter::with_defaults().export_jsonl(&[exported_flow()]);
+        let data = format!("{}\n{{not json}}\n", jsonl);
+        assert_eq!(FlowImporter::detect_format(&data), ImportFormat::JSONL);
+
+        let result = FlowImporter::with_defaults().import(&data).unwrap();
+        assert_eq!(result.flows.len(), 1);
+        assert_eq!(result.flows[0].id, "flow-001");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].starts_with("第 2 行"));
+    }
+
+    #[test]
+    fn test_openai_log_pair() {
+        let data = json!({
+            "timestamp": "2026-01-01T08:00:00Z",
+            "end_time": "2026-01-01T08:00:01.500Z",
+            "request": {
+                "model": "gpt-4o-mini",
+                "messages": [
+                    {"role": "system", "content": "Be brief."},
+                    {"role": "user", "content": "Weather?"}
+                ],
+                "tools": [{"type": "function", "function": {"name": "get_weather"}}]
+            },
+            "response": {
+                "object": "chat.completion",
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {"name": "get_weather", "arguments": "{}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25}
+            }
+        })
+        .to_string();
+        assert_eq!(FlowImporter::detect_format(&data), ImportFormat::Log);
+
+        let flow = FlowImporter::with_defaults().import(&data).unwrap().flows[0].clone();
+        assert_eq!(flow.flow_type, FlowType::ChatCompletions);
+        assert_eq!(flow.metadata.provider, ProviderType::OpenAI);
+        assert_eq!(flow.request.path, "/v1/chat/completions");
+        assert_eq!(flow.request.system_prompt.as_deref(), Some("Be brief."));
+        assert_eq!(flow.request.tools.as_ref().unwrap().len(), 1);
+        assert_eq!(flow.timestamps.duration_ms, 1500);
+        assert_eq!(flow.state, FlowState::Completed);
+        let response = flow.response.unwrap();
+        assert_eq!(response.tool_calls[0].function.name, "get_weather");
+        assert_eq!(response.stop_reason, Some(StopReason::ToolCalls));
+        assert_eq!(response.usage.total_tokens, 25);
+    }
+
+    #[test]
+    fn test_litellm_and_error_logs() {
+        let data = [
+            json!({
+                "litellm_call_id": "call-1",
+                "model": "claude-haiku-4-5",
+                "custom_llm_provider": "anthropic",
+                "messages": [{"role": "user", "content": "ping"}],
+                "response": {"choices": [{"message": {"content": "pong"}, "finish_reason": "stop"}]},
+                "startTime": 1767254400.0,
+                "endTime": 1767254400.25
+            }),
+            json!({
+                "request_body": "{\"model\":\"gpt-4o\",\"messages\":[]}",
+                "status_code": 429,
+                "response_body": {"error": {"message": "Rate limit reached"}}
+            }),
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        let result = FlowImporter::with_defaults().import(&data).unwrap();
+        assert_eq!(result.format, ImportFormat::Log);
+        let litellm = &result.flows[0];
+        assert_eq!(litellm.id, "call-1");
+        assert_eq!(litellm.metadata.provider, ProviderType::Anthropic);
+        assert_eq!(litellm.request.model, "claude-haiku-4-5");
+        assert_eq!(litellm.timestamps.duration_ms, 250);
+        assert_eq!(litellm.response.as_ref().unwrap().content, "pong");
+
+        let failed = &result.flows[1];
+        assert_eq!(failed.state, FlowState::Failed);
+        let error = failed.error.as_ref().unwrap();
+        assert_eq!(error.error_type, FlowErrorType::RateLimit);
+        assert_eq!(error.message, "Rate limit reached");
+    }
+
+    #[test]
+    fn test_import_into_store_skips_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let store =
+            FlowFileStore::new(dir.path().to_path_buf(), RotationConfig::default()).unwrap();
+        let data = FlowExporter::with_defaults().export_jsonl(&[exported_flow()]);
+        let importer = FlowImporter::with_defaults();
+
+        let first = importer.import_into(&data, &store).unwrap();
+        assert_eq!(first.imported, 1);
+        assert_eq!(first.flow_ids, vec!["flow-001"]);
+        let stored = store.get("flow-001").unwrap().unwrap();
+        assert!(stored.annotations.tags.contains(&IMPORTED_TAG.to_string()));
+
+        let second = importer.import_into(&data, &store).unwrap();
+        assert_eq!(second.imported, 0);
+        assert_eq!(second.duplicates, 1);
+    }
+}
@@ -0,0 +1,569 @@
+//! 自动拦截规则
+//!
+//! 匹配过滤表达式的 Flow 不等待人工操作，直接按顺序执行规则中的动作：
+//!
+//! - 按 JSON Pointer 修改或删除请求体/响应体字段
+//! - 注入系统消息
+//! - 直接返回指定响应（请求阶段不调用上游）
+//! - 增加延迟
+//! - 返回错误状态码
+//!
+//! 规则与人工拦截相互独立：规则先执行，修改后的请求再进入人工拦截。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use super::filter_parser::FilterParser;
+use super::interceptor::{InterceptType, InterceptorError};
+use super::models::{LLMFlow, LLMRequest};
+
+// ============================================================================
+// 规则定义
+// ============================================================================
+
+/// 自动拦截规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterceptRule {
+    /// 规则名称
+    pub name: String,
+    /// 是否启用
+    #[serde(default = "default_rule_enabled")]
+    pub enabled: bool,
+    /// 过滤表达式（为空时匹配所有 Flow）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_expr: Option<String>,
+    /// 生效阶段
+    #[serde(default = "default_rule_stage")]
+    pub stage: InterceptType,
+    /// 按顺序执行的动作
+    pub actions: Vec<RuleAction>,
+}
+
+fn default_rule_enabled() -> bool {
+    true
+}
+
+fn default_rule_stage() -> InterceptType {
+    InterceptType::Request
+}
+
+fn default_respond_status() -> u16 {
+    200
+}
+
+/// 规则动作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// 按 JSON Pointer 设置字段（请求阶段作用于请求体，响应阶段作用于响应体）
+    Patch { pointer: String, value: Value },
+    /// 按 JSON Pointer 删除字段
+    Remove { pointer: String },
+    /// 注入系统消息（仅请求阶段）
+    InjectSystem {
+        content: String,
+        /// 追加到已有系统消息之后（默认插入到最前）
+        #[serde(default)]
+        append: bool,
+    },
+    /// 直接返回指定响应体
+    Respond {
+        #[serde(default = "default_respond_status")]
+        status: u16,
+        body: Value,
+    },
+    /// 增加延迟
+    Delay { ms: u64 },
+    /// 返回错误状态码
+    Fail {
+        status: u16,
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
+
+impl InterceptRule {
+    /// 校验过滤表达式、JSON Pointer 和状态码
+    pub fn validate(&self) -> Result<(), InterceptorError> {
+        let invalid = |reason: String| {
+            InterceptorError::InvalidRule(format!("规则 '{}': {}", self.name, reason))
+        };
+
+        if let Some(expr) = self.filter_expr.as_deref().filter(|e| !e.trim().is_empty()) {
+            FilterParser::parse(expr)
+                .map_err(|e| InterceptorError::InvalidFilterExpr(e.to_string()))?;
+        }
+        for action in &self.actions {
+            match action {
+                RuleAction::Patch { pointer, .. } | RuleAction::Remove { pointer }
+                    if !pointer.starts_with('/') =>
+                {
+                    return Err(invalid(format!(
+                        "JSON Pointer 必须以 '/' 开头: {}",
+                        pointer
+                    )));
+                }
+                RuleAction::Respond { status, .. } | RuleAction::Fail { status, .. }
+                    if !(100..=599).contains(status) =>
+                {
+                    return Err(invalid(format!("无效的状态码: {}", status)));
+                }
+                RuleAction::InjectSystem { .. } if self.stage == InterceptType::Response => {
+                    return Err(invalid("inject_system 只能用于请求阶段".to_string()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// 规则执行
+// ============================================================================
+
+/// 规则执行结果
+#[derive(Debug, Clone, Default)]
+pub struct RuleOutcome {
+    /// 匹配的规则名称
+    pub matched: Vec<String>,
+    /// 累计延迟
+    pub delay: Duration,
+    /// 修改后的请求（仅请求阶段）
+    pub request: Option<LLMRequest>,
+    /// 直接返回的状态码和响应体（请求阶段不调用上游，响应阶段替换上游响应）
+    pub respond: Option<(u16, Value)>,
+}
+
+impl RuleOutcome {
+    /// 是否有规则匹配
+    pub fn is_matched(&self) -> bool {
+        !self.matched.is_empty()
+    }
+}
+
+type MatchFn = Arc<dyn Fn(&LLMFlow) -> bool + Send + Sync>;
+
+/// 编译后的规则集
+#[derive(Clone, Default)]
+pub struct CompiledRules {
+    rules: Vec<(InterceptRule, Option<MatchFn>)>,
+}
+
+impl CompiledRules {
+    /// 编译启用的规则（调用前应先校验）
+    pub fn compile(rules: &[InterceptRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .map(|rule| {
+                let matcher = rule
+                    .filter_expr
+                    .as_deref()
+                    .filter(|e| !e.trim().is_empty())
+                    .and_then(|expr| FilterParser::parse(expr).ok())
+                    .map(|parsed| {
+                        let filter = FilterParser::compile(&parsed);
+                        Arc::new(move |flow: &LLMFlow| filter(flow)) as MatchFn
+                    });
+                (rule.clone(), matcher)
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 按顺序执行匹配的规则，直到某条规则决定直接返回响应
+    ///
+    /// 请求阶段修改 `flow.request.body`，响应阶段修改 `flow.response.body`；
+    /// 响应体被修改时以原状态码直接返回修改后的响应体。
+    pub fn apply(&self, flow: &LLMFlow, stage: &InterceptType) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        let mut body = match stage {
+            InterceptType::Request => flow.request.body.clone(),
+            InterceptType::Response => flow
+                .response
+                .as_ref()
+                .map(|r| r.body.clone())
+                .unwrap_or(Value::Null),
+        };
+        let mut modified = false;
+
+        'rules: for (rule, matcher) in &self.rules {
+            if &rule.stage != stage || !matcher.as_ref().is_none_or(|m| m(flow)) {
+                continue;
+            }
+            outcome.matched.push(rule.name.clone());
+
+            for action in &rule.actions {
+                match action {
+                    RuleAction::Patch { pointer, value } => {
+                        modified |= set_pointer(&mut body, pointer, value.clone());
+                    }
+                    RuleAction::Remove { pointer } => {
+                        modified |= remove_pointer(&mut body, pointer);
+                    }
+                    RuleAction::InjectSystem { content, append } => {
+                        modified |= inject_system(&mut body, &flow.request.path, content, *append);
+                    }
+                    RuleAction::Delay { ms } => {
+                        outcome.delay += Duration::from_millis(*ms);
+                    }
+                    RuleAction::Respond { status, body } => {
+                        outcome.respond = Some((*status, body.clone()));
+                        break 'rules;
+                    }
+                    RuleAction::Fail { status, message } => {
+                        let message = message
+                            .clone()
+                            .unwrap_or_else(|| format!("Injected error by rule '{}'", rule.name));
+                        outcome.respond =
+                            Some((*status, error_body(&flow.request.path, *status, &message)));
+                        break 'rules;
+                    }
+                }
+            }
+        }
+
+        if modified && outcome.respond.is_none() {
+            match stage {
+                InterceptType::Request => {
+                    let mut request = flow.request.clone();
+                    request.body = body;
+                    outcome.request = Some(request);
+                }
+                InterceptType::Response => {
+                    let status = flow.response.as_ref().map_or(200, |r| r.status_code);
+                    outcome.respond = Some((status, body));
+                }
+            }
+        }
+        outcome
+    }
+}
+
+// ============================================================================
+// JSON 操作
+// ============================================================================
+
+/// 拆分 JSON Pointer 为父路径和最后一段（已反转义）
+fn split_pointer(pointer: &str) -> Option<(&str, String)> {
+    let index = pointer.rfind('/')?;
+    let token = pointer[index + 1..].replace("~1", "/").replace("~0", "~");
+    Some((&pointer[..index], token))
+}
+
+/// 按 JSON Pointer 设置字段，父路径必须存在；数组下标 `-` 表示追加
+fn set_pointer(target: &mut Value, pointer: &str, value: Value) -> bool {
+    let Some((parent, token)) = split_pointer(pointer) else {
+        return false;
+    };
+    match target.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+            true
+        }
+        Some(Value::Array(items)) => {
+            if token == "-" {
+                items.push(value);
+                return true;
+            }
+            match token.parse::<usize>() {
+                Ok(i) if i < items.len() => {
+                    items[i] = value;
+                    true
+                }
+                Ok(i) if i == items.len() => {
+                    items.push(value);
+                    true
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// 按 JSON Pointer 删除字段
+fn remove_pointer(target: &mut Value, pointer: &str) -> bool {
+    let Some((parent, token)) = split_pointer(pointer) else {
+        return false;
+    };
+    match target.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token).is_some(),
+        Some(Value::Array(items)) => match token.parse::<usize>() {
+            Ok(i) if i < items.len() => {
+                items.remove(i);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// 注入系统消息：Anthropic 写入 `system` 字段，OpenAI 插入 `system` 角色消息
+fn inject_system(body: &mut Value, path: &str, content: &str, append: bool) -> bool {
+    let Some(object) = body.as_object_mut() else {
+        return false;
+    };
+
+    if path == "/v1/messages" {
+        let system = object
+            .entry("system")
+            .or_insert_with(|| Value::String(String::new()));
+        match system {
+            Value::String(text) if text.is_empty() => *text = content.to_string(),
+            Value::String(text) if append => *text = format!("{}\n\n{}", text, content),
+            Value::String(text) => *text = format!("{}\n\n{}", content, text),
+            Value::Array(blocks) => {
+                let block = json!({"type": "text", "text": content});
+                if append {
+                    blocks.push(block);
+                } else {
+                    blocks.insert(0, block);
+                }
+            }
+            _ => return false,
+        }
+        return true;
+    }
+
+    let Some(messages) = object.get_mut("messages").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let index = if append {
+        messages
+            .iter()
+            .take_while(|m| m["role"] == "system")
+            .count()
+    } else {
+        0
+    };
+    messages.insert(index, json!({"role": "system", "content": content}));
+    true
+}
+
+/// 按端点格式构建错误响应体
+fn error_body(path: &str, status: u16, message: &str) -> Value {
+    if path == "/v1/messages" {
+        let error_type = match status {
+            400 => "invalid_request_error",
+            401 => "authentication_error",
+            403 => "permission_error",
+            404 => "not_found_error",
+            429 => "rate_limit_error",
+            529 => "overloaded_error",
+            _ => "api_error",
+        };
+        json!({"type": "error", "error": {"type": error_type, "message": message}})
+    } else {
+        json!({"error": {"message": message, "type": "injected_error", "code": status}})
+    }
+}
+
+// ============================================================================
+// 单元测试
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::models::{FlowMetadata, FlowType, LLMResponse};
+
+    fn flow(path: &str, body: Value) -> LLMFlow {
+        let request = LLMRequest {
+            path: path.to_string(),
+            model: body["model"].as_str().unwrap_or_default().to_string(),
+            body,
+            ..Default::default()
+        };
+        LLMFlow::new(
+            "flow-1".to_string(),
+            FlowType::ChatCompletions,
+            request,
+            FlowMetadata::default(),
+        )
+    }
+
+    fn rule(filter: Option<&str>, stage: InterceptType, actions: Vec<RuleAction>) -> InterceptRule {
+        InterceptRule {
+            name: "test".to_string(),
+            enabled: true,
+            filter_expr: filter.map(String::from),
+            stage,
+            actions,
+        }
+    }
+
+    #[test]
+    fn test_patch_and_inject_system_on_openai_request() {
+        let rules = CompiledRules::compile(&[rule(
+            Some("~m gpt-4o"),
+            InterceptType::Request,
+            vec![
+                RuleAction::Patch {
+                    pointer: "/temperature".to_string(),
+                    value: json!(0),
+                },
+                RuleAction::InjectSystem {
+                    content: "Be brief.".to_string(),
+                    append: true,
+                },
+                RuleAction::Delay { ms: 50 },
+            ],
+        )]);
+        let body = json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "hi"}
+            ]
+        });
+
+        let outcome = rules.apply(&flow("/v1/chat/completions", body), &InterceptType::Request);
+        let request = outcome.request.expect("request modified");
+        assert_eq!(request.body["temperature"], json!(0));
+        assert_eq!(request.body["messages"][1]["content"], "Be brief.");
+        assert_eq!(request.body["messages"][2]["role"], "user");
+        assert_eq!(outcome.delay, Duration::from_millis(50));
+        assert!(outcome.respond.is_none());
+
+        let other = json!({"model": "claude-sonnet-4", "messages": []});
+        let outcome = rules.apply(
+            &flow("/v1/chat/completions", other),
+            &InterceptType::Request,
+        );
+        assert!(!outcome.is_matched());
+    }
+
+    #[test]
+    fn test_inject_system_on_anthropic_request() {
+        let rules = CompiledRules::compile(&[rule(
+            None,
+            InterceptType::Request,
+            vec![RuleAction::InjectSystem {
+                content: "Be brief.".to_string(),
+                append: false,
+            }],
+        )]);
+        let body = json!({"model": "claude", "system": "Base.", "messages": []});
+        let outcome = rules.apply(&flow("/v1/messages", body), &InterceptType::Request);
+        assert_eq!(
+            outcome.request.unwrap().body["system"],
+            json!("Be brief.\n\nBase.")
+        );
+    }
+
+    #[test]
+    fn test_fail_and_respond_stop_processing() {
+        let rules = CompiledRules::compile(&[
+            rule(
+                None,
+                InterceptType::Request,
+                vec![RuleAction::Fail {
+                    status: 429,
+                    message: None,
+                }],
+            ),
+            rule(
+                None,
+                InterceptType::Request,
+                vec![RuleAction::Delay { ms: 10 }],
+            ),
+        ]);
+        let outcome = rules.apply(
+            &flow("/v1/messages", json!({"model": "claude"})),
+            &InterceptType::Request,
+        );
+        let (status, body) = outcome.respond.unwrap();
+        assert_eq!(status, 429);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert_eq!(outcome.delay, Duration::ZERO);
+        assert_eq!(outcome.matched.len(), 1);
+    }
+
+    #[test]
+    fn test_response_patch_returns_modified_body() {
+        let rules = CompiledRules::compile(&[rule(
+            Some("~bs secret"),
+            InterceptType::Response,
+            vec![RuleAction::Patch {
+                pointer: "/choices/0/message/content".to_string(),
+                value: json!("[redacted]"),
+            }],
+        )]);
+        let mut flow = flow("/v1/chat/completions", json!({"model": "gpt-4o"}));
+        flow.response = Some(LLMResponse {
+            status_code: 200,
+            content: "the secret".to_string(),
+            body: json!({"choices": [{"message": {"content": "the secret"}}]}),
+            ..Default::default()
+        });
+
+        let (status, body) = rules
+            .apply(&flow, &InterceptType::Response)
+            .respond
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body["choices"][0]["message"]["content"], "[redacted]");
+        assert!(rules
+            .apply(&flow, &InterceptType::Request)
+            .respond
+            .is_none());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_rules() {
+        let bad_pointer = rule(
+            None,
+            InterceptType::Request,
+            vec![RuleAction::Remove {
+                pointer: "temperature".to_string(),
+            }],
+        );
+        assert!(matches!(
+            bad_pointer.validate(),
+            Err(InterceptorError::InvalidRule(_))
+        ));
+
+        let bad_filter = rule(Some("~m"), InterceptType::Request, vec![]);
+        assert!(matches!(
+            bad_filter.validate(),
+            Err(InterceptorError::InvalidFilterExpr(_))
+        ));
+
+        let inject_response = rule(
+            None,
+            InterceptType::Response,
+            vec![RuleAction::InjectSystem {
+                content: "x".to_string(),
+                append: false,
+            }],
+        );
+        assert!(inject_response.validate().is_err());
+    }
+
+    #[test]
+    fn test_rule_deserializes_with_defaults() {
+        let rule: InterceptRule = serde_json::from_value(json!({
+            "name": "slow",
+            "actions": [{"type": "delay", "ms": 200}, {"type": "respond", "body": {"ok": true}}]
+        }))
+        .unwrap();
+        assert!(rule.enabled);
+        assert_eq!(rule.stage, InterceptType::Request);
+        assert_eq!(
+            rule.actions[1],
+            RuleAction::Respond {
+                status: 200,
+                body: json!({"ok": true})
+            }
+        );
+    }
+}
@@ -7,6 +7,7 @@
 //! - 根据过滤表达式拦截匹配的 Flow
 //! - 支持拦截请求、响应或两者
 //! - 支持超时自动处理
+//! - 自动拦截规则（无需人工操作，见 [`super::intercept_rules`]）
 //! - 实时事件广播
 
 use chrono::{DateTime, Utc};
@@ -17,6 +18,7 @@ use tokio::sync::{broadcast, oneshot, RwLock};
 use tokio::time::{timeout, Duration};
 
 use super::filter_parser::FilterParser;
+use super::intercept_rules::{CompiledRules, InterceptRule, RuleOutcome};
 use super::models::{LLMFlow, LLMRequest, LLMResponse};
 
 // ============================================================================
@@ -60,6 +62,9 @@ pub struct InterceptConfig {
     /// 超时动作
     #[serde(default)]
     pub timeout_action: TimeoutAction,
+    /// 自动拦截规则（不受 `enabled` 影响，按各规则自身的开关生效）
+    #[serde(default)]
+    pub rules: Vec<InterceptRule>,
 }
 
 fn default_intercept_request() -> bool {
@@ -79,6 +84,7 @@ impl Default for InterceptConfig {
             intercept_response: false,
             timeout_ms: default_timeout_ms(),
             timeout_action: TimeoutAction::default(),
+            rules: Vec::new(),
         }
     }
 }
@@ -224,6 +230,17 @@ pub enum InterceptEvent {
         /// 超时动作
         action: TimeoutAction,
     },
+    /// 自动拦截规则已执行
+    RuleApplied {
+        /// Flow ID
+        flow_id: String,
+        /// 生效阶段
+        stage: InterceptType,
+        /// 匹配的规则名称
+        rules: Vec<String>,
+        /// 是否直接返回了规则指定的响应
+        responded: bool,
+    },
     /// 配置已更新
     ConfigUpdated {
         /// 新配置
@@ -274,6 +291,9 @@ pub enum InterceptorError {
     /// 操作已完成
     #[error("Flow '{0}' 的拦截操作已完成")]
     AlreadyCompleted(String),
+    /// 无效的自动拦截规则
+    #[error("无效的拦截规则: {0}")]
+    InvalidRule(String),
     /// 内部错误
     #[error("内部错误: {0}")]
     Internal(String),
@@ -291,6 +311,8 @@ pub struct FlowInterceptor {
     config: RwLock<InterceptConfig>,
     /// 编译后的过滤器
     filter: RwLock<Option<Arc<dyn Fn(&LLMFlow) -> bool + Send + Sync>>>,
+    /// 编译后的自动拦截规则
+    rules: RwLock<CompiledRules>,
     /// 等待中的拦截
     pending_intercepts: RwLock<HashMap<String, PendingIntercept>>,
     /// 事件发送器
@@ -302,10 +324,12 @@ impl FlowInterceptor {
     pub fn new(config: InterceptConfig) -> Self {
         let (event_sender, _) = broadcast::channel(100);
         let filter = Self::compile_filter(&config.filter_expr);
+        let rules = CompiledRules::compile(&config.rules);
 
         Self {
             config: RwLock::new(config),
             filter: RwLock::new(filter),
+            rules: RwLock::new(rules),
             pending_intercepts: RwLock::new(HashMap::new()),
             event_sender,
         }
@@ -336,9 +360,13 @@ impl FlowInterceptor {
             FilterParser::parse(expr)
                 .map_err(|e| InterceptorError::InvalidFilterExpr(e.to_string()))?;
         }
+        for rule in &config.rules {
+            rule.validate()?;
+        }
 
-        // 编译新的过滤器
+        // 编译新的过滤器和规则
         let new_filter = Self::compile_filter(&config.filter_expr);
+        let new_rules = CompiledRules::compile(&config.rules);
 
         // 更新配置和过滤器
         {
@@ -349,6 +377,7 @@ impl FlowInterceptor {
             let mut current_filter = self.filter.write().await;
             *current_filter = new_filter;
         }
+        *self.rules.write().await = new_rules;
 
         // 发送配置更新事件
         let _ = self
@@ -396,6 +425,29 @@ impl FlowInterceptor {
         }
     }
 
+    /// 执行匹配的自动拦截规则
+    ///
+    /// 规则只计算修改结果和延迟，由调用方应用修改并等待延迟。
+    pub async fn apply_rules(&self, flow: &LLMFlow, stage: &InterceptType) -> RuleOutcome {
+        let outcome = {
+            let rules = self.rules.read().await;
+            if rules.is_empty() {
+                return RuleOutcome::default();
+            }
+            rules.apply(flow, stage)
+        };
+
+        if outcome.is_matched() {
+            let _ = self.event_sender.send(InterceptEvent::RuleApplied {
+                flow_id: flow.id.clone(),
+                stage: stage.clone(),
+                rules: outcome.matched.clone(),
+                responded: outcome.respond.is_some(),
+            });
+        }
+        outcome
+    }
+
     /// 拦截请求
     pub async fn intercept_request(&self, flow_id: &str, request: LLMRequest) -> InterceptedFlow {
         let intercepted = InterceptedFlow::new_request(flow_id.to_string(), request);
@@ -618,6 +670,7 @@ impl Default for FlowInterceptor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flow_monitor::intercept_rules::RuleAction;
     use crate::flow_monitor::models::{
         FlowMetadata, FlowType, LLMRequest, Message, MessageContent, MessageRole,
         RequestParameters, TokenUsage,
@@ -900,6 +953,70 @@ mod tests {
         assert!(matches!(result, Err(InterceptorError::FlowNotFound(_))));
     }
 
+    #[tokio::test]
+    async fn test_rules_apply_without_manual_interception() {
+        let interceptor = FlowInterceptor::default();
+        let mut events = interceptor.subscribe();
+
+        let invalid = InterceptConfig {
+            rules: vec![InterceptRule {
+                name: "bad".to_string(),
+                enabled: true,
+                filter_expr: None,
+                stage: InterceptType::Request,
+                actions: vec![RuleAction::Fail {
+                    status: 42,
+                    message: None,
+                }],
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            interceptor.update_config(invalid).await,
+            Err(InterceptorError::InvalidRule(_))
+        ));
+
+        let config = InterceptConfig {
+            rules: vec![InterceptRule {
+                name: "flaky-claude".to_string(),
+                enabled: true,
+                filter_expr: Some("~m claude".to_string()),
+                stage: InterceptType::Request,
+                actions: vec![RuleAction::Fail {
+                    status: 503,
+                    message: Some("injected".to_string()),
+                }],
+            }],
+            ..Default::default()
+        };
+        interceptor.update_config(config).await.unwrap();
+        let _ = events.recv().await; // ConfigUpdated
+
+        let flow = create_test_flow("claude-3-opus", ProviderType::Claude);
+        let outcome = interceptor
+            .apply_rules(&flow, &InterceptType::Request)
+            .await;
+        assert_eq!(outcome.respond.map(|(status, _)| status), Some(503));
+        assert!(
+            !interceptor
+                .should_intercept(&flow, &InterceptType::Request)
+                .await
+        );
+        assert!(matches!(
+            events.recv().await,
+            Ok(InterceptEvent::RuleApplied {
+                responded: true,
+                ..
+            })
+        ));
+
+        let other = create_test_flow("gpt-4", ProviderType::OpenAI);
+        assert!(!interceptor
+            .apply_rules(&other, &InterceptType::Request)
+            .await
+            .is_matched());
+    }
+
     #[tokio::test]
     async fn test_update_config() {
         let interceptor = FlowInterceptor::default();
@@ -911,6 +1028,7 @@ mod tests {
             intercept_response: true,
             timeout_ms: 60000,
             timeout_action: TimeoutAction::Cancel,
+            rules: Vec::new(),
         };
 
         let result = interceptor.update_config(new_config.clone()).await;
@@ -1202,6 +1320,7 @@ mod property_tests {
                         intercept_response,
                         timeout_ms,
                         timeout_action,
+                        rules: Vec::new(),
                     }
                 },
             )
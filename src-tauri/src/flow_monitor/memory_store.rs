@@ -127,6 +127,9 @@ pub struct FlowFilter {
     /// Flow 类型
     #[serde(skip_serializing_if = "Option::is_none")]
     pub flow_types: Option<Vec<FlowType>>,
+    /// 对话 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
 }
 
 impl FlowFilter {
@@ -280,6 +283,18 @@ impl FlowFilter {
             }
         }
 
+        // 对话过滤
+        if let Some(ref conversation_id) = self.conversation_id {
+            let flow_conversation = flow
+                .metadata
+                .conversation
+                .as_ref()
+                .map(|c| &c.conversation_id);
+            if flow_conversation != Some(conversation_id) {
+                return false;
+            }
+        }
+
         true
     }
 
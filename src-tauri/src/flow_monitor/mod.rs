@@ -11,21 +11,29 @@
 //! - `file_store`: 文件存储，支持 JSONL 格式和 SQLite 索引
 //! - `query_service`: 查询服务，支持多维度过滤、排序、分页和全文搜索
 //! - `exporter`: 导出服务，支持 HAR、JSON、JSONL、Markdown、CSV 格式
+//! - `importer`: 导入服务，支持 HAR、JSONL 以及 OpenAI/Anthropic 请求响应日志
 //! - `monitor`: 核心监控服务
+//! - `playback`: 会话录制与回放，用于离线、确定性地测试 Agent
 //! - `filter_parser`: 高级过滤表达式解析器，支持类似 mitmproxy 的语法
+//! - `intercept_rules`: 自动拦截规则，按过滤表达式自动修改请求/响应或注入故障
+//! - `conversation`: 对话串联，按消息前缀关系把多轮请求串成对话树
 
 pub mod batch_ops;
 pub mod bookmark;
 pub mod code_exporter;
+pub mod conversation;
 pub mod diff;
 pub mod enhanced_stats;
 pub mod exporter;
 pub mod file_store;
 pub mod filter_parser;
+pub mod importer;
+pub mod intercept_rules;
 pub mod interceptor;
 pub mod memory_store;
 pub mod models;
 pub mod monitor;
+pub mod playback;
 pub mod query_service;
 pub mod quick_filter;
 pub mod replayer;
@@ -78,6 +86,12 @@ pub use file_store::{
     CleanupResult, FileStoreError, FlowFileStore, FlowIndexRecord, FtsSearchResult, RotationConfig,
 };
 
+// 重新导出对话串联
+pub use conversation::{
+    Conversation, ConversationLink, ConversationNode, ConversationRelation, ConversationSummary,
+    ConversationTotals, ConversationTracker,
+};
+
 // 重新导出查询服务
 pub use query_service::{
     FlowQueryResult, FlowQueryService, FlowSearchResult, FlowSortBy, FlowStats, ModelStats,
@@ -90,6 +104,19 @@ pub use exporter::{
     HarEntry, HarLlmExtension, HarLog, RedactionRule, Redactor,
 };
 
+// 重新导出录制/回放
+pub use playback::{
+    FlowPlayback, PlaybackDecision, PlaybackError, PlaybackMissPolicy, PlaybackOptions,
+    PlaybackReply, PlaybackStatus, ReplayBody, ReplayChunk, SessionMode, PLAYBACK_HEADER,
+    SESSION_HEADER,
+};
+
+// 重新导出导入服务
+pub use importer::{
+    FlowImporter, ImportError, ImportFormat, ImportOptions, ImportResult, ImportStoreError,
+    ImportSummary,
+};
+
 // 重新导出监控服务
 pub use monitor::{
     FlowEvent, FlowMonitor, FlowMonitorConfig, FlowSummary, FlowUpdate, RequestRateTracker,
@@ -103,6 +130,7 @@ pub use filter_parser::{
 };
 
 // 重新导出拦截器
+pub use intercept_rules::{InterceptRule, RuleAction, RuleOutcome};
 pub use interceptor::{
     FlowInterceptor, InterceptAction, InterceptConfig, InterceptEvent, InterceptState,
     InterceptType, InterceptedFlow, InterceptorError, ModifiedData, TimeoutAction,
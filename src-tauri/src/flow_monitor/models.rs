@@ -6,6 +6,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::conversation::ConversationLink;
+use crate::resilience::HedgeAttemptRecord;
 use crate::ProviderType;
 
 // ============================================================================
@@ -561,6 +563,21 @@ pub struct FlowMetadata {
     /// 上下文使用百分比
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_usage_percentage: Option<f32>,
+    /// 是否由响应缓存直接返回（未请求上游）
+    #[serde(default)]
+    pub cached: bool,
+    /// 对冲请求的各次尝试（未触发对冲时为空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hedge_attempts: Vec<HedgeAttemptRecord>,
+    /// 录制到的会话 ID（会话处于录制模式时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording_session: Option<String>,
+    /// 回放的录制 Flow ID（由会话回放直接返回时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playback_of: Option<String>,
+    /// 所属对话（由消息前缀关系推断）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<ConversationLink>,
 }
 
 impl Default for FlowMetadata {
@@ -575,6 +592,11 @@ impl Default for FlowMetadata {
             routing_info: RoutingInfo::default(),
             injected_params: None,
             context_usage_percentage: None,
+            cached: false,
+            hedge_attempts: Vec::new(),
+            recording_session: None,
+            playback_of: None,
+            conversation: None,
         }
     }
 }
@@ -591,6 +613,12 @@ pub struct ClientInfo {
     /// 请求 ID
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
+    /// 客户端 key ID（多租户）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key_id: Option<String>,
+    /// 客户端 key 名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key_label: Option<String>,
 }
 
 /// 路由信息
@@ -1075,6 +1103,11 @@ mod property_tests {
                 routing_info: RoutingInfo::default(),
                 injected_params: None,
                 context_usage_percentage: None,
+                cached: false,
+                hedge_attempts: Vec::new(),
+                recording_session: None,
+                playback_of: None,
+                conversation: None,
             })
     }
 
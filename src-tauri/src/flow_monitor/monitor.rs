@@ -17,13 +17,19 @@ use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
+use super::conversation::{ConversationKey, ConversationTracker};
 use super::file_store::FlowFileStore;
 use super::memory_store::FlowMemoryStore;
 use super::models::{
     FlowAnnotations, FlowError, FlowMetadata, FlowState, FlowType, LLMFlow, LLMRequest,
     LLMResponse, TokenUsage,
 };
+use super::playback::{FlowPlayback, PlaybackError, PlaybackOptions, PlaybackStatus};
 use super::stream_rebuilder::{StreamFormat, StreamRebuilder};
+use crate::resilience::{CircuitTransition, HedgeAttemptRecord};
+use crate::services::alert_service::AlertEvent;
+use crate::services::canary_service::CanaryProbeEvent;
+use crate::services::token_refresh_scheduler::TokenRefreshEvent;
 
 // ============================================================================
 // 配置结构
@@ -310,6 +316,8 @@ pub enum NotificationType {
     LatencyWarning,
     /// Token 阈值警告
     TokenWarning,
+    /// 费用预算警告
+    BudgetWarning,
 }
 
 /// 通知配置
@@ -334,6 +342,9 @@ pub struct NotificationConfig {
     /// Token 警告通知配置
     #[serde(default = "default_token_warning")]
     pub token_warning: NotificationSettings,
+    /// 费用预算警告通知配置
+    #[serde(default = "default_budget_warning")]
+    pub budget_warning: NotificationSettings,
 }
 
 /// 通知设置
@@ -380,6 +391,15 @@ fn default_token_warning() -> NotificationSettings {
     }
 }
 
+fn default_budget_warning() -> NotificationSettings {
+    NotificationSettings {
+        enabled: true,
+        desktop: true,
+        sound: false,
+        sound_file: None,
+    }
+}
+
 impl Default for NotificationSettings {
     fn default() -> Self {
         Self {
@@ -399,6 +419,7 @@ impl Default for NotificationConfig {
             error_flow: default_error_notification(),
             latency_warning: default_latency_warning(),
             token_warning: default_token_warning(),
+            budget_warning: default_budget_warning(),
         }
     }
 }
@@ -507,6 +528,25 @@ impl NotificationEvent {
             sound_file: settings.sound_file.clone(),
         }
     }
+
+    /// 创建费用预算警告通知
+    pub fn budget_warning(
+        flow_id: String,
+        title: String,
+        message: String,
+        settings: &NotificationSettings,
+    ) -> Self {
+        Self {
+            notification_type: NotificationType::BudgetWarning,
+            title,
+            message,
+            flow_id,
+            timestamp: Utc::now(),
+            desktop: settings.desktop,
+            sound: settings.sound,
+            sound_file: settings.sound_file.clone(),
+        }
+    }
 }
 
 // ============================================================================
@@ -722,6 +762,14 @@ pub enum FlowEvent {
     ///
     /// **Validates: Requirements 10.7**
     RequestRateUpdate { rate: f64, count: usize },
+    /// 熔断器状态变化
+    CircuitBreakerStateChanged { transition: CircuitTransition },
+    /// OAuth Token 提前刷新结果
+    TokenRefreshed { event: TokenRefreshEvent },
+    /// 凭证健康定时探测结果（探测流量，不计入请求统计）
+    CanaryProbe { event: CanaryProbeEvent },
+    /// 告警规则触发或恢复
+    Alert { event: AlertEvent },
 }
 
 // ============================================================================
@@ -738,6 +786,8 @@ struct ActiveFlow {
     stream_rebuilder: Option<StreamRebuilder>,
     /// 请求开始时间
     request_start: DateTime<Utc>,
+    /// 对话串联凭据（请求中有消息时）
+    conversation: Option<ConversationKey>,
 }
 
 // ============================================================================
@@ -764,6 +814,10 @@ pub struct FlowMonitor {
     rate_tracker: RwLock<RequestRateTracker>,
     /// 通知配置
     notification_config: RwLock<NotificationConfig>,
+    /// 会话录制/回放控制器
+    playback: Arc<FlowPlayback>,
+    /// 对话串联追踪器
+    conversations: ConversationTracker,
 }
 
 impl FlowMonitor {
@@ -785,6 +839,8 @@ impl FlowMonitor {
             threshold_config: RwLock::new(ThresholdConfig::default()),
             rate_tracker: RwLock::new(RequestRateTracker::default()),
             notification_config: RwLock::new(NotificationConfig::default()),
+            playback: Arc::new(FlowPlayback::new()),
+            conversations: ConversationTracker::new(),
         }
     }
 
@@ -813,6 +869,8 @@ impl FlowMonitor {
             threshold_config: RwLock::new(threshold_config),
             rate_tracker: RwLock::new(RequestRateTracker::default()),
             notification_config: RwLock::new(notification_config),
+            playback: Arc::new(FlowPlayback::new()),
+            conversations: ConversationTracker::new(),
         }
     }
 
@@ -841,6 +899,8 @@ impl FlowMonitor {
             threshold_config: RwLock::new(threshold_config),
             rate_tracker: RwLock::new(RequestRateTracker::default()),
             notification_config: RwLock::new(notification_config),
+            playback: Arc::new(FlowPlayback::new()),
+            conversations: ConversationTracker::new(),
         }
     }
 
@@ -854,6 +914,37 @@ impl FlowMonitor {
         self.file_store.clone()
     }
 
+    /// 获取会话录制/回放控制器
+    pub fn playback(&self) -> Arc<FlowPlayback> {
+        self.playback.clone()
+    }
+
+    /// 按 ID 获取已完成的 Flow（先查内存，再查文件存储）
+    pub async fn get_flow(&self, flow_id: &str) -> Option<LLMFlow> {
+        let in_memory = self.memory_store.read().await.get(flow_id);
+        if let Some(flow) = in_memory {
+            return flow.read().ok().map(|f| f.clone());
+        }
+        self.file_store
+            .as_ref()
+            .and_then(|store| store.get(flow_id).ok().flatten())
+    }
+
+    /// 使用会话中录制的 Flow 开始回放
+    pub async fn start_playback(
+        &self,
+        session_id: &str,
+        options: PlaybackOptions,
+    ) -> Result<PlaybackStatus, PlaybackError> {
+        let mut flows = Vec::new();
+        for flow_id in self.playback.session_flow_ids(session_id)? {
+            if let Some(flow) = self.get_flow(&flow_id).await {
+                flows.push(flow);
+            }
+        }
+        self.playback.play(session_id, flows, options)
+    }
+
     /// 获取当前配置
     pub async fn config(&self) -> FlowMonitorConfig {
         self.config.read().await.clone()
@@ -1019,6 +1110,24 @@ impl FlowMonitor {
         }
     }
 
+    /// 发送费用预算警告通知
+    ///
+    /// 由预算服务在花费达到预警比例或超出预算时调用
+    pub async fn notify_budget_warning(&self, flow_id: &str, title: String, message: String) {
+        let config = self.notification_config.read().await;
+
+        if config.budget_warning.enabled {
+            let notification = NotificationEvent::budget_warning(
+                flow_id.to_string(),
+                title,
+                message,
+                &config.budget_warning,
+            );
+            drop(config);
+            self.trigger_notification(notification).await;
+        }
+    }
+
     /// 发送请求速率更新事件
     ///
     /// **Validates: Requirements 10.7**
@@ -1038,6 +1147,28 @@ impl FlowMonitor {
         self.event_sender.subscribe()
     }
 
+    /// 发布熔断器状态变化事件
+    pub fn publish_circuit_transition(&self, transition: CircuitTransition) {
+        let _ = self
+            .event_sender
+            .send(FlowEvent::CircuitBreakerStateChanged { transition });
+    }
+
+    /// 发布 OAuth Token 提前刷新结果事件
+    pub fn publish_token_refresh(&self, event: TokenRefreshEvent) {
+        let _ = self.event_sender.send(FlowEvent::TokenRefreshed { event });
+    }
+
+    /// 发布凭证健康定时探测结果事件
+    pub fn publish_canary_probe(&self, event: CanaryProbeEvent) {
+        let _ = self.event_sender.send(FlowEvent::CanaryProbe { event });
+    }
+
+    /// 发布告警规则触发/恢复事件
+    pub fn publish_alert(&self, event: AlertEvent) {
+        let _ = self.event_sender.send(FlowEvent::Alert { event });
+    }
+
     /// 开始捕获一个新的 Flow
     ///
     /// # 参数
@@ -1047,7 +1178,11 @@ impl FlowMonitor {
     /// # 返回
     /// - `Some(flow_id)`: 成功创建 Flow，返回 Flow ID
     /// - `None`: 根据配置跳过监控
-    pub async fn start_flow(&self, request: LLMRequest, metadata: FlowMetadata) -> Option<String> {
+    pub async fn start_flow(
+        &self,
+        request: LLMRequest,
+        mut metadata: FlowMetadata,
+    ) -> Option<String> {
         let config = self.config.read().await;
 
         // 检查是否应该监控
@@ -1076,6 +1211,17 @@ impl FlowMonitor {
         // 确定 Flow 类型
         let flow_type = Self::determine_flow_type(&request.path);
 
+        // 按消息前缀关系串联到已有对话
+        let conversation = self.conversations.link_request(
+            &flow_id,
+            &request,
+            metadata.client_info.client_key_id.as_deref(),
+        );
+        let conversation = conversation.map(|(link, key)| {
+            metadata.conversation = Some(link);
+            key
+        });
+
         // 创建 Flow
         let flow = LLMFlow::new(flow_id.clone(), flow_type, request.clone(), metadata);
 
@@ -1084,6 +1230,7 @@ impl FlowMonitor {
             flow: flow.clone(),
             stream_rebuilder: None,
             request_start: Utc::now(),
+            conversation,
         };
 
         // 添加到活跃 Flow
@@ -1137,6 +1284,8 @@ impl FlowMonitor {
 
         let mut active = self.active_flows.write().await;
         if let Some(active_flow) = active.get_mut(flow_id) {
+            // 录制中的 Flow 需要原始 chunks 才能按原始节奏回放
+            let save_chunks = save_chunks || active_flow.flow.metadata.recording_session.is_some();
             active_flow.flow.state = FlowState::Streaming;
             active_flow.stream_rebuilder =
                 Some(StreamRebuilder::new(format).with_save_raw_chunks(save_chunks));
@@ -1212,6 +1361,13 @@ impl FlowMonitor {
                 flow_id, active_flow.flow.state, active_flow.flow.timestamps.duration_ms
             );
 
+            // 登记响应，供下一轮请求串联
+            if let (Some(key), Some(response)) = (&active_flow.conversation, &final_response) {
+                if response.status_code < 400 {
+                    self.conversations.record_reply(key, response);
+                }
+            }
+
             // 检查阈值
             let threshold_result = self.check_threshold(&active_flow.flow).await;
 
@@ -1237,6 +1393,7 @@ impl FlowMonitor {
             } else {
                 eprintln!("[FLOW_MONITOR] 文件存储未启用");
             }
+            self.playback.record_flow(&active_flow.flow);
 
             // 发送完成事件
             let summary = FlowSummary::from(&active_flow.flow);
@@ -1292,6 +1449,7 @@ impl FlowMonitor {
                     tracing::error!("保存 Flow 到文件失败: {}", e);
                 }
             }
+            self.playback.record_flow(&active_flow.flow);
 
             // 发送失败事件
             let _ = self.event_sender.send(FlowEvent::FlowFailed {
@@ -1399,6 +1557,44 @@ impl FlowMonitor {
         })
     }
 
+    /// 记录对冲请求的各次尝试
+    ///
+    /// 请求进行中时写入活跃 Flow，随 Flow 完成一起保存
+    pub async fn record_hedge_attempts(
+        &self,
+        flow_id: &str,
+        attempts: Vec<HedgeAttemptRecord>,
+    ) -> bool {
+        if let Some(active_flow) = self.active_flows.write().await.get_mut(flow_id) {
+            active_flow.flow.metadata.hedge_attempts = attempts;
+            return true;
+        }
+        let store = self.memory_store.read().await;
+        store.update(flow_id, |flow| {
+            flow.metadata.hedge_attempts = attempts;
+        })
+    }
+
+    /// 记录一次断流续传：计入重试次数，Flow 的凭证改为续传所用的凭证
+    pub async fn record_stream_resume(
+        &self,
+        flow_id: &str,
+        credential_id: &str,
+        credential_name: Option<&str>,
+    ) -> bool {
+        let apply = |flow: &mut LLMFlow| {
+            flow.metadata.retry_count += 1;
+            flow.metadata.credential_id = Some(credential_id.to_string());
+            flow.metadata.credential_name = credential_name.map(str::to_string);
+        };
+        if let Some(active_flow) = self.active_flows.write().await.get_mut(flow_id) {
+            apply(&mut active_flow.flow);
+            return true;
+        }
+        let store = self.memory_store.read().await;
+        store.update(flow_id, apply)
+    }
+
     /// 设置标记
     pub async fn set_marker(&self, flow_id: &str, marker: Option<String>) -> bool {
         let store = self.memory_store.read().await;
@@ -1412,6 +1608,16 @@ impl FlowMonitor {
         self.active_flows.read().await.len()
     }
 
+    /// 获取所有活跃 Flow 的快照
+    pub async fn active_flows(&self) -> Vec<LLMFlow> {
+        self.active_flows
+            .read()
+            .await
+            .values()
+            .map(|active_flow| active_flow.flow.clone())
+            .collect()
+    }
+
     /// 获取内存中的 Flow 数量
     pub async fn memory_flow_count(&self) -> usize {
         self.memory_store.read().await.len()
@@ -1891,6 +2097,7 @@ mod property_tests {
                     error_flow: NotificationSettings::default(),
                     latency_warning: NotificationSettings::default(),
                     token_warning: NotificationSettings::default(),
+                    budget_warning: NotificationSettings::default(),
                 };
 
                 let monitor = FlowMonitor::with_notification_config(
@@ -1976,6 +2183,7 @@ mod property_tests {
                     error_flow: NotificationSettings::default(),
                     latency_warning: NotificationSettings::default(),
                     token_warning: NotificationSettings::default(),
+                    budget_warning: NotificationSettings::default(),
                 };
 
                 let monitor = FlowMonitor::with_notification_config(
@@ -2446,6 +2654,7 @@ mod property_tests {
                         sound: false,
                         sound_file: None,
                     },
+                    budget_warning: NotificationSettings::default(),
                 };
 
                 // 创建阈值配置（低阈值，容易触发）
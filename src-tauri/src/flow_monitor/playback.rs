@@ -0,0 +1,941 @@
+//! 会话录制与回放
+//!
+//! 将会话（`FlowSession`）切换为录制或回放模式，让接入 ProxyCast 的 Agent
+//! 可以离线、确定性地运行集成测试：
+//! - 录制：请求照常发往上游，完成的 Flow 自动加入会话，流式响应强制保存原始 chunks
+//! - 回放：按规范化请求哈希匹配会话中录制的 Flow，直接返回录制的响应；
+//!   配置 `matcher` 后，哈希未命中的请求在表达式匹配的录制 Flow 中按录制顺序依次回放。
+//!   流式响应按原始 chunk 间隔重新发送，可通过 `speed` 加速
+//!
+//! 请求通过 `x-proxycast-session` 请求头指定会话；未携带该请求头的请求照常发往上游，
+//! 不受任何会话影响。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+
+use super::filter_parser::FilterParser;
+use super::models::{FlowType, LLMFlow, StopReason};
+use super::session::{SessionError, SessionManager};
+use super::stream_rebuilder::StreamFormat;
+use crate::services::response_cache_service::CacheKey;
+
+/// 指定录制/回放会话的请求头
+pub const SESSION_HEADER: &str = "x-proxycast-session";
+
+/// 标记回放结果的响应头（值为录制 Flow 的 ID）
+pub const PLAYBACK_HEADER: &str = "x-proxycast-playback";
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+/// 录制/回放错误
+#[derive(Debug, Error)]
+pub enum PlaybackError {
+    #[error("会话管理器不可用")]
+    SessionManagerUnavailable,
+
+    #[error("会话不存在: {0}")]
+    SessionNotFound(String),
+
+    #[error("会话中没有可回放的 Flow: {0}")]
+    NoPlayableFlows(String),
+
+    #[error("无效的匹配表达式: {0}")]
+    InvalidMatcher(String),
+
+    #[error("无效的回放速度: {0}（应为 0 或 0.01~1000）")]
+    InvalidSpeed(f64),
+
+    #[error(transparent)]
+    Session(#[from] SessionError),
+}
+
+// ============================================================================
+// 配置与状态
+// ============================================================================
+
+/// 会话模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    /// 录制：请求发往上游，Flow 加入会话
+    Record,
+    /// 回放：从会话中录制的 Flow 返回响应
+    Playback,
+}
+
+/// 回放未命中时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackMissPolicy {
+    /// 返回错误，不访问上游（默认，保证离线确定性）
+    #[default]
+    Error,
+    /// 转发到上游
+    Passthrough,
+}
+
+/// 回放选项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaybackOptions {
+    /// 哈希未命中时使用的 `filter_parser` 匹配表达式
+    #[serde(default)]
+    pub matcher: Option<String>,
+    /// 流式回放速度倍数（1.0 为原速，0 表示不等待，其余取值范围为
+    /// [`MIN_SPEED`, `MAX_SPEED`]）
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// 未命中时的处理方式
+    #[serde(default)]
+    pub on_miss: PlaybackMissPolicy,
+}
+
+/// 最小回放速度倍数（0 除外）
+pub const MIN_SPEED: f64 = 0.01;
+
+/// 最大回放速度倍数
+pub const MAX_SPEED: f64 = 1000.0;
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            matcher: None,
+            speed: default_speed(),
+            on_miss: PlaybackMissPolicy::default(),
+        }
+    }
+}
+
+/// 会话的录制/回放状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackStatus {
+    pub session_id: String,
+    pub mode: SessionMode,
+    /// 回放选项（仅回放模式）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<PlaybackOptions>,
+    /// 本次录制加入会话的 Flow 数
+    pub recorded: usize,
+    /// 可回放的 Flow 数
+    pub playable: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// 回放内容
+// ============================================================================
+
+/// 回放的一个 SSE 事件
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayChunk {
+    /// 发送前等待的时间（已按速度缩放）
+    pub delay: Duration,
+    pub event: Option<String>,
+    pub data: String,
+}
+
+impl ReplayChunk {
+    /// 编码为 SSE 文本
+    pub fn to_sse(&self) -> String {
+        match &self.event {
+            Some(event) => format!("event: {}\ndata: {}\n\n", event, self.data),
+            None => format!("data: {}\n\n", self.data),
+        }
+    }
+}
+
+/// 回放的响应体
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayBody {
+    Json(Value),
+    Stream(Vec<ReplayChunk>),
+}
+
+/// 一次回放命中
+#[derive(Debug, Clone)]
+pub struct PlaybackReply {
+    pub session_id: String,
+    /// 被回放的录制 Flow ID
+    pub source_flow_id: String,
+    pub status_code: u16,
+    /// 流式回放时用于重建 Flow 响应的格式
+    pub format: StreamFormat,
+    pub body: ReplayBody,
+}
+
+/// 对一个请求的处理决定
+#[derive(Debug)]
+pub enum PlaybackDecision {
+    /// 不涉及录制/回放，照常处理
+    Passthrough,
+    /// 录制到指定会话
+    Record(String),
+    /// 返回录制的响应
+    Replay(PlaybackReply),
+    /// 回放未命中，且策略为返回错误
+    Miss { session_id: String },
+}
+
+// ============================================================================
+// 录制/回放控制器
+// ============================================================================
+
+/// 录制的一条可回放响应（延迟为原速）
+#[derive(Debug, Clone)]
+struct Recording {
+    flow_id: String,
+    flow: LLMFlow,
+    status_code: u16,
+    format: StreamFormat,
+    body: ReplayBody,
+}
+
+/// 回放索引：同一请求按录制顺序依次返回，用完后重复最后一条
+struct PlaybackIndex {
+    recordings: Vec<Recording>,
+    by_hash: HashMap<String, (Vec<usize>, usize)>,
+    matcher: Option<(MatchFn, Vec<usize>, usize)>,
+}
+
+type MatchFn = Box<dyn Fn(&LLMFlow) -> bool + Send + Sync>;
+
+struct SessionState {
+    mode: SessionMode,
+    options: PlaybackOptions,
+    index: Option<PlaybackIndex>,
+    recorded: usize,
+    hits: u64,
+    misses: u64,
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct PlaybackInner {
+    sessions: HashMap<String, SessionState>,
+}
+
+/// 会话录制/回放控制器
+#[derive(Default)]
+pub struct FlowPlayback {
+    inner: Mutex<PlaybackInner>,
+    session_manager: RwLock<Option<Arc<SessionManager>>>,
+}
+
+impl FlowPlayback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置会话管理器（录制的 Flow 写入其中，回放从中读取 Flow 列表）
+    pub fn set_session_manager(&self, manager: Arc<SessionManager>) {
+        *self.session_manager.write().unwrap() = Some(manager);
+    }
+
+    fn session_manager(&self) -> Result<Arc<SessionManager>, PlaybackError> {
+        self.session_manager
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(PlaybackError::SessionManagerUnavailable)
+    }
+
+    /// 是否有会话处于录制或回放模式
+    pub fn is_active(&self) -> bool {
+        !self.inner.lock().unwrap().sessions.is_empty()
+    }
+
+    /// 开始录制到会话
+    pub fn record(&self, session_id: &str) -> Result<PlaybackStatus, PlaybackError> {
+        let manager = self.session_manager()?;
+        if manager.get_session(session_id)?.is_none() {
+            return Err(PlaybackError::SessionNotFound(session_id.to_string()));
+        }
+        Ok(self.activate(
+            session_id,
+            SessionMode::Record,
+            PlaybackOptions::default(),
+            None,
+        ))
+    }
+
+    /// 创建新会话并开始录制
+    pub fn record_new_session(
+        &self,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<PlaybackStatus, PlaybackError> {
+        let session = self.session_manager()?.create_session(name, description)?;
+        Ok(self.activate(
+            &session.id,
+            SessionMode::Record,
+            PlaybackOptions::default(),
+            None,
+        ))
+    }
+
+    /// 会话中录制的 Flow ID
+    pub fn session_flow_ids(&self, session_id: &str) -> Result<Vec<String>, PlaybackError> {
+        let manager = self.session_manager()?;
+        if manager.get_session(session_id)?.is_none() {
+            return Err(PlaybackError::SessionNotFound(session_id.to_string()));
+        }
+        Ok(manager.get_session_flow_ids(session_id)?)
+    }
+
+    /// 使用会话中录制的 Flow 开始回放
+    pub fn play(
+        &self,
+        session_id: &str,
+        flows: Vec<LLMFlow>,
+        options: PlaybackOptions,
+    ) -> Result<PlaybackStatus, PlaybackError> {
+        // speed 用作 chunk 间隔的除数，过小或非有限值会让 `Duration::div_f64` 溢出
+        let speed = options.speed;
+        if speed != 0.0 && !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+            return Err(PlaybackError::InvalidSpeed(speed));
+        }
+
+        let matcher = match options.matcher.as_deref().map(str::trim) {
+            Some(expr) if !expr.is_empty() => Some(FilterParser::compile(
+                &FilterParser::parse(expr)
+                    .map_err(|e| PlaybackError::InvalidMatcher(e.to_string()))?,
+            )),
+            _ => None,
+        };
+
+        let mut flows = flows;
+        flows.sort_by_key(|f| f.timestamps.created);
+        let recordings: Vec<Recording> = flows.into_iter().filter_map(recording_of).collect();
+        if recordings.is_empty() {
+            return Err(PlaybackError::NoPlayableFlows(session_id.to_string()));
+        }
+
+        let mut by_hash: HashMap<String, (Vec<usize>, usize)> = HashMap::new();
+        for (i, recording) in recordings.iter().enumerate() {
+            by_hash
+                .entry(request_hash(
+                    &recording.flow.request.path,
+                    &recording.flow.request.body,
+                ))
+                .or_default()
+                .0
+                .push(i);
+        }
+        let matcher = matcher.map(|matches| {
+            let candidates = (0..recordings.len())
+                .filter(|i| matches(&recordings[*i].flow))
+                .collect();
+            (matches, candidates, 0)
+        });
+
+        let index = PlaybackIndex {
+            recordings,
+            by_hash,
+            matcher,
+        };
+        Ok(self.activate(session_id, SessionMode::Playback, options, Some(index)))
+    }
+
+    fn activate(
+        &self,
+        session_id: &str,
+        mode: SessionMode,
+        options: PlaybackOptions,
+        index: Option<PlaybackIndex>,
+    ) -> PlaybackStatus {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sessions.insert(
+            session_id.to_string(),
+            SessionState {
+                mode,
+                options,
+                index,
+                recorded: 0,
+                hits: 0,
+                misses: 0,
+                started_at: Utc::now(),
+            },
+        );
+        tracing::info!("[PLAYBACK] 会话 {} 切换为 {:?} 模式", session_id, mode);
+        status_of(&inner, session_id).expect("session just inserted")
+    }
+
+    /// 停止会话的录制/回放
+    pub fn stop(&self, session_id: &str) -> Option<PlaybackStatus> {
+        let mut inner = self.inner.lock().unwrap();
+        let status = status_of(&inner, session_id)?;
+        inner.sessions.remove(session_id);
+        tracing::info!("[PLAYBACK] 会话 {} 停止 {:?} 模式", session_id, status.mode);
+        Some(status)
+    }
+
+    /// 所有处于录制/回放模式的会话
+    pub fn status(&self) -> Vec<PlaybackStatus> {
+        let inner = self.inner.lock().unwrap();
+        let mut statuses: Vec<_> = inner
+            .sessions
+            .keys()
+            .filter_map(|id| status_of(&inner, id))
+            .collect();
+        statuses.sort_by_key(|s| s.started_at);
+        statuses
+    }
+
+    /// 决定如何处理一个请求
+    ///
+    /// # Arguments
+    /// * `session_header` - `x-proxycast-session` 请求头，缺失时直接转发到上游
+    /// * `endpoint` - 端点路径（与录制 Flow 的请求路径一致）
+    /// * `request` - 别名解析之后的请求体
+    /// * `incoming` - 当前请求对应的 Flow（仅在需要执行匹配表达式时调用）
+    pub fn decide(
+        &self,
+        session_header: Option<&str>,
+        endpoint: &str,
+        request: &Value,
+        incoming: impl FnOnce() -> LLMFlow,
+    ) -> PlaybackDecision {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(session_id) = session_header
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+        else {
+            return PlaybackDecision::Passthrough;
+        };
+        let Some(state) = inner.sessions.get_mut(&session_id) else {
+            return PlaybackDecision::Passthrough;
+        };
+
+        if state.mode == SessionMode::Record {
+            return PlaybackDecision::Record(session_id);
+        }
+        let Some(index) = state.index.as_mut() else {
+            return PlaybackDecision::Passthrough;
+        };
+
+        let hash = request_hash(endpoint, request);
+        let found = match index.by_hash.get_mut(&hash) {
+            Some((candidates, cursor)) => Some(next_candidate(candidates, cursor)),
+            None => match index.matcher.as_mut() {
+                Some((matches, candidates, cursor))
+                    if !candidates.is_empty() && matches(&incoming()) =>
+                {
+                    Some(next_candidate(candidates, cursor))
+                }
+                _ => None,
+            },
+        };
+
+        match found {
+            Some(i) => {
+                state.hits += 1;
+                let recording = &index.recordings[i];
+                PlaybackDecision::Replay(PlaybackReply {
+                    session_id,
+                    source_flow_id: recording.flow_id.clone(),
+                    status_code: recording.status_code,
+                    format: recording.format,
+                    body: scale_body(&recording.body, state.options.speed),
+                })
+            }
+            None => {
+                state.misses += 1;
+                match state.options.on_miss {
+                    PlaybackMissPolicy::Error => PlaybackDecision::Miss { session_id },
+                    PlaybackMissPolicy::Passthrough => PlaybackDecision::Passthrough,
+                }
+            }
+        }
+    }
+
+    /// 将录制中完成的 Flow 加入会话
+    pub fn record_flow(&self, flow: &LLMFlow) {
+        let Some(session_id) = flow.metadata.recording_session.as_deref() else {
+            return;
+        };
+        let Ok(manager) = self.session_manager() else {
+            return;
+        };
+        match manager.add_flow(session_id, &flow.id) {
+            Ok(()) => {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(state) = inner.sessions.get_mut(session_id) {
+                    state.recorded += 1;
+                }
+            }
+            Err(e) => tracing::warn!(
+                "[PLAYBACK] 录制 Flow {} 到会话 {} 失败: {}",
+                flow.id,
+                session_id,
+                e
+            ),
+        }
+    }
+}
+
+fn status_of(inner: &PlaybackInner, session_id: &str) -> Option<PlaybackStatus> {
+    let state = inner.sessions.get(session_id)?;
+    Some(PlaybackStatus {
+        session_id: session_id.to_string(),
+        mode: state.mode,
+        options: (state.mode == SessionMode::Playback).then(|| state.options.clone()),
+        recorded: state.recorded,
+        playable: state.index.as_ref().map_or(0, |i| i.recordings.len()),
+        hits: state.hits,
+        misses: state.misses,
+        started_at: state.started_at,
+    })
+}
+
+fn next_candidate(candidates: &[usize], cursor: &mut usize) -> usize {
+    let i = candidates[(*cursor).min(candidates.len() - 1)];
+    *cursor += 1;
+    i
+}
+
+/// 规范化请求哈希，与响应缓存使用相同的规范化规则（不区分客户端 key）
+pub fn request_hash(endpoint: &str, request: &Value) -> String {
+    CacheKey::new(endpoint, request, None).key
+}
+
+/// 按回放速度缩放 chunk 间隔
+fn scale_body(body: &ReplayBody, speed: f64) -> ReplayBody {
+    match body {
+        ReplayBody::Json(value) => ReplayBody::Json(value.clone()),
+        ReplayBody::Stream(chunks) => ReplayBody::Stream(
+            chunks
+                .iter()
+                .map(|chunk| ReplayChunk {
+                    delay: if speed != 0.0 {
+                        chunk.delay.div_f64(speed)
+                    } else {
+                        Duration::ZERO
+                    },
+                    ..chunk.clone()
+                })
+                .collect(),
+        ),
+    }
+}
+
+// ============================================================================
+// 从录制的 Flow 构建回放内容
+// ============================================================================
+
+/// 构建可回放的录制；流式请求缺少原始 chunks 或没有响应时返回 `None`
+fn recording_of(flow: LLMFlow) -> Option<Recording> {
+    let format = match flow.flow_type {
+        FlowType::AnthropicMessages => StreamFormat::Anthropic,
+        FlowType::GeminiGenerateContent => StreamFormat::Gemini,
+        _ => StreamFormat::OpenAI,
+    };
+
+    let (status_code, body) = if flow.request.parameters.stream {
+        let response = flow.response.as_ref()?;
+        let chunks = response.stream_info.as_ref()?.raw_chunks.as_ref()?;
+        let mut previous = flow.timestamps.request_start;
+        let chunks: Vec<ReplayChunk> = chunks
+            .iter()
+            .map(|chunk| {
+                let delay = (chunk.timestamp - previous).num_milliseconds().max(0) as u64;
+                previous = chunk.timestamp;
+                ReplayChunk {
+                    delay: Duration::from_millis(delay),
+                    event: chunk.event.clone(),
+                    data: chunk.data.clone(),
+                }
+            })
+            .collect();
+        if chunks.is_empty() {
+            return None;
+        }
+        (response.status_code, ReplayBody::Stream(chunks))
+    } else if let Some(response) = &flow.response {
+        let body = if response.body.is_null() {
+            synthesize_body(&flow)
+        } else {
+            response.body.clone()
+        };
+        (response.status_code, ReplayBody::Json(body))
+    } else {
+        let error = flow.error.as_ref()?;
+        let body = error
+            .raw_response
+            .as_deref()
+            .and_then(|raw| serde_json::from_str(raw).ok())
+            .unwrap_or_else(|| json!({"error": {"message": error.message}}));
+        (error.status_code.unwrap_or(500), ReplayBody::Json(body))
+    };
+
+    Some(Recording {
+        flow_id: flow.id.clone(),
+        status_code,
+        format,
+        body,
+        flow,
+    })
+}
+
+/// 录制时未保存响应体（部分 Provider 只记录了文本和用量），按端点格式重建
+pub(crate) fn synthesize_body(flow: &LLMFlow) -> Value {
+    let response = flow.response.as_ref();
+    let content = response.map(|r| r.content.clone()).unwrap_or_default();
+    let usage = response.map(|r| r.usage.clone()).unwrap_or_default();
+    let tool_calls = response.map(|r| r.tool_calls.clone()).unwrap_or_default();
+    let stop_reason = response.and_then(|r| r.stop_reason.clone());
+
+    if flow.flow_type == FlowType::AnthropicMessages {
+        let mut blocks = vec![json!({"type": "text", "text": content})];
+        blocks.extend(tool_calls.iter().map(|call| {
+            json!({
+                "type": "tool_use",
+                "id": call.id,
+                "name": call.function.name,
+                "input": serde_json::from_str::<Value>(&call.function.arguments)
+                    .unwrap_or_else(|_| json!({})),
+            })
+        }));
+        let stop_reason = match stop_reason {
+            Some(StopReason::Length) => "max_tokens",
+            Some(StopReason::ToolCalls) => "tool_use",
+            _ if !tool_calls.is_empty() => "tool_use",
+            _ => "end_turn",
+        };
+        json!({
+            "id": format!("msg_{}", flow.id),
+            "type": "message",
+            "role": "assistant",
+            "model": flow.request.model,
+            "content": blocks,
+            "stop_reason": stop_reason,
+            "usage": {
+                "input_tokens": usage.input_tokens,
+                "output_tokens": usage.output_tokens
+            }
+        })
+    } else {
+        let mut message = json!({"role": "assistant", "content": content});
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = json!(tool_calls
+                .iter()
+                .map(|call| json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": {"name": call.function.name, "arguments": call.function.arguments}
+                }))
+                .collect::<Vec<_>>());
+        }
+        let finish_reason = match stop_reason {
+            Some(StopReason::Length) => "length",
+            Some(StopReason::ToolCalls) => "tool_calls",
+            _ if !tool_calls.is_empty() => "tool_calls",
+            _ => "stop",
+        };
+        json!({
+            "id": format!("chatcmpl-{}", flow.id),
+            "object": "chat.completion",
+            "created": flow.timestamps.created.timestamp(),
+            "model": flow.request.model,
+            "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+            "usage": {
+                "prompt_tokens": usage.input_tokens,
+                "completion_tokens": usage.output_tokens,
+                "total_tokens": usage.input_tokens + usage.output_tokens
+            }
+        })
+    }
+}
+
+// ============================================================================
+// 测试模块
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::models::*;
+    use crate::ProviderType;
+    use rusqlite::Connection;
+
+    fn recorded_flow(id: &str, content: &str, offset_ms: i64) -> LLMFlow {
+        let body = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": content}]});
+        let request = LLMRequest {
+            path: "/v1/chat/completions".to_string(),
+            model: "gpt-4o".to_string(),
+            body,
+            ..Default::default()
+        };
+        let mut flow = LLMFlow::new(
+            id.to_string(),
+            FlowType::ChatCompletions,
+            request,
+            FlowMetadata {
+                provider: ProviderType::OpenAI,
+                ..Default::default()
+            },
+        );
+        flow.timestamps.created += chrono::Duration::milliseconds(offset_ms);
+        flow.response = Some(LLMResponse {
+            status_code: 200,
+            content: format!("answer to {}", content),
+            ..Default::default()
+        });
+        flow
+    }
+
+    fn streamed_flow(id: &str) -> LLMFlow {
+        let mut flow = recorded_flow(id, "stream", 0);
+        flow.request.parameters.stream = true;
+        flow.request.body["stream"] = json!(true);
+        let start = flow.timestamps.request_start;
+        let chunk = |index: u32, ms: i64, data: &str| StreamChunk {
+            index,
+            event: None,
+            data: data.to_string(),
+            timestamp: start + chrono::Duration::milliseconds(ms),
+            content_delta: None,
+            tool_call_delta: None,
+            thinking_delta: None,
+        };
+        flow.response.as_mut().unwrap().stream_info = Some(StreamInfo {
+            chunk_count: 2,
+            first_chunk_latency_ms: 200,
+            avg_chunk_interval_ms: 100.0,
+            raw_chunks: Some(vec![chunk(0, 200, "{\"a\":1}"), chunk(1, 300, "[DONE]")]),
+        });
+        flow
+    }
+
+    fn replay(decision: PlaybackDecision) -> PlaybackReply {
+        match decision {
+            PlaybackDecision::Replay(reply) => reply,
+            other => panic!("expected replay, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hash_match_replays_in_recorded_order() {
+        let playback = FlowPlayback::new();
+        let first = recorded_flow("f1", "hi", 0);
+        let again = recorded_flow("f2", "hi", 10);
+        let request = first.request.body.clone();
+        playback
+            .play(
+                "s1",
+                vec![again, first, recorded_flow("f3", "bye", 20)],
+                PlaybackOptions::default(),
+            )
+            .unwrap();
+
+        let incoming = || recorded_flow("x", "hi", 0);
+        let ids: Vec<_> = (0..3)
+            .map(|_| {
+                replay(playback.decide(Some("s1"), "/v1/chat/completions", &request, incoming))
+                    .source_flow_id
+            })
+            .collect();
+        assert_eq!(ids, vec!["f1", "f2", "f2"]);
+
+        // 未携带会话请求头的请求不受回放影响
+        assert!(matches!(
+            playback.decide(None, "/v1/chat/completions", &request, incoming),
+            PlaybackDecision::Passthrough
+        ));
+
+        // 未录制 Flow 的 null 响应体按 OpenAI 格式重建
+        let reply = replay(playback.decide(Some("s1"), "/v1/chat/completions", &request, incoming));
+        match reply.body {
+            ReplayBody::Json(body) => {
+                assert_eq!(body["choices"][0]["message"]["content"], "answer to hi")
+            }
+            other => panic!("unexpected body {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_miss_policy_and_matcher() {
+        let playback = FlowPlayback::new();
+        let unknown = json!({"model": "gpt-4o", "messages": [{"role": "user", "content": "new"}]});
+        playback
+            .play(
+                "s1",
+                vec![recorded_flow("f1", "hi", 0)],
+                PlaybackOptions::default(),
+            )
+            .unwrap();
+        assert!(matches!(
+            playback.decide(
+                Some("s1"),
+                "/v1/chat/completions",
+                &unknown,
+                || unreachable!()
+            ),
+            PlaybackDecision::Miss { .. }
+        ));
+
+        playback
+            .play(
+                "s1",
+                vec![recorded_flow("f1", "hi", 0)],
+                PlaybackOptions {
+                    matcher: Some("~m gpt-4o".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let reply = replay(
+            playback.decide(Some("s1"), "/v1/chat/completions", &unknown, || {
+                recorded_flow("x", "new", 0)
+            }),
+        );
+        assert_eq!(reply.source_flow_id, "f1");
+
+        playback
+            .play(
+                "s1",
+                vec![recorded_flow("f1", "hi", 0)],
+                PlaybackOptions {
+                    on_miss: PlaybackMissPolicy::Passthrough,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            playback.decide(
+                Some("s1"),
+                "/v1/chat/completions",
+                &unknown,
+                || unreachable!()
+            ),
+            PlaybackDecision::Passthrough
+        ));
+        assert_eq!(playback.status()[0].misses, 1);
+
+        assert!(matches!(
+            playback.play(
+                "s1",
+                vec![recorded_flow("f1", "hi", 0)],
+                PlaybackOptions {
+                    matcher: Some("~m (".to_string()),
+                    ..Default::default()
+                },
+            ),
+            Err(PlaybackError::InvalidMatcher(_))
+        ));
+    }
+
+    #[test]
+    fn test_stream_replay_keeps_chunk_timing() {
+        let playback = FlowPlayback::new();
+        let flow = streamed_flow("f1");
+        let request = flow.request.body.clone();
+        playback
+            .play(
+                "s1",
+                vec![flow],
+                PlaybackOptions {
+                    speed: 2.0,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let reply = replay(playback.decide(
+            Some("s1"),
+            "/v1/chat/completions",
+            &request,
+            || unreachable!(),
+        ));
+        let ReplayBody::Stream(chunks) = reply.body else {
+            panic!("expected stream body");
+        };
+        let delays: Vec<_> = chunks.iter().map(|c| c.delay.as_millis()).collect();
+        assert_eq!(delays, vec![100, 50]);
+        assert_eq!(chunks[1].to_sse(), "data: [DONE]\n\n");
+
+        for speed in [0.001, 1001.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                playback.play(
+                    "s1",
+                    vec![streamed_flow("f1")],
+                    PlaybackOptions {
+                        speed,
+                        ..Default::default()
+                    },
+                ),
+                Err(PlaybackError::InvalidSpeed(_))
+            ));
+        }
+
+        // 没有原始 chunks 的流式 Flow 无法回放
+        let mut flow = streamed_flow("f2");
+        flow.response.as_mut().unwrap().stream_info = None;
+        assert!(matches!(
+            playback.play("s2", vec![flow], PlaybackOptions::default()),
+            Err(PlaybackError::NoPlayableFlows(_))
+        ));
+    }
+
+    #[test]
+    fn test_record_adds_flows_to_session() {
+        let manager = Arc::new(
+            SessionManager::from_connection(Connection::open_in_memory().unwrap()).unwrap(),
+        );
+        let session = manager.create_session("agent run", None).unwrap();
+        let playback = FlowPlayback::new();
+        assert!(matches!(
+            playback.record(&session.id),
+            Err(PlaybackError::SessionManagerUnavailable)
+        ));
+        playback.set_session_manager(manager.clone());
+        assert!(matches!(
+            playback.record("missing"),
+            Err(PlaybackError::SessionNotFound(_))
+        ));
+        playback.record(&session.id).unwrap();
+
+        let request = json!({"model": "gpt-4o"});
+        match playback.decide(
+            Some(&session.id),
+            "/v1/chat/completions",
+            &request,
+            || unreachable!(),
+        ) {
+            PlaybackDecision::Record(id) => assert_eq!(id, session.id),
+            other => panic!("expected record, got {:?}", other),
+        }
+        assert!(matches!(
+            playback.decide(
+                Some("other"),
+                "/v1/chat/completions",
+                &request,
+                || unreachable!()
+            ),
+            PlaybackDecision::Passthrough
+        ));
+
+        let mut flow = recorded_flow("f1", "hi", 0);
+        flow.metadata.recording_session = Some(session.id.clone());
+        playback.record_flow(&flow);
+        assert_eq!(
+            manager.get_session_flow_ids(&session.id).unwrap(),
+            vec!["f1"]
+        );
+        assert_eq!(playback.status()[0].recorded, 1);
+
+        assert!(playback.stop(&session.id).is_some());
+        assert!(!playback.is_active());
+    }
+}
@@ -10,10 +10,18 @@ use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
 
+use super::conversation::{recent_conversation_ids, Conversation, ConversationSummary};
 use super::file_store::{FileStoreError, FlowFileStore};
 use super::filter_parser::{FilterParseError, FilterParser};
 use super::memory_store::{FlowFilter, FlowMemoryStore};
 use super::models::{FlowState, LLMFlow};
+use crate::models::model_registry::ModelPricing;
+
+/// 单个对话最多读取的 Flow 数量
+const MAX_CONVERSATION_FLOWS: usize = 10_000;
+
+/// 列出对话时扫描的最近 Flow 数量
+const CONVERSATION_SCAN_LIMIT: usize = 2_000;
 
 // ============================================================================
 // 错误类型
@@ -778,6 +786,73 @@ impl FlowQueryService {
         let store = self.memory_store.read().await;
         store.get_recent(limit)
     }
+
+    /// 获取对话树及合计（Token、费用、耗时、工具调用）
+    ///
+    /// # 参数
+    /// - `conversation_id`: 对话 ID（根 Flow 的 ID）
+    /// - `pricing`: 按模型名称查找定价
+    pub async fn get_conversation<F>(
+        &self,
+        conversation_id: &str,
+        pricing: F,
+    ) -> Result<Option<Conversation>, FileStoreError>
+    where
+        F: Fn(&str) -> Option<ModelPricing>,
+    {
+        let filter = FlowFilter {
+            conversation_id: Some(conversation_id.to_string()),
+            ..Default::default()
+        };
+        let flows = self.collect_flows(&filter, MAX_CONVERSATION_FLOWS).await?;
+        Ok(Conversation::build(conversation_id, flows, pricing))
+    }
+
+    /// 列出最近活跃的对话
+    ///
+    /// 从匹配过滤条件的最近 Flow 中提取对话，再汇总每个对话的全部 Flow
+    ///
+    /// # 参数
+    /// - `filter`: 过滤条件
+    /// - `limit`: 最大返回数量
+    /// - `pricing`: 按模型名称查找定价
+    pub async fn list_conversations<F>(
+        &self,
+        filter: &FlowFilter,
+        limit: usize,
+        pricing: F,
+    ) -> Result<Vec<ConversationSummary>, FileStoreError>
+    where
+        F: Fn(&str) -> Option<ModelPricing>,
+    {
+        let recent = self.collect_flows(filter, CONVERSATION_SCAN_LIMIT).await?;
+        let mut summaries = Vec::new();
+        for id in recent_conversation_ids(&recent, limit) {
+            if let Some(conversation) = self.get_conversation(&id, &pricing).await? {
+                summaries.push(conversation.summary);
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// 合并内存和文件存储中匹配的 Flow（以 ID 去重）
+    async fn collect_flows(
+        &self,
+        filter: &FlowFilter,
+        limit: usize,
+    ) -> Result<Vec<LLMFlow>, FileStoreError> {
+        let mut flows = {
+            let store = self.memory_store.read().await;
+            store.query(filter)
+        };
+        let memory_ids: std::collections::HashSet<_> = flows.iter().map(|f| f.id.clone()).collect();
+        for flow in self.file_store.query(filter, limit, 0)? {
+            if !memory_ids.contains(&flow.id) {
+                flows.push(flow);
+            }
+        }
+        Ok(flows)
+    }
 }
 
 // ============================================================================
@@ -1011,6 +1086,76 @@ mod tests {
         assert!(!result.has_next);
         assert!(!result.has_prev);
     }
+
+    #[tokio::test]
+    async fn test_get_conversation_merges_memory_and_file_flows() {
+        use crate::flow_monitor::conversation::{ConversationLink, ConversationRelation};
+        use crate::flow_monitor::file_store::RotationConfig;
+
+        let conversation_flow = |id: &str, conversation: &str, parent: Option<&str>, turn| {
+            let mut flow =
+                create_test_flow(id, "gpt-4", ProviderType::OpenAI, FlowState::Completed);
+            flow.metadata.conversation = Some(ConversationLink {
+                conversation_id: conversation.to_string(),
+                parent_flow_id: parent.map(String::from),
+                relation: if parent.is_some() {
+                    ConversationRelation::Continuation
+                } else {
+                    ConversationRelation::Root
+                },
+                turn,
+                retry_of: None,
+            });
+            flow.response = Some(LLMResponse {
+                usage: TokenUsage {
+                    input_tokens: 100,
+                    output_tokens: 10,
+                    total_tokens: 110,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            flow
+        };
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_store = Arc::new(
+            FlowFileStore::new(temp_dir.path().to_path_buf(), RotationConfig::default()).unwrap(),
+        );
+        let mut root = conversation_flow("a", "a", None, 1);
+        root.timestamps.created = Utc::now() - chrono::Duration::minutes(5);
+        file_store.write(&root).unwrap();
+
+        let mut memory = FlowMemoryStore::new(100);
+        memory.add(conversation_flow("b", "a", Some("a"), 2));
+        memory.add(conversation_flow("x", "x", None, 1));
+        let service = FlowQueryService::new(Arc::new(RwLock::new(memory)), file_store);
+
+        let conversation = service
+            .get_conversation("a", |_| None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(conversation.summary.totals.flows, 2);
+        assert_eq!(conversation.summary.totals.turns, 2);
+        assert_eq!(conversation.summary.totals.input_tokens, 200);
+        assert_eq!(conversation.summary.totals.unpriced_flows, 2);
+        assert_eq!(conversation.nodes[0].flow_id, "a");
+        assert_eq!(conversation.nodes[0].children, vec!["b"]);
+
+        let summaries = service
+            .list_conversations(&FlowFilter::default(), 10, |_| None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = summaries.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"a") && ids.contains(&"x"));
+        assert!(service
+            .get_conversation("missing", |_| None)
+            .await
+            .unwrap()
+            .is_none());
+    }
 }
 
 // ============================================================================
@@ -23,6 +23,7 @@ pub mod processor;
 pub mod proxy;
 pub mod resilience;
 pub mod router;
+#[cfg(feature = "gui")]
 pub mod screenshot;
 pub mod services;
 pub mod session;
@@ -30,27 +31,39 @@ pub mod session_files;
 pub mod stream;
 pub mod streaming;
 pub mod telemetry;
+#[cfg(feature = "gui")]
 pub mod terminal;
 pub mod translator;
+#[cfg(feature = "gui")]
 pub mod tray;
 pub mod websocket;
 
 // 内部模块
+#[cfg(feature = "gui")]
 mod commands;
 mod config;
+// 以下模块中部分类型和函数只被 Tauri 命令使用，无界面构建时不提示未使用
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 mod converter;
 mod data;
-#[cfg(debug_assertions)]
+#[cfg(all(debug_assertions, feature = "gui"))]
 mod dev_bridge;
 mod logger;
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 mod models;
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 mod providers;
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 mod server;
 mod server_utils;
 
 // 重新导出核心类型以保持向后兼容
-pub use app::{AppState, LogState, ProviderType, TokenCacheServiceState, TrayManagerState};
+#[cfg(feature = "gui")]
+pub use app::TrayManagerState;
+pub use app::{AppState, LogState, ProviderType, TokenCacheServiceState};
 pub use services::provider_pool_service::ProviderPoolService;
 
 // 重新导出 run 函数
+#[cfg(feature = "gui")]
 pub use app::run;
+pub use app::run_headless;
@@ -22,7 +22,8 @@ pub struct ModelCapabilities {
 }
 
 /// 模型定价
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModelPricing {
     /// 输入价格（每百万 token）
     pub input_per_million: Option<f64>,
@@ -48,6 +49,38 @@ impl Default for ModelPricing {
     }
 }
 
+impl ModelPricing {
+    /// 是否包含任何价格信息
+    pub fn is_priced(&self) -> bool {
+        self.input_per_million.is_some() || self.output_per_million.is_some()
+    }
+
+    /// 计算一次请求的费用
+    ///
+    /// `input_tokens` 为未命中缓存的输入 Token（与 Anthropic `usage.input_tokens` 口径一致）。
+    /// 未配置缓存价格时，缓存读取和写入 Token 按输入价格计费
+    pub fn cost(
+        &self,
+        input_tokens: u32,
+        output_tokens: u32,
+        cache_read_tokens: u32,
+        cache_write_tokens: u32,
+    ) -> f64 {
+        let input = self.input_per_million.unwrap_or(0.0);
+        let per_token = |tokens: u32, price: f64| tokens as f64 * price / 1_000_000.0;
+        per_token(input_tokens, input)
+            + per_token(output_tokens, self.output_per_million.unwrap_or(0.0))
+            + per_token(
+                cache_read_tokens,
+                self.cache_read_per_million.unwrap_or(input),
+            )
+            + per_token(
+                cache_write_tokens,
+                self.cache_write_per_million.unwrap_or(input),
+            )
+    }
+}
+
 /// 模型限制
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ModelLimits {
@@ -657,6 +690,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pricing_cost() {
+        let pricing = ModelPricing {
+            input_per_million: Some(3.0),
+            output_per_million: Some(15.0),
+            cache_read_per_million: Some(0.3),
+            cache_write_per_million: None,
+            currency: "USD".to_string(),
+        };
+        let cost = pricing.cost(1_000_000, 100_000, 2_000_000, 1_000_000);
+        // 3.0 + 1.5 + 0.6 + 3.0（未配置缓存写入价格时按输入价格）
+        assert!((cost - 8.1).abs() < 1e-9);
+        assert!(!ModelPricing::default().is_priced());
+    }
+
     #[test]
     fn test_model_status_parsing() {
         assert_eq!(
@@ -303,3 +303,79 @@ pub struct ImageData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub revised_prompt: Option<String>,
 }
+
+// ============================================================================
+// Embeddings API 数据模型
+// ============================================================================
+
+/// Embeddings 输入：单个字符串或字符串数组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl EmbeddingInput {
+    /// 获取所有输入文本
+    pub fn texts(&self) -> Vec<&str> {
+        match self {
+            EmbeddingInput::Single(s) => vec![s.as_str()],
+            EmbeddingInput::Multiple(v) => v.iter().map(|s| s.as_str()).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            EmbeddingInput::Single(s) => s.is_empty(),
+            EmbeddingInput::Multiple(v) => v.is_empty(),
+        }
+    }
+}
+
+/// OpenAI Embeddings 请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: EmbeddingInput,
+    /// 输出格式："float"（默认）或 "base64"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding_format: Option<String>,
+    /// 输出向量维度（仅部分模型支持）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// 向量数据：float 数组或 base64 编码的 little-endian f32
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingVector {
+    Float(Vec<f32>),
+    Base64(String),
+}
+
+/// 单个向量结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingData {
+    pub object: String,
+    pub embedding: EmbeddingVector,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// OpenAI Embeddings 响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub object: String,
+    pub data: Vec<EmbeddingData>,
+    pub model: String,
+    #[serde(default)]
+    pub usage: EmbeddingUsage,
+}
@@ -94,6 +94,20 @@ pub enum CredentialData {
 }
 
 impl CredentialData {
+    /// 自定义的上游 API 地址（未配置或 OAuth 凭证返回 None）
+    pub fn base_url(&self) -> Option<&str> {
+        match self {
+            CredentialData::OpenAIKey { base_url, .. }
+            | CredentialData::ClaudeKey { base_url, .. }
+            | CredentialData::VertexKey { base_url, .. }
+            | CredentialData::GeminiApiKey { base_url, .. }
+            | CredentialData::AnthropicKey { base_url, .. } => base_url.as_deref(),
+            CredentialData::CodexOAuth { api_base_url, .. } => api_base_url.as_deref(),
+            _ => None,
+        }
+        .filter(|url| !url.trim().is_empty())
+    }
+
     /// 获取凭证的显示名称（隐藏敏感信息）
     pub fn display_name(&self) -> String {
         match self {
@@ -244,12 +258,20 @@ pub struct ProviderCredential {
     pub source: CredentialSource,
     /// 代理 URL（可覆盖全局代理设置）
     pub proxy_url: Option<String>,
+    /// 负载均衡权重（加权策略下按权重分配请求，0 表示不参与加权选择）
+    #[serde(default = "default_weight")]
+    pub weight: u32,
 }
 
 fn default_true() -> bool {
     true
 }
 
+/// 默认负载均衡权重
+pub fn default_weight() -> u32 {
+    1
+}
+
 impl ProviderCredential {
     /// 创建新凭证
     pub fn new(provider_type: PoolProviderType, credential: CredentialData) -> Self {
@@ -277,6 +299,7 @@ impl ProviderCredential {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: default_weight(),
         }
     }
 
@@ -558,6 +581,8 @@ pub struct CredentialDisplay {
     pub api_key: Option<String>,
     /// 凭证级代理 URL（可覆盖全局代理设置）
     pub proxy_url: Option<String>,
+    /// 负载均衡权重
+    pub weight: u32,
 }
 
 /// 获取凭证类型字符串
@@ -660,6 +685,7 @@ impl From<&ProviderCredential> for CredentialDisplay {
             base_url: get_base_url(&cred.credential),
             api_key: get_api_key(&cred.credential),
             proxy_url: cred.proxy_url.clone(),
+            weight: cred.weight,
         }
     }
 }
@@ -719,6 +745,8 @@ pub struct UpdateCredentialRequest {
     pub new_api_key: Option<String>,
     /// 新的代理 URL（可覆盖全局代理设置）
     pub new_proxy_url: Option<String>,
+    /// 新的负载均衡权重
+    pub weight: Option<u32>,
 }
 
 pub type ProviderPools = HashMap<PoolProviderType, Vec<ProviderCredential>>;
@@ -788,6 +816,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         assert!(!cred.supports_model("claude-opus"));
@@ -823,6 +852,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         // Exact match exclusion
@@ -860,6 +890,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         // Prefix wildcard exclusion
@@ -901,6 +932,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         // Contains wildcard exclusion
@@ -939,6 +971,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         // Excluded by not_supported_models (exact match)
@@ -978,6 +1011,7 @@ mod tests {
             cached_token: None,
             source: CredentialSource::Manual,
             proxy_url: None,
+            weight: 1,
         };
 
         // All models should be supported since not_supported_models is empty
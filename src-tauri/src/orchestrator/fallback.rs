@@ -3,6 +3,7 @@
 //! 处理模型选择失败时的降级逻辑。
 
 use super::tier::{AvailableModel, ServiceTier};
+use crate::config::{EquivalentModel, ModelEquivalenceGroup};
 use serde::{Deserialize, Serialize};
 
 /// 降级策略
@@ -50,6 +51,8 @@ pub struct FallbackHandler {
     max_attempts: u32,
     /// 备用模型 ID（用于 Specific 策略）
     fallback_model_id: Option<String>,
+    /// 跨 Provider 模型等价组
+    equivalence_groups: Vec<ModelEquivalenceGroup>,
 }
 
 impl FallbackHandler {
@@ -59,6 +62,7 @@ impl FallbackHandler {
             policy,
             max_attempts: 3,
             fallback_model_id: None,
+            equivalence_groups: Vec::new(),
         }
     }
 
@@ -79,6 +83,42 @@ impl FallbackHandler {
         self.policy
     }
 
+    /// 设置模型等价组
+    pub fn with_equivalence_groups(mut self, groups: Vec<ModelEquivalenceGroup>) -> Self {
+        self.equivalence_groups = groups;
+        self
+    }
+
+    /// 更新模型等价组（热重载）
+    pub fn set_equivalence_groups(&mut self, groups: Vec<ModelEquivalenceGroup>) {
+        self.equivalence_groups = groups;
+    }
+
+    /// 获取模型等价组
+    pub fn equivalence_groups(&self) -> &[ModelEquivalenceGroup] {
+        &self.equivalence_groups
+    }
+
+    /// 获取请求的 Provider/模型的等价降级候选
+    ///
+    /// 只匹配 Provider 和模型都与请求一致的成员所在的组，
+    /// 返回组内其他成员，保持配置顺序
+    pub fn equivalents(&self, provider: &str, model: &str) -> Vec<EquivalentModel> {
+        let is_requested =
+            |m: &EquivalentModel| m.provider.eq_ignore_ascii_case(provider) && m.model == model;
+        self.equivalence_groups
+            .iter()
+            .find(|g| g.members.iter().any(is_requested))
+            .map(|g| {
+                g.members
+                    .iter()
+                    .filter(|m| !is_requested(m))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// 获取下一个降级等级
     pub fn next_tier(tier: ServiceTier) -> Option<ServiceTier> {
         match tier {
@@ -307,6 +347,39 @@ mod tests {
         assert_eq!(result.model.unwrap().id, "haiku");
     }
 
+    #[test]
+    fn test_equivalents() {
+        let member = |provider: &str, model: &str| EquivalentModel {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        };
+        let handler =
+            FallbackHandler::default().with_equivalence_groups(vec![ModelEquivalenceGroup {
+                name: "sonnet".to_string(),
+                members: vec![
+                    member("kiro", "claude-sonnet-4-5"),
+                    member("claude", "claude-sonnet-4-5-20250929"),
+                    member("antigravity", "gemini-claude-sonnet-4-5"),
+                ],
+            }]);
+
+        let candidates = handler.equivalents("kiro", "claude-sonnet-4-5");
+        assert_eq!(
+            candidates,
+            vec![
+                member("claude", "claude-sonnet-4-5-20250929"),
+                member("antigravity", "gemini-claude-sonnet-4-5"),
+            ]
+        );
+
+        // Provider 不在组内时不降级，即使模型名相同
+        assert!(handler
+            .equivalents("openai", "gemini-claude-sonnet-4-5")
+            .is_empty());
+
+        assert!(handler.equivalents("kiro", "gpt-4o").is_empty());
+    }
+
     #[test]
     fn test_next_tier() {
         assert_eq!(
@@ -3,6 +3,8 @@
 //! 定义请求处理过程中的上下文信息
 
 use crate::plugin::PluginContext;
+use crate::services::client_key_service::ClientIdentity;
+use crate::telemetry::{RequestTrace, SpanGuard};
 use crate::ProviderType;
 use chrono::{DateTime, Utc};
 use std::time::Instant;
@@ -26,6 +28,8 @@ pub struct RequestContext {
     pub provider: Option<ProviderType>,
     /// 使用的凭证 ID
     pub credential_id: Option<String>,
+    /// 发起请求的客户端 key（使用共享 API Key 时为 None）
+    pub client_key: Option<ClientIdentity>,
     /// 重试次数
     pub retry_count: u32,
     /// 是否为流式请求
@@ -34,6 +38,10 @@ pub struct RequestContext {
     pub plugin_ctx: Option<PluginContext>,
     /// 元数据
     pub metadata: std::collections::HashMap<String, serde_json::Value>,
+    /// 链路追踪（未启用或未被采样时为 None）
+    pub trace: Option<RequestTrace>,
+    /// 录制到的会话 ID（会话处于录制模式时）
+    pub recording_session: Option<String>,
 }
 
 impl RequestContext {
@@ -48,10 +56,13 @@ impl RequestContext {
             resolved_model: model,
             provider: None,
             credential_id: None,
+            client_key: None,
             retry_count: 0,
             is_stream: false,
             plugin_ctx: None,
             metadata: std::collections::HashMap::new(),
+            trace: None,
+            recording_session: None,
         }
     }
 
@@ -71,6 +82,21 @@ impl RequestContext {
         self.credential_id = Some(credential_id);
     }
 
+    /// 设置录制到的会话
+    pub fn set_recording_session(&mut self, session_id: Option<String>) {
+        self.recording_session = session_id;
+    }
+
+    /// 设置客户端 key 身份
+    pub fn set_client_key(&mut self, client_key: Option<ClientIdentity>) {
+        self.client_key = client_key;
+    }
+
+    /// 客户端 key ID
+    pub fn client_key_id(&self) -> Option<&str> {
+        self.client_key.as_ref().map(|c| c.id.as_str())
+    }
+
     /// 设置解析后的模型名称
     pub fn set_resolved_model(&mut self, model: String) {
         self.resolved_model = model;
@@ -109,6 +135,80 @@ impl RequestContext {
     pub fn get_metadata(&self, key: &str) -> Option<&serde_json::Value> {
         self.metadata.get(key)
     }
+
+    /// 设置链路追踪
+    pub fn set_trace(&mut self, trace: Option<RequestTrace>) {
+        self.trace = trace;
+    }
+
+    /// 在请求 trace 下开启子 span（未启用追踪时为空操作）
+    pub fn span(&self, name: &str) -> SpanGuard {
+        self.trace
+            .as_ref()
+            .map(|t| t.span(name))
+            .unwrap_or_default()
+    }
+
+    /// 将请求结果写入根 span（GenAI 语义约定属性）
+    pub fn record_trace_outcome(&self, status_code: u16, error: Option<&str>) {
+        let Some(trace) = &self.trace else {
+            return;
+        };
+        if let Some(provider) = self.provider {
+            trace.set_attribute("gen_ai.system", provider.to_string());
+        }
+        trace.set_attribute("gen_ai.request.model", self.original_model.as_str());
+        trace.set_attribute("proxycast.request_id", self.request_id.as_str());
+        trace.set_attribute("proxycast.resolved_model", self.resolved_model.as_str());
+        trace.set_attribute("proxycast.stream", self.is_stream);
+        trace.set_attribute("proxycast.retry_count", self.retry_count);
+        if let Some(credential_id) = &self.credential_id {
+            trace.set_attribute("proxycast.credential_id", credential_id.as_str());
+        }
+        if let Some(client_key_id) = self.client_key_id() {
+            trace.set_attribute("proxycast.client_key_id", client_key_id);
+        }
+        trace.set_attribute("http.response.status_code", status_code as i64);
+        match error {
+            Some(message) => trace.set_error(message),
+            None if status_code < 400 => trace.set_ok(),
+            None => trace.set_error(&format!("HTTP {}", status_code)),
+        }
+    }
+
+    /// 将 Token 用量写入根 span
+    pub fn record_trace_usage(&self, input_tokens: u32, output_tokens: u32) {
+        if let Some(trace) = &self.trace {
+            trace.set_attribute("gen_ai.usage.input_tokens", input_tokens);
+            trace.set_attribute("gen_ai.usage.output_tokens", output_tokens);
+        }
+    }
+
+    /// 从非流式响应体中提取响应模型和结束原因写入根 span
+    ///
+    /// 同时支持 OpenAI（`choices[].finish_reason`）和 Anthropic（`stop_reason`）格式
+    pub fn record_trace_response(&self, response: &serde_json::Value) {
+        let Some(trace) = &self.trace else {
+            return;
+        };
+        if let Some(model) = response.get("model").and_then(|v| v.as_str()) {
+            trace.set_attribute("gen_ai.response.model", model);
+        }
+        let mut finish_reasons: Vec<String> = response
+            .get("choices")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.get("finish_reason").and_then(|v| v.as_str()))
+            .map(|s| s.to_string())
+            .collect();
+        if let Some(reason) = response.get("stop_reason").and_then(|v| v.as_str()) {
+            finish_reasons.push(reason.to_string());
+        }
+        if !finish_reasons.is_empty() {
+            trace.set_attribute("gen_ai.response.finish_reasons", finish_reasons);
+        }
+    }
 }
 
 impl Default for RequestContext {
@@ -24,12 +24,20 @@ pub use steps::{
     RoutingStep, TelemetryStep,
 };
 
+use crate::config::EquivalentModel;
+use crate::database::DbConnection;
 use crate::injection::Injector;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::orchestrator::FallbackHandler;
 use crate::plugin::PluginManager;
-use crate::resilience::{Failover, Retrier, TimeoutController};
+use crate::resilience::{Failover, HedgeController, Retrier, TimeoutController};
 use crate::router::{ModelMapper, Router};
+use crate::services::alert_service::AlertService;
+use crate::services::budget_service::BudgetService;
+use crate::services::canary_service::CanaryProber;
 use crate::services::provider_pool_service::ProviderPoolService;
-use crate::telemetry::{StatsAggregator, TokenTracker};
+use crate::services::token_refresh_scheduler::TokenRefreshScheduler;
+use crate::telemetry::{MetricsRegistry, StatsAggregator, TokenTracker, Tracer};
 use parking_lot::RwLock as ParkingLotRwLock;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -50,14 +58,30 @@ pub struct RequestProcessor {
     pub failover: Arc<Failover>,
     /// 超时控制器
     pub timeout: Arc<TimeoutController>,
+    /// 对冲控制器（维护各模型近期 TTFB）
+    pub hedge: Arc<HedgeController>,
+    /// 降级处理器（跨 Provider 模型等价组）
+    pub fallback: Arc<ParkingLotRwLock<FallbackHandler>>,
     /// 插件管理器
     pub plugins: Arc<PluginManager>,
     /// 统计聚合器（使用 parking_lot::RwLock 以支持与 TelemetryState 共享）
     pub stats: Arc<ParkingLotRwLock<StatsAggregator>>,
     /// Token 追踪器（使用 parking_lot::RwLock 以支持与 TelemetryState 共享）
     pub tokens: Arc<ParkingLotRwLock<TokenTracker>>,
+    /// Prometheus 指标
+    pub metrics: Arc<MetricsRegistry>,
+    /// OpenTelemetry 链路追踪
+    pub tracer: Arc<Tracer>,
     /// 凭证池服务
     pub pool_service: Arc<ProviderPoolService>,
+    /// 预算服务（费用统计与预算检查）
+    pub budget: Arc<BudgetService>,
+    /// OAuth Token 提前刷新调度器
+    pub token_refresh: Arc<TokenRefreshScheduler>,
+    /// 凭证健康定时探测器
+    pub canary: Arc<CanaryProber>,
+    /// 流量告警服务
+    pub alerts: Arc<AlertService>,
     /// 热重载协调锁（避免配置更新期间请求读取不一致的配置）
     pub reload_lock: Arc<RwLock<()>>,
 }
@@ -83,10 +107,18 @@ impl RequestProcessor {
             retrier,
             failover,
             timeout,
+            hedge: Arc::new(HedgeController::default()),
+            fallback: Arc::new(ParkingLotRwLock::new(FallbackHandler::default())),
             plugins,
             stats,
             tokens,
+            metrics: Arc::new(MetricsRegistry::new()),
+            tracer: Arc::new(Tracer::new()),
             pool_service,
+            budget: Arc::new(BudgetService::default()),
+            token_refresh: Arc::new(TokenRefreshScheduler::default()),
+            canary: Arc::new(CanaryProber::default()),
+            alerts: Arc::new(AlertService::default()),
             reload_lock: Arc::new(RwLock::new(())),
         }
     }
@@ -100,10 +132,18 @@ impl RequestProcessor {
             retrier: Arc::new(Retrier::with_defaults()),
             failover: Arc::new(Failover::with_defaults()),
             timeout: Arc::new(TimeoutController::with_defaults()),
+            hedge: Arc::new(HedgeController::default()),
+            fallback: Arc::new(ParkingLotRwLock::new(FallbackHandler::default())),
             plugins: Arc::new(PluginManager::with_defaults()),
             stats: Arc::new(ParkingLotRwLock::new(StatsAggregator::with_defaults())),
             tokens: Arc::new(ParkingLotRwLock::new(TokenTracker::with_defaults())),
+            metrics: Arc::new(MetricsRegistry::new()),
+            tracer: Arc::new(Tracer::new()),
             pool_service,
+            budget: Arc::new(BudgetService::default()),
+            token_refresh: Arc::new(TokenRefreshScheduler::default()),
+            canary: Arc::new(CanaryProber::default()),
+            alerts: Arc::new(AlertService::default()),
             reload_lock: Arc::new(RwLock::new(())),
         }
     }
@@ -138,10 +178,18 @@ impl RequestProcessor {
             retrier: Arc::new(Retrier::with_defaults()),
             failover: Arc::new(Failover::with_defaults()),
             timeout: Arc::new(TimeoutController::with_defaults()),
+            hedge: Arc::new(HedgeController::default()),
+            fallback: Arc::new(ParkingLotRwLock::new(FallbackHandler::default())),
             plugins: Arc::new(PluginManager::with_defaults()),
             stats,
             tokens,
+            metrics: Arc::new(MetricsRegistry::new()),
+            tracer: Arc::new(Tracer::new()),
             pool_service,
+            budget: Arc::new(BudgetService::default()),
+            token_refresh: Arc::new(TokenRefreshScheduler::default()),
+            canary: Arc::new(CanaryProber::default()),
+            alerts: Arc::new(AlertService::default()),
             reload_lock: Arc::new(RwLock::new(())),
         }
     }
@@ -242,6 +290,34 @@ impl RequestProcessor {
         // 2. 根据解析后的模型选择 Provider
         self.route_for_context(ctx).await
     }
+
+    /// 按模型等价组为请求选择其他 Provider 的凭证
+    ///
+    /// 依次尝试等价组内除请求本身外的成员，返回第一个有可用凭证的成员。
+    /// 调用方需要将请求改写为该成员的 Provider 和模型
+    ///
+    /// # Arguments
+    /// * `db` - 数据库连接
+    /// * `provider` - 请求的 Provider
+    /// * `model` - 请求的模型
+    pub fn select_equivalent_credential(
+        &self,
+        db: &DbConnection,
+        provider: &str,
+        model: &str,
+    ) -> Option<(ProviderCredential, EquivalentModel)> {
+        let candidates = self.fallback.read().equivalents(provider, model);
+        candidates.into_iter().find_map(|candidate| {
+            match self.pool_service.select_credential(
+                db,
+                &candidate.provider,
+                Some(&candidate.model),
+            ) {
+                Ok(Some(credential)) => Some((credential, candidate)),
+                _ => None,
+            }
+        })
+    }
 }
 
 #[cfg(test)]
@@ -46,6 +46,17 @@ impl TelemetryStep {
             ctx.is_stream,
         );
 
+        let trace_error = match status {
+            RequestStatus::Failed => Some(error_message.clone().unwrap_or_default()),
+            RequestStatus::Timeout => Some("timeout".to_string()),
+            RequestStatus::Cancelled => Some("cancelled".to_string()),
+            RequestStatus::Success | RequestStatus::Retrying => None,
+        };
+        ctx.record_trace_outcome(
+            if trace_error.is_some() { 500 } else { 200 },
+            trace_error.as_deref(),
+        );
+
         // 设置状态和持续时间
         match status {
             RequestStatus::Success => log.mark_success(ctx.elapsed_ms(), 200),
@@ -87,6 +98,8 @@ impl TelemetryStep {
 
         // 只有当至少有一个 Token 值时才记录
         if input_tokens.is_some() || output_tokens.is_some() {
+            ctx.record_trace_usage(input_tokens.unwrap_or(0), output_tokens.unwrap_or(0));
+
             let record = TokenUsageRecord::new(
                 uuid::Uuid::new_v4().to_string(),
                 provider,
@@ -154,6 +167,7 @@ impl PipelineStep for TelemetryStep {
 
         // 从响应中提取并记录 Token（同步方法）
         self.record_tokens_from_response(ctx, payload);
+        ctx.record_trace_response(payload);
 
         tracing::info!(
             "[TELEMETRY] request_id={} provider={:?} model={} duration_ms={}",
@@ -368,12 +368,10 @@ impl ClaudeCustomProvider {
     pub async fn count_tokens(
         &self,
         request: &serde_json::Value,
-    ) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
-        let api_key = self
-            .config
-            .api_key
-            .as_ref()
-            .ok_or("Claude API key not configured")?;
+    ) -> Result<serde_json::Value, ProviderError> {
+        let api_key = self.config.api_key.as_ref().ok_or_else(|| {
+            ProviderError::ConfigurationError("Claude API key not configured".to_string())
+        })?;
 
         let url = self.build_url("messages/count_tokens");
 
@@ -385,16 +383,18 @@ impl ClaudeCustomProvider {
             .header("Content-Type", "application/json")
             .json(request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest_error(&e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
-            return Err(format!("Failed to count tokens: {status} - {body}").into());
+            return Err(ProviderError::from_http_status(status.as_u16(), &body));
         }
 
-        let data: serde_json::Value = resp.json().await?;
-        Ok(data)
+        resp.json()
+            .await
+            .map_err(|e| ProviderError::from_reqwest_error(&e))
     }
 }
 
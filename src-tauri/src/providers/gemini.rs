@@ -651,6 +651,56 @@ impl GeminiApiKeyProvider {
         Ok(resp)
     }
 
+    /// Make an embedContent request (single input) using the given credential
+    pub async fn embed_content(
+        &self,
+        credential: &GeminiApiKeyCredential,
+        model: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
+        self.call_embedding_action(credential, model, "embedContent", body)
+            .await
+    }
+
+    /// Make a batchEmbedContents request (multiple inputs) using the given credential
+    pub async fn batch_embed_contents(
+        &self,
+        credential: &GeminiApiKeyCredential,
+        model: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
+        self.call_embedding_action(credential, model, "batchEmbedContents", body)
+            .await
+    }
+
+    async fn call_embedding_action(
+        &self,
+        credential: &GeminiApiKeyCredential,
+        model: &str,
+        action: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
+        let url = credential.build_api_url(model, action);
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("x-goog-api-key", &credential.api_key)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("Gemini API {action} failed: {status} - {body}").into());
+        }
+
+        let data: serde_json::Value = resp.json().await?;
+        Ok(data)
+    }
+
     /// List available models using the given credential
     pub async fn list_models(
         &self,
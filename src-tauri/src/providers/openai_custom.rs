@@ -155,6 +155,31 @@ impl OpenAICustomProvider {
         Ok(resp)
     }
 
+    /// 调用 OpenAI 兼容的 embeddings 接口（OpenAI、Ollama 等）
+    pub async fn embeddings(
+        &self,
+        request: &serde_json::Value,
+    ) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        let api_key = self
+            .config
+            .api_key
+            .as_ref()
+            .ok_or("OpenAI API key not configured")?;
+
+        let url = self.build_url("embeddings");
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {api_key}"))
+            .header("Content-Type", "application/json")
+            .json(request)
+            .send()
+            .await?;
+
+        Ok(resp)
+    }
+
     pub async fn list_models(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
         let api_key = self
             .config
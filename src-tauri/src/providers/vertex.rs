@@ -237,6 +237,36 @@ impl VertexProvider {
         Ok(resp)
     }
 
+    /// Call the Vertex AI embeddings API
+    ///
+    /// `action` is `embedContent` for a single input or `batchEmbedContents` for multiple inputs.
+    pub async fn embed_content(
+        &self,
+        model: &str,
+        action: &str,
+        body: &serde_json::Value,
+    ) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+        let api_key = self
+            .config
+            .api_key
+            .as_ref()
+            .ok_or("Vertex AI API key not configured")?;
+
+        let model = self.resolve_model_alias(model);
+        let url = format!("{}/models/{}:{}", self.get_base_url(), model, action);
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("x-goog-api-key", api_key)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+
+        Ok(resp)
+    }
+
     /// List available models
     pub async fn list_models(&self) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
         let api_key = self
@@ -0,0 +1,620 @@
+//! 熔断器实现
+//!
+//! 按凭证和上游主机分别维护 Closed / Open / HalfOpen 三态熔断器：
+//! - Closed：正常放行，连续失败达到阈值或滚动窗口内错误率过高时打开
+//! - Open：拒绝选择，冷却时间结束后进入半开
+//! - HalfOpen：只放行有限的探测请求，全部成功后关闭，任一失败重新打开
+//!
+//! 每次状态变化都会通过广播通道发布 [`CircuitTransition`]。
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::config::CircuitBreakerConfig;
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// 关闭（正常放行）
+    Closed,
+    /// 打开（拒绝请求）
+    Open,
+    /// 半开（放行探测请求）
+    HalfOpen,
+}
+
+impl std::fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half_open"),
+        }
+    }
+}
+
+/// 熔断器作用范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitScope {
+    /// 单个凭证（key 为凭证 UUID）
+    Credential,
+    /// 上游主机（key 为 base_url 的主机名，未配置 base_url 时为 Provider 类型）
+    Host,
+}
+
+impl std::fmt::Display for CircuitScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitScope::Credential => write!(f, "credential"),
+            CircuitScope::Host => write!(f, "host"),
+        }
+    }
+}
+
+/// 熔断器状态变化事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitTransition {
+    pub scope: CircuitScope,
+    pub key: String,
+    pub from: CircuitState,
+    pub to: CircuitState,
+    /// 变化原因
+    pub reason: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 熔断器状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub scope: CircuitScope,
+    pub key: String,
+    pub state: CircuitState,
+    /// 连续失败次数
+    pub consecutive_failures: u32,
+    /// 滚动窗口内的请求数
+    pub window_requests: usize,
+    /// 滚动窗口内的失败数
+    pub window_failures: usize,
+    /// 最近一次打开的时间
+    pub opened_at: Option<DateTime<Utc>>,
+}
+
+/// 单个熔断器
+#[derive(Debug)]
+struct CircuitBreaker {
+    state: CircuitState,
+    /// 滚动窗口内的请求结果（时间, 是否成功）
+    window: VecDeque<(Instant, bool)>,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    opened_at_utc: Option<DateTime<Utc>>,
+    half_open_since: Option<Instant>,
+    probes_in_flight: u32,
+    probe_successes: u32,
+}
+
+/// 状态变化（原状态, 新状态, 原因）
+type StateChange = (CircuitState, CircuitState, String);
+
+impl CircuitBreaker {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            window: VecDeque::new(),
+            consecutive_failures: 0,
+            opened_at: None,
+            opened_at_utc: None,
+            half_open_since: None,
+            probes_in_flight: 0,
+            probe_successes: 0,
+        }
+    }
+
+    /// 是否允许选择（不改变状态）
+    fn permits(&self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        let cooldown = Duration::from_secs(config.open_duration_secs);
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => self
+                .opened_at
+                .is_some_and(|at| now.saturating_duration_since(at) >= cooldown),
+            CircuitState::HalfOpen => {
+                self.probes_in_flight < config.half_open_max_probes.max(1)
+                    || self.probe_timed_out(cooldown, now)
+            }
+        }
+    }
+
+    /// 被选中发送请求：冷却结束的熔断进入半开，半开状态占用一个探测名额
+    fn acquire(&mut self, config: &CircuitBreakerConfig, now: Instant) -> Option<StateChange> {
+        let cooldown = Duration::from_secs(config.open_duration_secs);
+        match self.state {
+            CircuitState::Closed => None,
+            CircuitState::Open => {
+                if !self.permits(config, now) {
+                    return None;
+                }
+                self.state = CircuitState::HalfOpen;
+                self.half_open_since = Some(now);
+                self.probes_in_flight = 1;
+                self.probe_successes = 0;
+                Some((
+                    CircuitState::Open,
+                    CircuitState::HalfOpen,
+                    format!("冷却 {} 秒结束，开始探测", config.open_duration_secs),
+                ))
+            }
+            CircuitState::HalfOpen => {
+                // 探测请求长时间没有结果（例如客户端断开），释放名额
+                if self.probe_timed_out(cooldown, now) {
+                    self.probes_in_flight = 0;
+                    self.half_open_since = Some(now);
+                }
+                self.probes_in_flight += 1;
+                None
+            }
+        }
+    }
+
+    /// 选中后没有发出请求或请求被放弃：归还半开状态占用的探测名额，不记录结果
+    fn release(&mut self) {
+        if self.state == CircuitState::HalfOpen {
+            self.probes_in_flight = self.probes_in_flight.saturating_sub(1);
+        }
+    }
+
+    fn on_success(&mut self, config: &CircuitBreakerConfig, now: Instant) -> Option<StateChange> {
+        self.push(config, now, true);
+        self.consecutive_failures = 0;
+
+        if self.state != CircuitState::HalfOpen {
+            return None;
+        }
+        self.probes_in_flight = self.probes_in_flight.saturating_sub(1);
+        self.probe_successes += 1;
+        if self.probe_successes < config.half_open_max_probes.max(1) {
+            return None;
+        }
+
+        self.state = CircuitState::Closed;
+        self.window.clear();
+        self.half_open_since = None;
+        self.probes_in_flight = 0;
+        Some((
+            CircuitState::HalfOpen,
+            CircuitState::Closed,
+            format!("{} 个探测请求成功", self.probe_successes),
+        ))
+    }
+
+    fn on_failure(&mut self, config: &CircuitBreakerConfig, now: Instant) -> Option<StateChange> {
+        self.push(config, now, false);
+        self.consecutive_failures += 1;
+
+        let reason = match self.state {
+            // 打开后才返回的请求结果不影响状态
+            CircuitState::Open => return None,
+            CircuitState::HalfOpen => "探测请求失败".to_string(),
+            CircuitState::Closed => {
+                let failures = self.window.iter().filter(|(_, ok)| !ok).count();
+                let total = self.window.len();
+                let error_rate = failures as f64 / total as f64;
+                if self.consecutive_failures >= config.failure_threshold.max(1) {
+                    format!("连续失败 {} 次", self.consecutive_failures)
+                } else if total >= config.min_requests as usize
+                    && error_rate >= config.error_rate_threshold
+                {
+                    format!(
+                        "{} 秒内错误率 {:.0}% ({}/{})",
+                        config.window_secs,
+                        error_rate * 100.0,
+                        failures,
+                        total
+                    )
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        let from = self.state;
+        self.state = CircuitState::Open;
+        self.opened_at = Some(now);
+        self.opened_at_utc = Some(Utc::now());
+        self.half_open_since = None;
+        self.probes_in_flight = 0;
+        self.probe_successes = 0;
+        Some((from, CircuitState::Open, reason))
+    }
+
+    fn push(&mut self, config: &CircuitBreakerConfig, now: Instant, success: bool) {
+        self.window.push_back((now, success));
+        let window = Duration::from_secs(config.window_secs);
+        while self
+            .window
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) > window)
+        {
+            self.window.pop_front();
+        }
+    }
+
+    fn probe_timed_out(&self, cooldown: Duration, now: Instant) -> bool {
+        self.half_open_since
+            .is_some_and(|at| now.saturating_duration_since(at) >= cooldown)
+    }
+}
+
+/// 凭证级和（可确定时）主机级熔断器的键
+fn scoped_keys<'a>(
+    credential: &'a str,
+    host: Option<&'a str>,
+) -> impl Iterator<Item = (CircuitScope, &'a str)> {
+    std::iter::once((CircuitScope::Credential, credential))
+        .chain(host.map(|host| (CircuitScope::Host, host)))
+}
+
+#[derive(Debug, Default)]
+struct RegistryInner {
+    breakers: HashMap<(CircuitScope, String), CircuitBreaker>,
+    /// 凭证 UUID -> 上游主机（选择凭证时记录）
+    credential_hosts: HashMap<String, String>,
+}
+
+/// 熔断器注册表
+///
+/// 同时维护凭证级和上游主机级熔断器，凭证被选择前两者都必须放行
+pub struct CircuitBreakerRegistry {
+    config: RwLock<CircuitBreakerConfig>,
+    inner: Mutex<RegistryInner>,
+    event_sender: broadcast::Sender<CircuitTransition>,
+}
+
+impl Default for CircuitBreakerRegistry {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+impl CircuitBreakerRegistry {
+    /// 创建熔断器注册表
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        let (event_sender, _) = broadcast::channel(64);
+        Self {
+            config: RwLock::new(config),
+            inner: Mutex::new(RegistryInner::default()),
+            event_sender,
+        }
+    }
+
+    /// 更新配置（禁用时清空所有熔断状态）
+    pub fn set_config(&self, config: CircuitBreakerConfig) {
+        if !config.enabled {
+            self.inner.lock().breakers.clear();
+        }
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> CircuitBreakerConfig {
+        self.config.read().clone()
+    }
+
+    /// 订阅状态变化事件
+    pub fn subscribe(&self) -> broadcast::Receiver<CircuitTransition> {
+        self.event_sender.subscribe()
+    }
+
+    /// 凭证及其上游主机的熔断器是否都允许选择
+    ///
+    /// `host` 为 None 时（无法确定上游主机）只检查凭证级熔断器
+    pub fn is_call_permitted(&self, credential: &str, host: Option<&str>) -> bool {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return true;
+        }
+        let now = Instant::now();
+        let inner = self.inner.lock();
+        scoped_keys(credential, host).all(|(scope, key)| {
+            inner
+                .breakers
+                .get(&(scope, key.to_string()))
+                .is_none_or(|breaker| breaker.permits(&config, now))
+        })
+    }
+
+    /// 凭证被选中后调用：记录上游主机，并让冷却结束的熔断器进入半开
+    pub fn on_selected(&self, credential: &str, host: Option<&str>) {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return;
+        }
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        {
+            let mut inner = self.inner.lock();
+            match host {
+                Some(host) => inner
+                    .credential_hosts
+                    .insert(credential.to_string(), host.to_string()),
+                None => inner.credential_hosts.remove(credential),
+            };
+            for (scope, key) in scoped_keys(credential, host) {
+                if let Some(breaker) = inner.breakers.get_mut(&(scope, key.to_string())) {
+                    if let Some(change) = breaker.acquire(&config, now) {
+                        changes.push((scope, key.to_string(), change));
+                    }
+                }
+            }
+        }
+        self.publish(changes);
+    }
+
+    /// 凭证被选中后未发出请求或请求被取消时调用，归还 [`Self::on_selected`] 占用的探测名额
+    pub fn release(&self, credential: &str) {
+        let mut inner = self.inner.lock();
+        let host = inner.credential_hosts.get(credential).cloned();
+        for (scope, key) in scoped_keys(credential, host.as_deref()) {
+            if let Some(breaker) = inner.breakers.get_mut(&(scope, key.to_string())) {
+                breaker.release();
+            }
+        }
+    }
+
+    /// 记录凭证请求成功
+    pub fn record_success(&self, credential: &str) {
+        self.record(credential, true);
+    }
+
+    /// 记录凭证请求失败
+    pub fn record_failure(&self, credential: &str) {
+        self.record(credential, false);
+    }
+
+    /// 重置凭证的熔断状态（手动恢复凭证健康时调用）
+    pub fn reset(&self, credential: &str) {
+        let mut inner = self.inner.lock();
+        inner
+            .breakers
+            .remove(&(CircuitScope::Credential, credential.to_string()));
+    }
+
+    /// 当前所有熔断器的状态快照
+    pub fn snapshot(&self) -> Vec<CircuitSnapshot> {
+        let inner = self.inner.lock();
+        let mut snapshots: Vec<CircuitSnapshot> = inner
+            .breakers
+            .iter()
+            .map(|((scope, key), breaker)| CircuitSnapshot {
+                scope: *scope,
+                key: key.clone(),
+                state: breaker.state,
+                consecutive_failures: breaker.consecutive_failures,
+                window_requests: breaker.window.len(),
+                window_failures: breaker.window.iter().filter(|(_, ok)| !ok).count(),
+                opened_at: breaker.opened_at_utc,
+            })
+            .collect();
+        snapshots.sort_by(|a, b| (a.scope as u8, &a.key).cmp(&(b.scope as u8, &b.key)));
+        snapshots
+    }
+
+    /// 处于打开或半开状态的熔断器数量
+    pub fn open_count(&self) -> usize {
+        self.inner
+            .lock()
+            .breakers
+            .values()
+            .filter(|breaker| breaker.state != CircuitState::Closed)
+            .count()
+    }
+
+    fn record(&self, credential: &str, success: bool) {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return;
+        }
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        {
+            let mut inner = self.inner.lock();
+            let mut targets = vec![(CircuitScope::Credential, credential.to_string())];
+            if let Some(host) = inner.credential_hosts.get(credential) {
+                targets.push((CircuitScope::Host, host.clone()));
+            }
+            for target in targets {
+                let breaker = inner
+                    .breakers
+                    .entry(target.clone())
+                    .or_insert_with(CircuitBreaker::new);
+                let change = if success {
+                    breaker.on_success(&config, now)
+                } else {
+                    breaker.on_failure(&config, now)
+                };
+                if let Some(change) = change {
+                    changes.push((target.0, target.1, change));
+                }
+            }
+        }
+        self.publish(changes);
+    }
+
+    fn publish(&self, changes: Vec<(CircuitScope, String, StateChange)>) {
+        for (scope, key, (from, to, reason)) in changes {
+            if to == CircuitState::Open {
+                tracing::warn!("[CIRCUIT] {} {} {} -> {}: {}", scope, key, from, to, reason);
+            } else {
+                tracing::info!("[CIRCUIT] {} {} {} -> {}: {}", scope, key, from, to, reason);
+            }
+            let _ = self.event_sender.send(CircuitTransition {
+                scope,
+                key,
+                from,
+                to,
+                reason,
+                timestamp: Utc::now(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            min_requests: 4,
+            error_rate_threshold: 0.5,
+            window_secs: 60,
+            open_duration_secs: 30,
+            half_open_max_probes: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let config = config();
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new();
+
+        assert!(breaker.on_failure(&config, now).is_none());
+        assert!(breaker.on_failure(&config, now).is_none());
+        let (from, to, _) = breaker.on_failure(&config, now).unwrap();
+        assert_eq!((from, to), (CircuitState::Closed, CircuitState::Open));
+        assert!(!breaker.permits(&config, now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_opens_on_error_rate() {
+        let config = config();
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new();
+
+        breaker.on_success(&config, now);
+        breaker.on_failure(&config, now);
+        breaker.on_success(&config, now);
+        let (_, to, reason) = breaker.on_failure(&config, now).unwrap();
+        assert_eq!(to, CircuitState::Open);
+        assert!(reason.contains("50%"));
+
+        // 窗口外的结果不计入错误率
+        let mut breaker = CircuitBreaker::new();
+        breaker.on_failure(&config, now);
+        breaker.on_failure(&config, now);
+        let later = now + Duration::from_secs(120);
+        breaker.on_success(&config, later);
+        breaker.on_success(&config, later);
+        assert!(breaker.on_failure(&config, later).is_none());
+    }
+
+    #[test]
+    fn test_half_open_probes() {
+        let config = config();
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new();
+        for _ in 0..3 {
+            breaker.on_failure(&config, now);
+        }
+
+        let after = now + Duration::from_secs(30);
+        assert!(breaker.permits(&config, after));
+        let (_, to, _) = breaker.acquire(&config, after).unwrap();
+        assert_eq!(to, CircuitState::HalfOpen);
+        assert!(breaker.acquire(&config, after).is_none());
+        // 探测名额已用完
+        assert!(!breaker.permits(&config, after));
+
+        assert!(breaker.on_success(&config, after).is_none());
+        let (from, to, _) = breaker.on_success(&config, after).unwrap();
+        assert_eq!((from, to), (CircuitState::HalfOpen, CircuitState::Closed));
+
+        // 半开状态下探测失败重新打开
+        for _ in 0..3 {
+            breaker.on_failure(&config, after);
+        }
+        let later = after + Duration::from_secs(30);
+        breaker.acquire(&config, later);
+        let (from, to, _) = breaker.on_failure(&config, later).unwrap();
+        assert_eq!((from, to), (CircuitState::HalfOpen, CircuitState::Open));
+    }
+
+    #[test]
+    fn test_release_returns_probe_slot() {
+        let registry = CircuitBreakerRegistry::new(config());
+        registry.on_selected("cred-1", Some("api.example.com"));
+        for _ in 0..3 {
+            registry.record_failure("cred-1");
+        }
+
+        // 冷却结束后两个探测名额都被占用，归还一个后可以再次选择
+        let mut inner = registry.inner.lock();
+        for breaker in inner.breakers.values_mut() {
+            breaker.opened_at = Some(Instant::now() - Duration::from_secs(30));
+        }
+        drop(inner);
+        registry.on_selected("cred-1", Some("api.example.com"));
+        registry.on_selected("cred-1", Some("api.example.com"));
+        assert!(!registry.is_call_permitted("cred-1", Some("api.example.com")));
+
+        registry.release("cred-1");
+        assert!(registry.is_call_permitted("cred-1", Some("api.example.com")));
+    }
+
+    #[test]
+    fn test_registry_tracks_credential_and_host() {
+        let registry = CircuitBreakerRegistry::new(config());
+        let mut events = registry.subscribe();
+
+        registry.on_selected("cred-1", Some("api.example.com"));
+        registry.on_selected("cred-2", Some("api.example.com"));
+        registry.record_failure("cred-1");
+        registry.record_failure("cred-2");
+        assert!(registry.is_call_permitted("cred-1", Some("api.example.com")));
+
+        // 主机连续失败 3 次后打开，同一主机上的其他凭证也被拒绝
+        registry.record_failure("cred-1");
+        assert!(!registry.is_call_permitted("cred-3", Some("api.example.com")));
+        assert!(registry.is_call_permitted("cred-3", Some("other.example.com")));
+
+        let transition = events.try_recv().unwrap();
+        assert_eq!(transition.scope, CircuitScope::Host);
+        assert_eq!(transition.to, CircuitState::Open);
+        assert_eq!(registry.open_count(), 1);
+
+        registry.set_config(CircuitBreakerConfig {
+            enabled: false,
+            ..config()
+        });
+        assert!(registry.is_call_permitted("cred-3", Some("api.example.com")));
+        assert!(registry.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_registry_without_host_only_tracks_credential() {
+        let registry = CircuitBreakerRegistry::new(config());
+
+        // 无法确定上游主机的凭证（如 OAuth 凭证）互不影响
+        registry.on_selected("oauth-1", None);
+        for _ in 0..3 {
+            registry.record_failure("oauth-1");
+        }
+        assert!(!registry.is_call_permitted("oauth-1", None));
+        assert!(registry.is_call_permitted("oauth-2", None));
+        assert_eq!(registry.open_count(), 1);
+        assert!(registry
+            .snapshot()
+            .iter()
+            .all(|s| s.scope == CircuitScope::Credential));
+    }
+}
@@ -0,0 +1,572 @@
+//! 并发限制与请求排队
+//!
+//! 按凭证和 Provider 限制同时在途的请求数。超出限制的请求进入公平的 FIFO 队列，
+//! 交互式请求优先于批量请求出队；队列已满或等待超时时返回错误，由调用方转换为 429
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::config::ConcurrencyConfig;
+
+/// 指定请求优先级的请求头
+pub const PRIORITY_HEADER: &str = "x-proxycast-priority";
+
+/// 请求优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPriority {
+    /// 交互式请求（默认），排队时优先出队
+    #[default]
+    Interactive,
+    /// 批量请求，仅在没有交互式请求等待时出队
+    Batch,
+}
+
+impl RequestPriority {
+    /// 从请求头的值解析优先级
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "interactive" | "high" => Some(Self::Interactive),
+            "batch" | "low" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// 排队失败
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QueueError {
+    #[error("Too many concurrent requests for {scope}, queue is full")]
+    QueueFull {
+        scope: String,
+        retry_after_secs: u64,
+    },
+    #[error("Timed out waiting for a free slot on {scope}")]
+    Timeout {
+        scope: String,
+        retry_after_secs: u64,
+    },
+}
+
+impl QueueError {
+    /// 建议的重试等待秒数
+    pub fn retry_after_secs(&self) -> u64 {
+        match self {
+            QueueError::QueueFull {
+                retry_after_secs, ..
+            }
+            | QueueError::Timeout {
+                retry_after_secs, ..
+            } => *retry_after_secs,
+        }
+    }
+}
+
+/// 单个凭证或 Provider 的并发闸门
+struct Gate {
+    state: Mutex<GateState>,
+}
+
+struct GateState {
+    limit: u32,
+    active: u32,
+    next_ticket: u64,
+    interactive: VecDeque<Waiter>,
+    batch: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    ticket: u64,
+    tx: oneshot::Sender<ConcurrencyPermit>,
+}
+
+impl GateState {
+    fn queued(&self) -> usize {
+        self.interactive.len() + self.batch.len()
+    }
+
+    fn remove(&mut self, ticket: u64) -> bool {
+        for queue in [&mut self.interactive, &mut self.batch] {
+            if let Some(index) = queue.iter().position(|w| w.ticket == ticket) {
+                queue.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Gate {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(GateState {
+                limit: 0,
+                active: 0,
+                next_ticket: 0,
+                interactive: VecDeque::new(),
+                batch: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// 在有空闲名额时按优先级依次唤醒等待者
+    ///
+    /// 名额随许可一起交给等待者；等待者已放弃时收回名额
+    fn grant_waiters(self: &Arc<Self>, state: &mut GateState) {
+        while state.active < state.limit {
+            let Some(waiter) = state
+                .interactive
+                .pop_front()
+                .or_else(|| state.batch.pop_front())
+            else {
+                break;
+            };
+            state.active += 1;
+            if let Err(mut permit) = waiter.tx.send(ConcurrencyPermit::new(self.clone())) {
+                permit.gate = None;
+                state.active -= 1;
+            }
+        }
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.active = state.active.saturating_sub(1);
+        self.grant_waiters(&mut state);
+    }
+
+    async fn acquire(
+        self: &Arc<Self>,
+        limit: u32,
+        priority: RequestPriority,
+        max_queue_size: usize,
+        deadline: Instant,
+    ) -> Result<ConcurrencyPermit, bool> {
+        let (ticket, mut rx) = {
+            let mut state = self.state.lock();
+            state.limit = limit;
+            self.grant_waiters(&mut state);
+            if state.active < state.limit && state.queued() == 0 {
+                state.active += 1;
+                return Ok(ConcurrencyPermit::new(self.clone()));
+            }
+            if state.queued() >= max_queue_size {
+                return Err(false);
+            }
+
+            let (tx, rx) = oneshot::channel();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            let waiter = Waiter { ticket, tx };
+            match priority {
+                RequestPriority::Interactive => state.interactive.push_back(waiter),
+                RequestPriority::Batch => state.batch.push_back(waiter),
+            }
+            (ticket, rx)
+        };
+
+        let wait = deadline.saturating_duration_since(Instant::now());
+        if let Ok(Ok(permit)) = tokio::time::timeout(wait, &mut rx).await {
+            return Ok(permit);
+        }
+
+        // 超时：仍在队列中则放弃；已被唤醒则许可已在通道中
+        let mut state = self.state.lock();
+        if state.remove(ticket) {
+            return Err(true);
+        }
+        drop(state);
+        rx.try_recv().map_err(|_| true)
+    }
+
+    fn is_saturated(&self) -> bool {
+        let state = self.state.lock();
+        state.limit > 0 && (state.active >= state.limit || state.queued() > 0)
+    }
+
+    fn snapshot(&self) -> (u32, u32, usize) {
+        let state = self.state.lock();
+        (state.limit, state.active, state.queued())
+    }
+}
+
+/// 单个闸门的并发许可，释放时唤醒下一个等待者
+pub struct ConcurrencyPermit {
+    gate: Option<Arc<Gate>>,
+}
+
+impl ConcurrencyPermit {
+    fn new(gate: Arc<Gate>) -> Self {
+        Self { gate: Some(gate) }
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        if let Some(gate) = self.gate.take() {
+            gate.release();
+        }
+    }
+}
+
+/// 一个请求持有的全部并发许可（Provider 与凭证），在请求结束时释放
+pub struct RequestPermit {
+    _permits: Vec<ConcurrencyPermit>,
+    /// 排队等待时间（毫秒）
+    pub queued_ms: u64,
+}
+
+/// 并发状态快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencySnapshot {
+    /// 作用域（`credential:<uuid>` 或 `provider:<type>`）
+    pub scope: String,
+    /// 并发上限
+    pub limit: u32,
+    /// 在途请求数
+    pub active: u32,
+    /// 排队请求数
+    pub queued: usize,
+}
+
+/// 并发限制器
+pub struct ConcurrencyLimiter {
+    config: RwLock<ConcurrencyConfig>,
+    gates: DashMap<String, Arc<Gate>>,
+}
+
+impl Default for ConcurrencyLimiter {
+    fn default() -> Self {
+        Self::new(ConcurrencyConfig::default())
+    }
+}
+
+impl ConcurrencyLimiter {
+    /// 创建新的并发限制器
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            gates: DashMap::new(),
+        }
+    }
+
+    /// 更新配置（热重载），新的上限在下一次排队时生效
+    pub fn set_config(&self, config: ConcurrencyConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> ConcurrencyConfig {
+        self.config.read().clone()
+    }
+
+    /// 是否启用
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().enabled
+    }
+
+    /// 凭证的并发上限（未限制时返回 None）
+    pub fn credential_limit(&self, credential_id: &str) -> Option<u32> {
+        let config = self.config.read();
+        let limit = config
+            .credential_limits
+            .get(credential_id)
+            .copied()
+            .unwrap_or(config.max_per_credential);
+        (config.enabled && limit > 0).then_some(limit)
+    }
+
+    /// Provider 的并发上限（未限制时返回 None）
+    pub fn provider_limit(&self, provider: &str) -> Option<u32> {
+        let config = self.config.read();
+        let limit = config
+            .provider_limits
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(provider))
+            .map_or(0, |(_, limit)| *limit);
+        (config.enabled && limit > 0).then_some(limit)
+    }
+
+    /// 解析请求优先级：按客户端 key 配置，默认交互式
+    ///
+    /// 请求头只能降低优先级，配置为批量的客户端 key 不能通过请求头提升为交互式
+    pub fn priority_for(
+        &self,
+        header: Option<&str>,
+        client_key: Option<(&str, &str)>,
+    ) -> RequestPriority {
+        let config = self.config.read();
+        let is_batch_key = client_key.is_some_and(|(id, label)| {
+            config
+                .batch_client_keys
+                .iter()
+                .any(|key| key == id || key == label)
+        });
+        if is_batch_key || header.and_then(RequestPriority::parse) == Some(RequestPriority::Batch) {
+            RequestPriority::Batch
+        } else {
+            RequestPriority::Interactive
+        }
+    }
+
+    /// 凭证是否已满（没有空闲名额或已有请求在排队）
+    pub fn is_saturated(&self, credential_id: &str) -> bool {
+        self.credential_limit(credential_id).is_some()
+            && self
+                .gates
+                .get(&credential_scope(credential_id))
+                .is_some_and(|gate| gate.is_saturated())
+    }
+
+    /// 获取请求许可
+    ///
+    /// 先占用凭证名额，再占用 Provider 名额，名额不足时排队等待，
+    /// 两者共享同一个最大等待时间。在凭证队列中等待时不占用 Provider 名额，
+    /// 避免一个繁忙的凭证阻塞同一 Provider 下的其他凭证。
+    /// 未启用或未配置上限时直接返回空许可
+    pub async fn acquire(
+        &self,
+        provider: &str,
+        credential_id: &str,
+        priority: RequestPriority,
+    ) -> Result<RequestPermit, QueueError> {
+        let started = Instant::now();
+        let config = self.config();
+        let deadline = started + Duration::from_millis(config.max_queue_time_ms);
+        let retry_after_secs = config.max_queue_time_ms.div_ceil(1000).max(1);
+
+        let scopes = [
+            (
+                credential_scope(credential_id),
+                self.credential_limit(credential_id),
+            ),
+            (provider_scope(provider), self.provider_limit(provider)),
+        ];
+
+        let mut permits = Vec::new();
+        for (scope, limit) in scopes {
+            let Some(limit) = limit else { continue };
+            let gate = self
+                .gates
+                .entry(scope.clone())
+                .or_insert_with(|| Arc::new(Gate::new()))
+                .clone();
+            match gate
+                .acquire(limit, priority, config.max_queue_size, deadline)
+                .await
+            {
+                Ok(permit) => permits.push(permit),
+                Err(timed_out) => {
+                    tracing::warn!(
+                        "[CONCURRENCY] {} 排队失败（{}），优先级 {:?}",
+                        scope,
+                        if timed_out {
+                            "等待超时"
+                        } else {
+                            "队列已满"
+                        },
+                        priority
+                    );
+                    return Err(if timed_out {
+                        QueueError::Timeout {
+                            scope,
+                            retry_after_secs,
+                        }
+                    } else {
+                        QueueError::QueueFull {
+                            scope,
+                            retry_after_secs,
+                        }
+                    });
+                }
+            }
+        }
+
+        let queued_ms = started.elapsed().as_millis() as u64;
+        if queued_ms > 0 && !permits.is_empty() {
+            tracing::debug!(
+                "[CONCURRENCY] 凭证 {} 排队 {}ms 后获得名额",
+                credential_id,
+                queued_ms
+            );
+        }
+        Ok(RequestPermit {
+            _permits: permits,
+            queued_ms,
+        })
+    }
+
+    /// 所有闸门的并发状态
+    pub fn snapshot(&self) -> Vec<ConcurrencySnapshot> {
+        let mut snapshots: Vec<ConcurrencySnapshot> = self
+            .gates
+            .iter()
+            .map(|entry| {
+                let (limit, active, queued) = entry.value().snapshot();
+                ConcurrencySnapshot {
+                    scope: entry.key().clone(),
+                    limit,
+                    active,
+                    queued,
+                }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.scope.cmp(&b.scope));
+        snapshots
+    }
+}
+
+fn credential_scope(credential_id: &str) -> String {
+    format!("credential:{}", credential_id)
+}
+
+fn provider_scope(provider: &str) -> String {
+    format!("provider:{}", provider.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_per_credential: u32, max_queue_size: usize) -> ConcurrencyLimiter {
+        ConcurrencyLimiter::new(ConcurrencyConfig {
+            enabled: true,
+            max_per_credential,
+            max_queue_size,
+            max_queue_time_ms: 200,
+            ..ConcurrencyConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_and_timeout() {
+        let limiter = limiter(1, 1);
+        let held = limiter
+            .acquire("kiro", "c1", RequestPriority::Interactive)
+            .await
+            .unwrap();
+        assert!(limiter.is_saturated("c1"));
+
+        let waiting = limiter.acquire("kiro", "c1", RequestPriority::Interactive);
+        let rejected = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            limiter
+                .acquire("kiro", "c1", RequestPriority::Interactive)
+                .await
+        };
+        let (waiting, rejected) = tokio::join!(waiting, rejected);
+        assert!(matches!(waiting, Err(QueueError::Timeout { .. })));
+        assert!(matches!(rejected, Err(QueueError::QueueFull { .. })));
+
+        drop(held);
+        assert!(!limiter.is_saturated("c1"));
+    }
+
+    #[tokio::test]
+    async fn test_interactive_dequeued_before_batch() {
+        let limiter = Arc::new(limiter(1, 8));
+        let held = limiter
+            .acquire("kiro", "c1", RequestPriority::Interactive)
+            .await
+            .unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for (name, priority) in [
+            ("batch", RequestPriority::Batch),
+            ("interactive", RequestPriority::Interactive),
+        ] {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire("kiro", "c1", priority).await.unwrap();
+                order.lock().push(name);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*order.lock(), vec!["interactive", "batch"]);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_on_credential_does_not_hold_provider_slot() {
+        let limiter = ConcurrencyLimiter::new(ConcurrencyConfig {
+            enabled: true,
+            max_per_credential: 1,
+            provider_limits: [("kiro".to_string(), 2)].into_iter().collect(),
+            max_queue_size: 8,
+            max_queue_time_ms: 200,
+            ..ConcurrencyConfig::default()
+        });
+        let _held = limiter
+            .acquire("kiro", "c1", RequestPriority::Interactive)
+            .await
+            .unwrap();
+
+        // c1 的第二个请求在凭证队列中等待，c2 仍能拿到 Provider 的第二个名额
+        let waiting = limiter.acquire("kiro", "c1", RequestPriority::Interactive);
+        let other = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            limiter
+                .acquire("kiro", "c2", RequestPriority::Interactive)
+                .await
+        };
+        let (waiting, other) = tokio::join!(waiting, other);
+        assert!(matches!(waiting, Err(QueueError::Timeout { .. })));
+        let other = other.unwrap();
+        assert!(other.queued_ms < 100);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_or_unlimited() {
+        let limiter = ConcurrencyLimiter::default();
+        let _a = limiter
+            .acquire("kiro", "c1", RequestPriority::Interactive)
+            .await
+            .unwrap();
+        let _b = limiter
+            .acquire("kiro", "c1", RequestPriority::Interactive)
+            .await
+            .unwrap();
+        assert!(!limiter.is_saturated("c1"));
+    }
+
+    #[test]
+    fn test_priority_for() {
+        let limiter = ConcurrencyLimiter::new(ConcurrencyConfig {
+            batch_client_keys: vec!["nightly-eval".to_string()],
+            ..ConcurrencyConfig::default()
+        });
+        assert_eq!(
+            limiter.priority_for(None, Some(("k1", "nightly-eval"))),
+            RequestPriority::Batch
+        );
+        // 请求头不能把批量 key 提升为交互式
+        assert_eq!(
+            limiter.priority_for(Some("interactive"), Some(("k1", "nightly-eval"))),
+            RequestPriority::Batch
+        );
+        assert_eq!(
+            limiter.priority_for(Some("batch"), Some(("k2", "ide"))),
+            RequestPriority::Batch
+        );
+        assert_eq!(
+            limiter.priority_for(Some("batch"), None),
+            RequestPriority::Batch
+        );
+        assert_eq!(
+            limiter.priority_for(None, None),
+            RequestPriority::Interactive
+        );
+    }
+}
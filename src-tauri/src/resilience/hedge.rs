@@ -0,0 +1,514 @@
+//! 对冲请求
+//!
+//! 记录每个模型近期的首字节时间（TTFB），当请求的首字节等待时间超过
+//! 该模型 TTFB 的指定分位数时，使用其他凭证或 Provider 发出对冲请求
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::HedgingConfig;
+use crate::ProviderType;
+
+/// 每个模型保留的 TTFB 样本数
+const TTFB_WINDOW_SIZE: usize = 200;
+
+/// 对冲尝试结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HedgeOutcome {
+    /// 最先产生 token，响应返回给客户端
+    Won,
+    /// 另一方胜出后被取消
+    Cancelled,
+    /// 请求失败
+    Failed,
+}
+
+/// 对冲请求中的一次尝试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HedgeAttemptRecord {
+    /// 尝试序号（0 为原始请求，1 为对冲请求）
+    pub index: u32,
+    /// 使用的 Provider
+    pub provider: ProviderType,
+    /// 使用的凭证 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<String>,
+    /// 相对原始请求的发出时间（毫秒）
+    pub started_after_ms: u64,
+    /// 首字节时间（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttfb_ms: Option<u64>,
+    /// 尝试结果
+    pub outcome: HedgeOutcome,
+    /// 失败原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 对冲请求中一次尝试的目标
+#[derive(Debug, Clone)]
+pub struct HedgeTarget {
+    /// 使用的 Provider
+    pub provider: ProviderType,
+    /// 使用的凭证 ID
+    pub credential_id: Option<String>,
+    /// 该尝试的取消令牌，尝试落败时被触发
+    pub cancel_token: CancellationToken,
+}
+
+/// 对冲调用结果
+#[derive(Debug)]
+pub struct HedgeRace<T, E> {
+    /// 胜出尝试的序号和结果；所有尝试均失败时优先返回原始请求的错误
+    pub result: Result<(u32, T), E>,
+    /// 发出了对冲请求时各次尝试的记录（未对冲时为空）
+    pub attempts: Vec<HedgeAttemptRecord>,
+}
+
+/// 对冲控制器
+///
+/// 维护按模型划分的 TTFB 滑动窗口，并根据配置计算对冲等待时间
+pub struct HedgeController {
+    config: RwLock<HedgingConfig>,
+    ttfb: Mutex<HashMap<String, VecDeque<u64>>>,
+}
+
+impl Default for HedgeController {
+    fn default() -> Self {
+        Self::new(HedgingConfig::default())
+    }
+}
+
+impl HedgeController {
+    /// 创建新的对冲控制器
+    pub fn new(config: HedgingConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            ttfb: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 更新配置（热重载），已记录的 TTFB 样本保留
+    pub fn set_config(&self, config: HedgingConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> HedgingConfig {
+        self.config.read().clone()
+    }
+
+    /// 记录一次首字节时间
+    pub fn record_ttfb(&self, model: &str, ttfb_ms: u64) {
+        let mut ttfb = self.ttfb.lock();
+        let samples = ttfb.entry(model.to_string()).or_default();
+        if samples.len() >= TTFB_WINDOW_SIZE {
+            samples.pop_front();
+        }
+        samples.push_back(ttfb_ms);
+    }
+
+    /// 模型当前的 TTFB 样本数
+    pub fn sample_count(&self, model: &str) -> usize {
+        self.ttfb.lock().get(model).map_or(0, VecDeque::len)
+    }
+
+    /// 计算模型近期 TTFB 的分位数（最近秩法）
+    pub fn ttfb_percentile(&self, model: &str, percentile: f64) -> Option<u64> {
+        let mut samples: Vec<u64> = self.ttfb.lock().get(model)?.iter().copied().collect();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = (percentile.clamp(0.0, 1.0) * samples.len() as f64).ceil() as usize;
+        Some(samples[rank.saturating_sub(1).min(samples.len() - 1)])
+    }
+
+    /// 对冲是否已启用且覆盖该 Provider
+    pub fn applies_to(&self, provider: ProviderType) -> bool {
+        let config = self.config.read();
+        config.enabled
+            && (config.providers.is_empty()
+                || config
+                    .providers
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(&provider.to_string())))
+    }
+
+    /// 计算对冲等待时间
+    ///
+    /// 未启用、Provider 不在范围内或样本不足时返回 None（不对冲）
+    pub fn hedge_delay(&self, provider: ProviderType, model: &str) -> Option<Duration> {
+        if !self.applies_to(provider) {
+            return None;
+        }
+        let config = self.config.read().clone();
+        if self.sample_count(model) < config.min_samples.max(1) {
+            return None;
+        }
+
+        let ttfb_ms = self.ttfb_percentile(model, config.ttfb_percentile)?;
+        let delay_ms = ttfb_ms.clamp(
+            config.min_delay_ms,
+            config.max_delay_ms.max(config.min_delay_ms),
+        );
+        Some(Duration::from_millis(delay_ms))
+    }
+
+    /// 执行一次可对冲的调用
+    ///
+    /// `primary` 和 `hedge` 返回的 Future 应在产生首个 token 时完成。
+    /// 原始请求超过对冲等待时间仍未完成时调用 `hedge` 发出第二次尝试（返回 None 表示没有
+    /// 可用的对冲目标），先成功的一方胜出。落败一方尚未结束时触发其目标的取消令牌，
+    /// 并等待其 Future 完成取消清理后返回，因此两个 Future 都应在令牌取消后尽快完成。
+    /// Provider 在对冲范围内时，胜出方的首字节时间计入该模型的 TTFB 样本
+    pub async fn race<T, E, PF, H, HF>(
+        &self,
+        model: &str,
+        primary_target: HedgeTarget,
+        primary: PF,
+        hedge: H,
+    ) -> HedgeRace<T, E>
+    where
+        PF: Future<Output = Result<T, E>>,
+        H: FnOnce() -> Option<(HedgeTarget, HF)>,
+        HF: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let started = Instant::now();
+        let sampled = self.applies_to(primary_target.provider);
+        let primary_fut = primary;
+        tokio::pin!(primary_fut);
+
+        // 在对冲等待时间内完成（或不对冲）时只有原始请求
+        let early = match self.hedge_delay(primary_target.provider, model) {
+            Some(delay) => tokio::select! {
+                result = &mut primary_fut => Some(result),
+                _ = tokio::time::sleep(delay) => None,
+            },
+            None => Some((&mut primary_fut).await),
+        };
+        let delay_ms = started.elapsed().as_millis() as u64;
+        let hedge_attempt = match early {
+            Some(_) => None,
+            None => hedge(),
+        };
+        let Some((hedge_target, hedge_fut)) = hedge_attempt else {
+            let result = match early {
+                Some(result) => result,
+                None => {
+                    tracing::info!(
+                        "[HEDGE] model={} 没有可用的对冲目标，继续等待原始请求",
+                        model
+                    );
+                    primary_fut.await
+                }
+            };
+            if sampled && result.is_ok() {
+                self.record_ttfb(model, started.elapsed().as_millis() as u64);
+            }
+            return HedgeRace {
+                result: result.map(|value| (0, value)),
+                attempts: Vec::new(),
+            };
+        };
+
+        tracing::info!(
+            "[HEDGE] model={} primary={} hedge={} delay_ms={}",
+            model,
+            primary_target.provider,
+            hedge_target.provider,
+            delay_ms
+        );
+        tokio::pin!(hedge_fut);
+
+        // 两个尝试赛跑，先成功的一方胜出；一方失败时继续等待另一方，
+        // 均失败时返回原始请求的错误
+        let mut errors: [Option<String>; 2] = [None, None];
+        let mut primary_error = None;
+        let winner = loop {
+            tokio::select! {
+                result = &mut primary_fut, if errors[0].is_none() => match result {
+                    Ok(value) => break Ok((0usize, value)),
+                    Err(err) => {
+                        errors[0] = Some(err.to_string());
+                        if errors[1].is_some() {
+                            break Err(err);
+                        }
+                        primary_error = Some(err);
+                    }
+                },
+                result = &mut hedge_fut, if errors[1].is_none() => match result {
+                    Ok(value) => break Ok((1usize, value)),
+                    Err(err) => {
+                        errors[1] = Some(err.to_string());
+                        if let Some(err) = primary_error.take() {
+                            break Err(err);
+                        }
+                    }
+                },
+            }
+        };
+        let finished_ms = started.elapsed().as_millis() as u64;
+
+        // 通过取消令牌取消尚未结束的落败一方，等待它放弃上游调用并完成清理
+        if let Ok((index, _)) = &winner {
+            let loser = 1 - index;
+            if errors[loser].is_none() {
+                if loser == 0 {
+                    primary_target.cancel_token.cancel();
+                    let _ = (&mut primary_fut).await;
+                } else {
+                    hedge_target.cancel_token.cancel();
+                    let _ = (&mut hedge_fut).await;
+                }
+            }
+        }
+
+        let started_after = [0, delay_ms];
+        let targets = [primary_target, hedge_target];
+        let winner_index = winner.as_ref().ok().map(|(index, _)| *index);
+        let attempts = (0..2)
+            .map(|i| HedgeAttemptRecord {
+                index: i as u32,
+                provider: targets[i].provider,
+                credential_id: targets[i].credential_id.clone(),
+                started_after_ms: started_after[i],
+                ttfb_ms: (winner_index == Some(i)).then(|| finished_ms - started_after[i]),
+                outcome: match (&errors[i], winner_index == Some(i)) {
+                    (_, true) => HedgeOutcome::Won,
+                    (Some(_), _) => HedgeOutcome::Failed,
+                    (None, false) => HedgeOutcome::Cancelled,
+                },
+                error: errors[i].clone(),
+            })
+            .collect();
+
+        let result = match winner {
+            Ok((index, value)) => {
+                let ttfb_ms = finished_ms - started_after[index];
+                tracing::info!(
+                    "[HEDGE] model={} winner={} provider={} ttfb_ms={}",
+                    model,
+                    index,
+                    targets[index].provider,
+                    ttfb_ms
+                );
+                if sampled {
+                    self.record_ttfb(model, ttfb_ms);
+                }
+                Ok((index as u32, value))
+            }
+            Err(err) => {
+                tracing::warn!("[HEDGE] model={} 原始请求和对冲请求均失败", model);
+                Err(err)
+            }
+        };
+        HedgeRace { result, attempts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_config() -> HedgingConfig {
+        HedgingConfig {
+            enabled: true,
+            min_samples: 5,
+            min_delay_ms: 100,
+            max_delay_ms: 5_000,
+            ..HedgingConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_ttfb_percentile() {
+        let controller = HedgeController::new(enabled_config());
+        for ttfb in 1..=100 {
+            controller.record_ttfb("claude-sonnet-4", ttfb * 10);
+        }
+
+        assert_eq!(
+            controller.ttfb_percentile("claude-sonnet-4", 0.5),
+            Some(500)
+        );
+        assert_eq!(
+            controller.ttfb_percentile("claude-sonnet-4", 0.95),
+            Some(950)
+        );
+        assert_eq!(
+            controller.ttfb_percentile("claude-sonnet-4", 1.0),
+            Some(1000)
+        );
+        assert_eq!(controller.ttfb_percentile("gpt-4o", 0.95), None);
+    }
+
+    #[test]
+    fn test_ttfb_window_is_bounded() {
+        let controller = HedgeController::default();
+        for _ in 0..TTFB_WINDOW_SIZE + 50 {
+            controller.record_ttfb("m", 1);
+        }
+        assert_eq!(controller.sample_count("m"), TTFB_WINDOW_SIZE);
+    }
+
+    #[test]
+    fn test_hedge_delay() {
+        let controller = HedgeController::new(enabled_config());
+        // 样本不足时不对冲
+        controller.record_ttfb("m", 800);
+        assert_eq!(controller.hedge_delay(ProviderType::Kiro, "m"), None);
+
+        for _ in 0..10 {
+            controller.record_ttfb("m", 800);
+        }
+        assert_eq!(
+            controller.hedge_delay(ProviderType::Kiro, "m"),
+            Some(Duration::from_millis(800))
+        );
+
+        // 等待时间受上下限约束
+        for _ in 0..TTFB_WINDOW_SIZE {
+            controller.record_ttfb("slow", 60_000);
+        }
+        assert_eq!(
+            controller.hedge_delay(ProviderType::Kiro, "slow"),
+            Some(Duration::from_millis(5_000))
+        );
+
+        // Provider 范围限制
+        controller.set_config(HedgingConfig {
+            providers: vec!["antigravity".to_string()],
+            ..enabled_config()
+        });
+        assert_eq!(controller.hedge_delay(ProviderType::Kiro, "m"), None);
+
+        // 未启用
+        controller.set_config(HedgingConfig::default());
+        assert_eq!(controller.hedge_delay(ProviderType::Kiro, "m"), None);
+    }
+
+    fn target(credential_id: &str) -> HedgeTarget {
+        HedgeTarget {
+            provider: ProviderType::Kiro,
+            credential_id: Some(credential_id.to_string()),
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    fn hedging_controller() -> HedgeController {
+        let controller = HedgeController::new(HedgingConfig {
+            enabled: true,
+            min_samples: 1,
+            min_delay_ms: 20,
+            max_delay_ms: 50,
+            ..HedgingConfig::default()
+        });
+        controller.record_ttfb("m", 20);
+        controller
+    }
+
+    #[tokio::test]
+    async fn test_race_hedge_wins() {
+        let controller = hedging_controller();
+        let primary_target = target("cred-0");
+        let primary_token = primary_target.cancel_token.clone();
+        let cleaned_up = std::sync::atomic::AtomicBool::new(false);
+
+        let race = controller
+            .race(
+                "m",
+                primary_target,
+                async {
+                    // 原始请求卡住，直到被取消
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(5)) => Ok::<_, String>("primary"),
+                        _ = primary_token.cancelled() => {
+                            cleaned_up.store(true, std::sync::atomic::Ordering::SeqCst);
+                            Err("cancelled".to_string())
+                        }
+                    }
+                },
+                || Some((target("cred-1"), async { Ok("hedge") })),
+            )
+            .await;
+
+        assert_eq!(race.result.unwrap(), (1, "hedge"));
+        // 落败的原始请求通过取消令牌取消，并在返回前完成清理
+        assert!(primary_token.is_cancelled());
+        assert!(cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(race.attempts.len(), 2);
+        assert_eq!(race.attempts[0].outcome, HedgeOutcome::Cancelled);
+        assert_eq!(race.attempts[0].credential_id.as_deref(), Some("cred-0"));
+        assert_eq!(race.attempts[0].error, None);
+        assert_eq!(race.attempts[1].outcome, HedgeOutcome::Won);
+        assert_eq!(race.attempts[1].credential_id.as_deref(), Some("cred-1"));
+        assert!(race.attempts[1].ttfb_ms.is_some());
+        assert_eq!(controller.sample_count("m"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_race_fast_primary_no_hedge() {
+        let controller = hedging_controller();
+        let race = controller
+            .race(
+                "m",
+                target("cred-0"),
+                async { Ok::<_, String>("primary") },
+                || -> Option<(HedgeTarget, std::future::Ready<Result<&str, String>>)> {
+                    panic!("原始请求及时完成时不应发出对冲请求")
+                },
+            )
+            .await;
+
+        assert_eq!(race.result.unwrap(), (0, "primary"));
+        assert!(race.attempts.is_empty());
+        assert_eq!(controller.sample_count("m"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_race_both_fail_returns_primary_error() {
+        let controller = hedging_controller();
+        let race = controller
+            .race(
+                "m",
+                target("cred-0"),
+                async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Err::<(), _>("primary failed".to_string())
+                },
+                || Some((target("cred-1"), async { Err("hedge failed".to_string()) })),
+            )
+            .await;
+
+        assert_eq!(race.result.unwrap_err(), "primary failed");
+        assert_eq!(race.attempts[0].outcome, HedgeOutcome::Failed);
+        assert_eq!(race.attempts[1].outcome, HedgeOutcome::Failed);
+        assert_eq!(race.attempts[1].error.as_deref(), Some("hedge failed"));
+        // 失败的尝试不计入 TTFB 样本
+        assert_eq!(controller.sample_count("m"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_race_disabled_records_no_samples() {
+        let controller = HedgeController::default();
+        let race = controller
+            .race(
+                "m",
+                target("cred-0"),
+                async { Ok::<_, String>(()) },
+                || -> Option<(HedgeTarget, std::future::Ready<Result<(), String>>)> { None },
+            )
+            .await;
+
+        assert!(race.result.is_ok());
+        assert_eq!(controller.sample_count("m"), 0);
+    }
+}
@@ -1,15 +1,26 @@
 //! 容错机制模块
 //!
-//! 提供重试、故障转移和超时控制功能
+//! 提供重试、故障转移、熔断、对冲请求、并发限制和超时控制功能
 
+mod circuit_breaker;
+mod concurrency;
 mod failover;
+mod hedge;
 mod retry;
 mod timeout;
 
+pub use circuit_breaker::{
+    CircuitBreakerRegistry, CircuitScope, CircuitSnapshot, CircuitState, CircuitTransition,
+};
+pub use concurrency::{
+    ConcurrencyLimiter, ConcurrencyPermit, ConcurrencySnapshot, QueueError, RequestPermit,
+    RequestPriority, PRIORITY_HEADER,
+};
 pub use failover::{
     Failover, FailoverConfig, FailoverManager, FailoverResult, FailureType, SwitchEvent,
     QUOTA_EXCEEDED_KEYWORDS, QUOTA_EXCEEDED_STATUS_CODES,
 };
+pub use hedge::{HedgeAttemptRecord, HedgeController, HedgeOutcome, HedgeRace, HedgeTarget};
 pub use retry::{Retrier, RetryConfig, RetryError};
 pub use timeout::{
     CancellationToken, StreamIdleDetector, StreamWithIdleTimeout, TimeoutConfig, TimeoutController,
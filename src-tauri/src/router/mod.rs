@@ -10,15 +10,22 @@
 //!
 //! 模型映射：
 //! - 支持模型别名映射（如 `gpt-4` -> `claude-sonnet-4-5-20250514`）
+//!
+//! 路由规则：
+//! - 按模型、客户端类型、请求特征和请求头声明式匹配，首个命中的规则决定 Provider 和模型改写
 
 mod amp_router;
 mod mapper;
 mod provider_router;
 mod route_registry;
+mod routing_rules;
 mod rules;
 
 pub use amp_router::{AmpRouteMatch, AmpRouter};
 pub use mapper::{ModelInfo, ModelMapper};
 pub use provider_router::ProviderRouter;
 pub use route_registry::{RegisteredRoute, RouteRegistry, RouteType};
+pub use routing_rules::{
+    glob_match, RequestFeatures, RouteExplainRequest, RouteExplanation, RoutingRule, RuleEvaluation,
+};
 pub use rules::{RouteResult, Router};
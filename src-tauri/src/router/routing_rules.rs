@@ -0,0 +1,468 @@
+//! 声明式路由规则
+//!
+//! 按配置顺序匹配请求特征（模型、客户端类型、工具 / 图片 / thinking、估算输入 Token、请求头），
+//! 首个命中的规则决定目标 Provider 和可选的模型改写。
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::RoutingRuleConfig;
+use crate::server::client_detector::ClientType;
+use crate::telemetry::AnthropicTokenCounter;
+
+/// 参与规则匹配的请求特征
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestFeatures {
+    /// 模型名（别名解析之后）
+    pub model: String,
+    /// 客户端类型
+    pub client_type: ClientType,
+    /// 是否携带工具定义
+    pub has_tools: bool,
+    /// 是否包含图片
+    pub has_images: bool,
+    /// 是否启用 thinking / reasoning
+    pub thinking: bool,
+    /// 估算输入 Token 数
+    pub estimated_input_tokens: u32,
+    /// 请求头（名称为小写）
+    pub headers: HashMap<String, String>,
+}
+
+impl RequestFeatures {
+    /// 创建只包含模型和客户端类型的请求特征
+    pub fn new(model: impl Into<String>, client_type: ClientType) -> Self {
+        Self {
+            model: model.into(),
+            client_type,
+            has_tools: false,
+            has_images: false,
+            thinking: false,
+            estimated_input_tokens: 0,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// 添加请求头
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .insert(name.to_ascii_lowercase(), value.to_string());
+        self
+    }
+
+    /// 从 OpenAI / Anthropic 格式的请求体中提取特征
+    ///
+    /// 输入 Token 使用与 `/v1/messages/count_tokens` 相同的计数器估算，
+    /// 保证 `min_input_tokens` / `max_input_tokens` 条件与计数端点的结果一致
+    pub fn with_body(mut self, body: &Value, token_counter: &AnthropicTokenCounter) -> Self {
+        self.has_tools = non_empty_array(&body["tools"]) || non_empty_array(&body["functions"]);
+        self.has_images = body["messages"]
+            .as_array()
+            .is_some_and(|messages| messages.iter().any(|m| contains_image(&m["content"])));
+        self.thinking = thinking_enabled(body);
+        self.estimated_input_tokens = token_counter.count_request(body).input_tokens;
+        self
+    }
+}
+
+/// 编译后的路由规则
+#[derive(Debug, Clone)]
+pub struct RoutingRule {
+    config: RoutingRuleConfig,
+    client_types: Vec<ClientType>,
+}
+
+impl RoutingRule {
+    /// 从配置创建规则，配置无效时返回错误
+    pub fn from_config(config: RoutingRuleConfig) -> Result<Self, String> {
+        if config.provider.trim().is_empty() {
+            return Err(format!("路由规则 '{}' 未指定 provider", config.name));
+        }
+        if let (Some(min), Some(max)) = (config.when.min_input_tokens, config.when.max_input_tokens)
+        {
+            if min > max {
+                return Err(format!(
+                    "路由规则 '{}' 的 min_input_tokens ({}) 大于 max_input_tokens ({})",
+                    config.name, min, max
+                ));
+            }
+        }
+        let client_types = config
+            .when
+            .client_types
+            .iter()
+            .map(|key| {
+                ClientType::from_config_key(&key.to_ascii_lowercase()).ok_or_else(|| {
+                    format!("路由规则 '{}' 包含未知的客户端类型: {}", config.name, key)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            config,
+            client_types,
+        })
+    }
+
+    /// 规则名称
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// 目标 Provider
+    pub fn provider(&self) -> &str {
+        &self.config.provider
+    }
+
+    /// 模型改写
+    pub fn model(&self) -> Option<&str> {
+        self.config.model.as_deref()
+    }
+
+    /// 检查请求是否命中规则，未命中时返回第一个不满足的条件
+    pub fn check(&self, features: &RequestFeatures) -> Result<(), String> {
+        let when = &self.config.when;
+
+        if !self.config.enabled {
+            return Err("规则已禁用".to_string());
+        }
+        if !when.models.is_empty()
+            && !when
+                .models
+                .iter()
+                .any(|pattern| glob_match(pattern, &features.model))
+        {
+            return Err(format!("模型 {} 不匹配 {:?}", features.model, when.models));
+        }
+        if !self.client_types.is_empty() && !self.client_types.contains(&features.client_type) {
+            return Err(format!(
+                "客户端类型 {} 不在 {:?} 中",
+                features.client_type.config_key(),
+                when.client_types
+            ));
+        }
+        check_flag("has_tools", when.has_tools, features.has_tools)?;
+        check_flag("has_images", when.has_images, features.has_images)?;
+        check_flag("thinking", when.thinking, features.thinking)?;
+        if let Some(min) = when.min_input_tokens {
+            if features.estimated_input_tokens < min {
+                return Err(format!(
+                    "估算输入 Token {} 小于 {}",
+                    features.estimated_input_tokens, min
+                ));
+            }
+        }
+        if let Some(max) = when.max_input_tokens {
+            if features.estimated_input_tokens > max {
+                return Err(format!(
+                    "估算输入 Token {} 大于 {}",
+                    features.estimated_input_tokens, max
+                ));
+            }
+        }
+        for (name, pattern) in &when.headers {
+            let name = name.to_ascii_lowercase();
+            match features.headers.get(&name) {
+                Some(value) if glob_match(pattern, value) => {}
+                Some(value) => {
+                    return Err(format!("请求头 {}={} 不匹配 {}", name, value, pattern));
+                }
+                None => return Err(format!("缺少请求头 {}", name)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 单条规则的匹配结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleEvaluation {
+    /// 规则在配置中的位置（从 0 开始）
+    pub index: usize,
+    pub name: String,
+    pub matched: bool,
+    /// 未命中原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// 路由 dry-run 结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteExplanation {
+    /// 参与匹配的请求特征
+    pub features: RequestFeatures,
+    /// 依次评估的规则（命中后不再评估后续规则）
+    pub evaluations: Vec<RuleEvaluation>,
+    /// 命中的规则名称
+    pub matched_rule: Option<String>,
+    /// 最终 Provider（未命中规则时为默认 Provider）
+    pub provider: Option<String>,
+    /// 最终模型名
+    pub model: String,
+}
+
+impl RouteExplanation {
+    /// 未命中任何规则时使用调用方解析出的回退 Provider（端点 Provider 或自定义 Provider ID）
+    pub fn with_fallback_provider(mut self, provider: &str) -> Self {
+        if self.matched_rule.is_none() && !provider.is_empty() {
+            self.provider = Some(provider.to_string());
+        }
+        self
+    }
+}
+
+/// 路由 dry-run 请求
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RouteExplainRequest {
+    /// 请求的模型名（会先做别名解析）
+    pub model: String,
+    /// 客户端类型配置键（如 `claude_code`），未指定时从 `User-Agent` 请求头识别
+    #[serde(default)]
+    pub client_type: Option<String>,
+    /// 请求头
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 完整请求体，用于检测工具、图片、thinking 和估算输入 Token
+    #[serde(default)]
+    pub body: Option<Value>,
+}
+
+impl RouteExplainRequest {
+    /// 构建请求特征
+    pub fn features(
+        &self,
+        resolved_model: &str,
+        token_counter: &AnthropicTokenCounter,
+    ) -> Result<RequestFeatures, String> {
+        let client_type = match &self.client_type {
+            Some(key) => ClientType::from_config_key(&key.to_ascii_lowercase())
+                .ok_or_else(|| format!("未知的客户端类型: {}", key))?,
+            None => ClientType::from_user_agent(
+                self.headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+                    .map(|(_, value)| value.as_str())
+                    .unwrap_or(""),
+            ),
+        };
+
+        let mut features = RequestFeatures::new(resolved_model, client_type);
+        if let Some(body) = &self.body {
+            features = features.with_body(body, token_counter);
+        }
+        for (name, value) in &self.headers {
+            features = features.with_header(name, value);
+        }
+        Ok(features)
+    }
+}
+
+fn check_flag(name: &str, expected: Option<bool>, actual: bool) -> Result<(), String> {
+    match expected {
+        Some(expected) if expected != actual => {
+            Err(format!("{} 要求为 {}，实际为 {}", name, expected, actual))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn non_empty_array(value: &Value) -> bool {
+    value.as_array().is_some_and(|items| !items.is_empty())
+}
+
+fn is_image_block(block: &Value) -> bool {
+    matches!(
+        block["type"].as_str(),
+        Some("image") | Some("image_url") | Some("input_image")
+    )
+}
+
+fn contains_image(content: &Value) -> bool {
+    content.as_array().is_some_and(|blocks| {
+        blocks
+            .iter()
+            .any(|block| is_image_block(block) || contains_image(&block["content"]))
+    })
+}
+
+fn thinking_enabled(body: &Value) -> bool {
+    // Anthropic: {"thinking": {"type": "enabled"}}
+    if body["thinking"]["type"].as_str() == Some("enabled") {
+        return true;
+    }
+    // OpenAI: reasoning_effort / reasoning.effort
+    let effort = body["reasoning_effort"]
+        .as_str()
+        .or_else(|| body["reasoning"]["effort"].as_str());
+    matches!(effort, Some(e) if !e.eq_ignore_ascii_case("none"))
+}
+
+/// 通配符匹配（不区分大小写，`*` 匹配任意字符串，`?` 匹配单个字符）
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = star {
+            p = star_p + 1;
+            v = star_v + 1;
+            star = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RoutingRuleConditions;
+    use serde_json::json;
+
+    fn rule(name: &str, when: RoutingRuleConditions) -> RoutingRuleConfig {
+        RoutingRuleConfig {
+            name: name.to_string(),
+            enabled: true,
+            when,
+            provider: "gemini".to_string(),
+            model: None,
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("claude-*", "claude-sonnet-4"));
+        assert!(glob_match("*sonnet*", "Claude-Sonnet-4"));
+        assert!(glob_match("gpt-4?", "gpt-4o"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("gpt-4?", "gpt-4"));
+        assert!(!glob_match("claude-*-4", "claude-sonnet-3"));
+    }
+
+    #[test]
+    fn test_features_from_body() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "system": "You are helpful.",
+            "thinking": {"type": "enabled", "budget_tokens": 1024},
+            "tools": [{"name": "search", "input_schema": {"type": "object"}}],
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Describe this image"},
+                    {"type": "image", "source": {"type": "base64", "data": "aGVsbG8gd29ybGQ="}}
+                ]
+            }]
+        });
+        let counter = AnthropicTokenCounter::new().unwrap();
+        let features = RequestFeatures::new("claude-sonnet-4", ClientType::ClaudeCode)
+            .with_body(&body, &counter)
+            .with_header("X-Team", "research");
+
+        assert!(features.has_tools);
+        assert!(features.has_images);
+        assert!(features.thinking);
+        // 与 count_tokens 端点的计数一致
+        assert_eq!(
+            features.estimated_input_tokens,
+            counter.count_request(&body).input_tokens
+        );
+        assert_eq!(features.headers.get("x-team").unwrap(), "research");
+
+        let openai = json!({
+            "messages": [{"role": "user", "content": "hi"}],
+            "reasoning_effort": "none"
+        });
+        let features =
+            RequestFeatures::new("gpt-4o", ClientType::Other).with_body(&openai, &counter);
+        assert!(!features.has_tools && !features.has_images && !features.thinking);
+    }
+
+    #[test]
+    fn test_rule_conditions() {
+        let rule = RoutingRule::from_config(rule(
+            "long-context",
+            RoutingRuleConditions {
+                models: vec!["claude-*".to_string()],
+                client_types: vec!["claude_code".to_string()],
+                has_tools: Some(true),
+                min_input_tokens: Some(10),
+                headers: HashMap::from([("X-Team".to_string(), "research*".to_string())]),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+        let mut features = RequestFeatures::new("claude-opus-4", ClientType::ClaudeCode)
+            .with_header("x-team", "research-lab");
+        features.has_tools = true;
+        features.estimated_input_tokens = 20;
+        assert!(rule.check(&features).is_ok());
+
+        features.estimated_input_tokens = 5;
+        assert!(rule.check(&features).unwrap_err().contains("Token"));
+
+        features.estimated_input_tokens = 20;
+        features.client_type = ClientType::Cursor;
+        assert!(rule.check(&features).unwrap_err().contains("cursor"));
+
+        features.client_type = ClientType::ClaudeCode;
+        features.headers.clear();
+        assert!(rule.check(&features).unwrap_err().contains("x-team"));
+    }
+
+    #[test]
+    fn test_explain_request_features() {
+        let request: RouteExplainRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "headers": {"User-Agent": "claude-code/1.0.0", "X-Team": "a"},
+            "body": {"tools": [{"type": "function"}]}
+        }))
+        .unwrap();
+        let counter = AnthropicTokenCounter::new().unwrap();
+        let features = request.features("gpt-4o", &counter).unwrap();
+        assert_eq!(features.client_type, ClientType::ClaudeCode);
+        assert!(features.has_tools);
+        assert_eq!(features.headers.get("x-team").unwrap(), "a");
+
+        let request = RouteExplainRequest {
+            client_type: Some("emacs".to_string()),
+            ..Default::default()
+        };
+        assert!(request.features("gpt-4o", &counter).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let mut config = rule(
+            "bad-client",
+            RoutingRuleConditions {
+                client_types: vec!["vim".to_string()],
+                ..Default::default()
+            },
+        );
+        assert!(RoutingRule::from_config(config.clone()).is_err());
+
+        config.when = RoutingRuleConditions {
+            min_input_tokens: Some(100),
+            max_input_tokens: Some(10),
+            ..Default::default()
+        };
+        assert!(RoutingRule::from_config(config.clone()).is_err());
+
+        config.when = RoutingRuleConditions::default();
+        config.provider = " ".to_string();
+        assert!(RoutingRule::from_config(config).is_err());
+    }
+}
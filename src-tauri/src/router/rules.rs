@@ -1,7 +1,9 @@
 //! 路由器
 //!
-//! 简化的路由器，直接使用用户配置的默认 Provider
+//! 简化的路由器，按配置顺序匹配声明式路由规则，未命中时使用用户配置的默认 Provider
 
+use super::routing_rules::{RequestFeatures, RouteExplanation, RoutingRule, RuleEvaluation};
+use crate::config::RoutingRuleConfig;
 use crate::ProviderType;
 
 /// 路由结果
@@ -11,13 +13,21 @@ pub struct RouteResult {
     pub provider: Option<ProviderType>,
     /// 是否使用默认 Provider
     pub is_default: bool,
+    /// 命中的路由规则名称
+    pub rule: Option<String>,
+    /// 命中规则指定的 Provider ID（可能是自定义 Provider ID）
+    pub provider_id: Option<String>,
+    /// 命中规则指定的模型改写
+    pub model: Option<String>,
 }
 
-/// 路由器 - 根据默认 Provider 路由请求
+/// 路由器 - 根据路由规则和默认 Provider 路由请求
 #[derive(Debug, Clone)]
 pub struct Router {
     /// 默认 Provider（可选，未设置时为 None）
     default_provider: Option<ProviderType>,
+    /// 声明式路由规则（按顺序匹配，首个命中生效）
+    rules: Vec<RoutingRule>,
 }
 
 impl Router {
@@ -25,6 +35,7 @@ impl Router {
     pub fn new(default_provider: ProviderType) -> Self {
         Self {
             default_provider: Some(default_provider),
+            rules: Vec::new(),
         }
     }
 
@@ -32,6 +43,7 @@ impl Router {
     pub fn new_empty() -> Self {
         Self {
             default_provider: None,
+            rules: Vec::new(),
         }
     }
 
@@ -50,6 +62,27 @@ impl Router {
         self.default_provider.is_some()
     }
 
+    /// 替换路由规则
+    ///
+    /// 无效的规则会被跳过，返回每条被跳过规则的错误信息
+    pub fn set_rules(&mut self, rules: &[RoutingRuleConfig]) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.rules = rules
+            .iter()
+            .filter_map(|config| {
+                RoutingRule::from_config(config.clone())
+                    .map_err(|e| errors.push(e))
+                    .ok()
+            })
+            .collect();
+        errors
+    }
+
+    /// 获取已加载的路由规则
+    pub fn rules(&self) -> &[RoutingRule] {
+        &self.rules
+    }
+
     /// 路由请求到 Provider
     ///
     /// 返回默认 Provider，如果未设置则返回 None
@@ -57,6 +90,57 @@ impl Router {
         RouteResult {
             provider: self.default_provider,
             is_default: true,
+            rule: None,
+            provider_id: None,
+            model: None,
+        }
+    }
+
+    /// 根据请求特征路由
+    ///
+    /// 按顺序匹配路由规则，首个命中的规则生效；均未命中时返回默认 Provider
+    pub fn route_request(&self, features: &RequestFeatures) -> RouteResult {
+        match self.rules.iter().find(|rule| rule.check(features).is_ok()) {
+            Some(rule) => RouteResult {
+                provider: rule.provider().parse::<ProviderType>().ok(),
+                is_default: false,
+                rule: Some(rule.name().to_string()),
+                provider_id: Some(rule.provider().to_string()),
+                model: rule.model().map(str::to_string),
+            },
+            None => self.route(&features.model),
+        }
+    }
+
+    /// 解释路由决策（dry-run），列出每条规则的匹配结果
+    pub fn explain(&self, features: &RequestFeatures) -> RouteExplanation {
+        let mut evaluations = Vec::new();
+        let mut matched = None;
+        for (index, rule) in self.rules.iter().enumerate() {
+            let result = rule.check(features);
+            evaluations.push(RuleEvaluation {
+                index,
+                name: rule.name().to_string(),
+                matched: result.is_ok(),
+                reason: result.err(),
+            });
+            if evaluations[index].matched {
+                matched = Some(rule);
+                break;
+            }
+        }
+
+        RouteExplanation {
+            features: features.clone(),
+            evaluations,
+            matched_rule: matched.map(|rule| rule.name().to_string()),
+            provider: matched
+                .map(|rule| rule.provider().to_string())
+                .or_else(|| self.default_provider.map(|p| p.to_string())),
+            model: matched
+                .and_then(|rule| rule.model())
+                .unwrap_or(&features.model)
+                .to_string(),
         }
     }
 }
@@ -70,6 +154,8 @@ impl Default for Router {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::RoutingRuleConditions;
+    use crate::server::client_detector::ClientType;
 
     #[test]
     fn test_new_router() {
@@ -106,6 +192,78 @@ mod tests {
         assert!(result.is_default);
     }
 
+    fn rule(name: &str, models: &[&str], provider: &str, model: Option<&str>) -> RoutingRuleConfig {
+        RoutingRuleConfig {
+            name: name.to_string(),
+            enabled: true,
+            when: RoutingRuleConditions {
+                models: models.iter().map(|m| m.to_string()).collect(),
+                ..Default::default()
+            },
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_route_request_first_match_wins() {
+        let mut router = Router::new(ProviderType::Kiro);
+        let errors = router.set_rules(&[
+            rule("opus-to-claude", &["claude-opus-*"], "claude", None),
+            rule(
+                "all-claude",
+                &["claude-*"],
+                "gemini",
+                Some("gemini-2.5-pro"),
+            ),
+        ]);
+        assert!(errors.is_empty());
+
+        let features = RequestFeatures::new("claude-opus-4", ClientType::Other);
+        let result = router.route_request(&features);
+        assert_eq!(result.rule.as_deref(), Some("opus-to-claude"));
+        assert_eq!(result.provider, Some(ProviderType::Claude));
+        assert_eq!(result.model, None);
+        assert!(!result.is_default);
+
+        let features = RequestFeatures::new("claude-sonnet-4", ClientType::Other);
+        let result = router.route_request(&features);
+        assert_eq!(result.rule.as_deref(), Some("all-claude"));
+        assert_eq!(result.model.as_deref(), Some("gemini-2.5-pro"));
+
+        let features = RequestFeatures::new("gpt-4o", ClientType::Other);
+        let result = router.route_request(&features);
+        assert_eq!(result.rule, None);
+        assert_eq!(result.provider, Some(ProviderType::Kiro));
+        assert!(result.is_default);
+    }
+
+    #[test]
+    fn test_explain_and_invalid_rules() {
+        let mut router = Router::new(ProviderType::Kiro);
+        let mut disabled = rule("disabled", &["*"], "openai", None);
+        disabled.enabled = false;
+        let errors = router.set_rules(&[
+            disabled,
+            rule("no-provider", &["*"], "", None),
+            rule("custom", &["gpt-*"], "custom-123", Some("my-model")),
+            rule("never-reached", &["*"], "claude", None),
+        ]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(router.rules().len(), 3);
+
+        let explanation = router.explain(&RequestFeatures::new("gpt-4o", ClientType::Cursor));
+        assert_eq!(explanation.evaluations.len(), 2);
+        assert!(!explanation.evaluations[0].matched);
+        assert_eq!(explanation.matched_rule.as_deref(), Some("custom"));
+        assert_eq!(explanation.provider.as_deref(), Some("custom-123"));
+        assert_eq!(explanation.model, "my-model");
+
+        let result = router.route_request(&RequestFeatures::new("gpt-4o", ClientType::Cursor));
+        assert_eq!(result.provider, None);
+        assert_eq!(result.provider_id.as_deref(), Some("custom-123"));
+    }
+
     #[test]
     fn test_set_default_provider() {
         let mut router = Router::new_empty();
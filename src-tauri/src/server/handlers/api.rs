@@ -23,10 +23,18 @@ use axum::{
     Json,
 };
 use chrono::Utc;
+use futures::StreamExt;
 use serde_json::json;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use subtle::ConstantTimeEq;
+use tokio_util::sync::CancellationToken;
 
+use crate::config::EquivalentModel;
 use crate::converter::anthropic_to_openai::convert_anthropic_to_openai;
+use crate::database::dao::provider_pool::ProviderPoolDao;
+use crate::flow_monitor::playback::synthesize_body;
 use crate::flow_monitor::{
     ClientInfo, FlowError, FlowErrorType, FlowMetadata, FlowType, InterceptAction, InterceptType,
     LLMFlow, LLMRequest, LLMResponse, Message, MessageContent, MessageRole, RequestParameters,
@@ -34,17 +42,31 @@ use crate::flow_monitor::{
 };
 use crate::models::anthropic::AnthropicMessagesRequest;
 use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::{PoolProviderType, ProviderCredential};
 use crate::processor::RequestContext;
+use crate::resilience::{HedgeTarget, RequestPermit, RequestPriority, PRIORITY_HEADER};
+use crate::router::{RequestFeatures, RouteResult};
 use crate::server::client_detector::ClientType;
-use crate::server::{record_request_telemetry, record_token_usage, AppState};
+use crate::server::{
+    record_request_telemetry, record_token_usage_with_cache, record_token_usage_with_source,
+    start_request_trace, trace_stream_response, AppState,
+};
 use crate::server_utils::{
     build_anthropic_response, build_anthropic_stream_response, message_content_len,
     parse_cw_response, safe_truncate,
 };
+use crate::services::budget_service::{BudgetDecision, BudgetExceeded, BudgetSubject};
+use crate::services::client_key_service::{ClientIdentity, ClientKeyError};
 use crate::streaming::StreamFormat as StreamingFormat;
+use crate::telemetry::{current_span_child, in_span, SpanGuard, SpanKind};
 use crate::ProviderType;
 
-use super::{call_provider_anthropic, call_provider_openai};
+use super::response_cache::extract_json_content;
+use super::usage_meter::{meter_response_usage, UsageMeter};
+use super::{
+    cache_upstream_response, call_provider_anthropic, call_provider_openai, create_cancel_token,
+    lookup_playback, lookup_response_cache, CacheLookup, PlaybackLookup, ResumeScope,
+};
 
 // ============================================================================
 // Flow 捕获辅助函数
@@ -272,7 +294,7 @@ fn build_flow_metadata(
     credential_id: Option<&str>,
     credential_name: Option<&str>,
     headers: &HeaderMap,
-    request_id: &str,
+    ctx: &RequestContext,
 ) -> FlowMetadata {
     // 提取客户端信息
     let client_ip = headers
@@ -295,11 +317,18 @@ fn build_flow_metadata(
         client_info: ClientInfo {
             ip: client_ip,
             user_agent,
-            request_id: Some(request_id.to_string()),
+            request_id: Some(ctx.request_id.clone()),
+            client_key_id: ctx.client_key.as_ref().map(|c| c.id.clone()),
+            client_key_label: ctx.client_key.as_ref().map(|c| c.label.clone()),
         },
         routing_info: RoutingInfo::default(),
         injected_params: None,
         context_usage_percentage: None,
+        cached: false,
+        hedge_attempts: Vec::new(),
+        recording_session: ctx.recording_session.clone(),
+        playback_of: None,
+        conversation: None,
     }
 }
 
@@ -348,7 +377,10 @@ fn build_llm_response(status_code: u16, content: &str, usage: Option<(u32, u32)>
 ///
 /// # 返回
 /// 选择的 Provider 名称和检测到的客户端类型
-async fn select_provider_for_client(headers: &HeaderMap, state: &AppState) -> (String, ClientType) {
+pub(crate) async fn select_provider_for_client(
+    headers: &HeaderMap,
+    state: &AppState,
+) -> (String, ClientType) {
     // 从 User-Agent 检测客户端类型
     let user_agent = headers
         .get("user-agent")
@@ -372,6 +404,63 @@ async fn select_provider_for_client(headers: &HeaderMap, state: &AppState) -> (S
     (selected_provider, client_type)
 }
 
+/// 匹配声明式路由规则
+///
+/// 命中时记录 `[ROUTE_RULE]` 日志并返回路由结果（Provider ID 和可选的模型改写），
+/// 未配置规则或均未命中时返回 None，由调用方回退到端点 / 默认 Provider。
+pub(crate) async fn match_routing_rule<T: serde::Serialize>(
+    state: &AppState,
+    headers: &HeaderMap,
+    ctx: &RequestContext,
+    client_type: ClientType,
+    request: &T,
+) -> Option<RouteResult> {
+    let router = state.processor.router.read().await;
+    if router.rules().is_empty() {
+        return None;
+    }
+
+    let features = request_features(state, headers, &ctx.resolved_model, client_type, request);
+    let result = router.route_request(&features);
+    drop(router);
+
+    let (Some(rule), Some(provider_id)) = (&result.rule, &result.provider_id) else {
+        return None;
+    };
+    if let Some(trace) = &ctx.trace {
+        trace.set_attribute("proxycast.routing_rule", rule.as_str());
+    }
+    state.logs.write().await.add(
+        "info",
+        &format!(
+            "[ROUTE_RULE] request_id={} rule={} provider={} model={}",
+            ctx.request_id,
+            rule,
+            provider_id,
+            result.model.as_deref().unwrap_or(&ctx.resolved_model)
+        ),
+    );
+    Some(result)
+}
+
+/// 从请求头和请求体提取路由规则所需的请求特征
+pub(crate) fn request_features<T: serde::Serialize>(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: &str,
+    client_type: ClientType,
+    request: &T,
+) -> RequestFeatures {
+    let body = serde_json::to_value(request).unwrap_or_default();
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .fold(
+            RequestFeatures::new(model, client_type).with_body(&body, &state.token_counter),
+            |features, (name, value)| features.with_header(name, value),
+        )
+}
+
 // ============================================================================
 // 拦截检查辅助函数
 // ============================================================================
@@ -382,6 +471,482 @@ pub enum InterceptCheckResult {
     Continue(Option<LLMRequest>),
     /// 请求被取消
     Cancelled,
+    /// 自动拦截规则直接返回了响应（Flow 已结束）
+    Responded(Response),
+}
+
+/// 开启上游 Provider 调用的 client span
+fn provider_call_span(ctx: &RequestContext, model: &str, credential_id: &str) -> SpanGuard {
+    let mut span = ctx.span("proxycast.provider.call");
+    span.set_kind(SpanKind::Client);
+    span.set_attribute("gen_ai.request.model", model);
+    span.set_attribute("proxycast.credential_id", credential_id);
+    span
+}
+
+/// 根据上游响应状态结束 Provider 调用 span
+fn finish_provider_call_span(mut span: SpanGuard, status: StatusCode) {
+    span.set_attribute("http.response.status_code", status.as_u16() as i64);
+    if status.is_success() {
+        span.set_ok();
+    } else {
+        span.set_error(&format!("HTTP {}", status.as_u16()));
+    }
+}
+
+/// 在当前 span（Provider 调用 span）下开启一次上游尝试的 span
+fn provider_attempt_span(credential: &ProviderCredential, model: &str, hedge: bool) -> SpanGuard {
+    let mut span = current_span_child("proxycast.provider.attempt");
+    span.set_attribute("proxycast.credential_id", credential.uuid.as_str());
+    span.set_attribute("gen_ai.request.model", model);
+    span.set_attribute("proxycast.hedge", hedge);
+    span
+}
+
+/// 在尝试 span 内执行上游调用，调用内部的重试和续传挂到该 span 下
+async fn traced_attempt(span: SpanGuard, call: impl Future<Output = Response>) -> Response {
+    let response = in_span(&span, call).await;
+    finish_provider_call_span(span, response.status());
+    response
+}
+
+/// 对冲请求中失败的一次尝试，保留上游响应以便全部失败时原样返回
+struct FailedAttempt(Response);
+
+impl std::fmt::Display for FailedAttempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}", self.0.status().as_u16())
+    }
+}
+
+/// 对冲中的一次上游尝试：获取并发许可、调用上游并等待首个 chunk
+///
+/// 尝试落败时取消令牌被触发，上游调用随之被丢弃（关闭上游连接），并在返回前完成取消清理：
+/// 释放该尝试持有的并发许可；上游调用尚未完成（结果还没有计入凭证健康状态）时归还
+/// 选择凭证时占用的熔断器探测名额；在请求日志中为该凭证记录一次已取消的请求
+async fn run_hedge_attempt(
+    state: &AppState,
+    ctx: &RequestContext,
+    target: (&ProviderCredential, &str),
+    cancel_token: CancellationToken,
+    permit: impl Future<Output = Result<Option<RequestPermit>, Response>>,
+    call: impl Future<Output = Response>,
+) -> Result<(Response, Option<RequestPermit>), FailedAttempt> {
+    let (credential, model) = target;
+    let called = AtomicBool::new(false);
+    let attempt = async {
+        let permit = permit.await.map_err(FailedAttempt)?;
+        let response = call.await;
+        called.store(true, Ordering::SeqCst);
+        Ok((await_first_chunk(response).await?, permit))
+    };
+    tokio::select! {
+        biased;
+        _ = cancel_token.cancelled() => {}
+        result = attempt => return result,
+    }
+
+    if !called.load(Ordering::SeqCst) {
+        state.pool_service.release_selection(&credential.uuid);
+    }
+    let mut attempt_ctx = ctx.clone();
+    // 落败的尝试不写入请求的根 span
+    attempt_ctx.trace = None;
+    attempt_ctx.set_provider(credential.provider_type);
+    attempt_ctx.set_credential_id(credential.uuid.clone());
+    attempt_ctx.set_resolved_model(model.to_string());
+    record_request_telemetry(
+        state,
+        &attempt_ctx,
+        crate::telemetry::RequestStatus::Cancelled,
+        None,
+    );
+    tracing::info!(
+        "[HEDGE] 已取消落败的尝试 credential={}",
+        &credential.uuid[..8.min(credential.uuid.len())]
+    );
+    Err(FailedAttempt(
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": {"message": "Hedge attempt cancelled"}})),
+        )
+            .into_response(),
+    ))
+}
+
+/// 等待响应体的首个 chunk（即首个 token），再与剩余响应体重新组装为响应
+///
+/// 非 2xx 响应或首个 chunk 读取失败视为本次尝试失败
+async fn await_first_chunk(response: Response) -> Result<Response, FailedAttempt> {
+    if !response.status().is_success() {
+        return Err(FailedAttempt(response));
+    }
+    let (parts, body) = response.into_parts();
+    let mut stream = body.into_data_stream();
+    match stream.next().await {
+        Some(Ok(first)) => {
+            let body = futures::stream::once(async move { Ok(first) }).chain(stream);
+            Ok(Response::from_parts(parts, Body::from_stream(body)))
+        }
+        Some(Err(e)) => Err(FailedAttempt(
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": {"message": e.to_string()}})),
+            )
+                .into_response(),
+        )),
+        None => Ok(Response::from_parts(parts, Body::empty())),
+    }
+}
+
+/// 选择对冲请求使用的凭证
+///
+/// 优先同 Provider 的其他空闲凭证，其次模型等价组中其他成员的空闲凭证；
+/// 返回凭证和该凭证应使用的模型
+fn select_hedge_credential(
+    state: &AppState,
+    primary: &ProviderCredential,
+    model: &str,
+) -> Option<(ProviderCredential, String)> {
+    let db = state.db.as_ref()?;
+    // 对冲同样不使用超出凭证预算的凭证
+    let mut exclude = vec![primary.uuid.clone()];
+    exclude.extend(
+        state
+            .processor
+            .budget
+            .over_budget_credentials(model)
+            .into_iter()
+            .map(|(id, _)| id),
+    );
+    let provider = primary.provider_type.to_string();
+    let concurrency = state.pool_service.concurrency();
+    let mut candidates = vec![(provider.clone(), model.to_string())];
+    candidates.extend(
+        state
+            .processor
+            .fallback
+            .read()
+            .equivalents(&provider, model)
+            .into_iter()
+            .map(|member| (member.provider, member.model)),
+    );
+    // 在选择前排除并发已满的凭证，避免选中后丢弃的凭证占用熔断器的探测名额
+    candidates.into_iter().find_map(|(provider, model)| {
+        let credential = state
+            .pool_service
+            .select_credential_matching(db, &provider, Some(&model), &exclude, |c| {
+                !concurrency.is_saturated(&c.uuid)
+            })
+            .ok()
+            .flatten()?;
+        Some((credential, model))
+    })
+}
+
+/// 调用上游 Provider，首个 token 迟迟未到时发出对冲请求
+///
+/// 每次尝试（含对冲）在当前 span 下记录为 `proxycast.provider.attempt`，被取消的一方
+/// 没有状态码。对冲只用于流式请求：非流式响应在上游生成完毕后才返回响应体，无法按首个 token
+/// 判断胜负。对冲启用且覆盖该 Provider 时，等待响应的首个 chunk 并记录 TTFB；超过该模型近期 TTFB
+/// 分位数仍未产生首个 chunk 时，用 [`select_hedge_credential`] 选出的凭证发出对冲请求，
+/// 先产生首个 chunk 的一方胜出，另一方通过取消令牌取消（见 [`run_hedge_attempt`]）。
+/// 两次尝试记录到 Flow 元数据。
+///
+/// `permit` 为原始请求已持有的并发许可；对冲请求发出前用 `acquire` 为对冲凭证获取许可，
+/// 获取失败视为对冲尝试失败。返回响应、实际响应请求的凭证和模型，以及胜出方的并发许可
+#[allow(clippy::too_many_arguments)]
+async fn call_provider_hedged<F, Fut, A, AF>(
+    state: &AppState,
+    ctx: &RequestContext,
+    credential: ProviderCredential,
+    permit: Option<RequestPermit>,
+    model: &str,
+    flow_id: Option<&str>,
+    acquire: A,
+    call: F,
+) -> (Response, ProviderCredential, String, Option<RequestPermit>)
+where
+    F: Fn(ProviderCredential, String) -> Fut,
+    Fut: Future<Output = Response>,
+    A: FnOnce(ProviderCredential) -> AF,
+    AF: Future<Output = Result<Option<RequestPermit>, Response>>,
+{
+    let hedge = &state.processor.hedge;
+    if !ctx.is_stream || !hedge.applies_to(credential.provider_type) {
+        let span = provider_attempt_span(&credential, model, false);
+        let response = traced_attempt(span, call(credential.clone(), model.to_string())).await;
+        return (response, credential, model.to_string(), permit);
+    }
+
+    let primary_target = HedgeTarget {
+        provider: credential.provider_type,
+        credential_id: Some(credential.uuid.clone()),
+        cancel_token: create_cancel_token(),
+    };
+    let call = &call;
+    let primary = {
+        let span = provider_attempt_span(&credential, model, false);
+        let response = Box::pin(traced_attempt(
+            span,
+            call(credential.clone(), model.to_string()),
+        ));
+        let attempt = run_hedge_attempt(
+            state,
+            ctx,
+            (&credential, model),
+            primary_target.cancel_token.clone(),
+            std::future::ready(Ok(permit)),
+            response,
+        );
+        Box::pin(async move {
+            let (response, permit) = attempt.await?;
+            Ok::<_, FailedAttempt>((response, None, permit))
+        })
+    };
+    let hedge_attempt = || {
+        let (hedge_credential, hedge_model) = select_hedge_credential(state, &credential, model)?;
+        let target = HedgeTarget {
+            provider: hedge_credential.provider_type,
+            credential_id: Some(hedge_credential.uuid.clone()),
+            cancel_token: create_cancel_token(),
+        };
+        let cancel_token = target.cancel_token.clone();
+        let span = provider_attempt_span(&hedge_credential, &hedge_model, true);
+        let hedge_permit = acquire(hedge_credential.clone());
+        let response = Box::pin(traced_attempt(
+            span,
+            call(hedge_credential.clone(), hedge_model.clone()),
+        ));
+        Some((
+            target,
+            Box::pin(async move {
+                // 许可获取失败时上游调用不会发出，同样归还探测名额
+                let (response, hedge_permit) = run_hedge_attempt(
+                    state,
+                    ctx,
+                    (&hedge_credential, &hedge_model),
+                    cancel_token,
+                    hedge_permit,
+                    response,
+                )
+                .await?;
+                Ok::<_, FailedAttempt>((
+                    response,
+                    Some((hedge_credential, hedge_model)),
+                    hedge_permit,
+                ))
+            }),
+        ))
+    };
+    // 上游调用、两次尝试和竞速本身都放到堆上：它们内联了两份完整的上游调用，
+    // 直接内联会让处理器 future 过大，调试构建下超出工作线程的栈
+    let race = Box::pin(hedge.race(model, primary_target, primary, hedge_attempt)).await;
+
+    if let (false, Some(fid)) = (race.attempts.is_empty(), flow_id) {
+        state
+            .flow_monitor
+            .record_hedge_attempts(fid, race.attempts)
+            .await;
+    }
+
+    // 每次尝试持有自己凭证的许可，落败或失败一方的许可已随其尝试释放
+    match race.result {
+        Ok((_, (response, Some((hedge_credential, hedge_model)), hedge_permit))) => {
+            (response, hedge_credential, hedge_model, hedge_permit)
+        }
+        Ok((_, (response, None, permit))) => (response, credential, model.to_string(), permit),
+        Err(FailedAttempt(response)) => (response, credential, model.to_string(), None),
+    }
+}
+
+/// 实际响应请求的 Provider 响应头
+pub const SERVED_PROVIDER_HEADER: &str = "x-proxycast-provider";
+/// 实际响应请求的模型响应头
+pub const SERVED_MODEL_HEADER: &str = "x-proxycast-model";
+
+/// 按模型等价组为请求选择其他 Provider 的凭证，并记录降级
+///
+/// 用于请求的 Provider 没有可用凭证（冷却中、配额耗尽或熔断），或上游返回 429 的情况。
+/// 返回选中的凭证和对应的等价成员，调用方需要将请求改写为该成员的 Provider 和模型
+fn select_equivalent_credential(
+    state: &AppState,
+    span: &SpanGuard,
+    provider: &str,
+    model: &str,
+    reason: &str,
+) -> Option<(ProviderCredential, EquivalentModel)> {
+    let db = state.db.as_ref()?;
+    let (credential, candidate) = state
+        .processor
+        .select_equivalent_credential(db, provider, model)?;
+    tracing::info!(
+        "[EQUIVALENCE] {}/{} 不可用（{}），降级到 {}/{}",
+        provider,
+        model,
+        reason,
+        candidate.provider,
+        candidate.model
+    );
+    record_failover(state, span, provider, &candidate.provider, reason);
+    Some((credential, candidate))
+}
+
+/// 记录一次 Provider 降级：计入故障转移指标，并在 `span` 下留下故障转移 span
+fn record_failover(state: &AppState, span: &SpanGuard, from: &str, to: &str, reason: &str) {
+    state.processor.metrics.record_failover(from, to, reason);
+    let mut failover = span.child("proxycast.provider.failover");
+    failover.set_attribute("proxycast.failover.from", from);
+    failover.set_attribute("proxycast.failover.to", to);
+    failover.set_attribute("proxycast.failover.reason", reason);
+}
+
+/// 在响应头中标记实际响应请求的 Provider 和模型
+fn set_served_by_headers(response: &mut Response, credential: &ProviderCredential, model: &str) {
+    let headers = response.headers_mut();
+    if let Ok(value) = header::HeaderValue::from_str(&credential.provider_type.to_string()) {
+        headers.insert(SERVED_PROVIDER_HEADER, value);
+    }
+    if let Ok(value) = header::HeaderValue::from_str(model) {
+        headers.insert(SERVED_MODEL_HEADER, value);
+    }
+}
+
+/// 请求的排队优先级，取自 `x-proxycast-priority` 请求头或客户端 key 配置
+pub(crate) fn request_priority(
+    state: &AppState,
+    headers: &HeaderMap,
+    ctx: &RequestContext,
+) -> RequestPriority {
+    state.pool_service.concurrency().priority_for(
+        headers.get(PRIORITY_HEADER).and_then(|v| v.to_str().ok()),
+        ctx.client_key
+            .as_ref()
+            .map(|c| (c.id.as_str(), c.label.as_str())),
+    )
+}
+
+/// 按凭证和 Provider 的并发上限排队获取许可
+///
+/// 优先级取自 `x-proxycast-priority` 请求头或客户端 key 配置。
+/// 排队失败时标记 Flow 失败并返回带 `retry-after` 的 429 响应
+pub(crate) async fn acquire_concurrency_permit(
+    state: &AppState,
+    headers: &HeaderMap,
+    ctx: &RequestContext,
+    credential: &ProviderCredential,
+    flow_id: Option<&str>,
+    anthropic: bool,
+) -> Result<Option<RequestPermit>, Response> {
+    let limiter = state.pool_service.concurrency();
+    if !limiter.is_enabled() {
+        return Ok(None);
+    }
+
+    let priority = request_priority(state, headers, ctx);
+    let provider = credential.provider_type.to_string();
+    match limiter.acquire(&provider, &credential.uuid, priority).await {
+        Ok(permit) => Ok(Some(permit)),
+        Err(e) => {
+            if let Some(fid) = flow_id {
+                let error = FlowError::new(FlowErrorType::RateLimit, e.to_string());
+                state.flow_monitor.fail_flow(fid, error).await;
+            }
+            Err(too_many_requests(
+                &e.to_string(),
+                e.retry_after_secs(),
+                anthropic,
+            ))
+        }
+    }
+}
+
+/// 在选择凭证前检查 Provider 和客户端 key 范围的费用预算
+///
+/// 超出拒绝类预算时返回带 `retry-after`（到统计周期结束）的 429 响应；
+/// 超出降级类预算时返回应改用的模型。凭证范围的预算见 [`BudgetService::over_budget_credentials`]
+///
+/// [`BudgetService::over_budget_credentials`]: crate::services::budget_service::BudgetService::over_budget_credentials
+async fn enforce_budget(
+    state: &AppState,
+    subject: &BudgetSubject,
+    model: &str,
+    anthropic: bool,
+) -> Result<Option<String>, Response> {
+    match state.processor.budget.check(subject, model) {
+        BudgetDecision::Allow => Ok(None),
+        BudgetDecision::Downgrade {
+            model: cheaper,
+            exceeded,
+        } => {
+            tracing::info!("[BUDGET] {}，模型 {} 降级为 {}", exceeded, model, cheaper);
+            Ok(Some(cheaper))
+        }
+        BudgetDecision::Block(exceeded) => Err(budget_exceeded_response(&exceeded, anthropic)),
+    }
+}
+
+/// 超出预算时的 429 响应
+fn budget_exceeded_response(exceeded: &BudgetExceeded, anthropic: bool) -> Response {
+    tracing::warn!("[BUDGET] 拒绝请求: {}", exceeded);
+    too_many_requests(
+        &exceeded.to_string(),
+        exceeded.retry_after_secs(),
+        anthropic,
+    )
+}
+
+/// 因超出凭证预算被排除、否则可以服务该请求的凭证所超出的预算
+///
+/// 凭证池中没有其他可用凭证时，据此返回 429 而不是 503
+fn budget_excluded_for<'a>(
+    state: &AppState,
+    provider: &str,
+    model: &str,
+    over_budget: &'a [(String, BudgetExceeded)],
+) -> Option<&'a BudgetExceeded> {
+    let db = state.db.as_ref()?;
+    let provider_type: PoolProviderType = provider.parse().ok()?;
+    let conn = db.lock().ok()?;
+    over_budget.iter().find_map(|(uuid, exceeded)| {
+        let credential = ProviderPoolDao::get_by_uuid(&conn, uuid).ok().flatten()?;
+        // Anthropic 和 Claude 共享凭证
+        let same_provider = credential.provider_type == provider_type
+            || matches!(
+                (credential.provider_type, provider_type),
+                (PoolProviderType::Anthropic, PoolProviderType::Claude)
+                    | (PoolProviderType::Claude, PoolProviderType::Anthropic)
+            );
+        (same_provider && credential.is_available() && credential.supports_model(model))
+            .then_some(exceeded)
+    })
+}
+
+/// 构建带 `retry-after` 的 429 响应
+fn too_many_requests(message: &str, retry_after_secs: u64, anthropic: bool) -> Response {
+    let body = if anthropic {
+        json!({
+            "type": "error",
+            "error": {"type": "rate_limit_error", "message": message}
+        })
+    } else {
+        json!({"error": {"message": message, "type": "rate_limit_error"}})
+    };
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    if let Ok(value) = header::HeaderValue::from_str(&retry_after_secs.to_string()) {
+        response.headers_mut().insert(header::RETRY_AFTER, value);
+    }
+    response
+}
+
+/// 将并发许可绑定到响应体，响应（包括流式响应）结束或客户端断开时释放
+pub(crate) fn hold_concurrency_permit(response: Response, permit: RequestPermit) -> Response {
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _permit = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
 }
 
 /// 检查是否需要拦截请求
@@ -398,20 +963,49 @@ async fn check_request_intercept(
     flow_metadata: &FlowMetadata,
 ) -> InterceptCheckResult {
     // 创建临时 Flow 用于拦截检查
-    let temp_flow = LLMFlow::new(
+    let mut temp_flow = LLMFlow::new(
         flow_id.to_string(),
         FlowType::ChatCompletions,
         llm_request.clone(),
         flow_metadata.clone(),
     );
 
+    // 先执行自动拦截规则，修改后的请求再进入人工拦截
+    let outcome = state
+        .flow_interceptor
+        .apply_rules(&temp_flow, &InterceptType::Request)
+        .await;
+    let mut rule_request = None;
+    if outcome.is_matched() {
+        state.logs.write().await.add(
+            "info",
+            &format!(
+                "[INTERCEPT] 规则命中请求: flow_id={}, rules={}",
+                flow_id,
+                outcome.matched.join(",")
+            ),
+        );
+        if !outcome.delay.is_zero() {
+            tokio::time::sleep(outcome.delay).await;
+        }
+        if let Some((status, body)) = outcome.respond {
+            return InterceptCheckResult::Responded(
+                respond_by_rule(state, flow_id, status, body).await,
+            );
+        }
+        if let Some(request) = outcome.request {
+            temp_flow.request = request.clone();
+            rule_request = Some(request);
+        }
+    }
+
     // 检查是否需要拦截
     if !state
         .flow_interceptor
         .should_intercept(&temp_flow, &InterceptType::Request)
         .await
     {
-        return InterceptCheckResult::Continue(None);
+        return InterceptCheckResult::Continue(rule_request);
     }
 
     state.logs.write().await.add(
@@ -422,7 +1016,7 @@ async fn check_request_intercept(
     // 拦截请求
     let _intercepted = state
         .flow_interceptor
-        .intercept_request(flow_id, llm_request.clone())
+        .intercept_request(flow_id, temp_flow.request.clone())
         .await;
 
     // 等待用户操作
@@ -442,7 +1036,7 @@ async fn check_request_intercept(
             if let Some(crate::flow_monitor::ModifiedData::Request(req)) = modified {
                 InterceptCheckResult::Continue(Some(req))
             } else {
-                InterceptCheckResult::Continue(None)
+                InterceptCheckResult::Continue(rule_request)
             }
         }
         InterceptAction::Cancel => {
@@ -462,7 +1056,7 @@ async fn check_request_intercept(
             );
             match timeout_action {
                 crate::flow_monitor::TimeoutAction::Continue => {
-                    InterceptCheckResult::Continue(None)
+                    InterceptCheckResult::Continue(rule_request)
                 }
                 crate::flow_monitor::TimeoutAction::Cancel => InterceptCheckResult::Cancelled,
             }
@@ -470,6 +1064,87 @@ async fn check_request_intercept(
     }
 }
 
+/// 执行响应阶段的自动拦截规则
+///
+/// 规则修改或替换响应时结束 Flow 并返回新的 HTTP 响应；仅增加延迟时返回 `None`。
+async fn check_response_rules(
+    state: &AppState,
+    flow_id: &str,
+    llm_response: &LLMResponse,
+    llm_request: &LLMRequest,
+    flow_metadata: &FlowMetadata,
+) -> Option<Response> {
+    let mut temp_flow = LLMFlow::new(
+        flow_id.to_string(),
+        FlowType::ChatCompletions,
+        llm_request.clone(),
+        flow_metadata.clone(),
+    );
+    if llm_request.path == "/v1/messages" {
+        temp_flow.flow_type = FlowType::AnthropicMessages;
+    }
+    temp_flow.response = Some(llm_response.clone());
+    // 部分上游响应只解析了文本内容，按端点格式重建响应体后再执行规则
+    if llm_response.body.is_null() && !llm_response.content.is_empty() {
+        let body = synthesize_body(&temp_flow);
+        if let Some(response) = temp_flow.response.as_mut() {
+            response.body = body;
+        }
+    }
+
+    let outcome = state
+        .flow_interceptor
+        .apply_rules(&temp_flow, &InterceptType::Response)
+        .await;
+    if !outcome.is_matched() {
+        return None;
+    }
+    state.logs.write().await.add(
+        "info",
+        &format!(
+            "[INTERCEPT] 规则命中响应: flow_id={}, rules={}",
+            flow_id,
+            outcome.matched.join(",")
+        ),
+    );
+    if !outcome.delay.is_zero() {
+        tokio::time::sleep(outcome.delay).await;
+    }
+    let (status, body) = outcome.respond?;
+    Some(respond_by_rule(state, flow_id, status, body).await)
+}
+
+/// 以规则指定的状态码和响应体结束 Flow，并构建返回给客户端的响应
+async fn respond_by_rule(
+    state: &AppState,
+    flow_id: &str,
+    status: u16,
+    body: serde_json::Value,
+) -> Response {
+    if status < 400 {
+        let mut llm_response = build_llm_response(status, &extract_json_content(&body), None);
+        llm_response.body = body.clone();
+        state
+            .flow_monitor
+            .complete_flow(flow_id, Some(llm_response))
+            .await;
+    } else {
+        let message = body["error"]["message"]
+            .as_str()
+            .unwrap_or("Injected error")
+            .to_string();
+        let error = FlowError::new(FlowErrorType::from_status_code(status), message)
+            .with_status_code(status)
+            .with_raw_response(body.to_string());
+        state.flow_monitor.fail_flow(flow_id, error).await;
+    }
+    (
+        StatusCode::from_u16(status).unwrap_or(StatusCode::OK),
+        Json(body),
+    )
+        .into_response()
+}
+
 /// 检查是否需要拦截响应
 ///
 /// **Validates: Requirements 2.1, 2.5**
@@ -567,7 +1242,7 @@ pub async fn verify_api_key(
         }
     };
 
-    if key != expected_key {
+    if !api_key_matches(key, expected_key) {
         return Err((
             StatusCode::UNAUTHORIZED,
             Json(serde_json::json!({"error": {"message": "Invalid API key"}})),
@@ -604,7 +1279,7 @@ pub async fn verify_api_key_anthropic(
         }
     };
 
-    if key != expected_key {
+    if !api_key_matches(key, expected_key) {
         return Err((
             StatusCode::UNAUTHORIZED,
             Json(serde_json::json!({
@@ -620,6 +1295,179 @@ pub async fn verify_api_key_anthropic(
     Ok(())
 }
 
+/// 常量时间比较 API key，避免时序侧信道
+pub fn api_key_matches(provided: &str, expected: &str) -> bool {
+    provided.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+/// 从请求头提取 API key
+///
+/// `anthropic` 为 true 时优先读取 `x-api-key`，否则优先读取 `Authorization`。
+fn extract_api_key(headers: &HeaderMap, anthropic: bool) -> Option<&str> {
+    let (primary, secondary) = if anthropic {
+        ("x-api-key", "authorization")
+    } else {
+        ("authorization", "x-api-key")
+    };
+    let value = headers
+        .get(primary)
+        .or_else(|| headers.get(secondary))
+        .and_then(|v| v.to_str().ok())?;
+    Some(value.strip_prefix("Bearer ").unwrap_or(value))
+}
+
+/// 校验共享 API Key 或客户端 key
+///
+/// 共享 API Key 返回 `Ok(None)`；客户端 key 通过状态、模型白名单和配额检查后
+/// 计入一次请求并返回其身份。
+fn authorize_key(
+    state: &AppState,
+    key: &str,
+    model: Option<&str>,
+) -> Result<Option<ClientIdentity>, ClientKeyError> {
+    if api_key_matches(key, &state.api_key) {
+        return Ok(None);
+    }
+
+    let client_key = state
+        .client_keys
+        .authenticate(key)
+        .ok_or(ClientKeyError::InvalidKey)?;
+    state.client_keys.authorize(&client_key, model)?;
+    state
+        .client_keys
+        .record_request(state.db.as_ref(), &client_key.id);
+
+    Ok(Some(ClientIdentity {
+        id: client_key.id,
+        label: client_key.label,
+    }))
+}
+
+/// 校验共享 API Key 或客户端 key，但不计为一次请求
+///
+/// 只检查 key 状态、模型白名单和月度预算，不占用 RPM / TPM 窗口，
+/// 用于 count_tokens 和 WebSocket 握手
+pub(crate) fn authenticate_key(
+    state: &AppState,
+    key: &str,
+    model: Option<&str>,
+) -> Result<Option<ClientIdentity>, ClientKeyError> {
+    if api_key_matches(key, &state.api_key) {
+        return Ok(None);
+    }
+
+    let client_key = state
+        .client_keys
+        .authenticate(key)
+        .ok_or(ClientKeyError::InvalidKey)?;
+    state.client_keys.check_access(&client_key, model)?;
+
+    Ok(Some(ClientIdentity {
+        id: client_key.id,
+        label: client_key.label,
+    }))
+}
+
+fn client_key_error_response(error: &ClientKeyError, body: serde_json::Value) -> Response {
+    let status = StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::UNAUTHORIZED);
+    let mut response = (status, Json(body)).into_response();
+    if let Some(secs) = error.retry_after_secs() {
+        if let Ok(value) = header::HeaderValue::from_str(&secs.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+    }
+    response
+}
+
+/// OpenAI 格式的请求认证（支持共享 API Key 和客户端 key）
+#[allow(clippy::result_large_err)]
+pub fn authorize_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: Option<&str>,
+) -> Result<Option<ClientIdentity>, Response> {
+    authorize_client_with(state, headers, model, authorize_key)
+}
+
+/// OpenAI 格式的请求认证，不计为一次请求（不占用客户端 key 的 RPM 窗口）
+#[allow(clippy::result_large_err)]
+pub fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: Option<&str>,
+) -> Result<Option<ClientIdentity>, Response> {
+    authorize_client_with(state, headers, model, authenticate_key)
+}
+
+/// 校验 API Key 并返回客户端身份（计入或不计入 RPM 窗口）
+type KeyCheck = fn(&AppState, &str, Option<&str>) -> Result<Option<ClientIdentity>, ClientKeyError>;
+
+#[allow(clippy::result_large_err)]
+fn authorize_client_with(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: Option<&str>,
+    check: KeyCheck,
+) -> Result<Option<ClientIdentity>, Response> {
+    let Some(key) = extract_api_key(headers, false) else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": {"message": "No API key provided"}})),
+        )
+            .into_response());
+    };
+
+    check(state, key, model).map_err(|e| {
+        let error_type = match e.status_code() {
+            401 => "invalid_request_error",
+            403 => "permission_error",
+            _ => "rate_limit_error",
+        };
+        client_key_error_response(
+            &e,
+            json!({"error": {"message": e.to_string(), "type": error_type}}),
+        )
+    })
+}
+
+/// Anthropic 格式的请求认证（支持共享 API Key 和客户端 key）
+#[allow(clippy::result_large_err)]
+pub fn authorize_client_anthropic(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: Option<&str>,
+) -> Result<Option<ClientIdentity>, Response> {
+    let Some(key) = extract_api_key(headers, true) else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "authentication_error",
+                    "message": "No API key provided. Please set the x-api-key header."
+                }
+            })),
+        )
+            .into_response());
+    };
+
+    authorize_key(state, key, model).map_err(|e| {
+        let error_type = match e.status_code() {
+            401 => "authentication_error",
+            403 => "permission_error",
+            _ => "rate_limit_error",
+        };
+        client_key_error_response(
+            &e,
+            json!({
+                "type": "error",
+                "error": {"type": error_type, "message": e.to_string()}
+            }),
+        )
+    })
+}
+
 pub async fn chat_completions(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -632,19 +1480,31 @@ pub async fn chat_completions(
     eprintln!("[CHAT_COMPLETIONS] 流式: {}", request.stream);
     eprintln!("[CHAT_COMPLETIONS] 消息数量: {}", request.messages.len());
 
-    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
-        eprintln!("[CHAT_COMPLETIONS] 认证失败!");
-        state
-            .logs
-            .write()
-            .await
-            .add("warn", "Unauthorized request to /v1/chat/completions");
-        return e.into_response();
-    }
+    let trace = start_request_trace(&state, &headers, "POST /v1/chat/completions");
+    let mut auth_span = trace
+        .as_ref()
+        .map(|t| t.span("proxycast.auth"))
+        .unwrap_or_default();
+    let client_key = match authorize_client(&state, &headers, Some(&request.model)) {
+        Ok(client_key) => client_key,
+        Err(e) => {
+            auth_span.set_error("unauthorized");
+            eprintln!("[CHAT_COMPLETIONS] 认证失败!");
+            state
+                .logs
+                .write()
+                .await
+                .add("warn", "Unauthorized request to /v1/chat/completions");
+            return e;
+        }
+    };
+    auth_span.end();
     eprintln!("[CHAT_COMPLETIONS] 认证成功");
 
     // 创建请求上下文
     let mut ctx = RequestContext::new(request.model.clone()).with_stream(request.stream);
+    ctx.set_client_key(client_key);
+    ctx.set_trace(trace);
     eprintln!("[CHAT_COMPLETIONS] 请求ID: {}", ctx.request_id);
 
     state.logs.write().await.add(
@@ -657,6 +1517,8 @@ pub async fn chat_completions(
 
     // 使用 RequestProcessor 解析模型别名
     eprintln!("[CHAT_COMPLETIONS] 开始模型别名解析...");
+    // 路由 span 覆盖别名解析、Provider 选择和凭证选择（含降级）
+    let mut routing_span = ctx.span("proxycast.routing");
     let resolved_model = state.processor.resolve_model(&request.model).await;
     ctx.set_resolved_model(resolved_model.clone());
     eprintln!(
@@ -679,9 +1541,14 @@ pub async fn chat_completions(
     // 应用参数注入
     let injection_enabled = *state.injection_enabled.read().await;
     if injection_enabled {
+        let mut injection_span = ctx.span("proxycast.injection");
         let injector = state.processor.injector.read().await;
         let mut payload = serde_json::to_value(&request).unwrap_or_default();
         let result = injector.inject(&request.model, &mut payload);
+        injection_span.set_attribute(
+            "proxycast.injection.applied_rules",
+            result.applied_rules.clone(),
+        );
         if result.has_injections() {
             state.logs.write().await.add(
                 "info",
@@ -699,7 +1566,19 @@ pub async fn chat_completions(
 
     // 根据客户端类型选择 Provider
     // **Validates: Requirements 3.1, 3.3, 3.4**
-    let (selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    let mut credential_span = routing_span.child("proxycast.credential_select");
+    let (mut selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    if let Some(route) = match_routing_rule(&state, &headers, &ctx, client_type, &request).await {
+        selected_provider = route.provider_id.unwrap_or(selected_provider);
+        if let Some(model) = route.model {
+            request.model = model.clone();
+            ctx.set_resolved_model(model);
+        }
+    }
+    credential_span.set_attribute("gen_ai.system", selected_provider.as_str());
+    if let Some(trace) = &ctx.trace {
+        trace.set_attribute("gen_ai.system", selected_provider.as_str());
+    }
     eprintln!(
         "[CHAT_COMPLETIONS] 客户端类型: {}, 选择的Provider: {}",
         client_type, selected_provider
@@ -723,12 +1602,99 @@ pub async fn chat_completions(
         ),
     );
 
+    // 在回放、缓存和凭证选择之前按客户端 key 和目标 Provider 检查费用预算，
+    // 降级后的模型参与后续的缓存查找和凭证选择
+    let budget_subject = BudgetSubject {
+        provider: Some(
+            headers
+                .get("x-provider-id")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_lowercase())
+                .unwrap_or_else(|| selected_provider.clone()),
+        ),
+        ..BudgetSubject::from_context(&ctx)
+    };
+    match enforce_budget(&state, &budget_subject, &request.model, false).await {
+        Ok(Some(cheaper)) => {
+            request.model = cheaper.clone();
+            ctx.set_resolved_model(cheaper);
+        }
+        Ok(None) => {}
+        Err(response) => return response,
+    }
+
+    // 会话录制/回放（回放时不调用上游）
+    if let PlaybackLookup::Hit(response) = lookup_playback(
+        &state,
+        &headers,
+        &mut ctx,
+        "/v1/chat/completions",
+        &serde_json::to_value(&request).unwrap_or_default(),
+        |ctx| {
+            let provider_type = selected_provider
+                .parse::<ProviderType>()
+                .unwrap_or(ProviderType::OpenAI);
+            (
+                build_llm_request_from_openai(&request, "/v1/chat/completions", &headers),
+                build_flow_metadata(
+                    provider_type,
+                    Some(&selected_provider),
+                    None,
+                    None,
+                    &headers,
+                    ctx,
+                ),
+            )
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    // 查找响应缓存（命中时不调用上游）
+    let cache_key = match lookup_response_cache(
+        &state,
+        &headers,
+        &ctx,
+        "/v1/chat/completions",
+        &serde_json::to_value(&request).unwrap_or_default(),
+        || {
+            let provider_type = selected_provider
+                .parse::<ProviderType>()
+                .unwrap_or(ProviderType::OpenAI);
+            (
+                build_llm_request_from_openai(&request, "/v1/chat/completions", &headers),
+                build_flow_metadata(
+                    provider_type,
+                    Some(&selected_provider),
+                    None,
+                    None,
+                    &headers,
+                    &ctx,
+                ),
+            )
+        },
+    )
+    .await
+    {
+        CacheLookup::Hit(response) => return response,
+        CacheLookup::Miss(cache_key) => cache_key,
+    };
+
     // 从请求头提取 X-Provider-Id（用于精确路由）
     let provider_id_header = headers
         .get("x-provider-id")
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_lowercase());
 
+    // 超出凭证范围预算的凭证不参与选择
+    let over_budget = state
+        .processor
+        .budget
+        .over_budget_credentials(&request.model);
+    let budget_excluded: Vec<String> = over_budget.iter().map(|(id, _)| id.clone()).collect();
+
     // 尝试从凭证池中选择凭证
     // 如果指定了 X-Provider-Id，优先使用它（不降级）
     // 否则使用 selected_provider
@@ -743,11 +1709,24 @@ pub async fn chat_completions(
                 );
                 let cred = state
                     .pool_service
-                    .select_credential(db, explicit_provider_id, Some(&request.model))
+                    .select_credential_excluding(
+                        db,
+                        explicit_provider_id,
+                        Some(&request.model),
+                        &budget_excluded,
+                    )
                     .ok()
                     .flatten();
 
                 if cred.is_none() {
+                    if let Some(exceeded) = budget_excluded_for(
+                        &state,
+                        explicit_provider_id,
+                        &request.model,
+                        &over_budget,
+                    ) {
+                        return budget_exceeded_response(exceeded, false);
+                    }
                     eprintln!(
                         "[CHAT_COMPLETIONS] X-Provider-Id '{}' 没有可用凭证，不进行降级",
                         explicit_provider_id
@@ -781,7 +1760,12 @@ pub async fn chat_completions(
                 );
                 let cred = state
                     .pool_service
-                    .select_credential(db, &selected_provider, Some(&request.model))
+                    .select_credential_excluding(
+                        db,
+                        &selected_provider,
+                        Some(&request.model),
+                        &budget_excluded,
+                    )
                     .ok()
                     .flatten();
 
@@ -947,13 +1931,53 @@ pub async fn chat_completions(
             }
         }
 
+        if found_credential.is_some() {
+            record_failover(
+                &state,
+                &credential_span,
+                &selected_provider,
+                "api_key_provider",
+                "no_pool_credential",
+            );
+        }
+
         found_credential
     } else {
         credential
     };
 
+    // 同一 Provider 的凭证池和 API Key Provider 都没有可用凭证时，按模型等价组降级到其他 Provider
+    let credential = match credential {
+        Some(cred) => Some(cred),
+        None => select_equivalent_credential(
+            &state,
+            &credential_span,
+            &selected_provider,
+            &request.model,
+            "model_equivalence",
+        )
+        .map(|(cred, member)| {
+            selected_provider = member.provider;
+            request.model = member.model;
+            cred
+        }),
+    };
+    if let Some(cred) = &credential {
+        credential_span.set_attribute("proxycast.credential_id", cred.uuid.as_str());
+    }
+    credential_span.end();
+    routing_span.set_attribute("gen_ai.request.model", request.model.as_str());
+    routing_span.set_attribute("gen_ai.system", selected_provider.as_str());
+    routing_span.end();
+
     // 如果找到凭证池中的凭证，使用它
     if let Some(cred) = credential {
+        // 凭证池的选择已排除超预算凭证，这里兜底 API Key Provider 和模型等价组选出的凭证
+        if let Some((_, exceeded)) = over_budget.iter().find(|(id, _)| *id == cred.uuid) {
+            return budget_exceeded_response(exceeded, false);
+        }
+        ctx.set_provider(cred.provider_type);
+        ctx.set_credential_id(cred.uuid.clone());
         eprintln!(
             "[CHAT_COMPLETIONS] 使用凭证: type={}, name={:?}, uuid={}",
             cred.provider_type,
@@ -996,7 +2020,7 @@ pub async fn chat_completions(
             Some(&cred.uuid),
             cred.name.as_deref(),
             &headers,
-            &ctx.request_id,
+            &ctx,
         );
         let flow_id = state
             .flow_monitor
@@ -1016,6 +2040,7 @@ pub async fn chat_completions(
                         }
                     }
                 }
+                InterceptCheckResult::Responded(response) => return response,
                 InterceptCheckResult::Cancelled => {
                     // 请求被取消，标记 Flow 失败并返回错误
                     let error = FlowError::new(FlowErrorType::Cancelled, "请求被用户取消");
@@ -1031,8 +2056,104 @@ pub async fn chat_completions(
             }
         }
 
+        let permit = match acquire_concurrency_permit(
+            &state,
+            &headers,
+            &ctx,
+            &cred,
+            flow_id.as_deref(),
+            false,
+        )
+        .await
+        {
+            Ok(permit) => permit,
+            Err(response) => return response,
+        };
+
         eprintln!("[CHAT_COMPLETIONS] 调用 Provider: {}", cred.provider_type);
-        let response = call_provider_openai(&state, &cred, &request, flow_id.as_deref()).await;
+        let provider_span = provider_call_span(&ctx, &request.model, &cred.uuid);
+        let (response, cred, served_model, permit) = {
+            let (state, request, flow_id) = (&state, &request, flow_id.as_deref());
+            let (headers, ctx) = (&headers, &ctx);
+            in_span(
+                &provider_span,
+                call_provider_hedged(
+                    state,
+                    ctx,
+                    cred,
+                    permit,
+                    &request.model,
+                    flow_id,
+                    // 对冲许可获取失败只算对冲尝试失败，不标记 Flow 失败
+                    |cred| async move {
+                        acquire_concurrency_permit(state, headers, ctx, &cred, None, false).await
+                    },
+                    |cred, model| async move {
+                        if model == request.model {
+                            call_provider_openai(state, &cred, request, flow_id).await
+                        } else {
+                            let mut request = request.clone();
+                            request.model = model;
+                            call_provider_openai(state, &cred, &request, flow_id).await
+                        }
+                    },
+                ),
+            )
+            .await
+        };
+        if served_model != request.model {
+            request.model = served_model.clone();
+            ctx.set_resolved_model(served_model);
+        }
+
+        // 凭证被限流时，按模型等价组换用其他 Provider 重试一次
+        let rate_limited =
+            response.status() == StatusCode::TOO_MANY_REQUESTS && provider_id_header.is_none();
+        let equivalent = if rate_limited {
+            select_equivalent_credential(
+                &state,
+                &provider_span,
+                &selected_provider,
+                &request.model,
+                "rate_limited",
+            )
+        } else {
+            None
+        };
+        let (mut response, cred, permit) = match equivalent {
+            Some((equivalent, member)) => {
+                drop(permit);
+                let permit = match acquire_concurrency_permit(
+                    &state,
+                    &headers,
+                    &ctx,
+                    &equivalent,
+                    flow_id.as_deref(),
+                    false,
+                )
+                .await
+                {
+                    Ok(permit) => permit,
+                    Err(response) => return response,
+                };
+                request.model = member.model.clone();
+                ctx.set_resolved_model(member.model);
+                let response = in_span(
+                    &provider_span,
+                    call_provider_openai(&state, &equivalent, &request, flow_id.as_deref()),
+                )
+                .await;
+                (response, equivalent, permit)
+            }
+            None => (response, cred, permit),
+        };
+        ctx.set_provider(cred.provider_type);
+        ctx.set_credential_id(cred.uuid.clone());
+        if let Some(permit) = permit {
+            response = hold_concurrency_permit(response, permit);
+        }
+        set_served_by_headers(&mut response, &cred, &request.model);
+        finish_provider_call_span(provider_span, response.status());
         eprintln!(
             "[CHAT_COMPLETIONS] Provider 响应状态: {}",
             response.status()
@@ -1120,6 +2241,8 @@ pub async fn chat_completions(
                 }
             }
 
+            ctx.record_trace_response(&response_json);
+
             let input_tokens = response_json["usage"]["prompt_tokens"]
                 .as_u64()
                 .unwrap_or(0) as u32;
@@ -1130,8 +2253,19 @@ pub async fn chat_completions(
             eprintln!("[CHAT_COMPLETIONS] 提取响应内容: content_len={}, input_tokens={}, output_tokens={}", 
                 content.len(), input_tokens, output_tokens);
 
-            // 记录 Token 使用量
-            record_token_usage(&state, &ctx, Some(input_tokens), Some(output_tokens));
+            // 记录 Token 使用量（命中缓存的输入 Token、缓存写入 Token 单独计价）
+            let mut meter = UsageMeter::new();
+            meter.observe_json(&response_json);
+            if let Some(usage) = meter.usage() {
+                record_token_usage_with_cache(
+                    &state,
+                    &ctx,
+                    Some(usage.input_tokens),
+                    Some(usage.output_tokens),
+                    (usage.cache_read_tokens, usage.cache_write_tokens),
+                    crate::telemetry::TokenSource::Actual,
+                );
+            }
 
             // 完成 Flow 捕获并检查响应拦截
             // **Validates: Requirements 2.1, 2.5**
@@ -1142,6 +2276,14 @@ pub async fn chat_completions(
                 llm_response.body = response_json.clone();
                 llm_response.headers = response_headers; // 设置响应头
 
+                // 执行响应阶段的自动拦截规则
+                if let Some(response) =
+                    check_response_rules(&state, &fid, &llm_response, &llm_request, &flow_metadata)
+                        .await
+                {
+                    return response;
+                }
+
                 // 检查是否需要拦截响应
                 if let Some(modified_response) = check_response_intercept(
                     &state,
@@ -1207,10 +2349,10 @@ pub async fn chat_completions(
 
             // 重新构建响应返回给客户端
             let response = Response::from_parts(parts, Body::from(body_bytes));
-            return response;
+            return cache_upstream_response(&state, cache_key, response);
         } else {
             // 流式响应或没有 Flow 捕获，直接返回
-            // 估算 Token 使用量（用于统计）
+            // 响应结束后按上游返回的 usage 计量；上游未返回 usage 时按输入/输出文本估算
             let estimated_input_tokens = request
                 .messages
                 .iter()
@@ -1222,16 +2364,8 @@ pub async fn chat_completions(
                     content_len / 4
                 })
                 .sum::<usize>() as u32;
-            let estimated_output_tokens = if is_success { 100u32 } else { 0u32 };
-
-            if is_success {
-                record_token_usage(
-                    &state,
-                    &ctx,
-                    Some(estimated_input_tokens),
-                    Some(estimated_output_tokens),
-                );
-            }
+            let response =
+                meter_response_usage(&state, ctx.clone(), response, Some(estimated_input_tokens));
 
             // 如果失败，标记 Flow 失败
             if let Some(fid) = flow_id {
@@ -1245,10 +2379,24 @@ pub async fn chat_completions(
                 }
             }
 
-            return response;
+            if request.stream {
+                return cache_upstream_response(
+                    &state,
+                    cache_key,
+                    trace_stream_response(&ctx, response),
+                );
+            }
+            return cache_upstream_response(&state, cache_key, response);
         }
     }
 
+    // 凭证池中的凭证都因超出预算被排除时返回 429
+    if let Some(exceeded) =
+        budget_excluded_for(&state, &selected_provider, &request.model, &over_budget)
+    {
+        return budget_exceeded_response(exceeded, false);
+    }
+
     // 回退到旧的单凭证模式（仅当选择的 Provider 是 Kiro 时）
     // 如果选择的 Provider 不是 Kiro，且凭证池中没有找到凭证，返回错误
     // **Validates: Requirements 3.2**
@@ -1295,7 +2443,7 @@ pub async fn chat_completions(
         None,
         None,
         &headers,
-        &ctx.request_id,
+        &ctx,
     );
     let flow_id = state
         .flow_monitor
@@ -1314,6 +2462,7 @@ pub async fn chat_completions(
                     }
                 }
             }
+            InterceptCheckResult::Responded(response) => return response,
             InterceptCheckResult::Cancelled => {
                 // 请求被取消，标记 Flow 失败并返回错误
                 let error = FlowError::new(FlowErrorType::Cancelled, "请求被用户取消");
@@ -1399,21 +2548,26 @@ pub async fn chat_completions(
                             })
                         };
 
-                        // 估算 Token 数量（基于字符数，约 4 字符 = 1 token）
-                        let estimated_output_tokens = (parsed.content.len() / 4) as u32;
-                        // 估算输入 Token（基于请求消息）
-                        let estimated_input_tokens = request
-                            .messages
-                            .iter()
-                            .map(|m| {
-                                let content_len = match &m.content {
-                                    Some(c) => message_content_len(c),
-                                    None => 0,
-                                };
-                                content_len / 4
-                            })
-                            .sum::<usize>()
-                            as u32;
+                        // Kiro 不返回 Token 数：输入按上游返回的上下文占用率估算，
+                        // 输出按内容和工具调用参数长度估算（约 4 字符 = 1 token）
+                        let (context_input_tokens, estimated_output_tokens) =
+                            parsed.estimate_tokens();
+                        // 上游未返回上下文占用率时按请求消息估算
+                        let estimated_input_tokens = if context_input_tokens > 0 {
+                            context_input_tokens
+                        } else {
+                            request
+                                .messages
+                                .iter()
+                                .map(|m| {
+                                    let content_len = match &m.content {
+                                        Some(c) => message_content_len(c),
+                                        None => 0,
+                                    };
+                                    content_len / 4
+                                })
+                                .sum::<usize>() as u32
+                        };
 
                         let response = serde_json::json!({
                             "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
@@ -1441,12 +2595,13 @@ pub async fn chat_completions(
                             crate::telemetry::RequestStatus::Success,
                             None,
                         );
-                        // 记录 Token 使用量
-                        record_token_usage(
+                        // 记录 Token 使用量（估算值）
+                        record_token_usage_with_source(
                             &state,
                             &ctx,
                             Some(estimated_input_tokens),
                             Some(estimated_output_tokens),
+                            crate::telemetry::TokenSource::Estimated,
                         );
                         // 完成 Flow 捕获并检查响应拦截
                         // **Validates: Requirements 2.1, 2.5**
@@ -1457,6 +2612,19 @@ pub async fn chat_completions(
                                 Some((estimated_input_tokens, estimated_output_tokens)),
                             );
 
+                            // 执行响应阶段的自动拦截规则
+                            if let Some(response) = check_response_rules(
+                                &state,
+                                fid,
+                                &llm_response,
+                                &llm_request,
+                                &flow_metadata,
+                            )
+                            .await
+                            {
+                                return response;
+                            }
+
                             // 检查是否需要拦截响应
                             if let Some(modified_response) = check_response_intercept(
                                 &state,
@@ -1626,6 +2794,19 @@ pub async fn chat_completions(
                                                     Some((est_input, est_output)),
                                                 );
 
+                                                // 执行响应阶段的自动拦截规则
+                                                if let Some(response) = check_response_rules(
+                                                    &state,
+                                                    fid,
+                                                    &llm_response,
+                                                    &llm_request,
+                                                    &flow_metadata,
+                                                )
+                                                .await
+                                                {
+                                                    return response;
+                                                }
+
                                                 // 检查是否需要拦截响应
                                                 if let Some(modified_response) =
                                                     check_response_intercept(
@@ -1802,18 +2983,31 @@ pub async fn anthropic_messages(
     headers: HeaderMap,
     Json(mut request): Json<AnthropicMessagesRequest>,
 ) -> Response {
+    let trace = start_request_trace(&state, &headers, "POST /v1/messages");
+    let mut auth_span = trace
+        .as_ref()
+        .map(|t| t.span("proxycast.auth"))
+        .unwrap_or_default();
+
     // 使用 Anthropic 格式的认证验证（优先检查 x-api-key）
-    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
-        state
-            .logs
-            .write()
-            .await
-            .add("warn", "Unauthorized request to /v1/messages");
-        return e.into_response();
-    }
+    let client_key = match authorize_client_anthropic(&state, &headers, Some(&request.model)) {
+        Ok(client_key) => client_key,
+        Err(e) => {
+            auth_span.set_error("unauthorized");
+            state
+                .logs
+                .write()
+                .await
+                .add("warn", "Unauthorized request to /v1/messages");
+            return e;
+        }
+    };
+    auth_span.end();
 
     // 创建请求上下文
     let mut ctx = RequestContext::new(request.model.clone()).with_stream(request.stream);
+    ctx.set_client_key(client_key);
+    ctx.set_trace(trace);
 
     // 详细记录请求信息
     let msg_count = request.messages.len();
@@ -1828,6 +3022,8 @@ pub async fn anthropic_messages(
     );
 
     // 使用 RequestProcessor 解析模型别名
+    // 路由 span 覆盖别名解析、Provider 选择和凭证选择（含降级）
+    let mut routing_span = ctx.span("proxycast.routing");
     let resolved_model = state.processor.resolve_model(&request.model).await;
     ctx.set_resolved_model(resolved_model.clone());
 
@@ -1872,9 +3068,14 @@ pub async fn anthropic_messages(
     // 应用参数注入
     let injection_enabled = *state.injection_enabled.read().await;
     if injection_enabled {
+        let mut injection_span = ctx.span("proxycast.injection");
         let injector = state.processor.injector.read().await;
         let mut payload = serde_json::to_value(&request).unwrap_or_default();
         let result = injector.inject(&request.model, &mut payload);
+        injection_span.set_attribute(
+            "proxycast.injection.applied_rules",
+            result.applied_rules.clone(),
+        );
         if result.has_injections() {
             state.logs.write().await.add(
                 "info",
@@ -1892,7 +3093,19 @@ pub async fn anthropic_messages(
 
     // 根据客户端类型选择 Provider
     // **Validates: Requirements 3.1, 3.3, 3.4**
-    let (selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    let mut credential_span = routing_span.child("proxycast.credential_select");
+    let (mut selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    if let Some(route) = match_routing_rule(&state, &headers, &ctx, client_type, &request).await {
+        selected_provider = route.provider_id.unwrap_or(selected_provider);
+        if let Some(model) = route.model {
+            request.model = model.clone();
+            ctx.set_resolved_model(model);
+        }
+    }
+    credential_span.set_attribute("gen_ai.system", selected_provider.as_str());
+    if let Some(trace) = &ctx.trace {
+        trace.set_attribute("gen_ai.system", selected_provider.as_str());
+    }
 
     // 记录客户端检测和 Provider 选择结果
     state.logs.write().await.add(
@@ -1912,12 +3125,99 @@ pub async fn anthropic_messages(
         ),
     );
 
+    // 在回放、缓存和凭证选择之前按客户端 key 和目标 Provider 检查费用预算，
+    // 降级后的模型参与后续的缓存查找和凭证选择
+    let budget_subject = BudgetSubject {
+        provider: Some(
+            headers
+                .get("x-provider-id")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_lowercase())
+                .unwrap_or_else(|| selected_provider.clone()),
+        ),
+        ..BudgetSubject::from_context(&ctx)
+    };
+    match enforce_budget(&state, &budget_subject, &request.model, true).await {
+        Ok(Some(cheaper)) => {
+            request.model = cheaper.clone();
+            ctx.set_resolved_model(cheaper);
+        }
+        Ok(None) => {}
+        Err(response) => return response,
+    }
+
+    // 会话录制/回放（回放时不调用上游）
+    if let PlaybackLookup::Hit(response) = lookup_playback(
+        &state,
+        &headers,
+        &mut ctx,
+        "/v1/messages",
+        &serde_json::to_value(&request).unwrap_or_default(),
+        |ctx| {
+            let provider_type = selected_provider
+                .parse::<ProviderType>()
+                .unwrap_or(ProviderType::OpenAI);
+            (
+                build_llm_request_from_anthropic(&request, "/v1/messages", &headers),
+                build_flow_metadata(
+                    provider_type,
+                    Some(&selected_provider),
+                    None,
+                    None,
+                    &headers,
+                    ctx,
+                ),
+            )
+        },
+    )
+    .await
+    {
+        return response;
+    }
+
+    // 查找响应缓存（命中时不调用上游）
+    let cache_key = match lookup_response_cache(
+        &state,
+        &headers,
+        &ctx,
+        "/v1/messages",
+        &serde_json::to_value(&request).unwrap_or_default(),
+        || {
+            let provider_type = selected_provider
+                .parse::<ProviderType>()
+                .unwrap_or(ProviderType::OpenAI);
+            (
+                build_llm_request_from_anthropic(&request, "/v1/messages", &headers),
+                build_flow_metadata(
+                    provider_type,
+                    Some(&selected_provider),
+                    None,
+                    None,
+                    &headers,
+                    &ctx,
+                ),
+            )
+        },
+    )
+    .await
+    {
+        CacheLookup::Hit(response) => return response,
+        CacheLookup::Miss(cache_key) => cache_key,
+    };
+
     // 从请求头提取 X-Provider-Id（用于精确路由）
     let provider_id_header = headers
         .get("x-provider-id")
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_lowercase());
 
+    // 超出凭证范围预算的凭证不参与选择
+    let over_budget = state
+        .processor
+        .budget
+        .over_budget_credentials(&request.model);
+    let budget_excluded: Vec<String> = over_budget.iter().map(|(id, _)| id.clone()).collect();
+
     // 尝试从凭证池中选择凭证
     // 如果指定了 X-Provider-Id，优先使用它（不降级）
     // 否则使用 selected_provider
@@ -1931,11 +3231,24 @@ pub async fn anthropic_messages(
                 );
                 let cred = state
                     .pool_service
-                    .select_credential(db, explicit_provider_id, Some(&request.model))
+                    .select_credential_excluding(
+                        db,
+                        explicit_provider_id,
+                        Some(&request.model),
+                        &budget_excluded,
+                    )
                     .ok()
                     .flatten();
 
                 if cred.is_none() {
+                    if let Some(exceeded) = budget_excluded_for(
+                        &state,
+                        explicit_provider_id,
+                        &request.model,
+                        &over_budget,
+                    ) {
+                        return budget_exceeded_response(exceeded, true);
+                    }
                     eprintln!(
                         "[AMP] X-Provider-Id '{}' 没有可用凭证，不进行降级",
                         explicit_provider_id
@@ -1968,7 +3281,12 @@ pub async fn anthropic_messages(
                 );
                 let cred = state
                     .pool_service
-                    .select_credential(db, &selected_provider, Some(&request.model))
+                    .select_credential_excluding(
+                        db,
+                        &selected_provider,
+                        Some(&request.model),
+                        &budget_excluded,
+                    )
                     .ok()
                     .flatten();
 
@@ -2031,13 +3349,53 @@ pub async fn anthropic_messages(
             }
         }
 
+        if found_credential.is_some() {
+            record_failover(
+                &state,
+                &credential_span,
+                &selected_provider,
+                "api_key_provider",
+                "no_pool_credential",
+            );
+        }
+
         found_credential
     } else {
         credential
     };
 
+    // 同一 Provider 的凭证池和 API Key Provider 都没有可用凭证时，按模型等价组降级到其他 Provider
+    let credential = match credential {
+        Some(cred) => Some(cred),
+        None => select_equivalent_credential(
+            &state,
+            &credential_span,
+            &selected_provider,
+            &request.model,
+            "model_equivalence",
+        )
+        .map(|(cred, member)| {
+            selected_provider = member.provider;
+            request.model = member.model;
+            cred
+        }),
+    };
+    if let Some(cred) = &credential {
+        credential_span.set_attribute("proxycast.credential_id", cred.uuid.as_str());
+    }
+    credential_span.end();
+    routing_span.set_attribute("gen_ai.request.model", request.model.as_str());
+    routing_span.set_attribute("gen_ai.system", selected_provider.as_str());
+    routing_span.end();
+
     // 如果找到凭证池中的凭证，使用它
     if let Some(cred) = credential {
+        // 凭证池的选择已排除超预算凭证，这里兜底 API Key Provider 和模型等价组选出的凭证
+        if let Some((_, exceeded)) = over_budget.iter().find(|(id, _)| *id == cred.uuid) {
+            return budget_exceeded_response(exceeded, true);
+        }
+        ctx.set_provider(cred.provider_type);
+        ctx.set_credential_id(cred.uuid.clone());
         state.logs.write().await.add(
             "info",
             &format!(
@@ -2075,7 +3433,7 @@ pub async fn anthropic_messages(
             Some(&cred.uuid),
             cred.name.as_deref(),
             &headers,
-            &ctx.request_id,
+            &ctx,
         );
         let flow_id = state
             .flow_monitor
@@ -2095,6 +3453,7 @@ pub async fn anthropic_messages(
                         }
                     }
                 }
+                InterceptCheckResult::Responded(response) => return response,
                 InterceptCheckResult::Cancelled => {
                     // 请求被取消，标记 Flow 失败并返回错误
                     let error = FlowError::new(FlowErrorType::Cancelled, "请求被用户取消");
@@ -2113,8 +3472,111 @@ pub async fn anthropic_messages(
                 }
             }
         }
-
-        let response = call_provider_anthropic(&state, &cred, &request, flow_id.as_deref()).await;
+
+        let permit = match acquire_concurrency_permit(
+            &state,
+            &headers,
+            &ctx,
+            &cred,
+            flow_id.as_deref(),
+            true,
+        )
+        .await
+        {
+            Ok(permit) => permit,
+            Err(response) => return response,
+        };
+
+        let provider_span = provider_call_span(&ctx, &request.model, &cred.uuid);
+        let resume = ResumeScope::new(&state, &headers, &ctx);
+        let (response, cred, served_model, permit) = {
+            let (state, request, flow_id) = (&state, &request, flow_id.as_deref());
+            let (headers, ctx, resume) = (&headers, &ctx, &resume);
+            in_span(
+                &provider_span,
+                call_provider_hedged(
+                    state,
+                    ctx,
+                    cred,
+                    permit,
+                    &request.model,
+                    flow_id,
+                    // 对冲许可获取失败只算对冲尝试失败，不标记 Flow 失败
+                    |cred| async move {
+                        acquire_concurrency_permit(state, headers, ctx, &cred, None, true).await
+                    },
+                    |cred, model| async move {
+                        if model == request.model {
+                            call_provider_anthropic(state, &cred, request, flow_id, resume).await
+                        } else {
+                            let mut request = request.clone();
+                            request.model = model;
+                            call_provider_anthropic(state, &cred, &request, flow_id, resume).await
+                        }
+                    },
+                ),
+            )
+            .await
+        };
+        if served_model != request.model {
+            request.model = served_model.clone();
+            ctx.set_resolved_model(served_model);
+        }
+
+        // 凭证被限流时，按模型等价组换用其他 Provider 重试一次
+        let rate_limited =
+            response.status() == StatusCode::TOO_MANY_REQUESTS && provider_id_header.is_none();
+        let equivalent = if rate_limited {
+            select_equivalent_credential(
+                &state,
+                &provider_span,
+                &selected_provider,
+                &request.model,
+                "rate_limited",
+            )
+        } else {
+            None
+        };
+        let (mut response, cred, permit) = match equivalent {
+            Some((equivalent, member)) => {
+                drop(permit);
+                let permit = match acquire_concurrency_permit(
+                    &state,
+                    &headers,
+                    &ctx,
+                    &equivalent,
+                    flow_id.as_deref(),
+                    true,
+                )
+                .await
+                {
+                    Ok(permit) => permit,
+                    Err(response) => return response,
+                };
+                request.model = member.model.clone();
+                ctx.set_resolved_model(member.model);
+                let response = in_span(
+                    &provider_span,
+                    call_provider_anthropic(
+                        &state,
+                        &equivalent,
+                        &request,
+                        flow_id.as_deref(),
+                        &resume,
+                    ),
+                )
+                .await;
+                (response, equivalent, permit)
+            }
+            None => (response, cred, permit),
+        };
+        ctx.set_provider(cred.provider_type);
+        ctx.set_credential_id(cred.uuid.clone());
+        if let Some(permit) = permit {
+            response = hold_concurrency_permit(response, permit);
+        }
+        set_served_by_headers(&mut response, &cred, &request.model);
+        finish_provider_call_span(provider_span, response.status());
 
         // 记录请求统计
         let is_success = response.status().is_success();
@@ -2125,7 +3587,7 @@ pub async fn anthropic_messages(
         };
         record_request_telemetry(&state, &ctx, status, None);
 
-        // 估算 Token 使用量
+        // 估算 Token 使用量（仅用于 Flow 记录，计费按上游返回的 usage）
         let estimated_input_tokens = request
             .messages
             .iter()
@@ -2143,26 +3605,68 @@ pub async fn anthropic_messages(
             })
             .sum::<usize>() as u32;
         let estimated_output_tokens = if is_success { 100u32 } else { 0u32 };
-
-        if is_success {
-            record_token_usage(
-                &state,
-                &ctx,
-                Some(estimated_input_tokens),
-                Some(estimated_output_tokens),
-            );
-        }
+        let mut response =
+            meter_response_usage(&state, ctx.clone(), response, Some(estimated_input_tokens));
 
         // 完成 Flow 捕获并检查响应拦截
         // **Validates: Requirements 2.1, 2.5**
         if let Some(fid) = flow_id {
             if is_success {
-                let llm_response = build_llm_response(
+                let mut llm_response = build_llm_response(
                     200,
                     "",
                     Some((estimated_input_tokens, estimated_output_tokens)),
                 );
 
+                // 非流式响应读取完整响应体，供响应阶段的自动拦截规则匹配；
+                // 流式响应在此时还没有响应体，不执行响应阶段规则
+                if !request.stream {
+                    let (parts, body) = response.into_parts();
+                    let body_bytes = match axum::body::to_bytes(body, usize::MAX).await {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            let error = FlowError::new(FlowErrorType::Network, e.to_string());
+                            state.flow_monitor.fail_flow(&fid, error).await;
+                            return (
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                Json(serde_json::json!({
+                                    "type": "error",
+                                    "error": {
+                                        "type": "api_error",
+                                        "message": format!("Failed to read response body: {}", e)
+                                    }
+                                })),
+                            )
+                                .into_response();
+                        }
+                    };
+                    if let Ok(body) = serde_json::from_slice::<serde_json::Value>(&body_bytes) {
+                        let mut meter = UsageMeter::new();
+                        meter.observe_json(&body);
+                        let usage = meter
+                            .usage()
+                            .map(|u| (u.input_tokens, u.output_tokens))
+                            .unwrap_or((estimated_input_tokens, estimated_output_tokens));
+                        llm_response =
+                            build_llm_response(200, &extract_json_content(&body), Some(usage));
+                        llm_response.body = body;
+                    }
+                    response = Response::from_parts(parts, Body::from(body_bytes));
+
+                    // 执行响应阶段的自动拦截规则
+                    if let Some(response) = check_response_rules(
+                        &state,
+                        &fid,
+                        &llm_response,
+                        &llm_request,
+                        &flow_metadata,
+                    )
+                    .await
+                    {
+                        return response;
+                    }
+                }
+
                 // 检查是否需要拦截响应
                 if let Some(modified_response) = check_response_intercept(
                     &state,
@@ -2223,7 +3727,14 @@ pub async fn anthropic_messages(
             }
         }
 
-        return response;
+        return cache_upstream_response(&state, cache_key, trace_stream_response(&ctx, response));
+    }
+
+    // 凭证池中的凭证都因超出预算被排除时返回 429
+    if let Some(exceeded) =
+        budget_excluded_for(&state, &selected_provider, &request.model, &over_budget)
+    {
+        return budget_exceeded_response(exceeded, true);
     }
 
     // 回退到旧的单凭证模式（仅当选择的 Provider 是 Kiro 时）
@@ -2272,7 +3783,7 @@ pub async fn anthropic_messages(
         None,
         None,
         &headers,
-        &ctx.request_id,
+        &ctx,
     );
     let flow_id = state
         .flow_monitor
@@ -2291,6 +3802,7 @@ pub async fn anthropic_messages(
                     }
                 }
             }
+            InterceptCheckResult::Responded(response) => return response,
             InterceptCheckResult::Cancelled => {
                 // 请求被取消，标记 Flow 失败并返回错误
                 let error = FlowError::new(FlowErrorType::Cancelled, "请求被用户取消");
@@ -2439,6 +3951,19 @@ pub async fn anthropic_messages(
                                     Some((est_input, est_output)),
                                 );
 
+                                // 执行响应阶段的自动拦截规则
+                                if let Some(response) = check_response_rules(
+                                    &state,
+                                    fid,
+                                    &llm_response,
+                                    &llm_request,
+                                    &flow_metadata,
+                                )
+                                .await
+                                {
+                                    return response;
+                                }
+
                                 // 检查是否需要拦截响应
                                 if let Some(modified_response) = check_response_intercept(
                                     &state,
@@ -2503,6 +4028,19 @@ pub async fn anthropic_messages(
                                 Some((est_input, est_output)),
                             );
 
+                            // 执行响应阶段的自动拦截规则
+                            if let Some(response) = check_response_rules(
+                                &state,
+                                fid,
+                                &llm_response,
+                                &llm_request,
+                                &flow_metadata,
+                            )
+                            .await
+                            {
+                                return response;
+                            }
+
                             // 检查是否需要拦截响应
                             if let Some(modified_response) = check_response_intercept(
                                 &state,
@@ -2634,6 +4172,19 @@ pub async fn anthropic_messages(
                                                     Some((est_input, est_output)),
                                                 );
 
+                                                // 执行响应阶段的自动拦截规则
+                                                if let Some(response) = check_response_rules(
+                                                    &state,
+                                                    fid,
+                                                    &llm_response,
+                                                    &llm_request,
+                                                    &flow_metadata,
+                                                )
+                                                .await
+                                                {
+                                                    return response;
+                                                }
+
                                                 // 检查是否需要拦截响应
                                                 if let Some(modified_response) =
                                                     check_response_intercept(
@@ -3166,3 +4717,1001 @@ fn convert_anthropic_response_to_openai(anthropic_resp: &serde_json::Value, mode
 
     serde_json::to_string(&openai_resp).unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HedgingConfig;
+    use crate::models::provider_pool_model::CredentialData;
+    use crate::resilience::HedgeOutcome;
+    use crate::server::test_support::{
+        add_credential, auth_headers, body_text, spawn_upstream, test_state,
+    };
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// OpenAI 兼容的 mock 上游：第一个到达的请求卡住，之后的请求立即返回（流式请求返回 SSE）
+    async fn stalling_openai_upstream() -> String {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let upstream = axum::Router::new().route(
+            "/:name/v1/chat/completions",
+            axum::routing::post(
+                move |axum::extract::Path(name): axum::extract::Path<String>,
+                      Json(body): Json<serde_json::Value>| {
+                    let hits = hits.clone();
+                    async move {
+                        if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                            tokio::time::sleep(Duration::from_secs(10)).await;
+                        }
+                        let content = format!("from {}", name);
+                        if body["stream"] == true {
+                            let chunk = json!({
+                                "id": "chatcmpl-test",
+                                "object": "chat.completion.chunk",
+                                "created": 0,
+                                "model": "gpt-4o",
+                                "choices": [{
+                                    "index": 0,
+                                    "delta": {"role": "assistant", "content": content},
+                                    "finish_reason": "stop"
+                                }]
+                            });
+                            return (
+                                [(header::CONTENT_TYPE, "text/event-stream")],
+                                format!("data: {}\n\ndata: [DONE]\n\n", chunk),
+                            )
+                                .into_response();
+                        }
+                        Json(json!({
+                            "id": "chatcmpl-test",
+                            "object": "chat.completion",
+                            "created": 0,
+                            "model": "gpt-4o",
+                            "choices": [{
+                                "index": 0,
+                                "message": {"role": "assistant", "content": content},
+                                "finish_reason": "stop"
+                            }],
+                            "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+                        }))
+                        .into_response()
+                    }
+                },
+            ),
+        );
+        spawn_upstream(upstream).await
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions_hedges_stalled_credential() {
+        let base = stalling_openai_upstream().await;
+        let state = test_state("openai");
+        for name in ["a", "b"] {
+            add_credential(
+                &state,
+                ProviderType::OpenAI,
+                CredentialData::OpenAIKey {
+                    api_key: "sk-test".to_string(),
+                    base_url: Some(format!("{}/{}", base, name)),
+                },
+            );
+        }
+        state.processor.hedge.set_config(HedgingConfig {
+            enabled: true,
+            min_samples: 1,
+            min_delay_ms: 50,
+            max_delay_ms: 100,
+            ..Default::default()
+        });
+        state.processor.hedge.record_ttfb("gpt-4o", 50);
+
+        let request = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        }))
+        .unwrap();
+        let started = Instant::now();
+        let response = chat_completions(State(state.clone()), auth_headers(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_text(response).await.contains("from "));
+        // 卡住的原始请求被对冲请求取代，无需等到上游返回
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // 流式请求的 Flow 由流式处理收尾，此时仍处于活跃状态
+        let flows = state.flow_monitor.active_flows().await;
+        assert_eq!(flows.len(), 1);
+        let attempts = &flows[0].metadata.hedge_attempts;
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].outcome, HedgeOutcome::Cancelled);
+        assert_eq!(attempts[1].outcome, HedgeOutcome::Won);
+        assert_ne!(attempts[0].credential_id, attempts[1].credential_id);
+        // 胜出方的 TTFB 计入样本
+        assert_eq!(state.processor.hedge.sample_count("gpt-4o"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_hedge_winner_holds_its_own_concurrency_permit() {
+        let state = test_state("openai");
+        let credentials: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                add_credential(
+                    &state,
+                    ProviderType::OpenAI,
+                    CredentialData::OpenAIKey {
+                        api_key: "sk-test".to_string(),
+                        base_url: Some(format!("http://127.0.0.1:1/{}", name)),
+                    },
+                )
+            })
+            .collect();
+        state
+            .pool_service
+            .concurrency()
+            .set_config(crate::config::ConcurrencyConfig {
+                enabled: true,
+                max_per_credential: 1,
+                ..Default::default()
+            });
+        state.processor.hedge.set_config(HedgingConfig {
+            enabled: true,
+            min_samples: 1,
+            min_delay_ms: 20,
+            max_delay_ms: 50,
+            ..Default::default()
+        });
+        state.processor.hedge.record_ttfb("gpt-4o", 20);
+
+        let headers = HeaderMap::new();
+        let ctx = RequestContext::new("gpt-4o".to_string()).with_stream(true);
+        let primary = credentials[0].clone();
+        let permit = acquire_concurrency_permit(&state, &headers, &ctx, &primary, None, false)
+            .await
+            .unwrap();
+        let (state_ref, headers, ctx) = (&state, &headers, &ctx);
+        let stalled = &primary.uuid;
+        let (response, served, _, permit) = call_provider_hedged(
+            state_ref,
+            ctx,
+            primary.clone(),
+            permit,
+            "gpt-4o",
+            None,
+            |cred| async move {
+                acquire_concurrency_permit(state_ref, headers, ctx, &cred, None, false).await
+            },
+            move |cred, _| async move {
+                if &cred.uuid == stalled {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                "ok".into_response()
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(served.uuid, credentials[1].uuid);
+        // 胜出的对冲请求持有对冲凭证的许可，原始请求的许可已释放
+        let active = |uuid: &str| {
+            state
+                .pool_service
+                .concurrency()
+                .snapshot()
+                .into_iter()
+                .find(|s| s.scope == format!("credential:{}", uuid))
+                .map_or(0, |s| s.active)
+        };
+        assert_eq!(active(&credentials[0].uuid), 0);
+        assert_eq!(active(&credentials[1].uuid), 1);
+        drop(permit);
+        assert_eq!(active(&credentials[1].uuid), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drain_strategy_queues_on_saturated_credential() {
+        let upstream = axum::Router::new().route(
+            "/:name/v1/chat/completions",
+            axum::routing::post(
+                |axum::extract::Path(name): axum::extract::Path<String>| async move {
+                    Json(json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "created": 0,
+                        "model": "gpt-4o",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": format!("from {}", name)},
+                            "finish_reason": "stop"
+                        }],
+                        "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+                    }))
+                },
+            ),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("openai");
+        let credentials: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                let credential = add_credential(
+                    &state,
+                    ProviderType::OpenAI,
+                    CredentialData::OpenAIKey {
+                        api_key: "sk-test".to_string(),
+                        base_url: Some(format!("{}/{}", base, name)),
+                    },
+                );
+                (credential, *name)
+            })
+            .collect();
+        state.pool_service.set_balance_strategies(HashMap::from([(
+            "openai".to_string(),
+            crate::credential::BalanceStrategy::Drain,
+        )]));
+        state
+            .pool_service
+            .concurrency()
+            .set_config(crate::config::ConcurrencyConfig {
+                enabled: true,
+                max_per_credential: 1,
+                ..Default::default()
+            });
+
+        // 占满耗尽策略当前使用的凭证
+        let drained = state
+            .pool_service
+            .select_credential(state.db.as_ref().unwrap(), "openai", Some("gpt-4o"))
+            .unwrap()
+            .unwrap();
+        state.pool_service.release_selection(&drained.uuid);
+        let (_, drained_name) = credentials
+            .iter()
+            .find(|(c, _)| c.uuid == drained.uuid)
+            .unwrap();
+        let (headers, ctx) = (HeaderMap::new(), RequestContext::new("gpt-4o".to_string()));
+        let held = acquire_concurrency_permit(&state, &headers, &ctx, &drained, None, false)
+            .await
+            .unwrap();
+
+        let request = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let mut pending = tokio::spawn(chat_completions(
+            State(state.clone()),
+            auth_headers(),
+            Json(request),
+        ));
+        // 请求在已满凭证的队列中等待，而不是切换到空闲凭证
+        assert!(
+            tokio::time::timeout(Duration::from_millis(300), &mut pending)
+                .await
+                .is_err()
+        );
+
+        drop(held);
+        let response = tokio::time::timeout(Duration::from_secs(5), pending)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_text(response)
+            .await
+            .contains(&format!("from {}", drained_name)));
+    }
+
+    #[tokio::test]
+    async fn test_losing_hedge_attempt_is_cancelled() {
+        let state = test_state("openai");
+        let credentials: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                add_credential(
+                    &state,
+                    ProviderType::OpenAI,
+                    CredentialData::OpenAIKey {
+                        api_key: "sk-test".to_string(),
+                        base_url: Some(format!("http://127.0.0.1:1/{}", name)),
+                    },
+                )
+            })
+            .collect();
+        let breaker = state.pool_service.circuit_breaker();
+        breaker.set_config(crate::config::CircuitBreakerConfig {
+            failure_threshold: 1,
+            open_duration_secs: 2,
+            half_open_max_probes: 1,
+            ..Default::default()
+        });
+        state.processor.hedge.set_config(HedgingConfig {
+            enabled: true,
+            min_samples: 1,
+            min_delay_ms: 20,
+            max_delay_ms: 50,
+            ..Default::default()
+        });
+        state.processor.hedge.record_ttfb("gpt-4o", 20);
+
+        // 原始凭证处于半开状态，唯一的探测名额被本次请求占用
+        let primary = credentials[0].clone();
+        breaker.on_selected(&primary.uuid, None);
+        breaker.record_failure(&primary.uuid);
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        breaker.on_selected(&primary.uuid, None);
+        assert!(!breaker.is_call_permitted(&primary.uuid, None));
+
+        let ctx = RequestContext::new("gpt-4o".to_string()).with_stream(true);
+        let stalled = &primary.uuid;
+        let (response, served, _, _) = call_provider_hedged(
+            &state,
+            &ctx,
+            primary.clone(),
+            None,
+            "gpt-4o",
+            None,
+            |_| async { Ok(None) },
+            move |cred, _| async move {
+                if &cred.uuid == stalled {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                "ok".into_response()
+            },
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(served.uuid, credentials[1].uuid);
+        // 落败的原始请求被取消时归还探测名额，并在请求日志中记为已取消
+        assert!(breaker.is_call_permitted(&primary.uuid, None));
+        let logs = state.processor.stats.read().get_all();
+        let cancelled: Vec<_> = logs
+            .iter()
+            .filter(|log| log.status == crate::telemetry::RequestStatus::Cancelled)
+            .collect();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(
+            cancelled[0].credential_id.as_deref(),
+            Some(primary.uuid.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_requests_are_not_hedged() {
+        let base = stalling_openai_upstream().await;
+        let state = test_state("openai");
+        for name in ["a", "b"] {
+            add_credential(
+                &state,
+                ProviderType::OpenAI,
+                CredentialData::OpenAIKey {
+                    api_key: "sk-test".to_string(),
+                    base_url: Some(format!("{}/{}", base, name)),
+                },
+            );
+        }
+        state.processor.hedge.set_config(HedgingConfig {
+            enabled: true,
+            min_samples: 1,
+            min_delay_ms: 50,
+            max_delay_ms: 100,
+            ..Default::default()
+        });
+        state.processor.hedge.record_ttfb("gpt-4o", 50);
+
+        let request = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = tokio::time::timeout(
+            Duration::from_millis(500),
+            chat_completions(State(state.clone()), auth_headers(), Json(request)),
+        )
+        .await;
+        // 非流式响应要等上游生成完毕，不按首个 chunk 对冲，继续等待原始请求
+        assert!(response.is_err());
+        assert_eq!(state.processor.hedge.sample_count("gpt-4o"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions_without_hedging_waits_for_primary() {
+        let base = stalling_openai_upstream().await;
+        let state = test_state("openai");
+        add_credential(
+            &state,
+            ProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: Some(format!("{}/a", base)),
+            },
+        );
+
+        let request = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = tokio::time::timeout(
+            Duration::from_millis(500),
+            chat_completions(State(state.clone()), auth_headers(), Json(request)),
+        )
+        .await;
+        // 未启用对冲时不会发出第二个请求，也不记录 TTFB 样本
+        assert!(response.is_err());
+        assert_eq!(state.processor.hedge.sample_count("gpt-4o"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions_fails_over_to_equivalent_after_rate_limit() {
+        let upstream = axum::Router::new()
+            .route(
+                "/openai/v1/chat/completions",
+                axum::routing::post(|| async {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [("retry-after", "30")],
+                        Json(json!({"error": {"message": "rate limited"}})),
+                    )
+                }),
+            )
+            .route(
+                "/claude/v1/messages",
+                axum::routing::post(|Json(body): Json<serde_json::Value>| async move {
+                    Json(json!({
+                        "id": "msg_test",
+                        "type": "message",
+                        "role": "assistant",
+                        "model": body["model"],
+                        "content": [{"type": "text", "text": "from claude"}],
+                        "stop_reason": "end_turn",
+                        "usage": {"input_tokens": 1, "output_tokens": 1}
+                    }))
+                }),
+            );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("openai");
+        add_credential(
+            &state,
+            ProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: Some(format!("{}/openai", base)),
+            },
+        );
+        add_credential(
+            &state,
+            ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(format!("{}/claude", base)),
+            },
+        );
+        let member = |provider: &str, model: &str| EquivalentModel {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        };
+        state
+            .processor
+            .fallback
+            .write()
+            .set_equivalence_groups(vec![crate::config::ModelEquivalenceGroup {
+                name: "test".to_string(),
+                members: vec![
+                    member("openai", "gpt-4o"),
+                    member("claude", "claude-sonnet-4-5"),
+                ],
+            }]);
+
+        let request = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = chat_completions(State(state.clone()), auth_headers(), Json(request)).await;
+
+        // 限流后按等价组降级到 claude，并转换回 OpenAI 格式
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[SERVED_PROVIDER_HEADER], "claude");
+        assert_eq!(response.headers()[SERVED_MODEL_HEADER], "claude-sonnet-4-5");
+        assert!(body_text(response).await.contains("from claude"));
+        assert!(state.processor.metrics.render(&[]).contains(
+            "proxycast_failover_switches_total{from=\"openai\",to=\"claude\",reason=\"rate_limited\"} 1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_messages_response_rules_see_upstream_body() {
+        let upstream = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|| async {
+                Json(json!({
+                    "id": "msg_test",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-sonnet-4-5",
+                    "content": [{"type": "text", "text": "the secret is 42"}],
+                    "stop_reason": "end_turn",
+                    "usage": {"input_tokens": 3, "output_tokens": 5}
+                }))
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("claude");
+        add_credential(
+            &state,
+            ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+        state
+            .flow_interceptor
+            .update_config(crate::flow_monitor::InterceptConfig {
+                rules: vec![crate::flow_monitor::InterceptRule {
+                    name: "redact".to_string(),
+                    enabled: true,
+                    filter_expr: Some("~bs secret".to_string()),
+                    stage: InterceptType::Response,
+                    actions: vec![crate::flow_monitor::RuleAction::Respond {
+                        status: 200,
+                        body: json!({"redacted": true}),
+                    }],
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let request = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "tell me"}]
+        }))
+        .unwrap();
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(request)).await;
+
+        // 规则按上游真实响应内容匹配，并替换响应体
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body_text(response).await).unwrap(),
+            json!({"redacted": true})
+        );
+        let flows = state.flow_monitor.memory_store().read().await.get_recent(1);
+        let flow_response = flows[0].response.as_ref().unwrap();
+        assert_eq!(flow_response.body, json!({"redacted": true}));
+    }
+
+    fn sse_event(event: &str, data: serde_json::Value) -> String {
+        format!("event: {}\ndata: {}\n\n", event, data)
+    }
+
+    fn sse_text_delta(text: &str) -> String {
+        sse_event(
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}}),
+        )
+    }
+
+    fn sse_message_head() -> String {
+        format!(
+            "{}{}",
+            sse_event(
+                "message_start",
+                json!({"type": "message_start", "message": {"id": "msg_test", "type": "message", "role": "assistant", "content": [], "model": "claude-sonnet-4-5"}}),
+            ),
+            sse_event(
+                "content_block_start",
+                json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            )
+        )
+    }
+
+    /// Anthropic 兼容的 mock 上游：第一个请求输出部分文本后断开连接，
+    /// 之后的请求返回续写内容，并记录收到的请求体
+    async fn interrupted_anthropic_upstream(
+        requests: Arc<Mutex<Vec<serde_json::Value>>>,
+    ) -> String {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let upstream = axum::Router::new().route(
+            "/:name/v1/messages",
+            axum::routing::post(move |Json(body): Json<serde_json::Value>| {
+                let hits = hits.clone();
+                let requests = requests.clone();
+                async move {
+                    requests.lock().push(body);
+                    let first = hits.fetch_add(1, Ordering::SeqCst) == 0;
+                    let stream = async_stream::stream! {
+                        if first {
+                            yield Ok::<_, std::io::Error>(format!("{}{}", sse_message_head(), sse_text_delta("Hello,")));
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            yield Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "upstream reset"));
+                        } else {
+                            yield Ok(format!(
+                                "{}{}{}{}{}",
+                                sse_message_head(),
+                                sse_text_delta(" world!"),
+                                sse_event("content_block_stop", json!({"type": "content_block_stop", "index": 0})),
+                                sse_event("message_delta", json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 3}})),
+                                sse_event("message_stop", json!({"type": "message_stop"})),
+                            ));
+                        }
+                    };
+                    Response::builder()
+                        .header(header::CONTENT_TYPE, "text/event-stream")
+                        .body(Body::from_stream(stream))
+                        .unwrap()
+                }
+            }),
+        );
+        spawn_upstream(upstream).await
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_messages_resumes_interrupted_stream() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let base = interrupted_anthropic_upstream(requests.clone()).await;
+        let state = test_state("claude");
+        for name in ["a", "b"] {
+            add_credential(
+                &state,
+                ProviderType::Claude,
+                CredentialData::ClaudeKey {
+                    api_key: "sk-ant-test".to_string(),
+                    base_url: Some(format!("{}/{}", base, name)),
+                },
+            );
+        }
+
+        let request = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Say hello"}]
+        }))
+        .unwrap();
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_text(response).await;
+
+        // 客户端看到的是同一条消息：只有一个 message_start，文本不重复
+        assert_eq!(body.matches("event: message_start").count(), 1);
+        assert_eq!(body.matches("event: content_block_start").count(), 1);
+        assert!(!body.contains("event: error"));
+        let text: String = body
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter_map(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+            .filter_map(|v| {
+                v.pointer("/delta/text")
+                    .and_then(|t| t.as_str())
+                    .map(String::from)
+            })
+            .collect();
+        assert_eq!(text, "Hello, world!");
+        assert!(state.processor.metrics.render(&[]).contains(
+            "proxycast_failover_switches_total{from=\"claude\",to=\"claude\",reason=\"stream_interrupted\"} 1"
+        ));
+
+        // 续传请求携带已输出的文本作为 assistant 预填充
+        let requests = requests.lock();
+        assert_eq!(requests.len(), 2);
+        let last_message = requests[1]["messages"]
+            .as_array()
+            .unwrap()
+            .last()
+            .unwrap()
+            .clone();
+        assert_eq!(last_message["role"], "assistant");
+        assert_eq!(last_message["content"], "Hello,");
+    }
+
+    /// Anthropic 兼容的 mock 上游：第一个请求带 usage 输出部分文本后断开连接，
+    /// 之后的续传请求返回 `resume_status`（成功时返回续写内容），并按到达顺序记录凭证路径
+    async fn resumable_anthropic_upstream(
+        hit_names: Arc<Mutex<Vec<String>>>,
+        resume_status: StatusCode,
+    ) -> String {
+        let upstream = axum::Router::new().route(
+            "/:name/v1/messages",
+            axum::routing::post(
+                move |axum::extract::Path(name): axum::extract::Path<String>| {
+                    let hit_names = hit_names.clone();
+                    async move {
+                        let first = {
+                            let mut hit_names = hit_names.lock();
+                            hit_names.push(name);
+                            hit_names.len() == 1
+                        };
+                        let head = |input_tokens: u32| {
+                            format!(
+                                "{}{}",
+                                sse_event(
+                                    "message_start",
+                                    json!({"type": "message_start", "message": {"id": "msg_test", "usage": {"input_tokens": input_tokens, "output_tokens": 1}}}),
+                                ),
+                                sse_event(
+                                    "content_block_start",
+                                    json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+                                )
+                            )
+                        };
+                        if !first && !resume_status.is_success() {
+                            return (
+                                resume_status,
+                                Json(json!({"type": "error", "error": {"type": "invalid_request_error", "message": "prefill is not supported with thinking"}})),
+                            )
+                                .into_response();
+                        }
+                        let stream = async_stream::stream! {
+                            if first {
+                                yield Ok::<_, std::io::Error>(format!("{}{}", head(10), sse_text_delta("Hello,")));
+                                tokio::time::sleep(Duration::from_millis(50)).await;
+                                yield Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "upstream reset"));
+                            } else {
+                                yield Ok(format!(
+                                    "{}{}{}{}{}",
+                                    head(14),
+                                    sse_text_delta(" world!"),
+                                    sse_event("content_block_stop", json!({"type": "content_block_stop", "index": 0})),
+                                    sse_event("message_delta", json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 3}})),
+                                    sse_event("message_stop", json!({"type": "message_stop"})),
+                                ));
+                            }
+                        };
+                        Response::builder()
+                            .header(header::CONTENT_TYPE, "text/event-stream")
+                            .body(Body::from_stream(stream))
+                            .unwrap()
+                    }
+                },
+            ),
+        );
+        spawn_upstream(upstream).await
+    }
+
+    /// 添加两个指向 mock 上游不同路径的 Claude API Key 凭证，返回路径名到凭证 ID 的映射
+    fn add_resume_credentials(state: &AppState, base: &str) -> HashMap<String, String> {
+        ["a", "b"]
+            .into_iter()
+            .map(|name| {
+                let credential = add_credential(
+                    state,
+                    ProviderType::Claude,
+                    CredentialData::ClaudeKey {
+                        api_key: "sk-ant-test".to_string(),
+                        base_url: Some(format!("{}/{}", base, name)),
+                    },
+                );
+                (name.to_string(), credential.uuid)
+            })
+            .collect()
+    }
+
+    fn stream_request() -> AnthropicMessagesRequest {
+        serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Say hello"}]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_resume_charges_each_credential() {
+        let hit_names = Arc::new(Mutex::new(Vec::new()));
+        let base = resumable_anthropic_upstream(hit_names.clone(), StatusCode::OK).await;
+        let state = test_state("claude");
+        let credentials = add_resume_credentials(&state, &base);
+
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(stream_request())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!body_text(response).await.contains("event: error"));
+
+        // 每次上游尝试的用量计入各自的凭证
+        let hit_names = hit_names.lock().clone();
+        assert_eq!(hit_names.len(), 2);
+        let (first, resumed) = (&credentials[&hit_names[0]], &credentials[&hit_names[1]]);
+        let metrics = state.processor.metrics.render(&[]);
+        for (credential, input_tokens) in [(first, 10), (resumed, 14)] {
+            let line = format!(
+                "proxycast_tokens_total{{provider=\"claude\",model=\"claude-sonnet-4-5\",credential=\"{}\",direction=\"input\"}} {}",
+                credential, input_tokens
+            );
+            assert!(metrics.contains(&line), "missing {} in\n{}", line, metrics);
+        }
+        assert_eq!(state.processor.tokens.read().get_all().len(), 2);
+
+        // Flow 记录续传所用的凭证
+        let flows = state.flow_monitor.memory_store().read().await.get_recent(1);
+        assert_eq!(flows[0].metadata.retry_count, 1);
+        assert_eq!(
+            flows[0].metadata.credential_id.as_deref(),
+            Some(resumed.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_resume_passes_client_error_through() {
+        let hit_names = Arc::new(Mutex::new(Vec::new()));
+        let base = resumable_anthropic_upstream(hit_names.clone(), StatusCode::BAD_REQUEST).await;
+        let state = test_state("claude");
+        let credentials = add_resume_credentials(&state, &base);
+
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(stream_request())).await;
+        let body = body_text(response).await;
+
+        // 上游拒绝续传请求的错误转发给客户端，不再尝试其他凭证，也不标记凭证不健康
+        assert!(body.contains("event: error"));
+        assert!(body.contains("prefill is not supported with thinking"));
+        let hit_names = hit_names.lock().clone();
+        assert_eq!(hit_names.len(), 2);
+        let rejected = ProviderPoolDao::get_by_uuid(
+            &state.db.as_ref().unwrap().lock().unwrap(),
+            &credentials[&hit_names[1]],
+        )
+        .unwrap()
+        .unwrap();
+        assert!(rejected.is_healthy);
+        assert_eq!(rejected.error_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_messages_traces_routing_and_stream_failover() {
+        let exported = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+        let collector = axum::Router::new().route(
+            "/v1/traces",
+            axum::routing::post({
+                let exported = exported.clone();
+                move |Json(body): Json<serde_json::Value>| {
+                    let exported = exported.clone();
+                    async move {
+                        for resource in body["resourceSpans"].as_array().into_iter().flatten() {
+                            for scope in resource["scopeSpans"].as_array().into_iter().flatten() {
+                                exported.lock().extend(
+                                    scope["spans"].as_array().into_iter().flatten().cloned(),
+                                );
+                            }
+                        }
+                        Json(json!({}))
+                    }
+                }
+            }),
+        );
+        let collector = spawn_upstream(collector).await;
+        let base = interrupted_anthropic_upstream(Arc::new(Mutex::new(Vec::new()))).await;
+        let state = test_state("claude");
+        state
+            .processor
+            .tracer
+            .configure(&crate::config::OtelConfig {
+                enabled: true,
+                endpoint: format!("{}/v1/traces", collector),
+                flush_interval_ms: 50,
+                ..Default::default()
+            });
+        for name in ["a", "b"] {
+            add_credential(
+                &state,
+                ProviderType::Claude,
+                CredentialData::ClaudeKey {
+                    api_key: "sk-ant-test".to_string(),
+                    base_url: Some(format!("{}/{}", base, name)),
+                },
+            );
+        }
+
+        let request = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Say hello"}]
+        }))
+        .unwrap();
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        body_text(response).await;
+
+        let find = |name: &str| {
+            exported
+                .lock()
+                .iter()
+                .find(|span| span["name"] == name)
+                .cloned()
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while find("proxycast.stream").is_none() || find("POST /v1/messages").is_none() {
+            assert!(Instant::now() < deadline, "collector did not receive spans");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let root = find("POST /v1/messages").unwrap();
+        let routing = find("proxycast.routing").unwrap();
+        let credential_select = find("proxycast.credential_select").unwrap();
+        let call = find("proxycast.provider.call").unwrap();
+        let attempt = find("proxycast.provider.attempt").unwrap();
+        let failover = find("proxycast.provider.failover").unwrap();
+        // 路由 span 覆盖凭证选择
+        assert_eq!(routing["parentSpanId"], root["spanId"]);
+        assert_eq!(credential_select["parentSpanId"], routing["spanId"]);
+        let end = |span: &serde_json::Value| -> u64 {
+            span["endTimeUnixNano"].as_str().unwrap().parse().unwrap()
+        };
+        assert!(end(&routing) >= end(&credential_select));
+        // 上游尝试挂在调用 span 下，断流续传挂在尝试下
+        assert_eq!(call["parentSpanId"], root["spanId"]);
+        assert_eq!(attempt["parentSpanId"], call["spanId"]);
+        assert_eq!(failover["parentSpanId"], attempt["spanId"]);
+        assert_eq!(failover["status"]["code"], 1);
+
+        state
+            .processor
+            .tracer
+            .configure(&crate::config::OtelConfig::default());
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_messages_charges_streamed_usage() {
+        let upstream = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|| async {
+                let body = format!(
+                    "{}{}{}{}",
+                    sse_event(
+                        "message_start",
+                        json!({"type": "message_start", "message": {"id": "msg_test", "usage": {"input_tokens": 12, "output_tokens": 1, "cache_read_input_tokens": 100, "cache_creation_input_tokens": 40}}}),
+                    ),
+                    sse_text_delta("Hi"),
+                    sse_event("message_delta", json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 27}})),
+                    sse_event("message_stop", json!({"type": "message_stop"})),
+                );
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "text/event-stream")
+                    .body(Body::from(body))
+                    .unwrap()
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("claude");
+        add_credential(
+            &state,
+            ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+
+        let request = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{"role": "user", "content": "Say hi"}]
+        }))
+        .unwrap();
+        let response =
+            anthropic_messages(State(state.clone()), auth_headers(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        body_text(response).await;
+
+        // 按流中的实际 usage 计量，而不是估算值
+        let records = state.processor.tokens.read().get_all();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.source, crate::telemetry::TokenSource::Actual);
+        assert_eq!(record.input_tokens, 12);
+        assert_eq!(record.output_tokens, 27);
+        assert_eq!(record.cache_read_tokens, 100);
+        assert_eq!(record.cache_write_tokens, 40);
+    }
+}
@@ -537,7 +537,7 @@ async fn try_get_api_key_token(
     // 解密 API Key
     let api_key = state
         .api_key_service
-        .decrypt_api_key(&api_key_entry.api_key_encrypted)
+        .decrypt_api_key(&api_key_entry.id, &api_key_entry.api_key_encrypted)
         .map_err(|e| CredentialApiError {
             error: "decryption_error".to_string(),
             message: format!("API Key 解密失败: {}", e),
@@ -0,0 +1,559 @@
+//! Embeddings API 处理器
+//!
+//! 实现 OpenAI 兼容的 `/v1/embeddings` 端点，通过 `ProviderPoolService` 选择凭证。
+//!
+//! # 支持的后端
+//! - OpenAI 兼容 API Key（OpenAI、DeepSeek 等）：直接透传
+//! - Ollama：API Key Provider 中配置的 Ollama 地址，调用其 OpenAI 兼容的 `/v1/embeddings`
+//! - Gemini API Key：`embedContent` / `batchEmbedContents`
+//! - Vertex AI：`embedContent` / `batchEmbedContents`
+//!
+//! Token 用量记录到 `TokenTracker`；上游未返回 usage 时使用 tiktoken 估算。
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::converter::openai_to_gemini_embeddings::{
+    convert_embedding_request_to_gemini, convert_gemini_embeddings_to_openai, GeminiEmbedAction,
+};
+use crate::models::openai::EmbeddingRequest;
+use crate::models::provider_pool_model::{CredentialData, PoolProviderType, ProviderCredential};
+use crate::processor::RequestContext;
+use crate::providers::{
+    GeminiApiKeyCredential, GeminiApiKeyProvider, OpenAICustomProvider, VertexProvider,
+};
+use crate::server::handlers::authorize_client;
+use crate::server::{record_request_telemetry, record_token_usage_with_source, AppState};
+use crate::telemetry::{RequestStatus, TokenSource};
+
+use super::api::{acquire_concurrency_permit, select_provider_for_client};
+use super::provider_calls::cool_down_if_rate_limited;
+
+/// 上游调用结果：响应体与 prompt tokens（上游未返回时为 None）
+struct EmbeddingOutcome {
+    body: serde_json::Value,
+    prompt_tokens: Option<u32>,
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>, error_type: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message.into(),
+                "type": error_type
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 判断模型是否为 Gemini 系列 embeddings 模型
+fn is_gemini_embedding_model(model: &str) -> bool {
+    let model = model.strip_prefix("models/").unwrap_or(model);
+    model.starts_with("gemini-embedding")
+        || model.starts_with("text-embedding-00")
+        || model.starts_with("text-multilingual-embedding")
+        || model == "embedding-001"
+}
+
+/// 按模型名推断 embeddings Provider 候选（按优先级排列）
+///
+/// - Gemini embeddings 模型：gemini_api_key → vertex
+/// - OpenAI embeddings 模型（text-embedding-3-*, ada）：openai
+/// - 其他模型：客户端选择的 Provider → openai → ollama
+fn embedding_provider_candidates(model: &str, selected_provider: &str) -> Vec<String> {
+    let mut candidates: Vec<String> = if is_gemini_embedding_model(model) {
+        vec!["gemini_api_key".to_string(), "vertex".to_string()]
+    } else if model.starts_with("text-embedding-") {
+        vec!["openai".to_string()]
+    } else {
+        vec![
+            selected_provider.to_lowercase(),
+            "openai".to_string(),
+            "ollama".to_string(),
+        ]
+    };
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|provider| seen.insert(provider.clone()));
+    candidates
+}
+
+/// 凭证是否支持 embeddings
+fn supports_embeddings(credential: &CredentialData) -> bool {
+    matches!(
+        credential,
+        CredentialData::OpenAIKey { .. }
+            | CredentialData::GeminiApiKey { .. }
+            | CredentialData::VertexKey { .. }
+    )
+}
+
+/// 处理 embeddings 请求
+///
+/// # 端点
+/// `POST /v1/embeddings`
+///
+/// 可通过 `X-Provider-Id` 请求头指定 Provider（如 `ollama`、`gemini_api_key`）。
+pub async fn embeddings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut request): Json<EmbeddingRequest>,
+) -> Response {
+    let client_key = match authorize_client(&state, &headers, Some(&request.model)) {
+        Ok(client_key) => client_key,
+        Err(e) => {
+            state
+                .logs
+                .write()
+                .await
+                .add("warn", "Unauthorized request to /v1/embeddings");
+            return e;
+        }
+    };
+
+    if request.input.is_empty() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "input is required and cannot be empty",
+            "invalid_request_error",
+        );
+    }
+
+    let mut ctx = RequestContext::new(request.model.clone());
+    ctx.set_client_key(client_key);
+    let resolved_model = state.processor.resolve_model(&request.model).await;
+    ctx.set_resolved_model(resolved_model.clone());
+    request.model = resolved_model;
+
+    let db = match &state.db {
+        Some(db) => db,
+        None => {
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Database not available",
+                "server_error",
+            )
+        }
+    };
+
+    let provider_id_header = headers
+        .get("x-provider-id")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_lowercase());
+    let candidates = match provider_id_header {
+        Some(provider_id) => vec![provider_id],
+        None => {
+            let (selected_provider, _) = select_provider_for_client(&headers, &state).await;
+            embedding_provider_candidates(&request.model, &selected_provider)
+        }
+    };
+
+    // 依次尝试候选 Provider：先从凭证池选择，池中没有凭证时降级到 API Key Provider。
+    // 只在支持 embeddings 的凭证中选择，避免选中后丢弃的凭证占用熔断器的探测名额
+    let selected = candidates.iter().find_map(|provider| {
+        let pool_credential = state
+            .pool_service
+            .select_credential_matching(db, provider, Some(&request.model), &[], |c| {
+                supports_embeddings(&c.credential)
+            })
+            .ok()
+            .flatten();
+        let (credential, source) = match pool_credential {
+            Some(credential) => (credential, provider.as_str()),
+            None => {
+                let provider_type = provider.parse().unwrap_or(PoolProviderType::OpenAI);
+                let credential = state
+                    .api_key_service
+                    .get_fallback_credential(db, &provider_type, Some(provider))
+                    .ok()
+                    .flatten()?;
+                (credential, "api_key_provider")
+            }
+        };
+        supports_embeddings(&credential.credential).then_some((credential, source))
+    });
+
+    let Some((credential, source)) = selected else {
+        state.logs.write().await.add(
+            "error",
+            &format!(
+                "[EMBEDDINGS] request_id={} model={} 没有支持 embeddings 的凭证 (candidates={:?})",
+                ctx.request_id, request.model, candidates
+            ),
+        );
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "No embeddings-capable credentials available for model '{}'",
+                request.model
+            ),
+            "provider_unavailable",
+        );
+    };
+
+    // 没有使用首选 Provider 的凭证池时计为一次故障转移
+    if source != candidates[0] {
+        state
+            .processor
+            .metrics
+            .record_failover(&candidates[0], source, "no_pool_credential");
+    }
+
+    ctx.set_provider(credential.provider_type);
+    ctx.set_credential_id(credential.uuid.clone());
+    state.logs.write().await.add(
+        "info",
+        &format!(
+            "POST /v1/embeddings request_id={} model={} inputs={} provider={} credential={:?}",
+            ctx.request_id,
+            request.model,
+            request.input.texts().len(),
+            credential.provider_type,
+            credential.name
+        ),
+    );
+
+    // 许可在请求结束（函数返回）时释放
+    let _permit =
+        match acquire_concurrency_permit(&state, &headers, &ctx, &credential, None, false).await {
+            Ok(permit) => permit,
+            Err(response) => return response,
+        };
+
+    match call_embeddings_provider(&credential, &request).await {
+        Ok(outcome) => {
+            let _ = state
+                .pool_service
+                .mark_healthy(db, &credential.uuid, Some(&request.model));
+            let _ = state.pool_service.record_usage(db, &credential.uuid);
+            record_request_telemetry(&state, &ctx, RequestStatus::Success, None);
+
+            let (prompt_tokens, source) = match outcome.prompt_tokens {
+                Some(tokens) => (tokens, TokenSource::Actual),
+                None => (
+                    estimate_prompt_tokens(&state, &request),
+                    TokenSource::Estimated,
+                ),
+            };
+            record_token_usage_with_source(&state, &ctx, Some(prompt_tokens), None, source);
+
+            let mut body = outcome.body;
+            if source == TokenSource::Estimated {
+                body["usage"] = json!({
+                    "prompt_tokens": prompt_tokens,
+                    "total_tokens": prompt_tokens
+                });
+            }
+            Json(body).into_response()
+        }
+        Err((status, message)) => {
+            let rate_limited = cool_down_if_rate_limited(
+                &state,
+                &credential,
+                status.as_u16(),
+                None,
+                &message,
+                &request.model,
+            );
+            // 认证失败、5xx 和网络错误（映射为 502）计入凭证健康状态；
+            // 其他 4xx 是请求本身的问题，凭证可用，计为熔断器成功以释放探测名额后原样返回
+            if !rate_limited {
+                if is_credential_failure(status) {
+                    let _ = state
+                        .pool_service
+                        .mark_unhealthy(db, &credential.uuid, Some(&message));
+                } else {
+                    state
+                        .pool_service
+                        .circuit_breaker()
+                        .record_success(&credential.uuid);
+                    let _ = state.pool_service.mark_probe_healthy(
+                        db,
+                        &credential.uuid,
+                        Some(&request.model),
+                    );
+                }
+            }
+            record_request_telemetry(&state, &ctx, RequestStatus::Failed, Some(message.clone()));
+            state.logs.write().await.add(
+                "error",
+                &format!(
+                    "[EMBEDDINGS] request_id={} 上游调用失败: {}",
+                    ctx.request_id, message
+                ),
+            );
+            error_response(status, message, "api_error")
+        }
+    }
+}
+
+/// 上游错误是否说明凭证本身不可用
+fn is_credential_failure(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) || status.is_server_error()
+}
+
+fn estimate_prompt_tokens(state: &AppState, request: &EmbeddingRequest) -> u32 {
+    request
+        .input
+        .texts()
+        .iter()
+        .map(|text| state.token_counter.estimate_text(text, &request.model))
+        .sum()
+}
+
+/// 根据凭证类型调用上游 embeddings 接口
+async fn call_embeddings_provider(
+    credential: &ProviderCredential,
+    request: &EmbeddingRequest,
+) -> Result<EmbeddingOutcome, (StatusCode, String)> {
+    let upstream_error =
+        |e: Box<dyn std::error::Error + Send + Sync>| (StatusCode::BAD_GATEWAY, e.to_string());
+
+    match &credential.credential {
+        CredentialData::OpenAIKey { api_key, base_url } => {
+            let provider = OpenAICustomProvider::with_config(api_key.clone(), base_url.clone());
+            let payload = serde_json::to_value(request)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            let resp = provider
+                .embeddings(&payload)
+                .await
+                .map_err(upstream_error)?;
+            let status = resp.status();
+            let text = resp
+                .text()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+            if !status.is_success() {
+                return Err((
+                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                    text,
+                ));
+            }
+            let body: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Invalid upstream response: {e}"),
+                )
+            })?;
+            let prompt_tokens = body["usage"]["prompt_tokens"].as_u64().map(|t| t as u32);
+            Ok(EmbeddingOutcome {
+                body,
+                prompt_tokens,
+            })
+        }
+        CredentialData::GeminiApiKey {
+            api_key,
+            base_url,
+            excluded_models,
+        } => {
+            let gemini_credential =
+                GeminiApiKeyCredential::new(credential.uuid.clone(), api_key.clone())
+                    .with_base_url(base_url.clone())
+                    .with_excluded_models(excluded_models.clone());
+            if !gemini_credential.supports_model(&request.model) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Model '{}' is excluded for this credential", request.model),
+                ));
+            }
+
+            let (action, body) = convert_embedding_request_to_gemini(request);
+            let provider = GeminiApiKeyProvider::new();
+            let response = match action {
+                GeminiEmbedAction::EmbedContent => {
+                    provider
+                        .embed_content(&gemini_credential, &request.model, &body)
+                        .await
+                }
+                GeminiEmbedAction::BatchEmbedContents => {
+                    provider
+                        .batch_embed_contents(&gemini_credential, &request.model, &body)
+                        .await
+                }
+            }
+            .map_err(upstream_error)?;
+            gemini_outcome(&response, request)
+        }
+        CredentialData::VertexKey {
+            api_key,
+            base_url,
+            model_aliases,
+        } => {
+            let mut provider = VertexProvider::with_config(api_key.clone(), base_url.clone());
+            for (alias, model) in model_aliases {
+                provider = provider.with_model_alias(alias, model);
+            }
+
+            let (action, body) = convert_embedding_request_to_gemini(request);
+            let resp = provider
+                .embed_content(&request.model, action.as_str(), &body)
+                .await
+                .map_err(upstream_error)?;
+            let status = resp.status();
+            let text = resp
+                .text()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+            if !status.is_success() {
+                return Err((
+                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                    text,
+                ));
+            }
+            let response: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Invalid upstream response: {e}"),
+                )
+            })?;
+            gemini_outcome(&response, request)
+        }
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "This credential type does not support embeddings".to_string(),
+        )),
+    }
+}
+
+/// Gemini 响应转换为 OpenAI 格式；Gemini 不返回 usage，由调用方估算后回填
+fn gemini_outcome(
+    response: &serde_json::Value,
+    request: &EmbeddingRequest,
+) -> Result<EmbeddingOutcome, (StatusCode, String)> {
+    let mut converted = convert_gemini_embeddings_to_openai(
+        response,
+        &request.model,
+        request.encoding_format.as_deref(),
+        0,
+    )
+    .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    converted.model = request.model.clone();
+
+    let body = serde_json::to_value(&converted)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(EmbeddingOutcome {
+        body,
+        prompt_tokens: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{auth_headers, body_text, spawn_upstream, test_state};
+
+    #[test]
+    fn test_gemini_models_route_to_gemini_providers() {
+        assert_eq!(
+            embedding_provider_candidates("gemini-embedding-001", "kiro"),
+            vec!["gemini_api_key", "vertex"]
+        );
+        assert_eq!(
+            embedding_provider_candidates("models/text-embedding-004", "kiro"),
+            vec!["gemini_api_key", "vertex"]
+        );
+    }
+
+    #[test]
+    fn test_openai_models_route_to_openai() {
+        assert_eq!(
+            embedding_provider_candidates("text-embedding-3-small", "kiro"),
+            vec!["openai"]
+        );
+    }
+
+    #[test]
+    fn test_other_models_try_selected_then_openai_then_ollama() {
+        assert_eq!(
+            embedding_provider_candidates("nomic-embed-text", "DeepSeek"),
+            vec!["deepseek", "openai", "ollama"]
+        );
+        assert_eq!(
+            embedding_provider_candidates("nomic-embed-text", "openai"),
+            vec!["openai", "ollama"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ollama_embeddings_use_api_key_provider_host() {
+        let upstream = axum::Router::new().route(
+            "/v1/embeddings",
+            axum::routing::post(|Json(body): Json<serde_json::Value>| async move {
+                Json(json!({
+                    "object": "list",
+                    "data": [{"object": "embedding", "index": 0, "embedding": [0.1, 0.2]}],
+                    "model": body["model"],
+                    "usage": {"prompt_tokens": 3, "total_tokens": 3}
+                }))
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("kiro");
+        let db = state.db.as_ref().unwrap();
+        state
+            .api_key_service
+            .initialize_system_providers(db)
+            .unwrap();
+        state
+            .api_key_service
+            .update_provider(
+                db,
+                "ollama",
+                None,
+                None,
+                Some(base),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        state
+            .api_key_service
+            .add_api_key(db, "ollama", "ollama", None)
+            .unwrap();
+
+        let request = serde_json::from_value(json!({
+            "model": "nomic-embed-text",
+            "input": "hello"
+        }))
+        .unwrap();
+        let response = embeddings(State(state.clone()), auth_headers(), Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["model"], "nomic-embed-text");
+        assert_eq!(body["data"][0]["embedding"], json!([0.1, 0.2]));
+        assert_eq!(body["usage"]["prompt_tokens"], 3);
+    }
+
+    #[test]
+    fn test_only_auth_and_server_errors_mark_credential_unhealthy() {
+        assert!(is_credential_failure(StatusCode::UNAUTHORIZED));
+        assert!(is_credential_failure(StatusCode::FORBIDDEN));
+        assert!(is_credential_failure(StatusCode::BAD_GATEWAY));
+        assert!(is_credential_failure(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_credential_failure(StatusCode::BAD_REQUEST));
+        assert!(!is_credential_failure(StatusCode::NOT_FOUND));
+        assert!(!is_credential_failure(StatusCode::UNPROCESSABLE_ENTITY));
+    }
+
+    #[test]
+    fn test_supports_embeddings() {
+        assert!(supports_embeddings(&CredentialData::OpenAIKey {
+            api_key: "k".to_string(),
+            base_url: None,
+        }));
+        assert!(!supports_embeddings(&CredentialData::KiroOAuth {
+            creds_file_path: "/tmp/x".to_string(),
+        }));
+    }
+}
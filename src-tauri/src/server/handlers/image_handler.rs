@@ -29,7 +29,7 @@ use crate::converter::openai_to_antigravity::{
 use crate::models::openai::ImageGenerationRequest;
 use crate::models::provider_pool_model::CredentialData;
 use crate::providers::AntigravityProvider;
-use crate::server::handlers::verify_api_key;
+use crate::server::handlers::authorize_client;
 use crate::server::AppState;
 
 /// 处理图像生成请求
@@ -66,8 +66,8 @@ pub async fn handle_image_generation(
     Json(request): Json<ImageGenerationRequest>,
 ) -> Response {
     // 验证 API Key
-    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
-        return e.into_response();
+    if let Err(e) = authorize_client(&state, &headers, Some(&request.model)) {
+        return e;
     }
 
     // 验证请求参数
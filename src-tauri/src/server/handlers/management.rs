@@ -4,11 +4,23 @@
 
 #![allow(dead_code)]
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::database::dao::client_api_key::ClientApiKey;
 use crate::database::dao::provider_pool::ProviderPoolDao;
+use crate::flow_monitor::{InterceptRule, PlaybackError, PlaybackOptions, SessionMode};
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::router::RouteExplainRequest;
 use crate::server::AppState;
+use crate::services::client_key_service::{CreateClientKeyRequest, UpdateClientKeyRequest};
+use crate::services::provider_pool_service::ProviderPoolService;
+use crate::telemetry::GaugeSample;
 
 // ============ Types ============
 
@@ -149,6 +161,39 @@ pub struct UpdateConfigResponse {
     pub message: String,
 }
 
+/// 客户端 API Key 列表响应
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientKeysListResponse {
+    pub keys: Vec<ClientApiKey>,
+    pub total: usize,
+}
+
+/// 客户端 API Key 操作响应
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientKeyResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<ClientApiKey>,
+    /// 明文 key（仅在创建时返回一次）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+impl ClientKeyResponse {
+    fn error(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<Self>) {
+        (
+            status,
+            Json(Self {
+                success: false,
+                message: message.into(),
+                key: None,
+                api_key: None,
+            }),
+        )
+    }
+}
+
 // ============ Handlers ============
 
 /// GET /v0/management/status - 获取服务器状态
@@ -586,3 +631,527 @@ pub async fn management_update_config(
         )
     }
 }
+
+/// GET /v0/management/client-keys - 获取客户端 API Key 列表
+pub async fn management_list_client_keys(State(state): State<AppState>) -> impl IntoResponse {
+    let keys = state.client_keys.list_keys();
+    let total = keys.len();
+    Json(ClientKeysListResponse { keys, total })
+}
+
+/// POST /v0/management/client-keys - 创建客户端 API Key
+pub async fn management_create_client_key(
+    State(state): State<AppState>,
+    Json(request): Json<CreateClientKeyRequest>,
+) -> impl IntoResponse {
+    let Some(db) = state.db.as_ref() else {
+        return ClientKeyResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Database not available");
+    };
+
+    match state.client_keys.create_key(db, request) {
+        Ok((key, plaintext)) => {
+            tracing::info!(
+                "[MANAGEMENT] Created client key: {} ({})",
+                key.label,
+                key.id
+            );
+            (
+                StatusCode::CREATED,
+                Json(ClientKeyResponse {
+                    success: true,
+                    message:
+                        "Client key created; store the api_key now, it will not be shown again"
+                            .to_string(),
+                    key: Some(key),
+                    api_key: Some(plaintext),
+                }),
+            )
+        }
+        Err(e) => ClientKeyResponse::error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// PUT /v0/management/client-keys/:id - 更新客户端 API Key
+pub async fn management_update_client_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateClientKeyRequest>,
+) -> impl IntoResponse {
+    let Some(db) = state.db.as_ref() else {
+        return ClientKeyResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Database not available");
+    };
+
+    match state.client_keys.update_key(db, &id, request) {
+        Ok(Some(key)) => (
+            StatusCode::OK,
+            Json(ClientKeyResponse {
+                success: true,
+                message: "Client key updated".to_string(),
+                key: Some(key),
+                api_key: None,
+            }),
+        ),
+        Ok(None) => ClientKeyResponse::error(
+            StatusCode::NOT_FOUND,
+            format!("Client key not found: {}", id),
+        ),
+        Err(e) => ClientKeyResponse::error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// DELETE /v0/management/client-keys/:id - 删除客户端 API Key
+pub async fn management_delete_client_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Some(db) = state.db.as_ref() else {
+        return ClientKeyResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Database not available");
+    };
+
+    match state.client_keys.delete_key(db, &id) {
+        Ok(true) => {
+            tracing::info!("[MANAGEMENT] Deleted client key: {}", id);
+            (
+                StatusCode::OK,
+                Json(ClientKeyResponse {
+                    success: true,
+                    message: "Client key deleted".to_string(),
+                    key: None,
+                    api_key: None,
+                }),
+            )
+        }
+        Ok(false) => ClientKeyResponse::error(
+            StatusCode::NOT_FOUND,
+            format!("Client key not found: {}", id),
+        ),
+        Err(e) => ClientKeyResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// GET /v0/management/response-cache - 获取响应缓存统计
+pub async fn management_response_cache_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.response_cache.stats(state.db.as_ref()))
+}
+
+/// DELETE /v0/management/response-cache - 清空响应缓存
+pub async fn management_clear_response_cache(State(state): State<AppState>) -> impl IntoResponse {
+    let Some(db) = state.db.as_ref() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"success": false, "message": "Database not available"})),
+        );
+    };
+
+    match state.response_cache.clear(db) {
+        Ok(removed) => {
+            tracing::info!("[MANAGEMENT] Cleared response cache: {} entries", removed);
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"success": true, "removed": removed})),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"success": false, "message": e})),
+        ),
+    }
+}
+
+/// POST /v0/management/routing/explain - 解释路由规则匹配过程（dry-run）
+pub async fn management_explain_routing(
+    State(state): State<AppState>,
+    Json(request): Json<RouteExplainRequest>,
+) -> impl IntoResponse {
+    let resolved_model = state.processor.resolve_model(&request.model).await;
+    let features = match request.features(&resolved_model, &state.token_counter) {
+        Ok(features) => features,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"success": false, "message": e})),
+            )
+                .into_response();
+        }
+    };
+
+    let fallback = match state
+        .endpoint_providers
+        .read()
+        .await
+        .get_provider(features.client_type.config_key())
+    {
+        Some(provider) => provider.clone(),
+        None => state.default_provider.read().await.clone(),
+    };
+    let explanation = state.processor.router.read().await.explain(&features);
+    Json(explanation.with_fallback_provider(&fallback)).into_response()
+}
+
+/// GET /v0/management/circuit-breakers - 获取熔断器状态
+pub async fn management_circuit_breakers(State(state): State<AppState>) -> impl IntoResponse {
+    let registry = state.pool_service.circuit_breaker();
+    Json(serde_json::json!({
+        "enabled": registry.config().enabled,
+        "open": registry.open_count(),
+        "breakers": registry.snapshot(),
+    }))
+}
+
+/// GET /v0/management/budgets - 获取预算规则在当前统计周期的花费
+pub async fn management_budgets(State(state): State<AppState>) -> impl IntoResponse {
+    let budget = &state.processor.budget;
+    let config = budget.config();
+    Json(serde_json::json!({
+        "enabled": config.enabled,
+        "currency": config.currency,
+        "budgets": budget.status(),
+    }))
+}
+
+/// GET /v0/management/playback - 获取会话录制/回放状态
+pub async fn management_playback_status(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "sessions": state.flow_monitor.playback().status(),
+    }))
+}
+
+/// 创建录制会话请求
+#[derive(Debug, Deserialize)]
+pub struct CreateRecordingSessionRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// POST /v0/management/playback/sessions - 创建会话并开始录制
+pub async fn management_create_recording_session(
+    State(state): State<AppState>,
+    Json(request): Json<CreateRecordingSessionRequest>,
+) -> impl IntoResponse {
+    match state
+        .flow_monitor
+        .playback()
+        .record_new_session(&request.name, request.description.as_deref())
+    {
+        Ok(status) => {
+            tracing::info!(
+                "[MANAGEMENT] Recording to new session: {}",
+                status.session_id
+            );
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({"success": true, "session": status})),
+            )
+        }
+        Err(e) => playback_error(e),
+    }
+}
+
+/// 切换会话模式请求
+#[derive(Debug, Deserialize)]
+pub struct SetSessionModeRequest {
+    pub mode: SessionMode,
+    /// 回放选项（仅回放模式使用）
+    #[serde(flatten)]
+    pub options: PlaybackOptions,
+}
+
+/// PUT /v0/management/playback/:session_id - 将会话切换为录制或回放模式
+pub async fn management_set_session_mode(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+    Json(request): Json<SetSessionModeRequest>,
+) -> impl IntoResponse {
+    let result = match request.mode {
+        SessionMode::Record => state.flow_monitor.playback().record(&session_id),
+        SessionMode::Playback => {
+            state
+                .flow_monitor
+                .start_playback(&session_id, request.options)
+                .await
+        }
+    };
+
+    match result {
+        Ok(status) => {
+            tracing::info!(
+                "[MANAGEMENT] Session {} switched to {:?}",
+                session_id,
+                status.mode
+            );
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"success": true, "session": status})),
+            )
+        }
+        Err(e) => playback_error(e),
+    }
+}
+
+/// DELETE /v0/management/playback/:session_id - 停止会话的录制/回放
+pub async fn management_stop_session_mode(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> impl IntoResponse {
+    match state.flow_monitor.playback().stop(&session_id) {
+        Some(status) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"success": true, "session": status})),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "success": false,
+                "message": format!("Session is not recording or playing back: {}", session_id),
+            })),
+        ),
+    }
+}
+
+fn playback_error(e: PlaybackError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match e {
+        PlaybackError::SessionNotFound(_) => StatusCode::NOT_FOUND,
+        PlaybackError::NoPlayableFlows(_)
+        | PlaybackError::InvalidMatcher(_)
+        | PlaybackError::InvalidSpeed(_) => StatusCode::BAD_REQUEST,
+        PlaybackError::SessionManagerUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        PlaybackError::Session(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(serde_json::json!({"success": false, "message": e.to_string()})),
+    )
+}
+
+/// GET /v0/management/alerts - 获取告警规则状态和最近的告警
+pub async fn management_alerts(State(state): State<AppState>) -> impl IntoResponse {
+    let alerts = &state.processor.alerts;
+    Json(serde_json::json!({
+        "enabled": alerts.is_enabled(),
+        "rules": alerts.status(),
+        "recent": alerts.recent_events(),
+    }))
+}
+
+/// POST /v0/management/alerts/test - 向所有告警 Webhook 发送测试消息
+pub async fn management_test_alert_webhooks(State(state): State<AppState>) -> impl IntoResponse {
+    let results = state.processor.alerts.test_webhooks().await;
+    if results.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "success": false,
+                "message": "No alert webhooks configured",
+            })),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "success": results.iter().all(|r| r.success),
+            "results": results,
+        })),
+    )
+}
+
+/// GET /v0/management/intercept/rules - 获取自动拦截规则
+pub async fn management_get_intercept_rules(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "rules": state.flow_interceptor.config().await.rules,
+    }))
+}
+
+/// 更新自动拦截规则请求
+#[derive(Debug, Deserialize)]
+pub struct UpdateInterceptRulesRequest {
+    pub rules: Vec<InterceptRule>,
+}
+
+/// PUT /v0/management/intercept/rules - 替换全部自动拦截规则
+pub async fn management_update_intercept_rules(
+    State(state): State<AppState>,
+    Json(request): Json<UpdateInterceptRulesRequest>,
+) -> impl IntoResponse {
+    let mut config = state.flow_interceptor.config().await;
+    let count = request.rules.len();
+    config.rules = request.rules;
+
+    match state.flow_interceptor.update_config(config).await {
+        Ok(()) => {
+            tracing::info!("[MANAGEMENT] Intercept rules updated: {} rule(s)", count);
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "success": true,
+                    "message": format!("{} intercept rule(s) applied", count),
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"success": false, "message": e.to_string()})),
+        ),
+    }
+}
+
+/// 健康检查历史查询参数
+#[derive(Debug, Deserialize)]
+pub struct HealthHistoryQuery {
+    /// 返回条数（默认 20）
+    #[serde(default = "default_health_history_limit")]
+    pub limit: usize,
+}
+
+fn default_health_history_limit() -> usize {
+    20
+}
+
+/// GET /v0/management/credentials/:uuid/health-history - 获取凭证健康检查历史
+pub async fn management_credential_health_history(
+    State(state): State<AppState>,
+    Path(uuid): Path<String>,
+    Query(query): Query<HealthHistoryQuery>,
+) -> impl IntoResponse {
+    let Some(db) = state.db.as_ref() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"success": false, "message": "Database not available"})),
+        );
+    };
+
+    let health = match state.pool_service.get_credential_health(db, &uuid) {
+        Ok(Some(health)) => health,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "success": false,
+                    "message": format!("Credential not found: {}", uuid),
+                })),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"success": false, "message": e})),
+            );
+        }
+    };
+
+    match state
+        .pool_service
+        .get_health_history(db, &uuid, query.limit)
+    {
+        Ok(history) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "success": true,
+                "is_healthy": health.is_healthy,
+                "history": history,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"success": false, "message": e})),
+        ),
+    }
+}
+
+/// GET /metrics - Prometheus 指标
+pub async fn management_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut gauges = Vec::new();
+
+    // 凭证池健康状态
+    if let Some(ref db) = state.db {
+        if let Ok(conn) = db.lock() {
+            if let Ok(pool_credentials) = ProviderPoolDao::get_all(&conn) {
+                gauges = credential_gauges(&state.pool_service, &pool_credentials);
+            }
+        }
+    }
+
+    gauges.push(GaugeSample::new(
+        "proxycast_active_flows",
+        "Number of in-flight requests tracked by the flow monitor.",
+        Vec::new(),
+        state.flow_monitor.active_flow_count().await as f64,
+    ));
+
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            crate::telemetry::PROMETHEUS_CONTENT_TYPE,
+        )],
+        state.processor.metrics.render(&gauges),
+    )
+}
+
+/// 根据凭证池生成健康状态指标
+fn credential_gauges(
+    pool_service: &ProviderPoolService,
+    credentials: &[ProviderCredential],
+) -> Vec<GaugeSample> {
+    let mut gauges = Vec::new();
+    let mut pool_sizes: std::collections::BTreeMap<(String, &'static str), u64> =
+        std::collections::BTreeMap::new();
+
+    for cred in credentials {
+        let provider_type = cred.provider_type.to_string();
+        let labels = vec![
+            ("provider_type", provider_type.clone()),
+            ("credential", cred.uuid.clone()),
+            ("name", cred.name.clone().unwrap_or_default()),
+        ];
+        // 被限流、配额耗尽或风控冷却中的凭证暂时退出轮询
+        let in_cooldown = pool_service.is_cooling_down(&cred.uuid);
+        let pool_state = if cred.is_disabled {
+            "disabled"
+        } else if cred.is_healthy {
+            "healthy"
+        } else {
+            "unhealthy"
+        };
+        *pool_sizes.entry((provider_type, pool_state)).or_default() += 1;
+
+        gauges.push(GaugeSample::new(
+            "proxycast_credential_healthy",
+            "Whether the pool credential is healthy (1) or not (0).",
+            labels.clone(),
+            if cred.is_healthy { 1.0 } else { 0.0 },
+        ));
+        gauges.push(GaugeSample::new(
+            "proxycast_credential_cooldown",
+            "Whether the pool credential is cooling down after rate limiting, quota exhaustion or risk control.",
+            labels.clone(),
+            if in_cooldown { 1.0 } else { 0.0 },
+        ));
+        gauges.push(GaugeSample::new(
+            "proxycast_credential_errors",
+            "Consecutive error count of the pool credential.",
+            labels.clone(),
+            cred.error_count as f64,
+        ));
+        gauges.push(GaugeSample::new(
+            "proxycast_credential_usage",
+            "Total number of requests served by the pool credential.",
+            labels,
+            cred.usage_count as f64,
+        ));
+    }
+
+    for ((provider_type, pool_state), count) in pool_sizes {
+        gauges.push(GaugeSample::new(
+            "proxycast_credential_pool_size",
+            "Number of pool credentials by provider type and state.",
+            vec![
+                ("provider_type", provider_type),
+                ("state", pool_state.to_string()),
+            ],
+            count as f64,
+        ));
+    }
+
+    gauges
+}
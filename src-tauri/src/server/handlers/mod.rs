@@ -4,16 +4,26 @@
 
 pub mod api;
 pub mod credentials_api;
+pub mod embeddings;
 pub mod image_handler;
 pub mod kiro_credential;
 pub mod management;
+pub mod playback;
 pub mod provider_calls;
+pub mod response_cache;
+pub mod responses;
+pub mod usage_meter;
 pub mod websocket;
 
 pub use api::*;
 pub use credentials_api::*;
+pub use embeddings::*;
 pub use image_handler::*;
 pub use kiro_credential::*;
 pub use management::*;
+pub use playback::*;
 pub use provider_calls::*;
+pub use response_cache::*;
+pub use responses::*;
+pub use usage_meter::*;
 pub use websocket::*;
@@ -0,0 +1,193 @@
+//! 会话录制/回放处理
+//!
+//! 在 `/v1/chat/completions` 和 `/v1/messages` 中、响应缓存之前检查会话状态：
+//! - 录制：在请求上下文中记下会话，Flow 完成后加入会话
+//! - 回放命中：直接返回录制的响应（流式响应按录制时的 chunk 间隔发送），并记录一条回放 Flow
+//! - 回放未命中：默认返回 404，不调用上游
+//!
+//! 会话通过 `x-proxycast-session` 请求头指定，未携带该请求头的请求照常处理。
+
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::flow_monitor::{
+    FlowMetadata, FlowMonitor, FlowType, LLMFlow, LLMRequest, LLMResponse, PlaybackDecision,
+    PlaybackReply, ReplayBody, PLAYBACK_HEADER, SESSION_HEADER,
+};
+use crate::processor::RequestContext;
+use crate::server::AppState;
+
+use super::response_cache::extract_json_content;
+
+/// 回放查找结果
+pub enum PlaybackLookup {
+    /// 回放命中或未命中错误，直接返回给客户端
+    Hit(Response),
+    /// 照常处理（可能已在上下文中标记录制会话）
+    Continue,
+}
+
+/// 按会话录制/回放状态处理请求
+///
+/// # Arguments
+/// * `request` - 别名解析和路由之后的请求体
+/// * `flow` - 根据请求上下文构建用于匹配表达式和记录回放 Flow 的请求与元数据
+pub async fn lookup_playback(
+    state: &AppState,
+    headers: &HeaderMap,
+    ctx: &mut RequestContext,
+    endpoint: &str,
+    request: &serde_json::Value,
+    flow: impl FnOnce(&RequestContext) -> (LLMRequest, FlowMetadata),
+) -> PlaybackLookup {
+    let playback = state.flow_monitor.playback();
+    if !playback.is_active() {
+        return PlaybackLookup::Continue;
+    }
+
+    let (llm_request, metadata) = flow(ctx);
+    let request_id = ctx.request_id.clone();
+    let decision = playback.decide(
+        headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()),
+        endpoint,
+        request,
+        || {
+            LLMFlow::new(
+                request_id,
+                flow_type_of(endpoint),
+                llm_request.clone(),
+                metadata.clone(),
+            )
+        },
+    );
+
+    match decision {
+        PlaybackDecision::Passthrough => PlaybackLookup::Continue,
+        PlaybackDecision::Record(session_id) => {
+            ctx.set_recording_session(Some(session_id));
+            PlaybackLookup::Continue
+        }
+        PlaybackDecision::Miss { session_id } => {
+            state.logs.write().await.add(
+                "warn",
+                &format!(
+                    "[PLAYBACK] request_id={} miss session={} endpoint={}",
+                    ctx.request_id, session_id, endpoint
+                ),
+            );
+            if let Some(trace) = &ctx.trace {
+                trace.set_attribute("proxycast.playback_hit", false);
+            }
+            let body = serde_json::json!({
+                "error": {
+                    "type": "playback_miss",
+                    "message": format!(
+                        "No recorded response in session {} matches this request",
+                        session_id
+                    ),
+                }
+            });
+            PlaybackLookup::Hit((StatusCode::NOT_FOUND, Json(body)).into_response())
+        }
+        PlaybackDecision::Replay(reply) => {
+            state.logs.write().await.add(
+                "info",
+                &format!(
+                    "[PLAYBACK] request_id={} hit session={} source_flow={} endpoint={}",
+                    ctx.request_id, reply.session_id, reply.source_flow_id, endpoint
+                ),
+            );
+            if let Some(trace) = &ctx.trace {
+                trace.set_attribute("proxycast.playback_hit", true);
+                trace.set_ok();
+            }
+
+            let mut metadata = metadata;
+            metadata.playback_of = Some(reply.source_flow_id.clone());
+            let flow_id = state.flow_monitor.start_flow(llm_request, metadata).await;
+            PlaybackLookup::Hit(
+                build_replay_response(state.flow_monitor.clone(), flow_id, reply).await,
+            )
+        }
+    }
+}
+
+/// 端点对应的 Flow 类型
+fn flow_type_of(endpoint: &str) -> FlowType {
+    if endpoint == "/v1/messages" {
+        FlowType::AnthropicMessages
+    } else {
+        FlowType::ChatCompletions
+    }
+}
+
+/// 构建回放响应；流式响应按 chunk 间隔发送，发送完毕后完成回放 Flow
+async fn build_replay_response(
+    monitor: Arc<FlowMonitor>,
+    flow_id: Option<String>,
+    reply: PlaybackReply,
+) -> Response {
+    let status = StatusCode::from_u16(reply.status_code).unwrap_or(StatusCode::OK);
+    let builder = Response::builder()
+        .status(status)
+        .header(PLAYBACK_HEADER, reply.source_flow_id.as_str());
+
+    let response = match reply.body {
+        ReplayBody::Json(body) => {
+            if let Some(flow_id) = flow_id {
+                let response = LLMResponse {
+                    status_code: reply.status_code,
+                    content: extract_json_content(&body),
+                    size_bytes: body.to_string().len(),
+                    body: body.clone(),
+                    ..LLMResponse::default()
+                };
+                monitor.complete_flow(&flow_id, Some(response)).await;
+            }
+            builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+        }
+        ReplayBody::Stream(chunks) => {
+            if let Some(flow_id) = &flow_id {
+                monitor.set_streaming(flow_id, reply.format).await;
+            }
+            let stream = async_stream::stream! {
+                for chunk in chunks {
+                    if !chunk.delay.is_zero() {
+                        tokio::time::sleep(chunk.delay).await;
+                    }
+                    if let Some(flow_id) = &flow_id {
+                        monitor
+                            .process_chunk(flow_id, chunk.event.as_deref(), &chunk.data)
+                            .await;
+                    }
+                    yield Ok::<_, std::convert::Infallible>(Bytes::from(chunk.to_sse()));
+                }
+                if let Some(flow_id) = &flow_id {
+                    monitor.complete_flow(flow_id, None).await;
+                }
+            };
+            builder
+                .header(header::CONTENT_TYPE, "text/event-stream")
+                .header(header::CACHE_CONTROL, "no-cache")
+                .body(Body::from_stream(stream))
+        }
+    };
+
+    response.unwrap_or_else(|e| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(format!(
+                "Failed to build playback response: {}",
+                e
+            )))
+            .unwrap_or_default()
+    })
+}
@@ -20,6 +20,13 @@
 //! 3. 使用 `AnthropicSseGenerator` 转换为 Anthropic SSE 格式
 //! 4. 通过 `FlowMonitor.process_chunk()` 记录每个 chunk
 //!
+//! # 断流续传
+//!
+//! Kiro 凭证的流和 Claude / Anthropic API Key 凭证透传的 Anthropic SSE 流在中途断开时，
+//! 以已输出的文本作为 assistant 预填充换用其他凭证续传（见 `resume_kiro_stream()` 和
+//! `resume_anthropic_sse()`）。续传和首次尝试一样获取并发许可、跳过超出预算的凭证并记录到 Flow。
+//! OpenAI 格式的 SSE 流不支持 assistant 预填充，中途断开时直接向客户端返回错误事件。
+//!
 //! # 错误处理
 //!
 //! 流式传输期间的错误处理：
@@ -43,7 +50,7 @@
 
 use axum::{
     body::Body,
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -55,25 +62,242 @@ use crate::converter::openai_to_antigravity::{
 };
 use crate::flow_monitor::models::{FlowError, FlowErrorType};
 use crate::flow_monitor::stream_rebuilder::StreamFormat;
-use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::anthropic::{AnthropicMessage, AnthropicMessagesRequest};
 use crate::models::openai::ChatCompletionRequest;
 use crate::models::provider_pool_model::{CredentialData, ProviderCredential};
+use crate::processor::RequestContext;
 use crate::providers::{
     AntigravityApiError, AntigravityProvider, ClaudeCustomProvider, CodexProvider, IFlowProvider,
-    KiroProvider, OpenAICustomProvider, VertexProvider,
+    KiroProvider, OpenAICustomProvider, ProviderError, VertexProvider,
 };
+use crate::resilience::{RequestPermit, RequestPriority};
 use crate::server::AppState;
 use crate::server_utils::{
     build_anthropic_response, build_anthropic_stream_response, build_error_response,
     build_error_response_with_status, parse_cw_response, safe_truncate, CWParsedResponse,
 };
 use crate::session::store_thought_signature;
-use crate::stream::{PipelineConfig, StreamPipeline};
+use crate::stream::{AnthropicSseRelay, PipelineConfig, StreamPipeline};
 use crate::streaming::traits::StreamingProvider;
 use crate::streaming::{
     StreamConfig, StreamContext, StreamError, StreamFormat as StreamingFormat, StreamManager,
     StreamResponse,
 };
+use crate::telemetry::{current_span, current_span_child, in_span, SpanGuard, SpanParent};
+
+use super::api::request_priority;
+use super::usage_meter::{record_metered_usage, MeteredPerAttempt, UsageMeter};
+
+/// 上游返回失败响应时，限流响应按上游给出的重置时间冷却凭证
+///
+/// 返回是否为限流响应（429 / 402 / 529）；限流由冷却处理，调用方不再将其计入健康度
+pub(crate) fn cool_down_if_rate_limited(
+    state: &AppState,
+    credential: &ProviderCredential,
+    status: u16,
+    headers: Option<&reqwest::header::HeaderMap>,
+    body: &str,
+    model: &str,
+) -> bool {
+    state
+        .pool_service
+        .mark_rate_limited(&credential.uuid, status, headers, body, Some(model))
+        .is_some()
+}
+
+/// 流式调用返回限流错误时冷却凭证（拿不到响应头，按默认冷却时间），返回是否为限流错误
+pub(crate) fn cool_down_on_provider_error(
+    state: &AppState,
+    credential: &ProviderCredential,
+    error: &ProviderError,
+    model: &str,
+) -> bool {
+    match error {
+        ProviderError::RateLimitError(message) => {
+            cool_down_if_rate_limited(state, credential, 429, None, message, model)
+        }
+        _ => false,
+    }
+}
+
+/// 记录凭证本次调用的首字节延迟或失败，供 EWMA 延迟优先策略选择凭证
+///
+/// 延迟统一取首字节时间：从发起调用到响应体的第一个数据块。非流式响应在上游生成完毕后
+/// 才返回响应体，其首字节时间即完整响应时间。5xx、429 以及首个数据块读取失败计为失败，
+/// 其他 4xx 是请求本身的问题，不计入
+fn track_credential_latency(
+    state: &AppState,
+    credential: &ProviderCredential,
+    started: std::time::Instant,
+    response: Response,
+) -> Response {
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        state
+            .pool_service
+            .record_latency_failure(&credential.uuid, started.elapsed().as_millis() as u64);
+        return response;
+    }
+    if !status.is_success() {
+        return response;
+    }
+
+    let pool_service = state.pool_service.clone();
+    let uuid = credential.uuid.clone();
+    let (parts, body) = response.into_parts();
+    let mut upstream = body.into_data_stream();
+    let stream = async_stream::stream! {
+        let mut first_chunk = true;
+        while let Some(chunk) = upstream.next().await {
+            if std::mem::take(&mut first_chunk) {
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+                match &chunk {
+                    Ok(_) => pool_service.record_latency(&uuid, elapsed_ms),
+                    Err(_) => pool_service.record_latency_failure(&uuid, elapsed_ms),
+                }
+            }
+            yield chunk;
+        }
+    };
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+/// 在当前尝试 span 下开启一次刷新 Token 后重试的 span，`trigger` 为触发重试的上游错误
+fn token_refresh_retry_span(credential: &ProviderCredential, trigger: &str) -> SpanGuard {
+    let mut span = current_span_child("proxycast.provider.retry");
+    span.set_attribute("proxycast.credential_id", credential.uuid.as_str());
+    span.set_attribute("proxycast.retry.reason", "token_refresh");
+    span.set_attribute("proxycast.retry.trigger", trigger);
+    span
+}
+
+/// 断流续传沿用的原始请求信息
+///
+/// 续传请求和首次尝试走同样的准入和计量：按原请求的优先级获取并发许可、
+/// 跳过超出凭证预算的凭证，续传流的用量计入续传所用的凭证
+#[derive(Debug, Clone)]
+pub struct ResumeScope {
+    /// 原始请求上下文
+    pub ctx: RequestContext,
+    /// 原始请求的排队优先级
+    pub priority: RequestPriority,
+}
+
+impl ResumeScope {
+    pub fn new(state: &AppState, headers: &HeaderMap, ctx: &RequestContext) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            priority: request_priority(state, headers, ctx),
+        }
+    }
+
+    /// 某次上游尝试的请求上下文，用量按该尝试的凭证和模型计入
+    fn attempt_context(&self, credential: &ProviderCredential, model: &str) -> RequestContext {
+        let mut ctx = self.ctx.clone();
+        ctx.set_provider(credential.provider_type);
+        ctx.set_credential_id(credential.uuid.clone());
+        ctx.set_resolved_model(model.to_string());
+        ctx
+    }
+}
+
+/// 为断流续传选择凭证并获取并发许可
+///
+/// 排除已尝试过和超出凭证预算的凭证，只选择满足 `accept` 的凭证；
+/// 排队获取许可失败时归还熔断器的探测名额，换下一个凭证
+async fn admit_resume_credential(
+    state: &AppState,
+    provider: &str,
+    model: &str,
+    scope: &ResumeScope,
+    tried_credentials: &mut Vec<String>,
+    accept: impl Fn(&ProviderCredential) -> bool,
+) -> Option<(ProviderCredential, Option<RequestPermit>)> {
+    let db = state.db.as_ref()?;
+    let over_budget = state.processor.budget.over_budget_credentials(model);
+    let limiter = state.pool_service.concurrency();
+    loop {
+        let mut exclude = tried_credentials.clone();
+        exclude.extend(over_budget.iter().map(|(id, _)| id.clone()));
+        let credential = match state.pool_service.select_credential_matching(
+            db,
+            provider,
+            Some(model),
+            &exclude,
+            &accept,
+        ) {
+            Ok(Some(credential)) => credential,
+            Ok(None) => {
+                tracing::warn!(
+                    "[STREAM_FAILOVER] 没有其他可用的 {} 凭证，放弃续传",
+                    provider
+                );
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("[STREAM_FAILOVER] 选择凭证失败: {}", e);
+                return None;
+            }
+        };
+        tried_credentials.push(credential.uuid.clone());
+
+        if !limiter.is_enabled() {
+            return Some((credential, None));
+        }
+        let provider_type = credential.provider_type.to_string();
+        match limiter
+            .acquire(&provider_type, &credential.uuid, scope.priority)
+            .await
+        {
+            Ok(permit) => return Some((credential, Some(permit))),
+            Err(e) => {
+                state.pool_service.release_selection(&credential.uuid);
+                tracing::warn!(
+                    "[STREAM_FAILOVER] 凭证 {} 排队失败: {}，尝试下一个",
+                    credential.name.as_deref().unwrap_or(&credential.uuid),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// 在 `parent`（流开始时的尝试 span）下开启一次断流续传的 span
+fn stream_failover_span(
+    parent: Option<&SpanParent>,
+    continuation: u32,
+    error: &StreamError,
+) -> SpanGuard {
+    let mut span = parent
+        .map(|p| p.child("proxycast.provider.failover"))
+        .unwrap_or_default();
+    span.set_attribute("proxycast.failover.reason", "stream_interrupted");
+    span.set_attribute("proxycast.failover.continuation", continuation + 1);
+    span.set_attribute("error.message", error.to_string());
+    span
+}
+
+/// 根据断流续传结果结束续传 span，续传成功时计入故障转移指标
+fn finish_stream_failover_span(
+    state: &AppState,
+    provider: &str,
+    mut span: SpanGuard,
+    tried_credentials: &[String],
+    resumed: bool,
+) {
+    if resumed {
+        state
+            .processor
+            .metrics
+            .record_failover(provider, provider, "stream_interrupted");
+        if let Some(credential_id) = tried_credentials.last() {
+            span.set_attribute("proxycast.credential_id", credential_id.as_str());
+        }
+        span.set_ok();
+    } else {
+        span.set_error("no credential could resume the stream");
+    }
+}
 
 /// 根据凭证调用 Provider (Anthropic 格式)
 ///
@@ -82,11 +306,26 @@ use crate::streaming::{
 /// - `credential`: 凭证信息
 /// - `request`: Anthropic 格式请求
 /// - `flow_id`: Flow ID（可选，用于流式响应处理）
+/// - `scope`: 原始请求信息，流式响应断流续传时沿用
 pub async fn call_provider_anthropic(
     state: &AppState,
     credential: &ProviderCredential,
     request: &AnthropicMessagesRequest,
     flow_id: Option<&str>,
+    scope: &ResumeScope,
+) -> Response {
+    let started = std::time::Instant::now();
+    let response = dispatch_anthropic_call(state, credential, request, flow_id, scope).await;
+    track_credential_latency(state, credential, started, response)
+}
+
+/// 按凭证类型分发 Anthropic 格式请求
+async fn dispatch_anthropic_call(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+    scope: &ResumeScope,
 ) -> Response {
     // 如果是流式请求且有 flow_id，设置流式状态
     if request.stream {
@@ -107,7 +346,7 @@ pub async fn call_provider_anthropic(
         CredentialData::KiroOAuth { creds_file_path } => {
             // 如果是流式请求，使用真正的流式处理（需求 1.1, 6.1）
             if request.stream {
-                return handle_kiro_stream(state, credential, request, flow_id).await;
+                return handle_kiro_stream(state, credential, request, flow_id, scope).await;
             }
 
             // 非流式请求，使用现有的 call_api() 方法（需求 6.1, 6.2, 6.3）
@@ -222,6 +461,7 @@ pub async fn call_provider_anthropic(
                     status,
                     &credential.uuid[..8]
                 );
+                let mut retry_span = token_refresh_retry_span(credential, status.as_str());
                 let new_token = match state
                     .token_cache
                     .refresh_and_cache(db, &credential.uuid, true)
@@ -230,6 +470,7 @@ pub async fn call_provider_anthropic(
                     Ok(t) => t,
                     Err(e) => {
                         // 记录 Token 刷新失败
+                        retry_span.set_error(&format!("Token refresh failed: {}", e));
                         let _ = state.pool_service.mark_unhealthy(
                             db,
                             &credential.uuid,
@@ -244,7 +485,7 @@ pub async fn call_provider_anthropic(
                 };
                 // 使用新 token 重试
                 kiro.credentials.access_token = Some(new_token);
-                match kiro.call_api(&openai_request).await {
+                let response = match kiro.call_api(&openai_request).await {
                     Ok(retry_resp) => {
                         if retry_resp.status().is_success() {
                             match retry_resp.bytes().await {
@@ -300,11 +541,26 @@ pub async fn call_provider_anthropic(
                         )
                             .into_response()
                     }
+                };
+                if response.status().is_success() {
+                    retry_span.set_ok();
+                } else {
+                    retry_span.set_error(&format!("HTTP {}", response.status().as_u16()));
                 }
+                response
             } else {
                 let status_code = status.as_u16();
+                let headers = resp.headers().clone();
                 let body = resp.text().await.unwrap_or_default();
                 eprintln!("[PROVIDER_CALL] Kiro 请求失败: status={} body={}", status_code, &body[..body.len().min(500)]);
+                // 限流响应按上游给出的重置时间冷却凭证
+                state.pool_service.mark_rate_limited(
+                    &credential.uuid,
+                    status_code,
+                    Some(&headers),
+                    &body,
+                    Some(&request.model),
+                );
                 // 只有 5xx 错误才标记为不健康
                 if status_code >= 500 {
                     let _ = state
@@ -447,6 +703,14 @@ pub async fn call_provider_anthropic(
                     }
                 }
                 Err(api_err) => {
+                    // 配额耗尽时按响应体中的 quotaResetDelay / quotaResetTime 冷却凭证
+                    state.pool_service.mark_rate_limited(
+                        &credential.uuid,
+                        api_err.status_code,
+                        None,
+                        api_err.body.as_deref().unwrap_or(&api_err.message),
+                        Some(&request.model),
+                    );
                     // 记录 API 调用失败
                     if let Some(db) = &state.db {
                         let _ = state.pool_service.mark_unhealthy(
@@ -534,8 +798,17 @@ pub async fn call_provider_anthropic(
                         }
                     } else {
                         let status_code = status.as_u16();
+                        let headers = resp.headers().clone();
                         let body = resp.text().await.unwrap_or_default();
                         eprintln!("[PROVIDER_CALL] OpenAI 请求失败: status={} body={}", status_code, &body[..body.len().min(500)]);
+                        // 限流响应按上游给出的重置时间冷却凭证
+                        state.pool_service.mark_rate_limited(
+                            &credential.uuid,
+                            status_code,
+                            Some(&headers),
+                            &body,
+                            Some(&request.model),
+                        );
                         // 只有 5xx 错误才标记为不健康，4xx 错误（如模型不支持）不应该标记凭证为不健康
                         if status_code >= 500 {
                             if let Some(db) = &state.db {
@@ -597,6 +870,7 @@ pub async fn call_provider_anthropic(
             match claude.call_api(request).await {
                 Ok(resp) => {
                     let status = resp.status();
+                    let headers = resp.headers().clone();
                     // 打印响应状态
                     state.logs.write().await.add(
                         "info",
@@ -623,23 +897,8 @@ pub async fn call_provider_anthropic(
                             );
                             let _ = state.pool_service.record_usage(db, &credential.uuid);
                         }
-                        // 透传流式响应，保持 SSE 格式
-                        let stream = resp.bytes_stream();
-                        return Response::builder()
-                            .status(StatusCode::OK)
-                            .header(header::CONTENT_TYPE, "text/event-stream")
-                            .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
-                            .header("Connection", "keep-alive")
-                            .header("X-Accel-Buffering", "no") // 禁用 nginx 等代理的缓冲
-                            .header("Transfer-Encoding", "chunked")
-                            .body(Body::from_stream(stream))
-                            .unwrap_or_else(|_| {
-                                (
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    Json(serde_json::json!({"error": {"message": "Failed to build stream response"}})),
-                                )
-                                    .into_response()
-                            });
+                        // 透传流式响应，保持 SSE 格式，断流时换用其他凭证续传
+                        return relay_anthropic_sse(state, credential, request, flow_id, scope, resp);
                     }
 
                     // 非流式请求，读取完整响应
@@ -683,7 +942,15 @@ pub async fn call_provider_anthropic(
                                         &body.chars().take(200).collect::<String>()
                                     ),
                                 );
-                                if let Some(db) = &state.db {
+                                let rate_limited = cool_down_if_rate_limited(
+                                    state,
+                                    credential,
+                                    status.as_u16(),
+                                    Some(&headers),
+                                    &body,
+                                    &request.model,
+                                );
+                                if let (false, Some(db)) = (rate_limited, &state.db) {
                                     let _ = state.pool_service.mark_unhealthy(
                                         db,
                                         &credential.uuid,
@@ -741,6 +1008,7 @@ pub async fn call_provider_anthropic(
             match vertex.chat_completions(&serde_json::to_value(&openai_request).unwrap_or_default()).await {
                 Ok(resp) => {
                     let status = resp.status();
+                    let headers = resp.headers().clone();
                     match resp.text().await {
                         Ok(body) => {
                             if status.is_success() {
@@ -756,7 +1024,8 @@ pub async fn call_provider_anthropic(
                                         (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": {"message": "Failed to build response"}}))).into_response()
                                     })
                             } else {
-                                if let Some(db) = &state.db {
+                                let rate_limited = cool_down_if_rate_limited(state, credential, status.as_u16(), Some(&headers), &body, &request.model);
+                                if let (false, Some(db)) = (rate_limited, &state.db) {
                                     let _ = state.pool_service.mark_unhealthy(db, &credential.uuid, Some(&body));
                                 }
                                 (StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR), Json(serde_json::json!({"error": {"message": body}}))).into_response()
@@ -814,6 +1083,7 @@ pub async fn call_provider_anthropic(
             match claude.call_api(request).await {
                 Ok(resp) => {
                     let status = resp.status();
+                    let headers = resp.headers().clone();
                     state.logs.write().await.add(
                         "info",
                         &format!(
@@ -838,22 +1108,7 @@ pub async fn call_provider_anthropic(
                             );
                             let _ = state.pool_service.record_usage(db, &credential.uuid);
                         }
-                        let stream = resp.bytes_stream();
-                        return Response::builder()
-                            .status(StatusCode::OK)
-                            .header(header::CONTENT_TYPE, "text/event-stream")
-                            .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
-                            .header("Connection", "keep-alive")
-                            .header("X-Accel-Buffering", "no") // 禁用 nginx 等代理的缓冲
-                            .header("Transfer-Encoding", "chunked")
-                            .body(Body::from_stream(stream))
-                            .unwrap_or_else(|_| {
-                                (
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    Json(serde_json::json!({"error": {"message": "Failed to build stream response"}})),
-                                )
-                                    .into_response()
-                            });
+                        return relay_anthropic_sse(state, credential, request, flow_id, scope, resp);
                     }
 
                     // 非流式请求，读取完整响应
@@ -888,7 +1143,15 @@ pub async fn call_provider_anthropic(
                                         &body[..body.len().min(500)]
                                     ),
                                 );
-                                if let Some(db) = &state.db {
+                                let rate_limited = cool_down_if_rate_limited(
+                                    state,
+                                    credential,
+                                    status.as_u16(),
+                                    Some(&headers),
+                                    &body,
+                                    &request.model,
+                                );
+                                if let (false, Some(db)) = (rate_limited, &state.db) {
                                     let _ = state.pool_service.mark_unhealthy(
                                         db,
                                         &credential.uuid,
@@ -940,10 +1203,20 @@ pub async fn call_provider_openai(
     state: &AppState,
     credential: &ProviderCredential,
     request: &ChatCompletionRequest,
-    _flow_id: Option<&str>,
+    flow_id: Option<&str>,
 ) -> Response {
-    let _start_time = std::time::Instant::now();
+    let started = std::time::Instant::now();
+    let response = dispatch_openai_call(state, credential, request, flow_id).await;
+    track_credential_latency(state, credential, started, response)
+}
 
+/// 按凭证类型分发 OpenAI 格式请求
+async fn dispatch_openai_call(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+    _flow_id: Option<&str>,
+) -> Response {
     // 调试：打印凭证类型
     let cred_type = match &credential.credential {
         CredentialData::KiroOAuth { .. } => "KiroOAuth",
@@ -1132,7 +1405,14 @@ pub async fn call_provider_openai(
                             });
                     }
                     Err(e) => {
-                        // 记录请求错误
+                        // 限流错误冷却凭证，其余错误计入健康度
+                        if cool_down_on_provider_error(state, credential, &e, &request.model) {
+                            return (
+                                StatusCode::TOO_MANY_REQUESTS,
+                                Json(serde_json::json!({"error": {"message": e.to_string()}})),
+                            )
+                                .into_response();
+                        }
                         if let Some(db) = &state.db {
                             let _ = state.pool_service.mark_unhealthy(db, &credential.uuid, Some(&e.to_string()));
                         }
@@ -1158,6 +1438,8 @@ pub async fn call_provider_openai(
                         match resp.text().await {
                             Ok(body) => {
                                 let parsed = parse_cw_response(&body);
+                                // Kiro 不返回 Token 数，按上下文占用率和输出长度估算
+                                let (input_tokens, output_tokens) = parsed.estimate_tokens();
                                 let has_tool_calls = !parsed.tool_calls.is_empty();
                                 let message = if has_tool_calls {
                                     serde_json::json!({
@@ -1194,9 +1476,9 @@ pub async fn call_provider_openai(
                                         "finish_reason": if has_tool_calls { "tool_calls" } else { "stop" }
                                     }],
                                     "usage": {
-                                        "prompt_tokens": 0,
-                                        "completion_tokens": 0,
-                                        "total_tokens": 0
+                                        "prompt_tokens": input_tokens,
+                                        "completion_tokens": output_tokens,
+                                        "total_tokens": input_tokens + output_tokens
                                     }
                                 }))
                                 .into_response()
@@ -1209,7 +1491,15 @@ pub async fn call_provider_openai(
                         }
                     } else {
                         // 记录 API 调用失败
+                        let headers = resp.headers().clone();
                         let body = resp.text().await.unwrap_or_default();
+                        if cool_down_if_rate_limited(state, credential, status.as_u16(), Some(&headers), &body, &request.model) {
+                            return (
+                                StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::TOO_MANY_REQUESTS),
+                                Json(serde_json::json!({"error": {"message": body}})),
+                            )
+                                .into_response();
+                        }
                         if let Some(db) = &state.db {
                             let _ = state.pool_service.mark_unhealthy(db, &credential.uuid, Some(&format!("HTTP {}: {}", status, safe_truncate(&body, 100))));
                         }
@@ -1550,6 +1840,7 @@ pub async fn call_provider_openai(
                             });
                     }
                     Err(provider_err) => {
+                        cool_down_on_provider_error(state, credential, &provider_err, &request.model);
                         // call_api_stream 返回 ProviderError，使用字符串解析状态码
                         return build_error_response(&provider_err.to_string());
                     }
@@ -1581,6 +1872,16 @@ pub async fn call_provider_openai(
                     eprintln!("[ANTIGRAVITY_OPENAI] generate_content 失败 (HTTP {}): {}", api_err.status_code, api_err.message);
                     eprintln!("[ANTIGRAVITY_OPENAI] ========== 非流式请求处理失败 ==========");
 
+                    // 配额耗尽时按响应体中的 quotaResetDelay / quotaResetTime 冷却凭证
+                    cool_down_if_rate_limited(
+                        state,
+                        credential,
+                        api_err.status_code,
+                        None,
+                        api_err.body.as_deref().unwrap_or(&api_err.message),
+                        &request.model,
+                    );
+
                     // 直接使用 AntigravityApiError 的状态码构建响应
                     build_error_response_with_status(api_err.status_code, &api_err.to_string())
                 }
@@ -1625,8 +1926,13 @@ pub async fn call_provider_openai(
                             });
                     }
                     Err(e) => {
+                        let status = if cool_down_on_provider_error(state, credential, &e, &request.model) {
+                            StatusCode::TOO_MANY_REQUESTS
+                        } else {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        };
                         return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
+                            status,
                             Json(serde_json::json!({"error": {"message": e.to_string()}})),
                         )
                             .into_response();
@@ -1657,9 +1963,16 @@ pub async fn call_provider_openai(
                                 .into_response(),
                         }
                     } else {
+                        let status = resp.status();
+                        let headers = resp.headers().clone();
                         let body = resp.text().await.unwrap_or_default();
+                        let status = if cool_down_if_rate_limited(state, credential, status.as_u16(), Some(&headers), &body, &request.model) {
+                            status
+                        } else {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        };
                         (
-                            StatusCode::INTERNAL_SERVER_ERROR,
+                            status,
                             Json(serde_json::json!({"error": {"message": body}})),
                         )
                             .into_response()
@@ -1764,8 +2077,13 @@ pub async fn call_provider_openai(
                             });
                     }
                     Err(e) => {
+                        let status = if cool_down_on_provider_error(state, credential, &e, &request.model) {
+                            StatusCode::TOO_MANY_REQUESTS
+                        } else {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        };
                         return (
-                            StatusCode::INTERNAL_SERVER_ERROR,
+                            status,
                             Json(serde_json::json!({"error": {"message": e.to_string()}})),
                         )
                             .into_response();
@@ -1776,11 +2094,25 @@ pub async fn call_provider_openai(
             // 非流式请求处理
             match claude.call_openai_api(request).await {
                 Ok(resp) => Json(resp).into_response(),
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({"error": {"message": e.to_string()}})),
-                )
-                    .into_response(),
+                Err(e) => {
+                    let message = e.to_string();
+                    // call_openai_api 以 "Claude API error: {status} - {body}" 报告上游失败
+                    let upstream_status = message
+                        .strip_prefix("Claude API error: ")
+                        .and_then(|rest| rest.get(..3))
+                        .and_then(|code| code.parse::<u16>().ok());
+                    let status = match upstream_status {
+                        Some(code) if cool_down_if_rate_limited(state, credential, code, None, &message, &request.model) => {
+                            StatusCode::from_u16(code).unwrap_or(StatusCode::TOO_MANY_REQUESTS)
+                        }
+                        _ => StatusCode::INTERNAL_SERVER_ERROR,
+                    };
+                    (
+                        status,
+                        Json(serde_json::json!({"error": {"message": message}})),
+                    )
+                        .into_response()
+                }
             }
         }
         CredentialData::VertexKey { api_key, base_url, model_aliases } => {
@@ -1803,8 +2135,15 @@ pub async fn call_provider_openai(
                             Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": {"message": e.to_string()}}))).into_response(),
                         }
                     } else {
+                        let status = resp.status();
+                        let headers = resp.headers().clone();
                         let body = resp.text().await.unwrap_or_default();
-                        (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": {"message": body}}))).into_response()
+                        let status = if cool_down_if_rate_limited(state, credential, status.as_u16(), Some(&headers), &body, &request.model) {
+                            status
+                        } else {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        };
+                        (status, Json(serde_json::json!({"error": {"message": body}}))).into_response()
                     }
                 }
                 Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": {"message": e.to_string()}}))).into_response(),
@@ -1887,13 +2226,28 @@ pub async fn call_provider_openai(
                                 let _ = state.pool_service.record_usage(db, &credential.uuid);
                             }
                         } else {
-                            if let Some(db) = &state.db {
+                            let headers = resp.headers().clone();
+                            let body = resp.bytes().await.unwrap_or_default();
+                            let rate_limited = cool_down_if_rate_limited(
+                                state,
+                                credential,
+                                status.as_u16(),
+                                Some(&headers),
+                                &String::from_utf8_lossy(&body),
+                                &request.model,
+                            );
+                            if let (false, Some(db)) = (rate_limited, &state.db) {
                                 let _ = state.pool_service.mark_unhealthy(
                                     db,
                                     &credential.uuid,
                                     Some(&format!("API error: {}", status)),
                                 );
                             }
+                            return Response::builder()
+                                .status(status)
+                                .header(header::CONTENT_TYPE, "application/json")
+                                .body(Body::from(body))
+                                .unwrap_or_else(|_| status.into_response());
                         }
 
                         match resp.bytes().await {
@@ -2001,6 +2355,16 @@ pub async fn call_provider_openai(
 
                     match response.bytes().await {
                         Ok(body) => {
+                            if !status.is_success() {
+                                cool_down_if_rate_limited(
+                                    state,
+                                    credential,
+                                    status.as_u16(),
+                                    Some(&headers),
+                                    &String::from_utf8_lossy(&body),
+                                    &request.model,
+                                );
+                            }
                             let mut response_builder = Response::builder().status(status);
                             for (key, value) in headers.iter() {
                                 response_builder = response_builder.header(key, value);
@@ -2047,6 +2411,16 @@ pub async fn call_provider_openai(
 
                     match response.bytes().await {
                         Ok(body) => {
+                            if !status.is_success() {
+                                cool_down_if_rate_limited(
+                                    state,
+                                    credential,
+                                    status.as_u16(),
+                                    Some(&headers),
+                                    &String::from_utf8_lossy(&body),
+                                    &request.model,
+                                );
+                            }
                             let mut response_builder = Response::builder().status(status);
                             for (key, value) in headers.iter() {
                                 response_builder = response_builder.header(key, value);
@@ -2124,6 +2498,24 @@ pub async fn call_provider_openai(
                     let status = response.status();
                     let headers = response.headers().clone();
 
+                    // 上游失败时原样转发错误，限流响应冷却凭证
+                    if !status.is_success() {
+                        let body = response.text().await.unwrap_or_default();
+                        cool_down_if_rate_limited(
+                            state,
+                            credential,
+                            status.as_u16(),
+                            Some(&headers),
+                            &body,
+                            &request.model,
+                        );
+                        return (
+                            status,
+                            Json(serde_json::json!({"error": {"message": body}})),
+                        )
+                            .into_response();
+                    }
+
                     // 检查是否为流式响应
                     if request.stream {
                         // 流式响应：读取 Codex SSE 流，转换为 OpenAI SSE 格式
@@ -2792,46 +3184,25 @@ pub async fn monitor_client_disconnect(cancel_token: tokio_util::sync::Cancellat
 // Kiro 凭证真正流式响应处理
 // ============================================================================
 
-/// Kiro 凭证流式响应处理
+/// 使用指定 Kiro 凭证发起流式请求
 ///
-/// 实现真正的端到端流式传输，将 AWS Event Stream 格式转换为 Anthropic SSE 格式。
-///
-/// # 参数
-/// - `state`: 应用状态
-/// - `credential`: Kiro 凭证信息
-/// - `request`: Anthropic 格式请求
-/// - `flow_id`: Flow ID（可选，用于流式响应处理）
-///
-/// # 需求覆盖
-/// - 需求 1.1: 使用 reqwest 的流式响应模式
-/// - 需求 1.2: 实时解析每个 JSON payload 并转换为 Anthropic SSE 事件
-/// - 需求 1.3: 立即发送 content_block_delta 事件给客户端
-/// - 需求 3.1: Flow Monitor 记录 chunk_count 大于 0
-/// - 需求 3.2: 调用 process_chunk 更新流重建器
-/// - 需求 3.3: 流完成时拥有完整的重建响应内容
-/// - 需求 4.4: 在流式请求前检查 Token 是否即将过期（10分钟内）并提前刷新
-pub async fn handle_kiro_stream(
+/// 负责 Token 校验/刷新和 401/403 重试，成功时返回上游字节流，
+/// 失败时返回可直接发送给客户端的错误响应
+async fn open_kiro_stream(
     state: &AppState,
     credential: &ProviderCredential,
     request: &AnthropicMessagesRequest,
-    flow_id: Option<&str>,
-) -> Response {
-    tracing::info!(
-        "[KIRO_STREAM] handle_kiro_stream 被调用, model={}, flow_id={:?}",
-        request.model,
-        flow_id
-    );
-
+) -> Result<StreamResponse, Response> {
     // 提取凭证文件路径
     let creds_file_path = match &credential.credential {
         CredentialData::KiroOAuth { creds_file_path } => creds_file_path.clone(),
         _ => {
             tracing::error!("[KIRO_STREAM] 无效的凭证类型");
-            return (
+            return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(serde_json::json!({"error": {"message": "Invalid credential type for Kiro stream"}})),
             )
-                .into_response();
+                .into_response());
         }
     };
 
@@ -2842,11 +3213,11 @@ pub async fn handle_kiro_stream(
         Some(db) => db,
         None => {
             tracing::error!("[KIRO_STREAM] 数据库不可用");
-            return (
+            return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(serde_json::json!({"error": {"message": "Database not available"}})),
             )
-                .into_response();
+                .into_response());
         }
     };
 
@@ -2870,11 +3241,11 @@ pub async fn handle_kiro_stream(
                     &credential.uuid,
                     Some(&format!("Failed to load credentials: {}", e)),
                 );
-                return (
+                return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({"error": {"message": format!("Failed to load Kiro credentials: {}", e)}})),
                 )
-                    .into_response();
+                    .into_response());
             }
             if let Err(e) = kiro.refresh_token().await {
                 let _ = state.pool_service.mark_unhealthy(
@@ -2882,11 +3253,11 @@ pub async fn handle_kiro_stream(
                     &credential.uuid,
                     Some(&format!("Token refresh failed: {}", e)),
                 );
-                return (
+                return Err((
                     StatusCode::UNAUTHORIZED,
                     Json(serde_json::json!({"error": {"message": format!("Token refresh failed: {}", e)}})),
                 )
-                    .into_response();
+                    .into_response());
             }
             kiro.credentials.access_token.unwrap_or_default()
         }
@@ -2913,8 +3284,7 @@ pub async fn handle_kiro_stream(
             // 检查是否是 401/403 错误或 Token 过期，需要刷新 token 重试（需求 4.1）
             let needs_token_refresh = matches!(
                 &e,
-                crate::providers::ProviderError::AuthenticationError(_)
-                    | crate::providers::ProviderError::TokenExpired(_)
+                ProviderError::AuthenticationError(_) | ProviderError::TokenExpired(_)
             );
 
             if needs_token_refresh {
@@ -2923,6 +3293,7 @@ pub async fn handle_kiro_stream(
                     e.short_message(),
                     &credential.uuid[..8]
                 );
+                let mut retry_span = token_refresh_retry_span(credential, e.short_message());
                 // 强制刷新 token（需求 4.1）
                 let new_token = match state
                     .token_cache
@@ -2932,12 +3303,13 @@ pub async fn handle_kiro_stream(
                     Ok(t) => t,
                     Err(refresh_err) => {
                         // 需求 4.3: Token 刷新失败时返回明确的错误信息
+                        retry_span.set_error(&format!("Token refresh failed: {}", refresh_err));
                         let _ = state.pool_service.mark_unhealthy(
                             db,
                             &credential.uuid,
                             Some(&format!("Token refresh failed: {}", refresh_err)),
                         );
-                        return (
+                        return Err((
                             StatusCode::UNAUTHORIZED,
                             Json(serde_json::json!({
                                 "error": {
@@ -2946,21 +3318,33 @@ pub async fn handle_kiro_stream(
                                 }
                             })),
                         )
-                            .into_response();
+                            .into_response());
                     }
                 };
 
                 // 使用新 token 重试（需求 4.2）
                 kiro.credentials.access_token = Some(new_token);
                 match kiro.call_api_stream_anthropic(request).await {
-                    Ok(stream) => stream,
+                    Ok(stream) => {
+                        retry_span.set_ok();
+                        stream
+                    }
                     Err(retry_err) => {
+                        retry_span.set_error(&retry_err.to_string());
+                        if cool_down_on_provider_error(
+                            state,
+                            credential,
+                            &retry_err,
+                            &request.model,
+                        ) {
+                            return Err(kiro_rate_limited_response(&retry_err));
+                        }
                         let _ = state.pool_service.mark_unhealthy(
                             db,
                             &credential.uuid,
                             Some(&retry_err.to_string()),
                         );
-                        return (
+                        return Err((
                             StatusCode::INTERNAL_SERVER_ERROR,
                             Json(serde_json::json!({
                                 "error": {
@@ -2969,15 +3353,18 @@ pub async fn handle_kiro_stream(
                                 }
                             })),
                         )
-                            .into_response();
+                            .into_response());
                     }
                 }
             } else {
+                if cool_down_on_provider_error(state, credential, &e, &request.model) {
+                    return Err(kiro_rate_limited_response(&e));
+                }
                 let _ =
                     state
                         .pool_service
                         .mark_unhealthy(db, &credential.uuid, Some(&e.to_string()));
-                return (
+                return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({
                         "error": {
@@ -2986,7 +3373,7 @@ pub async fn handle_kiro_stream(
                         }
                     })),
                 )
-                    .into_response();
+                    .into_response());
             }
         }
     };
@@ -2997,6 +3384,471 @@ pub async fn handle_kiro_stream(
         .mark_healthy(db, &credential.uuid, Some(&request.model));
     let _ = state.pool_service.record_usage(db, &credential.uuid);
 
+    Ok(stream_response)
+}
+
+/// Kiro 限流错误的 Anthropic 格式 429 响应
+fn kiro_rate_limited_response(error: &ProviderError) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": "rate_limit_error",
+                "message": error.to_string()
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 单个流式响应的断流续传状态
+struct StreamResumeState {
+    scope: ResumeScope,
+    flow_id: Option<String>,
+    /// 已尝试过的凭证，最后一个为当前正在输出的凭证
+    tried_credentials: Vec<String>,
+    /// 已续传次数
+    continuations: u32,
+}
+
+impl StreamResumeState {
+    fn new(scope: &ResumeScope, flow_id: Option<&str>, credential: &ProviderCredential) -> Self {
+        Self {
+            scope: scope.clone(),
+            flow_id: flow_id.map(str::to_string),
+            tried_credentials: vec![credential.uuid.clone()],
+            continuations: 0,
+        }
+    }
+
+    /// 是否还可以续传
+    fn can_continue(&self, state: &AppState) -> bool {
+        let settings = &state.retry_settings;
+        settings.stream_failover && self.continuations < settings.max_stream_continuations
+    }
+
+    /// 中断由上游故障（网络、超时、5xx/429）引起时，将中断的凭证记录为一次失败；
+    /// 解析错误等不归咎于凭证
+    fn blame_interrupted(&self, state: &AppState, error: &StreamError) {
+        let (Some(db), Some(failed)) = (&state.db, self.tried_credentials.last()) else {
+            return;
+        };
+        if error.is_retryable() {
+            let _ = state.pool_service.mark_unhealthy(
+                db,
+                failed,
+                Some(&format!("流式传输中断: {}", error)),
+            );
+        }
+    }
+
+    /// 续传成功：计入续传次数，并在 Flow 上记录续传所用的凭证
+    async fn record_resumed(&mut self, state: &AppState, credential: &ProviderCredential) {
+        self.continuations += 1;
+        if let Some(fid) = &self.flow_id {
+            state
+                .flow_monitor
+                .record_stream_resume(fid, &credential.uuid, credential.name.as_deref())
+                .await;
+        }
+    }
+}
+
+/// 上游流中途断开后，换用其他 Kiro 凭证续传
+///
+/// 以已输出的文本作为 assistant 预填充重新发起请求，并保留管道状态，
+/// 使续传的增量沿用同一消息 ID 和内容块索引。返回续传流及其占用的并发许可，无法续传时返回 None
+async fn resume_kiro_stream(
+    state: &AppState,
+    request: &AnthropicMessagesRequest,
+    pipeline: &tokio::sync::Mutex<StreamPipeline>,
+    resume: &mut StreamResumeState,
+    error: &StreamError,
+) -> Option<(StreamResponse, Option<RequestPermit>)> {
+    if !resume.can_continue(state) || matches!(error, StreamError::ClientDisconnected) {
+        return None;
+    }
+
+    let partial_text = {
+        let pipeline_guard = pipeline.lock().await;
+        if !pipeline_guard.can_resume() {
+            tracing::info!("[STREAM_FAILOVER] 已输出工具调用或消息已结束，无法续传");
+            return None;
+        }
+        pipeline_guard.partial_text().to_string()
+    };
+    resume.blame_interrupted(state, error);
+
+    let resumed_request = with_assistant_prefill(request, &partial_text);
+    loop {
+        let (credential, permit) = admit_resume_credential(
+            state,
+            "kiro",
+            &request.model,
+            &resume.scope,
+            &mut resume.tried_credentials,
+            |_| true,
+        )
+        .await?;
+
+        // open_kiro_stream 自行处理限流冷却和健康度
+        match open_kiro_stream(state, &credential, &resumed_request).await {
+            Ok(stream) => {
+                pipeline.lock().await.resume();
+                resume.record_resumed(state, &credential).await;
+                tracing::info!(
+                    "[STREAM_FAILOVER] 已切换到凭证 {} 续传 (第 {} 次), 已输出 {} 字符",
+                    credential.name.as_deref().unwrap_or(&credential.uuid),
+                    resume.continuations,
+                    partial_text.chars().count()
+                );
+                return Some((stream, permit));
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "[STREAM_FAILOVER] 凭证 {} 续传请求失败，尝试下一个",
+                    credential.name.as_deref().unwrap_or(&credential.uuid)
+                );
+            }
+        }
+    }
+}
+
+/// 将已输出的部分文本作为 assistant 预填充追加到请求中
+///
+/// 上游拒绝以空白结尾的 assistant 预填充，末尾空白不放入预填充，
+/// 由续传流重新生成（转发时去掉重复的部分）
+fn with_assistant_prefill(
+    request: &AnthropicMessagesRequest,
+    partial_text: &str,
+) -> AnthropicMessagesRequest {
+    let mut request = request.clone();
+    let partial_text = partial_text.trim_end();
+    if partial_text.is_empty() {
+        return request;
+    }
+
+    if let Some(last) = request
+        .messages
+        .last_mut()
+        .filter(|m| m.role == "assistant")
+    {
+        match &mut last.content {
+            serde_json::Value::String(text) => {
+                text.push_str(partial_text);
+                return request;
+            }
+            serde_json::Value::Array(blocks) => {
+                blocks.push(serde_json::json!({"type": "text", "text": partial_text}));
+                return request;
+            }
+            _ => {}
+        }
+    }
+
+    request.messages.push(AnthropicMessage {
+        role: "assistant".to_string(),
+        content: serde_json::Value::String(partial_text.to_string()),
+    });
+    request
+}
+
+/// Anthropic SSE 断流续传的结果
+enum SseResumeOutcome {
+    /// 续传请求成功，返回续传流及其占用的并发许可
+    Resumed(Box<(ProviderCredential, reqwest::Response, Option<RequestPermit>)>),
+    /// 续传请求被上游以请求错误拒绝（如启用 thinking 时不接受 assistant 预填充），
+    /// 换用其他凭证也不会成功，将上游错误作为 error 事件转发给客户端
+    Rejected(String),
+    /// 无法续传
+    GaveUp,
+}
+
+/// 透传 Anthropic 格式的上游 SSE 流
+///
+/// 上游中途断开、被截断或发送 error 事件时，若启用了 `retry.stream_failover`，
+/// 以已输出的文本作为 assistant 预填充，换用同一 Provider 的其他 API Key 凭证续传。
+/// 每次上游尝试的用量分别计入所用的凭证，响应带 [`MeteredPerAttempt`] 扩展
+fn relay_anthropic_sse(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+    scope: &ResumeScope,
+    resp: reqwest::Response,
+) -> Response {
+    let state = state.clone();
+    let request = request.clone();
+    let mut resume = StreamResumeState::new(scope, flow_id, credential);
+    let provider = credential.provider_type.to_string();
+    let attempt_span = current_span();
+    let mut attempt_ctx = scope.attempt_context(credential, &request.model);
+
+    let stream = async_stream::stream! {
+        let mut relay = AnthropicSseRelay::new();
+        let mut upstream = resp.bytes_stream();
+        let mut meter = UsageMeter::new();
+        // 续传请求占用的并发许可，随续传流一直持有到响应结束
+        let mut _resume_permit: Option<RequestPermit> = None;
+
+        loop {
+            let interruption = match upstream.next().await {
+                Some(Ok(chunk)) => {
+                    meter.observe_sse_chunk(&chunk);
+                    for event in relay.process_chunk(&chunk) {
+                        yield Ok::<bytes::Bytes, std::convert::Infallible>(bytes::Bytes::from(event));
+                    }
+                    match relay.take_error() {
+                        Some(error_event) => (StreamError::provider_error(529, "上游返回 error 事件"), Some(error_event)),
+                        None => continue,
+                    }
+                }
+                Some(Err(e)) => (StreamError::from(e), None),
+                None if relay.is_finished() => break,
+                None => (StreamError::network("上游流在 message_stop 前结束"), None),
+            };
+            let (error, error_event) = interruption;
+            tracing::warn!("[STREAM_FAILOVER] Anthropic SSE 流中断: {}", error);
+
+            let failover_span = stream_failover_span(attempt_span.as_ref(), resume.continuations, &error);
+            let outcome = in_span(
+                &failover_span,
+                resume_anthropic_sse(&state, &provider, &request, &relay, &mut resume, &error),
+            )
+            .await;
+            let resumed = matches!(outcome, SseResumeOutcome::Resumed(..));
+            finish_stream_failover_span(&state, &provider, failover_span, &resume.tried_credentials, resumed);
+            match outcome {
+                SseResumeOutcome::Resumed(next_attempt) => {
+                    let (next_credential, next, permit) = *next_attempt;
+                    record_metered_usage(&state, &attempt_ctx, &meter, None);
+                    attempt_ctx = resume.scope.attempt_context(&next_credential, &request.model);
+                    meter = UsageMeter::new();
+                    _resume_permit = permit;
+                    relay.resume();
+                    upstream = next.bytes_stream();
+                }
+                SseResumeOutcome::Rejected(rejected_event) => {
+                    yield Ok(bytes::Bytes::from(rejected_event));
+                    break;
+                }
+                SseResumeOutcome::GaveUp => {
+                    let error_event = error_event.unwrap_or_else(|| error.to_sse_error());
+                    yield Ok(bytes::Bytes::from(error_event));
+                    break;
+                }
+            }
+        }
+        record_metered_usage(&state, &attempt_ctx, &meter, None);
+    };
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .header("Connection", "keep-alive")
+        .header("X-Accel-Buffering", "no") // 禁用 nginx 等代理的缓冲
+        .header("Transfer-Encoding", "chunked")
+        .body(Body::from_stream(stream))
+        .unwrap_or_else(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": {"message": "Failed to build stream response"}})),
+            )
+                .into_response()
+        });
+    response.extensions_mut().insert(MeteredPerAttempt);
+    response
+}
+
+/// 续传请求被拒绝时转发给客户端的 SSE error 事件
+///
+/// 上游返回的是 Anthropic 错误格式时原样转发，否则包装为 `api_error`
+fn rejected_resume_event(status: StatusCode, body: &str) -> String {
+    let error = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .filter(|json| json.get("error").is_some())
+        .unwrap_or_else(|| {
+            serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": "api_error",
+                    "message": format!("续传请求失败 ({}): {}", status, safe_truncate(body, 200))
+                }
+            })
+        });
+    format!("event: error\ndata: {}\n\n", error)
+}
+
+/// Anthropic SSE 流中途断开后，换用同一 Provider 的其他 API Key 凭证续传
+///
+/// 续传请求的失败按首次尝试的规则处理：429 等限流响应冷却凭证，
+/// 认证失败、5xx 和网络错误标记凭证不健康并换下一个凭证；其他 4xx 是续传请求本身的问题，
+/// 不影响凭证健康度，直接转发给客户端
+async fn resume_anthropic_sse(
+    state: &AppState,
+    provider: &str,
+    request: &AnthropicMessagesRequest,
+    relay: &AnthropicSseRelay,
+    resume: &mut StreamResumeState,
+    error: &StreamError,
+) -> SseResumeOutcome {
+    if !resume.can_continue(state) {
+        return SseResumeOutcome::GaveUp;
+    }
+    if !relay.can_resume() {
+        tracing::info!("[STREAM_FAILOVER] 已输出工具调用或消息已结束，无法续传");
+        return SseResumeOutcome::GaveUp;
+    }
+    let Some(db) = state.db.as_ref() else {
+        return SseResumeOutcome::GaveUp;
+    };
+    resume.blame_interrupted(state, error);
+
+    let resumed_request = with_assistant_prefill(request, relay.partial_text());
+    loop {
+        // 只有 API Key 凭证的响应是可以透传的 Anthropic SSE
+        let Some((credential, permit)) = admit_resume_credential(
+            state,
+            provider,
+            &request.model,
+            &resume.scope,
+            &mut resume.tried_credentials,
+            |c| {
+                matches!(
+                    c.credential,
+                    CredentialData::ClaudeKey { .. } | CredentialData::AnthropicKey { .. }
+                )
+            },
+        )
+        .await
+        else {
+            return SseResumeOutcome::GaveUp;
+        };
+        let claude = match &credential.credential {
+            CredentialData::ClaudeKey { api_key, base_url }
+            | CredentialData::AnthropicKey { api_key, base_url } => {
+                ClaudeCustomProvider::with_config(api_key.clone(), base_url.clone())
+            }
+            _ => unreachable!("admit_resume_credential 只返回 API Key 凭证"),
+        };
+        let label = credential.name.as_deref().unwrap_or(&credential.uuid);
+        match claude.call_api(&resumed_request).await {
+            Ok(resp) if resp.status().is_success() => {
+                let _ = state
+                    .pool_service
+                    .mark_healthy(db, &credential.uuid, Some(&request.model));
+                let _ = state.pool_service.record_usage(db, &credential.uuid);
+                resume.record_resumed(state, &credential).await;
+                tracing::info!(
+                    "[STREAM_FAILOVER] 已切换到凭证 {} 续传 (第 {} 次), 已输出 {} 字符",
+                    label,
+                    resume.continuations,
+                    relay.partial_text().chars().count()
+                );
+                return SseResumeOutcome::Resumed(Box::new((credential, resp, permit)));
+            }
+            Ok(resp) => {
+                let status = resp.status();
+                let headers = resp.headers().clone();
+                let body = resp.text().await.unwrap_or_default();
+                if cool_down_if_rate_limited(
+                    state,
+                    &credential,
+                    status.as_u16(),
+                    Some(&headers),
+                    &body,
+                    &request.model,
+                ) {
+                    tracing::warn!(
+                        "[STREAM_FAILOVER] 凭证 {} 续传请求被限流 ({})，尝试下一个",
+                        label,
+                        status
+                    );
+                    continue;
+                }
+                if matches!(status.as_u16(), 401 | 403) || status.is_server_error() {
+                    let _ = state.pool_service.mark_unhealthy(
+                        db,
+                        &credential.uuid,
+                        Some(&format!("API error: {}", status)),
+                    );
+                    tracing::warn!(
+                        "[STREAM_FAILOVER] 凭证 {} 续传请求失败 ({})，尝试下一个",
+                        label,
+                        status
+                    );
+                    continue;
+                }
+                // 请求本身的错误：凭证是可用的，计入熔断器成功以归还半开状态的探测名额
+                state
+                    .pool_service
+                    .circuit_breaker()
+                    .record_success(&credential.uuid);
+                tracing::warn!(
+                    "[STREAM_FAILOVER] 凭证 {} 拒绝续传请求 ({})，放弃续传: {}",
+                    label,
+                    status,
+                    safe_truncate(&body, 200)
+                );
+                return SseResumeOutcome::Rejected(rejected_resume_event(status, &body));
+            }
+            Err(e) => {
+                let _ = state.pool_service.mark_unhealthy(
+                    db,
+                    &credential.uuid,
+                    Some(&format!("API call failed: {}", e)),
+                );
+                tracing::warn!(
+                    "[STREAM_FAILOVER] 凭证 {} 续传请求失败: {}，尝试下一个",
+                    label,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Kiro 凭证流式响应处理
+///
+/// 实现真正的端到端流式传输，将 AWS Event Stream 格式转换为 Anthropic SSE 格式。
+///
+/// # 参数
+/// - `state`: 应用状态
+/// - `credential`: Kiro 凭证信息
+/// - `request`: Anthropic 格式请求
+/// - `flow_id`: Flow ID（可选，用于流式响应处理）
+///
+/// # 需求覆盖
+/// - 需求 1.1: 使用 reqwest 的流式响应模式
+/// - 需求 1.2: 实时解析每个 JSON payload 并转换为 Anthropic SSE 事件
+/// - 需求 1.3: 立即发送 content_block_delta 事件给客户端
+/// - 需求 3.1: Flow Monitor 记录 chunk_count 大于 0
+/// - 需求 3.2: 调用 process_chunk 更新流重建器
+/// - 需求 3.3: 流完成时拥有完整的重建响应内容
+/// - 需求 4.4: 在流式请求前检查 Token 是否即将过期（10分钟内）并提前刷新
+///
+/// 上游流中途断开时，若启用了 `retry.stream_failover`，会以已输出的文本作为
+/// assistant 预填充，换用其他 Kiro 凭证续传，客户端看到的仍是同一条响应
+pub async fn handle_kiro_stream(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+    scope: &ResumeScope,
+) -> Response {
+    tracing::info!(
+        "[KIRO_STREAM] handle_kiro_stream 被调用, model={}, flow_id={:?}",
+        request.model,
+        flow_id
+    );
+
+    let stream_response = match open_kiro_stream(state, credential, request).await {
+        Ok(stream) => stream,
+        Err(response) => return response,
+    };
+
     tracing::info!(
         "[KIRO_STREAM] 开始处理流式响应, model={}, flow_id={:?}",
         request.model,
@@ -3020,10 +3872,18 @@ pub async fn handle_kiro_stream(
     let flow_id_for_finalize = flow_id_owned.clone();
     let flow_monitor_for_finalize = flow_monitor.clone();
 
+    // 断流续传所需的状态
+    let state_for_resume = state.clone();
+    let request_for_resume = request.clone();
+    let mut resume = StreamResumeState::new(scope, flow_id, credential);
+    let attempt_span = current_span();
+
     let final_stream = async_stream::stream! {
         use futures::StreamExt;
 
         let mut stream_response = stream_response;
+        // 续传请求占用的并发许可，随续传流一直持有到响应结束
+        let mut _resume_permit: Option<RequestPermit> = None;
 
         while let Some(chunk_result) = stream_response.next().await {
             match chunk_result {
@@ -3088,6 +3948,27 @@ pub async fn handle_kiro_stream(
                     // 需求 5.1, 5.3: 流式传输期间发生错误时，发出错误事件并以失败状态完成 flow
                     tracing::error!("[KIRO_STREAM] 流式传输期间发生错误: {}", e);
 
+                    // 尝试换用其他凭证续传
+                    let failover_span =
+                        stream_failover_span(attempt_span.as_ref(), resume.continuations, &e);
+                    let resumed = in_span(
+                        &failover_span,
+                        resume_kiro_stream(
+                            &state_for_resume,
+                            &request_for_resume,
+                            &pipeline_clone,
+                            &mut resume,
+                            &e,
+                        ),
+                    )
+                    .await;
+                    finish_stream_failover_span(&state_for_resume, "kiro", failover_span, &resume.tried_credentials, resumed.is_some());
+                    if let Some((next, permit)) = resumed {
+                        stream_response = next;
+                        _resume_permit = permit;
+                        continue;
+                    }
+
                     // 根据 StreamError 类型映射到 FlowErrorType
                     let flow_error_type = match &e {
                         StreamError::Network(_) => FlowErrorType::Network,
@@ -3981,3 +4862,121 @@ fn convert_codex_event_to_openai_sse(
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with(messages: Vec<AnthropicMessage>) -> AnthropicMessagesRequest {
+        AnthropicMessagesRequest {
+            model: "claude-sonnet-4".to_string(),
+            messages,
+            max_tokens: Some(1024),
+            system: None,
+            temperature: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+
+    #[test]
+    fn test_with_assistant_prefill() {
+        let user = AnthropicMessage {
+            role: "user".to_string(),
+            content: serde_json::json!("写一首诗"),
+        };
+
+        // 追加新的 assistant 消息
+        let request = with_assistant_prefill(&request_with(vec![user.clone()]), "床前明月光");
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[1].role, "assistant");
+        assert_eq!(request.messages[1].content, serde_json::json!("床前明月光"));
+
+        // 已有 assistant 预填充时拼接到末尾
+        let prefilled = AnthropicMessage {
+            role: "assistant".to_string(),
+            content: serde_json::json!("床前"),
+        };
+        let request =
+            with_assistant_prefill(&request_with(vec![user.clone(), prefilled]), "明月光");
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[1].content, serde_json::json!("床前明月光"));
+
+        // 末尾空白不放入预填充
+        let request = with_assistant_prefill(&request_with(vec![user.clone()]), "1 2 \n");
+        assert_eq!(request.messages[1].content, serde_json::json!("1 2"));
+
+        // 没有已输出文本时保持原样
+        let request = with_assistant_prefill(&request_with(vec![user.clone()]), "");
+        assert_eq!(request.messages.len(), 1);
+        let request = with_assistant_prefill(&request_with(vec![user]), "  ");
+        assert_eq!(request.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_provider_calls_feed_latency_and_failures() {
+        use crate::credential::BalanceStrategy;
+        use crate::server::test_support::{add_credential, body_text, spawn_upstream, test_state};
+
+        let upstream = axum::Router::new().route(
+            "/:name/v1/chat/completions",
+            axum::routing::post(
+                |axum::extract::Path(name): axum::extract::Path<String>| async move {
+                    if name == "failing" {
+                        return (StatusCode::BAD_GATEWAY, "upstream down").into_response();
+                    }
+                    Json(serde_json::json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "created": 0,
+                        "model": "gpt-4o",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "ok"},
+                            "finish_reason": "stop"
+                        }]
+                    }))
+                    .into_response()
+                },
+            ),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("openai");
+        state
+            .pool_service
+            .set_balance_strategies([("openai".to_string(), BalanceStrategy::EwmaLatency)].into());
+        let credentials: Vec<_> = ["failing", "healthy"]
+            .into_iter()
+            .map(|name| {
+                add_credential(
+                    &state,
+                    crate::ProviderType::OpenAI,
+                    CredentialData::OpenAIKey {
+                        api_key: "sk-test".to_string(),
+                        base_url: Some(format!("{}/{}", base, name)),
+                    },
+                )
+            })
+            .collect();
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        for credential in &credentials {
+            let response = call_provider_openai(&state, credential, &request, None).await;
+            // 成功响应的首字节延迟在读取响应体时记录
+            body_text(response).await;
+        }
+
+        // 失败按惩罚延迟计入，延迟优先策略改选健康凭证
+        let selected = state
+            .pool_service
+            .select_credential(state.db.as_ref().unwrap(), "openai", Some("gpt-4o"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(selected.uuid, credentials[1].uuid);
+    }
+}
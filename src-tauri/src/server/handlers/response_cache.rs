@@ -0,0 +1,365 @@
+//! 响应缓存处理
+//!
+//! 在 `/v1/chat/completions` 和 `/v1/messages` 中按规范化请求查找缓存：
+//! - 命中：直接返回缓存的 JSON，或按事件重放 SSE，并记录一条 `cached` Flow（上游 Token 为 0）
+//! - 未命中：包装上游的成功响应，在响应体完整发送给客户端后写入缓存；
+//!   流式响应只有收到终止事件且没有错误事件时才写入
+//!
+//! 客户端可通过 `Cache-Control: no-cache` 跳过查找（仍会写入），`no-store` 完全绕过缓存。
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use futures::StreamExt;
+
+use crate::database::dao::response_cache::ResponseCacheEntry;
+use crate::flow_monitor::{
+    FlowMetadata, LLMRequest, LLMResponse, StreamFormat, StreamRebuilder, TokenUsage,
+};
+use crate::processor::RequestContext;
+use crate::server::AppState;
+use crate::services::response_cache_service::{
+    is_complete_stream, split_sse_events, CacheDirective, CacheKey, CACHE_STATUS_HEADER,
+};
+
+/// 缓存查找结果
+pub enum CacheLookup {
+    /// 命中，直接返回给客户端
+    Hit(Response),
+    /// 未命中；上游成功响应需使用该 key 写入缓存（`None` 表示不写入）
+    Miss(Option<CacheKey>),
+}
+
+/// 查找响应缓存
+///
+/// # Arguments
+/// * `request` - 别名解析和参数注入之后的请求体
+/// * `flow` - 命中时用于记录 Flow 的请求与元数据（仅命中时调用）
+pub async fn lookup_response_cache(
+    state: &AppState,
+    headers: &HeaderMap,
+    ctx: &RequestContext,
+    endpoint: &str,
+    request: &serde_json::Value,
+    flow: impl FnOnce() -> (LLMRequest, FlowMetadata),
+) -> CacheLookup {
+    let Some(db) = state.db.as_ref() else {
+        return CacheLookup::Miss(None);
+    };
+    if !state.response_cache.is_enabled() {
+        return CacheLookup::Miss(None);
+    }
+
+    let directive = CacheDirective::from_cache_control(
+        headers
+            .get(header::CACHE_CONTROL)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let key = CacheKey::new(endpoint, request, ctx.client_key_id());
+
+    if directive.allows_lookup() {
+        if let Some(entry) = state.response_cache.lookup(db, &key) {
+            state.processor.metrics.record_cache_lookup(endpoint, "hit");
+            if let Some(trace) = &ctx.trace {
+                trace.set_attribute("proxycast.cache_hit", true);
+                trace.set_ok();
+            }
+            state.logs.write().await.add(
+                "info",
+                &format!(
+                    "[CACHE] request_id={} hit endpoint={} model={} stream={} hits={}",
+                    ctx.request_id,
+                    endpoint,
+                    entry.model,
+                    entry.is_stream,
+                    entry.hit_count + 1
+                ),
+            );
+
+            let (llm_request, mut metadata) = flow();
+            metadata.cached = true;
+            if let Some(flow_id) = state.flow_monitor.start_flow(llm_request, metadata).await {
+                state
+                    .flow_monitor
+                    .complete_flow(&flow_id, Some(cached_llm_response(&entry)))
+                    .await;
+            }
+
+            return CacheLookup::Hit(build_cached_response(entry));
+        }
+        state
+            .processor
+            .metrics
+            .record_cache_lookup(endpoint, "miss");
+    }
+
+    if let Some(trace) = &ctx.trace {
+        trace.set_attribute("proxycast.cache_hit", false);
+    }
+    CacheLookup::Miss(directive.allows_store().then_some(key))
+}
+
+/// 包装上游响应：响应体完整发送后写入缓存
+///
+/// 仅缓存 200 响应；响应体出错、客户端中途断开或超过单条大小上限时不写入。
+/// 流式响应还需以 `message_stop` / `[DONE]` 结束且不含错误事件，
+/// 避免把上游中途报错或被截断的流缓存下来反复重放。
+pub fn cache_upstream_response(
+    state: &AppState,
+    key: Option<CacheKey>,
+    mut response: Response,
+) -> Response {
+    let (Some(key), Some(db)) = (key, state.db.clone()) else {
+        return response;
+    };
+    response
+        .headers_mut()
+        .insert(CACHE_STATUS_HEADER, HeaderValue::from_static("miss"));
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(if key.is_stream {
+            "text/event-stream"
+        } else {
+            "application/json"
+        })
+        .to_string();
+    let service = state.response_cache.clone();
+    let max_bytes = service.max_entry_bytes();
+
+    let (parts, body) = response.into_parts();
+    let mut upstream = body.into_data_stream();
+    let stream = async_stream::stream! {
+        let mut captured = Some(Vec::new());
+        while let Some(chunk) = upstream.next().await {
+            match &chunk {
+                Ok(bytes) => {
+                    if captured
+                        .as_ref()
+                        .is_some_and(|buf| buf.len() + bytes.len() > max_bytes)
+                    {
+                        captured = None;
+                    }
+                    if let Some(buf) = captured.as_mut() {
+                        buf.extend_from_slice(bytes);
+                    }
+                }
+                Err(_) => captured = None,
+            }
+            yield chunk;
+        }
+        if let Some(body) = captured {
+            if key.is_stream && !is_complete_stream(&body) {
+                tracing::info!("[CACHE] 流式响应不完整或包含错误事件，跳过写入");
+            } else {
+                service.store(&db, &key, &content_type, body);
+            }
+        }
+    };
+
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+/// 构建命中时返回给客户端的响应
+fn build_cached_response(entry: ResponseCacheEntry) -> Response {
+    let builder = Response::builder()
+        .status(StatusCode::from_u16(entry.status_code).unwrap_or(StatusCode::OK))
+        .header(header::CONTENT_TYPE, entry.content_type.as_str())
+        .header(CACHE_STATUS_HEADER, "hit");
+
+    let response = if entry.is_stream {
+        let events = split_sse_events(&entry.body)
+            .into_iter()
+            .map(|event| Ok::<_, std::convert::Infallible>(Bytes::from(event)));
+        builder
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from_stream(futures::stream::iter(events)))
+    } else {
+        builder.body(Body::from(entry.body))
+    };
+
+    response.unwrap_or_else(|e| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(format!(
+                "Failed to build cached response: {}",
+                e
+            )))
+            .unwrap_or_default()
+    })
+}
+
+/// 从缓存条目构建 Flow 响应（SSE 通过 `StreamRebuilder` 重建），上游 Token 记为 0
+fn cached_llm_response(entry: &ResponseCacheEntry) -> LLMResponse {
+    let mut response = if entry.is_stream {
+        let format = if entry.endpoint == "/v1/messages" {
+            StreamFormat::Anthropic
+        } else {
+            StreamFormat::OpenAI
+        };
+        let mut rebuilder = StreamRebuilder::new(format);
+        for event in split_sse_events(&entry.body) {
+            let text = String::from_utf8_lossy(&event);
+            let mut event_type = None;
+            let mut data = String::new();
+            for line in text.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    event_type = Some(value.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    if !data.is_empty() {
+                        data.push('\n');
+                    }
+                    data.push_str(value.trim_start());
+                }
+            }
+            if !data.is_empty() {
+                let _ = rebuilder.process_event(event_type.as_deref(), &data);
+            }
+        }
+        rebuilder.finish()
+    } else {
+        let body: serde_json::Value =
+            serde_json::from_slice(&entry.body).unwrap_or(serde_json::Value::Null);
+        LLMResponse {
+            content: extract_json_content(&body),
+            body,
+            ..LLMResponse::default()
+        }
+    };
+
+    response.status_code = entry.status_code;
+    response.usage = TokenUsage::default();
+    response.size_bytes = entry.body.len();
+    response
+        .headers
+        .insert(CACHE_STATUS_HEADER.to_string(), "hit".to_string());
+    response
+}
+
+/// 提取 OpenAI / Anthropic 非流式响应中的文本内容
+pub(crate) fn extract_json_content(body: &serde_json::Value) -> String {
+    if let Some(content) = body["choices"][0]["message"]["content"].as_str() {
+        return content.to_string();
+    }
+    body["content"]
+        .as_array()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|block| block["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ResponseCacheConfig;
+    use crate::server::test_support::{body_text, test_state};
+    use chrono::Utc;
+
+    fn entry(endpoint: &str, is_stream: bool, body: &[u8]) -> ResponseCacheEntry {
+        ResponseCacheEntry {
+            cache_key: "k".to_string(),
+            endpoint: endpoint.to_string(),
+            model: "gpt-4o".to_string(),
+            is_stream,
+            status_code: 200,
+            content_type: if is_stream {
+                "text/event-stream"
+            } else {
+                "application/json"
+            }
+            .to_string(),
+            body: body.to_vec(),
+            hit_count: 0,
+            created_at: Utc::now(),
+            expires_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_cached_json_response_has_zero_usage() {
+        let body = br#"{"choices":[{"message":{"content":"hello"}}],"usage":{"prompt_tokens":10,"completion_tokens":2}}"#;
+        let response = cached_llm_response(&entry("/v1/chat/completions", false, body));
+        assert_eq!(response.content, "hello");
+        assert_eq!(response.usage.input_tokens, 0);
+        assert_eq!(response.usage.output_tokens, 0);
+        assert_eq!(response.size_bytes, body.len());
+    }
+
+    #[test]
+    fn test_cached_anthropic_stream_is_rebuilt() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude\",\"usage\":{\"input_tokens\":5,\"output_tokens\":0}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let response = cached_llm_response(&entry("/v1/messages", true, body.as_bytes()));
+        assert_eq!(response.content, "Hi");
+        assert_eq!(response.usage.input_tokens, 0);
+        assert_eq!(
+            response
+                .headers
+                .get(CACHE_STATUS_HEADER)
+                .map(String::as_str),
+            Some("hit")
+        );
+    }
+
+    /// 经过缓存包装转发一个 SSE 响应体，返回是否写入了缓存
+    async fn relay_and_lookup(body: &'static str) -> bool {
+        let state = test_state("openai");
+        state.response_cache.set_config(ResponseCacheConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let request = serde_json::json!({"model": "gpt-4o", "stream": true, "messages": []});
+        let key = CacheKey::new("/v1/chat/completions", &request, None);
+
+        let upstream = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .body(Body::from(body))
+            .unwrap();
+        let response = cache_upstream_response(&state, Some(key.clone()), upstream);
+        // 客户端仍收到完整的原始响应体
+        assert_eq!(body_text(response).await, body);
+
+        let db = state.db.as_ref().unwrap();
+        state.response_cache.lookup(db, &key).is_some()
+    }
+
+    #[tokio::test]
+    async fn test_errored_stream_is_not_cached() {
+        let errored = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"error\":{\"message\":\"upstream overloaded\"}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        assert!(!relay_and_lookup(errored).await);
+
+        let truncated = "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n";
+        assert!(!relay_and_lookup(truncated).await);
+
+        let complete = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        assert!(relay_and_lookup(complete).await);
+    }
+}
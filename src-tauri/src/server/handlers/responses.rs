@@ -0,0 +1,256 @@
+//! OpenAI Responses API 端点处理器
+//!
+//! `/v1/responses` 请求转换为 chat completions 请求后复用 `chat_completions`
+//! 的模型解析、Provider 路由、凭证池与 Flow Monitor 逻辑，再将结果转换回
+//! Responses 格式（非流式为 response 对象，流式为语义化 SSE 事件）。
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use crate::converter::openai_responses::{
+    chat_completion_to_chunk, convert_openai_to_responses, convert_responses_to_openai,
+    ResponsesStreamConverter,
+};
+use crate::server::AppState;
+
+use super::chat_completions;
+
+/// Responses API 单次请求体大小上限（非流式读取上游响应时使用）
+const MAX_RESPONSE_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+fn invalid_request(message: impl Into<String>) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": {
+                "message": message.into(),
+                "type": "invalid_request_error"
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// POST /v1/responses
+pub async fn openai_responses(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    // 代理不保存会话状态，无法按 previous_response_id 续接对话
+    if request
+        .get("previous_response_id")
+        .is_some_and(|v| !v.is_null())
+    {
+        return invalid_request(
+            "previous_response_id is not supported by this proxy; send the full conversation in input instead",
+        );
+    }
+
+    // 认证（含客户端 key 的模型白名单和配额）由 chat_completions 统一处理
+    let chat_request = match convert_responses_to_openai(&request) {
+        Ok(req) => req,
+        Err(e) => return invalid_request(e),
+    };
+
+    let model = chat_request.model.clone();
+    let stream = chat_request.stream;
+    state.logs.write().await.add(
+        "info",
+        &format!(
+            "POST /v1/responses model={} stream={} messages={}",
+            model,
+            stream,
+            chat_request.messages.len()
+        ),
+    );
+
+    let response = chat_completions(State(state.clone()), headers, Json(chat_request)).await;
+
+    // 错误状态直接透传（错误体已是 OpenAI 格式）
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let is_sse = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"));
+
+    if stream {
+        stream_responses(response, model, is_sse)
+    } else {
+        non_stream_responses(response, &model).await
+    }
+}
+
+async fn non_stream_responses(response: Response, model: &str) -> Response {
+    let bytes = match axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(e) => return (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": {"message": format!("Failed to read upstream response: {}", e)}})),
+        )
+            .into_response(),
+    };
+
+    match serde_json::from_slice::<Value>(&bytes) {
+        Ok(chat_response) => {
+            Json(convert_openai_to_responses(&chat_response, model)).into_response()
+        }
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": {"message": format!("Invalid upstream response: {}", e)}})),
+        )
+            .into_response(),
+    }
+}
+
+fn stream_responses(response: Response, model: String, is_sse: bool) -> Response {
+    let mut upstream = response.into_body().into_data_stream();
+
+    let output = async_stream::stream! {
+        let mut converter = ResponsesStreamConverter::new(model);
+        for event in converter.start() {
+            yield Ok::<String, std::io::Error>(event);
+        }
+
+        // 按字节缓冲，多字节字符跨数据块时在完整的行内再解码
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = upstream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    for event in converter.fail(&e.to_string()) {
+                        yield Ok(event);
+                    }
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&chunk);
+
+            if !is_sse {
+                continue;
+            }
+
+            // 按行解析上游 SSE，保留不完整的尾部
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\r', '\n']);
+
+                let Some(data) = line.strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data.is_empty() || data == "[DONE]" {
+                    continue;
+                }
+                match serde_json::from_str::<Value>(data) {
+                    Ok(value) if value.get("error").is_some() => {
+                        let message = value["error"]["message"]
+                            .as_str()
+                            .unwrap_or("upstream error")
+                            .to_string();
+                        for event in converter.fail(&message) {
+                            yield Ok(event);
+                        }
+                        return;
+                    }
+                    Ok(value) => {
+                        for event in converter.process_chunk(&value) {
+                            yield Ok(event);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!("[RESPONSES] 跳过无法解析的 SSE 数据: {}", e);
+                    }
+                }
+            }
+        }
+
+        // 上游返回了非流式 JSON（部分 Provider 不支持流式），整体转换为单个 chunk
+        if !is_sse {
+            match serde_json::from_slice::<Value>(&buffer) {
+                Ok(value) => {
+                    for event in converter.process_chunk(&chat_completion_to_chunk(&value)) {
+                        yield Ok(event);
+                    }
+                }
+                Err(e) => {
+                    for event in converter.fail(&format!("Invalid upstream response: {}", e)) {
+                        yield Ok(event);
+                    }
+                    return;
+                }
+            }
+        }
+
+        for event in converter.finish() {
+            yield Ok(event);
+        }
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::CONNECTION, "keep-alive")
+        .body(Body::from_stream(output))
+        .unwrap_or_else(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": {"message": "Failed to build stream response"}})),
+            )
+                .into_response()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{auth_headers, body_text, test_state};
+
+    #[tokio::test]
+    async fn test_previous_response_id_is_rejected() {
+        let state = test_state("openai");
+        let response = openai_responses(
+            State(state),
+            auth_headers(),
+            Json(json!({
+                "model": "gpt-4o",
+                "input": "continue",
+                "previous_response_id": "resp_123"
+            })),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.contains("previous_response_id"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_decodes_characters_split_across_chunks() {
+        let payload = format!(
+            "data: {}\n\ndata: [DONE]\n\n",
+            json!({"choices": [{"index": 0, "delta": {"content": "你好"}}]})
+        );
+        // 在多字节字符中间切分
+        let split = payload.find("你").unwrap() + 1;
+        let (first, second) = payload.as_bytes().split_at(split);
+        let chunks = vec![Ok::<_, std::io::Error>(first.to_vec()), Ok(second.to_vec())];
+        let upstream = Response::new(Body::from_stream(futures::stream::iter(chunks)));
+
+        let body = body_text(stream_responses(upstream, "gpt-4o".to_string(), true)).await;
+        assert!(body.contains("你好"));
+        assert!(!body.contains('\u{FFFD}'));
+    }
+}
@@ -0,0 +1,276 @@
+//! 响应 Token 用量计量
+//!
+//! 包装发给客户端的响应体，从上游返回的真实 usage 中统计 Token：
+//! - JSON 响应：读取 `usage` 字段（OpenAI / Anthropic 两种格式）
+//! - SSE 响应：读取 Anthropic `message_start` / `message_delta` 或 OpenAI 末尾数据块中的 `usage`
+//!
+//! 响应体发送完毕后按实际用量计入遥测、预算和客户端 key 的 TPM 窗口。
+//! 上游没有返回 usage 时（如未开启 `include_usage` 的 OpenAI 流），
+//! 按实际输出的文本长度估算输出 Token，并标记为估算值。
+
+use axum::{body::Body, response::Response};
+use futures::StreamExt;
+
+use crate::processor::RequestContext;
+use crate::server::{record_token_usage_with_cache, AppState};
+use crate::telemetry::TokenSource;
+
+/// 从响应中读取到的实际 Token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MeteredUsage {
+    /// 未命中缓存的输入 Token
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub cache_read_tokens: u32,
+    pub cache_write_tokens: u32,
+}
+
+/// 增量解析响应体中的 usage
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    /// 未完成的 SSE 事件缓冲
+    buffer: Vec<u8>,
+    usage: MeteredUsage,
+    seen: bool,
+    /// 已输出文本的字符数（无 usage 时用于估算）
+    output_chars: usize,
+}
+
+impl UsageMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理一段 SSE 字节
+    pub fn observe_sse_chunk(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        while let Some(pos) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..pos + 2).collect();
+            let text = String::from_utf8_lossy(&event);
+            for line in text.lines() {
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+                    self.observe_json(&json);
+                }
+            }
+        }
+    }
+
+    /// 处理一个 JSON 响应体或 SSE 数据块
+    pub fn observe_json(&mut self, json: &serde_json::Value) {
+        self.output_chars += output_text_len(json);
+
+        // Anthropic message_start 的 usage 位于 message 内
+        let usage = json
+            .pointer("/message/usage")
+            .or_else(|| json.get("usage"))
+            .filter(|u| u.is_object());
+        let Some(usage) = usage else {
+            return;
+        };
+        let read = |key: &str| usage.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+
+        if let Some(prompt_tokens) = read("prompt_tokens") {
+            // OpenAI：prompt_tokens 包含命中缓存的部分
+            let cached = usage
+                .pointer("/prompt_tokens_details/cached_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as u32;
+            let cached = cached.min(prompt_tokens);
+            self.usage.input_tokens = prompt_tokens - cached;
+            self.usage.cache_read_tokens = cached;
+            if let Some(output) = read("completion_tokens") {
+                self.usage.output_tokens = output;
+            }
+            self.seen = true;
+            return;
+        }
+
+        // Anthropic：input_tokens 不含缓存读写；message_delta 中的 output_tokens 为累计值
+        if let Some(input) = read("input_tokens") {
+            self.usage.input_tokens = input;
+            self.seen = true;
+        }
+        if let Some(output) = read("output_tokens") {
+            self.usage.output_tokens = output;
+            self.seen = true;
+        }
+        if let Some(cache_read) = read("cache_read_input_tokens") {
+            self.usage.cache_read_tokens = cache_read;
+        }
+        if let Some(cache_write) = read("cache_creation_input_tokens") {
+            self.usage.cache_write_tokens = cache_write;
+        }
+    }
+
+    /// 读取到的用量；响应中没有 usage 时返回 None
+    pub fn usage(&self) -> Option<MeteredUsage> {
+        self.seen.then_some(self.usage)
+    }
+
+    /// 按已输出文本估算的输出 Token（约 4 字符 = 1 token）
+    pub fn estimated_output_tokens(&self) -> u32 {
+        (self.output_chars / 4) as u32
+    }
+}
+
+/// 提取 JSON 响应体或 SSE 数据块中输出文本的长度
+fn output_text_len(json: &serde_json::Value) -> usize {
+    let str_len = |v: Option<&serde_json::Value>| v.and_then(|t| t.as_str()).map_or(0, str::len);
+
+    // Anthropic 流式增量 / OpenAI 流式增量
+    let delta = str_len(json.pointer("/delta/text"))
+        + str_len(json.pointer("/delta/partial_json"))
+        + str_len(json.pointer("/choices/0/delta/content"));
+    // OpenAI 非流式响应
+    let message = str_len(json.pointer("/choices/0/message/content"));
+    // Anthropic 非流式响应
+    let blocks = json
+        .get("content")
+        .and_then(|c| c.as_array())
+        .map_or(0, |blocks| {
+            blocks.iter().map(|b| str_len(b.get("text"))).sum()
+        });
+    delta + message + blocks
+}
+
+/// 响应扩展：响应体的用量已按每次上游尝试分别计入所用凭证（如断流续传的 SSE 中继），
+/// [`meter_response_usage`] 不再对整个响应体重复计量
+#[derive(Debug, Clone, Copy)]
+pub struct MeteredPerAttempt;
+
+/// 按计量结果记录 Token 用量
+///
+/// 没有读取到 usage 时，若提供了 `estimated_input_tokens`，按输出文本长度估算计入；否则不计量
+pub fn record_metered_usage(
+    state: &AppState,
+    ctx: &RequestContext,
+    meter: &UsageMeter,
+    estimated_input_tokens: Option<u32>,
+) {
+    if let Some(usage) = meter.usage() {
+        record_token_usage_with_cache(
+            state,
+            ctx,
+            Some(usage.input_tokens),
+            Some(usage.output_tokens),
+            (usage.cache_read_tokens, usage.cache_write_tokens),
+            TokenSource::Actual,
+        );
+    } else if let Some(input_tokens) = estimated_input_tokens {
+        record_token_usage_with_cache(
+            state,
+            ctx,
+            Some(input_tokens),
+            Some(meter.estimated_output_tokens()),
+            (0, 0),
+            TokenSource::Estimated,
+        );
+    }
+}
+
+/// 包装响应体，发送完毕后按上游返回的实际用量记录 Token
+///
+/// 响应中没有 usage 时，若提供了 `estimated_input_tokens`，按输出文本长度估算计入；
+/// 否则不计量。非 2xx 响应和带 [`MeteredPerAttempt`] 扩展的响应不计量
+pub fn meter_response_usage(
+    state: &AppState,
+    ctx: RequestContext,
+    response: Response,
+    estimated_input_tokens: Option<u32>,
+) -> Response {
+    if !response.status().is_success() || response.extensions().get::<MeteredPerAttempt>().is_some()
+    {
+        return response;
+    }
+    let is_sse = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+
+    let state = state.clone();
+    let (parts, body) = response.into_parts();
+    let mut upstream = body.into_data_stream();
+    let stream = async_stream::stream! {
+        let mut meter = UsageMeter::new();
+        let mut json_body = Vec::new();
+        while let Some(chunk) = upstream.next().await {
+            if let Ok(bytes) = &chunk {
+                if is_sse {
+                    meter.observe_sse_chunk(bytes);
+                } else {
+                    json_body.extend_from_slice(bytes);
+                }
+            }
+            yield chunk;
+        }
+        if !is_sse {
+            if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&json_body) {
+                meter.observe_json(&json);
+            }
+        }
+        record_metered_usage(&state, &ctx, &meter, estimated_input_tokens);
+    };
+
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anthropic_stream_usage() {
+        let mut meter = UsageMeter::new();
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1,\"cache_read_input_tokens\":100,\"cache_creation_input_tokens\":40}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":27}}\n\n",
+        );
+        // 按任意位置切分，验证跨块缓冲
+        let (a, b) = body.as_bytes().split_at(57);
+        meter.observe_sse_chunk(a);
+        meter.observe_sse_chunk(b);
+
+        assert_eq!(
+            meter.usage(),
+            Some(MeteredUsage {
+                input_tokens: 12,
+                output_tokens: 27,
+                cache_read_tokens: 100,
+                cache_write_tokens: 40,
+            })
+        );
+    }
+
+    #[test]
+    fn test_openai_json_usage_excludes_cached_input() {
+        let mut meter = UsageMeter::new();
+        meter.observe_json(&serde_json::json!({
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 50,
+                "completion_tokens": 8,
+                "prompt_tokens_details": {"cached_tokens": 30}
+            }
+        }));
+        let usage = meter.usage().unwrap();
+        assert_eq!(usage.input_tokens, 20);
+        assert_eq!(usage.cache_read_tokens, 30);
+        assert_eq!(usage.output_tokens, 8);
+
+        // 没有 usage 的流按输出文本估算
+        let mut empty = UsageMeter::new();
+        empty.observe_sse_chunk(
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"abcdefgh\"}}]}\n\ndata: [DONE]\n\n",
+        );
+        assert_eq!(empty.usage(), None);
+        assert_eq!(empty.estimated_output_tokens(), 2);
+    }
+}
@@ -33,6 +33,8 @@ use crate::websocket::{
     WsApiRequest, WsApiResponse, WsEndpoint, WsError, WsFlowEvent, WsMessage as WsProtoMessage,
 };
 
+use super::provider_calls::cool_down_if_rate_limited;
+
 /// WebSocket 查询参数
 #[derive(Debug, Deserialize, Default)]
 pub struct WsQueryParams {
@@ -64,11 +66,12 @@ pub async fn ws_upgrade_handler(
         }
     };
 
+    // 接受共享 API Key（常量时间比较）或有效的客户端 key；握手不计为一次请求
     // 如果没有提供任何认证信息，允许连接（用于内部 Flow Monitor）
     // 但会在日志中记录
-    let authenticated = match key {
-        Some(k) if k == state.api_key => true,
-        Some(_) => {
+    let authenticated = match key.map(|k| super::api::authenticate_key(&state, k, None)) {
+        Some(Ok(_)) => true,
+        Some(Err(_)) => {
             return axum::http::Response::builder()
                 .status(401)
                 .body(Body::from("Invalid API key"))
@@ -657,8 +660,18 @@ pub async fn call_provider_openai_for_ws(
                     }
                 }))
             } else {
+                let status = resp.status().as_u16();
+                let headers = resp.headers().clone();
                 let body = resp.text().await.unwrap_or_default();
-                if let Some(db) = &state.db {
+                let rate_limited = cool_down_if_rate_limited(
+                    state,
+                    credential,
+                    status,
+                    Some(&headers),
+                    &body,
+                    &request.model,
+                );
+                if let (false, Some(db)) = (rate_limited, &state.db) {
                     let _ = state
                         .pool_service
                         .mark_unhealthy(db, &credential.uuid, Some(&body));
@@ -694,8 +707,18 @@ pub async fn call_provider_openai_for_ws(
                     .await
                     .map_err(|e| e.to_string())
             } else {
+                let status = resp.status().as_u16();
+                let headers = resp.headers().clone();
                 let body = resp.text().await.unwrap_or_default();
-                if let Some(db) = &state.db {
+                let rate_limited = cool_down_if_rate_limited(
+                    state,
+                    credential,
+                    status,
+                    Some(&headers),
+                    &body,
+                    &request.model,
+                );
+                if let (false, Some(db)) = (rate_limited, &state.db) {
                     let _ = state
                         .pool_service
                         .mark_unhealthy(db, &credential.uuid, Some(&body));
@@ -879,8 +902,18 @@ pub async fn call_provider_anthropic_for_ws(
                     .await
                     .map_err(|e| e.to_string())
             } else {
+                let status = resp.status().as_u16();
+                let headers = resp.headers().clone();
                 let body = resp.text().await.unwrap_or_default();
-                if let Some(db) = &state.db {
+                let rate_limited = cool_down_if_rate_limited(
+                    state,
+                    credential,
+                    status,
+                    Some(&headers),
+                    &body,
+                    &request.model,
+                );
+                if let (false, Some(db)) = (rate_limited, &state.db) {
                     let _ = state
                         .pool_service
                         .mark_unhealthy(db, &credential.uuid, Some(&body));
@@ -24,6 +24,7 @@ use crate::providers::gemini::GeminiProvider;
 use crate::providers::kiro::KiroProvider;
 use crate::providers::openai_custom::OpenAICustomProvider;
 use crate::providers::qwen::QwenProvider;
+use crate::providers::ProviderError;
 use crate::server_utils::{
     build_anthropic_response, build_anthropic_stream_response, build_error_response,
     build_error_response_with_status, build_gemini_cli_request, build_gemini_native_request,
@@ -83,15 +84,30 @@ pub fn record_request_telemetry(
         log.set_credential_id(cred_id.clone());
     }
 
+    // 设置客户端 key ID
+    if let Some(client_key_id) = ctx.client_key_id() {
+        log.set_client_key_id(client_key_id.to_string());
+    }
+
     // 设置重试次数
     log.retry_count = ctx.retry_count;
 
+    // 写入链路追踪根 span
+    let trace_status = log.http_status.unwrap_or(match status {
+        crate::telemetry::RequestStatus::Success => 200,
+        _ => 500,
+    });
+    ctx.record_trace_outcome(trace_status, error_message.as_deref());
+
     // 记录到统计聚合器
     {
         let stats = state.processor.stats.write();
         stats.record(log.clone());
     }
 
+    // 累加 Prometheus 指标
+    state.processor.metrics.observe_request(&log);
+
     // 记录到请求日志记录器（用于前端日志列表显示）
     if let Some(logger) = &state.request_logger {
         let _ = logger.record(log.clone());
@@ -114,7 +130,40 @@ pub fn record_token_usage(
     input_tokens: Option<u32>,
     output_tokens: Option<u32>,
 ) {
-    use crate::telemetry::{TokenSource, TokenUsageRecord};
+    record_token_usage_with_source(
+        state,
+        ctx,
+        input_tokens,
+        output_tokens,
+        crate::telemetry::TokenSource::Actual,
+    );
+}
+
+/// 记录 Token 使用量到遥测系统（指定数据来源：实际值或估算值）
+pub fn record_token_usage_with_source(
+    state: &AppState,
+    ctx: &RequestContext,
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+    source: crate::telemetry::TokenSource,
+) {
+    record_token_usage_with_cache(state, ctx, input_tokens, output_tokens, (0, 0), source);
+}
+
+/// 记录 Token 使用量到遥测系统（包含缓存读取 / 写入 Token）
+///
+/// `input_tokens` 为未命中缓存的输入 Token。按模型定价计算费用并计入预算，
+/// 花费达到预警比例或超出预算时发送通知
+pub fn record_token_usage_with_cache(
+    state: &AppState,
+    ctx: &RequestContext,
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+    (cache_read_tokens, cache_write_tokens): (u32, u32),
+    source: crate::telemetry::TokenSource,
+) {
+    use crate::services::budget_service::BudgetSubject;
+    use crate::telemetry::TokenUsageRecord;
 
     // 只有当至少有一个 Token 值时才记录
     if input_tokens.is_none() && output_tokens.is_none() {
@@ -128,9 +177,25 @@ pub fn record_token_usage(
         ctx.resolved_model.clone(),
         input_tokens.unwrap_or(0),
         output_tokens.unwrap_or(0),
-        TokenSource::Actual,
+        source,
     )
-    .with_request_id(ctx.request_id.clone());
+    .with_request_id(ctx.request_id.clone())
+    .with_client_key_id(ctx.client_key_id().map(|s| s.to_string()))
+    .with_cache_tokens(cache_read_tokens, cache_write_tokens);
+
+    // 按模型定价计算费用并计入预算
+    let budget = &state.processor.budget;
+    let cost = budget.cost(
+        &ctx.resolved_model,
+        input_tokens.unwrap_or(0),
+        output_tokens.unwrap_or(0),
+        cache_read_tokens,
+        cache_write_tokens,
+    );
+    let record = match &cost {
+        Some((cost, currency)) => record.with_cost(*cost, currency.clone()),
+        None => record,
+    };
 
     // 记录到 Token 追踪器
     {
@@ -138,6 +203,39 @@ pub fn record_token_usage(
         tokens.record(record);
     }
 
+    if let Some((cost, currency)) = cost {
+        let alerts = budget.record_spend(&BudgetSubject::from_context(ctx), cost, &currency);
+        for alert in alerts {
+            tracing::warn!("[BUDGET] {}: {}", alert.title(), alert.message());
+            let flow_monitor = state.flow_monitor.clone();
+            let flow_id = ctx.request_id.clone();
+            tokio::spawn(async move {
+                flow_monitor
+                    .notify_budget_warning(&flow_id, alert.title(), alert.message())
+                    .await;
+            });
+        }
+    }
+
+    ctx.record_trace_usage(input_tokens.unwrap_or(0), output_tokens.unwrap_or(0));
+
+    // 累加 Prometheus 指标
+    state.processor.metrics.observe_tokens(
+        provider,
+        &ctx.resolved_model,
+        ctx.credential_id.as_deref(),
+        input_tokens.unwrap_or(0),
+        output_tokens.unwrap_or(0),
+    );
+
+    // 计入客户端 key 的 TPM 窗口和月度预算
+    if let Some(client_key_id) = ctx.client_key_id() {
+        let total = input_tokens.unwrap_or(0) as u64 + output_tokens.unwrap_or(0) as u64;
+        state
+            .client_keys
+            .record_tokens(state.db.as_ref(), client_key_id, total);
+    }
+
     tracing::debug!(
         "[TOKEN] request_id={} input={} output={}",
         ctx.request_id,
@@ -146,6 +244,58 @@ pub fn record_token_usage(
     );
 }
 
+/// 为请求开启链路追踪（沿用请求头中的 W3C traceparent）
+///
+/// 未启用追踪或未被采样时返回 None
+pub fn start_request_trace(
+    state: &AppState,
+    headers: &HeaderMap,
+    name: &str,
+) -> Option<crate::telemetry::RequestTrace> {
+    let traceparent = headers.get("traceparent").and_then(|v| v.to_str().ok());
+    state.processor.tracer.start_trace(name, traceparent)
+}
+
+/// 用 `proxycast.stream` span 包裹流式响应体，使 trace 覆盖到流结束为止
+pub fn trace_stream_response(ctx: &RequestContext, response: Response) -> Response {
+    use futures::StreamExt;
+
+    let mut span = ctx.span("proxycast.stream");
+    if !span.is_recording() {
+        return response;
+    }
+    let trace = ctx.trace.clone();
+    let (parts, body) = response.into_parts();
+    let mut bytes = 0u64;
+    let stream = body.into_data_stream().map(move |chunk| {
+        match &chunk {
+            Ok(data) => {
+                bytes += data.len() as u64;
+                span.set_attribute("proxycast.stream.bytes", bytes);
+                if let (Some(trace), Some(reason)) = (&trace, extract_finish_reason(data)) {
+                    trace.set_attribute("gen_ai.response.finish_reasons", vec![reason]);
+                }
+            }
+            Err(e) => span.set_error(&e.to_string()),
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+/// 从 SSE 数据块中提取结束原因（OpenAI `finish_reason` / Anthropic `stop_reason`）
+fn extract_finish_reason(chunk: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(chunk).ok()?;
+    ["\"finish_reason\"", "\"stop_reason\""]
+        .iter()
+        .filter_map(|key| {
+            let rest = text[text.rfind(key)? + key.len()..].trim_start();
+            let rest = rest.strip_prefix(':')?.trim_start().strip_prefix('"')?;
+            Some(rest[..rest.find('"')?].to_string())
+        })
+        .next()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerStatus {
     pub running: bool,
@@ -174,6 +324,8 @@ pub struct ServerState {
     pub running_api_key: Option<String>,
     /// 服务器实际监听的 host（可能与配置不同，因为会自动切换到有效的 IP）
     pub running_host: Option<String>,
+    /// 配置文件路径（用于热重载，未设置时使用默认路径）
+    config_path: Option<std::path::PathBuf>,
 }
 
 impl ServerState {
@@ -200,9 +352,16 @@ impl ServerState {
             shutdown_tx: None,
             running_api_key: None,
             running_host: None,
+            config_path: None,
         }
     }
 
+    /// 指定配置文件路径（用于热重载）
+    pub fn with_config_path(mut self, config_path: std::path::PathBuf) -> Self {
+        self.config_path = Some(config_path);
+        self
+    }
+
     pub fn status(&self) -> ServerStatus {
         ServerStatus {
             running: self.running,
@@ -331,7 +490,10 @@ impl ServerState {
 
         // 获取配置和配置路径用于热重载
         let config = self.config.clone();
-        let config_path = crate::config::ConfigManager::default_config_path();
+        let config_path = self
+            .config_path
+            .clone()
+            .unwrap_or_else(crate::config::ConfigManager::default_config_path);
 
         // 创建请求处理器（在 spawn 之前创建，以便保存 router_ref）
         let processor = match (&shared_stats, &shared_tokens) {
@@ -441,6 +603,8 @@ impl Clone for KiroProvider {
 }
 
 pub mod handlers;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 #[derive(Clone)]
 #[allow(dead_code)]
@@ -482,6 +646,101 @@ pub struct AppState {
     pub kiro_event_service: Arc<KiroEventService>,
     /// API Key Provider 服务（用于智能降级）
     pub api_key_service: Arc<crate::services::api_key_provider_service::ApiKeyProviderService>,
+    /// Anthropic 请求 Token 计数器
+    pub token_counter: Arc<crate::telemetry::AnthropicTokenCounter>,
+    /// Token 计数配置（启动时的配置，启用热重载时以热重载管理器中的配置为准）
+    pub token_counting: crate::config::TokenCountingConfig,
+    /// 重试配置（流式断流续传）
+    pub retry_settings: crate::config::RetrySettings,
+    /// 多租户客户端 API Key 服务
+    pub client_keys: Arc<crate::services::client_key_service::ClientKeyService>,
+    /// 响应缓存服务
+    pub response_cache: Arc<crate::services::response_cache_service::ResponseCacheService>,
+}
+
+/// 将 Token 提前刷新结果转发到 Flow 事件总线
+fn spawn_token_refresh_forwarder(
+    scheduler: &crate::services::token_refresh_scheduler::TokenRefreshScheduler,
+    flow_monitor: Arc<FlowMonitor>,
+) -> tokio::task::JoinHandle<()> {
+    let mut events = scheduler.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => flow_monitor.publish_token_refresh(event),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[TOKEN_REFRESH] 事件转发落后，丢弃 {} 条刷新结果", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// 将凭证健康探测结果转发到 Flow 事件总线
+fn spawn_canary_forwarder(
+    prober: &crate::services::canary_service::CanaryProber,
+    flow_monitor: Arc<FlowMonitor>,
+) -> tokio::task::JoinHandle<()> {
+    let mut events = prober.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => flow_monitor.publish_canary_probe(event),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[CANARY] 事件转发落后，丢弃 {} 条探测结果", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// 将告警事件转发到 Flow 事件总线
+fn spawn_alert_forwarder(
+    alerts: &crate::services::alert_service::AlertService,
+    flow_monitor: Arc<FlowMonitor>,
+) -> tokio::task::JoinHandle<()> {
+    let mut events = alerts.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => flow_monitor.publish_alert(event),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[ALERT] 事件转发落后，丢弃 {} 条告警事件", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// 订阅 Flow 完成事件，将首字节时间（TTFB）写入 Prometheus 指标
+fn spawn_flow_metrics_feeder(
+    flow_monitor: Arc<FlowMonitor>,
+    metrics: Arc<crate::telemetry::MetricsRegistry>,
+) {
+    let mut events = flow_monitor.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let id = match events.recv().await {
+                Ok(crate::flow_monitor::FlowEvent::FlowCompleted { id, .. }) => id,
+                Ok(_) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::debug!("[METRICS] Flow 事件积压，跳过 {} 条", skipped);
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+
+            let flow = flow_monitor.memory_store().read().await.get(&id);
+            let Some(flow) = flow else { continue };
+            let Ok(flow) = flow.read() else { continue };
+            if let Some(ttfb_ms) = flow.timestamps.ttfb_ms {
+                metrics.observe_ttfb(flow.metadata.provider, &flow.request.model, ttfb_ms);
+            }
+        }
+    });
 }
 
 /// 启动配置文件监控
@@ -502,6 +761,7 @@ async fn start_config_watcher(
     logs: Arc<RwLock<LogStore>>,
     db: Option<DbConnection>,
     config_manager: Option<Arc<std::sync::RwLock<ConfigManager>>>,
+    token_counter: Arc<crate::telemetry::AnthropicTokenCounter>,
 ) -> Option<FileWatcher> {
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<FileChangeEvent>();
 
@@ -556,6 +816,8 @@ async fn start_config_watcher(
                         // 更新处理器中的组件
                         let new_config = manager.config();
                         update_processor_config(&processor_clone, &new_config).await;
+                        token_counter
+                            .set_calibration_overrides(new_config.token_counting.calibration);
 
                         // 同步凭证池
                         if let (Some(ref db), Some(ref cfg_manager)) =
@@ -673,6 +935,33 @@ async fn update_processor_config(processor: &RequestProcessor, config: &Config)
         }
     }
 
+    // 更新路由规则
+    load_routing_rules(processor, &config.routing.rules, "HOT_RELOAD").await;
+
+    // 更新模型等价组、熔断器、并发限制、凭证负载均衡策略和对冲配置
+    processor
+        .pool_service
+        .circuit_breaker()
+        .set_config(config.circuit_breaker.clone());
+    processor
+        .pool_service
+        .concurrency()
+        .set_config(config.concurrency.clone());
+    processor
+        .pool_service
+        .set_balance_strategies(config.credential_pool.strategies.clone());
+    processor.hedge.set_config(config.hedging.clone());
+    processor
+        .fallback
+        .write()
+        .set_equivalence_groups(config.routing.equivalence_groups.clone());
+    processor.budget.set_config(config.budget.clone());
+    processor
+        .token_refresh
+        .set_config(config.token_refresh.clone());
+    processor.canary.set_config(config.canary.clone());
+    processor.alerts.set_config(config.alerts.clone());
+
     // 更新模型映射器
     {
         let mut mapper = processor.mapper.write().await;
@@ -686,6 +975,9 @@ async fn update_processor_config(processor: &RequestProcessor, config: &Config)
         );
     }
 
+    // 更新链路追踪导出配置
+    processor.tracer.configure(&config.otel);
+
     // 注意：重试配置目前不支持热更新，因为 Retrier 是不可变的
     // 如果需要更新重试配置，需要重启服务器
     tracing::debug!(
@@ -749,6 +1041,24 @@ async fn sync_credential_pool_from_config(
     Ok(synced_count)
 }
 
+/// 加载声明式路由规则到处理器的 Router，无效规则记录警告后跳过
+async fn load_routing_rules(
+    processor: &RequestProcessor,
+    rules: &[crate::config::RoutingRuleConfig],
+    tag: &str,
+) {
+    let mut router = processor.router.write().await;
+    for error in router.set_rules(rules) {
+        tracing::warn!("[{}] 跳过无效的路由规则: {}", tag, error);
+    }
+    tracing::info!(
+        "[{}] 路由规则已加载: {}/{} 条",
+        tag,
+        router.rules().len(),
+        rules.len()
+    );
+}
+
 async fn run_server(
     host: &str,
     port: u16,
@@ -794,6 +1104,11 @@ async fn run_server(
         }
     }
 
+    // 应用链路追踪配置
+    if let Some(cfg) = &config {
+        processor.tracer.configure(&cfg.otel);
+    }
+
     // 从配置初始化 Router 的默认 Provider
     if let Some(cfg) = &config {
         let default_provider_str = &cfg.routing.default_provider;
@@ -823,6 +1138,43 @@ async fn run_server(
         }
     }
 
+    // 加载声明式路由规则、模型等价组、熔断器、并发限制、凭证负载均衡策略和对冲配置
+    if let Some(cfg) = &config {
+        load_routing_rules(&processor, &cfg.routing.rules, "SERVER").await;
+        processor
+            .pool_service
+            .circuit_breaker()
+            .set_config(cfg.circuit_breaker.clone());
+        processor
+            .pool_service
+            .concurrency()
+            .set_config(cfg.concurrency.clone());
+        processor
+            .pool_service
+            .set_balance_strategies(cfg.credential_pool.strategies.clone());
+        processor.hedge.set_config(cfg.hedging.clone());
+        processor
+            .fallback
+            .write()
+            .set_equivalence_groups(cfg.routing.equivalence_groups.clone());
+        processor.budget.set_config(cfg.budget.clone());
+        processor
+            .token_refresh
+            .set_config(cfg.token_refresh.clone());
+        processor.canary.set_config(cfg.canary.clone());
+        processor.alerts.set_config(cfg.alerts.clone());
+    }
+
+    // 恢复重启前仍未过期的限流冷却
+    if let Some(db) = &db {
+        let restored = processor.pool_service.attach_cooldown_store(db);
+        if restored > 0 {
+            tracing::info!("[SERVER] 已恢复 {} 条限流冷却记录", restored);
+        }
+        // 加载本日 / 本月已产生的花费
+        processor.budget.attach_db(db.clone());
+    }
+
     // 初始化 WebSocket 管理器
     let ws_manager = Arc::new(WsConnectionManager::new(WsConfig::default()));
     let ws_stats = ws_manager.stats().clone();
@@ -876,6 +1228,41 @@ async fn run_server(
     let api_key_service =
         Arc::new(crate::services::api_key_provider_service::ApiKeyProviderService::new());
 
+    // 创建 Token 计数器（加载 BPE 词表，只在启动时执行一次）
+    let token_counting = config
+        .as_ref()
+        .map(|c| c.token_counting.clone())
+        .unwrap_or_default();
+    let token_counter = Arc::new(
+        crate::telemetry::AnthropicTokenCounter::new()
+            .map_err(|e| e.to_string())?
+            .with_calibration_overrides(token_counting.calibration.clone()),
+    );
+
+    let retry_settings = config.as_ref().map(|c| c.retry.clone()).unwrap_or_default();
+
+    // 加载客户端 API Key
+    let client_keys = Arc::new(crate::services::client_key_service::ClientKeyService::new());
+    if let Some(db) = &db {
+        match client_keys.load(db) {
+            Ok(count) if count > 0 => {
+                tracing::info!("[CLIENT_KEY] 已加载 {} 个客户端 key", count)
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("[CLIENT_KEY] 加载客户端 key 失败: {}", e),
+        }
+    }
+
+    // 响应缓存
+    let response_cache = Arc::new(
+        crate::services::response_cache_service::ResponseCacheService::new(
+            config
+                .as_ref()
+                .map(|c| c.response_cache.clone())
+                .unwrap_or_default(),
+        ),
+    );
+
     let state = AppState {
         api_key: api_key.to_string(),
         base_url,
@@ -901,12 +1288,53 @@ async fn run_server(
         endpoint_providers,
         kiro_event_service,
         api_key_service,
+        token_counter,
+        token_counting,
+        retry_settings,
+        client_keys,
+        response_cache,
     };
 
+    spawn_flow_metrics_feeder(state.flow_monitor.clone(), state.processor.metrics.clone());
+
+    // 后台提前刷新 OAuth Token，服务器停止时随句柄一起停止
+    let _token_refresh = state.db.clone().map(|db| {
+        state
+            .processor
+            .token_refresh
+            .spawn(db, state.token_cache.clone(), state.pool_service.clone())
+            .attach(spawn_token_refresh_forwarder(
+                &state.processor.token_refresh,
+                state.flow_monitor.clone(),
+            ))
+    });
+
+    // 后台定时探测凭证健康状态，服务器停止时随句柄一起停止
+    let _canary = state.db.clone().map(|db| {
+        state
+            .processor
+            .canary
+            .spawn(db, state.pool_service.clone())
+            .attach(spawn_canary_forwarder(
+                &state.processor.canary,
+                state.flow_monitor.clone(),
+            ))
+    });
+
+    // 后台评估告警规则，服务器停止时随句柄一起停止
+    let _alerts = state
+        .processor
+        .alerts
+        .spawn(state.flow_monitor.clone())
+        .attach(spawn_alert_forwarder(
+            &state.processor.alerts,
+            state.flow_monitor.clone(),
+        ));
+
     // ========== 开发模式：启动独立的 HTTP 桥接服务器 ==========
     // 仅在 debug 模式下，启动一个独立的开发服务器在端口 3030
     // 允许浏览器 dev server 通过 HTTP 调用 Tauri 命令
-    #[cfg(debug_assertions)]
+    #[cfg(all(debug_assertions, feature = "gui"))]
     {
         eprintln!("[DevBridge] ===== 准备启动开发桥接服务器 =====");
         use tokio::sync::RwLock as TokioRwLock;
@@ -934,6 +1362,7 @@ async fn run_server(
             logs_clone,
             db_clone,
             config_manager,
+            state.token_counter.clone(),
         )
         .await
     } else {
@@ -950,6 +1379,7 @@ async fn run_server(
         .unwrap_or_default();
 
     let management_routes = Router::new()
+        .route("/metrics", get(handlers::management_metrics))
         .route("/v0/management/status", get(handlers::management_status))
         .route(
             "/v0/management/credentials",
@@ -967,6 +1397,56 @@ async fn run_server(
             "/v0/management/config",
             axum::routing::put(handlers::management_update_config),
         )
+        .route(
+            "/v0/management/client-keys",
+            get(handlers::management_list_client_keys).post(handlers::management_create_client_key),
+        )
+        .route(
+            "/v0/management/client-keys/:id",
+            axum::routing::put(handlers::management_update_client_key)
+                .delete(handlers::management_delete_client_key),
+        )
+        .route(
+            "/v0/management/response-cache",
+            get(handlers::management_response_cache_stats)
+                .delete(handlers::management_clear_response_cache),
+        )
+        .route(
+            "/v0/management/routing/explain",
+            post(handlers::management_explain_routing),
+        )
+        .route(
+            "/v0/management/circuit-breakers",
+            get(handlers::management_circuit_breakers),
+        )
+        .route("/v0/management/budgets", get(handlers::management_budgets))
+        .route(
+            "/v0/management/playback",
+            get(handlers::management_playback_status),
+        )
+        .route(
+            "/v0/management/playback/sessions",
+            post(handlers::management_create_recording_session),
+        )
+        .route(
+            "/v0/management/playback/:session_id",
+            axum::routing::put(handlers::management_set_session_mode)
+                .delete(handlers::management_stop_session_mode),
+        )
+        .route("/v0/management/alerts", get(handlers::management_alerts))
+        .route(
+            "/v0/management/alerts/test",
+            post(handlers::management_test_alert_webhooks),
+        )
+        .route(
+            "/v0/management/intercept/rules",
+            get(handlers::management_get_intercept_rules)
+                .put(handlers::management_update_intercept_rules),
+        )
+        .route(
+            "/v0/management/credentials/:uuid/health-history",
+            get(handlers::management_credential_health_history),
+        )
         .layer(crate::middleware::ManagementAuthLayer::new(
             management_config,
         ));
@@ -1003,6 +1483,8 @@ async fn run_server(
         .route("/v1/models", get(models))
         .route("/v1/routes", get(list_routes))
         .route("/v1/chat/completions", post(handlers::chat_completions))
+        .route("/v1/responses", post(handlers::openai_responses))
+        .route("/v1/embeddings", post(handlers::embeddings))
         .route("/v1/messages", post(handlers::anthropic_messages))
         .route("/v1/messages/count_tokens", post(count_tokens))
         // 图像生成 API 路由
@@ -1075,19 +1557,126 @@ async fn run_server(
 async fn count_tokens(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(_request): Json<serde_json::Value>,
+    Json(request): Json<serde_json::Value>,
 ) -> Response {
-    if let Err(e) = handlers::verify_api_key(&headers, &state.api_key).await {
-        return e.into_response();
+    // 计数请求不占用客户端 key 的 RPM 窗口
+    let request_model = request.get("model").and_then(|m| m.as_str());
+    if let Err(e) = handlers::authenticate_client(&state, &headers, request_model) {
+        return e;
     }
 
-    // Claude Code 需要这个端点，返回估算值
+    let model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    // 优先使用上游原生计数端点（如果启用且路由到的凭证支持）
+    if current_token_counting(&state).use_native_endpoint {
+        if let Some(input_tokens) = count_tokens_native(&state, &headers, &model, &request).await {
+            return Json(serde_json::json!({ "input_tokens": input_tokens })).into_response();
+        }
+    }
+
+    let result = state.token_counter.count_request(&request);
+    tracing::debug!(
+        "[COUNT_TOKENS] model={} family={:?} calibration={} input_tokens={} breakdown={:?}",
+        model,
+        result.family,
+        result.calibration,
+        result.input_tokens,
+        result.breakdown
+    );
+
     Json(serde_json::json!({
-        "input_tokens": 100
+        "input_tokens": result.input_tokens
     }))
     .into_response()
 }
 
+/// 当前生效的 Token 计数配置（启用热重载时读取最新配置）
+fn current_token_counting(state: &AppState) -> crate::config::TokenCountingConfig {
+    match &state.hot_reload_manager {
+        Some(manager) => manager.config_ref().read().token_counting.clone(),
+        None => state.token_counting.clone(),
+    }
+}
+
+/// 调用上游原生 count_tokens 端点
+///
+/// 仅当路由到的凭证为 Anthropic 兼容 API Key 时可用，
+/// 其他凭证类型或调用失败时返回 None，由调用方回退到本地计数。
+/// 成功只更新健康状态并计入熔断器（释放半开状态占用的探测名额），不清除限流冷却；
+/// 只有认证失败、5xx 和网络错误标记凭证不健康，限流冷却凭证，400 等请求本身的错误不计入
+async fn count_tokens_native(
+    state: &AppState,
+    headers: &HeaderMap,
+    model: &str,
+    request: &serde_json::Value,
+) -> Option<u64> {
+    let db = state.db.as_ref()?;
+    let (selected_provider, _) = handlers::api::select_provider_for_client(headers, state).await;
+    // 只有 Anthropic 兼容的 API Key 凭证支持原生计数，其他 Provider 不选择凭证，
+    // 避免占用熔断器的探测名额却不上报结果
+    if !matches!(
+        selected_provider.parse::<crate::ProviderType>(),
+        Ok(crate::ProviderType::Claude | crate::ProviderType::Anthropic)
+    ) {
+        return None;
+    }
+    let credential = state
+        .pool_service
+        .select_credential(db, &selected_provider, Some(model))
+        .ok()
+        .flatten()?;
+
+    let (api_key, base_url) = match &credential.credential {
+        CredentialData::ClaudeKey { api_key, base_url }
+        | CredentialData::AnthropicKey { api_key, base_url } => (api_key.clone(), base_url.clone()),
+        _ => {
+            state.pool_service.release_selection(&credential.uuid);
+            return None;
+        }
+    };
+
+    let provider = ClaudeCustomProvider::with_config(api_key, base_url);
+    match provider.count_tokens(request).await {
+        Ok(resp) => {
+            state
+                .pool_service
+                .circuit_breaker()
+                .record_success(&credential.uuid);
+            let _ = state
+                .pool_service
+                .mark_probe_healthy(db, &credential.uuid, Some(model));
+            let input_tokens = resp.get("input_tokens").and_then(|t| t.as_u64());
+            if input_tokens.is_none() {
+                tracing::warn!("[COUNT_TOKENS] 上游响应缺少 input_tokens，回退到本地计数");
+            }
+            input_tokens
+        }
+        Err(e) => {
+            if matches!(
+                e,
+                ProviderError::AuthenticationError(_)
+                    | ProviderError::ServerError(_)
+                    | ProviderError::NetworkError(_)
+            ) {
+                let _ =
+                    state
+                        .pool_service
+                        .mark_unhealthy(db, &credential.uuid, Some(&e.to_string()));
+            } else {
+                // 限流按上游冷却，其他错误不影响健康状态，均归还半开状态的探测名额
+                handlers::cool_down_on_provider_error(state, &credential, &e, model);
+                state.pool_service.release_selection(&credential.uuid);
+            }
+            tracing::warn!("[COUNT_TOKENS] 上游计数失败，回退到本地计数: {}", e);
+            None
+        }
+    }
+}
+
 /// Gemini 原生协议处理
 /// 路由: POST /v1/gemini/{model}:{method}
 /// 例如: /v1/gemini/gemini-3-pro-preview:generateContent
@@ -1097,8 +1686,9 @@ async fn gemini_generate_content(
     Path(path): Path<String>,
     Json(request): Json<serde_json::Value>,
 ) -> Response {
-    if let Err(e) = handlers::verify_api_key(&headers, &state.api_key).await {
-        return e.into_response();
+    let path_model = path.split(':').next();
+    if let Err(e) = handlers::authorize_client(&state, &headers, path_model) {
+        return e;
     }
 
     // 解析路径: {model}:{method}
@@ -1492,29 +2082,24 @@ async fn list_routes(State(state): State<AppState>) -> impl IntoResponse {
             host == "0.0.0.0"
         } else {
             // 检查 IP 是否在当前网卡列表中
-            if let Ok(network_info) = crate::commands::network_cmd::get_network_info() {
-                !network_info.all_ips.contains(&host.to_string())
-            } else {
-                false
-            }
+            !crate::app::network::network_info()
+                .all_ips
+                .contains(&host.to_string())
         };
 
         if should_replace {
             // 获取局域网 IP 进行替换
             // 优先选择 192.168.x.x 或 10.x.x.x 开头的 IP（真正的局域网 IP）
-            if let Ok(network_info) = crate::commands::network_cmd::get_network_info() {
-                let new_ip = network_info
-                    .all_ips
-                    .iter()
-                    .find(|ip| ip.starts_with("192.168.") || ip.starts_with("10."))
-                    .or_else(|| network_info.lan_ip.as_ref())
-                    .or_else(|| network_info.all_ips.first())
-                    .cloned()
-                    .unwrap_or_else(|| "localhost".to_string());
-                state.base_url.replace(host, &new_ip)
-            } else {
-                state.base_url.replace(host, "localhost")
-            }
+            let network_info = crate::app::network::network_info();
+            let new_ip = network_info
+                .all_ips
+                .iter()
+                .find(|ip| ip.starts_with("192.168.") || ip.starts_with("10."))
+                .or_else(|| network_info.lan_ip.as_ref())
+                .or_else(|| network_info.all_ips.first())
+                .cloned()
+                .unwrap_or_else(|| "localhost".to_string());
+            state.base_url.replace(host, &new_ip)
         } else {
             state.base_url.clone()
         }
@@ -1562,6 +2147,45 @@ async fn list_routes(State(state): State<AppState>) -> impl IntoResponse {
     Json(response)
 }
 
+/// 为直连路由（选择器 / Amp）创建请求上下文，携带客户端 key 身份
+fn direct_route_context(
+    original_model: &str,
+    model: &str,
+    is_stream: bool,
+    client_key: Option<crate::services::client_key_service::ClientIdentity>,
+) -> RequestContext {
+    let mut ctx = RequestContext::new(original_model.to_string()).with_stream(is_stream);
+    ctx.set_resolved_model(model.to_string());
+    ctx.set_client_key(client_key);
+    ctx
+}
+
+/// 记录直连路由的请求统计，并按上游返回的实际用量计量 Token
+///
+/// 使请求和 Token 归属到发起请求的客户端 key（TPM 窗口、预算和日志）；
+/// 并发许可绑定到响应体，响应结束时释放
+fn finish_direct_route(
+    state: &AppState,
+    mut ctx: RequestContext,
+    credential: &crate::models::provider_pool_model::ProviderCredential,
+    response: Response,
+    permit: Option<crate::resilience::RequestPermit>,
+) -> Response {
+    ctx.set_provider(credential.provider_type);
+    ctx.set_credential_id(credential.uuid.clone());
+    let status = if response.status().is_success() {
+        crate::telemetry::RequestStatus::Success
+    } else {
+        crate::telemetry::RequestStatus::Failed
+    };
+    record_request_telemetry(state, &ctx, status, None);
+    let response = handlers::meter_response_usage(state, ctx, response, None);
+    match permit {
+        Some(permit) => handlers::hold_concurrency_permit(response, permit),
+        None => response,
+    }
+}
+
 /// 带选择器的 Anthropic messages 处理
 async fn anthropic_messages_with_selector(
     State(state): State<AppState>,
@@ -1570,13 +2194,17 @@ async fn anthropic_messages_with_selector(
     Json(request): Json<AnthropicMessagesRequest>,
 ) -> Response {
     // 使用 Anthropic 格式的认证验证
-    if let Err(e) = handlers::verify_api_key_anthropic(&headers, &state.api_key).await {
-        state.logs.write().await.add(
-            "warn",
-            &format!("Unauthorized request to /{}/v1/messages", selector),
-        );
-        return e.into_response();
-    }
+    let client_key =
+        match handlers::authorize_client_anthropic(&state, &headers, Some(&request.model)) {
+            Ok(client_key) => client_key,
+            Err(e) => {
+                state.logs.write().await.add(
+                    "warn",
+                    &format!("Unauthorized request to /{}/v1/messages", selector),
+                );
+                return e;
+            }
+        };
 
     state.logs.write().await.add(
         "info",
@@ -1625,7 +2253,20 @@ async fn anthropic_messages_with_selector(
 
             // 根据凭证类型调用相应的 Provider
             // 注意：这里没有 Flow 捕获，因为是通过 selector 路由的请求
-            handlers::call_provider_anthropic(&state, &cred, &request, None).await
+            let ctx =
+                direct_route_context(&request.model, &request.model, request.stream, client_key);
+            let permit = match handlers::acquire_concurrency_permit(
+                &state, &headers, &ctx, &cred, None, true,
+            )
+            .await
+            {
+                Ok(permit) => permit,
+                Err(response) => return response,
+            };
+            let resume = handlers::ResumeScope::new(&state, &headers, &ctx);
+            let response =
+                handlers::call_provider_anthropic(&state, &cred, &request, None, &resume).await;
+            finish_direct_route(&state, ctx, &cred, response, permit)
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
@@ -1657,13 +2298,16 @@ async fn chat_completions_with_selector(
     headers: HeaderMap,
     Json(request): Json<ChatCompletionRequest>,
 ) -> Response {
-    if let Err(e) = handlers::verify_api_key(&headers, &state.api_key).await {
-        state.logs.write().await.add(
-            "warn",
-            &format!("Unauthorized request to /{}/v1/chat/completions", selector),
-        );
-        return e.into_response();
-    }
+    let client_key = match handlers::authorize_client(&state, &headers, Some(&request.model)) {
+        Ok(client_key) => client_key,
+        Err(e) => {
+            state.logs.write().await.add(
+                "warn",
+                &format!("Unauthorized request to /{}/v1/chat/completions", selector),
+            );
+            return e;
+        }
+    };
 
     state.logs.write().await.add(
         "info",
@@ -1706,7 +2350,18 @@ async fn chat_completions_with_selector(
             );
 
             // 注意：这里没有 Flow 捕获，因为是通过 selector 路由的请求
-            handlers::call_provider_openai(&state, &cred, &request, None).await
+            let ctx =
+                direct_route_context(&request.model, &request.model, request.stream, client_key);
+            let permit = match handlers::acquire_concurrency_permit(
+                &state, &headers, &ctx, &cred, None, false,
+            )
+            .await
+            {
+                Ok(permit) => permit,
+                Err(response) => return response,
+            };
+            let response = handlers::call_provider_openai(&state, &cred, &request, None).await;
+            finish_direct_route(&state, ctx, &cred, response, permit)
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
@@ -1744,16 +2399,19 @@ async fn amp_chat_completions(
     headers: HeaderMap,
     Json(mut request): Json<ChatCompletionRequest>,
 ) -> Response {
-    if let Err(e) = handlers::verify_api_key(&headers, &state.api_key).await {
-        state.logs.write().await.add(
-            "warn",
-            &format!(
-                "Unauthorized request to /api/provider/{}/v1/chat/completions",
-                provider
-            ),
-        );
-        return e.into_response();
-    }
+    let client_key = match handlers::authorize_client(&state, &headers, Some(&request.model)) {
+        Ok(client_key) => client_key,
+        Err(e) => {
+            state.logs.write().await.add(
+                "warn",
+                &format!(
+                    "Unauthorized request to /api/provider/{}/v1/chat/completions",
+                    provider
+                ),
+            );
+            return e;
+        }
+    };
 
     // 应用模型映射
     let original_model = request.model.clone();
@@ -1829,6 +2487,11 @@ async fn amp_chat_completions(
                             "[AMP] 通过 provider_id '{}' 找到 API Key Provider 凭证: name={:?}",
                             provider, cred.name
                         );
+                        state.processor.metrics.record_failover(
+                            &provider,
+                            "api_key_provider",
+                            "no_pool_credential",
+                        );
                         Some(cred)
                     }
                     Ok(None) => {
@@ -1862,7 +2525,18 @@ async fn amp_chat_completions(
                 ),
             );
             // 注意：这里没有 Flow 捕获，因为是通过 AMP CLI 路由的请求
-            handlers::call_provider_openai(&state, &cred, &request, None).await
+            let ctx =
+                direct_route_context(&original_model, &request.model, request.stream, client_key);
+            let permit = match handlers::acquire_concurrency_permit(
+                &state, &headers, &ctx, &cred, None, false,
+            )
+            .await
+            {
+                Ok(permit) => permit,
+                Err(response) => return response,
+            };
+            let response = handlers::call_provider_openai(&state, &cred, &request, None).await;
+            finish_direct_route(&state, ctx, &cred, response, permit)
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
@@ -1899,16 +2573,20 @@ async fn amp_messages(
     Json(mut request): Json<AnthropicMessagesRequest>,
 ) -> Response {
     // 使用 Anthropic 格式的认证验证
-    if let Err(e) = handlers::verify_api_key_anthropic(&headers, &state.api_key).await {
-        state.logs.write().await.add(
-            "warn",
-            &format!(
-                "Unauthorized request to /api/provider/{}/v1/messages",
-                provider
-            ),
-        );
-        return e.into_response();
-    }
+    let client_key =
+        match handlers::authorize_client_anthropic(&state, &headers, Some(&request.model)) {
+            Ok(client_key) => client_key,
+            Err(e) => {
+                state.logs.write().await.add(
+                    "warn",
+                    &format!(
+                        "Unauthorized request to /api/provider/{}/v1/messages",
+                        provider
+                    ),
+                );
+                return e;
+            }
+        };
 
     // 应用模型映射
     let original_model = request.model.clone();
@@ -1971,6 +2649,11 @@ async fn amp_messages(
                             "[AMP_MESSAGES] 通过 provider_id '{}' 找到 API Key Provider 凭证: name={:?}",
                             provider, cred.name
                         );
+                        state.processor.metrics.record_failover(
+                            &provider,
+                            "api_key_provider",
+                            "no_pool_credential",
+                        );
                         Some(cred)
                     }
                     Ok(None) => {
@@ -2001,7 +2684,20 @@ async fn amp_messages(
                 ),
             );
             // 注意：这里没有 Flow 捕获，因为是通过 AMP CLI 路由的请求
-            handlers::call_provider_anthropic(&state, &cred, &request, None).await
+            let ctx =
+                direct_route_context(&original_model, &request.model, request.stream, client_key);
+            let permit = match handlers::acquire_concurrency_permit(
+                &state, &headers, &ctx, &cred, None, true,
+            )
+            .await
+            {
+                Ok(permit) => permit,
+                Err(response) => return response,
+            };
+            let resume = handlers::ResumeScope::new(&state, &headers, &ctx);
+            let response =
+                handlers::call_provider_anthropic(&state, &cred, &request, None, &resume).await;
+            finish_direct_route(&state, ctx, &cred, response, permit)
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
@@ -2391,3 +3087,306 @@ async fn chat_completions_internal(state: &AppState, request: &ChatCompletionReq
             .into_response(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{add_credential, body_text, spawn_upstream, test_state};
+    use crate::services::client_key_service::CreateClientKeyRequest;
+    use crate::ProviderType;
+
+    /// 创建客户端 key，返回 (key id, 明文 key 请求头)
+    fn client_key_headers(state: &AppState, rpm_limit: Option<u32>) -> (String, HeaderMap) {
+        let (key, plaintext) = state
+            .client_keys
+            .create_key(
+                state.db.as_ref().unwrap(),
+                CreateClientKeyRequest {
+                    label: "ci".to_string(),
+                    rpm_limit,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            format!("Bearer {}", plaintext).parse().unwrap(),
+        );
+        (key.id, headers)
+    }
+
+    fn month_tokens(state: &AppState, id: &str) -> u64 {
+        state
+            .client_keys
+            .list_keys()
+            .into_iter()
+            .find(|k| k.id == id)
+            .map(|k| k.month_tokens)
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn test_count_tokens_does_not_consume_rpm() {
+        let state = test_state("kiro");
+        let (_, headers) = client_key_headers(&state, Some(1));
+        let request = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "hello"}]
+        });
+
+        for _ in 0..3 {
+            let response =
+                count_tokens(State(state.clone()), headers.clone(), Json(request.clone())).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        // RPM 窗口仍留给真正的请求
+        assert!(handlers::authorize_client(&state, &headers, None).is_ok());
+        assert!(handlers::authorize_client(&state, &headers, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_native_count_tokens_reports_credential_outcome() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let upstream_calls = calls.clone();
+        let upstream = axum::Router::new().route(
+            "/v1/messages/count_tokens",
+            post(move || {
+                let calls = upstream_calls.clone();
+                async move {
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Json(serde_json::json!({"error": {"message": "boom"}})),
+                        )
+                    } else {
+                        (
+                            StatusCode::OK,
+                            Json(serde_json::json!({"input_tokens": 123})),
+                        )
+                    }
+                }
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let mut state = test_state("claude");
+        state.token_counting.use_native_endpoint = true;
+        let credential = add_credential(
+            &state,
+            ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+        let request = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "hello"}]
+        });
+        let headers = crate::server::test_support::auth_headers();
+        let error_count = || {
+            let conn = state.db.as_ref().unwrap().lock().unwrap();
+            ProviderPoolDao::get_by_uuid(&conn, &credential.uuid)
+                .unwrap()
+                .unwrap()
+                .error_count
+        };
+        let consecutive_failures = || {
+            state
+                .pool_service
+                .circuit_breaker()
+                .snapshot()
+                .into_iter()
+                .find(|s| s.key == credential.uuid)
+                .map(|s| s.consecutive_failures)
+        };
+
+        // 上游失败：回退到本地计数，失败计入凭证和熔断器
+        let response =
+            count_tokens(State(state.clone()), headers.clone(), Json(request.clone())).await;
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_ne!(body["input_tokens"], 123);
+        assert_eq!(error_count(), 1);
+        assert_eq!(consecutive_failures(), Some(1));
+
+        // 上游成功：使用原生计数，成功计入凭证和熔断器
+        let response = count_tokens(State(state.clone()), headers, Json(request)).await;
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["input_tokens"], 123);
+        assert_eq!(error_count(), 0);
+        assert_eq!(consecutive_failures(), Some(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_native_count_tokens_returns_probe_slot_on_client_errors() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let upstream_calls = calls.clone();
+        let upstream = axum::Router::new().route(
+            "/v1/messages/count_tokens",
+            post(move || {
+                let calls = upstream_calls.clone();
+                async move {
+                    let status = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::BAD_REQUEST
+                    } else {
+                        StatusCode::TOO_MANY_REQUESTS
+                    };
+                    (
+                        status,
+                        Json(serde_json::json!({"error": {"message": "rejected"}})),
+                    )
+                }
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let mut state = test_state("claude");
+        state.token_counting.use_native_endpoint = true;
+        let credential = add_credential(
+            &state,
+            ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+        let breaker = state.pool_service.circuit_breaker();
+        breaker.set_config(crate::config::CircuitBreakerConfig {
+            failure_threshold: 1,
+            open_duration_secs: 2,
+            half_open_max_probes: 1,
+            ..Default::default()
+        });
+        // 凭证处于半开状态，只有一个探测名额
+        breaker.on_selected(&credential.uuid, None);
+        breaker.record_failure(&credential.uuid);
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+        let request = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "hello"}]
+        });
+        let headers = crate::server::test_support::auth_headers();
+
+        // 400：回退到本地计数，探测名额归还
+        let response =
+            count_tokens(State(state.clone()), headers.clone(), Json(request.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(breaker.is_call_permitted(&credential.uuid, None));
+
+        // 429：凭证进入限流冷却，探测名额同样归还
+        let response = count_tokens(State(state.clone()), headers, Json(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.pool_service.is_cooling_down(&credential.uuid));
+        assert!(breaker.is_call_permitted(&credential.uuid, None));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_selector_route_charges_client_key() {
+        let upstream = axum::Router::new().route(
+            "/v1/chat/completions",
+            post(|| async {
+                Json(serde_json::json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "created": 0,
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "hi"},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 30, "completion_tokens": 12, "total_tokens": 42}
+                }))
+            }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("openai");
+        add_credential(
+            &state,
+            ProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+        let (key_id, headers) = client_key_headers(&state, None);
+
+        let request = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = chat_completions_with_selector(
+            State(state.clone()),
+            Path("openai".to_string()),
+            headers,
+            Json(request),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        body_text(response).await;
+
+        // 实际用量计入发起请求的客户端 key
+        assert_eq!(month_tokens(&state, &key_id), 42);
+    }
+
+    #[tokio::test]
+    async fn test_selector_and_responses_routes_respect_concurrency_limit() {
+        let state = test_state("openai");
+        let credential = add_credential(
+            &state,
+            ProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: Some("http://127.0.0.1:9".to_string()),
+            },
+        );
+        let limiter = state.pool_service.concurrency();
+        limiter.set_config(crate::config::ConcurrencyConfig {
+            enabled: true,
+            max_per_credential: 1,
+            max_queue_size: 0,
+            ..Default::default()
+        });
+        let _held = limiter
+            .acquire(
+                "openai",
+                &credential.uuid,
+                crate::resilience::RequestPriority::Interactive,
+            )
+            .await
+            .unwrap();
+        let (_, headers) = client_key_headers(&state, None);
+
+        let request = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = chat_completions_with_selector(
+            State(state.clone()),
+            Path("openai".to_string()),
+            headers.clone(),
+            Json(request),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response
+            .headers()
+            .contains_key(axum::http::header::RETRY_AFTER));
+
+        let response = handlers::openai_responses(
+            State(state.clone()),
+            headers,
+            Json(serde_json::json!({"model": "gpt-4o", "input": "hi"})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
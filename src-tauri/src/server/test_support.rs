@@ -0,0 +1,128 @@
+//! 服务器处理器测试辅助
+//!
+//! 构建使用内存数据库的 AppState，并在本地端口启动 mock 上游，
+//! 用于经过完整处理器路径的端到端测试
+
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use rusqlite::Connection;
+use tokio::sync::RwLock;
+
+use super::AppState;
+use crate::database::{schema, DbConnection};
+use crate::flow_monitor::{FlowInterceptor, FlowMonitor, FlowMonitorConfig};
+use crate::injection::Injector;
+use crate::logger::LogStore;
+use crate::models::provider_pool_model::{CredentialData, ProviderCredential};
+use crate::processor::RequestProcessor;
+use crate::providers::kiro::KiroProvider;
+use crate::services::provider_pool_service::ProviderPoolService;
+use crate::services::token_cache_service::TokenCacheService;
+use crate::websocket::{WsConfig, WsConnectionManager};
+use crate::ProviderType;
+
+/// 测试服务器的 API Key
+pub const TEST_API_KEY: &str = "test-api-key";
+
+/// 创建使用内存数据库的 AppState，默认 Provider 为 `default_provider`
+///
+/// 凭证写入数据库前必须加密，这里同时安装测试加密器
+pub fn test_state(default_provider: &str) -> AppState {
+    crate::database::secrets::install_test_cipher();
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create_tables(&conn).unwrap();
+    let db: DbConnection = Arc::new(Mutex::new(conn));
+
+    let pool_service = Arc::new(ProviderPoolService::new());
+    let ws_manager = Arc::new(WsConnectionManager::new(WsConfig::default()));
+    let ws_stats = ws_manager.stats().clone();
+    let logging = crate::config::LoggingConfig {
+        enabled: false,
+        ..Default::default()
+    };
+
+    AppState {
+        api_key: TEST_API_KEY.to_string(),
+        base_url: "http://127.0.0.1:0".to_string(),
+        default_provider: Arc::new(RwLock::new(default_provider.to_string())),
+        kiro: Arc::new(RwLock::new(KiroProvider::new())),
+        logs: Arc::new(RwLock::new(LogStore::with_config(&logging))),
+        kiro_refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+        gemini_refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+        qwen_refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+        pool_service: pool_service.clone(),
+        token_cache: Arc::new(TokenCacheService::new()),
+        db: Some(db),
+        injector: Arc::new(RwLock::new(Injector::new())),
+        injection_enabled: Arc::new(RwLock::new(false)),
+        processor: Arc::new(RequestProcessor::with_defaults(pool_service)),
+        ws_manager,
+        ws_stats,
+        hot_reload_manager: None,
+        request_logger: None,
+        amp_router: Arc::new(crate::router::AmpRouter::new(Default::default())),
+        flow_monitor: Arc::new(FlowMonitor::new(FlowMonitorConfig::default(), None)),
+        flow_interceptor: Arc::new(FlowInterceptor::default()),
+        endpoint_providers: Arc::new(RwLock::new(Default::default())),
+        kiro_event_service: Arc::new(crate::services::kiro_event_service::KiroEventService::new()),
+        api_key_service: Arc::new(
+            crate::services::api_key_provider_service::ApiKeyProviderService::new(),
+        ),
+        token_counter: Arc::new(crate::telemetry::AnthropicTokenCounter::new().unwrap()),
+        token_counting: Default::default(),
+        retry_settings: Default::default(),
+        client_keys: Arc::new(crate::services::client_key_service::ClientKeyService::new()),
+        response_cache: Arc::new(
+            crate::services::response_cache_service::ResponseCacheService::new(Default::default()),
+        ),
+    }
+}
+
+/// 向测试状态的凭证池添加凭证
+pub fn add_credential(
+    state: &AppState,
+    provider: ProviderType,
+    credential: CredentialData,
+) -> ProviderCredential {
+    state
+        .pool_service
+        .add_credential(
+            state.db.as_ref().unwrap(),
+            &provider.to_string(),
+            credential,
+            None,
+            Some(false),
+            None,
+        )
+        .unwrap()
+}
+
+/// 在本地随机端口启动 mock 上游，返回其基础 URL
+pub async fn spawn_upstream(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// 带测试 API Key 的请求头
+pub fn auth_headers() -> axum::http::HeaderMap {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(
+        axum::http::header::AUTHORIZATION,
+        format!("Bearer {}", TEST_API_KEY).parse().unwrap(),
+    );
+    headers.insert("x-api-key", TEST_API_KEY.parse().unwrap());
+    headers
+}
+
+/// 读取完整的响应体
+pub async fn body_text(response: axum::response::Response) -> String {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
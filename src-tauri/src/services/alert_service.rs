@@ -0,0 +1,885 @@
+//! 流量告警
+//!
+//! 按 [`AlertConfig`] 中的规则持续评估代理流量：
+//!
+//! - 每个完成或失败的 Flow 按规则的过滤表达式计入各规则的滑动窗口
+//! - 定时计算窗口内的错误率、延迟分位数等指标并与阈值比较
+//! - 规则从正常变为触发时发送一次告警，指标恢复后发送一次解除通知，期间不重复发送
+//! - 告警发送到 Webhook（通用 JSON 或 Slack 兼容格式），并广播为 [`AlertEvent`]
+
+use crate::config::{
+    AlertConfig, AlertMetric, AlertOperator, AlertRule, AlertWebhook, AlertWebhookFormat,
+};
+use crate::flow_monitor::{FilterParser, FlowEvent, FlowMonitor, FlowState, LLMFlow};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// 每条规则窗口内保留的最大样本数
+const MAX_SAMPLES_PER_RULE: usize = 10_000;
+
+/// 保留的最近告警事件数
+const MAX_RECENT_EVENTS: usize = 50;
+
+/// Webhook 发送失败后的重试间隔（秒）
+const WEBHOOK_RETRY_DELAYS_SECS: [u64; 2] = [2, 10];
+
+type FlowFilterFn = Arc<dyn Fn(&LLMFlow) -> bool + Send + Sync>;
+
+/// 告警状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    /// 触发
+    Firing,
+    /// 已恢复
+    Resolved,
+}
+
+/// 告警事件（规则状态变化）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub status: AlertStatus,
+    pub metric: AlertMetric,
+    pub operator: AlertOperator,
+    pub threshold: f64,
+    /// 当前指标值（窗口内样本不足时为空）
+    pub value: Option<f64>,
+    /// 窗口内的请求数
+    pub samples: usize,
+    pub window_secs: u64,
+    pub filter_expr: Option<String>,
+    /// 告警开始时间
+    pub started_at: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AlertEvent {
+    /// 一行摘要，用于日志和 Webhook 文本
+    pub fn summary(&self) -> String {
+        let label = match self.status {
+            AlertStatus::Firing => "告警",
+            AlertStatus::Resolved => "恢复",
+        };
+        format!(
+            "[{}] {}: {} = {}（阈值 {} {}，最近 {} 秒 {} 个请求）",
+            label,
+            self.rule,
+            metric_key(self.metric),
+            format_value(self.value),
+            operator_symbol(self.operator),
+            self.threshold,
+            self.window_secs,
+            self.samples
+        )
+    }
+}
+
+/// 规则当前状态
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertRuleStatus {
+    pub name: String,
+    pub firing: bool,
+    /// 告警开始时间
+    pub since: Option<DateTime<Utc>>,
+    /// 最近一次评估的指标值
+    pub value: Option<f64>,
+    /// 窗口内的请求数
+    pub samples: usize,
+}
+
+/// Webhook 发送结果
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub webhook: String,
+    pub success: bool,
+    pub message: Option<String>,
+}
+
+/// 窗口样本
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: DateTime<Utc>,
+    error: bool,
+    latency_ms: u64,
+    tokens: u64,
+}
+
+impl Sample {
+    fn from_flow(flow: &LLMFlow, at: DateTime<Utc>) -> Self {
+        let response = flow.response.as_ref();
+        let tokens = response.map_or(0, |r| {
+            let usage = &r.usage;
+            if usage.total_tokens > 0 {
+                u64::from(usage.total_tokens)
+            } else {
+                u64::from(usage.input_tokens) + u64::from(usage.output_tokens)
+            }
+        });
+        Self {
+            at,
+            error: flow.state == FlowState::Failed
+                || response.is_some_and(|r| r.status_code >= 400),
+            latency_ms: flow.timestamps.duration_ms,
+            tokens,
+        }
+    }
+}
+
+/// 单条规则的评估状态
+struct RuleState {
+    rule: AlertRule,
+    filter: Option<FlowFilterFn>,
+    samples: VecDeque<Sample>,
+    firing_since: Option<DateTime<Utc>>,
+    last_value: Option<f64>,
+}
+
+impl RuleState {
+    fn new(rule: AlertRule, filter: Option<FlowFilterFn>) -> Self {
+        Self {
+            rule,
+            filter,
+            samples: VecDeque::new(),
+            firing_since: None,
+            last_value: None,
+        }
+    }
+
+    fn event(
+        &self,
+        status: AlertStatus,
+        started_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> AlertEvent {
+        AlertEvent {
+            rule: self.rule.name.clone(),
+            status,
+            metric: self.rule.metric,
+            operator: self.rule.operator,
+            threshold: self.rule.threshold,
+            value: self.last_value,
+            samples: self.samples.len(),
+            window_secs: self.rule.window_secs,
+            filter_expr: self.rule.filter_expr.clone(),
+            started_at,
+            timestamp: now,
+        }
+    }
+}
+
+/// 流量告警服务
+pub struct AlertService {
+    config: RwLock<AlertConfig>,
+    rules: Mutex<Vec<RuleState>>,
+    recent: Mutex<VecDeque<AlertEvent>>,
+    client: reqwest::Client,
+    events: broadcast::Sender<AlertEvent>,
+}
+
+impl Default for AlertService {
+    fn default() -> Self {
+        Self::new(AlertConfig::default())
+    }
+}
+
+impl AlertService {
+    pub fn new(config: AlertConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        let service = Self {
+            config: RwLock::new(AlertConfig::default()),
+            rules: Mutex::new(Vec::new()),
+            recent: Mutex::new(VecDeque::new()),
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            events,
+        };
+        service.set_config(config);
+        service
+    }
+
+    /// 更新配置（热重载）
+    ///
+    /// 名称和过滤表达式不变的规则保留窗口样本和告警状态；关闭告警时清空所有状态
+    pub fn set_config(&self, config: AlertConfig) {
+        let mut rules = self.rules.lock();
+        let mut previous: HashMap<String, RuleState> = if config.enabled {
+            rules.drain(..).map(|s| (s.rule.name.clone(), s)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        *rules = config
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                let filter = match compile_filter(rule.filter_expr.as_deref()) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        tracing::warn!(
+                            "[ALERT] 规则 {} 的过滤表达式无效，已忽略: {}",
+                            rule.name,
+                            e
+                        );
+                        return None;
+                    }
+                };
+                Some(match previous.remove(&rule.name) {
+                    Some(mut state) if state.rule.filter_expr == rule.filter_expr => {
+                        state.rule = rule.clone();
+                        state.filter = filter;
+                        state
+                    }
+                    _ => RuleState::new(rule.clone(), filter),
+                })
+            })
+            .collect();
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> AlertConfig {
+        self.config.read().clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().enabled
+    }
+
+    /// 订阅告警事件
+    pub fn subscribe(&self) -> broadcast::Receiver<AlertEvent> {
+        self.events.subscribe()
+    }
+
+    /// 各规则当前状态
+    pub fn status(&self) -> Vec<AlertRuleStatus> {
+        self.rules
+            .lock()
+            .iter()
+            .map(|state| AlertRuleStatus {
+                name: state.rule.name.clone(),
+                firing: state.firing_since.is_some(),
+                since: state.firing_since,
+                value: state.last_value,
+                samples: state.samples.len(),
+            })
+            .collect()
+    }
+
+    /// 最近的告警事件（新的在前）
+    pub fn recent_events(&self) -> Vec<AlertEvent> {
+        self.recent.lock().iter().rev().cloned().collect()
+    }
+
+    /// 启动后台任务：统计完成的 Flow 并定时评估规则，返回的句柄被丢弃时停止
+    pub fn spawn(self: &Arc<Self>, flow_monitor: Arc<FlowMonitor>) -> AlertHandle {
+        let service = self.clone();
+        let mut flow_events = flow_monitor.subscribe();
+        let feeder = tokio::spawn(async move {
+            loop {
+                let id = match flow_events.recv().await {
+                    Ok(FlowEvent::FlowCompleted { id, .. })
+                    | Ok(FlowEvent::FlowFailed { id, .. }) => id,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("[ALERT] Flow 事件积压，{} 条事件未计入告警统计", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if !service.is_enabled() {
+                    continue;
+                }
+
+                let flow = flow_monitor.memory_store().read().await.get(&id);
+                let Some(flow) = flow else { continue };
+                let Ok(flow) = flow.read() else { continue };
+                service.record(&flow, Utc::now());
+            }
+        });
+
+        let service = self.clone();
+        let evaluator = tokio::spawn(async move {
+            loop {
+                let interval = service.config.read().evaluate_interval_secs.max(1);
+                tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+                if !service.is_enabled() {
+                    continue;
+                }
+                for event in service.evaluate(Utc::now()) {
+                    service.dispatch(event);
+                }
+            }
+        });
+
+        AlertHandle(vec![feeder, evaluator])
+    }
+
+    /// 将 Flow 计入匹配规则的窗口
+    fn record(&self, flow: &LLMFlow, at: DateTime<Utc>) {
+        let sample = Sample::from_flow(flow, at);
+        for state in self.rules.lock().iter_mut() {
+            if state.filter.as_ref().is_none_or(|filter| filter(flow)) {
+                if state.samples.len() >= MAX_SAMPLES_PER_RULE {
+                    state.samples.pop_front();
+                }
+                state.samples.push_back(sample);
+            }
+        }
+    }
+
+    /// 评估所有规则，返回状态发生变化的告警
+    fn evaluate(&self, now: DateTime<Utc>) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for state in self.rules.lock().iter_mut() {
+            let cutoff = now - Duration::seconds(state.rule.window_secs as i64);
+            while state.samples.front().is_some_and(|s| s.at < cutoff) {
+                state.samples.pop_front();
+            }
+
+            state.last_value = compute_metric(&state.rule, &state.samples);
+            // 样本不足时没有数据可判断，保持当前告警状态，不解除正在触发的告警
+            let Some(value) = state.last_value else {
+                continue;
+            };
+            let triggered = compare(state.rule.operator, value, state.rule.threshold);
+            match (state.firing_since, triggered) {
+                (None, true) => {
+                    state.firing_since = Some(now);
+                    events.push(state.event(AlertStatus::Firing, now, now));
+                }
+                (Some(since), false) => {
+                    state.firing_since = None;
+                    events.push(state.event(AlertStatus::Resolved, since, now));
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    /// 记录、广播告警事件并发送到规则对应的 Webhook
+    fn dispatch(&self, event: AlertEvent) {
+        match event.status {
+            AlertStatus::Firing => tracing::warn!("[ALERT] {}", event.summary()),
+            AlertStatus::Resolved => tracing::info!("[ALERT] {}", event.summary()),
+        }
+        {
+            let mut recent = self.recent.lock();
+            if recent.len() >= MAX_RECENT_EVENTS {
+                recent.pop_front();
+            }
+            recent.push_back(event.clone());
+        }
+        let _ = self.events.send(event.clone());
+
+        for webhook in self.webhooks_for(&event.rule) {
+            let client = self.client.clone();
+            let event = event.clone();
+            tokio::spawn(async move {
+                if let Err(e) = deliver(&client, &webhook, &event).await {
+                    tracing::warn!("[ALERT] 告警发送到 Webhook {} 失败: {}", webhook.name, e);
+                }
+            });
+        }
+    }
+
+    /// 规则对应的 Webhook（规则未指定时为全部 Webhook）
+    fn webhooks_for(&self, rule_name: &str) -> Vec<AlertWebhook> {
+        let config = self.config.read();
+        let targets = config
+            .rules
+            .iter()
+            .find(|rule| rule.name == rule_name)
+            .map(|rule| rule.webhooks.clone())
+            .unwrap_or_default();
+        config
+            .webhooks
+            .iter()
+            .filter(|webhook| targets.is_empty() || targets.contains(&webhook.name))
+            .cloned()
+            .collect()
+    }
+
+    /// 向所有 Webhook 发送一条测试告警（不重试）
+    pub async fn test_webhooks(&self) -> Vec<WebhookDelivery> {
+        let webhooks = self.config.read().webhooks.clone();
+        let now = Utc::now();
+        let event = AlertEvent {
+            rule: "ProxyCast 测试告警".to_string(),
+            status: AlertStatus::Firing,
+            metric: AlertMetric::ErrorRate,
+            operator: AlertOperator::Gt,
+            threshold: 0.0,
+            value: Some(0.0),
+            samples: 0,
+            window_secs: 0,
+            filter_expr: None,
+            started_at: now,
+            timestamp: now,
+        };
+
+        let mut results = Vec::with_capacity(webhooks.len());
+        for webhook in webhooks {
+            let payload = build_payload(webhook.format, &event);
+            let result = send_once(&self.client, &webhook, &payload).await;
+            results.push(WebhookDelivery {
+                webhook: webhook.name,
+                success: result.is_ok(),
+                message: result.err(),
+            });
+        }
+        results
+    }
+}
+
+/// 后台告警任务句柄，丢弃时停止任务
+pub struct AlertHandle(Vec<JoinHandle<()>>);
+
+impl AlertHandle {
+    /// 附加需要随告警服务一起停止的任务（如事件转发）
+    pub fn attach(mut self, task: JoinHandle<()>) -> Self {
+        self.0.push(task);
+        self
+    }
+}
+
+impl Drop for AlertHandle {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+fn compile_filter(expr: Option<&str>) -> Result<Option<FlowFilterFn>, String> {
+    let Some(expr) = expr.filter(|e| !e.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let parsed = FilterParser::parse(expr).map_err(|e| e.to_string())?;
+    let filter = FilterParser::compile(&parsed);
+    Ok(Some(Arc::new(move |flow: &LLMFlow| filter(flow))))
+}
+
+/// 计算窗口指标；错误率和延迟类指标在样本不足时返回 `None`
+fn compute_metric(rule: &AlertRule, samples: &VecDeque<Sample>) -> Option<f64> {
+    let count = samples.len();
+    let errors = samples.iter().filter(|s| s.error).count();
+    let value = match rule.metric {
+        AlertMetric::RequestCount => count as f64,
+        AlertMetric::ErrorCount => errors as f64,
+        AlertMetric::TotalTokens => samples.iter().map(|s| s.tokens).sum::<u64>() as f64,
+        _ if count == 0 || count < rule.min_samples => return None,
+        AlertMetric::ErrorRate => errors as f64 * 100.0 / count as f64,
+        AlertMetric::AvgLatencyMs => {
+            samples.iter().map(|s| s.latency_ms).sum::<u64>() as f64 / count as f64
+        }
+        AlertMetric::P50LatencyMs => latency_percentile(samples, 0.50),
+        AlertMetric::P95LatencyMs => latency_percentile(samples, 0.95),
+        AlertMetric::P99LatencyMs => latency_percentile(samples, 0.99),
+    };
+    Some(value)
+}
+
+/// 延迟的最近秩百分位数，`samples` 不能为空
+fn latency_percentile(samples: &VecDeque<Sample>, p: f64) -> f64 {
+    let mut latencies: Vec<u64> = samples.iter().map(|s| s.latency_ms).collect();
+    latencies.sort_unstable();
+    let rank = ((p * latencies.len() as f64).ceil() as usize).clamp(1, latencies.len());
+    latencies[rank - 1] as f64
+}
+
+fn compare(operator: AlertOperator, value: f64, threshold: f64) -> bool {
+    match operator {
+        AlertOperator::Gt => value > threshold,
+        AlertOperator::Gte => value >= threshold,
+        AlertOperator::Lt => value < threshold,
+        AlertOperator::Lte => value <= threshold,
+    }
+}
+
+fn metric_key(metric: AlertMetric) -> &'static str {
+    match metric {
+        AlertMetric::ErrorRate => "error_rate",
+        AlertMetric::ErrorCount => "error_count",
+        AlertMetric::RequestCount => "request_count",
+        AlertMetric::AvgLatencyMs => "avg_latency_ms",
+        AlertMetric::P50LatencyMs => "p50_latency_ms",
+        AlertMetric::P95LatencyMs => "p95_latency_ms",
+        AlertMetric::P99LatencyMs => "p99_latency_ms",
+        AlertMetric::TotalTokens => "total_tokens",
+    }
+}
+
+fn operator_symbol(operator: AlertOperator) -> &'static str {
+    match operator {
+        AlertOperator::Gt => ">",
+        AlertOperator::Gte => ">=",
+        AlertOperator::Lt => "<",
+        AlertOperator::Lte => "<=",
+    }
+}
+
+fn format_value(value: Option<f64>) -> String {
+    value.map_or_else(|| "样本不足".to_string(), |v| format!("{:.2}", v))
+}
+
+/// 按 Webhook 格式构建消息体
+fn build_payload(format: AlertWebhookFormat, event: &AlertEvent) -> Value {
+    match format {
+        AlertWebhookFormat::Json => {
+            let mut payload = serde_json::to_value(event).unwrap_or_else(|_| json!({}));
+            payload["text"] = Value::String(event.summary());
+            payload
+        }
+        AlertWebhookFormat::Slack => {
+            let color = match event.status {
+                AlertStatus::Firing => "danger",
+                AlertStatus::Resolved => "good",
+            };
+            json!({
+                "text": event.summary(),
+                "attachments": [{
+                    "color": color,
+                    "fields": [
+                        {"title": "规则", "value": event.rule, "short": true},
+                        {"title": "指标", "value": metric_key(event.metric), "short": true},
+                        {"title": "当前值", "value": format_value(event.value), "short": true},
+                        {
+                            "title": "阈值",
+                            "value": format!("{} {}", operator_symbol(event.operator), event.threshold),
+                            "short": true
+                        },
+                        {
+                            "title": "过滤表达式",
+                            "value": event.filter_expr.as_deref().unwrap_or("（全部请求）"),
+                            "short": false
+                        }
+                    ],
+                    "ts": event.timestamp.timestamp(),
+                }],
+            })
+        }
+    }
+}
+
+/// 发送告警，失败后按间隔重试
+async fn deliver(
+    client: &reqwest::Client,
+    webhook: &AlertWebhook,
+    event: &AlertEvent,
+) -> Result<(), String> {
+    let payload = build_payload(webhook.format, event);
+    let mut result = send_once(client, webhook, &payload).await;
+    for delay in WEBHOOK_RETRY_DELAYS_SECS {
+        if result.is_ok() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+        result = send_once(client, webhook, &payload).await;
+    }
+    result
+}
+
+async fn send_once(
+    client: &reqwest::Client,
+    webhook: &AlertWebhook,
+    payload: &Value,
+) -> Result<(), String> {
+    let mut request = client
+        .post(&webhook.url)
+        .header(
+            "User-Agent",
+            format!("ProxyCast/{}", env!("CARGO_PKG_VERSION")),
+        )
+        .json(payload);
+    for (name, value) in &webhook.headers {
+        request = request.header(name, value);
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP 状态码: {}", response.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::{FlowMetadata, FlowType, LLMRequest, LLMResponse};
+    use crate::ProviderType;
+    use axum::{extract::State, routing::post, Json, Router};
+    use tokio::sync::mpsc;
+
+    fn flow(provider: ProviderType, failed: bool, latency_ms: u64) -> LLMFlow {
+        let mut flow = LLMFlow::new(
+            uuid::Uuid::new_v4().to_string(),
+            FlowType::ChatCompletions,
+            LLMRequest::default(),
+            FlowMetadata {
+                provider,
+                ..Default::default()
+            },
+        );
+        flow.timestamps.duration_ms = latency_ms;
+        if failed {
+            flow.state = FlowState::Failed;
+        } else {
+            flow.state = FlowState::Completed;
+            flow.response = Some(LLMResponse {
+                status_code: 200,
+                ..Default::default()
+            });
+        }
+        flow
+    }
+
+    fn rule(name: &str, metric: AlertMetric, threshold: f64) -> AlertRule {
+        AlertRule {
+            name: name.to_string(),
+            filter_expr: None,
+            metric,
+            operator: AlertOperator::Gt,
+            threshold,
+            window_secs: 300,
+            min_samples: 5,
+            webhooks: Vec::new(),
+        }
+    }
+
+    fn service(rules: Vec<AlertRule>, webhooks: Vec<AlertWebhook>) -> AlertService {
+        AlertService::new(AlertConfig {
+            enabled: true,
+            rules,
+            webhooks,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_error_rate_fires_once_and_resolves() {
+        let mut kiro_errors = rule("kiro-errors", AlertMetric::ErrorRate, 20.0);
+        kiro_errors.filter_expr = Some("~p kiro".to_string());
+        let service = service(vec![kiro_errors], Vec::new());
+        let t0 = Utc::now();
+
+        for i in 0..10 {
+            service.record(&flow(ProviderType::Kiro, i < 3, 100), t0);
+            // 其他 Provider 的失败不计入
+            service.record(&flow(ProviderType::Gemini, true, 100), t0);
+        }
+        let events = service.evaluate(t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, AlertStatus::Firing);
+        assert_eq!(events[0].samples, 10);
+        assert_eq!(events[0].value, Some(30.0));
+
+        // 持续触发期间不重复告警
+        assert!(service.evaluate(t0 + Duration::seconds(15)).is_empty());
+        assert!(service.status()[0].firing);
+
+        for _ in 0..20 {
+            service.record(
+                &flow(ProviderType::Kiro, false, 100),
+                t0 + Duration::seconds(20),
+            );
+        }
+        let events = service.evaluate(t0 + Duration::seconds(30));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, AlertStatus::Resolved);
+        assert_eq!(events[0].started_at, t0);
+        assert_eq!(events[0].value, Some(10.0));
+    }
+
+    #[test]
+    fn test_latency_percentile_and_window_expiry() {
+        let service = service(
+            vec![rule("slow", AlertMetric::P95LatencyMs, 9_000.0)],
+            Vec::new(),
+        );
+        let t0 = Utc::now();
+        for i in 1..=100 {
+            service.record(&flow(ProviderType::Claude, false, i * 100), t0);
+        }
+
+        let events = service.evaluate(t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].value, Some(9_500.0));
+
+        // 样本移出窗口后没有数据，保持触发状态
+        assert!(service.evaluate(t0 + Duration::seconds(301)).is_empty());
+        let status = &service.status()[0];
+        assert!(status.firing);
+        assert_eq!(status.value, None);
+        assert_eq!(status.samples, 0);
+
+        // 有足够样本后再按指标解除
+        for _ in 0..5 {
+            service.record(
+                &flow(ProviderType::Claude, false, 100),
+                t0 + Duration::seconds(310),
+            );
+        }
+        let events = service.evaluate(t0 + Duration::seconds(310));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, AlertStatus::Resolved);
+        assert_eq!(events[0].value, Some(100.0));
+    }
+
+    #[test]
+    fn test_min_samples_and_count_metrics() {
+        let mut no_traffic = rule("no-traffic", AlertMetric::RequestCount, 1.0);
+        no_traffic.operator = AlertOperator::Lt;
+        let service = service(
+            vec![rule("errors", AlertMetric::ErrorRate, 20.0), no_traffic],
+            Vec::new(),
+        );
+        let t0 = Utc::now();
+
+        // 计数类指标不受最少样本数限制
+        let events = service.evaluate(t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "no-traffic");
+
+        for _ in 0..4 {
+            service.record(&flow(ProviderType::Claude, true, 100), t0);
+        }
+        let events = service.evaluate(t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "no-traffic");
+        assert_eq!(events[0].status, AlertStatus::Resolved);
+        assert_eq!(service.status()[0].value, None);
+
+        service.record(&flow(ProviderType::Claude, true, 100), t0);
+        let events = service.evaluate(t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "errors");
+        assert_eq!(events[0].value, Some(100.0));
+    }
+
+    #[test]
+    fn test_reload_keeps_state_of_unchanged_rules() {
+        let config = AlertConfig {
+            enabled: true,
+            rules: vec![rule("errors", AlertMetric::ErrorCount, 0.0)],
+            ..Default::default()
+        };
+        let service = AlertService::new(config.clone());
+        let t0 = Utc::now();
+        service.record(&flow(ProviderType::Claude, true, 100), t0);
+        assert_eq!(service.evaluate(t0).len(), 1);
+
+        service.set_config(config.clone());
+        assert!(service.evaluate(t0).is_empty());
+        assert!(service.status()[0].firing);
+
+        // 过滤表达式变化后重新统计
+        let mut changed = config;
+        changed.rules[0].filter_expr = Some("~m gpt".to_string());
+        service.set_config(changed);
+        assert!(!service.status()[0].firing);
+        assert_eq!(service.status()[0].samples, 0);
+
+        // 无效的过滤表达式被忽略
+        let mut invalid = rule("invalid", AlertMetric::ErrorCount, 0.0);
+        invalid.filter_expr = Some("~m (".to_string());
+        service.set_config(AlertConfig {
+            enabled: true,
+            rules: vec![invalid],
+            ..Default::default()
+        });
+        assert!(service.status().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delivery_to_local_receiver() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(String, Option<String>, Value)>();
+        let app = Router::new()
+            .route(
+                "/hooks/:name",
+                post(
+                    |State(tx): State<mpsc::UnboundedSender<(String, Option<String>, Value)>>,
+                     axum::extract::Path(name): axum::extract::Path<String>,
+                     headers: axum::http::HeaderMap,
+                     Json(body): Json<Value>| async move {
+                        let token = headers
+                            .get("x-alert-token")
+                            .and_then(|v| v.to_str().ok())
+                            .map(|s| s.to_string());
+                        let _ = tx.send((name, token, body));
+                        Json(json!({"ok": true}))
+                    },
+                ),
+            )
+            .with_state(tx);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let webhook = |name: &str, format| AlertWebhook {
+            name: name.to_string(),
+            url: format!("http://{}/hooks/{}", addr, name),
+            format,
+            headers: HashMap::from([("x-alert-token".to_string(), "secret".to_string())]),
+        };
+        let mut slack_only = rule("slack-only", AlertMetric::ErrorCount, 100.0);
+        slack_only.webhooks = vec!["slack".to_string()];
+        let service = service(
+            vec![rule("errors", AlertMetric::ErrorCount, 0.0), slack_only],
+            vec![
+                webhook("json", AlertWebhookFormat::Json),
+                webhook("slack", AlertWebhookFormat::Slack),
+            ],
+        );
+        let mut events = service.subscribe();
+
+        let t0 = Utc::now();
+        service.record(&flow(ProviderType::Claude, true, 100), t0);
+        for event in service.evaluate(t0) {
+            service.dispatch(event);
+        }
+        assert_eq!(events.recv().await.unwrap().rule, "errors");
+
+        let mut received = HashMap::new();
+        while received.len() < 2 {
+            let (name, token, body) =
+                tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("webhook receiver got no request")
+                    .unwrap();
+            assert_eq!(token.as_deref(), Some("secret"));
+            received.insert(name, body);
+        }
+        let json_body = &received["json"];
+        assert_eq!(json_body["rule"], "errors");
+        assert_eq!(json_body["status"], "firing");
+        assert_eq!(json_body["metric"], "error_count");
+        assert_eq!(json_body["value"], 1.0);
+        assert!(json_body["text"].as_str().unwrap().contains("errors"));
+        let slack_body = &received["slack"];
+        assert!(slack_body["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("[告警] errors"));
+        assert_eq!(slack_body["attachments"][0]["color"], "danger");
+
+        assert_eq!(service.webhooks_for("slack-only").len(), 1);
+        assert_eq!(service.recent_events().len(), 1);
+
+        let results = service.test_webhooks().await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.success));
+    }
+}
@@ -9,11 +9,10 @@ use crate::database::dao::api_key_provider::{
     ApiKeyEntry, ApiKeyProvider, ApiKeyProviderDao, ApiProviderType, ProviderGroup,
     ProviderWithKeys,
 };
+use crate::database::secrets;
 use crate::database::system_providers::{get_system_providers, to_api_key_provider};
 use crate::database::DbConnection;
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::Utc;
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
@@ -22,88 +21,36 @@ use std::sync::RwLock;
 // 加密服务
 // ============================================================================
 
-/// 简单的 API Key 加密服务
-/// 使用 XOR 加密 + Base64 编码
-/// 注意：这是一个简单的混淆方案，不是强加密
-struct EncryptionService {
-    /// 加密密钥（从机器 ID 派生）
-    key: Vec<u8>,
-}
+/// API Key 加密服务
+///
+/// 使用数据库层的 XChaCha20-Poly1305 加密器（见 `database::secrets`），
+/// 以 API Key 条目的 ID 作为 AAD，同时兼容读取旧版本的 XOR + Base64 混淆值。
+struct EncryptionService;
 
 impl EncryptionService {
     /// 创建新的加密服务
     fn new() -> Self {
-        // 使用机器特定信息生成密钥
-        let machine_id = Self::get_machine_id();
-        let mut hasher = Sha256::new();
-        hasher.update(machine_id.as_bytes());
-        hasher.update(b"proxycast-api-key-encryption-salt");
-        let key = hasher.finalize().to_vec();
-
-        Self { key }
-    }
-
-    /// 获取机器 ID
-    fn get_machine_id() -> String {
-        // 尝试获取机器 ID，失败则使用默认值
-        if let Ok(id) = std::fs::read_to_string("/etc/machine-id") {
-            return id.trim().to_string();
-        }
-        if let Ok(id) = std::fs::read_to_string("/var/lib/dbus/machine-id") {
-            return id.trim().to_string();
-        }
-        // macOS: 使用 IOPlatformUUID
-        #[cfg(target_os = "macos")]
-        {
-            if let Ok(output) = std::process::Command::new("ioreg")
-                .args(["-rd1", "-c", "IOPlatformExpertDevice"])
-                .output()
-            {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                for line in stdout.lines() {
-                    if line.contains("IOPlatformUUID") {
-                        if let Some(uuid) = line.split('"').nth(3) {
-                            return uuid.to_string();
-                        }
-                    }
-                }
-            }
-        }
-        // 默认值
-        "proxycast-default-machine-id".to_string()
+        Self
     }
 
     /// 加密 API Key
-    fn encrypt(&self, plaintext: &str) -> String {
-        let encrypted: Vec<u8> = plaintext
-            .as_bytes()
-            .iter()
-            .enumerate()
-            .map(|(i, b)| b ^ self.key[i % self.key.len()])
-            .collect();
-        BASE64.encode(encrypted)
+    fn encrypt(&self, key_id: &str, plaintext: &str) -> Result<String, String> {
+        secrets::encrypt_field(plaintext, key_id)
     }
 
     /// 解密 API Key
-    fn decrypt(&self, ciphertext: &str) -> Result<String, String> {
-        let encrypted = BASE64
-            .decode(ciphertext)
-            .map_err(|e| format!("Base64 解码失败: {}", e))?;
-        let decrypted: Vec<u8> = encrypted
-            .iter()
-            .enumerate()
-            .map(|(i, b)| b ^ self.key[i % self.key.len()])
-            .collect();
-        String::from_utf8(decrypted).map_err(|e| format!("UTF-8 解码失败: {}", e))
+    fn decrypt(&self, key_id: &str, ciphertext: &str) -> Result<String, String> {
+        if secrets::is_encrypted(ciphertext) {
+            secrets::decrypt_field(ciphertext, key_id)
+        } else {
+            // 尚未完成迁移的旧值
+            secrets::decrypt_legacy_xor(ciphertext)
+        }
     }
 
     /// 检查是否为加密后的值（非明文）
     fn is_encrypted(&self, value: &str) -> bool {
-        // 加密后的值是 Base64 编码的，通常不包含常见的 API Key 前缀
-        !value.starts_with("sk-")
-            && !value.starts_with("pk-")
-            && !value.starts_with("api-")
-            && BASE64.decode(value).is_ok()
+        secrets::is_encrypted(value)
     }
 }
 
@@ -378,19 +325,23 @@ impl ApiKeyProviderService {
             existing_keys.len()
         );
 
-        // 检查是否有相同的 API Key（比较加密后的值）
-        let encrypted_input = self.encryption.encrypt(api_key);
+        // 检查是否有相同的 API Key（每次加密的随机数不同，需比较解密后的值）
         for existing_key in &existing_keys {
-            if existing_key.api_key_encrypted == encrypted_input {
+            let existing = self
+                .encryption
+                .decrypt(&existing_key.id, &existing_key.api_key_encrypted);
+            if existing.as_deref() == Ok(api_key) {
                 return Err("该 API Key 已存在".to_string());
             }
         }
+        let key_id = uuid::Uuid::new_v4().to_string();
+        let encrypted_input = self.encryption.encrypt(&key_id, api_key)?;
 
         let should_enable_provider = existing_keys.is_empty() && !provider.enabled;
 
         let now = Utc::now();
         let key = ApiKeyEntry {
-            id: uuid::Uuid::new_v4().to_string(),
+            id: key_id,
             provider_id: provider_id.to_string(),
             api_key_encrypted: encrypted_input,
             alias: alias.clone(),
@@ -509,7 +460,9 @@ impl ApiKeyProviderService {
         let selected_key = &keys[index % keys.len()];
 
         // 解密并返回
-        let decrypted = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+        let decrypted = self
+            .encryption
+            .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
         Ok(Some(decrypted))
     }
 
@@ -542,7 +495,9 @@ impl ApiKeyProviderService {
         let selected_key = &keys[index % keys.len()];
 
         // 解密并返回
-        let decrypted = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+        let decrypted = self
+            .encryption
+            .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
         Ok(Some((selected_key.id.clone(), decrypted)))
     }
 
@@ -600,7 +555,9 @@ impl ApiKeyProviderService {
         let selected_key = &keys[index % keys.len()];
 
         // 解密并返回
-        let decrypted = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+        let decrypted = self
+            .encryption
+            .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
         Ok(Some((decrypted, provider)))
     }
 
@@ -635,7 +592,9 @@ impl ApiKeyProviderService {
         let (selected_key, provider) = &keys[index % keys.len()];
 
         // 解密并返回
-        let decrypted = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+        let decrypted = self
+            .encryption
+            .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
         Ok(Some((selected_key.id.clone(), decrypted, provider.clone())))
     }
 
@@ -652,14 +611,14 @@ impl ApiKeyProviderService {
         self.encryption.is_encrypted(value)
     }
 
-    /// 解密 API Key（用于 API 调用）
-    pub fn decrypt_api_key(&self, encrypted: &str) -> Result<String, String> {
-        self.encryption.decrypt(encrypted)
+    /// 解密 API Key（用于 API 调用），`key_id` 为 API Key 条目的 ID
+    pub fn decrypt_api_key(&self, key_id: &str, encrypted: &str) -> Result<String, String> {
+        self.encryption.decrypt(key_id, encrypted)
     }
 
-    /// 加密 API Key（用于存储）
-    pub fn encrypt_api_key(&self, plaintext: &str) -> String {
-        self.encryption.encrypt(plaintext)
+    /// 加密 API Key（用于存储），`key_id` 为 API Key 条目的 ID
+    pub fn encrypt_api_key(&self, key_id: &str, plaintext: &str) -> Result<String, String> {
+        self.encryption.encrypt(key_id, plaintext)
     }
 
     // ==================== UI 状态 ====================
@@ -947,7 +906,9 @@ impl ApiKeyProviderService {
             let selected_key = &keys[index % keys.len()];
 
             // 解密 API Key
-            let api_key = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+            let api_key = self
+                .encryption
+                .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
 
             // 转换为 ProviderCredential
             let credential = self.convert_to_provider_credential(
@@ -1036,7 +997,9 @@ impl ApiKeyProviderService {
         let selected_key = &keys[index % keys.len()];
 
         // 解密 API Key
-        let api_key = self.encryption.decrypt(&selected_key.api_key_encrypted)?;
+        let api_key = self
+            .encryption
+            .decrypt(&selected_key.id, &selected_key.api_key_encrypted)?;
 
         // 根据 Provider 类型转换为对应的 ProviderCredential
         let credential =
@@ -1111,6 +1074,7 @@ impl ApiKeyProviderService {
             cached_token: None,
             source: CredentialSource::Imported,
             proxy_url: None,
+            weight: crate::models::provider_pool_model::default_weight(),
         })
     }
 
@@ -1169,6 +1133,7 @@ impl ApiKeyProviderService {
             cached_token: None,
             source: CredentialSource::Imported, // 标记为导入来源
             proxy_url: None,
+            weight: crate::models::provider_pool_model::default_weight(),
         })
     }
 }
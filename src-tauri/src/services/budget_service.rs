@@ -0,0 +1,838 @@
+//! 预算服务
+//!
+//! 把 Token 用量按模型定价换算为费用，按 Provider、凭证和客户端 key 统计每日 / 每月花费，
+//! 超出预算时按规则阻止请求、发送预警或降级到更便宜的模型。
+//!
+//! - 定价来自模型注册表（`model_registry` 表），配置中的 `budget.pricing` 优先
+//! - 花费持久化到 `budget_spend` 表，按 UTC 自然日 / 自然月统计，跨周期自动重置
+//! - 每条规则、每个目标在一个统计周期内只预警一次（达到预警比例、超出预算各一次）
+
+use crate::config::{BudgetAction, BudgetConfig, BudgetRule, BudgetScope, BudgetWindow};
+use crate::database::dao::budget::BudgetDao;
+use crate::database::DbConnection;
+use crate::models::model_registry::ModelPricing;
+use crate::processor::RequestContext;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// 模型注册表定价的刷新间隔
+const PRICING_REFRESH: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// 预算统计对象（一次请求对应的 Provider、凭证和客户端 key）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BudgetSubject {
+    pub provider: Option<String>,
+    pub credential_id: Option<String>,
+    pub client_key_id: Option<String>,
+    pub client_key_label: Option<String>,
+}
+
+impl BudgetSubject {
+    /// 从请求上下文构建
+    pub fn from_context(ctx: &RequestContext) -> Self {
+        Self {
+            provider: ctx.provider.map(|p| p.to_string()),
+            credential_id: ctx.credential_id.clone(),
+            client_key_id: ctx.client_key.as_ref().map(|c| c.id.clone()),
+            client_key_label: ctx.client_key.as_ref().map(|c| c.label.clone()),
+        }
+    }
+
+    /// 该范围下的统计目标（客户端 key 以 ID 统计）
+    fn target(&self, scope: BudgetScope) -> Option<&str> {
+        match scope {
+            BudgetScope::Provider => self.provider.as_deref(),
+            BudgetScope::Credential => self.credential_id.as_deref(),
+            BudgetScope::ClientKey => self.client_key_id.as_deref(),
+        }
+    }
+
+    /// 规则是否适用于该对象
+    fn matches(&self, rule: &BudgetRule) -> bool {
+        let Some(target) = self.target(rule.scope) else {
+            return false;
+        };
+        rule.target == "*"
+            || rule.target.eq_ignore_ascii_case(target)
+            || (rule.scope == BudgetScope::ClientKey
+                && self.client_key_label.as_deref() == Some(rule.target.as_str()))
+    }
+}
+
+/// 超出预算
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{} budget exceeded for '{target}': {spent:.4}/{limit:.4} {currency} this {}", scope_key(*.scope), window_label(*.window))]
+pub struct BudgetExceeded {
+    pub scope: BudgetScope,
+    pub target: String,
+    pub window: BudgetWindow,
+    pub spent: f64,
+    pub limit: f64,
+    pub currency: String,
+}
+
+impl BudgetExceeded {
+    /// 距离当前统计周期结束的秒数
+    pub fn retry_after_secs(&self) -> u64 {
+        (period_end(self.window, Utc::now()) - Utc::now())
+            .num_seconds()
+            .max(1) as u64
+    }
+}
+
+/// 预算检查结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetDecision {
+    /// 放行
+    Allow,
+    /// 拒绝请求
+    Block(BudgetExceeded),
+    /// 改用更便宜的模型
+    Downgrade {
+        model: String,
+        exceeded: BudgetExceeded,
+    },
+}
+
+/// 预警级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAlertLevel {
+    /// 达到预警比例
+    Warning,
+    /// 超出预算
+    Exceeded,
+}
+
+/// 预算预警
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetAlert {
+    pub level: BudgetAlertLevel,
+    pub scope: BudgetScope,
+    pub target: String,
+    pub window: BudgetWindow,
+    pub spent: f64,
+    pub limit: f64,
+    pub currency: String,
+    pub action: BudgetAction,
+}
+
+impl BudgetAlert {
+    /// 通知标题
+    pub fn title(&self) -> String {
+        match self.level {
+            BudgetAlertLevel::Warning => "预算预警".to_string(),
+            BudgetAlertLevel::Exceeded => "预算已超出".to_string(),
+        }
+    }
+
+    /// 通知内容
+    pub fn message(&self) -> String {
+        format!(
+            "{} {} {}花费 {:.4}/{:.4} {}",
+            scope_key(self.scope),
+            self.target,
+            match self.window {
+                BudgetWindow::Daily => "今日",
+                BudgetWindow::Monthly => "本月",
+            },
+            self.spent,
+            self.limit,
+            self.currency
+        )
+    }
+}
+
+/// 预算状态（用于管理 API）
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub scope: BudgetScope,
+    pub target: String,
+    pub window: BudgetWindow,
+    pub period: String,
+    pub spent: f64,
+    pub limit: f64,
+    pub currency: String,
+    pub action: BudgetAction,
+    pub exceeded: bool,
+}
+
+/// 预算服务
+#[derive(Default)]
+pub struct BudgetService {
+    config: RwLock<BudgetConfig>,
+    db: OnceLock<DbConnection>,
+    /// 模型注册表中的定价（模型 ID -> 定价）
+    registry_pricing: RwLock<HashMap<String, ModelPricing>>,
+    pricing_loaded_at: RwLock<Option<Instant>>,
+    /// (范围, 目标, 周期) -> 累计花费
+    spend: DashMap<(BudgetScope, String, String), f64>,
+    /// 已发送的预警（规则 + 目标 + 周期 -> 级别）
+    alerted: DashMap<String, BudgetAlertLevel>,
+}
+
+impl BudgetService {
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            ..Self::default()
+        }
+    }
+
+    /// 更新预算配置（热重载）
+    pub fn set_config(&self, config: BudgetConfig) {
+        *self.config.write() = config;
+        self.alerted.clear();
+    }
+
+    /// 获取预算配置
+    pub fn config(&self) -> BudgetConfig {
+        self.config.read().clone()
+    }
+
+    /// 挂载数据库：加载当前统计周期的花费和模型注册表定价
+    ///
+    /// 返回加载的花费记录数
+    pub fn attach_db(&self, db: DbConnection) -> usize {
+        let now = Utc::now();
+        let day = period_key(BudgetWindow::Daily, now);
+        let month = period_key(BudgetWindow::Monthly, now);
+        let rows = match db.lock() {
+            Ok(conn) => BudgetDao::list_by_periods(&conn, &[&day, &month]).unwrap_or_else(|e| {
+                tracing::warn!("[BUDGET] 加载花费记录失败: {}", e);
+                Vec::new()
+            }),
+            Err(e) => {
+                tracing::warn!("[BUDGET] 加载花费记录失败: {}", e);
+                Vec::new()
+            }
+        };
+
+        let loaded = rows.len();
+        for row in rows {
+            if let Some(scope) = parse_scope(&row.scope) {
+                self.spend
+                    .insert((scope, row.target, row.period), row.amount);
+            }
+        }
+        let _ = self.db.set(db);
+        self.refresh_pricing();
+        loaded
+    }
+
+    /// 从模型注册表重新加载定价
+    fn refresh_pricing(&self) {
+        let Some(db) = self.db.get() else {
+            return;
+        };
+        let pricing = match db.lock() {
+            Ok(conn) => BudgetDao::list_model_pricing(&conn),
+            Err(e) => {
+                tracing::warn!("[BUDGET] 加载模型定价失败: {}", e);
+                return;
+            }
+        };
+        match pricing {
+            Ok(pricing) => {
+                *self.registry_pricing.write() = pricing.into_iter().collect();
+            }
+            Err(e) => tracing::warn!("[BUDGET] 加载模型定价失败: {}", e),
+        }
+        *self.pricing_loaded_at.write() = Some(Instant::now());
+    }
+
+    /// 获取模型定价（配置覆盖优先，其次为模型注册表）
+    pub fn pricing_for(&self, model: &str) -> Option<ModelPricing> {
+        if let Some(pricing) = lookup_pricing(&self.config.read().pricing, model) {
+            return Some(pricing);
+        }
+
+        let stale = self
+            .pricing_loaded_at
+            .read()
+            .is_none_or(|t| t.elapsed() >= PRICING_REFRESH);
+        if stale {
+            self.refresh_pricing();
+        }
+        lookup_pricing(&self.registry_pricing.read(), model)
+    }
+
+    /// 计算一次请求的费用，模型无定价时返回 None
+    ///
+    /// 返回 `(费用, 货币)`
+    pub fn cost(
+        &self,
+        model: &str,
+        input_tokens: u32,
+        output_tokens: u32,
+        cache_read_tokens: u32,
+        cache_write_tokens: u32,
+    ) -> Option<(f64, String)> {
+        let pricing = self.pricing_for(model)?;
+        Some((
+            pricing.cost(
+                input_tokens,
+                output_tokens,
+                cache_read_tokens,
+                cache_write_tokens,
+            ),
+            pricing.currency,
+        ))
+    }
+
+    /// 记录一次请求的花费，返回需要发送的预警
+    ///
+    /// 花费统计始终进行；货币与预算货币不同的费用不计入
+    pub fn record_spend(
+        &self,
+        subject: &BudgetSubject,
+        cost: f64,
+        currency: &str,
+    ) -> Vec<BudgetAlert> {
+        let config = self.config.read().clone();
+        if cost <= 0.0 || !currency.eq_ignore_ascii_case(&config.currency) {
+            return Vec::new();
+        }
+
+        let now = Utc::now();
+        self.prune_stale_periods(now);
+        for scope in [
+            BudgetScope::Provider,
+            BudgetScope::Credential,
+            BudgetScope::ClientKey,
+        ] {
+            let Some(target) = subject.target(scope) else {
+                continue;
+            };
+            for window in [BudgetWindow::Daily, BudgetWindow::Monthly] {
+                let period = period_key(window, now);
+                *self
+                    .spend
+                    .entry((scope, target.to_string(), period.clone()))
+                    .or_insert(0.0) += cost;
+                if let Some(db) = self.db.get() {
+                    if let Ok(conn) = db.lock() {
+                        if let Err(e) =
+                            BudgetDao::add_spend(&conn, scope_key(scope), target, &period, cost)
+                        {
+                            tracing::warn!("[BUDGET] 记录花费失败: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !config.enabled {
+            return Vec::new();
+        }
+        self.collect_alerts(&config, subject, now)
+    }
+
+    /// 检查预算，决定放行、拒绝或降级
+    ///
+    /// 多条规则同时超出时拒绝优先于降级；请求模型已经是降级模型时放行
+    pub fn check(&self, subject: &BudgetSubject, model: &str) -> BudgetDecision {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return BudgetDecision::Allow;
+        }
+
+        let now = Utc::now();
+        let mut downgrade = None;
+        for rule in config.rules.iter().filter(|r| subject.matches(r)) {
+            let Some(exceeded) = self.exceeded(&config, rule, subject, now) else {
+                continue;
+            };
+            match (rule.action, &rule.downgrade_model) {
+                (BudgetAction::Warn, _) => {}
+                (BudgetAction::Downgrade, Some(cheaper)) => {
+                    if cheaper != model && downgrade.is_none() {
+                        downgrade = Some(BudgetDecision::Downgrade {
+                            model: cheaper.clone(),
+                            exceeded,
+                        });
+                    }
+                }
+                // 未配置降级模型的降级规则按拒绝处理
+                (BudgetAction::Block, _) | (BudgetAction::Downgrade, None) => {
+                    return BudgetDecision::Block(exceeded);
+                }
+            }
+        }
+        downgrade.unwrap_or(BudgetDecision::Allow)
+    }
+
+    /// 当前统计周期超出凭证范围预算的凭证及超出的预算
+    ///
+    /// 凭证范围的预算在选择凭证时生效，返回的凭证应从候选中排除。凭证选定后不再改写模型，
+    /// 所以凭证范围的降级规则（请求模型不是降级模型时）同样按排除处理
+    pub fn over_budget_credentials(&self, model: &str) -> Vec<(String, BudgetExceeded)> {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return Vec::new();
+        }
+        let rules: Vec<&BudgetRule> = config
+            .rules
+            .iter()
+            .filter(|r| {
+                r.scope == BudgetScope::Credential
+                    && r.action != BudgetAction::Warn
+                    && !(r.action == BudgetAction::Downgrade
+                        && r.downgrade_model.as_deref() == Some(model))
+            })
+            .collect();
+        if rules.is_empty() {
+            return Vec::new();
+        }
+
+        let now = Utc::now();
+        let mut credentials: Vec<String> = self
+            .spend
+            .iter()
+            .filter(|entry| entry.key().0 == BudgetScope::Credential)
+            .map(|entry| entry.key().1.clone())
+            .collect();
+        credentials.sort();
+        credentials.dedup();
+        credentials
+            .into_iter()
+            .filter_map(|credential_id| {
+                let subject = BudgetSubject {
+                    credential_id: Some(credential_id.clone()),
+                    ..BudgetSubject::default()
+                };
+                let exceeded = rules
+                    .iter()
+                    .filter(|rule| subject.matches(rule))
+                    .find_map(|rule| self.exceeded(&config, rule, &subject, now))?;
+                Some((credential_id, exceeded))
+            })
+            .collect()
+    }
+
+    /// 当前统计周期内某个目标的花费
+    pub fn spent(&self, scope: BudgetScope, target: &str, window: BudgetWindow) -> f64 {
+        let period = period_key(window, Utc::now());
+        self.spend
+            .get(&(scope, target.to_string(), period))
+            .map(|v| *v)
+            .unwrap_or(0.0)
+    }
+
+    /// 所有预算规则在当前周期的状态
+    pub fn status(&self) -> Vec<BudgetStatus> {
+        let config = self.config.read().clone();
+        let now = Utc::now();
+        let mut statuses = Vec::new();
+        for rule in &config.rules {
+            let period = period_key(rule.window, now);
+            let targets: Vec<(String, f64)> = self
+                .spend
+                .iter()
+                .filter(|entry| {
+                    let (scope, target, p) = entry.key();
+                    *scope == rule.scope
+                        && *p == period
+                        && (rule.target == "*" || rule.target.eq_ignore_ascii_case(target))
+                })
+                .map(|entry| (entry.key().1.clone(), *entry.value()))
+                .collect();
+            let targets = if targets.is_empty() && rule.target != "*" {
+                vec![(rule.target.clone(), 0.0)]
+            } else {
+                targets
+            };
+            for (target, spent) in targets {
+                statuses.push(BudgetStatus {
+                    scope: rule.scope,
+                    target,
+                    window: rule.window,
+                    period: period.clone(),
+                    spent,
+                    limit: rule.limit,
+                    currency: config.currency.clone(),
+                    action: rule.action,
+                    exceeded: spent >= rule.limit,
+                });
+            }
+        }
+        statuses
+    }
+
+    /// 规则对该对象是否已超出预算
+    fn exceeded(
+        &self,
+        config: &BudgetConfig,
+        rule: &BudgetRule,
+        subject: &BudgetSubject,
+        now: DateTime<Utc>,
+    ) -> Option<BudgetExceeded> {
+        let target = subject.target(rule.scope)?;
+        let spent = self
+            .spend
+            .get(&(rule.scope, target.to_string(), period_key(rule.window, now)))
+            .map(|v| *v)
+            .unwrap_or(0.0);
+        (spent >= rule.limit).then(|| BudgetExceeded {
+            scope: rule.scope,
+            target: target.to_string(),
+            window: rule.window,
+            spent,
+            limit: rule.limit,
+            currency: config.currency.clone(),
+        })
+    }
+
+    /// 收集新跨过预警线或预算线的规则
+    fn collect_alerts(
+        &self,
+        config: &BudgetConfig,
+        subject: &BudgetSubject,
+        now: DateTime<Utc>,
+    ) -> Vec<BudgetAlert> {
+        let mut alerts = Vec::new();
+        for (index, rule) in config.rules.iter().enumerate() {
+            if !subject.matches(rule) {
+                continue;
+            }
+            let Some(target) = subject.target(rule.scope) else {
+                continue;
+            };
+            let period = period_key(rule.window, now);
+            let spent = self
+                .spend
+                .get(&(rule.scope, target.to_string(), period.clone()))
+                .map(|v| *v)
+                .unwrap_or(0.0);
+
+            let level = if spent >= rule.limit {
+                BudgetAlertLevel::Exceeded
+            } else if rule.warn_ratio > 0.0 && spent >= rule.limit * rule.warn_ratio {
+                BudgetAlertLevel::Warning
+            } else {
+                continue;
+            };
+
+            let key = format!("{}:{}:{}", index, target, period);
+            let already = self.alerted.get(&key).map(|l| *l);
+            if already.is_some_and(|l| l >= level) {
+                continue;
+            }
+            self.alerted.insert(key, level);
+            alerts.push(BudgetAlert {
+                level,
+                scope: rule.scope,
+                target: target.to_string(),
+                window: rule.window,
+                spent,
+                limit: rule.limit,
+                currency: config.currency.clone(),
+                action: rule.action,
+            });
+        }
+        alerts
+    }
+
+    /// 丢弃已经结束的统计周期
+    fn prune_stale_periods(&self, now: DateTime<Utc>) {
+        let day = period_key(BudgetWindow::Daily, now);
+        let month = period_key(BudgetWindow::Monthly, now);
+        if self
+            .spend
+            .iter()
+            .any(|e| e.key().2 != day && e.key().2 != month)
+        {
+            self.spend.retain(|(_, _, p), _| *p == day || *p == month);
+            self.alerted
+                .retain(|key, _| key.ends_with(&day) || key.ends_with(&month));
+        }
+    }
+}
+
+/// 模型定价快照（配置覆盖优先，其次为模型注册表）
+///
+/// 用于在代理服务之外（如 Tauri 命令）按历史 Flow 计算费用
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    overrides: HashMap<String, ModelPricing>,
+    registry: HashMap<String, ModelPricing>,
+}
+
+impl PricingTable {
+    /// 从预算配置和模型注册表加载
+    pub fn load(config: &BudgetConfig, db: &DbConnection) -> Self {
+        let registry = match db.lock() {
+            Ok(conn) => BudgetDao::list_model_pricing(&conn).unwrap_or_else(|e| {
+                tracing::warn!("[BUDGET] 加载模型定价失败: {}", e);
+                Vec::new()
+            }),
+            Err(e) => {
+                tracing::warn!("[BUDGET] 加载模型定价失败: {}", e);
+                Vec::new()
+            }
+        };
+        Self {
+            overrides: config.pricing.clone(),
+            registry: registry.into_iter().collect(),
+        }
+    }
+
+    /// 获取模型定价
+    pub fn get(&self, model: &str) -> Option<ModelPricing> {
+        lookup_pricing(&self.overrides, model).or_else(|| lookup_pricing(&self.registry, model))
+    }
+}
+
+/// 按模型 ID 查找定价：精确匹配，其次忽略大小写，最后取最长的前缀匹配
+/// （如 `claude-sonnet-4-5` 匹配 `claude-sonnet-4-5-20250929`）
+fn lookup_pricing(pricing: &HashMap<String, ModelPricing>, model: &str) -> Option<ModelPricing> {
+    if let Some(p) = pricing.get(model) {
+        return Some(p.clone());
+    }
+    let lower = model.to_lowercase();
+    pricing
+        .iter()
+        .filter(|(id, p)| p.is_priced() && lower.starts_with(&id.to_lowercase()))
+        .max_by_key(|(id, _)| id.len())
+        .map(|(_, p)| p.clone())
+}
+
+fn scope_key(scope: BudgetScope) -> &'static str {
+    match scope {
+        BudgetScope::Provider => "provider",
+        BudgetScope::Credential => "credential",
+        BudgetScope::ClientKey => "client_key",
+    }
+}
+
+fn parse_scope(scope: &str) -> Option<BudgetScope> {
+    match scope {
+        "provider" => Some(BudgetScope::Provider),
+        "credential" => Some(BudgetScope::Credential),
+        "client_key" => Some(BudgetScope::ClientKey),
+        _ => None,
+    }
+}
+
+fn window_label(window: BudgetWindow) -> &'static str {
+    match window {
+        BudgetWindow::Daily => "day",
+        BudgetWindow::Monthly => "month",
+    }
+}
+
+/// 统计周期 key（UTC，`YYYY-MM-DD` / `YYYY-MM`）
+fn period_key(window: BudgetWindow, now: DateTime<Utc>) -> String {
+    match window {
+        BudgetWindow::Daily => now.format("%Y-%m-%d").to_string(),
+        BudgetWindow::Monthly => now.format("%Y-%m").to_string(),
+    }
+}
+
+/// 当前统计周期的结束时间
+fn period_end(window: BudgetWindow, now: DateTime<Utc>) -> DateTime<Utc> {
+    let next = match window {
+        BudgetWindow::Daily => now.date_naive() + Duration::days(1),
+        BudgetWindow::Monthly => {
+            let (year, month) = if now.month() == 12 {
+                (now.year() + 1, 1)
+            } else {
+                (now.year(), now.month() + 1)
+            };
+            NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(now.date_naive())
+        }
+    };
+    Utc.from_utc_datetime(&next.and_hms_opt(0, 0, 0).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pricing(input: f64, output: f64) -> ModelPricing {
+        ModelPricing {
+            input_per_million: Some(input),
+            output_per_million: Some(output),
+            ..ModelPricing::default()
+        }
+    }
+
+    fn rule(scope: BudgetScope, target: &str, limit: f64, action: BudgetAction) -> BudgetRule {
+        BudgetRule {
+            scope,
+            target: target.to_string(),
+            window: BudgetWindow::Daily,
+            limit,
+            action,
+            downgrade_model: None,
+            warn_ratio: 0.5,
+        }
+    }
+
+    fn subject() -> BudgetSubject {
+        BudgetSubject {
+            provider: Some("kiro".to_string()),
+            credential_id: Some("cred-1".to_string()),
+            client_key_id: Some("key-1".to_string()),
+            client_key_label: Some("ci-nightly".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_cost_uses_config_override_and_prefix_match() {
+        let mut config = BudgetConfig::default();
+        config
+            .pricing
+            .insert("claude-sonnet-4-5".to_string(), pricing(3.0, 15.0));
+        let service = BudgetService::new(config);
+
+        let (cost, currency) = service
+            .cost("claude-sonnet-4-5-20250929", 1_000_000, 0, 0, 0)
+            .unwrap();
+        assert!((cost - 3.0).abs() < 1e-9);
+        assert_eq!(currency, "USD");
+        assert!(service.cost("gpt-4o", 1000, 1000, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_block_and_alerts() {
+        let service = BudgetService::new(BudgetConfig {
+            enabled: true,
+            rules: vec![rule(
+                BudgetScope::ClientKey,
+                "ci-nightly",
+                1.0,
+                BudgetAction::Block,
+            )],
+            ..BudgetConfig::default()
+        });
+        let subject = subject();
+
+        assert!(service.record_spend(&subject, 0.2, "USD").is_empty());
+        assert_eq!(service.check(&subject, "m"), BudgetDecision::Allow);
+
+        // 达到预警比例只预警一次
+        let alerts = service.record_spend(&subject, 0.4, "USD");
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].level, BudgetAlertLevel::Warning);
+        assert!(service.record_spend(&subject, 0.1, "USD").is_empty());
+
+        let alerts = service.record_spend(&subject, 0.5, "USD");
+        assert_eq!(alerts[0].level, BudgetAlertLevel::Exceeded);
+        match service.check(&subject, "m") {
+            BudgetDecision::Block(exceeded) => {
+                assert_eq!(exceeded.target, "key-1");
+                assert!(exceeded.retry_after_secs() <= 86_400);
+            }
+            other => panic!("unexpected decision: {:?}", other),
+        }
+
+        // 其他货币的费用不计入预算
+        service.record_spend(&subject, 100.0, "CNY");
+        assert!(
+            (service.spent(BudgetScope::ClientKey, "key-1", BudgetWindow::Daily) - 1.2).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_downgrade_and_warn_actions() {
+        let mut downgrade = rule(BudgetScope::Provider, "*", 1.0, BudgetAction::Downgrade);
+        downgrade.downgrade_model = Some("claude-haiku-4-5".to_string());
+        let service = BudgetService::new(BudgetConfig {
+            enabled: true,
+            rules: vec![
+                rule(BudgetScope::Credential, "cred-1", 0.5, BudgetAction::Warn),
+                downgrade,
+            ],
+            ..BudgetConfig::default()
+        });
+        let subject = subject();
+        service.record_spend(&subject, 2.0, "USD");
+
+        assert!(matches!(
+            service.check(&subject, "claude-sonnet-4-5"),
+            BudgetDecision::Downgrade { ref model, .. } if model == "claude-haiku-4-5"
+        ));
+        // 已经是降级模型时放行
+        assert_eq!(
+            service.check(&subject, "claude-haiku-4-5"),
+            BudgetDecision::Allow
+        );
+        assert_eq!(service.status().len(), 2);
+    }
+
+    #[test]
+    fn test_over_budget_credentials() {
+        let mut downgrade = rule(
+            BudgetScope::Credential,
+            "cred-2",
+            1.0,
+            BudgetAction::Downgrade,
+        );
+        downgrade.downgrade_model = Some("claude-haiku-4-5".to_string());
+        let service = BudgetService::new(BudgetConfig {
+            enabled: true,
+            rules: vec![
+                rule(BudgetScope::Credential, "cred-1", 1.0, BudgetAction::Block),
+                downgrade,
+                rule(BudgetScope::Credential, "*", 0.5, BudgetAction::Warn),
+            ],
+            ..BudgetConfig::default()
+        });
+        for (credential_id, cost) in [("cred-1", 2.0), ("cred-2", 2.0), ("cred-3", 2.0)] {
+            let subject = BudgetSubject {
+                credential_id: Some(credential_id.to_string()),
+                ..subject()
+            };
+            service.record_spend(&subject, cost, "USD");
+        }
+
+        // 预警规则不排除凭证；降级规则在请求模型不是降级模型时排除
+        let excluded = service.over_budget_credentials("claude-sonnet-4-5");
+        let ids: Vec<&str> = excluded.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["cred-1", "cred-2"]);
+        assert_eq!(excluded[0].1.target, "cred-1");
+
+        let excluded = service.over_budget_credentials("claude-haiku-4-5");
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0].0, "cred-1");
+    }
+
+    #[test]
+    fn test_spend_survives_restart() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let db = std::sync::Arc::new(std::sync::Mutex::new(conn));
+
+        let service = BudgetService::default();
+        service.attach_db(db.clone());
+        service.record_spend(&subject(), 0.75, "USD");
+        service.record_spend(&subject(), 0.25, "USD");
+
+        let restarted = BudgetService::default();
+        assert_eq!(restarted.attach_db(db), 6);
+        assert!(
+            (restarted.spent(BudgetScope::Provider, "kiro", BudgetWindow::Monthly) - 1.0).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_period_end() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 31, 10, 0, 0).unwrap();
+        assert_eq!(
+            period_end(BudgetWindow::Daily, now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            period_end(BudgetWindow::Monthly, now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+}
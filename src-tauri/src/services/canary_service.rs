@@ -0,0 +1,427 @@
+//! 凭证健康定时探测（Canary）
+//!
+//! 健康检查默认只在手动触发时执行，凭证失效往往要等真实请求失败才被发现。
+//! 探测器在后台按间隔向每个启用健康检查的凭证发送一个极小的请求：
+//!
+//! - 每个凭证的探测时间在 `jitter_secs` 范围内错开，避免同时打到上游
+//! - 探测模型可按 Provider 类型配置为便宜的模型
+//! - 结果和延迟写入健康检查历史（来源为 `canary`）
+//! - 连续失败达到凭证池的最大错误次数后凭证被移出轮换，探测成功后自动恢复
+//! - 探测请求直接发往上游，不经过代理，不计入使用统计和预算
+//! - 每次探测结果都广播为 [`CanaryProbeEvent`]，在 Flow Monitor 中单独标记
+
+use crate::config::CanaryConfig;
+use crate::database::dao::provider_pool::ProviderPoolDao;
+use crate::database::DbConnection;
+use crate::services::provider_pool_service::{HealthCheckSource, ProviderPoolService};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::{DashMap, DashSet};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// 扫描间隔（秒），决定探测时间的精度
+const SCAN_TICK_SECS: u64 = 5;
+
+/// 探测事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryProbeEvent {
+    pub credential_id: String,
+    pub provider_type: String,
+    pub name: Option<String>,
+    pub model: Option<String>,
+    pub success: bool,
+    pub latency_ms: u64,
+    /// 探测前凭证是否健康
+    pub was_healthy: bool,
+    /// 探测后凭证是否健康（`false` 表示已移出轮换）
+    pub is_healthy: bool,
+    pub message: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 待探测的凭证
+#[derive(Debug, Clone)]
+struct ProbeCandidate {
+    uuid: String,
+    provider_type: String,
+    name: Option<String>,
+    is_healthy: bool,
+}
+
+/// 凭证健康定时探测器
+pub struct CanaryProber {
+    config: RwLock<CanaryConfig>,
+    /// 各凭证下次探测时间
+    next_probe_at: DashMap<String, DateTime<Utc>>,
+    in_flight: DashSet<String>,
+    events: broadcast::Sender<CanaryProbeEvent>,
+}
+
+impl Default for CanaryProber {
+    fn default() -> Self {
+        Self::new(CanaryConfig::default())
+    }
+}
+
+impl CanaryProber {
+    pub fn new(config: CanaryConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            config: RwLock::new(config),
+            next_probe_at: DashMap::new(),
+            in_flight: DashSet::new(),
+            events,
+        }
+    }
+
+    /// 更新配置（热重载）
+    pub fn set_config(&self, config: CanaryConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> CanaryConfig {
+        self.config.read().clone()
+    }
+
+    /// 订阅探测事件
+    pub fn subscribe(&self) -> broadcast::Receiver<CanaryProbeEvent> {
+        self.events.subscribe()
+    }
+
+    /// 启动后台探测任务，返回的句柄被丢弃时停止
+    pub fn spawn(
+        self: &Arc<Self>,
+        db: DbConnection,
+        pool_service: Arc<ProviderPoolService>,
+    ) -> CanaryHandle {
+        let prober = self.clone();
+        CanaryHandle(vec![tokio::spawn(async move {
+            loop {
+                let config = prober.config();
+                if config.enabled {
+                    prober.scan(&config, &db, &pool_service);
+                } else {
+                    // 关闭后重新开启时重新错开首次探测
+                    prober.next_probe_at.clear();
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(SCAN_TICK_SECS)).await;
+            }
+        })])
+    }
+
+    /// 扫描一轮，为到期的凭证启动探测任务
+    fn scan(
+        self: &Arc<Self>,
+        config: &CanaryConfig,
+        db: &DbConnection,
+        pool_service: &Arc<ProviderPoolService>,
+    ) {
+        let candidates = match load_candidates(db) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                tracing::warn!("[CANARY] 加载凭证失败: {}", e);
+                return;
+            }
+        };
+
+        let slots = config
+            .max_concurrent
+            .max(1)
+            .saturating_sub(self.in_flight.len());
+        let now = Utc::now();
+        let due = self.due_candidates(candidates, config, now);
+        for candidate in due.into_iter().take(slots) {
+            if !self.in_flight.insert(candidate.uuid.clone()) {
+                continue;
+            }
+            self.next_probe_at.insert(
+                candidate.uuid.clone(),
+                next_probe_time(&candidate.uuid, config, now),
+            );
+
+            let prober = self.clone();
+            let db = db.clone();
+            let pool_service = pool_service.clone();
+            let model = config.models.get(&candidate.provider_type).cloned();
+            tokio::spawn(async move {
+                let event = prober
+                    .probe(&db, &pool_service, &candidate, model.as_deref())
+                    .await;
+                prober.in_flight.remove(&candidate.uuid);
+                if let Some(event) = event {
+                    let _ = prober.events.send(event);
+                }
+            });
+        }
+    }
+
+    /// 探测单个凭证
+    async fn probe(
+        &self,
+        db: &DbConnection,
+        pool_service: &ProviderPoolService,
+        candidate: &ProbeCandidate,
+        model: Option<&str>,
+    ) -> Option<CanaryProbeEvent> {
+        let result = match pool_service
+            .check_credential_health_with(db, &candidate.uuid, model, HealthCheckSource::Canary)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!(
+                    "[CANARY] 探测 {} 失败: {}",
+                    &candidate.uuid[..8.min(candidate.uuid.len())],
+                    e
+                );
+                return None;
+            }
+        };
+
+        let is_healthy = pool_service
+            .get_credential_health(db, &candidate.uuid)
+            .ok()
+            .flatten()
+            .map(|info| info.is_healthy)
+            .unwrap_or(result.success);
+        if candidate.is_healthy && !is_healthy {
+            tracing::warn!(
+                "[CANARY] {} ({}) 探测连续失败，已移出轮换: {}",
+                &candidate.uuid[..8.min(candidate.uuid.len())],
+                candidate.provider_type,
+                result.message.as_deref().unwrap_or_default()
+            );
+        } else if !candidate.is_healthy && is_healthy {
+            tracing::info!(
+                "[CANARY] {} ({}) 探测恢复正常，已重新加入轮换",
+                &candidate.uuid[..8.min(candidate.uuid.len())],
+                candidate.provider_type
+            );
+        }
+
+        Some(CanaryProbeEvent {
+            credential_id: candidate.uuid.clone(),
+            provider_type: candidate.provider_type.clone(),
+            name: candidate.name.clone(),
+            model: result.model,
+            success: result.success,
+            latency_ms: result.duration_ms,
+            was_healthy: candidate.is_healthy,
+            is_healthy,
+            message: result.message,
+            timestamp: Utc::now(),
+        })
+    }
+
+    /// 筛选到期需要探测的凭证
+    ///
+    /// 首次见到的凭证在抖动范围内安排首次探测，已删除凭证的调度状态被清理
+    fn due_candidates(
+        &self,
+        candidates: Vec<ProbeCandidate>,
+        config: &CanaryConfig,
+        now: DateTime<Utc>,
+    ) -> Vec<ProbeCandidate> {
+        let known: HashSet<&str> = candidates.iter().map(|c| c.uuid.as_str()).collect();
+        self.next_probe_at
+            .retain(|uuid, _| known.contains(uuid.as_str()));
+
+        let mut due: Vec<(DateTime<Utc>, ProbeCandidate)> = candidates
+            .into_iter()
+            .filter(|c| !self.in_flight.contains(&c.uuid))
+            .filter_map(|c| {
+                let at = *self
+                    .next_probe_at
+                    .entry(c.uuid.clone())
+                    .or_insert_with(|| now + jitter(&c.uuid, now, config.jitter_secs));
+                (now >= at).then_some((at, c))
+            })
+            .collect();
+        due.sort_by_key(|(at, _)| *at);
+        due.into_iter().map(|(_, c)| c).collect()
+    }
+}
+
+/// 后台探测任务句柄，丢弃时停止任务
+pub struct CanaryHandle(Vec<JoinHandle<()>>);
+
+impl CanaryHandle {
+    /// 附加需要随探测器一起停止的任务（如事件转发）
+    pub fn attach(mut self, task: JoinHandle<()>) -> Self {
+        self.0.push(task);
+        self
+    }
+}
+
+impl Drop for CanaryHandle {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// 下次探测时间：间隔 + 抖动
+fn next_probe_time(uuid: &str, config: &CanaryConfig, now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::seconds(config.interval_secs.max(SCAN_TICK_SECS) as i64)
+        + jitter(uuid, now, config.jitter_secs)
+}
+
+/// 按凭证和时间计算 `[0, jitter_secs]` 范围内的抖动
+fn jitter(uuid: &str, now: DateTime<Utc>, jitter_secs: u64) -> Duration {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    uuid.hash(&mut hasher);
+    now.timestamp().hash(&mut hasher);
+    Duration::seconds((hasher.finish() % (jitter_secs + 1)) as i64)
+}
+
+/// 加载未禁用且启用健康检查的凭证
+fn load_candidates(db: &DbConnection) -> Result<Vec<ProbeCandidate>, String> {
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    let credentials = ProviderPoolDao::get_all(&conn).map_err(|e| e.to_string())?;
+    Ok(credentials
+        .into_iter()
+        .filter(|c| !c.is_disabled && c.check_health)
+        .map(|c| ProbeCandidate {
+            uuid: c.uuid,
+            provider_type: c.provider_type.to_string(),
+            name: c.name,
+            is_healthy: c.is_healthy,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(uuid: &str) -> ProbeCandidate {
+        ProbeCandidate {
+            uuid: uuid.to_string(),
+            provider_type: "claude".to_string(),
+            name: None,
+            is_healthy: true,
+        }
+    }
+
+    #[test]
+    fn test_first_probe_spread_within_jitter() {
+        let config = CanaryConfig {
+            enabled: true,
+            jitter_secs: 60,
+            ..Default::default()
+        };
+        let prober = CanaryProber::new(config.clone());
+        let now = Utc::now();
+        let ids: Vec<String> = (0..20).map(|i| format!("cred-{}", i)).collect();
+
+        prober.due_candidates(ids.iter().map(|id| candidate(id)).collect(), &config, now);
+        for id in &ids {
+            let at = *prober.next_probe_at.get(id).unwrap();
+            assert!(at >= now && at <= now + Duration::seconds(60));
+        }
+        // 抖动结束后全部到期
+        let due = prober.due_candidates(
+            ids.iter().map(|id| candidate(id)).collect(),
+            &config,
+            now + Duration::seconds(60),
+        );
+        assert_eq!(due.len(), ids.len());
+    }
+
+    #[test]
+    fn test_reschedule_after_probe() {
+        let config = CanaryConfig {
+            enabled: true,
+            interval_secs: 300,
+            jitter_secs: 0,
+            ..Default::default()
+        };
+        let prober = CanaryProber::new(config.clone());
+        let now = Utc::now();
+
+        let due = prober.due_candidates(vec![candidate("a")], &config, now);
+        assert_eq!(due.len(), 1);
+
+        prober
+            .next_probe_at
+            .insert("a".to_string(), next_probe_time("a", &config, now));
+        assert!(prober
+            .due_candidates(vec![candidate("a")], &config, now + Duration::seconds(299))
+            .is_empty());
+        assert_eq!(
+            prober
+                .due_candidates(vec![candidate("a")], &config, now + Duration::seconds(300))
+                .len(),
+            1
+        );
+
+        // 探测中的凭证不会重复调度
+        prober.in_flight.insert("a".to_string());
+        assert!(prober
+            .due_candidates(vec![candidate("a")], &config, now + Duration::seconds(600))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_forget_removed_credentials() {
+        let config = CanaryConfig::default();
+        let prober = CanaryProber::new(config.clone());
+        let now = Utc::now();
+
+        prober.due_candidates(vec![candidate("a"), candidate("b")], &config, now);
+        assert_eq!(prober.next_probe_at.len(), 2);
+        prober.due_candidates(vec![candidate("b")], &config, now);
+        assert!(prober.next_probe_at.get("a").is_none());
+        assert!(prober.next_probe_at.get("b").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_successful_probe_keeps_rate_limit_cooldown() {
+        use crate::models::provider_pool_model::CredentialData;
+        use crate::server::test_support::{add_credential, spawn_upstream, test_state};
+
+        let upstream = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|| async { axum::Json(serde_json::json!({"type": "message"})) }),
+        );
+        let base = spawn_upstream(upstream).await;
+        let state = test_state("claude");
+        let credential = add_credential(
+            &state,
+            crate::ProviderType::Claude,
+            CredentialData::ClaudeKey {
+                api_key: "sk-ant-test".to_string(),
+                base_url: Some(base),
+            },
+        );
+        let pool_service = state.pool_service.clone();
+        pool_service
+            .mark_rate_limited(&credential.uuid, 429, None, "", None)
+            .unwrap();
+
+        let prober = CanaryProber::new(CanaryConfig::default());
+        let event = prober
+            .probe(
+                state.db.as_ref().unwrap(),
+                &pool_service,
+                &candidate(&credential.uuid),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // 探测成功只更新健康状态，限流冷却和熔断器不受影响
+        assert!(event.success);
+        assert!(pool_service.rate_limits().is_rate_limited(&credential.uuid));
+        assert!(pool_service.circuit_breaker().snapshot().is_empty());
+    }
+}
@@ -0,0 +1,648 @@
+//! 客户端 API Key 服务
+//!
+//! 管理多租户客户端 key：签发、校验、模型白名单、RPM/TPM 限流和月度 Token 预算。
+//!
+//! - key 只以 SHA-256 哈希形式存储，校验时遍历所有 key 做常量时间比较
+//! - RPM/TPM 使用内存中的 60 秒滑动窗口统计
+//! - 月度 Token 用量持久化到 SQLite，跨月自动重置
+
+use crate::database::dao::client_api_key::{ClientApiKey, ClientApiKeyDao};
+use crate::database::DbConnection;
+use crate::router::glob_match;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+
+/// 客户端 key 前缀
+const CLIENT_KEY_PREFIX: &str = "pc-";
+
+/// 限流窗口长度
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// 客户端 key 校验错误
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ClientKeyError {
+    #[error("Invalid API key")]
+    InvalidKey,
+    #[error("API key is disabled")]
+    Disabled,
+    #[error("API key has expired")]
+    Expired,
+    #[error("Model '{0}' is not allowed for this API key")]
+    ModelNotAllowed(String),
+    #[error("Rate limit exceeded: {limit} requests per minute")]
+    RateLimited { limit: u32, retry_after_secs: u64 },
+    #[error("Token rate limit exceeded: {limit} tokens per minute")]
+    TokenRateLimited { limit: u64, retry_after_secs: u64 },
+    #[error("Monthly token budget exhausted: {used}/{budget} tokens used")]
+    BudgetExceeded { used: u64, budget: u64 },
+}
+
+impl ClientKeyError {
+    /// 对应的 HTTP 状态码
+    pub fn status_code(&self) -> u16 {
+        match self {
+            ClientKeyError::InvalidKey | ClientKeyError::Expired => 401,
+            ClientKeyError::Disabled | ClientKeyError::ModelNotAllowed(_) => 403,
+            ClientKeyError::RateLimited { .. }
+            | ClientKeyError::TokenRateLimited { .. }
+            | ClientKeyError::BudgetExceeded { .. } => 429,
+        }
+    }
+
+    /// 限流时建议的重试等待秒数
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            ClientKeyError::RateLimited {
+                retry_after_secs, ..
+            }
+            | ClientKeyError::TokenRateLimited {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
+        }
+    }
+}
+
+/// 已认证的客户端身份（用于 Flow 与遥测归属）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    pub id: String,
+    pub label: String,
+}
+
+/// 创建客户端 key 请求
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateClientKeyRequest {
+    pub label: String,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub rpm_limit: Option<u32>,
+    #[serde(default)]
+    pub tpm_limit: Option<u64>,
+    #[serde(default)]
+    pub monthly_token_budget: Option<u64>,
+}
+
+/// 更新客户端 key 请求（未提供的字段保持不变，`Some(None)` 表示清除）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateClientKeyRequest {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default)]
+    pub allowed_models: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub rpm_limit: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub tpm_limit: Option<Option<u64>>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub monthly_token_budget: Option<Option<u64>>,
+}
+
+/// 区分「字段缺失」与「显式 null」
+fn deserialize_nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// 单个 key 的滑动窗口用量
+#[derive(Debug, Default)]
+struct UsageWindow {
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+}
+
+impl UsageWindow {
+    fn prune(&mut self, now: Instant) {
+        while matches!(self.requests.front(), Some(t) if now.duration_since(*t) >= RATE_WINDOW) {
+            self.requests.pop_front();
+        }
+        while matches!(self.tokens.front(), Some((t, _)) if now.duration_since(*t) >= RATE_WINDOW) {
+            self.tokens.pop_front();
+        }
+    }
+
+    fn token_sum(&self) -> u64 {
+        self.tokens.iter().map(|(_, n)| n).sum()
+    }
+
+    /// 最早一条记录滑出窗口所需秒数
+    fn retry_after(oldest: Option<&Instant>, now: Instant) -> u64 {
+        oldest
+            .map(|t| RATE_WINDOW.saturating_sub(now.duration_since(*t)).as_secs() + 1)
+            .unwrap_or(1)
+    }
+}
+
+/// 客户端 API Key 服务
+#[derive(Default)]
+pub struct ClientKeyService {
+    /// 内存缓存（与数据库保持同步）
+    keys: RwLock<Vec<ClientApiKey>>,
+    /// key ID -> 滑动窗口用量
+    windows: DashMap<String, UsageWindow>,
+}
+
+impl ClientKeyService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从数据库加载所有客户端 key
+    pub fn load(&self, db: &DbConnection) -> Result<usize, String> {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let keys = ClientApiKeyDao::get_all(&conn).map_err(|e| e.to_string())?;
+        let count = keys.len();
+        *self.keys.write() = keys;
+        Ok(count)
+    }
+
+    /// 是否配置了客户端 key
+    pub fn has_keys(&self) -> bool {
+        !self.keys.read().is_empty()
+    }
+
+    /// 获取所有客户端 key
+    pub fn list_keys(&self) -> Vec<ClientApiKey> {
+        self.keys.read().clone()
+    }
+
+    /// 生成新的随机 key
+    pub fn generate_key() -> String {
+        let mut bytes = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        format!("{}{}", CLIENT_KEY_PREFIX, hex::encode(bytes))
+    }
+
+    /// 计算 key 的 SHA-256 哈希
+    pub fn hash_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// 创建客户端 key，返回记录和明文 key（明文只返回这一次）
+    pub fn create_key(
+        &self,
+        db: &DbConnection,
+        request: CreateClientKeyRequest,
+    ) -> Result<(ClientApiKey, String), String> {
+        let label = request.label.trim().to_string();
+        if label.is_empty() {
+            return Err("label is required".to_string());
+        }
+
+        let plaintext = Self::generate_key();
+        let now = Utc::now();
+        let key = ClientApiKey {
+            id: uuid::Uuid::new_v4().to_string(),
+            label,
+            key_hash: Self::hash_key(&plaintext),
+            key_prefix: plaintext
+                .chars()
+                .take(CLIENT_KEY_PREFIX.len() + 6)
+                .collect(),
+            enabled: true,
+            expires_at: request.expires_at,
+            allowed_models: request.allowed_models,
+            rpm_limit: request.rpm_limit,
+            tpm_limit: request.tpm_limit,
+            monthly_token_budget: request.monthly_token_budget,
+            usage_month: None,
+            month_tokens: 0,
+            request_count: 0,
+            last_used_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            ClientApiKeyDao::insert(&conn, &key).map_err(|e| e.to_string())?;
+        }
+        self.keys.write().push(key.clone());
+        Ok((key, plaintext))
+    }
+
+    /// 更新客户端 key 配置
+    pub fn update_key(
+        &self,
+        db: &DbConnection,
+        id: &str,
+        request: UpdateClientKeyRequest,
+    ) -> Result<Option<ClientApiKey>, String> {
+        let Some(mut key) = self.keys.read().iter().find(|k| k.id == id).cloned() else {
+            return Ok(None);
+        };
+
+        if let Some(label) = request.label {
+            let label = label.trim().to_string();
+            if label.is_empty() {
+                return Err("label cannot be empty".to_string());
+            }
+            key.label = label;
+        }
+        if let Some(enabled) = request.enabled {
+            key.enabled = enabled;
+        }
+        if let Some(expires_at) = request.expires_at {
+            key.expires_at = expires_at;
+        }
+        if let Some(models) = request.allowed_models {
+            key.allowed_models = models;
+        }
+        if let Some(rpm) = request.rpm_limit {
+            key.rpm_limit = rpm;
+        }
+        if let Some(tpm) = request.tpm_limit {
+            key.tpm_limit = tpm;
+        }
+        if let Some(budget) = request.monthly_token_budget {
+            key.monthly_token_budget = budget;
+        }
+        key.updated_at = Utc::now();
+
+        {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            ClientApiKeyDao::update(&conn, &key).map_err(|e| e.to_string())?;
+        }
+        if let Some(cached) = self.keys.write().iter_mut().find(|k| k.id == id) {
+            *cached = key.clone();
+        }
+        Ok(Some(key))
+    }
+
+    /// 删除客户端 key
+    pub fn delete_key(&self, db: &DbConnection, id: &str) -> Result<bool, String> {
+        let deleted = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            ClientApiKeyDao::delete(&conn, id).map_err(|e| e.to_string())?
+        };
+        self.keys.write().retain(|k| k.id != id);
+        self.windows.remove(id);
+        Ok(deleted)
+    }
+
+    /// 根据明文 key 查找客户端 key
+    ///
+    /// 对所有 key 的哈希做常量时间比较，不提前返回，避免时序侧信道。
+    pub fn authenticate(&self, provided: &str) -> Option<ClientApiKey> {
+        let provided_hash = Self::hash_key(provided);
+        let keys = self.keys.read();
+        let mut found: Option<&ClientApiKey> = None;
+        for key in keys.iter() {
+            let matches: bool = key
+                .key_hash
+                .as_bytes()
+                .ct_eq(provided_hash.as_bytes())
+                .into();
+            if matches && found.is_none() {
+                found = Some(key);
+            }
+        }
+        found.cloned()
+    }
+
+    /// 检查 key 状态、模型白名单和月度预算，不占用 RPM / TPM 窗口
+    ///
+    /// 用于 count_tokens、WebSocket 握手等不应计为一次请求的场景
+    pub fn check_access(
+        &self,
+        key: &ClientApiKey,
+        model: Option<&str>,
+    ) -> Result<(), ClientKeyError> {
+        if !key.enabled {
+            return Err(ClientKeyError::Disabled);
+        }
+        if key.expires_at.is_some_and(|t| t <= Utc::now()) {
+            return Err(ClientKeyError::Expired);
+        }
+        if let Some(model) = model {
+            if !model_allowed(&key.allowed_models, model) {
+                return Err(ClientKeyError::ModelNotAllowed(model.to_string()));
+            }
+        }
+        if let Some(budget) = key.monthly_token_budget {
+            let used = if key.usage_month.as_deref() == Some(current_month().as_str()) {
+                key.month_tokens
+            } else {
+                0
+            };
+            if used >= budget {
+                return Err(ClientKeyError::BudgetExceeded { used, budget });
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查 key 状态、模型白名单和配额，通过后计入一次请求
+    pub fn authorize(&self, key: &ClientApiKey, model: Option<&str>) -> Result<(), ClientKeyError> {
+        self.check_access(key, model)?;
+
+        let now = Instant::now();
+        let mut window = self.windows.entry(key.id.clone()).or_default();
+        window.prune(now);
+
+        if let Some(limit) = key.rpm_limit {
+            if window.requests.len() as u64 >= limit as u64 {
+                return Err(ClientKeyError::RateLimited {
+                    limit,
+                    retry_after_secs: UsageWindow::retry_after(window.requests.front(), now),
+                });
+            }
+        }
+        if let Some(limit) = key.tpm_limit {
+            if window.token_sum() >= limit {
+                return Err(ClientKeyError::TokenRateLimited {
+                    limit,
+                    retry_after_secs: UsageWindow::retry_after(
+                        window.tokens.front().map(|(t, _)| t),
+                        now,
+                    ),
+                });
+            }
+        }
+
+        window.requests.push_back(now);
+        Ok(())
+    }
+
+    /// 持久化请求计数
+    pub fn record_request(&self, db: Option<&DbConnection>, id: &str) {
+        let now = Utc::now();
+        if let Some(key) = self.keys.write().iter_mut().find(|k| k.id == id) {
+            key.request_count += 1;
+            key.last_used_at = Some(now);
+        }
+        if let Some(db) = db {
+            if let Ok(conn) = db.lock() {
+                if let Err(e) = ClientApiKeyDao::record_request(&conn, id, now) {
+                    tracing::warn!("[CLIENT_KEY] 记录请求失败: {}", e);
+                }
+            }
+        }
+    }
+
+    /// 记录 Token 用量（TPM 窗口 + 月度预算）
+    pub fn record_tokens(&self, db: Option<&DbConnection>, id: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let now = Instant::now();
+        {
+            let mut window = self.windows.entry(id.to_string()).or_default();
+            window.prune(now);
+            window.tokens.push_back((now, tokens));
+        }
+
+        let month = current_month();
+        if let Some(key) = self.keys.write().iter_mut().find(|k| k.id == id) {
+            if key.usage_month.as_deref() == Some(month.as_str()) {
+                key.month_tokens += tokens;
+            } else {
+                key.usage_month = Some(month.clone());
+                key.month_tokens = tokens;
+            }
+        }
+        if let Some(db) = db {
+            if let Ok(conn) = db.lock() {
+                if let Err(e) = ClientApiKeyDao::add_month_tokens(&conn, id, &month, tokens) {
+                    tracing::warn!("[CLIENT_KEY] 记录 Token 用量失败: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// 当前统计月份（UTC，YYYY-MM）
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+/// 模型是否在白名单中（空白名单表示不限制，通配符规则与路由规则的模型匹配一致）
+pub fn model_allowed(allowed_models: &[String], model: &str) -> bool {
+    allowed_models.is_empty()
+        || allowed_models
+            .iter()
+            .any(|pattern| glob_match(pattern, model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn setup_db() -> DbConnection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        Arc::new(Mutex::new(conn))
+    }
+
+    fn create(
+        service: &ClientKeyService,
+        db: &DbConnection,
+        req: CreateClientKeyRequest,
+    ) -> (ClientApiKey, String) {
+        service.create_key(db, req).unwrap()
+    }
+
+    #[test]
+    fn test_create_and_authenticate() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        let (key, plaintext) = create(
+            &service,
+            &db,
+            CreateClientKeyRequest {
+                label: "alice".to_string(),
+                ..Default::default()
+            },
+        );
+
+        assert!(plaintext.starts_with(CLIENT_KEY_PREFIX));
+        assert!(plaintext.starts_with(&key.key_prefix));
+        assert_ne!(key.key_hash, plaintext);
+        assert_eq!(service.authenticate(&plaintext).unwrap().id, key.id);
+        assert!(service.authenticate("pc-wrong").is_none());
+
+        // 重新加载后仍可认证
+        let reloaded = ClientKeyService::new();
+        assert_eq!(reloaded.load(&db).unwrap(), 1);
+        assert_eq!(reloaded.authenticate(&plaintext).unwrap().label, "alice");
+    }
+
+    #[test]
+    fn test_create_requires_label() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        assert!(service
+            .create_key(&db, CreateClientKeyRequest::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_authorize_disabled_expired_and_models() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        let (key, _) = create(
+            &service,
+            &db,
+            CreateClientKeyRequest {
+                label: "ci".to_string(),
+                allowed_models: vec!["claude-*".to_string(), "gpt-4o".to_string()],
+                ..Default::default()
+            },
+        );
+
+        assert!(service.authorize(&key, Some("claude-sonnet-4-5")).is_ok());
+        assert!(service.authorize(&key, Some("gpt-4o")).is_ok());
+        assert_eq!(
+            service.authorize(&key, Some("gemini-2.5-pro")),
+            Err(ClientKeyError::ModelNotAllowed(
+                "gemini-2.5-pro".to_string()
+            ))
+        );
+
+        let mut disabled = key.clone();
+        disabled.enabled = false;
+        assert_eq!(
+            service.authorize(&disabled, None),
+            Err(ClientKeyError::Disabled)
+        );
+
+        let mut expired = key.clone();
+        expired.expires_at = Some(Utc::now() - chrono::Duration::minutes(1));
+        assert_eq!(
+            service.authorize(&expired, None),
+            Err(ClientKeyError::Expired)
+        );
+        assert_eq!(ClientKeyError::Expired.status_code(), 401);
+    }
+
+    #[test]
+    fn test_rpm_limit() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        let (key, _) = create(
+            &service,
+            &db,
+            CreateClientKeyRequest {
+                label: "ci".to_string(),
+                rpm_limit: Some(2),
+                ..Default::default()
+            },
+        );
+
+        // 只检查访问权限不占用 RPM 窗口
+        for _ in 0..5 {
+            assert!(service.check_access(&key, None).is_ok());
+        }
+        assert!(service.authorize(&key, None).is_ok());
+        assert!(service.authorize(&key, None).is_ok());
+        let err = service.authorize(&key, None).unwrap_err();
+        assert!(matches!(err, ClientKeyError::RateLimited { limit: 2, .. }));
+        assert_eq!(err.status_code(), 429);
+        assert!(err.retry_after_secs().unwrap() <= 61);
+    }
+
+    #[test]
+    fn test_tpm_limit_and_monthly_budget() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        let (key, plaintext) = create(
+            &service,
+            &db,
+            CreateClientKeyRequest {
+                label: "bob".to_string(),
+                tpm_limit: Some(1000),
+                monthly_token_budget: Some(1500),
+                ..Default::default()
+            },
+        );
+
+        assert!(service.authorize(&key, None).is_ok());
+        service.record_tokens(Some(&db), &key.id, 1000);
+        let err = service.authorize(&key, None).unwrap_err();
+        assert!(matches!(err, ClientKeyError::TokenRateLimited { .. }));
+
+        // 月度预算：清除 TPM 窗口后仍受预算限制
+        service.windows.clear();
+        service.record_tokens(Some(&db), &key.id, 600);
+        let current = service.authenticate(&plaintext).unwrap();
+        assert_eq!(current.month_tokens, 1600);
+        service.windows.clear();
+        assert!(matches!(
+            service.authorize(&current, None),
+            Err(ClientKeyError::BudgetExceeded {
+                used: 1600,
+                budget: 1500
+            })
+        ));
+
+        // 用量已持久化
+        let reloaded = ClientKeyService::new();
+        reloaded.load(&db).unwrap();
+        assert_eq!(reloaded.list_keys()[0].month_tokens, 1600);
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let db = setup_db();
+        let service = ClientKeyService::new();
+        let (key, plaintext) = create(
+            &service,
+            &db,
+            CreateClientKeyRequest {
+                label: "ci".to_string(),
+                rpm_limit: Some(10),
+                ..Default::default()
+            },
+        );
+
+        let update: UpdateClientKeyRequest =
+            serde_json::from_str(r#"{"enabled": false, "rpm_limit": null}"#).unwrap();
+        let updated = service.update_key(&db, &key.id, update).unwrap().unwrap();
+        assert!(!updated.enabled);
+        assert_eq!(updated.rpm_limit, None);
+        assert_eq!(updated.label, "ci");
+        assert!(!service.authenticate(&plaintext).unwrap().enabled);
+
+        assert!(service
+            .update_key(&db, "missing", UpdateClientKeyRequest::default())
+            .unwrap()
+            .is_none());
+
+        assert!(service.delete_key(&db, &key.id).unwrap());
+        assert!(service.authenticate(&plaintext).is_none());
+        assert!(!service.has_keys());
+    }
+
+    #[test]
+    fn test_model_allowed() {
+        let allowed = |patterns: &[&str], model: &str| {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            model_allowed(&patterns, model)
+        };
+        assert!(allowed(&["*"], "anything"));
+        assert!(allowed(&["claude-*"], "claude-opus-4"));
+        assert!(allowed(&["*-mini"], "gpt-4o-mini"));
+        assert!(allowed(&["gpt-*-mini"], "gpt-4o-mini"));
+        assert!(!allowed(&["gpt-*-mini"], "gpt-4o"));
+        assert!(!allowed(&["claude-*", "gpt-4o"], "gpt-4o-mini"));
+        assert!(model_allowed(&[], "any"));
+    }
+}
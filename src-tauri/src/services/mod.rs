@@ -1,5 +1,10 @@
+pub mod alert_service;
 pub mod api_key_provider_service;
 pub mod backup_service;
+pub mod budget_service;
+pub mod canary_service;
+pub mod client_key_service;
+#[cfg(feature = "gui")]
 pub mod file_browser_service;
 pub mod kiro_event_service;
 pub mod live_sync;
@@ -11,10 +16,14 @@ pub mod model_service;
 pub mod prompt_service;
 pub mod prompt_sync;
 pub mod provider_pool_service;
+pub mod response_cache_service;
 pub mod skill_service;
 pub mod switch;
+#[cfg(feature = "gui")]
 pub mod sysinfo_service;
 pub mod token_cache_service;
+pub mod token_refresh_scheduler;
 pub mod update_check_service;
+#[cfg(feature = "gui")]
 pub mod update_window;
 pub mod usage_service;
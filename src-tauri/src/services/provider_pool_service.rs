@@ -4,6 +4,12 @@
 
 #![allow(dead_code)]
 
+use crate::credential::{
+    select_lowest_latency, BalanceStrategy, LatencyTracker, QuotaManager, RateLimitEvent,
+    RiskController, WeightedRoundRobin,
+};
+use crate::database::dao::cooldown_state::CooldownStore;
+use crate::database::dao::health_history::{HealthHistoryDao, HealthHistoryRecord};
 use crate::database::dao::provider_pool::ProviderPoolDao;
 use crate::database::DbConnection;
 use crate::models::provider_pool_model::{
@@ -14,12 +20,15 @@ use crate::models::provider_pool_model::{
 use crate::models::route_model::RouteInfo;
 use crate::providers::antigravity::TokenRefreshError;
 use crate::providers::kiro::KiroProvider;
+use crate::resilience::{CircuitBreakerRegistry, ConcurrencyLimiter};
 use crate::services::api_key_provider_service::ApiKeyProviderService;
+use crate::session::{extract_retry_delay, RateLimitReason, RateLimitRecord, RateLimitTracker};
 use chrono::Utc;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// 凭证健康信息
@@ -44,6 +53,28 @@ pub struct CredentialHealthInfo {
     pub requires_reauth: bool,
 }
 
+/// 每个凭证保留的健康检查历史条数
+pub const HEALTH_HISTORY_KEEP: usize = 100;
+
+/// 健康检查来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthCheckSource {
+    /// 手动或批量触发
+    Manual,
+    /// 定时探测
+    Canary,
+}
+
+impl HealthCheckSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthCheckSource::Manual => "manual",
+            HealthCheckSource::Canary => "canary",
+        }
+    }
+}
+
 /// 凭证选择错误
 /// Requirements: 3.4
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +97,22 @@ pub struct ProviderPoolService {
     max_error_count: u32,
     /// 健康检查超时时间
     health_check_timeout: Duration,
+    /// 凭证 / 上游主机熔断器
+    circuit_breaker: Arc<CircuitBreakerRegistry>,
+    /// 凭证 / Provider 并发限制器
+    concurrency: Arc<ConcurrencyLimiter>,
+    /// 按 Provider 类型配置的负载均衡策略（键为小写 Provider 类型）
+    balance_strategies: parking_lot::RwLock<HashMap<String, BalanceStrategy>>,
+    /// 加权轮询状态
+    weighted: WeightedRoundRobin,
+    /// 凭证 EWMA 延迟
+    latency: LatencyTracker,
+    /// 凭证 / 模型级别的限流冷却
+    rate_limits: Arc<RateLimitTracker>,
+    /// 配额耗尽的凭证（按上游给出的配额重置时间冷却）
+    quota: QuotaManager,
+    /// 凭证风控状态（连续限流时逐级延长冷却）
+    risk: RiskController,
 }
 
 impl Default for ProviderPoolService {
@@ -84,9 +131,116 @@ impl ProviderPoolService {
             round_robin_index: std::sync::RwLock::new(HashMap::new()),
             max_error_count: 3,
             health_check_timeout: Duration::from_secs(30),
+            circuit_breaker: Arc::new(CircuitBreakerRegistry::default()),
+            concurrency: Arc::new(ConcurrencyLimiter::default()),
+            balance_strategies: parking_lot::RwLock::new(HashMap::new()),
+            weighted: WeightedRoundRobin::new(),
+            latency: LatencyTracker::new(),
+            rate_limits: Arc::new(RateLimitTracker::default()),
+            quota: QuotaManager::with_defaults(),
+            risk: RiskController::with_defaults(),
         }
     }
 
+    /// 挂载冷却状态持久化，恢复重启前仍未过期的限流记录
+    ///
+    /// 返回恢复的记录数
+    pub fn attach_cooldown_store(&self, db: &DbConnection) -> usize {
+        let store = CooldownStore::new(db.clone());
+        store.purge_expired();
+        self.rate_limits.attach_store(store.clone())
+            + self.quota.attach_store(store.clone())
+            + self.risk.attach_store(store)
+    }
+
+    /// 凭证是否处于限流、配额耗尽或风控冷却中
+    pub fn is_cooling_down(&self, uuid: &str) -> bool {
+        self.rate_limits.is_rate_limited(uuid)
+            || !self.quota.is_available(uuid)
+            || self.risk.is_in_cooldown(uuid)
+    }
+
+    /// 获取限流追踪器
+    pub fn rate_limits(&self) -> &Arc<RateLimitTracker> {
+        &self.rate_limits
+    }
+
+    /// 记录上游限流响应
+    ///
+    /// 根据状态码和响应体判断限流原因，并优先使用 `retry-after` 头或响应体中的
+    /// `retryDelay` / `quotaResetTime` 作为冷却时间。不是限流响应时返回 None
+    pub fn mark_rate_limited(
+        &self,
+        uuid: &str,
+        status_code: u16,
+        headers: Option<&reqwest::header::HeaderMap>,
+        body: &str,
+        model: Option<&str>,
+    ) -> Option<RateLimitRecord> {
+        let reason = RateLimitReason::classify(status_code, body)?;
+        let body_json = serde_json::from_str::<serde_json::Value>(body).ok();
+        let retry_after = extract_retry_delay(headers, body_json.as_ref());
+        // 模型容量不足只影响该模型，其余原因冷却整个凭证
+        let model = match reason {
+            RateLimitReason::ModelCapacityExhausted => model,
+            _ => None,
+        };
+        match reason {
+            RateLimitReason::QuotaExhausted => {
+                self.quota
+                    .mark_quota_exceeded_with_delay(uuid, body, retry_after);
+            }
+            RateLimitReason::ModelCapacityExhausted => {}
+            _ => {
+                let mut event = RateLimitEvent::new(uuid.to_string()).with_status_code(status_code);
+                if let Some(delay) = retry_after {
+                    event = event.with_retry_after(delay.num_seconds().max(0) as u64);
+                }
+                self.risk.record_rate_limit(event);
+            }
+        }
+        Some(
+            self.rate_limits
+                .mark_rate_limited(uuid, reason, retry_after, model),
+        )
+    }
+
+    /// 设置按 Provider 类型的负载均衡策略（热重载）
+    pub fn set_balance_strategies(&self, strategies: HashMap<String, BalanceStrategy>) {
+        *self.balance_strategies.write() = strategies
+            .into_iter()
+            .map(|(provider, strategy)| (provider.to_lowercase(), strategy))
+            .collect();
+    }
+
+    /// 获取 Provider 类型配置的负载均衡策略
+    pub fn balance_strategy(&self, provider_type: &str) -> Option<BalanceStrategy> {
+        self.balance_strategies
+            .read()
+            .get(&provider_type.to_lowercase())
+            .copied()
+    }
+
+    /// 记录凭证成功请求的首字节延迟（用于 EWMA 延迟优先策略）
+    pub fn record_latency(&self, uuid: &str, latency_ms: u64) {
+        self.latency.record(uuid, latency_ms);
+    }
+
+    /// 记录凭证的一次失败请求，按惩罚延迟计入 EWMA
+    pub fn record_latency_failure(&self, uuid: &str, elapsed_ms: u64) {
+        self.latency.record_failure(uuid, elapsed_ms);
+    }
+
+    /// 获取熔断器注册表
+    pub fn circuit_breaker(&self) -> &Arc<CircuitBreakerRegistry> {
+        &self.circuit_breaker
+    }
+
+    /// 获取并发限制器
+    pub fn concurrency(&self) -> &Arc<ConcurrencyLimiter> {
+        &self.concurrency
+    }
+
     /// 获取所有凭证概览
     pub fn get_overview(&self, db: &DbConnection) -> Result<Vec<ProviderPoolOverview>, String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
@@ -171,6 +325,7 @@ impl ProviderPoolService {
         check_model_name: Option<String>,
         not_supported_models: Option<Vec<String>>,
         proxy_url: Option<String>,
+        weight: Option<u32>,
     ) -> Result<ProviderCredential, String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
         let mut cred = ProviderPoolDao::get_by_uuid(&conn, uuid)
@@ -198,6 +353,9 @@ impl ProviderPoolService {
         if let Some(p) = proxy_url {
             cred.proxy_url = if p.is_empty() { None } else { Some(p) };
         }
+        if let Some(w) = weight {
+            cred.weight = w;
+        }
         cred.updated_at = Utc::now();
 
         ProviderPoolDao::update(&conn, &cred).map_err(|e| e.to_string())?;
@@ -207,6 +365,9 @@ impl ProviderPoolService {
     /// 删除凭证
     pub fn delete_credential(&self, db: &DbConnection, uuid: &str) -> Result<bool, String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
+        if let Err(e) = HealthHistoryDao::delete_by_credential(&conn, uuid) {
+            tracing::warn!("[凭证池] 删除健康检查历史失败: {}", e);
+        }
         ProviderPoolDao::delete(&conn, uuid).map_err(|e| e.to_string())
     }
 
@@ -222,6 +383,34 @@ impl ProviderPoolService {
         db: &DbConnection,
         provider_type: &str,
         model: Option<&str>,
+    ) -> Result<Option<ProviderCredential>, String> {
+        self.select_credential_excluding(db, provider_type, model, &[])
+    }
+
+    /// 选择一个可用的凭证，排除指定的凭证
+    ///
+    /// 用于流式响应断流续传等需要换用其他凭证的场景
+    pub fn select_credential_excluding(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        exclude: &[String],
+    ) -> Result<Option<ProviderCredential>, String> {
+        self.select_credential_matching(db, provider_type, model, exclude, |_| true)
+    }
+
+    /// 选择一个满足 `accept` 的可用凭证，排除指定的凭证
+    ///
+    /// 调用方只能使用部分凭证类型时（如只有 API Key 凭证可以透传续传），
+    /// 在选择前过滤，避免选中无法使用的凭证却占用熔断器的探测名额
+    pub fn select_credential_matching(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        exclude: &[String],
+        accept: impl Fn(&ProviderCredential) -> bool,
     ) -> Result<Option<ProviderCredential>, String> {
         // 对于未知的 provider_type，直接返回 None（不是错误）
         // 这样可以让 select_credential_with_fallback 继续尝试智能降级
@@ -321,6 +510,46 @@ impl ProviderPoolService {
             });
         }
 
+        available.retain(|c| !exclude.contains(&c.uuid) && accept(c));
+
+        // 过滤熔断中的凭证（凭证或其上游主机熔断打开）
+        available.retain(|c| {
+            let permitted = self
+                .circuit_breaker
+                .is_call_permitted(&c.uuid, circuit_host(c).as_deref());
+            if !permitted {
+                eprintln!(
+                    "[SELECT_CREDENTIAL] credential {} 熔断中，跳过",
+                    c.name.as_deref().unwrap_or("unnamed")
+                );
+            }
+            permitted
+        });
+
+        // 过滤限流冷却中的凭证（含当前模型的模型级限流）
+        available.retain(|c| {
+            let limited = self.is_cooling_down(&c.uuid)
+                || model.is_some_and(|m| self.rate_limits.is_model_rate_limited(&c.uuid, m));
+            if limited {
+                eprintln!(
+                    "[SELECT_CREDENTIAL] credential {} 限流冷却中，跳过",
+                    c.name.as_deref().unwrap_or("unnamed")
+                );
+            }
+            !limited
+        });
+
+        // 并发已满的凭证只在没有空闲凭证时才选择（随后在其队列中排队）。
+        // 耗尽策略不因并发已满而切换凭证，而是在当前凭证的队列中等待，保持 Prompt Caching 命中
+        let strategy = self.balance_strategy(&pt.to_string());
+        if strategy != Some(BalanceStrategy::Drain)
+            && available
+                .iter()
+                .any(|c| !self.concurrency.is_saturated(&c.uuid))
+        {
+            available.retain(|c| !self.concurrency.is_saturated(&c.uuid));
+        }
+
         eprintln!(
             "[SELECT_CREDENTIAL] final available count: {}",
             available.len()
@@ -330,13 +559,18 @@ impl ProviderPoolService {
             return Ok(None);
         }
 
-        // 如果只有一个可用凭证，直接返回
-        if available.len() == 1 {
-            return Ok(Some(available.into_iter().next().unwrap()));
-        }
-
-        // 智能选择：基于权重分数选择最优凭证
-        let selected = self.select_best_credential_by_weight(&available);
+        // 如果只有一个可用凭证，直接返回；否则按配置的策略选择，
+        // 未配置策略时基于综合分数选择最优凭证
+        let selected = if available.len() == 1 {
+            available.into_iter().next().unwrap()
+        } else {
+            match strategy {
+                Some(strategy) => self.select_by_strategy(strategy, available),
+                None => self.select_best_credential_by_weight(&available),
+            }
+        };
+        self.circuit_breaker
+            .on_selected(&selected.uuid, circuit_host(&selected).as_deref());
 
         Ok(Some(selected))
     }
@@ -404,6 +638,46 @@ impl ProviderPoolService {
         Ok(None)
     }
 
+    /// 按负载均衡策略从多个可用凭证中选择一个
+    fn select_by_strategy(
+        &self,
+        strategy: BalanceStrategy,
+        mut credentials: Vec<ProviderCredential>,
+    ) -> ProviderCredential {
+        // 按创建顺序排列，保证轮询和耗尽策略的顺序稳定
+        credentials.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.uuid.cmp(&b.uuid)));
+
+        let index = match strategy {
+            BalanceStrategy::RoundRobin | BalanceStrategy::Weighted => {
+                let candidates: Vec<(&str, u32)> = credentials
+                    .iter()
+                    .map(|c| {
+                        let weight = if strategy == BalanceStrategy::Weighted {
+                            c.weight
+                        } else {
+                            1
+                        };
+                        (c.uuid.as_str(), weight)
+                    })
+                    .collect();
+                self.weighted.pick(&candidates)
+            }
+            BalanceStrategy::EwmaLatency => {
+                select_lowest_latency(&credentials, |c| self.latency.get(&c.uuid))
+            }
+            // 可用凭证已排除冷却、熔断和不健康的凭证，取第一个即持续使用同一凭证
+            BalanceStrategy::Drain => Some(0),
+            BalanceStrategy::LeastUsed => credentials
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, c)| c.usage_count)
+                .map(|(index, _)| index),
+            BalanceStrategy::Random => Some(rand::random::<usize>() % credentials.len()),
+        };
+
+        credentials.swap_remove(index.unwrap_or(0))
+    }
+
     /// 基于权重分数选择最优凭证
     fn select_best_credential_by_weight(
         &self,
@@ -482,6 +756,11 @@ impl ProviderPoolService {
         score
     }
 
+    /// 选中的凭证最终没有发出请求（或请求被取消）时调用，归还熔断器半开状态的探测名额
+    pub fn release_selection(&self, uuid: &str) {
+        self.circuit_breaker.release(uuid);
+    }
+
     /// 记录凭证使用
     pub fn record_usage(&self, db: &DbConnection, uuid: &str) -> Result<(), String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
@@ -499,6 +778,26 @@ impl ProviderPoolService {
         db: &DbConnection,
         uuid: &str,
         check_model: Option<&str>,
+    ) -> Result<(), String> {
+        self.circuit_breaker.record_success(uuid);
+        self.rate_limits.clear_rate_limit(uuid);
+        self.quota.restore_credential(uuid);
+        self.risk.clear_cooldown(uuid);
+        if let Some(model) = check_model {
+            self.rate_limits.clear_model_rate_limit(uuid, model);
+        }
+        self.mark_probe_healthy(db, uuid, check_model)
+    }
+
+    /// 探测成功后标记凭证为健康
+    ///
+    /// 只更新持久化的健康状态，不清除限流冷却、不计入熔断器：
+    /// 一次轻量探测成功不代表上游已解除限流，也不应替代半开状态下的真实请求
+    pub fn mark_probe_healthy(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        check_model: Option<&str>,
     ) -> Result<(), String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
         ProviderPoolDao::update_health_status(
@@ -520,6 +819,20 @@ impl ProviderPoolService {
         db: &DbConnection,
         uuid: &str,
         error_message: Option<&str>,
+    ) -> Result<(), String> {
+        self.circuit_breaker.record_failure(uuid);
+        self.mark_probe_unhealthy(db, uuid, error_message)
+    }
+
+    /// 探测失败时标记凭证为不健康
+    ///
+    /// 只更新持久化的健康状态和错误计数，不计入熔断器：
+    /// 熔断器只统计真实请求，探测失败不应让凭证提前熔断
+    pub fn mark_probe_unhealthy(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        error_message: Option<&str>,
     ) -> Result<(), String> {
         let conn = db.lock().map_err(|e| e.to_string())?;
         let cred = ProviderPoolDao::get_by_uuid(&conn, uuid)
@@ -544,6 +857,10 @@ impl ProviderPoolService {
 
     /// 重置凭证计数器
     pub fn reset_counters(&self, db: &DbConnection, uuid: &str) -> Result<(), String> {
+        self.circuit_breaker.reset(uuid);
+        self.rate_limits.clear_rate_limit(uuid);
+        self.quota.restore_credential(uuid);
+        self.risk.clear_cooldown(uuid);
         let conn = db.lock().map_err(|e| e.to_string())?;
         ProviderPoolDao::reset_counters(&conn, uuid).map_err(|e| e.to_string())
     }
@@ -621,6 +938,7 @@ impl ProviderPoolService {
         uuid: &str,
         error: &TokenRefreshError,
     ) -> Result<(), String> {
+        self.circuit_breaker.record_failure(uuid);
         let error_message = error.user_message();
         let requires_reauth = error.requires_reauth();
 
@@ -656,6 +974,34 @@ impl ProviderPoolService {
         .map_err(|e| e.to_string())
     }
 
+    /// 标记凭证需要重新授权（refresh token 已失效）
+    ///
+    /// 与 `mark_unhealthy` 不同，不受最大错误次数限制，直接标记为不健康
+    pub fn mark_reauth_required(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        reason: &str,
+    ) -> Result<(), String> {
+        self.circuit_breaker.record_failure(uuid);
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let cred = ProviderPoolDao::get_by_uuid(&conn, uuid)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Credential not found: {}", uuid))?;
+
+        ProviderPoolDao::update_health_status(
+            &conn,
+            uuid,
+            false,
+            cred.error_count + 1,
+            Some(Utc::now()),
+            Some(&format!("[需要重新授权] {}", reason)),
+            None,
+            None,
+        )
+        .map_err(|e| e.to_string())
+    }
+
     /// 选择一个健康的凭证
     /// Requirements: 2.4, 3.3, 3.4
     pub fn select_healthy_credential(
@@ -775,6 +1121,21 @@ impl ProviderPoolService {
         &self,
         db: &DbConnection,
         uuid: &str,
+    ) -> Result<HealthCheckResult, String> {
+        self.check_credential_health_with(db, uuid, None, HealthCheckSource::Manual)
+            .await
+    }
+
+    /// 使用指定模型执行健康检查，并写入健康检查历史
+    ///
+    /// `model` 为空时使用凭证配置的检查模型或 Provider 默认模型。
+    /// 健康检查直接请求上游，不经过代理，不计入使用统计。
+    pub async fn check_credential_health_with(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        model: Option<&str>,
+        source: HealthCheckSource,
     ) -> Result<HealthCheckResult, String> {
         let cred = {
             let conn = db.lock().map_err(|e| e.to_string())?;
@@ -783,11 +1144,66 @@ impl ProviderPoolService {
                 .ok_or_else(|| format!("Credential not found: {}", uuid))?
         };
 
-        let check_model = cred
-            .check_model_name
-            .clone()
+        let check_model = model
+            .map(str::to_string)
+            .or_else(|| cred.check_model_name.clone())
             .unwrap_or_else(|| get_default_check_model(cred.provider_type).to_string());
 
+        let result = self
+            .run_health_check(db, &cred, check_model, source)
+            .await?;
+
+        let record = HealthHistoryRecord {
+            credential_uuid: cred.uuid.clone(),
+            provider_type: cred.provider_type.to_string(),
+            source: source.as_str().to_string(),
+            success: result.success,
+            latency_ms: result.duration_ms,
+            model: result.model.clone(),
+            message: result.message.clone(),
+            checked_at: Utc::now(),
+        };
+        if let Err(e) = db.lock().map_err(|e| e.to_string()).and_then(|conn| {
+            HealthHistoryDao::insert(&conn, &record, HEALTH_HISTORY_KEEP).map_err(|e| e.to_string())
+        }) {
+            tracing::warn!("[健康检查] 写入健康检查历史失败: {}", e);
+        }
+
+        Ok(result)
+    }
+
+    /// 获取凭证最近的健康检查历史（按时间倒序）
+    pub fn get_health_history(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        limit: usize,
+    ) -> Result<Vec<HealthHistoryRecord>, String> {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        HealthHistoryDao::list_by_credential(&conn, uuid, limit.min(HEALTH_HISTORY_KEEP))
+            .map_err(|e| e.to_string())
+    }
+
+    /// 执行健康检查请求并更新凭证健康状态
+    ///
+    /// 定时探测只更新健康状态，不影响限流冷却和熔断器；
+    /// 手动检查成功时同时清除限流冷却和熔断状态，失败时计入熔断器
+    async fn run_health_check(
+        &self,
+        db: &DbConnection,
+        cred: &ProviderCredential,
+        check_model: String,
+        source: HealthCheckSource,
+    ) -> Result<HealthCheckResult, String> {
+        let uuid = cred.uuid.as_str();
+        let mark_healthy = |model: &str| match source {
+            HealthCheckSource::Manual => self.mark_healthy(db, uuid, Some(model)),
+            HealthCheckSource::Canary => self.mark_probe_healthy(db, uuid, Some(model)),
+        };
+        let mark_unhealthy = |error: &str| match source {
+            HealthCheckSource::Manual => self.mark_unhealthy(db, uuid, Some(error)),
+            HealthCheckSource::Canary => self.mark_probe_unhealthy(db, uuid, Some(error)),
+        };
         let start = std::time::Instant::now();
         let result = self
             .perform_health_check(&cred.credential, &check_model)
@@ -796,7 +1212,7 @@ impl ProviderPoolService {
 
         match result {
             Ok(_) => {
-                self.mark_healthy(db, uuid, Some(&check_model))?;
+                mark_healthy(&check_model)?;
                 Ok(HealthCheckResult {
                     uuid: uuid.to_string(),
                     success: true,
@@ -832,7 +1248,7 @@ impl ProviderPoolService {
 
                             match retry_result {
                                 Ok(_) => {
-                                    self.mark_healthy(db, uuid, Some(&check_model))?;
+                                    mark_healthy(&check_model)?;
                                     return Ok(HealthCheckResult {
                                         uuid: uuid.to_string(),
                                         success: true,
@@ -845,7 +1261,7 @@ impl ProviderPoolService {
                                 }
                                 Err(retry_e) => {
                                     tracing::warn!("[健康检查] Token 刷新后仍然失败: {}", retry_e);
-                                    self.mark_unhealthy(db, uuid, Some(&retry_e))?;
+                                    mark_unhealthy(&retry_e)?;
                                     return Ok(HealthCheckResult {
                                         uuid: uuid.to_string(),
                                         success: false,
@@ -859,7 +1275,7 @@ impl ProviderPoolService {
                         Err(refresh_err) => {
                             tracing::warn!("[健康检查] Token 刷新失败: {}", refresh_err);
                             // Token 刷新失败，返回原始错误
-                            self.mark_unhealthy(db, uuid, Some(&e))?;
+                            mark_unhealthy(&e)?;
                             return Ok(HealthCheckResult {
                                 uuid: uuid.to_string(),
                                 success: false,
@@ -871,7 +1287,7 @@ impl ProviderPoolService {
                     }
                 }
 
-                self.mark_unhealthy(db, uuid, Some(&e))?;
+                mark_unhealthy(&e)?;
                 Ok(HealthCheckResult {
                     uuid: uuid.to_string(),
                     success: false,
@@ -2103,6 +2519,17 @@ impl ProviderPoolService {
     }
 }
 
+/// 熔断器使用的上游主机标识：自定义 base_url 的主机名
+///
+/// 未配置 base_url 的凭证（OAuth 凭证、使用默认地址的 API Key）无法确定实际上游主机，
+/// 返回 None 只使用凭证级熔断，避免同一 Provider 的所有凭证共用一个主机熔断器
+fn circuit_host(cred: &ProviderCredential) -> Option<String> {
+    cred.credential
+        .base_url()
+        .and_then(|url| url::Url::parse(url).ok())
+        .and_then(|url| url.host_str().map(str::to_string))
+}
+
 /// 迁移结果
 #[derive(Debug, Clone, Default)]
 pub struct MigrationResult {
@@ -2249,4 +2676,71 @@ mod tests {
         assert_eq!(deserialized.uuid, info.uuid);
         assert_eq!(deserialized.is_healthy, info.is_healthy);
     }
+
+    #[test]
+    fn test_cooldowns_restored_on_attach() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let db: DbConnection = Arc::new(std::sync::Mutex::new(conn));
+
+        let service = ProviderPoolService::new();
+        service.attach_cooldown_store(&db);
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "3600".parse().unwrap());
+        service.mark_rate_limited("quota", 429, Some(&headers), "quota exceeded", None);
+        service.mark_rate_limited("busy", 429, None, "too many requests", None);
+
+        // 模拟重启：限流、配额和风控三类记录都应恢复
+        let restarted = ProviderPoolService::new();
+        assert_eq!(restarted.attach_cooldown_store(&db), 4);
+        assert!(!restarted.quota.is_available("quota"));
+        assert!(restarted.risk.is_in_cooldown("busy"));
+        assert!(restarted.is_cooling_down("quota"));
+        assert!(restarted.is_cooling_down("busy"));
+
+        restarted.reset_counters(&db, "busy").ok();
+        assert!(!restarted.is_cooling_down("busy"));
+    }
+
+    #[test]
+    fn test_probe_failure_does_not_trip_breaker() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let cred = ProviderCredential::new(
+            PoolProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: None,
+            },
+        );
+        ProviderPoolDao::insert(&conn, &cred).unwrap();
+        let db: DbConnection = Arc::new(std::sync::Mutex::new(conn));
+
+        let service = ProviderPoolService::new();
+        service
+            .circuit_breaker()
+            .set_config(crate::config::CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            });
+
+        service
+            .mark_probe_unhealthy(&db, &cred.uuid, Some("canary failed"))
+            .unwrap();
+        let stored = ProviderPoolDao::get_by_uuid(&db.lock().unwrap(), &cred.uuid)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.error_count, 1);
+        assert_eq!(stored.last_error_message.as_deref(), Some("canary failed"));
+        assert!(service
+            .circuit_breaker()
+            .is_call_permitted(&cred.uuid, None));
+
+        service
+            .mark_unhealthy(&db, &cred.uuid, Some("request failed"))
+            .unwrap();
+        assert!(!service
+            .circuit_breaker()
+            .is_call_permitted(&cred.uuid, None));
+    }
 }
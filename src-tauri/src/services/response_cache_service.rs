@@ -0,0 +1,502 @@
+//! 响应缓存服务
+//!
+//! 对完全相同的请求直接返回缓存的上游响应，适用于 CI 反复运行同一批评测 prompt 的场景。
+//!
+//! - 缓存 key 为规范化请求（端点、模型、消息、工具、采样参数）的 SHA-256，
+//!   忽略 `user`/`metadata` 等不影响输出的字段，并按客户端 key 隔离
+//! - JSON 响应缓存响应体，SSE 响应缓存完整的原始事件流，命中时按事件重放
+//! - 条目保存在 SQLite 中，写入时清理过期条目并按最近使用时间淘汰超限条目
+
+use crate::config::ResponseCacheConfig;
+use crate::database::dao::response_cache::{ResponseCacheDao, ResponseCacheEntry};
+use crate::database::DbConnection;
+use chrono::{Duration, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 标记缓存结果的响应头（`hit` / `miss` / `bypass`）
+pub const CACHE_STATUS_HEADER: &str = "x-proxycast-cache";
+
+/// 计算缓存 key 时忽略的顶层字段（只用于调用方标识，不影响模型输出）
+const IGNORED_FIELDS: &[&str] = &["user", "metadata"];
+
+/// 客户端通过 `Cache-Control` 请求头表达的缓存指令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheDirective {
+    /// 先查缓存，未命中时写入
+    Default,
+    /// `no-cache`：跳过查找，但用新响应刷新缓存
+    Refresh,
+    /// `no-store`：既不查找也不写入
+    Bypass,
+}
+
+impl CacheDirective {
+    /// 解析 `Cache-Control` 请求头
+    pub fn from_cache_control(value: Option<&str>) -> Self {
+        let Some(value) = value else {
+            return CacheDirective::Default;
+        };
+        let directives: Vec<String> = value
+            .split(',')
+            .map(|d| d.trim().to_ascii_lowercase())
+            .collect();
+        if directives.iter().any(|d| d == "no-store") {
+            CacheDirective::Bypass
+        } else if directives.iter().any(|d| d == "no-cache") {
+            CacheDirective::Refresh
+        } else {
+            CacheDirective::Default
+        }
+    }
+
+    pub fn allows_lookup(self) -> bool {
+        self == CacheDirective::Default
+    }
+
+    pub fn allows_store(self) -> bool {
+        self != CacheDirective::Bypass
+    }
+}
+
+/// 规范化请求对应的缓存 key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    /// SHA-256（十六进制）
+    pub key: String,
+    pub endpoint: String,
+    pub model: String,
+    pub is_stream: bool,
+}
+
+impl CacheKey {
+    /// 根据请求体计算缓存 key
+    ///
+    /// `request` 应为别名解析和参数注入之后的最终请求
+    pub fn new(endpoint: &str, request: &Value, client_key_id: Option<&str>) -> Self {
+        let mut normalized = request.clone();
+        if let Some(obj) = normalized.as_object_mut() {
+            for field in IGNORED_FIELDS {
+                obj.remove(*field);
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(endpoint.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(client_key_id.unwrap_or("").as_bytes());
+        hasher.update(b"\n");
+        hasher.update(canonical_json(&normalized).as_bytes());
+
+        Self {
+            key: hex::encode(hasher.finalize()),
+            endpoint: endpoint.to_string(),
+            model: request
+                .get("model")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            is_stream: request
+                .get("stream")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// 按键名排序的紧凑 JSON 序列化，保证字段顺序不同的同一请求得到相同的 key
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .filter(|k| !map[*k].is_null())
+                .map(|k| format!("{}:{}", Value::String(k.clone()), canonical_json(&map[k])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// 缓存统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResponseCacheStats {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    pub max_size_mb: u64,
+    pub entries: u64,
+    pub size_bytes: u64,
+    /// 所有条目的累计命中次数
+    pub total_hits: u64,
+    /// 本次启动以来的命中/未命中/写入次数
+    pub hits: u64,
+    pub misses: u64,
+    pub stores: u64,
+}
+
+/// 响应缓存服务
+#[derive(Default)]
+pub struct ResponseCacheService {
+    config: RwLock<ResponseCacheConfig>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stores: AtomicU64,
+}
+
+impl ResponseCacheService {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            ..Self::default()
+        }
+    }
+
+    /// 更新缓存配置
+    pub fn set_config(&self, config: ResponseCacheConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 是否启用
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().enabled
+    }
+
+    /// 单个响应允许缓存的最大字节数
+    pub fn max_entry_bytes(&self) -> usize {
+        (self.config.read().max_entry_kb as usize).saturating_mul(1024)
+    }
+
+    /// 查找缓存，命中时累加命中次数
+    pub fn lookup(&self, db: &DbConnection, key: &CacheKey) -> Option<ResponseCacheEntry> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let conn = db.lock().ok()?;
+        let now = Utc::now();
+        match ResponseCacheDao::get(&conn, &key.key, now) {
+            Ok(Some(entry)) => {
+                if let Err(e) = ResponseCacheDao::record_hit(&conn, &key.key, now) {
+                    tracing::warn!("[CACHE] 记录命中失败: {}", e);
+                }
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry)
+            }
+            Ok(None) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("[CACHE] 查询缓存失败: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 写入缓存，返回是否已写入
+    ///
+    /// 超过单条大小上限的响应不缓存；写入后清理过期条目并淘汰超限条目
+    pub fn store(
+        &self,
+        db: &DbConnection,
+        key: &CacheKey,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> bool {
+        let config = self.config.read().clone();
+        if !config.enabled || body.is_empty() || body.len() > self.max_entry_bytes() {
+            return false;
+        }
+
+        let now = Utc::now();
+        let entry = ResponseCacheEntry {
+            cache_key: key.key.clone(),
+            endpoint: key.endpoint.clone(),
+            model: key.model.clone(),
+            is_stream: key.is_stream,
+            status_code: 200,
+            content_type: content_type.to_string(),
+            body,
+            hit_count: 0,
+            created_at: now,
+            expires_at: now + Duration::seconds(config.ttl_secs.min(i64::MAX as u64) as i64),
+        };
+
+        let Ok(conn) = db.lock() else {
+            return false;
+        };
+        let result = ResponseCacheDao::upsert(&conn, &entry)
+            .and_then(|_| ResponseCacheDao::delete_expired(&conn, now))
+            .and_then(|_| {
+                ResponseCacheDao::evict_to_limits(
+                    &conn,
+                    config.max_entries,
+                    config.max_size_mb.saturating_mul(1024 * 1024),
+                )
+            });
+        match result {
+            Ok(evicted) => {
+                self.stores.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(
+                    "[CACHE] 已缓存 endpoint={} model={} stream={} bytes={} evicted={}",
+                    key.endpoint,
+                    key.model,
+                    key.is_stream,
+                    entry.body.len(),
+                    evicted
+                );
+                true
+            }
+            Err(e) => {
+                tracing::warn!("[CACHE] 写入缓存失败: {}", e);
+                false
+            }
+        }
+    }
+
+    /// 清空缓存
+    pub fn clear(&self, db: &DbConnection) -> Result<usize, String> {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        ResponseCacheDao::clear(&conn).map_err(|e| e.to_string())
+    }
+
+    /// 获取缓存统计
+    pub fn stats(&self, db: Option<&DbConnection>) -> ResponseCacheStats {
+        let config = self.config.read().clone();
+        let usage = db
+            .and_then(|db| db.lock().ok())
+            .and_then(|conn| ResponseCacheDao::usage(&conn).ok())
+            .unwrap_or_default();
+        ResponseCacheStats {
+            enabled: config.enabled,
+            ttl_secs: config.ttl_secs,
+            max_entries: config.max_entries,
+            max_size_mb: config.max_size_mb,
+            entries: usage.entries,
+            size_bytes: usage.size_bytes,
+            total_hits: usage.total_hits,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            stores: self.stores.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 将缓存的 SSE 字节流按事件拆分（保留每个事件末尾的空行），用于重放
+pub fn split_sse_events(body: &[u8]) -> Vec<Vec<u8>> {
+    let mut events = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < body.len() {
+        if body[i..].starts_with(b"\n\n") {
+            events.push(body[start..i + 2].to_vec());
+            i += 2;
+            start = i;
+        } else if body[i..].starts_with(b"\r\n\r\n") {
+            events.push(body[start..i + 4].to_vec());
+            i += 4;
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    if start < body.len() {
+        events.push(body[start..].to_vec());
+    }
+    events
+}
+
+/// 判断捕获的 SSE 响应是否完整且无错误，只有完整的流才可写入缓存
+///
+/// 要求收到终止事件（Anthropic `message_stop` 或 OpenAI `[DONE]`），
+/// 且流中没有 `error` 事件或带 `error` 字段的数据块；被截断的流不满足条件
+pub fn is_complete_stream(body: &[u8]) -> bool {
+    let mut terminated = false;
+    for event in split_sse_events(body) {
+        let text = String::from_utf8_lossy(&event);
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if let Some(value) = line.strip_prefix("event:") {
+                if value.trim() == "error" {
+                    return false;
+                }
+                continue;
+            }
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            if data == "[DONE]" {
+                terminated = true;
+                continue;
+            }
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(data) else {
+                continue;
+            };
+            if json.get("error").is_some_and(|e| !e.is_null()) || json["type"] == "error" {
+                return false;
+            }
+            if json["type"] == "message_stop" {
+                terminated = true;
+            }
+        }
+    }
+    terminated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    fn setup_db() -> DbConnection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        Arc::new(Mutex::new(conn))
+    }
+
+    fn enabled_config() -> ResponseCacheConfig {
+        ResponseCacheConfig {
+            enabled: true,
+            ..ResponseCacheConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_cache_key_normalization() {
+        let a = json!({"model": "gpt-4o", "temperature": 0, "messages": [{"role": "user", "content": "hi"}]});
+        let b = json!({"messages": [{"content": "hi", "role": "user"}], "temperature": 0, "model": "gpt-4o", "user": "ci-42"});
+        let c = json!({"model": "gpt-4o", "temperature": 1, "messages": [{"role": "user", "content": "hi"}]});
+
+        let ka = CacheKey::new("/v1/chat/completions", &a, None);
+        assert_eq!(ka, CacheKey::new("/v1/chat/completions", &b, None));
+        assert_ne!(ka.key, CacheKey::new("/v1/chat/completions", &c, None).key);
+        assert_ne!(ka.key, CacheKey::new("/v1/messages", &a, None).key);
+        assert_ne!(
+            ka.key,
+            CacheKey::new("/v1/chat/completions", &a, Some("ck-1")).key
+        );
+        assert_eq!(ka.model, "gpt-4o");
+        assert!(!ka.is_stream);
+    }
+
+    #[test]
+    fn test_cache_directive() {
+        assert_eq!(
+            CacheDirective::from_cache_control(None),
+            CacheDirective::Default
+        );
+        assert_eq!(
+            CacheDirective::from_cache_control(Some("No-Cache")),
+            CacheDirective::Refresh
+        );
+        assert_eq!(
+            CacheDirective::from_cache_control(Some("no-cache, no-store")),
+            CacheDirective::Bypass
+        );
+        assert!(!CacheDirective::Refresh.allows_lookup());
+        assert!(CacheDirective::Refresh.allows_store());
+    }
+
+    #[test]
+    fn test_store_and_lookup() {
+        let db = setup_db();
+        let service = ResponseCacheService::new(enabled_config());
+        let key = CacheKey::new(
+            "/v1/chat/completions",
+            &json!({"model": "m", "stream": true}),
+            None,
+        );
+
+        assert!(service.lookup(&db, &key).is_none());
+        assert!(service.store(&db, &key, "text/event-stream", b"data: {}\n\n".to_vec()));
+
+        let entry = service.lookup(&db, &key).unwrap();
+        assert!(entry.is_stream);
+        assert_eq!(entry.body, b"data: {}\n\n");
+
+        let stats = service.stats(Some(&db));
+        assert_eq!((stats.hits, stats.misses, stats.stores), (1, 1, 1));
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.total_hits, 1);
+
+        assert_eq!(service.clear(&db).unwrap(), 1);
+        assert!(service.lookup(&db, &key).is_none());
+    }
+
+    #[test]
+    fn test_disabled_and_limits() {
+        let db = setup_db();
+        let service = ResponseCacheService::new(ResponseCacheConfig::default());
+        let key = CacheKey::new("/v1/messages", &json!({"model": "m"}), None);
+        assert!(!service.store(&db, &key, "application/json", b"{}".to_vec()));
+
+        service.set_config(ResponseCacheConfig {
+            enabled: true,
+            max_entry_kb: 1,
+            ..ResponseCacheConfig::default()
+        });
+        assert!(!service.store(&db, &key, "application/json", vec![b'x'; 2048]));
+
+        // TTL 为 0 的条目立即过期
+        service.set_config(ResponseCacheConfig {
+            enabled: true,
+            ttl_secs: 0,
+            ..ResponseCacheConfig::default()
+        });
+        assert!(service.store(&db, &key, "application/json", b"{}".to_vec()));
+        assert!(service.lookup(&db, &key).is_none());
+    }
+
+    #[test]
+    fn test_eviction_by_entry_count() {
+        let db = setup_db();
+        let service = ResponseCacheService::new(ResponseCacheConfig {
+            enabled: true,
+            max_entries: 2,
+            ..ResponseCacheConfig::default()
+        });
+        let keys: Vec<CacheKey> = (0..3)
+            .map(|i| CacheKey::new("/v1/messages", &json!({"model": "m", "n": i}), None))
+            .collect();
+        for key in &keys {
+            assert!(service.store(&db, key, "application/json", b"{}".to_vec()));
+        }
+        assert_eq!(service.stats(Some(&db)).entries, 2);
+    }
+
+    #[test]
+    fn test_split_sse_events() {
+        let body = b"event: a\ndata: 1\n\nevent: b\ndata: 2\n\ndata: [DONE]\n\n";
+        let events = split_sse_events(body);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], b"event: a\ndata: 1\n\n");
+        assert_eq!(events.concat(), body.to_vec());
+    }
+
+    #[test]
+    fn test_is_complete_stream() {
+        let anthropic = b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\"}\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+        assert!(is_complete_stream(anthropic));
+        let openai = b"data: {\"choices\":[]}\n\ndata: [DONE]\n\n";
+        assert!(is_complete_stream(openai));
+
+        // 截断的流
+        assert!(!is_complete_stream(b"data: {\"choices\":[]}\n\n"));
+        // 流中带 error 的数据块，即使之后收到终止事件
+        let errored = b"data: {\"error\":{\"message\":\"overloaded\"}}\n\ndata: [DONE]\n\n";
+        assert!(!is_complete_stream(errored));
+        let error_event = b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\"}}\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+        assert!(!is_complete_stream(error_event));
+    }
+}
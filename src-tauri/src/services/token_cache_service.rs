@@ -843,7 +843,7 @@ impl TokenCacheService {
     }
 
     /// 从源文件读取 Token（不刷新）
-    async fn read_token_from_source(
+    pub(crate) async fn read_token_from_source(
         &self,
         credential: &ProviderCredential,
     ) -> Result<CachedTokenInfo, String> {
@@ -863,11 +863,25 @@ impl TokenCacheService {
                     .as_str()
                     .or_else(|| creds["refresh_token"].as_str())
                     .map(|s| s.to_string());
+                // expiresAt 可能是 RFC3339 字符串或秒级时间戳，旧版凭证文件没有该字段
+                let expiry_time = match &creds["expiresAt"] {
+                    serde_json::Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .ok()
+                        .or_else(|| {
+                            s.parse::<i64>()
+                                .ok()
+                                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                        }),
+                    value => value
+                        .as_i64()
+                        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0)),
+                };
 
                 Ok(CachedTokenInfo {
                     access_token,
                     refresh_token,
-                    expiry_time: None, // Kiro 源文件通常没有过期时间
+                    expiry_time,
                     last_refresh: None,
                     refresh_error_count: 0,
                     last_refresh_error: None,
@@ -1234,6 +1248,59 @@ impl TokenCacheService {
             .await
     }
 
+    /// 在过期前提前刷新 Token（供后台刷新调度器使用）
+    ///
+    /// 与 `refresh_and_cache` 不同：不添加随机延迟（由调度器负责错开），
+    /// 失败时也不自动禁用凭证，由调度器根据连续失败情况决定是否标记不健康。
+    /// 缓存的 Token 在 `stale_before` 之后才过期（如等锁期间已被其他请求刷新）时不刷新，返回 `None`
+    pub async fn refresh_ahead(
+        &self,
+        db: &DbConnection,
+        uuid: &str,
+        stale_before: chrono::DateTime<Utc>,
+    ) -> Result<Option<CachedTokenInfo>, String> {
+        let lock = self
+            .locks
+            .entry(uuid.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone();
+        let _guard = lock.lock().await;
+
+        let (credential, cached) = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            let credential = ProviderPoolDao::get_by_uuid(&conn, uuid)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Credential not found: {}", uuid))?;
+            let cached =
+                ProviderPoolDao::get_token_cache(&conn, uuid).map_err(|e| e.to_string())?;
+            (credential, cached)
+        };
+
+        if let Some(cache) = cached {
+            let fresh = cache
+                .expiry_time
+                .is_some_and(|expiry| expiry > stale_before);
+            if cache.access_token.is_some() && fresh {
+                return Ok(None);
+            }
+        }
+
+        match self.do_refresh(&credential).await {
+            Ok(token_info) => {
+                let conn = db.lock().map_err(|e| e.to_string())?;
+                ProviderPoolDao::update_token_cache(&conn, uuid, &token_info)
+                    .map_err(|e| e.to_string())?;
+                Ok(Some(token_info))
+            }
+            Err(e) => {
+                if let Ok(conn) = db.lock() {
+                    let _ = ProviderPoolDao::record_token_refresh_error(&conn, uuid, &e);
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// 检查 Token 是否即将过期并提前刷新（需求 4.4）
     ///
     /// 在流式请求前调用此方法，检查 Token 是否在指定分钟数内过期。
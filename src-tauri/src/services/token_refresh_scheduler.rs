@@ -0,0 +1,602 @@
+//! OAuth Token 提前刷新调度器
+//!
+//! Token 默认只在使用时或遇到 401/403 后才刷新，空闲后的第一个请求要承担刷新延迟，
+//! 刷新失败时请求也随之失败。调度器在后台定期扫描凭证池中 OAuth 凭证
+//! （Kiro、Gemini、Qwen、Antigravity、Codex、Claude OAuth、iFlow）的 Token 过期时间：
+//!
+//! - 在过期前 `refresh_before_secs` 加上按凭证错开的随机抖动提前刷新；从未使用过、
+//!   还没有缓存 Token 的凭证按凭证文件中的过期时间调度
+//! - 同时进行的刷新数不超过 `max_concurrent`，超出的留到下一轮扫描
+//! - 刷新失败按指数退避重试
+//! - 只有 refresh token 连续被明确拒绝（如 `invalid_grant`）才标记凭证需要重新授权
+//! - 每次刷新结果都广播为 [`TokenRefreshEvent`]
+
+use crate::config::TokenRefreshConfig;
+use crate::database::dao::provider_pool::ProviderPoolDao;
+use crate::database::DbConnection;
+use crate::models::provider_pool_model::{CachedTokenInfo, CredentialData, ProviderCredential};
+use crate::services::provider_pool_service::ProviderPoolService;
+use crate::services::token_cache_service::TokenCacheService;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::{DashMap, DashSet};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// refresh token 被明确拒绝时错误信息中的关键字（小写）
+const REFRESH_TOKEN_REJECTED_MARKERS: &[&str] = &[
+    "invalid_grant",
+    "invalid_refresh_token",
+    "unauthorized_client",
+    "bad credentials",
+    "revoked",
+    "被撤销",
+    "需要重新认证",
+    "重新登录授权",
+];
+
+/// 刷新结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRefreshOutcome {
+    /// 刷新成功
+    Refreshed,
+    /// Token 已被其他请求刷新，本次未刷新
+    Skipped,
+    /// 刷新失败，将退避后重试
+    Failed,
+    /// refresh token 已失效，凭证已标记为需要重新授权
+    ReauthRequired,
+}
+
+/// 刷新事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenRefreshEvent {
+    pub credential_id: String,
+    pub provider_type: String,
+    pub name: Option<String>,
+    pub outcome: TokenRefreshOutcome,
+    /// 新 Token 的过期时间（刷新成功时）
+    pub expires_at: Option<DateTime<Utc>>,
+    /// 下次重试时间（刷新失败时）
+    pub retry_at: Option<DateTime<Utc>>,
+    /// 连续失败次数
+    pub consecutive_failures: u32,
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 待刷新的凭证
+#[derive(Debug, Clone)]
+struct RefreshCandidate {
+    uuid: String,
+    provider_type: String,
+    name: Option<String>,
+    expiry: DateTime<Utc>,
+    last_refresh: Option<DateTime<Utc>>,
+}
+
+/// 单个凭证的调度状态
+#[derive(Debug, Clone, Default)]
+struct RefreshState {
+    consecutive_failures: u32,
+    /// refresh token 连续被拒绝的次数
+    rejected_failures: u32,
+    retry_at: Option<DateTime<Utc>>,
+    /// 标记需要重新授权的时间；之后 Token 被重新刷新（如用户重新授权）则恢复调度
+    reauth_required_at: Option<DateTime<Utc>>,
+}
+
+/// OAuth Token 提前刷新调度器
+pub struct TokenRefreshScheduler {
+    config: RwLock<TokenRefreshConfig>,
+    states: DashMap<String, RefreshState>,
+    in_flight: DashSet<String>,
+    events: broadcast::Sender<TokenRefreshEvent>,
+}
+
+impl Default for TokenRefreshScheduler {
+    fn default() -> Self {
+        Self::new(TokenRefreshConfig::default())
+    }
+}
+
+impl TokenRefreshScheduler {
+    pub fn new(config: TokenRefreshConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            config: RwLock::new(config),
+            states: DashMap::new(),
+            in_flight: DashSet::new(),
+            events,
+        }
+    }
+
+    /// 更新配置（热重载）
+    pub fn set_config(&self, config: TokenRefreshConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> TokenRefreshConfig {
+        self.config.read().clone()
+    }
+
+    /// 订阅刷新事件
+    pub fn subscribe(&self) -> broadcast::Receiver<TokenRefreshEvent> {
+        self.events.subscribe()
+    }
+
+    /// 启动后台扫描任务，返回的句柄被丢弃时停止
+    pub fn spawn(
+        self: &Arc<Self>,
+        db: DbConnection,
+        token_cache: Arc<TokenCacheService>,
+        pool_service: Arc<ProviderPoolService>,
+    ) -> TokenRefreshHandle {
+        let scheduler = self.clone();
+        TokenRefreshHandle(vec![tokio::spawn(async move {
+            loop {
+                let config = scheduler.config();
+                if config.enabled {
+                    scheduler
+                        .scan(&config, &db, &token_cache, &pool_service)
+                        .await;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    config.scan_interval_secs.max(1),
+                ))
+                .await;
+            }
+        })])
+    }
+
+    /// 扫描一轮，为到期的凭证启动刷新任务
+    async fn scan(
+        self: &Arc<Self>,
+        config: &TokenRefreshConfig,
+        db: &DbConnection,
+        token_cache: &Arc<TokenCacheService>,
+        pool_service: &Arc<ProviderPoolService>,
+    ) {
+        let candidates = match load_candidates(db, token_cache).await {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                tracing::warn!("[TOKEN_REFRESH] 加载凭证失败: {}", e);
+                return;
+            }
+        };
+
+        let slots = config
+            .max_concurrent
+            .max(1)
+            .saturating_sub(self.in_flight.len());
+        let due = self.due_candidates(candidates, config, Utc::now());
+        for candidate in due.into_iter().take(slots) {
+            if !self.in_flight.insert(candidate.uuid.clone()) {
+                continue;
+            }
+            tracing::info!(
+                "[TOKEN_REFRESH] 提前刷新 {} ({})，Token 过期时间 {}",
+                &candidate.uuid[..8.min(candidate.uuid.len())],
+                candidate.provider_type,
+                candidate.expiry
+            );
+
+            let scheduler = self.clone();
+            let db = db.clone();
+            let token_cache = token_cache.clone();
+            let pool_service = pool_service.clone();
+            tokio::spawn(async move {
+                // 调度依据的过期时间已经包含抖动提前量，缓存 Token 仍在该时间前过期就必须刷新
+                let result = token_cache
+                    .refresh_ahead(&db, &candidate.uuid, candidate.expiry)
+                    .await;
+                let event = scheduler.record_result(&candidate, result, Utc::now());
+                if event.outcome == TokenRefreshOutcome::ReauthRequired {
+                    let reason = event.error.as_deref().unwrap_or("refresh token 已失效");
+                    if let Err(e) = pool_service.mark_reauth_required(&db, &candidate.uuid, reason)
+                    {
+                        tracing::warn!("[TOKEN_REFRESH] 标记凭证不健康失败: {}", e);
+                    }
+                }
+                scheduler.in_flight.remove(&candidate.uuid);
+                let _ = scheduler.events.send(event);
+            });
+        }
+    }
+
+    /// 筛选到期需要刷新的凭证，按过期时间排序
+    fn due_candidates(
+        &self,
+        candidates: Vec<RefreshCandidate>,
+        config: &TokenRefreshConfig,
+        now: DateTime<Utc>,
+    ) -> Vec<RefreshCandidate> {
+        let mut due: Vec<RefreshCandidate> = candidates
+            .into_iter()
+            .filter(|c| !self.in_flight.contains(&c.uuid))
+            .filter(|c| {
+                let Some(mut state) = self.states.get_mut(&c.uuid) else {
+                    return true;
+                };
+                if let Some(marked_at) = state.reauth_required_at {
+                    // 标记后 Token 又被成功刷新，说明已重新授权
+                    if c.last_refresh.is_some_and(|t| t > marked_at) {
+                        *state = RefreshState::default();
+                    } else {
+                        return false;
+                    }
+                }
+                state.retry_at.is_none_or(|t| now >= t)
+            })
+            .filter(|c| now >= refresh_due_at(c, config))
+            .collect();
+        due.sort_by_key(|c| c.expiry);
+        due
+    }
+
+    /// 记录刷新结果，返回对应事件
+    fn record_result(
+        &self,
+        candidate: &RefreshCandidate,
+        result: Result<Option<CachedTokenInfo>, String>,
+        now: DateTime<Utc>,
+    ) -> TokenRefreshEvent {
+        let config = self.config();
+        let mut state = self.states.entry(candidate.uuid.clone()).or_default();
+        let mut event = TokenRefreshEvent {
+            credential_id: candidate.uuid.clone(),
+            provider_type: candidate.provider_type.clone(),
+            name: candidate.name.clone(),
+            outcome: TokenRefreshOutcome::Refreshed,
+            expires_at: None,
+            retry_at: None,
+            consecutive_failures: 0,
+            error: None,
+            timestamp: now,
+        };
+
+        match result {
+            // 没有发生刷新，保留退避状态
+            Ok(None) => {
+                event.outcome = TokenRefreshOutcome::Skipped;
+                event.consecutive_failures = state.consecutive_failures;
+                tracing::debug!(
+                    "[TOKEN_REFRESH] {} 的 Token 已被其他请求刷新，跳过",
+                    &candidate.uuid[..8.min(candidate.uuid.len())]
+                );
+            }
+            Ok(Some(info)) => {
+                let expires_at = info.expiry_time;
+                *state = RefreshState::default();
+                event.expires_at = expires_at;
+                tracing::info!(
+                    "[TOKEN_REFRESH] {} 刷新成功，新 Token 过期时间 {:?}",
+                    &candidate.uuid[..8.min(candidate.uuid.len())],
+                    expires_at
+                );
+            }
+            Err(error) => {
+                state.consecutive_failures += 1;
+                if is_refresh_token_rejected(&error) {
+                    state.rejected_failures += 1;
+                } else {
+                    state.rejected_failures = 0;
+                }
+                event.consecutive_failures = state.consecutive_failures;
+
+                if state.rejected_failures >= config.dead_after_failures.max(1) {
+                    state.reauth_required_at = Some(now);
+                    state.retry_at = None;
+                    event.outcome = TokenRefreshOutcome::ReauthRequired;
+                    tracing::error!(
+                        "[TOKEN_REFRESH] {} 的 refresh token 已失效，需要重新授权: {}",
+                        &candidate.uuid[..8.min(candidate.uuid.len())],
+                        error
+                    );
+                } else {
+                    let retry_at = now + backoff(&config, state.consecutive_failures);
+                    state.retry_at = Some(retry_at);
+                    event.outcome = TokenRefreshOutcome::Failed;
+                    event.retry_at = Some(retry_at);
+                    tracing::warn!(
+                        "[TOKEN_REFRESH] {} 刷新失败（第 {} 次），{} 后重试: {}",
+                        &candidate.uuid[..8.min(candidate.uuid.len())],
+                        state.consecutive_failures,
+                        retry_at,
+                        error
+                    );
+                }
+                event.error = Some(error);
+            }
+        }
+        event
+    }
+}
+
+/// 后台扫描任务句柄，丢弃时停止任务
+pub struct TokenRefreshHandle(Vec<JoinHandle<()>>);
+
+impl TokenRefreshHandle {
+    /// 附加需要随调度器一起停止的任务（如事件转发）
+    pub fn attach(mut self, task: JoinHandle<()>) -> Self {
+        self.0.push(task);
+        self
+    }
+}
+
+impl Drop for TokenRefreshHandle {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// 是否支持提前刷新（OAuth 类凭证）
+fn is_refreshable(credential: &CredentialData) -> bool {
+    matches!(
+        credential,
+        CredentialData::KiroOAuth { .. }
+            | CredentialData::GeminiOAuth { .. }
+            | CredentialData::QwenOAuth { .. }
+            | CredentialData::AntigravityOAuth { .. }
+            | CredentialData::CodexOAuth { .. }
+            | CredentialData::ClaudeOAuth { .. }
+            | CredentialData::IFlowOAuth { .. }
+    )
+}
+
+/// refresh token 是否被明确拒绝（而非网络、服务端等临时错误）
+fn is_refresh_token_rejected(error: &str) -> bool {
+    let error = error.to_lowercase();
+    REFRESH_TOKEN_REJECTED_MARKERS
+        .iter()
+        .any(|marker| error.contains(marker))
+}
+
+/// 计算刷新时间：过期时间 - 提前量 - 抖动
+///
+/// 抖动由凭证 UUID 和过期时间决定，同一个 Token 在多轮扫描间保持不变
+fn refresh_due_at(candidate: &RefreshCandidate, config: &TokenRefreshConfig) -> DateTime<Utc> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    candidate.uuid.hash(&mut hasher);
+    candidate.expiry.timestamp().hash(&mut hasher);
+    let jitter = hasher.finish() % (config.jitter_secs + 1);
+
+    candidate.expiry
+        - Duration::seconds(config.refresh_before_secs as i64)
+        - Duration::seconds(jitter as i64)
+}
+
+/// 第 n 次失败后的退避时间
+fn backoff(config: &TokenRefreshConfig, failures: u32) -> Duration {
+    let secs = config
+        .backoff_base_secs
+        .saturating_mul(1u64 << failures.saturating_sub(1).min(20))
+        .min(config.backoff_max_secs);
+    Duration::seconds(secs as i64)
+}
+
+/// 加载未禁用、带过期时间的 OAuth 凭证
+async fn load_candidates(
+    db: &DbConnection,
+    token_cache: &TokenCacheService,
+) -> Result<Vec<RefreshCandidate>, String> {
+    let credentials = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let credentials = ProviderPoolDao::get_all(&conn).map_err(|e| e.to_string())?;
+        credentials
+            .into_iter()
+            .filter(|c| !c.is_disabled && is_refreshable(&c.credential))
+            .map(|c| {
+                let cache =
+                    ProviderPoolDao::get_token_cache(&conn, &c.uuid).map_err(|e| e.to_string())?;
+                Ok((c, cache))
+            })
+            .collect::<Result<Vec<_>, String>>()?
+    };
+
+    let mut candidates = Vec::new();
+    for (credential, cache) in credentials {
+        let source = if cache.as_ref().and_then(|c| c.expiry_time).is_none() {
+            token_cache.read_token_from_source(&credential).await.ok()
+        } else {
+            None
+        };
+        if let Some(candidate) = to_candidate(credential, cache.as_ref(), source.as_ref()) {
+            candidates.push(candidate);
+        }
+    }
+    Ok(candidates)
+}
+
+/// 优先使用数据库中缓存的过期时间，没有时（如从未使用过的凭证）使用凭证文件中的过期时间；
+/// 都没有过期时间的凭证仍按需刷新
+fn to_candidate(
+    credential: ProviderCredential,
+    cache: Option<&CachedTokenInfo>,
+    source: Option<&CachedTokenInfo>,
+) -> Option<RefreshCandidate> {
+    let expiry = cache
+        .and_then(|c| c.expiry_time)
+        .or_else(|| source.and_then(|s| s.expiry_time))?;
+    Some(RefreshCandidate {
+        uuid: credential.uuid,
+        provider_type: credential.provider_type.to_string(),
+        name: credential.name,
+        expiry,
+        last_refresh: cache.and_then(|c| c.last_refresh),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(uuid: &str, expires_in_secs: i64, now: DateTime<Utc>) -> RefreshCandidate {
+        RefreshCandidate {
+            uuid: uuid.to_string(),
+            provider_type: "kiro".to_string(),
+            name: None,
+            expiry: now + Duration::seconds(expires_in_secs),
+            last_refresh: None,
+        }
+    }
+
+    fn token_expiring_at(expiry: DateTime<Utc>) -> CachedTokenInfo {
+        CachedTokenInfo {
+            access_token: Some("token".to_string()),
+            expiry_time: Some(expiry),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_due_with_jitter() {
+        let config = TokenRefreshConfig::default();
+        let now = Utc::now();
+        let c = candidate("cred-1", 3600, now);
+        let due_at = refresh_due_at(&c, &config);
+        assert!(due_at <= c.expiry - Duration::seconds(600));
+        assert!(due_at >= c.expiry - Duration::seconds(720));
+        assert_eq!(due_at, refresh_due_at(&c, &config));
+
+        let scheduler = TokenRefreshScheduler::new(config);
+        let due = scheduler.due_candidates(
+            vec![
+                candidate("later", 3600, now),
+                candidate("soon", 300, now),
+                candidate("sooner", 60, now),
+            ],
+            &scheduler.config(),
+            now,
+        );
+        let ids: Vec<_> = due.iter().map(|c| c.uuid.as_str()).collect();
+        assert_eq!(ids, vec!["sooner", "soon"]);
+    }
+
+    #[test]
+    fn test_backoff_on_transient_failures() {
+        let scheduler = TokenRefreshScheduler::default();
+        let now = Utc::now();
+        let c = candidate("cred-1", 60, now);
+
+        for (attempt, expected) in [(1u32, 30i64), (2, 60), (3, 120)] {
+            let event = scheduler.record_result(&c, Err("connection reset".to_string()), now);
+            assert_eq!(event.outcome, TokenRefreshOutcome::Failed);
+            assert_eq!(event.consecutive_failures, attempt);
+            assert_eq!(event.retry_at, Some(now + Duration::seconds(expected)));
+        }
+        // 退避期间不再调度
+        assert!(scheduler
+            .due_candidates(vec![c.clone()], &scheduler.config(), now)
+            .is_empty());
+        assert_eq!(
+            scheduler
+                .due_candidates(
+                    vec![c.clone()],
+                    &scheduler.config(),
+                    now + Duration::seconds(120)
+                )
+                .len(),
+            1
+        );
+
+        // 其他请求已经刷新时不重置退避
+        let event = scheduler.record_result(&c, Ok(None), now);
+        assert_eq!(event.outcome, TokenRefreshOutcome::Skipped);
+        assert_eq!(event.consecutive_failures, 3);
+        assert!(scheduler
+            .due_candidates(vec![c.clone()], &scheduler.config(), now)
+            .is_empty());
+
+        let event = scheduler.record_result(
+            &c,
+            Ok(Some(token_expiring_at(now + Duration::hours(1)))),
+            now,
+        );
+        assert_eq!(event.outcome, TokenRefreshOutcome::Refreshed);
+        assert_eq!(event.consecutive_failures, 0);
+        assert_eq!(backoff(&scheduler.config(), 30), Duration::seconds(1800));
+    }
+
+    #[test]
+    fn test_reauth_required_only_after_repeated_rejection() {
+        let scheduler = TokenRefreshScheduler::default();
+        let now = Utc::now();
+        let mut c = candidate("cred-1", 60, now);
+
+        let rejected = || Err(r#"400 {"error": "invalid_grant"}"#.to_string());
+        assert_eq!(
+            scheduler.record_result(&c, rejected(), now).outcome,
+            TokenRefreshOutcome::Failed
+        );
+        // 中间的临时错误会重置被拒绝计数
+        scheduler.record_result(&c, Err("503 service unavailable".to_string()), now);
+        assert_eq!(
+            scheduler.record_result(&c, rejected(), now).outcome,
+            TokenRefreshOutcome::Failed
+        );
+        assert_eq!(
+            scheduler.record_result(&c, rejected(), now).outcome,
+            TokenRefreshOutcome::ReauthRequired
+        );
+
+        let later = now + Duration::hours(1);
+        assert!(scheduler
+            .due_candidates(vec![c.clone()], &scheduler.config(), later)
+            .is_empty());
+
+        // 重新授权后 Token 被刷新，恢复调度
+        c.last_refresh = Some(now + Duration::minutes(5));
+        assert_eq!(
+            scheduler
+                .due_candidates(vec![c], &scheduler.config(), later)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_candidate_falls_back_to_source_expiry() {
+        let now = Utc::now();
+        let credential = || {
+            ProviderCredential::new(
+                crate::models::provider_pool_model::PoolProviderType::Kiro,
+                CredentialData::KiroOAuth {
+                    creds_file_path: "/tmp/kiro.json".to_string(),
+                },
+            )
+        };
+        let source = token_expiring_at(now + Duration::minutes(5));
+
+        // 从未使用过的凭证没有缓存，按凭证文件中的过期时间调度
+        let c = to_candidate(credential(), None, Some(&source)).unwrap();
+        assert_eq!(c.expiry, now + Duration::minutes(5));
+        assert!(c.last_refresh.is_none());
+
+        // 缓存的过期时间优先
+        let cached = token_expiring_at(now + Duration::hours(1));
+        let c = to_candidate(credential(), Some(&cached), Some(&source)).unwrap();
+        assert_eq!(c.expiry, now + Duration::hours(1));
+
+        assert!(to_candidate(credential(), None, Some(&CachedTokenInfo::default())).is_none());
+    }
+
+    #[test]
+    fn test_refresh_token_rejection_markers() {
+        assert!(is_refresh_token_rejected("Refresh token 已失效或被撤销"));
+        assert!(is_refresh_token_rejected("401 Bad credentials"));
+        assert!(!is_refresh_token_rejected(
+            "error sending request: connection timeout"
+        ));
+        assert!(!is_refresh_token_rejected("502 Bad Gateway"));
+    }
+}
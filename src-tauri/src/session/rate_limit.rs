@@ -5,11 +5,19 @@
 //! - 指数退避策略
 //! - 账号级别和模型级别限流
 //! - 连续失败计数
+//! - 限流记录持久化到 SQLite，重启后恢复
 
+use crate::database::dao::cooldown_state::CooldownStore;
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+/// 账号级别限流记录的持久化作用域
+const ACCOUNT_SCOPE: &str = "rate_limit";
+/// 模型级别限流记录的持久化作用域
+const MODEL_SCOPE: &str = "rate_limit_model";
 
 /// 限流原因类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +34,22 @@ pub enum RateLimitReason {
     Unknown,
 }
 
+impl RateLimitReason {
+    /// 根据上游错误响应判断限流原因，不是限流错误时返回 None
+    pub fn classify(status_code: u16, body: &str) -> Option<Self> {
+        let body = body.to_lowercase();
+        let quota_exhausted = body.contains("quota") || body.contains("resource_exhausted");
+        match status_code {
+            429 if body.contains("capacity") => Some(Self::ModelCapacityExhausted),
+            429 if quota_exhausted => Some(Self::QuotaExhausted),
+            429 => Some(Self::RateLimitExceeded),
+            402 => Some(Self::QuotaExhausted),
+            529 => Some(Self::ModelCapacityExhausted),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for RateLimitReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -68,6 +92,8 @@ pub struct RateLimitTracker {
     base_backoff_seconds: u64,
     /// 最大退避时间（秒）
     max_backoff_seconds: u64,
+    /// 持久化存储（可选）
+    store: OnceLock<CooldownStore>,
 }
 
 impl Default for RateLimitTracker {
@@ -85,7 +111,34 @@ impl RateLimitTracker {
             failure_counts: DashMap::new(),
             base_backoff_seconds,
             max_backoff_seconds,
+            store: OnceLock::new(),
+        }
+    }
+
+    /// 挂载持久化存储，并恢复未过期的限流记录
+    ///
+    /// 之后的限流标记和清除都会写入存储。返回恢复的记录数
+    pub fn attach_store(&self, store: CooldownStore) -> usize {
+        let accounts: Vec<(String, RateLimitRecord)> = store.load(ACCOUNT_SCOPE);
+        let models: Vec<(String, RateLimitRecord)> = store.load(MODEL_SCOPE);
+        let restored = accounts.len() + models.len();
+
+        for (key, record) in accounts {
+            self.failure_counts
+                .entry(key.clone())
+                .or_insert_with(|| AtomicU32::new(0))
+                .store(record.consecutive_failures, Ordering::SeqCst);
+            self.account_limits.insert(key, record);
         }
+        for (key, record) in models {
+            self.model_limits.insert(key, record);
+        }
+        let _ = self.store.set(store);
+
+        if restored > 0 {
+            tracing::info!("[RATE_LIMIT] 已恢复 {} 条未过期的限流记录", restored);
+        }
+        restored
     }
 
     /// 标记账号限流
@@ -124,12 +177,17 @@ impl RateLimitTracker {
         };
 
         // 根据是否有模型信息决定存储位置
-        if let Some(m) = model {
+        let (scope, key) = if let Some(m) = model {
             let key = format!("{}:{}", account_id, m);
-            self.model_limits.insert(key, record.clone());
+            self.model_limits.insert(key.clone(), record.clone());
+            (MODEL_SCOPE, key)
         } else {
             self.account_limits
                 .insert(account_id.to_string(), record.clone());
+            (ACCOUNT_SCOPE, account_id.to_string())
+        };
+        if let Some(store) = self.store.get() {
+            store.save(scope, &key, &record, reset_at);
         }
 
         tracing::warn!(
@@ -192,7 +250,11 @@ impl RateLimitTracker {
 
     /// 清除账号的限流状态（成功请求后调用）
     pub fn clear_rate_limit(&self, account_id: &str) {
-        self.account_limits.remove(account_id);
+        if self.account_limits.remove(account_id).is_some() {
+            if let Some(store) = self.store.get() {
+                store.remove(ACCOUNT_SCOPE, account_id);
+            }
+        }
         // 重置连续失败计数
         if let Some(counter) = self.failure_counts.get(account_id) {
             counter.store(0, Ordering::SeqCst);
@@ -202,7 +264,11 @@ impl RateLimitTracker {
     /// 清除模型的限流状态
     pub fn clear_model_rate_limit(&self, account_id: &str, model: &str) {
         let key = format!("{}:{}", account_id, model);
-        self.model_limits.remove(&key);
+        if self.model_limits.remove(&key).is_some() {
+            if let Some(store) = self.store.get() {
+                store.remove(MODEL_SCOPE, &key);
+            }
+        }
     }
 
     /// 清理过期的限流记录
@@ -215,6 +281,10 @@ impl RateLimitTracker {
 
         // 清理模型级别限流
         self.model_limits.retain(|_, record| record.reset_at > now);
+
+        if let Some(store) = self.store.get() {
+            store.purge_expired();
+        }
     }
 
     /// 获取所有被限流的账号
@@ -295,9 +365,12 @@ pub fn parse_duration_string(s: &str) -> Option<Duration> {
 /// 从 429 响应中提取重试延迟
 ///
 /// 尝试从以下位置提取：
-/// 1. Retry-After 头
+/// 1. Retry-After 头（秒数、HTTP 日期或 Duration 字符串）
 /// 2. 响应体中的 retryDelay 字段
 /// 3. 响应体中的 quotaResetDelay 字段
+/// 4. 响应体中的 quotaResetTime / quotaResetTimeStamp 字段（RFC 3339 时间点）
+///
+/// 以上字段会在 `error.details[]`、`error.details[].metadata`、`error` 和顶层依次查找
 ///
 /// # 参数
 /// - `headers`: HTTP 响应头
@@ -316,6 +389,9 @@ pub fn extract_retry_delay(
             if let Ok(secs) = retry_after.parse::<i64>() {
                 return Some(Duration::seconds(secs));
             }
+            if let Ok(date) = DateTime::parse_from_rfc2822(retry_after) {
+                return Some((date.with_timezone(&Utc) - Utc::now()).max(Duration::zero()));
+            }
             // 尝试解析为 Duration 字符串
             if let Some(d) = parse_duration_string(retry_after) {
                 return d.into();
@@ -325,29 +401,21 @@ pub fn extract_retry_delay(
 
     // 2. 尝试从响应体提取
     if let Some(json) = body {
-        // 尝试 error.details[].retryDelay
-        if let Some(details) = json
-            .get("error")
+        let error = json.get("error");
+        let details = error
             .and_then(|e| e.get("details"))
             .and_then(|d| d.as_array())
-        {
-            for detail in details {
-                if let Some(retry_delay) = detail.get("retryDelay").and_then(|r| r.as_str()) {
-                    if let Some(d) = parse_duration_string(retry_delay) {
-                        return Some(d);
-                    }
-                }
-                if let Some(quota_reset) = detail.get("quotaResetDelay").and_then(|r| r.as_str()) {
-                    if let Some(d) = parse_duration_string(quota_reset) {
-                        return Some(d);
-                    }
-                }
-            }
-        }
+            .map(Vec::as_slice)
+            .unwrap_or_default();
 
-        // 尝试顶层 retryDelay
-        if let Some(retry_delay) = json.get("retryDelay").and_then(|r| r.as_str()) {
-            if let Some(d) = parse_duration_string(retry_delay) {
+        // error.details[] 及其 metadata，其次 error 对象和顶层
+        let candidates = details
+            .iter()
+            .flat_map(|detail| [Some(detail), detail.get("metadata")])
+            .chain([error, Some(json)])
+            .flatten();
+        for candidate in candidates {
+            if let Some(d) = retry_delay_from_object(candidate) {
                 return Some(d);
             }
         }
@@ -356,6 +424,29 @@ pub fn extract_retry_delay(
     None
 }
 
+/// 从单个 JSON 对象中读取重试延迟或配额重置时间
+fn retry_delay_from_object(value: &serde_json::Value) -> Option<Duration> {
+    for field in ["retryDelay", "quotaResetDelay"] {
+        if let Some(d) = value
+            .get(field)
+            .and_then(|v| v.as_str())
+            .and_then(parse_duration_string)
+        {
+            return Some(d);
+        }
+    }
+    for field in ["quotaResetTime", "quotaResetTimeStamp"] {
+        if let Some(reset_at) = value
+            .get(field)
+            .and_then(|v| v.as_str())
+            .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        {
+            return Some((reset_at.with_timezone(&Utc) - Utc::now()).max(Duration::zero()));
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -447,4 +538,81 @@ mod tests {
         tracker.clear_rate_limit("account1");
         assert!(!tracker.is_rate_limited("account1"));
     }
+
+    #[test]
+    fn test_extract_retry_delay_from_body() {
+        let body = serde_json::json!({
+            "error": {
+                "code": 429,
+                "details": [{
+                    "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                    "metadata": {"quotaResetDelay": "1h2m3s"}
+                }]
+            }
+        });
+        assert_eq!(
+            extract_retry_delay(None, Some(&body)),
+            Some(Duration::seconds(3723))
+        );
+
+        let reset_at = Utc::now() + Duration::hours(5);
+        let body = serde_json::json!({"error": {"quotaResetTime": reset_at.to_rfc3339()}});
+        let delay = extract_retry_delay(None, Some(&body)).unwrap();
+        assert!((delay - Duration::hours(5)).num_seconds().abs() <= 1);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "120".parse().unwrap());
+        assert_eq!(
+            extract_retry_delay(Some(&headers), Some(&body)),
+            Some(Duration::seconds(120))
+        );
+    }
+
+    #[test]
+    fn test_rate_limits_survive_restart() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::database::schema::create_tables(&conn).unwrap();
+        let db = std::sync::Arc::new(std::sync::Mutex::new(conn));
+
+        let tracker = RateLimitTracker::new(5, 300);
+        tracker.attach_store(CooldownStore::new(db.clone()));
+        tracker.mark_rate_limited(
+            "account1",
+            RateLimitReason::QuotaExhausted,
+            Some(Duration::hours(2)),
+            None,
+        );
+        tracker.mark_rate_limited(
+            "account2",
+            RateLimitReason::ModelCapacityExhausted,
+            Some(Duration::minutes(10)),
+            Some("claude-sonnet-4"),
+        );
+        tracker.mark_rate_limited("account3", RateLimitReason::Unknown, None, None);
+        tracker.clear_rate_limit("account3");
+
+        // 模拟重启
+        let restarted = RateLimitTracker::new(5, 300);
+        assert_eq!(restarted.attach_store(CooldownStore::new(db)), 2);
+        assert!(restarted.get_remaining_wait("account1") > 7100);
+        assert!(restarted.is_model_rate_limited("account2", "claude-sonnet-4"));
+        assert!(!restarted.is_rate_limited("account3"));
+    }
+
+    #[test]
+    fn test_classify_rate_limit() {
+        assert_eq!(
+            RateLimitReason::classify(429, r#"{"error":{"status":"RESOURCE_EXHAUSTED"}}"#),
+            Some(RateLimitReason::QuotaExhausted)
+        );
+        assert_eq!(
+            RateLimitReason::classify(429, "Too many requests"),
+            Some(RateLimitReason::RateLimitExceeded)
+        );
+        assert_eq!(
+            RateLimitReason::classify(429, "No capacity available for model"),
+            Some(RateLimitReason::ModelCapacityExhausted)
+        );
+        assert_eq!(RateLimitReason::classify(500, "quota"), None);
+    }
 }
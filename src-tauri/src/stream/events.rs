@@ -218,6 +218,8 @@ pub struct StreamContext {
     pub input_tokens: u32,
     /// 累计输出 tokens
     pub output_tokens: u32,
+    /// 已输出的文本（用于断流续传时作为 assistant 预填充）
+    pub emitted_text: String,
 }
 
 impl StreamContext {
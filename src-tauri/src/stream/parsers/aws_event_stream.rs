@@ -15,6 +15,7 @@
 //! - `{"contextUsagePercentage": 54.36}` - 上下文使用百分比
 
 use crate::stream::events::{ContentBlockType, StopReason, StreamContext, StreamEvent};
+use crate::stream::relay::strip_regenerated_whitespace;
 use std::collections::HashMap;
 
 /// 解析器状态
@@ -72,6 +73,8 @@ pub struct AwsEventStreamParser {
     in_text_block: bool,
     /// 当前文本块索引
     text_block_index: Option<u32>,
+    /// 续传时需要从续传文本开头去掉的空白（已输出但未放入预填充）
+    pending_whitespace: String,
 }
 
 impl Default for AwsEventStreamParser {
@@ -97,6 +100,7 @@ impl AwsEventStreamParser {
             message_stopped: false,
             in_text_block: false,
             text_block_index: None,
+            pending_whitespace: String::new(),
         }
     }
 
@@ -133,6 +137,34 @@ impl AwsEventStreamParser {
         self.message_stopped = false;
         self.in_text_block = false;
         self.text_block_index = None;
+        self.pending_whitespace.clear();
+    }
+
+    /// 已输出的文本内容
+    pub fn emitted_text(&self) -> &str {
+        &self.context.emitted_text
+    }
+
+    /// 是否可以在断流后续传
+    ///
+    /// 消息已结束或已输出工具调用时无法通过文本预填充续传
+    pub fn can_resume(&self) -> bool {
+        !self.message_stopped && self.tool_accumulators.is_empty()
+    }
+
+    /// 为续传的上游流准备解析器
+    ///
+    /// 丢弃未解析完的残留字节，保留消息和内容块状态，
+    /// 使续传的增量沿用同一消息 ID 和块索引。预填充不含已输出文本的末尾空白，
+    /// 续传流重新生成的这部分空白不再重复输出
+    pub fn resume(&mut self) {
+        self.buffer.clear();
+        self.parse_error_count = 0;
+        let emitted = &self.context.emitted_text;
+        self.pending_whitespace = emitted[emitted.trim_end().len()..].to_string();
+        if self.state != ParserState::Idle {
+            self.state = ParserState::Parsing;
+        }
     }
 
     /// 处理接收到的字节
@@ -403,7 +435,12 @@ impl AwsEventStreamParser {
         // 处理 content 事件
         if let Some(content) = value.get("content").and_then(|v| v.as_str()) {
             // 跳过 followupPrompt
-            if value.get("followupPrompt").is_none() {
+            let content = if value.get("followupPrompt").is_none() {
+                strip_regenerated_whitespace(&mut self.pending_whitespace, content)
+            } else {
+                ""
+            };
+            if !content.is_empty() {
                 // 如果还没有文本块，创建一个
                 if !self.in_text_block {
                     self.in_text_block = true;
@@ -415,6 +452,7 @@ impl AwsEventStreamParser {
                     });
                 }
 
+                self.context.emitted_text.push_str(content);
                 events.push(StreamEvent::TextDelta {
                     text: content.to_string(),
                 });
@@ -583,6 +621,38 @@ mod tests {
         assert!(matches!(&events[2], StreamEvent::TextDelta { text } if text == "Hello"));
     }
 
+    #[test]
+    fn test_resume_keeps_message_state() {
+        let mut parser = AwsEventStreamParser::with_model("test-model".to_string());
+        parser.process(br#"{"content":"Hello"}"#);
+        // 上游断流，残留半个事件
+        parser.process(br#"{"content":"Wor"#);
+        assert!(parser.can_resume());
+
+        parser.resume();
+        let events = parser.process(br#"{"content":" World"}"#);
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, StreamEvent::MessageStart { .. })));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, StreamEvent::ContentBlockStart { .. })));
+        assert!(matches!(&events[0], StreamEvent::TextDelta { text } if text == " World"));
+        assert_eq!(parser.emitted_text(), "Hello World");
+
+        // 已输出文本以空白结尾时，续传重新生成的空白不重复输出
+        parser.process(br#"{"content":" "}"#);
+        parser.resume();
+        assert!(parser.process(br#"{"content":" "}"#).is_empty());
+        let events = parser.process(br#"{"content":"again"}"#);
+        assert!(matches!(&events[0], StreamEvent::TextDelta { text } if text == "again"));
+        assert_eq!(parser.emitted_text(), "Hello World again");
+
+        // 已输出工具调用后不能续传
+        parser.process(br#"{"toolUseId":"tool_1","name":"read_file"}"#);
+        assert!(!parser.can_resume());
+    }
+
     #[test]
     fn test_parse_tool_use_event() {
         let mut parser = AwsEventStreamParser::new();
@@ -197,6 +197,30 @@ impl StreamPipeline {
         &self.config
     }
 
+    /// 是否可以在上游断流后续传
+    pub fn can_resume(&self) -> bool {
+        self.aws_parser
+            .as_ref()
+            .is_some_and(AwsEventStreamParser::can_resume)
+    }
+
+    /// 已向客户端输出的部分文本
+    pub fn partial_text(&self) -> &str {
+        self.aws_parser
+            .as_ref()
+            .map_or("", AwsEventStreamParser::emitted_text)
+    }
+
+    /// 切换到续传的上游流
+    ///
+    /// 与 `reset` 不同，保留解析器和生成器的消息状态，
+    /// 客户端看到的仍是同一条消息
+    pub fn resume(&mut self) {
+        if let Some(ref mut parser) = self.aws_parser {
+            parser.resume();
+        }
+    }
+
     /// 重置管道状态
     pub fn reset(&mut self) {
         if let Some(ref mut parser) = self.aws_parser {
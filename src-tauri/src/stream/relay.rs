@@ -0,0 +1,436 @@
+//! Anthropic SSE 透传中继
+//!
+//! 原样转发 Anthropic 格式的上游 SSE 流，同时跟踪已输出的文本和内容块状态，
+//! 使上游中途断开后可以换用其他凭证续传：续传流的 `message_start` 被丢弃，
+//! 内容块索引接续原流，客户端看到的仍是同一条消息。
+//!
+//! 上游不接受以空白结尾的 assistant 预填充，续传时预填充去掉了末尾空白，
+//! 续传流会重新生成这部分空白，转发前从续传文本开头去掉重复的部分。
+
+/// Anthropic SSE 透传中继
+#[derive(Debug, Default)]
+pub struct AnthropicSseRelay {
+    /// 未完成的事件缓冲
+    buffer: Vec<u8>,
+    /// 已向客户端输出的文本
+    emitted_text: String,
+    /// 下一个内容块索引（客户端视角）
+    next_index: u64,
+    /// 当前打开的内容块索引
+    open_block: Option<u64>,
+    /// 当前打开的内容块是否为文本块
+    open_block_is_text: bool,
+    /// 续传流的索引偏移
+    index_offset: u64,
+    /// 续传时是否丢弃下一个 message_start
+    drop_message_start: bool,
+    /// 续传时是否丢弃下一个 content_block_start（接续已打开的文本块）
+    drop_block_start: bool,
+    /// 是否输出过工具调用
+    has_tool_use: bool,
+    /// 续传时需要从续传文本开头去掉的空白（已输出但未放入预填充）
+    pending_whitespace: String,
+    /// 续传时需要先转发给客户端的事件（关闭中断时打开的非文本块）
+    queued_events: Vec<String>,
+    /// 是否收到 message_stop
+    finished: bool,
+    /// 暂存的上游 error 事件，由调用方决定续传还是转发
+    pending_error: Option<String>,
+}
+
+impl AnthropicSseRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理上游字节块，返回需要转发给客户端的 SSE 事件
+    pub fn process_chunk(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = std::mem::take(&mut self.queued_events);
+        while let Some(pos) = find_event_end(&self.buffer) {
+            let raw: Vec<u8> = self.buffer.drain(..pos.0).collect();
+            self.buffer.drain(..pos.1);
+            let raw = String::from_utf8_lossy(&raw).replace('\r', "");
+            if let Some(event) = self.process_event(&raw) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// 已向客户端输出的文本
+    pub fn partial_text(&self) -> &str {
+        &self.emitted_text
+    }
+
+    /// 是否收到了完整的 message_stop
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 取出上游发送的 error 事件
+    ///
+    /// 流中途的 error 事件（如 overloaded_error）不直接转发，
+    /// 调用方可以先尝试续传，续传失败再将其转发给客户端
+    pub fn take_error(&mut self) -> Option<String> {
+        self.pending_error.take()
+    }
+
+    /// 是否可以在上游断流后续传
+    ///
+    /// 已输出工具调用（无法作为文本预填充）或消息已结束时不可续传
+    pub fn can_resume(&self) -> bool {
+        !self.has_tool_use && !self.finished
+    }
+
+    /// 切换到续传的上游流
+    ///
+    /// 中断时打开的是文本块则接续该块；打开的是其他块（如 thinking）则先关闭它，
+    /// 续传内容从下一个索引开始
+    pub fn resume(&mut self) {
+        self.buffer.clear();
+        self.drop_message_start = true;
+        let prefill_len = self.emitted_text.trim_end().len();
+        self.pending_whitespace = self.emitted_text[prefill_len..].to_string();
+        match self.open_block {
+            Some(index) if self.open_block_is_text => {
+                self.drop_block_start = true;
+                self.index_offset = index;
+            }
+            open_block => {
+                if let Some(index) = open_block {
+                    self.queued_events.push(format!(
+                        "event: content_block_stop\ndata: {}\n\n",
+                        serde_json::json!({"type": "content_block_stop", "index": index})
+                    ));
+                    self.open_block = None;
+                }
+                self.drop_block_start = false;
+                self.index_offset = self.next_index;
+            }
+        }
+    }
+
+    fn process_event(&mut self, raw: &str) -> Option<String> {
+        let mut event_type: Option<&str> = None;
+        let mut data: Option<&str> = None;
+        for line in raw.lines() {
+            if let Some(value) = line.strip_prefix("event:") {
+                event_type = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                data = Some(value.trim());
+            }
+        }
+
+        let Some(mut json) = data.and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+        else {
+            // 注释行、ping 等非 JSON 事件原样转发
+            return Some(format!("{}\n\n", raw));
+        };
+        let kind = json
+            .get("type")
+            .and_then(|t| t.as_str())
+            .or(event_type)
+            .unwrap_or_default()
+            .to_string();
+
+        match kind.as_str() {
+            "message_start" if self.drop_message_start => {
+                self.drop_message_start = false;
+                return None;
+            }
+            "content_block_start" => {
+                if self.drop_block_start {
+                    self.drop_block_start = false;
+                    return None;
+                }
+                let index = self.remap_index(&mut json);
+                let block_type = json
+                    .pointer("/content_block/type")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default();
+                // thinking 等块不影响以文本预填充续传，只有工具调用不可续传
+                if block_type == "tool_use" {
+                    self.has_tool_use = true;
+                }
+                self.open_block_is_text = block_type == "text";
+                self.open_block = Some(index);
+                self.next_index = self.next_index.max(index + 1);
+            }
+            "content_block_delta" => {
+                self.remap_index(&mut json);
+                if let Some(text) = json.pointer("/delta/text").and_then(|t| t.as_str()) {
+                    if self.pending_whitespace.is_empty() {
+                        self.emitted_text.push_str(text);
+                    } else {
+                        let text = strip_regenerated_whitespace(&mut self.pending_whitespace, text)
+                            .to_string();
+                        if text.is_empty() {
+                            return None;
+                        }
+                        self.emitted_text.push_str(&text);
+                        json["delta"]["text"] = serde_json::json!(text);
+                        return Some(format!("event: {}\ndata: {}\n\n", kind, json));
+                    }
+                }
+            }
+            "content_block_stop" => {
+                self.remap_index(&mut json);
+                self.open_block = None;
+            }
+            "message_stop" => self.finished = true,
+            "error" => {
+                self.pending_error = Some(format!("{}\n\n", raw));
+                return None;
+            }
+            _ => {}
+        }
+
+        if self.index_offset == 0 {
+            return Some(format!("{}\n\n", raw));
+        }
+        Some(format!("event: {}\ndata: {}\n\n", kind, json))
+    }
+
+    /// 将续传流的内容块索引映射到客户端视角，返回映射后的索引
+    fn remap_index(&self, json: &mut serde_json::Value) -> u64 {
+        let index = json.get("index").and_then(|i| i.as_u64()).unwrap_or(0) + self.index_offset;
+        if self.index_offset != 0 {
+            json["index"] = serde_json::json!(index);
+        }
+        index
+    }
+}
+
+/// 去掉续传文本开头重新生成的空白
+///
+/// `pending` 为已输出但未放入预填充的末尾空白，逐字符与续传文本开头比对，
+/// 相同的部分去掉；遇到不同的字符说明续传没有重复这部分空白，停止比对
+pub(crate) fn strip_regenerated_whitespace<'a>(pending: &mut String, text: &'a str) -> &'a str {
+    let mut rest = text;
+    while let Some(expected) = pending.chars().next() {
+        match rest.chars().next() {
+            Some(c) if c == expected => {
+                rest = &rest[c.len_utf8()..];
+                pending.remove(0);
+            }
+            Some(_) => pending.clear(),
+            None => break,
+        }
+    }
+    rest
+}
+
+/// 查找事件结束位置，返回 (事件长度, 分隔符长度)
+fn find_event_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|p| (p, 2));
+    let crlf = buffer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|p| (p, 4));
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sse(event: &str, data: serde_json::Value) -> String {
+        format!("event: {}\ndata: {}\n\n", event, data)
+    }
+
+    fn text_delta(index: u64, text: &str) -> String {
+        sse(
+            "content_block_delta",
+            serde_json::json!({"type": "content_block_delta", "index": index, "delta": {"type": "text_delta", "text": text}}),
+        )
+    }
+
+    fn message_start() -> String {
+        sse(
+            "message_start",
+            serde_json::json!({"type": "message_start", "message": {"id": "msg_1"}}),
+        )
+    }
+
+    fn text_block_start(index: u64) -> String {
+        sse(
+            "content_block_start",
+            serde_json::json!({"type": "content_block_start", "index": index, "content_block": {"type": "text", "text": ""}}),
+        )
+    }
+
+    fn collect_text(events: &[String]) -> String {
+        events
+            .iter()
+            .filter_map(|e| e.lines().find_map(|l| l.strip_prefix("data: ")))
+            .filter_map(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+            .filter_map(|v| {
+                v.pointer("/delta/text")
+                    .and_then(|t| t.as_str())
+                    .map(String::from)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tracks_text_across_split_chunks() {
+        let mut relay = AnthropicSseRelay::new();
+        let payload = format!(
+            "{}{}{}",
+            message_start(),
+            text_block_start(0),
+            text_delta(0, "你好")
+        );
+        let bytes = payload.as_bytes();
+        // 在多字节字符中间切分
+        let split = payload.find("你").unwrap() + 1;
+
+        let mut events = relay.process_chunk(&bytes[..split]);
+        events.extend(relay.process_chunk(&bytes[split..]));
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(relay.partial_text(), "你好");
+        assert!(relay.can_resume());
+    }
+
+    #[test]
+    fn test_resume_continues_open_block() {
+        let mut relay = AnthropicSseRelay::new();
+        relay.process_chunk(
+            format!(
+                "{}{}{}",
+                message_start(),
+                text_block_start(0),
+                text_delta(0, "1 2 ")
+            )
+            .as_bytes(),
+        );
+
+        relay.resume();
+        let events = relay.process_chunk(
+            format!(
+                "{}{}{}{}{}",
+                message_start(),
+                text_block_start(0),
+                text_delta(0, " 3 4"),
+                sse(
+                    "content_block_stop",
+                    serde_json::json!({"type": "content_block_stop", "index": 0})
+                ),
+                sse("message_stop", serde_json::json!({"type": "message_stop"})),
+            )
+            .as_bytes(),
+        );
+
+        // 续传流的 message_start 和 content_block_start 被丢弃，
+        // 预填充不含末尾空格，续传重新生成的空格不重复输出
+        assert_eq!(events.len(), 3);
+        assert_eq!(collect_text(&events), "3 4");
+        assert_eq!(relay.partial_text(), "1 2 3 4");
+        assert!(relay.is_finished());
+        assert!(!relay.can_resume());
+    }
+
+    #[test]
+    fn test_resume_after_closed_block_offsets_index() {
+        let mut relay = AnthropicSseRelay::new();
+        relay.process_chunk(
+            format!(
+                "{}{}{}{}",
+                message_start(),
+                text_block_start(0),
+                text_delta(0, "a"),
+                sse(
+                    "content_block_stop",
+                    serde_json::json!({"type": "content_block_stop", "index": 0})
+                ),
+            )
+            .as_bytes(),
+        );
+
+        relay.resume();
+        let events =
+            relay.process_chunk(format!("{}{}", message_start(), text_block_start(0)).as_bytes());
+
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains("\"index\":1"));
+    }
+
+    #[test]
+    fn test_resume_skips_regenerated_whitespace_across_deltas() {
+        let mut pending = " \n".to_string();
+        assert_eq!(strip_regenerated_whitespace(&mut pending, " "), "");
+        assert_eq!(strip_regenerated_whitespace(&mut pending, "\nok"), "ok");
+        assert!(pending.is_empty());
+
+        // 续传没有重复空白时原样输出
+        let mut pending = " ".to_string();
+        assert_eq!(strip_regenerated_whitespace(&mut pending, "3"), "3");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_thinking_block_does_not_block_resume() {
+        let mut relay = AnthropicSseRelay::new();
+        relay.process_chunk(
+            format!(
+                "{}{}",
+                message_start(),
+                sse(
+                    "content_block_start",
+                    serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+                ),
+            )
+            .as_bytes(),
+        );
+        assert!(relay.can_resume());
+
+        relay.resume();
+        let events = relay.process_chunk(
+            format!(
+                "{}{}{}",
+                message_start(),
+                text_block_start(0),
+                text_delta(0, "hi")
+            )
+            .as_bytes(),
+        );
+
+        // 中断的 thinking 块先被关闭，续传内容从下一个索引开始
+        assert_eq!(events.len(), 3);
+        assert!(events[0].contains("content_block_stop"));
+        assert!(events[0].contains("\"index\":0"));
+        assert!(events[1].contains("\"index\":1"));
+        assert_eq!(collect_text(&events), "hi");
+    }
+
+    #[test]
+    fn test_tool_use_blocks_resume_and_error_is_held() {
+        let mut relay = AnthropicSseRelay::new();
+        relay.process_chunk(
+            sse(
+                "content_block_start",
+                serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "t", "name": "f", "input": {}}}),
+            )
+            .as_bytes(),
+        );
+        assert!(!relay.can_resume());
+
+        let mut relay = AnthropicSseRelay::new();
+        let events = relay.process_chunk(
+            sse(
+                "error",
+                serde_json::json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}),
+            )
+            .as_bytes(),
+        );
+        assert!(events.is_empty());
+        assert!(relay.take_error().unwrap().contains("overloaded_error"));
+        assert!(relay.can_resume());
+    }
+}
@@ -0,0 +1,546 @@
+//! Prometheus 指标
+//!
+//! `StatsAggregator` 和 `TokenTracker` 只保留滚动窗口内的数据，无法提供单调递增的计数器，
+//! 因此请求、Token 和故障转移在记录到聚合器的同时累加到 `MetricsRegistry`。
+//! 凭证池健康状态等瞬时值在抓取时由调用方通过 `GaugeSample` 传入。
+//!
+//! 输出格式为 Prometheus text exposition format 0.0.4。
+
+use crate::telemetry::types::RequestLog;
+use crate::ProviderType;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+/// Prometheus 文本格式的 Content-Type
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// 请求耗时直方图的桶（秒）
+const DURATION_BUCKETS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// 首字节时间直方图的桶（秒）
+const TTFB_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0];
+
+/// `model` 标签的不同取值上限，超出后归入 [`OTHER_MODEL_LABEL`]
+///
+/// 模型名来自客户端请求，不加限制时任意模型名都会产生新的时间序列
+const MAX_MODEL_LABELS: usize = 200;
+
+/// 超出上限的模型使用的标签值
+const OTHER_MODEL_LABEL: &str = "other";
+
+// ============================================================================
+// 指标类型
+// ============================================================================
+
+/// 带标签的计数器
+struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn inc_by(&self, label_values: Vec<String>, value: u64) {
+        debug_assert_eq!(label_values.len(), self.labels.len());
+        *self.values.lock().entry(label_values).or_default() += value;
+    }
+
+    #[cfg(test)]
+    fn get(&self, label_values: &[&str]) -> u64 {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        self.values.lock().get(&key).copied().unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "counter");
+        for (label_values, value) in self.values.lock().iter() {
+            let _ = writeln!(
+                out,
+                "{}{} {}",
+                self.name,
+                format_labels(self.labels, label_values, None),
+                value
+            );
+        }
+    }
+}
+
+/// 单个标签组合的直方图数据
+#[derive(Default, Clone)]
+struct HistogramData {
+    /// 每个桶的计数（非累积）
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// 带标签的直方图
+struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    bounds: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, HistogramData>>,
+}
+
+impl HistogramVec {
+    fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+        bounds: &'static [f64],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            bounds,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn observe(&self, label_values: Vec<String>, value: f64) {
+        debug_assert_eq!(label_values.len(), self.labels.len());
+        let mut values = self.values.lock();
+        let data = values.entry(label_values).or_insert_with(|| HistogramData {
+            buckets: vec![0; self.bounds.len()],
+            ..Default::default()
+        });
+        if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+            data.buckets[index] += 1;
+        }
+        data.sum += value;
+        data.count += 1;
+    }
+
+    #[cfg(test)]
+    fn count(&self, label_values: &[&str]) -> u64 {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        self.values.lock().get(&key).map(|d| d.count).unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String) {
+        write_header(out, self.name, self.help, "histogram");
+        for (label_values, data) in self.values.lock().iter() {
+            let mut cumulative = 0;
+            for (bound, count) in self.bounds.iter().zip(&data.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    self.name,
+                    format_labels(self.labels, label_values, Some(&bound.to_string())),
+                    cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                self.name,
+                format_labels(self.labels, label_values, Some("+Inf")),
+                data.count
+            );
+            let labels = format_labels(self.labels, label_values, None);
+            let _ = writeln!(out, "{}_sum{} {}", self.name, labels, data.sum);
+            let _ = writeln!(out, "{}_count{} {}", self.name, labels, data.count);
+        }
+    }
+}
+
+/// 抓取时计算的瞬时值
+#[derive(Debug, Clone)]
+pub struct GaugeSample {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl GaugeSample {
+    pub fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: Vec<(&'static str, String)>,
+        value: f64,
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            value,
+        }
+    }
+}
+
+// ============================================================================
+// 指标注册表
+// ============================================================================
+
+/// Prometheus 指标注册表
+///
+/// 所有计数器单调递增，进程重启后归零（由 Prometheus 的 `rate()` 处理）。
+pub struct MetricsRegistry {
+    requests: CounterVec,
+    request_duration: HistogramVec,
+    ttfb: HistogramVec,
+    tokens: CounterVec,
+    retries: CounterVec,
+    failover_switches: CounterVec,
+    response_cache: CounterVec,
+    /// 已出现过的 `model` 标签值
+    model_labels: Mutex<HashSet<String>>,
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsRegistry {
+    /// 创建新的指标注册表
+    pub fn new() -> Self {
+        Self {
+            requests: CounterVec::new(
+                "proxycast_requests_total",
+                "Total number of proxied requests.",
+                &["provider", "model", "credential", "status"],
+            ),
+            request_duration: HistogramVec::new(
+                "proxycast_request_duration_seconds",
+                "End-to-end request latency in seconds.",
+                &["provider", "model"],
+                DURATION_BUCKETS,
+            ),
+            ttfb: HistogramVec::new(
+                "proxycast_ttfb_seconds",
+                "Time to first byte from the upstream provider in seconds.",
+                &["provider", "model"],
+                TTFB_BUCKETS,
+            ),
+            tokens: CounterVec::new(
+                "proxycast_tokens_total",
+                "Total number of tokens by direction.",
+                &["provider", "model", "credential", "direction"],
+            ),
+            retries: CounterVec::new(
+                "proxycast_retries_total",
+                "Total number of upstream retries.",
+                &["provider", "model"],
+            ),
+            failover_switches: CounterVec::new(
+                "proxycast_failover_switches_total",
+                "Total number of failover switches between providers or credential sources.",
+                &["from", "to", "reason"],
+            ),
+            response_cache: CounterVec::new(
+                "proxycast_response_cache_total",
+                "Total number of response cache lookups by result.",
+                &["endpoint", "result"],
+            ),
+            model_labels: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 规范化 `model` 标签：去掉首尾空白并转为小写，不同取值超过上限后归入 `other`
+    fn model_label(&self, model: &str) -> String {
+        let model = model.trim().to_lowercase();
+        if model.is_empty() {
+            return "unknown".to_string();
+        }
+        let mut labels = self.model_labels.lock();
+        if labels.contains(&model) {
+            return model;
+        }
+        if labels.len() >= MAX_MODEL_LABELS {
+            return OTHER_MODEL_LABEL.to_string();
+        }
+        labels.insert(model.clone());
+        model
+    }
+
+    /// 记录一次完成的请求
+    pub fn observe_request(&self, log: &RequestLog) {
+        let provider = log.provider.to_string();
+        let model = self.model_label(&log.model);
+        let credential = log.credential_id.clone().unwrap_or_default();
+
+        self.requests.inc_by(
+            vec![
+                provider.clone(),
+                model.clone(),
+                credential,
+                log.status.to_string(),
+            ],
+            1,
+        );
+        self.request_duration.observe(
+            vec![provider.clone(), model.clone()],
+            log.duration_ms as f64 / 1000.0,
+        );
+        if log.retry_count > 0 {
+            self.retries
+                .inc_by(vec![provider, model], log.retry_count as u64);
+        }
+    }
+
+    /// 记录首字节时间
+    pub fn observe_ttfb(&self, provider: ProviderType, model: &str, ttfb_ms: u64) {
+        self.ttfb.observe(
+            vec![provider.to_string(), self.model_label(model)],
+            ttfb_ms as f64 / 1000.0,
+        );
+    }
+
+    /// 记录 Token 使用量
+    pub fn observe_tokens(
+        &self,
+        provider: ProviderType,
+        model: &str,
+        credential: Option<&str>,
+        input_tokens: u32,
+        output_tokens: u32,
+    ) {
+        let model = self.model_label(model);
+        let labels = |direction: &str| {
+            vec![
+                provider.to_string(),
+                model.clone(),
+                credential.unwrap_or_default().to_string(),
+                direction.to_string(),
+            ]
+        };
+        self.tokens.inc_by(labels("input"), input_tokens as u64);
+        self.tokens.inc_by(labels("output"), output_tokens as u64);
+    }
+
+    /// 记录一次故障转移
+    pub fn record_failover(&self, from: &str, to: &str, reason: &str) {
+        self.failover_switches.inc_by(
+            vec![from.to_string(), to.to_string(), reason.to_string()],
+            1,
+        );
+    }
+
+    /// 记录一次响应缓存查找（`result` 为 `hit` 或 `miss`）
+    pub fn record_cache_lookup(&self, endpoint: &str, result: &str) {
+        self.response_cache
+            .inc_by(vec![endpoint.to_string(), result.to_string()], 1);
+    }
+
+    /// 渲染所有指标
+    ///
+    /// # Arguments
+    /// * `gauges` - 抓取时计算的瞬时值（凭证池健康状态等）
+    pub fn render(&self, gauges: &[GaugeSample]) -> String {
+        let mut out = String::new();
+        self.requests.render(&mut out);
+        self.request_duration.render(&mut out);
+        self.ttfb.render(&mut out);
+        self.tokens.render(&mut out);
+        self.retries.render(&mut out);
+        self.failover_switches.render(&mut out);
+        self.response_cache.render(&mut out);
+        render_gauges(&mut out, gauges);
+        out
+    }
+}
+
+// ============================================================================
+// 格式化
+// ============================================================================
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// 渲染瞬时值，同名指标只输出一次 HELP/TYPE
+fn render_gauges(out: &mut String, gauges: &[GaugeSample]) {
+    let mut grouped: BTreeMap<&str, Vec<&GaugeSample>> = BTreeMap::new();
+    for gauge in gauges {
+        grouped.entry(gauge.name).or_default().push(gauge);
+    }
+
+    for (name, samples) in grouped {
+        write_header(out, name, samples[0].help, "gauge");
+        for sample in samples {
+            let names: Vec<&str> = sample.labels.iter().map(|(k, _)| *k).collect();
+            let values: Vec<String> = sample.labels.iter().map(|(_, v)| v.clone()).collect();
+            let _ = writeln!(
+                out,
+                "{}{} {}",
+                name,
+                format_labels(&names, &values, None),
+                sample.value
+            );
+        }
+    }
+}
+
+/// 格式化标签，`le` 用于直方图的桶
+fn format_labels(names: &[&str], values: &[String], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// 转义标签值中的反斜杠、双引号和换行
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log(status_success: bool, duration_ms: u64) -> RequestLog {
+        let mut log = RequestLog::new(
+            "req-1".to_string(),
+            ProviderType::Kiro,
+            "claude-sonnet-4".to_string(),
+            false,
+        );
+        if status_success {
+            log.mark_success(duration_ms, 200);
+        } else {
+            log.mark_failed(duration_ms, Some(500), "boom".to_string());
+        }
+        log.set_credential_id("cred-1".to_string());
+        log
+    }
+
+    #[test]
+    fn test_request_counters_and_histogram() {
+        let registry = MetricsRegistry::new();
+        registry.observe_request(&sample_log(true, 300));
+        registry.observe_request(&sample_log(true, 3000));
+        registry.observe_request(&sample_log(false, 50));
+
+        assert_eq!(
+            registry
+                .requests
+                .get(&["kiro", "claude-sonnet-4", "cred-1", "success"]),
+            2
+        );
+        assert_eq!(
+            registry
+                .requests
+                .get(&["kiro", "claude-sonnet-4", "cred-1", "failed"]),
+            1
+        );
+        assert_eq!(
+            registry
+                .request_duration
+                .count(&["kiro", "claude-sonnet-4"]),
+            3
+        );
+
+        let text = registry.render(&[]);
+        assert!(text.contains("# TYPE proxycast_request_duration_seconds histogram"));
+        // 0.05s 和 0.3s 落入 0.5 桶（累积），3s 只在 5.0 及以上的桶
+        assert!(text.contains(
+            "proxycast_request_duration_seconds_bucket{provider=\"kiro\",model=\"claude-sonnet-4\",le=\"0.5\"} 2"
+        ));
+        assert!(text.contains(
+            "proxycast_request_duration_seconds_bucket{provider=\"kiro\",model=\"claude-sonnet-4\",le=\"+Inf\"} 3"
+        ));
+        assert!(text.contains(
+            "proxycast_request_duration_seconds_count{provider=\"kiro\",model=\"claude-sonnet-4\"} 3"
+        ));
+    }
+
+    #[test]
+    fn test_tokens_and_failover() {
+        let registry = MetricsRegistry::new();
+        registry.observe_tokens(ProviderType::OpenAI, "gpt-4o", Some("c1"), 100, 20);
+        registry.observe_tokens(ProviderType::OpenAI, "gpt-4o", Some("c1"), 50, 5);
+        registry.record_failover("kiro", "api_key_provider", "no_pool_credential");
+        registry.record_cache_lookup("/v1/messages", "hit");
+
+        assert_eq!(
+            registry.tokens.get(&["openai", "gpt-4o", "c1", "input"]),
+            150
+        );
+        assert_eq!(
+            registry.tokens.get(&["openai", "gpt-4o", "c1", "output"]),
+            25
+        );
+
+        let text = registry.render(&[]);
+        assert!(text.contains(
+            "proxycast_failover_switches_total{from=\"kiro\",to=\"api_key_provider\",reason=\"no_pool_credential\"} 1"
+        ));
+        assert!(text.contains(
+            "proxycast_response_cache_total{endpoint=\"/v1/messages\",result=\"hit\"} 1"
+        ));
+    }
+
+    #[test]
+    fn test_model_label_is_normalized_and_bounded() {
+        let registry = MetricsRegistry::new();
+        registry.observe_ttfb(ProviderType::OpenAI, " GPT-4o ", 100);
+        registry.observe_ttfb(ProviderType::OpenAI, "gpt-4o", 100);
+        assert_eq!(registry.ttfb.count(&["openai", "gpt-4o"]), 2);
+
+        for i in 0..MAX_MODEL_LABELS + 5 {
+            registry.observe_ttfb(ProviderType::OpenAI, &format!("model-{}", i), 100);
+        }
+        // 已出现的模型保留原标签，超出上限的新模型归入 other
+        assert_eq!(registry.ttfb.count(&["openai", "gpt-4o"]), 2);
+        assert_eq!(registry.ttfb.count(&["openai", OTHER_MODEL_LABEL]), 6);
+    }
+
+    #[test]
+    fn test_gauges_and_escaping() {
+        let registry = MetricsRegistry::new();
+        let gauges = vec![
+            GaugeSample::new(
+                "proxycast_credential_healthy",
+                "Whether the credential is healthy.",
+                vec![("credential", "a\"b".to_string())],
+                1.0,
+            ),
+            GaugeSample::new(
+                "proxycast_credential_healthy",
+                "Whether the credential is healthy.",
+                vec![("credential", "c".to_string())],
+                0.0,
+            ),
+        ];
+        let text = registry.render(&gauges);
+        assert_eq!(
+            text.matches("# TYPE proxycast_credential_healthy gauge")
+                .count(),
+            1
+        );
+        assert!(text.contains("proxycast_credential_healthy{credential=\"a\\\"b\"} 1"));
+        assert!(text.contains("proxycast_credential_healthy{credential=\"c\"} 0"));
+    }
+}
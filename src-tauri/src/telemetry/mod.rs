@@ -3,12 +3,24 @@
 //! 提供请求日志记录、统计聚合和 Token 追踪功能
 
 mod logger;
+mod metrics;
+mod otel;
 mod stats;
+mod token_counter;
 mod tokens;
 mod types;
 
 pub use logger::{LogRotationConfig, LoggerError, RequestLogger};
+pub use metrics::{GaugeSample, MetricsRegistry, PROMETHEUS_CONTENT_TYPE};
+pub use otel::{
+    current_span, current_span_child, in_span, AttributeValue, RequestTrace, SpanGuard, SpanKind,
+    SpanParent, Tracer,
+};
 pub use stats::StatsAggregator;
+pub use token_counter::{
+    estimate_image_tokens, image_dimensions, AnthropicTokenCounter, ModelFamily,
+    TokenCountBreakdown, TokenCountResult,
+};
 pub use tokens::{
     ModelTokenStats, PeriodTokenStats, ProviderTokenStats, TokenSource, TokenStatsSummary,
     TokenTracker, TokenUsageRecord,
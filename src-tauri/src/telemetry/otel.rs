@@ -0,0 +1,872 @@
+//! OpenTelemetry 链路追踪
+//!
+//! 为每个代理请求构建一棵 span 树（认证、注入、路由与凭证选择、每次上游尝试及重试/故障转移、流式传输），
+//! 并以 OTLP/HTTP JSON 编码批量导出到采集器，便于与 Agent 侧的 trace 关联。
+//!
+//! 这里只实现代理需要的最小子集，不引入 opentelemetry SDK：
+//! - 根 span 由 `RequestTrace` 持有，最后一个引用释放时结束并整体导出，
+//!   因此流式响应的 span 会让根 span 覆盖到流结束为止
+//! - 子 span 由 `SpanGuard` 持有，drop 时自动结束；未启用或未采样时为空操作
+//! - 上游传入的 W3C `traceparent` 会被沿用（trace id、父 span、采样标记）
+//! - `in_span` 将 span 设为当前任务的当前 span，provider 调用内部的重试、
+//!   故障转移通过 `current_span_child` 挂到调用方的 span 下
+
+use crate::config::OtelConfig;
+use parking_lot::{Mutex, RwLock};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// 导出队列容量相对批量大小的倍数
+const QUEUE_CAPACITY_FACTOR: usize = 8;
+
+/// 导出请求超时
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// Span 数据
+// ============================================================================
+
+/// span 属性值
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    StringArray(Vec<String>),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<u32> for AttributeValue {
+    fn from(v: u32) -> Self {
+        Self::Int(v as i64)
+    }
+}
+
+impl From<u64> for AttributeValue {
+    fn from(v: u64) -> Self {
+        Self::Int(v as i64)
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(v: f64) -> Self {
+        Self::Double(v)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<Vec<String>> for AttributeValue {
+    fn from(v: Vec<String>) -> Self {
+        Self::StringArray(v)
+    }
+}
+
+/// span 类型（对应 OTLP `SpanKind`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+impl SpanKind {
+    fn as_otlp(self) -> u8 {
+        match self {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        }
+    }
+}
+
+/// span 状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpanStatus {
+    Unset,
+    Ok,
+    Error(String),
+}
+
+/// 已结束（或进行中）的 span
+#[derive(Debug, Clone)]
+pub struct SpanData {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start_unix_nanos: u64,
+    pub end_unix_nanos: u64,
+    pub attributes: Vec<(String, AttributeValue)>,
+    pub status: SpanStatus,
+}
+
+impl SpanData {
+    fn start(
+        trace_id: [u8; 16],
+        parent_span_id: Option<[u8; 8]>,
+        name: &str,
+        kind: SpanKind,
+    ) -> Self {
+        Self {
+            trace_id,
+            span_id: new_span_id(),
+            parent_span_id,
+            name: name.to_string(),
+            kind,
+            start_unix_nanos: now_unix_nanos(),
+            end_unix_nanos: 0,
+            attributes: Vec::new(),
+            status: SpanStatus::Unset,
+        }
+    }
+
+    fn set_attribute(&mut self, key: &str, value: AttributeValue) {
+        if let Some(slot) = self.attributes.iter_mut().find(|(k, _)| k == key) {
+            slot.1 = value;
+        } else {
+            self.attributes.push((key.to_string(), value));
+        }
+    }
+
+    fn finish(&mut self) {
+        self.end_unix_nanos = now_unix_nanos().max(self.start_unix_nanos);
+    }
+}
+
+fn now_unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn new_span_id() -> [u8; 8] {
+    loop {
+        let id: [u8; 8] = rand::random();
+        if id != [0u8; 8] {
+            return id;
+        }
+    }
+}
+
+fn new_trace_id() -> [u8; 16] {
+    loop {
+        let id: [u8; 16] = rand::random();
+        if id != [0u8; 16] {
+            return id;
+        }
+    }
+}
+
+/// 解析 W3C `traceparent`（`00-<trace_id>-<parent_id>-<flags>`）
+///
+/// 返回 (trace_id, parent_span_id, sampled)，格式非法或 id 全零时返回 None
+pub fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8], bool)> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" {
+        return None;
+    }
+    let mut trace_id = [0u8; 16];
+    let mut parent_id = [0u8; 8];
+    hex::decode_to_slice(parts[1], &mut trace_id).ok()?;
+    hex::decode_to_slice(parts[2], &mut parent_id).ok()?;
+    let flags = u8::from_str_radix(parts[3], 16).ok()?;
+    if trace_id == [0u8; 16] || parent_id == [0u8; 8] {
+        return None;
+    }
+    Some((trace_id, parent_id, flags & 0x01 == 0x01))
+}
+
+// ============================================================================
+// Trace / Span 句柄
+// ============================================================================
+
+struct TraceInner {
+    root: Mutex<SpanData>,
+    finished: Mutex<Vec<SpanData>>,
+    exporter: Arc<OtlpExporter>,
+}
+
+impl Drop for TraceInner {
+    fn drop(&mut self) {
+        let mut root = self.root.lock().clone();
+        root.finish();
+        let mut spans = std::mem::take(&mut *self.finished.lock());
+        spans.push(root);
+        self.exporter.export(spans);
+    }
+}
+
+/// 单个请求的 trace
+///
+/// 可廉价克隆；最后一个克隆（包括进行中的 `SpanGuard`）释放时结束根 span 并导出整棵树
+#[derive(Clone)]
+pub struct RequestTrace {
+    inner: Arc<TraceInner>,
+}
+
+impl std::fmt::Debug for RequestTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestTrace")
+            .field("trace_id", &self.trace_id())
+            .finish()
+    }
+}
+
+impl RequestTrace {
+    /// trace id（32 位十六进制）
+    pub fn trace_id(&self) -> String {
+        hex::encode(self.inner.root.lock().trace_id)
+    }
+
+    /// 在根 span 上设置属性
+    pub fn set_attribute(&self, key: &str, value: impl Into<AttributeValue>) {
+        self.inner.root.lock().set_attribute(key, value.into());
+    }
+
+    /// 将根 span 标记为成功
+    pub fn set_ok(&self) {
+        self.inner.root.lock().status = SpanStatus::Ok;
+    }
+
+    /// 将根 span 标记为失败
+    pub fn set_error(&self, message: &str) {
+        self.inner.root.lock().status = SpanStatus::Error(message.to_string());
+    }
+
+    /// 创建根 span 的子 span
+    pub fn span(&self, name: &str) -> SpanGuard {
+        let (trace_id, parent) = {
+            let root = self.inner.root.lock();
+            (root.trace_id, root.span_id)
+        };
+        SpanGuard {
+            active: Some(ActiveSpan {
+                trace: self.inner.clone(),
+                data: SpanData::start(trace_id, Some(parent), name, SpanKind::Internal),
+            }),
+        }
+    }
+}
+
+struct ActiveSpan {
+    trace: Arc<TraceInner>,
+    data: SpanData,
+}
+
+/// 进行中的子 span
+///
+/// drop 时自动结束；未启用追踪时所有操作均为空操作
+#[derive(Default)]
+pub struct SpanGuard {
+    active: Option<ActiveSpan>,
+}
+
+impl SpanGuard {
+    /// 是否正在记录
+    pub fn is_recording(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// 创建子 span
+    pub fn child(&self, name: &str) -> SpanGuard {
+        self.parent().map(|p| p.child(name)).unwrap_or_default()
+    }
+
+    /// 当前 span 的父句柄，用于在 span 之外（如其他任务）创建其子 span
+    pub fn parent(&self) -> Option<SpanParent> {
+        self.active.as_ref().map(|a| SpanParent {
+            trace: a.trace.clone(),
+            trace_id: a.data.trace_id,
+            span_id: a.data.span_id,
+        })
+    }
+
+    /// 设置 span 类型
+    pub fn set_kind(&mut self, kind: SpanKind) {
+        if let Some(a) = self.active.as_mut() {
+            a.data.kind = kind;
+        }
+    }
+
+    /// 设置属性
+    pub fn set_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        if let Some(a) = self.active.as_mut() {
+            a.data.set_attribute(key, value.into());
+        }
+    }
+
+    /// 标记为成功
+    pub fn set_ok(&mut self) {
+        if let Some(a) = self.active.as_mut() {
+            a.data.status = SpanStatus::Ok;
+        }
+    }
+
+    /// 标记为失败
+    pub fn set_error(&mut self, message: &str) {
+        if let Some(a) = self.active.as_mut() {
+            a.data.status = SpanStatus::Error(message.to_string());
+        }
+    }
+
+    /// 立即结束 span
+    pub fn end(self) {}
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        if let Some(mut a) = self.active.take() {
+            a.data.finish();
+            a.trace.finished.lock().push(a.data);
+        }
+    }
+}
+
+/// span 的父句柄
+///
+/// 可克隆并跨任务传递，持有期间 trace 不会导出
+#[derive(Clone)]
+pub struct SpanParent {
+    trace: Arc<TraceInner>,
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+impl SpanParent {
+    /// 创建子 span
+    pub fn child(&self, name: &str) -> SpanGuard {
+        SpanGuard {
+            active: Some(ActiveSpan {
+                trace: self.trace.clone(),
+                data: SpanData::start(self.trace_id, Some(self.span_id), name, SpanKind::Internal),
+            }),
+        }
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_SPAN: Option<SpanParent>;
+}
+
+/// 以 `span` 作为当前 span 执行 `fut`
+///
+/// `fut` 内部（如 provider 调用、重试、故障转移）可通过 `current_span_child`
+/// 挂到该 span 下，无需逐层传递 span 参数
+pub async fn in_span<F: std::future::Future>(span: &SpanGuard, fut: F) -> F::Output {
+    CURRENT_SPAN.scope(span.parent(), fut).await
+}
+
+/// 当前 span 的父句柄；不在 `in_span` 内或未启用追踪时返回 None
+pub fn current_span() -> Option<SpanParent> {
+    CURRENT_SPAN.try_with(|p| p.clone()).ok().flatten()
+}
+
+/// 创建当前 span 的子 span；没有当前 span 时为空操作
+pub fn current_span_child(name: &str) -> SpanGuard {
+    current_span().map(|p| p.child(name)).unwrap_or_default()
+}
+
+// ============================================================================
+// Tracer
+// ============================================================================
+
+struct TracerState {
+    config: OtelConfig,
+    exporter: Arc<OtlpExporter>,
+}
+
+/// 请求追踪器
+///
+/// 由 `RequestProcessor` 持有，配置热重载时通过 `configure` 替换导出器
+#[derive(Default)]
+pub struct Tracer {
+    state: RwLock<Option<TracerState>>,
+}
+
+impl Tracer {
+    /// 创建未启用的追踪器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 应用配置
+    ///
+    /// 配置未变化时保留现有导出器；禁用时丢弃导出器（已排队的 span 仍会发送完毕）
+    pub fn configure(&self, config: &OtelConfig) {
+        let mut state = self.state.write();
+        if !config.enabled {
+            if state.take().is_some() {
+                tracing::info!("[OTEL] 链路追踪已禁用");
+            }
+            return;
+        }
+        if state.as_ref().is_some_and(|s| &s.config == config) {
+            return;
+        }
+        match OtlpExporter::spawn(config) {
+            Ok(exporter) => {
+                tracing::info!(
+                    "[OTEL] 链路追踪已启用: endpoint={} sample_ratio={}",
+                    config.endpoint,
+                    config.sample_ratio
+                );
+                *state = Some(TracerState {
+                    config: config.clone(),
+                    exporter: Arc::new(exporter),
+                });
+            }
+            Err(e) => {
+                tracing::warn!("[OTEL] 无法启动导出器: {}", e);
+                *state = None;
+            }
+        }
+    }
+
+    /// 开始一个请求 trace
+    ///
+    /// 未启用或未被采样时返回 None
+    pub fn start_trace(&self, name: &str, traceparent: Option<&str>) -> Option<RequestTrace> {
+        let state = self.state.read();
+        let state = state.as_ref()?;
+
+        let (trace_id, parent, sampled) = match traceparent.and_then(parse_traceparent) {
+            Some((trace_id, parent, sampled)) => (trace_id, Some(parent), sampled),
+            None => {
+                let ratio = state.config.sample_ratio;
+                let sampled = ratio >= 1.0 || (ratio > 0.0 && rand::random::<f64>() < ratio);
+                (new_trace_id(), None, sampled)
+            }
+        };
+        if !sampled {
+            return None;
+        }
+
+        Some(RequestTrace {
+            inner: Arc::new(TraceInner {
+                root: Mutex::new(SpanData::start(trace_id, parent, name, SpanKind::Server)),
+                finished: Mutex::new(Vec::new()),
+                exporter: state.exporter.clone(),
+            }),
+        })
+    }
+}
+
+// ============================================================================
+// OTLP/HTTP 导出
+// ============================================================================
+
+/// OTLP/HTTP JSON 导出器
+///
+/// span 通过有界队列交给后台任务，按批量大小或时间间隔发送；队列满时丢弃并计数，不阻塞请求路径
+pub struct OtlpExporter {
+    tx: mpsc::Sender<SpanData>,
+    dropped: AtomicU64,
+}
+
+impl OtlpExporter {
+    /// 启动后台导出任务（需要在 tokio 运行时中调用）
+    pub fn spawn(config: &OtelConfig) -> Result<Self, String> {
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| "当前不在 tokio 运行时中".to_string())?;
+        let client = reqwest::Client::builder()
+            .timeout(EXPORT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+
+        let max_batch_size = config.max_batch_size.max(1);
+        let (tx, rx) = mpsc::channel(max_batch_size * QUEUE_CAPACITY_FACTOR);
+        handle.spawn(run_export_loop(
+            rx,
+            client,
+            config.clone(),
+            max_batch_size,
+            Duration::from_millis(config.flush_interval_ms.max(1)),
+        ));
+
+        Ok(Self {
+            tx,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// 提交 span
+    pub fn export(&self, spans: Vec<SpanData>) {
+        for span in spans {
+            if self.tx.try_send(span).is_err() {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped.is_power_of_two() {
+                    tracing::warn!("[OTEL] 导出队列已满，累计丢弃 {} 个 span", dropped);
+                }
+            }
+        }
+    }
+}
+
+async fn run_export_loop(
+    mut rx: mpsc::Receiver<SpanData>,
+    client: reqwest::Client,
+    config: OtelConfig,
+    max_batch_size: usize,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::with_capacity(max_batch_size);
+    let mut ticker = tokio::time::interval(flush_interval);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            span = rx.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    if batch.len() >= max_batch_size {
+                        send_batch(&client, &config, std::mem::take(&mut batch)).await;
+                    }
+                }
+                None => {
+                    send_batch(&client, &config, std::mem::take(&mut batch)).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => {
+                send_batch(&client, &config, std::mem::take(&mut batch)).await;
+            }
+        }
+    }
+}
+
+async fn send_batch(client: &reqwest::Client, config: &OtelConfig, spans: Vec<SpanData>) {
+    if spans.is_empty() {
+        return;
+    }
+    let body = encode_export_request(&config.service_name, &spans);
+    let mut request = client.post(&config.endpoint).json(&body);
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    match request.send().await {
+        Ok(resp) if resp.status().is_success() => {
+            tracing::debug!("[OTEL] 已导出 {} 个 span", spans.len());
+        }
+        Ok(resp) => {
+            tracing::warn!(
+                "[OTEL] 采集器拒绝了 {} 个 span: HTTP {}",
+                spans.len(),
+                resp.status()
+            );
+        }
+        Err(e) => {
+            tracing::warn!("[OTEL] 导出 {} 个 span 失败: {}", spans.len(), e);
+        }
+    }
+}
+
+/// 编码为 OTLP `ExportTraceServiceRequest`（JSON）
+pub fn encode_export_request(service_name: &str, spans: &[SpanData]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [encode_attribute("service.name", &AttributeValue::from(service_name))]
+            },
+            "scopeSpans": [{
+                "scope": {
+                    "name": "proxycast",
+                    "version": env!("CARGO_PKG_VERSION")
+                },
+                "spans": spans.iter().map(encode_span).collect::<Vec<_>>()
+            }]
+        }]
+    })
+}
+
+fn encode_span(span: &SpanData) -> Value {
+    let mut value = json!({
+        "traceId": hex::encode(span.trace_id),
+        "spanId": hex::encode(span.span_id),
+        "name": span.name,
+        "kind": span.kind.as_otlp(),
+        "startTimeUnixNano": span.start_unix_nanos.to_string(),
+        "endTimeUnixNano": span.end_unix_nanos.to_string(),
+        "attributes": span
+            .attributes
+            .iter()
+            .map(|(k, v)| encode_attribute(k, v))
+            .collect::<Vec<_>>(),
+    });
+    if let Some(parent) = span.parent_span_id {
+        value["parentSpanId"] = json!(hex::encode(parent));
+    }
+    value["status"] = match &span.status {
+        SpanStatus::Unset => json!({}),
+        SpanStatus::Ok => json!({ "code": 1 }),
+        SpanStatus::Error(message) => json!({ "code": 2, "message": message }),
+    };
+    value
+}
+
+fn encode_attribute(key: &str, value: &AttributeValue) -> Value {
+    json!({ "key": key, "value": encode_value(value) })
+}
+
+fn encode_value(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::String(v) => json!({ "stringValue": v }),
+        // OTLP JSON 中 int64 按字符串编码
+        AttributeValue::Int(v) => json!({ "intValue": v.to_string() }),
+        AttributeValue::Double(v) => json!({ "doubleValue": v }),
+        AttributeValue::Bool(v) => json!({ "boolValue": v }),
+        AttributeValue::StringArray(values) => json!({
+            "arrayValue": {
+                "values": values.iter().map(|v| json!({ "stringValue": v })).collect::<Vec<_>>()
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::collections::HashMap;
+
+    fn spans_by_name(request: &Value) -> HashMap<String, Value> {
+        let mut out = HashMap::new();
+        for resource in request["resourceSpans"].as_array().into_iter().flatten() {
+            for scope in resource["scopeSpans"].as_array().into_iter().flatten() {
+                for span in scope["spans"].as_array().into_iter().flatten() {
+                    if let Some(name) = span["name"].as_str() {
+                        out.insert(name.to_string(), span.clone());
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn test_config(endpoint: String) -> OtelConfig {
+        OtelConfig {
+            enabled: true,
+            endpoint,
+            headers: HashMap::from([("x-collector-token".to_string(), "secret".to_string())]),
+            flush_interval_ms: 50,
+            ..OtelConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_traceparent() {
+        let (trace_id, parent, sampled) =
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(hex::encode(trace_id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(hex::encode(parent), "00f067aa0ba902b7");
+        assert!(sampled);
+
+        let (_, _, sampled) =
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap();
+        assert!(!sampled);
+
+        assert!(parse_traceparent("garbage").is_none());
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none()
+        );
+    }
+
+    #[test]
+    fn test_encode_span_tree() {
+        let trace_id = new_trace_id();
+        let mut root = SpanData::start(trace_id, None, "proxycast.request", SpanKind::Server);
+        root.set_attribute("gen_ai.usage.input_tokens", AttributeValue::from(12u32));
+        root.status = SpanStatus::Error("boom".to_string());
+        root.finish();
+        let mut child = SpanData::start(
+            trace_id,
+            Some(root.span_id),
+            "proxycast.routing",
+            SpanKind::Internal,
+        );
+        child.set_attribute(
+            "gen_ai.response.finish_reasons",
+            AttributeValue::from(vec!["stop".to_string()]),
+        );
+        child.finish();
+
+        let body = encode_export_request("proxycast", &[child, root.clone()]);
+        assert_eq!(
+            body["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "proxycast"
+        );
+        let spans = spans_by_name(&body);
+        let root_json = &spans["proxycast.request"];
+        assert_eq!(root_json["kind"], 2);
+        assert!(root_json.get("parentSpanId").is_none());
+        assert_eq!(root_json["status"]["code"], 2);
+        assert_eq!(root_json["attributes"][0]["value"]["intValue"], "12");
+
+        let child_json = &spans["proxycast.routing"];
+        assert_eq!(child_json["parentSpanId"], hex::encode(root.span_id));
+        assert_eq!(child_json["traceId"], hex::encode(trace_id));
+        assert_eq!(
+            child_json["attributes"][0]["value"]["arrayValue"]["values"][0]["stringValue"],
+            "stop"
+        );
+    }
+
+    #[test]
+    fn test_disabled_tracer_is_noop() {
+        let tracer = Tracer::new();
+        assert!(tracer.start_trace("proxycast.request", None).is_none());
+        let mut span = SpanGuard::default();
+        span.set_attribute("k", "v");
+        assert!(!span.is_recording());
+        assert!(!span.child("x").is_recording());
+    }
+
+    #[tokio::test]
+    async fn test_export_to_local_collector() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(Option<String>, Value)>();
+        let app = Router::new()
+            .route(
+                "/v1/traces",
+                post(
+                    |State(tx): State<mpsc::UnboundedSender<(Option<String>, Value)>>,
+                     headers: axum::http::HeaderMap,
+                     Json(body): Json<Value>| async move {
+                        let token = headers
+                            .get("x-collector-token")
+                            .and_then(|v| v.to_str().ok())
+                            .map(|s| s.to_string());
+                        let _ = tx.send((token, body));
+                        Json(json!({}))
+                    },
+                ),
+            )
+            .with_state(tx);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let tracer = Tracer::new();
+        tracer.configure(&test_config(format!("http://{}/v1/traces", addr)));
+        assert!(tracer.state.read().is_some());
+
+        let trace = tracer
+            .start_trace(
+                "proxycast.request",
+                Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            )
+            .unwrap();
+        trace.set_attribute("gen_ai.request.model", "claude-sonnet-4-5");
+        {
+            let attempt = trace.span("proxycast.provider.attempt");
+            let mut call = attempt.child("proxycast.provider.call");
+            call.set_kind(SpanKind::Client);
+            call.set_error("HTTP 500");
+        }
+        let stream = trace.span("proxycast.stream");
+        drop(trace);
+        // 流式 span 仍持有 trace，此时不应导出
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(rx.try_recv().is_err());
+        drop(stream);
+
+        // 批次可能被定时刷新拆开，收齐 4 个 span 为止
+        let mut spans = HashMap::new();
+        while spans.len() < 4 {
+            let (token, body) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("collector did not receive spans")
+                .unwrap();
+            assert_eq!(token.as_deref(), Some("secret"));
+            spans.extend(spans_by_name(&body));
+        }
+        assert_eq!(spans.len(), 4);
+        let root = &spans["proxycast.request"];
+        assert_eq!(root["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(root["parentSpanId"], "00f067aa0ba902b7");
+        let attempt = &spans["proxycast.provider.attempt"];
+        let call = &spans["proxycast.provider.call"];
+        assert_eq!(attempt["parentSpanId"], root["spanId"]);
+        assert_eq!(call["parentSpanId"], attempt["spanId"]);
+        assert_eq!(call["kind"], 3);
+        assert_eq!(call["status"]["message"], "HTTP 500");
+
+        let root_end: u64 = root["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        let stream_end: u64 = spans["proxycast.stream"]["endTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(root_end >= stream_end);
+    }
+
+    #[tokio::test]
+    async fn test_in_span_parents_nested_spans() {
+        let tracer = Tracer::new();
+        tracer.configure(&test_config("http://127.0.0.1:9/v1/traces".to_string()));
+        let trace = tracer.start_trace("proxycast.request", None).unwrap();
+        let call = trace.span("proxycast.provider.call");
+        let call_id = call.parent().unwrap().span_id;
+
+        assert!(current_span().is_none());
+        assert!(!current_span_child("proxycast.provider.retry").is_recording());
+        let retry_parent = in_span(&call, async {
+            // 跨 await 后仍能取得当前 span
+            tokio::task::yield_now().await;
+            let retry = current_span_child("proxycast.provider.retry");
+            let parent = retry.active.as_ref().unwrap().data.parent_span_id;
+            retry.end();
+            parent
+        })
+        .await;
+        assert_eq!(retry_parent, Some(call_id));
+        assert!(current_span().is_none());
+        drop(call);
+        assert_eq!(trace.inner.finished.lock().len(), 2);
+
+        tracer.configure(&OtelConfig::default());
+    }
+
+    #[tokio::test]
+    async fn test_unsampled_parent_is_respected() {
+        let tracer = Tracer::new();
+        tracer.configure(&test_config("http://127.0.0.1:9/v1/traces".to_string()));
+        assert!(tracer
+            .start_trace(
+                "proxycast.request",
+                Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"),
+            )
+            .is_none());
+
+        tracer.configure(&OtelConfig::default());
+        assert!(tracer.state.read().is_none());
+    }
+}
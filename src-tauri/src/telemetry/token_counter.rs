@@ -0,0 +1,655 @@
+//! Anthropic 请求 Token 计数模块
+//!
+//! 为 `/v1/messages/count_tokens` 提供本地 Token 计数能力：
+//! - 完整遍历 Anthropic 请求（system、messages、tools、图片、thinking 块）
+//! - 使用 tiktoken 编码后按模型家族校准系数修正
+//! - 图片按 Anthropic 官方公式 `(宽 × 高) / 750` 估算
+
+#![allow(dead_code)]
+
+use super::tokens::{TokenEstimator, TokenEstimatorError};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// 每条消息的格式化开销（role 标记、分隔符等）
+const TOKENS_PER_MESSAGE: u32 = 3;
+/// system 提示词的固定开销
+const TOKENS_PER_SYSTEM: u32 = 3;
+/// 每个工具定义的固定开销（name/description/input_schema 包装）
+const TOKENS_PER_TOOL: u32 = 8;
+/// 存在工具时 Anthropic 注入的工具使用系统提示词开销
+const TOOL_USE_SYSTEM_PROMPT_TOKENS: u32 = 346;
+/// 图片长边上限（超过会被服务端等比缩放）
+const IMAGE_MAX_LONG_EDGE: f64 = 1568.0;
+/// 图片像素上限（约 1.15 百万像素）
+const IMAGE_MAX_PIXELS: f64 = 1_150_000.0;
+/// 无法解析尺寸时的图片 Token 估算值（接近上限）
+const IMAGE_FALLBACK_TOKENS: u32 = 1600;
+/// 非文本文档（如 PDF）每 KB 的估算 Token 数
+const DOCUMENT_TOKENS_PER_KB: u32 = 60;
+
+/// 模型家族
+///
+/// 不同家族的分词器与 cl100k/o200k 存在系统性偏差，使用校准系数修正
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelFamily {
+    Claude,
+    Gpt,
+    Gemini,
+    Qwen,
+    Deepseek,
+    Other,
+}
+
+impl ModelFamily {
+    /// 根据模型名称识别模型家族
+    pub fn from_model(model: &str) -> Self {
+        let m = model.to_lowercase();
+        if m.contains("claude") || m.contains("sonnet") || m.contains("opus") || m.contains("haiku")
+        {
+            ModelFamily::Claude
+        } else if m.contains("gemini") || m.contains("gemma") {
+            ModelFamily::Gemini
+        } else if m.starts_with("gpt")
+            || m.starts_with("o1")
+            || m.starts_with("o3")
+            || m.starts_with("o4")
+            || m.contains("codex")
+        {
+            ModelFamily::Gpt
+        } else if m.contains("qwen") {
+            ModelFamily::Qwen
+        } else if m.contains("deepseek") {
+            ModelFamily::Deepseek
+        } else {
+            ModelFamily::Other
+        }
+    }
+
+    /// 默认校准系数（相对 tiktoken 编码结果）
+    pub fn default_calibration(&self) -> f64 {
+        match self {
+            // Claude 分词器对代码和非英文文本切分更细
+            ModelFamily::Claude => 1.15,
+            ModelFamily::Gpt => 1.0,
+            ModelFamily::Gemini => 1.05,
+            ModelFamily::Qwen => 1.05,
+            ModelFamily::Deepseek => 1.05,
+            ModelFamily::Other => 1.1,
+        }
+    }
+
+    /// 配置键名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelFamily::Claude => "claude",
+            ModelFamily::Gpt => "gpt",
+            ModelFamily::Gemini => "gemini",
+            ModelFamily::Qwen => "qwen",
+            ModelFamily::Deepseek => "deepseek",
+            ModelFamily::Other => "other",
+        }
+    }
+}
+
+/// Token 计数明细
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenCountBreakdown {
+    /// system 提示词 Token 数
+    pub system: u32,
+    /// 消息文本 Token 数（含 tool_use / tool_result）
+    pub messages: u32,
+    /// 工具定义 Token 数
+    pub tools: u32,
+    /// 图片 Token 数
+    pub images: u32,
+    /// thinking 块 Token 数
+    pub thinking: u32,
+}
+
+impl TokenCountBreakdown {
+    /// 校准前的原始总数
+    pub fn raw_total(&self) -> u32 {
+        self.system + self.messages + self.tools + self.images + self.thinking
+    }
+}
+
+/// Token 计数结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenCountResult {
+    /// 校准后的输入 Token 数
+    pub input_tokens: u32,
+    /// 模型家族
+    pub family: ModelFamily,
+    /// 使用的校准系数
+    pub calibration: f64,
+    /// 明细
+    pub breakdown: TokenCountBreakdown,
+}
+
+/// Anthropic 请求 Token 计数器
+pub struct AnthropicTokenCounter {
+    estimator: TokenEstimator,
+    /// 按模型家族覆盖的校准系数（配置热重载时更新）
+    calibration_overrides: RwLock<HashMap<String, f64>>,
+}
+
+impl AnthropicTokenCounter {
+    /// 创建新的计数器
+    pub fn new() -> Result<Self, TokenEstimatorError> {
+        Ok(Self {
+            estimator: TokenEstimator::new()?,
+            calibration_overrides: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// 设置校准系数覆盖（键为模型家族名，如 `claude`）
+    pub fn with_calibration_overrides(self, overrides: HashMap<String, f64>) -> Self {
+        self.set_calibration_overrides(overrides);
+        self
+    }
+
+    /// 替换校准系数覆盖（配置热重载时调用）
+    pub fn set_calibration_overrides(&self, overrides: HashMap<String, f64>) {
+        *self.calibration_overrides.write() = overrides
+            .into_iter()
+            .filter(|(_, factor)| factor.is_finite() && *factor > 0.0)
+            .map(|(family, factor)| (family.to_lowercase(), factor))
+            .collect();
+    }
+
+    /// 获取模型家族的校准系数
+    pub fn calibration_for(&self, family: ModelFamily) -> f64 {
+        self.calibration_overrides
+            .read()
+            .get(family.as_str())
+            .copied()
+            .unwrap_or_else(|| family.default_calibration())
+    }
+
+    /// 估算纯文本的 Token 数（不应用校准系数）
+    pub fn estimate_text(&self, text: &str, model: &str) -> u32 {
+        self.estimator.estimate(text, Some(model))
+    }
+
+    /// 计算 Anthropic Messages 请求的输入 Token 数
+    pub fn count_request(&self, request: &Value) -> TokenCountResult {
+        let model = request.get("model").and_then(|m| m.as_str()).unwrap_or("");
+        let family = ModelFamily::from_model(model);
+        let bpe_model = Some(model);
+        let mut breakdown = TokenCountBreakdown::default();
+
+        // system: 字符串或 text 块数组
+        if let Some(system) = request.get("system") {
+            let text_tokens = match system {
+                Value::String(s) => self.estimator.estimate(s, bpe_model),
+                Value::Array(blocks) => blocks
+                    .iter()
+                    .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                    .map(|t| self.estimator.estimate(t, bpe_model))
+                    .sum(),
+                _ => 0,
+            };
+            if text_tokens > 0 {
+                breakdown.system = text_tokens + TOKENS_PER_SYSTEM;
+            }
+        }
+
+        // messages
+        if let Some(messages) = request.get("messages").and_then(|m| m.as_array()) {
+            // 只有最后一条 assistant 消息中的 thinking 块会计入上下文，
+            // 之前轮次的 thinking 块会被上游剥离
+            let last_assistant = messages
+                .iter()
+                .rposition(|m| m.get("role").and_then(|r| r.as_str()) == Some("assistant"));
+
+            for (idx, message) in messages.iter().enumerate() {
+                breakdown.messages += TOKENS_PER_MESSAGE;
+                let keep_thinking = Some(idx) == last_assistant;
+                match message.get("content") {
+                    Some(Value::String(s)) => {
+                        breakdown.messages += self.estimator.estimate(s, bpe_model);
+                    }
+                    Some(Value::Array(blocks)) => {
+                        for block in blocks {
+                            self.count_block(block, bpe_model, keep_thinking, &mut breakdown);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // tools
+        if let Some(tools) = request.get("tools").and_then(|t| t.as_array()) {
+            if !tools.is_empty() {
+                breakdown.tools += TOOL_USE_SYSTEM_PROMPT_TOKENS;
+            }
+            for tool in tools {
+                breakdown.tools += TOKENS_PER_TOOL;
+                if let Some(name) = tool.get("name").and_then(|n| n.as_str()) {
+                    breakdown.tools += self.estimator.estimate(name, bpe_model);
+                }
+                if let Some(desc) = tool.get("description").and_then(|d| d.as_str()) {
+                    breakdown.tools += self.estimator.estimate(desc, bpe_model);
+                }
+                if let Some(schema) = tool.get("input_schema") {
+                    breakdown.tools += self.estimate_json(schema, bpe_model);
+                }
+            }
+        }
+
+        let calibration = self.calibration_for(family);
+        // 图片 Token 由官方公式直接得出，不参与分词器校准
+        let text_total = breakdown.raw_total() - breakdown.images;
+        let input_tokens = (text_total as f64 * calibration).ceil() as u32 + breakdown.images;
+
+        TokenCountResult {
+            input_tokens,
+            family,
+            calibration,
+            breakdown,
+        }
+    }
+
+    /// 统计单个内容块
+    fn count_block(
+        &self,
+        block: &Value,
+        model: Option<&str>,
+        keep_thinking: bool,
+        breakdown: &mut TokenCountBreakdown,
+    ) {
+        let block_type = block.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        match block_type {
+            "text" => {
+                if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                    breakdown.messages += self.estimator.estimate(text, model);
+                }
+            }
+            "image" => {
+                breakdown.images += estimate_image_tokens(block.get("source"));
+            }
+            "document" => {
+                breakdown.messages += self.estimate_document(block.get("source"), model);
+            }
+            "tool_use" => {
+                if let Some(name) = block.get("name").and_then(|n| n.as_str()) {
+                    breakdown.messages += self.estimator.estimate(name, model);
+                }
+                if let Some(input) = block.get("input") {
+                    breakdown.messages += self.estimate_json(input, model);
+                }
+            }
+            "tool_result" => match block.get("content") {
+                Some(Value::String(s)) => {
+                    breakdown.messages += self.estimator.estimate(s, model);
+                }
+                Some(Value::Array(inner)) => {
+                    for inner_block in inner {
+                        self.count_block(inner_block, model, keep_thinking, breakdown);
+                    }
+                }
+                _ => {}
+            },
+            "thinking" => {
+                if keep_thinking {
+                    if let Some(thinking) = block.get("thinking").and_then(|t| t.as_str()) {
+                        breakdown.thinking += self.estimator.estimate(thinking, model);
+                    }
+                }
+            }
+            "redacted_thinking" => {
+                if keep_thinking {
+                    // 加密内容无法分词，按 base64 长度粗略估算
+                    let len = block
+                        .get("data")
+                        .and_then(|d| d.as_str())
+                        .map(|d| d.len())
+                        .unwrap_or(0);
+                    breakdown.thinking += (len / 4) as u32;
+                }
+            }
+            _ => {
+                // 未知块类型：按 JSON 文本估算，避免低估
+                breakdown.messages += self.estimate_json(block, model);
+            }
+        }
+    }
+
+    /// 估算文档块 Token 数
+    fn estimate_document(&self, source: Option<&Value>, model: Option<&str>) -> u32 {
+        let Some(source) = source else {
+            return 0;
+        };
+        match source.get("type").and_then(|t| t.as_str()) {
+            Some("text") => source
+                .get("data")
+                .and_then(|d| d.as_str())
+                .map(|d| self.estimator.estimate(d, model))
+                .unwrap_or(0),
+            Some("content") => source
+                .get("content")
+                .map(|c| self.estimate_json(c, model))
+                .unwrap_or(0),
+            _ => {
+                let encoded_len = source
+                    .get("data")
+                    .and_then(|d| d.as_str())
+                    .map(|d| d.len())
+                    .unwrap_or(0);
+                let kb = (encoded_len * 3 / 4).div_ceil(1024) as u32;
+                kb.max(1) * DOCUMENT_TOKENS_PER_KB
+            }
+        }
+    }
+
+    /// 估算 JSON 值序列化后的 Token 数
+    fn estimate_json(&self, value: &Value, model: Option<&str>) -> u32 {
+        match value {
+            Value::String(s) => self.estimator.estimate(s, model),
+            Value::Null => 0,
+            other => {
+                let text = serde_json::to_string(other).unwrap_or_default();
+                self.estimator.estimate(&text, model)
+            }
+        }
+    }
+}
+
+/// 估算图片块 Token 数
+///
+/// 按 Anthropic 官方公式 `tokens = (width × height) / 750` 计算，
+/// 超过长边或像素上限时先等比缩放
+pub fn estimate_image_tokens(source: Option<&Value>) -> u32 {
+    let dims = source
+        .filter(|s| s.get("type").and_then(|t| t.as_str()) == Some("base64"))
+        .and_then(|s| s.get("data").and_then(|d| d.as_str()))
+        .and_then(|data| BASE64.decode(data.trim()).ok())
+        .and_then(|bytes| image_dimensions(&bytes));
+
+    match dims {
+        Some((width, height)) => image_tokens_for_dimensions(width, height),
+        None => IMAGE_FALLBACK_TOKENS,
+    }
+}
+
+/// 根据图片尺寸计算 Token 数
+pub fn image_tokens_for_dimensions(width: u32, height: u32) -> u32 {
+    if width == 0 || height == 0 {
+        return 0;
+    }
+    let mut w = width as f64;
+    let mut h = height as f64;
+
+    let long_edge = w.max(h);
+    if long_edge > IMAGE_MAX_LONG_EDGE {
+        let scale = IMAGE_MAX_LONG_EDGE / long_edge;
+        w *= scale;
+        h *= scale;
+    }
+    if w * h > IMAGE_MAX_PIXELS {
+        let scale = (IMAGE_MAX_PIXELS / (w * h)).sqrt();
+        w *= scale;
+        h *= scale;
+    }
+
+    ((w * h) / 750.0).ceil() as u32
+}
+
+/// 从图片字节中解析宽高（支持 PNG、JPEG、GIF、WebP）
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    // PNG: 8 字节签名 + IHDR（宽高位于偏移 16..24）
+    if bytes.len() >= 24 && bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        let w = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+        let h = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+        return Some((w, h));
+    }
+
+    // GIF: 逻辑屏幕宽高（小端）
+    if bytes.len() >= 10 && bytes.starts_with(b"GIF") {
+        let w = u16::from_le_bytes([bytes[6], bytes[7]]) as u32;
+        let h = u16::from_le_bytes([bytes[8], bytes[9]]) as u32;
+        return Some((w, h));
+    }
+
+    // WebP: RIFF....WEBP + VP8 / VP8L / VP8X
+    if bytes.len() >= 30 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return match &bytes[12..16] {
+            b"VP8 " => {
+                let w = u16::from_le_bytes([bytes[26], bytes[27]]) & 0x3FFF;
+                let h = u16::from_le_bytes([bytes[28], bytes[29]]) & 0x3FFF;
+                Some((w as u32, h as u32))
+            }
+            b"VP8L" => {
+                let b = &bytes[21..25];
+                let w = 1 + (((b[1] as u32 & 0x3F) << 8) | b[0] as u32);
+                let h =
+                    1 + (((b[3] as u32 & 0x0F) << 10) | ((b[2] as u32) << 2) | (b[1] as u32 >> 6));
+                Some((w, h))
+            }
+            b"VP8X" => {
+                let w = 1 + u32::from_le_bytes([bytes[24], bytes[25], bytes[26], 0]);
+                let h = 1 + u32::from_le_bytes([bytes[27], bytes[28], bytes[29], 0]);
+                Some((w, h))
+            }
+            _ => None,
+        };
+    }
+
+    // JPEG: 扫描 SOF 段
+    if bytes.len() >= 4 && bytes[0] == 0xFF && bytes[1] == 0xD8 {
+        let mut i = 2;
+        while i + 9 < bytes.len() {
+            if bytes[i] != 0xFF {
+                i += 1;
+                continue;
+            }
+            let marker = bytes[i + 1];
+            // SOF0..SOF15（排除 DHT/JPG/DAC）
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                let h = u16::from_be_bytes([bytes[i + 5], bytes[i + 6]]) as u32;
+                let w = u16::from_be_bytes([bytes[i + 7], bytes[i + 8]]) as u32;
+                return Some((w, h));
+            }
+            let seg_len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+            i += 2 + seg_len;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn counter() -> AnthropicTokenCounter {
+        AnthropicTokenCounter::new().unwrap()
+    }
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        bytes.extend_from_slice(&[0, 0, 0, 13]);
+        bytes.extend_from_slice(b"IHDR");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_model_family_detection() {
+        assert_eq!(
+            ModelFamily::from_model("claude-sonnet-4-5-20250929"),
+            ModelFamily::Claude
+        );
+        assert_eq!(ModelFamily::from_model("gpt-4o"), ModelFamily::Gpt);
+        assert_eq!(
+            ModelFamily::from_model("gemini-2.5-flash"),
+            ModelFamily::Gemini
+        );
+        assert_eq!(
+            ModelFamily::from_model("qwen3-coder-plus"),
+            ModelFamily::Qwen
+        );
+        assert_eq!(
+            ModelFamily::from_model("deepseek-chat"),
+            ModelFamily::Deepseek
+        );
+        assert_eq!(ModelFamily::from_model("llama3.2"), ModelFamily::Other);
+    }
+
+    #[test]
+    fn test_count_simple_request() {
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "system": "You are a helpful assistant.",
+            "messages": [{"role": "user", "content": "Hello, how are you today?"}]
+        });
+        let result = counter().count_request(&request);
+
+        assert_eq!(result.family, ModelFamily::Claude);
+        assert!(result.breakdown.system > TOKENS_PER_SYSTEM);
+        assert!(result.breakdown.messages > TOKENS_PER_MESSAGE);
+        assert_eq!(result.breakdown.tools, 0);
+        assert!(result.input_tokens > result.breakdown.raw_total());
+    }
+
+    #[test]
+    fn test_count_grows_with_content() {
+        let c = counter();
+        let short = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+        let long = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hi ".repeat(500)}]
+        });
+        assert!(c.count_request(&long).input_tokens > c.count_request(&short).input_tokens);
+    }
+
+    #[test]
+    fn test_count_tools_include_system_prompt_overhead() {
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "weather?"}],
+            "tools": [{
+                "name": "get_weather",
+                "description": "Get the current weather",
+                "input_schema": {"type": "object", "properties": {"city": {"type": "string"}}}
+            }]
+        });
+        let result = counter().count_request(&request);
+        assert!(result.breakdown.tools > TOOL_USE_SYSTEM_PROMPT_TOKENS + TOKENS_PER_TOOL);
+    }
+
+    #[test]
+    fn test_count_tool_use_and_result_blocks() {
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "t1", "name": "read_file", "input": {"path": "/tmp/a.txt"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "t1", "content": [
+                        {"type": "text", "text": "file contents here"}
+                    ]}
+                ]}
+            ]
+        });
+        let result = counter().count_request(&request);
+        assert!(result.breakdown.messages > 2 * TOKENS_PER_MESSAGE + 5);
+    }
+
+    #[test]
+    fn test_thinking_only_counted_in_last_assistant_turn() {
+        let thinking = "Let me reason about this carefully. ".repeat(20);
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [
+                {"role": "user", "content": "q1"},
+                {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": thinking, "signature": "sig"},
+                    {"type": "text", "text": "a1"}
+                ]},
+                {"role": "user", "content": "q2"},
+                {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "short", "signature": "sig"}
+                ]}
+            ]
+        });
+        let c = counter();
+        let result = c.count_request(&request);
+        assert_eq!(
+            result.breakdown.thinking,
+            c.estimator.estimate("short", None)
+        );
+    }
+
+    #[test]
+    fn test_image_tokens_from_png_dimensions() {
+        let data = BASE64.encode(png_header(200, 150));
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": [
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": data}}
+            ]}]
+        });
+        let result = counter().count_request(&request);
+        assert_eq!(result.breakdown.images, 40);
+    }
+
+    #[test]
+    fn test_image_tokens_scaled_and_fallback() {
+        // 大图会被缩放，Token 数不超过上限
+        assert!(image_tokens_for_dimensions(4000, 3000) <= 1534);
+        assert_eq!(image_tokens_for_dimensions(0, 100), 0);
+        // URL 图片无法解析尺寸，使用回退值
+        let source = json!({"type": "url", "url": "https://example.com/a.png"});
+        assert_eq!(estimate_image_tokens(Some(&source)), IMAGE_FALLBACK_TOKENS);
+    }
+
+    #[test]
+    fn test_image_dimensions_formats() {
+        assert_eq!(image_dimensions(&png_header(640, 480)), Some((640, 480)));
+
+        let gif = [b'G', b'I', b'F', b'8', b'9', b'a', 0x40, 0x01, 0xF0, 0x00];
+        assert_eq!(image_dimensions(&gif), Some((320, 240)));
+
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01,
+            0xE0, 0x02, 0x80, 0x03,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((640, 480)));
+
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_calibration_overrides() {
+        let mut overrides = HashMap::new();
+        overrides.insert("Claude".to_string(), 1.5);
+        overrides.insert("gpt".to_string(), -1.0);
+        let c = counter().with_calibration_overrides(overrides);
+
+        assert_eq!(c.calibration_for(ModelFamily::Claude), 1.5);
+        // 非法系数被忽略
+        assert_eq!(c.calibration_for(ModelFamily::Gpt), 1.0);
+
+        // 热重载替换全部覆盖
+        c.set_calibration_overrides(HashMap::from([("gpt".to_string(), 1.2)]));
+        assert_eq!(
+            c.calibration_for(ModelFamily::Claude),
+            ModelFamily::Claude.default_calibration()
+        );
+        assert_eq!(c.calibration_for(ModelFamily::Gpt), 1.2);
+    }
+}
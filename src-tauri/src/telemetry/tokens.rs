@@ -31,6 +31,21 @@ pub struct TokenUsageRecord {
     pub source: TokenSource,
     /// 关联的请求 ID
     pub request_id: Option<String>,
+    /// 发起请求的客户端 key ID
+    #[serde(default)]
+    pub client_key_id: Option<String>,
+    /// 缓存读取 Token 数
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+    /// 缓存写入 Token 数
+    #[serde(default)]
+    pub cache_write_tokens: u32,
+    /// 按模型定价计算的费用（模型无定价时为 None）
+    #[serde(default)]
+    pub cost: Option<f64>,
+    /// 费用货币单位
+    #[serde(default)]
+    pub currency: Option<String>,
 }
 
 impl TokenUsageRecord {
@@ -53,6 +68,11 @@ impl TokenUsageRecord {
             total_tokens: input_tokens + output_tokens,
             source,
             request_id: None,
+            client_key_id: None,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+            cost: None,
+            currency: None,
         }
     }
 
@@ -61,6 +81,26 @@ impl TokenUsageRecord {
         self.request_id = Some(request_id);
         self
     }
+
+    /// 设置客户端 key ID
+    pub fn with_client_key_id(mut self, client_key_id: Option<String>) -> Self {
+        self.client_key_id = client_key_id;
+        self
+    }
+
+    /// 设置缓存读取 / 写入 Token 数
+    pub fn with_cache_tokens(mut self, cache_read_tokens: u32, cache_write_tokens: u32) -> Self {
+        self.cache_read_tokens = cache_read_tokens;
+        self.cache_write_tokens = cache_write_tokens;
+        self
+    }
+
+    /// 设置费用
+    pub fn with_cost(mut self, cost: f64, currency: String) -> Self {
+        self.cost = Some(cost);
+        self.currency = Some(currency);
+        self
+    }
 }
 
 /// Token 来源
@@ -101,6 +141,8 @@ pub struct TokenStatsSummary {
     pub avg_input_tokens: f64,
     /// 平均输出 Token 数
     pub avg_output_tokens: f64,
+    /// 按模型定价计算的总费用
+    pub total_cost: f64,
 }
 
 impl TokenStatsSummary {
@@ -132,6 +174,7 @@ impl TokenStatsSummary {
             estimated_count,
             avg_input_tokens: total_input_tokens as f64 / record_count as f64,
             avg_output_tokens: total_output_tokens as f64 / record_count as f64,
+            total_cost: records.iter().filter_map(|r| r.cost).sum(),
         }
     }
 }
@@ -65,6 +65,9 @@ pub struct RequestLog {
     pub is_streaming: bool,
     /// 使用的凭证 ID（如果有）
     pub credential_id: Option<String>,
+    /// 发起请求的客户端 key ID（如果有）
+    #[serde(default)]
+    pub client_key_id: Option<String>,
     /// 重试次数
     pub retry_count: u32,
 }
@@ -86,6 +89,7 @@ impl RequestLog {
             error_message: None,
             is_streaming,
             credential_id: None,
+            client_key_id: None,
             retry_count: 0,
         }
     }
@@ -133,6 +137,11 @@ impl RequestLog {
         self.credential_id = Some(id);
     }
 
+    /// 设置客户端 key ID
+    pub fn set_client_key_id(&mut self, id: String) {
+        self.client_key_id = Some(id);
+    }
+
     /// 增加重试次数
     pub fn increment_retry(&mut self) {
         self.retry_count += 1;
@@ -67,6 +67,9 @@ struct ProcessedMessage {
 // 转换函数
 // ============================================================================
 
+/// 最后一条为 assistant 消息时使用的续写指令
+const CONTINUE_PREFILL_PROMPT: &str = "Continue your previous response exactly where it stopped. Do not repeat any text you have already written.";
+
 /// 将 Anthropic MessagesRequest 直接转换为 CodeWhisperer 请求
 pub fn convert_anthropic_to_codewhisperer(
     request: &AnthropicMessagesRequest,
@@ -123,12 +126,19 @@ pub fn convert_anthropic_to_codewhisperer(
         start_idx = 1;
     }
 
-    // 处理历史消息（除最后一条）
-    for msg in messages
-        .iter()
-        .take(messages.len().saturating_sub(1))
-        .skip(start_idx)
-    {
+    // 最后一条为 assistant 时（如续写预填充），它本身属于历史，当前消息改为续写指令
+    let ends_with_assistant = messages
+        .last()
+        .map(|m| m.role == "assistant")
+        .unwrap_or(false);
+    let history_len = if ends_with_assistant {
+        messages.len()
+    } else {
+        messages.len().saturating_sub(1)
+    };
+
+    // 处理历史消息（除最后一条用户消息）
+    for msg in messages.iter().take(history_len).skip(start_idx) {
         match msg.role.as_str() {
             "user" => {
                 let content = if msg.content.is_empty() {
@@ -185,7 +195,7 @@ pub fn convert_anthropic_to_codewhisperer(
     let (current_content, current_tool_results, current_images) =
         if let Some(last_msg) = messages.last() {
             if last_msg.role == "assistant" {
-                ("Continue".to_string(), None, None)
+                (CONTINUE_PREFILL_PROMPT.to_string(), None, None)
             } else {
                 let content = if last_msg.content.is_empty() {
                     if last_msg.tool_results.is_some() {
@@ -733,4 +743,45 @@ mod tests {
         let text = extract_system_text(&system);
         assert_eq!(text, "Line 1\nLine 2");
     }
+
+    #[test]
+    fn test_trailing_assistant_prefill_kept_in_history() {
+        let request = AnthropicMessagesRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            messages: vec![
+                AnthropicMessage {
+                    role: "user".to_string(),
+                    content: serde_json::json!("Count to ten"),
+                },
+                AnthropicMessage {
+                    role: "assistant".to_string(),
+                    content: serde_json::json!("1 2 3 4"),
+                },
+            ],
+            system: None,
+            max_tokens: Some(1024),
+            stream: true,
+            temperature: None,
+            tools: None,
+            tool_choice: None,
+        };
+
+        let cw_request = convert_anthropic_to_codewhisperer(&request, None);
+        let history = cw_request.conversation_state.history.unwrap();
+        assert_eq!(history.len(), 2);
+        match history.last().unwrap() {
+            HistoryItem::Assistant(item) => {
+                assert_eq!(item.assistant_response_message.content, "1 2 3 4");
+            }
+            _ => panic!("history should end with the assistant prefill"),
+        }
+        assert_eq!(
+            cw_request
+                .conversation_state
+                .current_message
+                .user_input_message
+                .content,
+            CONTINUE_PREFILL_PROMPT
+        );
+    }
 }
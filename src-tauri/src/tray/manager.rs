@@ -12,6 +12,7 @@ use super::menu::build_tray_menu;
 use super::menu_handler::handle_menu_event;
 use super::state::{TrayIconStatus, TrayStateSnapshot};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tauri::{
     image::Image,
@@ -191,8 +192,13 @@ pub struct TrayManager<R: Runtime> {
     icons: TrayIcons,
     /// AppHandle 引用
     app: AppHandle<R>,
+    /// 打开（含半开）的熔断器数量
+    open_circuits: AtomicUsize,
 }
 
+/// 默认托盘提示文本
+const DEFAULT_TOOLTIP: &str = "ProxyCast - AI API 代理";
+
 impl<R: Runtime> TrayManager<R> {
     /// 创建托盘管理器
     ///
@@ -223,7 +229,7 @@ impl<R: Runtime> TrayManager<R> {
             .icon(initial_icon.clone())
             .menu(&menu)
             .show_menu_on_left_click(false)
-            .tooltip("ProxyCast - AI API 代理")
+            .tooltip(DEFAULT_TOOLTIP)
             .on_tray_icon_event(|tray, event| {
                 let app = tray.app_handle();
                 handle_tray_icon_event(app, event);
@@ -241,6 +247,7 @@ impl<R: Runtime> TrayManager<R> {
             state: Arc::new(RwLock::new(initial_state)),
             icons,
             app: app.clone(),
+            open_circuits: AtomicUsize::new(0),
         })
     }
 
@@ -274,8 +281,8 @@ impl<R: Runtime> TrayManager<R> {
         }
 
         // 如果图标状态变化，更新图标
-        if old_status != snapshot.icon_status {
-            self.set_icon(snapshot.icon_status)?;
+        if self.effective_status(old_status) != self.effective_status(snapshot.icon_status) {
+            self.set_icon(self.effective_status(snapshot.icon_status))?;
             info!(
                 "托盘图标状态更新: {:?} -> {:?}",
                 old_status, snapshot.icon_status
@@ -326,6 +333,35 @@ impl<R: Runtime> TrayManager<R> {
         Ok(())
     }
 
+    /// 更新打开的熔断器数量
+    ///
+    /// 服务器正常运行但存在打开的熔断器时显示警告图标，并在提示文本中显示熔断数量
+    pub async fn set_open_circuits(&self, count: usize) -> Result<(), TrayError> {
+        let status = self.state.read().await.icon_status;
+        let before = self.effective_status(status);
+        self.open_circuits.store(count, Ordering::Relaxed);
+        let after = self.effective_status(status);
+        if before != after {
+            self.set_icon(after)?;
+            info!("托盘图标状态更新（熔断器）: {:?} -> {:?}", before, after);
+        }
+
+        if count > 0 {
+            self.set_tooltip(&format!("{} - {} 个熔断器已打开", DEFAULT_TOOLTIP, count))
+        } else {
+            self.set_tooltip(DEFAULT_TOOLTIP)
+        }
+    }
+
+    /// 结合熔断器状态计算实际显示的图标状态
+    fn effective_status(&self, status: TrayIconStatus) -> TrayIconStatus {
+        if status == TrayIconStatus::Running && self.open_circuits.load(Ordering::Relaxed) > 0 {
+            TrayIconStatus::Warning
+        } else {
+            status
+        }
+    }
+
     /// 设置托盘提示文本
     pub fn set_tooltip(&self, tooltip: &str) -> Result<(), TrayError> {
         self.tray
@@ -10,6 +10,10 @@ use crate::flow_monitor::models::FlowError;
 use crate::flow_monitor::monitor::{
     FlowEvent, FlowSummary, FlowUpdate, NotificationEvent, ThresholdCheckResult,
 };
+use crate::resilience::CircuitTransition;
+use crate::services::alert_service::AlertEvent;
+use crate::services::canary_service::CanaryProbeEvent;
+use crate::services::token_refresh_scheduler::TokenRefreshEvent;
 
 /// WebSocket 连接信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -356,6 +360,14 @@ pub enum WsFlowEvent {
     Notification { notification: NotificationEvent },
     /// 请求速率更新
     RequestRateUpdate { rate: f64, count: usize },
+    /// 熔断器状态变化
+    CircuitBreakerStateChanged { transition: CircuitTransition },
+    /// OAuth Token 提前刷新结果
+    TokenRefreshed { event: TokenRefreshEvent },
+    /// 凭证健康定时探测结果（探测流量，不计入请求统计）
+    CanaryProbe { event: CanaryProbeEvent },
+    /// 告警规则触发或恢复
+    Alert { event: AlertEvent },
 }
 
 impl From<FlowEvent> for WsFlowEvent {
@@ -372,6 +384,12 @@ impl From<FlowEvent> for WsFlowEvent {
             FlowEvent::RequestRateUpdate { rate, count } => {
                 WsFlowEvent::RequestRateUpdate { rate, count }
             }
+            FlowEvent::CircuitBreakerStateChanged { transition } => {
+                WsFlowEvent::CircuitBreakerStateChanged { transition }
+            }
+            FlowEvent::TokenRefreshed { event } => WsFlowEvent::TokenRefreshed { event },
+            FlowEvent::CanaryProbe { event } => WsFlowEvent::CanaryProbe { event },
+            FlowEvent::Alert { event } => WsFlowEvent::Alert { event },
         }
     }
 }
@@ -12,10 +12,16 @@ use tempfile::TempDir;
 use proxycast_lib::database::dao::api_key_provider::{
     ApiKeyEntry, ApiKeyProvider, ApiKeyProviderDao, ApiProviderType, ProviderGroup,
 };
+use proxycast_lib::database::secrets::{self, SecretCipher};
 use proxycast_lib::database::DbConnection;
 use proxycast_lib::services::api_key_provider_service::ApiKeyProviderService;
 use rusqlite::Connection;
 
+/// 安装固定密钥的测试加密器（生产环境由 `database::secrets::init` 安装）
+fn install_test_cipher() {
+    secrets::install(Arc::new(SecretCipher::from_key(&[7u8; 32])));
+}
+
 /// 测试上下文
 #[allow(dead_code)]
 struct TestContext {
@@ -27,6 +33,7 @@ struct TestContext {
 impl TestContext {
     /// 创建测试上下文
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        install_test_cipher();
         let temp_dir = TempDir::new()?;
         let db_path = temp_dir.path().join("test.db");
         let conn = Connection::open(&db_path)?;
@@ -571,7 +578,7 @@ proptest! {
 
         // 验证：解密后的 API Key 应与原始值相同
         let decrypted = ctx.service
-            .decrypt_api_key(&loaded_key.api_key_encrypted)
+            .decrypt_api_key(&loaded_key.id, &loaded_key.api_key_encrypted)
             .expect("Failed to decrypt");
         prop_assert_eq!(&decrypted, &api_key, "Decrypted API key should match original");
     }
@@ -610,14 +617,16 @@ proptest! {
             "API Key should be encrypted, not stored as plaintext"
         );
 
-        // 验证：加密后的值看起来像 Base64
+        // 验证：加密后的值带有版本前缀，且前缀后为 Base64
+        let payload = entry.api_key_encrypted.strip_prefix(secrets::ENCRYPTED_PREFIX);
+        prop_assert!(payload.is_some(), "Encrypted value should carry the enc:v1: prefix");
         prop_assert!(
-            entry.api_key_encrypted.chars().all(|c| c.is_alphanumeric() || c == '+' || c == '/' || c == '='),
+            payload.unwrap().chars().all(|c| c.is_alphanumeric() || c == '+' || c == '/' || c == '='),
             "Encrypted value should be Base64 encoded"
         );
 
         // 验证：可以正确解密
-        let decrypted = ctx.service.decrypt_api_key(&entry.api_key_encrypted)
+        let decrypted = ctx.service.decrypt_api_key(&entry.id, &entry.api_key_encrypted)
             .expect("Failed to decrypt API key");
         prop_assert_eq!(
             &decrypted,
@@ -634,10 +643,13 @@ proptest! {
     /// **Validates: Requirements 9.2**
     #[test]
     fn test_encryption_round_trip(api_key in "[a-zA-Z0-9_-]{10,100}") {
+        install_test_cipher();
         let service = ApiKeyProviderService::new();
 
         // 加密
-        let encrypted = service.encrypt_api_key(&api_key);
+        let encrypted = service
+            .encrypt_api_key("key-1", &api_key)
+            .expect("Failed to encrypt");
 
         // 验证：加密后不等于原文
         prop_assert_ne!(
@@ -647,9 +659,12 @@ proptest! {
         );
 
         // 解密
-        let decrypted = service.decrypt_api_key(&encrypted)
+        let decrypted = service.decrypt_api_key("key-1", &encrypted)
             .expect("Failed to decrypt");
 
+        // 验证：密文与 API Key ID 绑定
+        prop_assert!(service.decrypt_api_key("key-2", &encrypted).is_err());
+
         // 验证：解密后等于原文
         prop_assert_eq!(
             &decrypted,
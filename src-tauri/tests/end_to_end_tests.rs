@@ -135,8 +135,10 @@ impl E2ETestContext {
                 retry_count: 0,
                 injected_params: Some(HashMap::new()),
                 context_usage_percentage: None,
+                cached: false,
                 client_info: ClientInfo::default(),
                 routing_info: RoutingInfo::default(),
+                ..Default::default()
             },
             timestamps: FlowTimestamps {
                 created: now,
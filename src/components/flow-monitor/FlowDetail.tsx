@@ -1124,6 +1124,11 @@ function MetadataTab({ flow, onCopy }: MetadataTabProps) {
               {(metadata.context_usage_percentage * 100).toFixed(1)}%
             </div>
           )}
+          {metadata.cached && (
+            <div>
+              <span className="text-muted-foreground">响应缓存:</span> 命中
+            </div>
+          )}
         </div>
       </div>
 
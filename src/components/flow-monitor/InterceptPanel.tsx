@@ -47,6 +47,29 @@ export interface InterceptConfig {
   intercept_response: boolean;
   timeout_ms: number;
   timeout_action: TimeoutAction;
+  rules: InterceptRule[];
+}
+
+/**
+ * 自动拦截规则动作
+ */
+export type RuleAction =
+  | { type: "patch"; pointer: string; value: unknown }
+  | { type: "remove"; pointer: string }
+  | { type: "inject_system"; content: string; append?: boolean }
+  | { type: "respond"; status?: number; body: unknown }
+  | { type: "delay"; ms: number }
+  | { type: "fail"; status: number; message?: string | null };
+
+/**
+ * 自动拦截规则
+ */
+export interface InterceptRule {
+  name: string;
+  enabled: boolean;
+  filter_expr: string | null;
+  stage: InterceptType;
+  actions: RuleAction[];
 }
 
 /**
@@ -86,6 +109,13 @@ export type InterceptEvent =
   | { type: "FlowContinued"; flow_id: string; modified: boolean }
   | { type: "FlowCancelled"; flow_id: string }
   | { type: "FlowTimedOut"; flow_id: string; action: TimeoutAction }
+  | {
+      type: "RuleApplied";
+      flow_id: string;
+      stage: InterceptType;
+      rules: string[];
+      responded: boolean;
+    }
   | { type: "ConfigUpdated"; config: InterceptConfig };
 
 // ============================================================================
@@ -113,6 +143,7 @@ export function InterceptPanel({
     intercept_response: false,
     timeout_ms: 30000,
     timeout_action: "continue",
+    rules: [],
   });
   const [interceptedFlows, setInterceptedFlows] = useState<InterceptedFlow[]>(
     [],
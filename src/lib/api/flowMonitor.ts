@@ -341,6 +341,45 @@ export interface FlowMetadata {
   routing_info: RoutingInfo;
   injected_params?: Record<string, unknown>;
   context_usage_percentage?: number;
+  cached?: boolean; // 是否由响应缓存直接返回
+  hedge_attempts?: HedgeAttempt[]; // 对冲请求的各次尝试
+  recording_session?: string; // 录制到的会话 ID
+  playback_of?: string; // 回放的录制 Flow ID
+  conversation?: ConversationLink; // 所属对话
+}
+
+/**
+ * Flow 与上一个 Flow 的关系
+ */
+export type ConversationRelation = "root" | "continuation" | "retry";
+
+/**
+ * Flow 所属对话及其在对话树中的位置
+ */
+export interface ConversationLink {
+  /** 对话 ID（根 Flow 的 ID） */
+  conversation_id: string;
+  parent_flow_id?: string;
+  relation: ConversationRelation;
+  /** 轮次（根节点为 1） */
+  turn: number;
+  /** 重试的原始 Flow ID */
+  retry_of?: string;
+}
+
+/**
+ * 对冲请求中的一次尝试
+ */
+export interface HedgeAttempt {
+  /** 尝试序号（0 为原始请求，1 为对冲请求） */
+  index: number;
+  provider: ProviderType;
+  credential_id?: string;
+  /** 相对原始请求的发出时间（毫秒） */
+  started_after_ms: number;
+  ttfb_ms?: number;
+  outcome: "won" | "cancelled" | "failed";
+  error?: string;
 }
 
 /**
@@ -445,6 +484,7 @@ export interface FlowFilter {
   starred_only?: boolean;
   credential_id?: string;
   flow_types?: FlowType[];
+  conversation_id?: string;
   filter_expression?: string;
 }
 
@@ -535,6 +575,76 @@ export interface FlowStats {
   by_state: StateStats[];
 }
 
+// ============================================================================
+// 对话类型
+// ============================================================================
+
+/**
+ * 对话合计
+ */
+export interface ConversationTotals {
+  flows: number;
+  turns: number;
+  /** 分支数（编辑历史消息产生的额外子节点） */
+  branches: number;
+  retries: number;
+  errors: number;
+  input_tokens: number;
+  output_tokens: number;
+  cache_read_tokens: number;
+  cache_write_tokens: number;
+  total_tokens: number;
+  /** 按货币汇总的费用 */
+  cost: Record<string, number>;
+  /** 模型无定价、未计入费用的 Flow 数量 */
+  unpriced_flows: number;
+  /** 各 Flow 耗时之和（毫秒） */
+  duration_ms: number;
+  /** 从第一个请求开始到最后一个响应结束的时长（毫秒） */
+  wall_time_ms: number;
+  tool_calls: number;
+}
+
+/**
+ * 对话摘要
+ */
+export interface ConversationSummary {
+  id: string;
+  title: string;
+  started_at: string;
+  last_activity_at: string;
+  models: string[];
+  totals: ConversationTotals;
+}
+
+/**
+ * 对话树节点
+ */
+export interface ConversationNode {
+  flow_id: string;
+  parent_flow_id?: string;
+  relation: ConversationRelation;
+  turn: number;
+  retry_of?: string;
+  children: string[];
+  created_at: string;
+  model: string;
+  state: FlowState;
+  duration_ms: number;
+  input_tokens: number;
+  output_tokens: number;
+  tool_calls: number;
+  cost?: number;
+  currency?: string;
+}
+
+/**
+ * 完整对话树
+ */
+export interface Conversation extends ConversationSummary {
+  nodes: ConversationNode[];
+}
+
 // ============================================================================
 // 导出类型
 // ============================================================================
@@ -581,6 +691,67 @@ export interface ExportResult {
   mime_type: string;
 }
 
+/**
+ * 导入格式（auto 根据内容自动识别）
+ */
+export type ImportFormat = "auto" | "har" | "jsonl" | "log";
+
+/**
+ * 导入选项
+ */
+export interface ImportOptions {
+  format?: ImportFormat;
+  /** 来源名称（如文件名或分享者），写入 `source:<名称>` 标签 */
+  source?: string;
+}
+
+/**
+ * 导入结果
+ */
+export interface ImportSummary {
+  format: ImportFormat | null;
+  imported: number;
+  duplicates: number;
+  skipped: number;
+  errors: string[];
+  flow_ids: string[];
+}
+
+// ============================================================================
+// 会话录制/回放类型
+// ============================================================================
+
+/**
+ * 会话模式
+ */
+export type SessionMode = "record" | "playback";
+
+/**
+ * 回放选项
+ */
+export interface PlaybackOptions {
+  /** 请求哈希未命中时使用的过滤表达式（如 `~m gpt-4o`） */
+  matcher?: string | null;
+  /** 流式回放速度倍数（1 为原速，0 表示不等待，其余取值 0.01~1000） */
+  speed?: number;
+  /** 未命中时返回错误（默认）或转发到上游 */
+  on_miss?: "error" | "passthrough";
+}
+
+/**
+ * 会话的录制/回放状态
+ */
+export interface PlaybackStatus {
+  session_id: string;
+  mode: SessionMode;
+  options?: PlaybackOptions;
+  recorded: number;
+  playable: number;
+  hits: number;
+  misses: number;
+  started_at: string;
+}
+
 // ============================================================================
 // 标注更新类型
 // ============================================================================
@@ -640,7 +811,93 @@ export type FlowEvent =
   | { type: "FlowUpdated"; id: string; update: FlowUpdate }
   | { type: "FlowCompleted"; id: string; summary: FlowSummary }
   | { type: "FlowFailed"; id: string; error: FlowError }
-  | { type: "ThresholdWarning"; id: string; result: ThresholdCheckResult };
+  | { type: "ThresholdWarning"; id: string; result: ThresholdCheckResult }
+  | { type: "CircuitBreakerStateChanged"; transition: CircuitTransition }
+  | { type: "TokenRefreshed"; event: TokenRefreshEvent }
+  | { type: "CanaryProbe"; event: CanaryProbeEvent }
+  | { type: "Alert"; event: AlertEvent };
+
+/**
+ * 熔断器状态变化（用于事件）
+ */
+export interface CircuitTransition {
+  /** 熔断范围 */
+  scope: "credential" | "host";
+  /** 凭证 UUID 或上游主机名 */
+  key: string;
+  from: "closed" | "open" | "half_open";
+  to: "closed" | "open" | "half_open";
+  /** 状态变化原因 */
+  reason: string;
+  timestamp: string;
+}
+
+/**
+ * OAuth Token 提前刷新结果（用于事件）
+ */
+export interface TokenRefreshEvent {
+  /** 凭证 UUID */
+  credential_id: string;
+  provider_type: string;
+  name?: string;
+  outcome: "refreshed" | "skipped" | "failed" | "reauth_required";
+  /** 新 Token 的过期时间（刷新成功时） */
+  expires_at?: string;
+  /** 下次重试时间（刷新失败时） */
+  retry_at?: string;
+  /** 连续失败次数 */
+  consecutive_failures: number;
+  error?: string;
+  timestamp: string;
+}
+
+/**
+ * 凭证健康定时探测结果（用于事件，探测流量不计入请求统计）
+ */
+export interface CanaryProbeEvent {
+  /** 凭证 UUID */
+  credential_id: string;
+  provider_type: string;
+  name?: string;
+  model?: string;
+  success: boolean;
+  latency_ms: number;
+  /** 探测前凭证是否健康 */
+  was_healthy: boolean;
+  /** 探测后凭证是否健康（false 表示已移出轮换） */
+  is_healthy: boolean;
+  message?: string;
+  timestamp: string;
+}
+
+/**
+ * 告警规则触发或恢复（用于事件）
+ */
+export interface AlertEvent {
+  /** 规则名称 */
+  rule: string;
+  status: "firing" | "resolved";
+  metric:
+    | "error_rate"
+    | "error_count"
+    | "request_count"
+    | "avg_latency_ms"
+    | "p50_latency_ms"
+    | "p95_latency_ms"
+    | "p99_latency_ms"
+    | "total_tokens";
+  operator: "gt" | "gte" | "lt" | "lte";
+  threshold: number;
+  /** 当前指标值（窗口内样本不足时为空） */
+  value?: number | null;
+  /** 窗口内的请求数 */
+  samples: number;
+  window_secs: number;
+  filter_expr?: string | null;
+  /** 告警开始时间 */
+  started_at: string;
+  timestamp: string;
+}
 
 /**
  * 阈值检测结果（用于事件）
@@ -784,6 +1041,30 @@ export const flowMonitorApi = {
     return safeInvoke("get_flow_stats", { filter });
   },
 
+  /**
+   * 列出最近活跃的对话
+   *
+   * @param filter - 过滤条件（可选）
+   * @param limit - 最大返回数量
+   * @returns 对话摘要列表
+   */
+  async listConversations(
+    filter: FlowFilter = {},
+    limit: number = 50,
+  ): Promise<ConversationSummary[]> {
+    return safeInvoke("list_conversations", { filter, limit });
+  },
+
+  /**
+   * 获取对话树及合计
+   *
+   * @param conversationId - 对话 ID（根 Flow 的 ID）
+   * @returns 对话树，不存在时返回 null
+   */
+  async getConversation(conversationId: string): Promise<Conversation | null> {
+    return safeInvoke("get_conversation", { conversationId });
+  },
+
   /**
    * 导出 Flow
    *
@@ -820,6 +1101,71 @@ export const flowMonitorApi = {
     };
   },
 
+  /**
+   * 导入 Flow
+   *
+   * 支持 HAR、JSONL 以及 OpenAI/Anthropic 请求响应日志。
+   *
+   * @param data - 文件内容
+   * @param options - 导入选项
+   * @returns 导入结果
+   */
+  async importFlows(
+    data: string,
+    options: ImportOptions = {},
+  ): Promise<ImportSummary> {
+    return safeInvoke<ImportSummary>("import_flows", {
+      request: {
+        data,
+        format: options.format ?? "auto",
+        source: options.source ?? null,
+      },
+    });
+  },
+
+  /**
+   * 将会话切换为录制或回放模式
+   *
+   * @param sessionId - 会话 ID
+   * @param mode - 录制或回放
+   * @param options - 回放选项（仅回放模式使用）
+   * @returns 会话的录制/回放状态
+   */
+  async setSessionMode(
+    sessionId: string,
+    mode: SessionMode,
+    options: PlaybackOptions = {},
+  ): Promise<PlaybackStatus> {
+    return safeInvoke<PlaybackStatus>("set_session_mode", {
+      request: {
+        session_id: sessionId,
+        mode,
+        options: {
+          matcher: options.matcher ?? null,
+          speed: options.speed ?? 1,
+          on_miss: options.on_miss ?? "error",
+        },
+      },
+    });
+  },
+
+  /**
+   * 停止会话的录制/回放
+   *
+   * @param sessionId - 会话 ID
+   * @returns 停止前的状态，会话未处于录制或回放模式时为 null
+   */
+  async stopSessionMode(sessionId: string): Promise<PlaybackStatus | null> {
+    return safeInvoke("stop_session_mode", { sessionId });
+  },
+
+  /**
+   * 获取所有处于录制或回放模式的会话状态
+   */
+  async getPlaybackStatus(): Promise<PlaybackStatus[]> {
+    return safeInvoke("get_playback_status");
+  },
+
   /**
    * 更新 Flow 标注
    *
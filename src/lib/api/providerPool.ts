@@ -99,6 +99,8 @@ export interface ProviderCredential {
   check_health: boolean;
   check_model_name?: string;
   not_supported_models: string[];
+  // 负载均衡权重（加权策略下使用，默认 1）
+  weight?: number;
   usage_count: number;
   error_count: number;
   last_used?: string;
@@ -125,6 +127,8 @@ export interface CredentialDisplay {
   check_health: boolean;
   check_model_name?: string;
   not_supported_models: string[];
+  // 负载均衡权重（加权策略下使用）
+  weight?: number;
   usage_count: number;
   error_count: number;
   last_used?: string;
@@ -207,6 +211,8 @@ export interface UpdateCredentialRequest {
   check_health?: boolean;
   check_model_name?: string;
   not_supported_models?: string[];
+  /// 负载均衡权重（加权策略下使用，0 表示不参与加权分配）
+  weight?: number;
   /// 新的凭证文件路径（仅适用于OAuth凭证，用于重新上传文件）
   new_creds_file_path?: string;
   /// OAuth相关：新的project_id（仅适用于Gemini）
@@ -26,6 +26,40 @@ export interface CurlExample {
   command: string;
 }
 
+export interface RouteExplainRequest {
+  model: string;
+  /** 客户端类型（cursor/claude_code/codex/windsurf/kiro/other），未指定时从 User-Agent 识别 */
+  client_type?: string;
+  headers?: Record<string, string>;
+  /** 完整请求体，用于检测工具、图片、thinking 和估算输入 Token */
+  body?: unknown;
+}
+
+export interface RequestFeatures {
+  model: string;
+  client_type: string;
+  has_tools: boolean;
+  has_images: boolean;
+  thinking: boolean;
+  estimated_input_tokens: number;
+  headers: Record<string, string>;
+}
+
+export interface RuleEvaluation {
+  index: number;
+  name: string;
+  matched: boolean;
+  reason?: string;
+}
+
+export interface RouteExplanation {
+  features: RequestFeatures;
+  evaluations: RuleEvaluation[];
+  matched_rule: string | null;
+  provider: string | null;
+  model: string;
+}
+
 export const routesApi = {
   async getAvailableRoutes(): Promise<RouteListResponse> {
     return safeInvoke("get_available_routes");
@@ -34,4 +68,10 @@ export const routesApi = {
   async getCurlExamples(selector: string): Promise<CurlExample[]> {
     return safeInvoke("get_route_curl_examples", { selector });
   },
+
+  async explainRoutingRules(
+    request: RouteExplainRequest,
+  ): Promise<RouteExplanation> {
+    return safeInvoke("explain_routing_rules", { request });
+  },
 };
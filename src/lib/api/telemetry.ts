@@ -79,6 +79,8 @@ export interface TokenStatsSummary {
   estimated_count: number;
   avg_input_tokens: number;
   avg_output_tokens: number;
+  /** 按模型定价计算的总费用 */
+  total_cost: number;
 }
 
 export interface ProviderTokenStats {
@@ -91,6 +93,8 @@ export interface ProviderTokenStats {
   estimated_count: number;
   avg_input_tokens: number;
   avg_output_tokens: number;
+  /** 按模型定价计算的总费用 */
+  total_cost: number;
 }
 
 export interface ModelTokenStats {
@@ -103,6 +107,8 @@ export interface ModelTokenStats {
   estimated_count: number;
   avg_input_tokens: number;
   avg_output_tokens: number;
+  /** 按模型定价计算的总费用 */
+  total_cost: number;
 }
 
 export interface PeriodTokenStats {
@@ -116,6 +122,8 @@ export interface PeriodTokenStats {
   estimated_count: number;
   avg_input_tokens: number;
   avg_output_tokens: number;
+  /** 按模型定价计算的总费用 */
+  total_cost: number;
 }
 
 export interface TimeRangeParam {
@@ -36,6 +36,8 @@ export interface NotificationConfig {
   latency_warning: NotificationSettings;
   /** Token 警告通知配置 */
   token_warning: NotificationSettings;
+  /** 费用预算警告通知配置 */
+  budget_warning: NotificationSettings;
 }
 
 /**